@@ -3,6 +3,8 @@
 //! Provides zero-copy audio data flow between decoder and output
 
 use crate::audio::format::AudioFormat;
+use crate::audio::processor::{ResampleQuality, SampleRateConverter};
+use crate::error::{Error, Result};
 use std::sync::Arc;
 
 /// Audio buffer for storing decoded audio data
@@ -62,6 +64,13 @@ impl AudioBuffer {
         &self.data
     }
 
+    /// Get mutable access to the audio data
+    ///
+    /// Copies the data first if it is shared with a clone.
+    pub fn data_mut(&mut self) -> &mut [f64] {
+        Arc::make_mut(&mut self.data).as_mut_slice()
+    }
+
     /// Get audio data for a specific channel
     pub fn channel_data(&self, channel: usize) -> Option<Vec<f64>> {
         if channel >= self.format.channels as usize {
@@ -110,22 +119,75 @@ impl AudioBuffer {
         self.data.is_empty()
     }
 
-    /// Get a slice of frames
-    pub fn slice(&self, start_frame: usize, frame_count: usize) -> Option<AudioBuffer> {
-        if start_frame >= self.frames || start_frame + frame_count > self.frames {
-            return None;
+    /// Copy the frames in `[start_frame, end_frame)` into a new buffer
+    ///
+    /// A zero-length range gives an empty buffer of the same format.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start_frame > end_frame` or `end_frame` is past the end.
+    pub fn slice(&self, start_frame: usize, end_frame: usize) -> AudioBuffer {
+        assert!(
+            start_frame <= end_frame && end_frame <= self.frames,
+            "slice [{}, {}) out of bounds for {} frames",
+            start_frame,
+            end_frame,
+            self.frames
+        );
+
+        let channels = self.format.channels as usize;
+        let data = self.data[start_frame * channels..end_frame * channels].to_vec();
+        AudioBuffer::with_data(self.format.clone(), data)
+    }
+
+    /// Append the frames of `other` to this buffer
+    ///
+    /// Both buffers must share a sample rate and channel count.
+    pub fn append(&mut self, other: &AudioBuffer) -> Result<()> {
+        if self.format.sample_rate != other.format.sample_rate
+            || self.format.channels != other.format.channels
+        {
+            return Err(Error::AudioFormat(format!(
+                "Cannot append {}Hz/{}ch audio to {}Hz/{}ch buffer",
+                other.format.sample_rate,
+                other.format.channels,
+                self.format.sample_rate,
+                self.format.channels
+            )));
         }
 
-        let start_sample = start_frame * self.format.channels as usize;
-        let sample_count = frame_count * self.format.channels as usize;
-        let end_sample = start_sample + sample_count;
+        Arc::make_mut(&mut self.data).extend_from_slice(&other.data);
+        self.frames += other.frames;
+        Ok(())
+    }
 
-        if end_sample <= self.data.len() {
-            let slice_data = self.data[start_sample..end_sample].to_vec();
-            Some(AudioBuffer::with_data(self.format.clone(), slice_data))
-        } else {
-            None
+    /// Convert to a new buffer at `target_rate`
+    ///
+    /// Runs the whole buffer through a `SampleRateConverter` of the given
+    /// quality, flushing the kernel's tail so the result has exactly
+    /// `ceil(frames * target_rate / sample_rate)` frames. A buffer already
+    /// at the target rate is cloned.
+    pub fn resample(&self, target_rate: u32, quality: ResampleQuality) -> AudioBuffer {
+        let source_rate = self.format.sample_rate;
+        if source_rate == target_rate {
+            return self.clone();
         }
+
+        let channels = self.format.channels as usize;
+        let expected = (self.frames as u64 * target_rate as u64).div_ceil(source_rate as u64);
+        let mut converter =
+            SampleRateConverter::with_quality(source_rate, target_rate, channels, quality);
+        converter.push(self.data.iter().copied());
+        let padding = converter.input_frames_needed(expected as usize);
+        converter.push(std::iter::repeat_n(0.0, padding * channels));
+
+        let mut data = vec![0.0; expected as usize * channels];
+        let written = converter.read(&mut data);
+        debug_assert_eq!(written, data.len());
+
+        let mut format = self.format.clone();
+        format.sample_rate = target_rate;
+        AudioBuffer::with_data(format, data)
     }
 }
 
@@ -171,4 +233,82 @@ mod tests {
         assert_eq!(f32_samples.len(), 4);
         assert!((f32_samples[0] - 0.5).abs() < 0.001);
     }
+
+    #[test]
+    fn test_slice_frame_range() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F32);
+        let data: Vec<f64> = (0..20).map(|i| i as f64).collect();
+        let buffer = AudioBuffer::with_data(format.clone(), data);
+
+        let slice = buffer.slice(3, 7);
+        assert_eq!(slice.samples(), (7 - 3) * 2);
+        assert_eq!(slice.frames(), 4);
+        assert_eq!(slice.data()[0], 6.0);
+        assert_eq!(slice.data()[7], 13.0);
+        assert_eq!(slice.format(), &format);
+
+        let empty = buffer.slice(5, 5);
+        assert!(empty.is_empty());
+        assert_eq!(empty.frames(), 0);
+        assert_eq!(buffer.slice(0, 10).data(), buffer.data());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_slice_past_end_panics() {
+        let buffer = AudioBuffer::new(AudioFormat::new(44100, 2, SampleFormat::F32), 10);
+        buffer.slice(4, 11);
+    }
+
+    #[test]
+    fn test_append() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F32);
+        let mut buffer = AudioBuffer::with_data(format.clone(), vec![0.1, 0.2]);
+        let shared = buffer.clone();
+        let tail = AudioBuffer::with_data(format, vec![0.3, 0.4, 0.5, 0.6]);
+
+        buffer.append(&tail).unwrap();
+        assert_eq!(buffer.frames(), 3);
+        assert_eq!(buffer.data(), &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        // Clones sharing the data are left untouched
+        assert_eq!(shared.frames(), 1);
+
+        let mono = AudioBuffer::with_data(AudioFormat::new(44100, 1, SampleFormat::F32), vec![0.0]);
+        assert!(buffer.append(&mono).is_err());
+        let other_rate =
+            AudioBuffer::with_data(AudioFormat::new(48000, 2, SampleFormat::F32), vec![0.0; 2]);
+        assert!(buffer.append(&other_rate).is_err());
+        assert_eq!(buffer.frames(), 3);
+    }
+
+    #[test]
+    fn test_resample_upsamples_stereo() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F32);
+        // 10 ms of a 1 kHz tone, inverted on the right channel
+        let data: Vec<f64> = (0..441)
+            .flat_map(|i| {
+                let s = (2.0 * std::f64::consts::PI * 1000.0 * i as f64 / 44100.0).sin() * 0.5;
+                [s, -s]
+            })
+            .collect();
+        let buffer = AudioBuffer::with_data(format, data);
+
+        for quality in [ResampleQuality::Linear, ResampleQuality::Sinc] {
+            let resampled = buffer.resample(88200, quality);
+            assert_eq!(resampled.format().sample_rate, 88200);
+            assert_eq!(resampled.format().channels, 2);
+            assert_eq!(resampled.frames(), 882);
+            assert!((resampled.duration_seconds() - buffer.duration_seconds()).abs() < 1e-9);
+
+            // Channels stay separate
+            let left = resampled.channel_data(0).unwrap();
+            let right = resampled.channel_data(1).unwrap();
+            assert!(left.iter().zip(&right).all(|(l, r)| (l + r).abs() < 1e-9));
+            let peak = left[100..700].iter().fold(0.0f64, |m, s| m.max(s.abs()));
+            assert!((peak - 0.5).abs() < 0.02, "peak {}", peak);
+        }
+
+        let same = buffer.resample(44100, ResampleQuality::Sinc);
+        assert_eq!(same.data(), buffer.data());
+    }
 }
@@ -194,6 +194,27 @@ pub fn calculate_peak(samples: &[i16]) -> i16 {
     samples.iter().map(|&s| s.abs()).max().unwrap_or(0)
 }
 
+/// Phase correlation of interleaved stereo samples
+///
+/// Normalized cross-correlation of left and right over the block, in
+/// [-1.0, 1.0]: +1 = fully correlated (mono), 0 = decorrelated, -1 = out of
+/// phase. Returns 0.0 when either channel is silent.
+pub fn phase_correlation(samples: &[f64]) -> f64 {
+    let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+    for frame in samples.chunks_exact(2) {
+        let (left, right) = (frame[0], frame[1]);
+        lr += left * right;
+        ll += left * left;
+        rr += right * right;
+    }
+
+    let energy = (ll * rr).sqrt();
+    if energy == 0.0 {
+        return 0.0;
+    }
+    (lr / energy).clamp(-1.0, 1.0)
+}
+
 /// Audio statistics
 #[derive(Debug, Clone)]
 pub struct AudioStats {
@@ -240,6 +261,84 @@ pub fn calculate_audio_stats(samples: &[i16]) -> AudioStats {
     }
 }
 
+/// Level at or above which a sample counts as full scale
+///
+/// One 24-bit step below 1.0, so the largest positive integer sample of
+/// 16- and 24-bit sources is included.
+pub const CLIP_LEVEL: f64 = 1.0 - 1.0 / 8_388_608.0;
+
+/// Default number of consecutive full-scale samples forming a clip event
+pub const DEFAULT_CLIP_RUN: usize = 3;
+
+/// Clipping statistics of audio samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClipStats {
+    /// Samples at or over full scale
+    pub clipped_samples: usize,
+    /// Runs of at least the threshold number of consecutive full-scale samples
+    pub clip_events: usize,
+}
+
+impl ClipStats {
+    /// Check if any clip event was found
+    pub fn is_clipped(&self) -> bool {
+        self.clip_events > 0
+    }
+}
+
+/// Count full-scale samples and clip events in a single channel
+///
+/// A flat-topped run of `consecutive_threshold` or more full-scale samples
+/// counts as one event, which reflects audible clipping better than the raw
+/// sample count. A threshold of 0 is treated as 1.
+pub fn count_clipped_samples(samples: &[f64], consecutive_threshold: usize) -> ClipStats {
+    count_clipped_strided(samples.iter().copied(), consecutive_threshold)
+}
+
+/// Count clipping per channel of interleaved samples and sum the results
+pub fn count_clipped_samples_interleaved(
+    samples: &[f64],
+    channels: usize,
+    consecutive_threshold: usize,
+) -> ClipStats {
+    let channels = channels.max(1);
+    (0..channels)
+        .map(|ch| {
+            count_clipped_strided(
+                samples.iter().skip(ch).step_by(channels).copied(),
+                consecutive_threshold,
+            )
+        })
+        .fold(ClipStats::default(), |total, stats| ClipStats {
+            clipped_samples: total.clipped_samples + stats.clipped_samples,
+            clip_events: total.clip_events + stats.clip_events,
+        })
+}
+
+fn count_clipped_strided(
+    samples: impl Iterator<Item = f64>,
+    consecutive_threshold: usize,
+) -> ClipStats {
+    let threshold = consecutive_threshold.max(1);
+    let mut stats = ClipStats::default();
+    let mut run = 0;
+
+    for sample in samples {
+        if sample.abs() >= CLIP_LEVEL {
+            stats.clipped_samples += 1;
+            run += 1;
+            // Count the event once, when the run reaches the threshold
+            if run == threshold {
+                stats.clip_events += 1;
+            }
+        } else {
+            run = 0;
+        }
+    }
+
+    stats
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -334,6 +433,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_phase_correlation() {
+        use rand::{Rng, SeedableRng};
+
+        let tone: Vec<f64> = (0..4800).map(|i| (i as f64 * 0.05).sin() * 0.5).collect();
+
+        let identical: Vec<f64> = tone.iter().flat_map(|&s| [s, s]).collect();
+        assert!((phase_correlation(&identical) - 1.0).abs() < 1e-9);
+
+        let inverted: Vec<f64> = tone.iter().flat_map(|&s| [s, -s]).collect();
+        assert!((phase_correlation(&inverted) + 1.0).abs() < 1e-9);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let noise: Vec<f64> = (0..96000).map(|_| rng.random_range(-1.0..1.0)).collect();
+        assert!(phase_correlation(&noise).abs() < 0.05);
+
+        assert_eq!(phase_correlation(&[0.0; 64]), 0.0);
+    }
+
     #[test]
     fn test_calculate_peak() {
         let samples = vec![100i16, -200, 300, -400, 500];
@@ -405,4 +523,39 @@ mod tests {
             assert!(!checksum.value.is_empty());
         }
     }
+
+    #[test]
+    fn test_count_clipped_samples() {
+        // Sine-like ramp with a flat top of 5 samples, a 2-sample touch and
+        // a negative flat bottom of 3 samples
+        let mut samples = vec![0.0, 0.5, 0.9];
+        samples.extend([1.0; 5]);
+        samples.extend([0.9, 0.2, 1.0, 1.0, 0.3, -0.7]);
+        samples.extend([-1.0; 3]);
+        samples.extend([-0.5, 0.0]);
+
+        let stats = count_clipped_samples(&samples, 3);
+        assert_eq!(stats.clipped_samples, 10);
+        assert_eq!(stats.clip_events, 2);
+        assert!(stats.is_clipped());
+
+        let stats = count_clipped_samples(&samples, 1);
+        assert_eq!(stats.clip_events, 3);
+
+        let clean = count_clipped_samples(&[0.0, 0.99, -0.99], 1);
+        assert_eq!(clean, ClipStats::default());
+        assert!(!clean.is_clipped());
+    }
+
+    #[test]
+    fn test_count_clipped_samples_interleaved() {
+        // Left channel flat-topped for 4 frames, right channel clean
+        let samples = [1.0, 0.1, 1.0, 0.1, 1.0, 0.1, 1.0, 0.1, 0.2, 0.1];
+        let stats = count_clipped_samples_interleaved(&samples, 2, 3);
+        assert_eq!(stats.clipped_samples, 4);
+        assert_eq!(stats.clip_events, 1);
+
+        // Treated as one channel the run is broken up
+        assert_eq!(count_clipped_samples(&samples, 3).clip_events, 0);
+    }
 }
@@ -3,36 +3,86 @@
 //! Handles decoding of various audio formats (FLAC, MP3, AAC, etc.)
 
 use crate::audio::buffer::AudioBuffer;
+use crate::audio::dsd::{self, DsdDecoder};
 use crate::audio::format::AudioFormat;
 use crate::audio::ring_buffer::{
     AudioRingBuffer, RingBufferConfig, RingBufferConsumer, RingBufferProducer,
+    MIN_BUFFER_DURATION_SECONDS,
 };
 use crate::Result;
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use symphonia::core::audio::{AudioBufferRef, Signal};
-use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::codecs::{
+    CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_MP1, CODEC_TYPE_MP2, CODEC_TYPE_MP3,
+    CODEC_TYPE_NULL,
+};
 use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::{FormatOptions, FormatReader};
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
+use symphonia::core::io::{MediaSource, MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::TimeBase;
 
 /// Audio decoder using Symphonia
+///
+/// DSD files, which Symphonia can't read, are converted to PCM by the
+/// `audio::dsd` module instead.
 pub struct AudioDecoder {
-    /// Format reader for the audio file
-    format_reader: Box<dyn FormatReader>,
-    /// Audio decoder
-    decoder: Box<dyn Decoder>,
-    /// Track ID being decoded
-    track_id: u32,
+    /// Decoding backend
+    source: DecoderSource,
     /// Audio format information
     format: AudioFormat,
     /// Total duration in samples (if known)
     duration: Option<u64>,
+    /// How `duration` was arrived at
+    duration_accuracy: DurationAccuracy,
+    /// File being decoded, for error messages
+    path: PathBuf,
+    /// Stream position after the last decoded packet, in samples
+    position: u64,
+    /// Frames handed out since opening or the last seek
+    frames: FrameAccounting,
+    /// Textual tags read when the file was opened
+    tags: TrackTags,
+    /// ReplayGain tags read when the file was opened
+    replay_gain: ReplayGainTags,
+    /// Whether the underlying source supports seeking
+    seekable: bool,
+}
+
+/// Running count of decoded frames, for catching dropped or repeated audio
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameAccounting {
+    /// Frames decoded since counting started
+    decoded: u64,
+    /// Stream position counting started at
+    start: u64,
+    /// First packet that didn't start where the previous one ended, as
+    /// (expected, actual) start positions
+    discontinuity: Option<(u64, u64)>,
+}
+
+/// Decoding backend of an `AudioDecoder`
+enum DecoderSource {
+    /// Formats handled by Symphonia
+    Symphonia {
+        /// Format reader for the audio file
+        format_reader: Box<dyn FormatReader>,
+        /// Audio decoder
+        decoder: Box<dyn Decoder>,
+        /// Track ID being decoded
+        track_id: u32,
+    },
+    /// DSD stream decimated to PCM
+    Dsd(DsdDecoder),
 }
 
 /// Decoded audio packet
@@ -41,10 +91,235 @@ pub struct DecodedPacket {
     pub samples: Vec<f64>,
     /// Number of frames in this packet
     pub frames: usize,
+    /// Stream position of the first frame, in samples at the stream rate
+    pub timestamp_samples: u64,
     /// Audio format
     pub format: AudioFormat,
 }
 
+/// Iterator over the packets of an `AudioDecoder`, see `AudioDecoder::packets`
+pub struct Packets<'a> {
+    decoder: &'a mut AudioDecoder,
+    done: bool,
+}
+
+impl Iterator for Packets<'_> {
+    type Item = Result<DecodedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.decoder.decode_next().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+impl std::iter::FusedIterator for Packets<'_> {}
+
+/// Textual tags of a track, normalized across tag formats
+///
+/// ID3v2 frames, Vorbis comments, MP4 atoms and RIFF INFO chunks all map
+/// to the same fields; tags a file doesn't carry are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackTags {
+    /// Track title
+    pub title: Option<String>,
+    /// Track artist
+    pub artist: Option<String>,
+    /// Album name
+    pub album: Option<String>,
+    /// Album artist
+    pub album_artist: Option<String>,
+    /// Track number in the album (the "3" of "3/12")
+    pub track_number: Option<u32>,
+    /// Disc number in a multi-disc release
+    pub disc_number: Option<u32>,
+    /// Year of release
+    pub year: Option<u32>,
+    /// Genre
+    pub genre: Option<String>,
+    /// Composer
+    pub composer: Option<String>,
+}
+
+/// `REPLAYGAIN_*` gains a track is tagged with
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplayGainTags {
+    /// Track gain in dB
+    pub track_gain_db: Option<f64>,
+    /// Album gain in dB
+    pub album_gain_db: Option<f64>,
+}
+
+impl ReplayGainTags {
+    /// Copy a ReplayGain gain tag into the matching field
+    pub(crate) fn apply(&mut self, tag: &Tag) {
+        let track_gain = matches!(tag.std_key, Some(StandardTagKey::ReplayGainTrackGain))
+            || tag.key.eq_ignore_ascii_case("REPLAYGAIN_TRACK_GAIN");
+        let album_gain = matches!(tag.std_key, Some(StandardTagKey::ReplayGainAlbumGain))
+            || tag.key.eq_ignore_ascii_case("REPLAYGAIN_ALBUM_GAIN");
+        if track_gain {
+            self.track_gain_db = parse_gain_db(&tag.value.to_string());
+        } else if album_gain {
+            self.album_gain_db = parse_gain_db(&tag.value.to_string());
+        }
+    }
+
+    /// Album gain if `album` is set, else track gain; each falls back to
+    /// the other
+    pub fn gain_db(&self, album: bool) -> Option<f64> {
+        if album {
+            self.album_gain_db.or(self.track_gain_db)
+        } else {
+            self.track_gain_db.or(self.album_gain_db)
+        }
+    }
+}
+
+/// Parse a ReplayGain tag value ("-6.48 dB")
+pub(crate) fn parse_gain_db(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number
+        .trim()
+        .parse()
+        .ok()
+        .filter(|gain: &f64| gain.is_finite())
+}
+
+/// An audio track of a container, for choosing which one to decode
+///
+/// Containers like Matroska can carry several audio tracks (e.g. a main
+/// mix and a commentary). Track names are not reported by the demuxer, so
+/// the language is the only descriptive tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTrackInfo {
+    /// Container track ID, as passed to `AudioDecoder::new_with_track`
+    pub id: u32,
+    /// Short codec name (e.g. "flac", "aac")
+    pub codec: String,
+    /// Number of channels (if known)
+    pub channels: Option<u16>,
+    /// Sample rate in Hz (if known)
+    pub sample_rate: Option<u32>,
+    /// Language code (e.g. "eng"), if the container carries one
+    pub language: Option<String>,
+}
+
+/// How a decoder's duration was arrived at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationAccuracy {
+    /// Declared by the container or counted from packet timestamps
+    Exact,
+    /// Extrapolated from the file size and bitrate; may be off for
+    /// variable-bitrate files
+    Estimated,
+}
+
+impl TrackTags {
+    /// Copy a standard tag into the matching field, replacing earlier values
+    pub(crate) fn apply(&mut self, tag: &Tag) {
+        let value = tag.value.to_string();
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => self.title = Some(value),
+            Some(StandardTagKey::Artist) => self.artist = Some(value),
+            Some(StandardTagKey::Album) => self.album = Some(value),
+            Some(StandardTagKey::AlbumArtist) => self.album_artist = Some(value),
+            Some(StandardTagKey::TrackNumber) => self.track_number = leading_number(&value),
+            Some(StandardTagKey::DiscNumber) => self.disc_number = leading_number(&value),
+            Some(StandardTagKey::Date) => self.year = leading_number(&value),
+            Some(StandardTagKey::Genre) => self.genre = Some(value),
+            Some(StandardTagKey::Composer) => self.composer = Some(value),
+            _ => {}
+        }
+    }
+}
+
+/// Parse the number at the start of a tag value ("3/12", "2001-05-03")
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Visit the tags of a probed file
+///
+/// Tags found while probing (e.g. ID3v2) come first so the container's own
+/// tags override them.
+pub(crate) fn visit_tags(probed: &mut ProbeResult, mut visit: impl FnMut(&Tag)) {
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        revision.tags().iter().for_each(&mut visit);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        revision.tags().iter().for_each(&mut visit);
+    }
+}
+
+/// Chapter marker embedded in a container (audiobooks, podcasts)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Chapter title, if the container names it
+    pub title: Option<String>,
+    /// First sample of the chapter, at the stream's sample rate
+    pub start_sample: u64,
+    /// Sample rate `start_sample` is expressed in
+    pub sample_rate: u32,
+}
+
+impl Chapter {
+    /// Start of the chapter in seconds
+    pub fn start_seconds(&self) -> f64 {
+        self.start_sample as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// Packet-by-packet decoding as driven by the stream reader threads
+trait PacketSource: Send {
+    /// Decode the next packet (`None` at the end of the stream)
+    fn decode_next(&mut self) -> Result<Option<DecodedPacket>>;
+
+    /// Rewind to the start of the stream
+    fn reset(&mut self) -> Result<()>;
+}
+
+impl PacketSource for AudioDecoder {
+    fn decode_next(&mut self) -> Result<Option<DecodedPacket>> {
+        AudioDecoder::decode_next(self)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        AudioDecoder::reset(self)
+    }
+}
+
+/// Run a decoder call, turning a panic into a decoding error
+///
+/// Malformed files can trip arithmetic overflows or slice bounds inside
+/// codecs; catching them here keeps the decoder's lock unpoisoned and lets
+/// the reader report the cause instead of a bare disconnect.
+fn catch_decoder_panic<T>(call: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|payload| {
+        let cause = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown cause".to_string());
+        Err(crate::Error::Decoding(format!(
+            "Decoder panicked: {}",
+            cause
+        )))
+    })
+}
+
 /// Audio stream reader for continuous decoding
 pub struct AudioStreamReader {
     /// The decoder
@@ -61,13 +336,38 @@ pub struct AudioStreamReader {
 pub struct AudioStreamReaderWithRingBuffer {
     /// The decoder
     decoder: Arc<Mutex<AudioDecoder>>,
+    /// Stream configuration the reader was created with
+    config: StreamConfig,
+    /// Configuration of the output ring buffer
+    ring_buffer_config: RingBufferConfig,
     /// Handle to the decoding thread
     decode_thread: Option<thread::JoinHandle<()>>,
     /// Flag to stop the decoding thread
     stop_flag: Arc<Mutex<bool>>,
+    /// Whether the decoding thread restarts at the end of the stream
+    looping: Arc<AtomicBool>,
+    /// Error that ended decoding, if any
+    error: Arc<Mutex<Option<crate::Error>>>,
+    /// Whether the decoder's source supports seeking
+    seekable: bool,
 }
 
+/// Smallest accepted packet size in frames
+pub const MIN_STREAM_BUFFER_FRAMES: usize = 64;
+
+/// Largest accepted packet size in frames
+pub const MAX_STREAM_BUFFER_FRAMES: usize = 65536;
+
+/// Largest accepted number of packets buffered ahead
+pub const MAX_PREFETCH_PACKETS: usize = 64;
+
+/// Smallest progress step reported by `decode_all_with_progress`
+pub const DECODE_PROGRESS_STEP: f64 = 0.01;
+
 /// Configuration for audio stream reading
+///
+/// Small packets and prefetch depth lower latency; larger values ride out
+/// slow storage (e.g. network shares) without underruns.
 #[derive(Debug, Clone)]
 pub struct StreamConfig {
     /// Buffer size in frames per packet
@@ -78,41 +378,203 @@ pub struct StreamConfig {
     pub prefetch_size: usize,
 }
 
+impl StreamConfig {
+    /// Validate the configuration
+    ///
+    /// `buffer_size` must be within 64..=65536 frames and `prefetch_size`
+    /// within 1..=64 packets.
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_STREAM_BUFFER_FRAMES..=MAX_STREAM_BUFFER_FRAMES).contains(&self.buffer_size) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Stream buffer size must be {}-{} frames, got {}",
+                MIN_STREAM_BUFFER_FRAMES, MAX_STREAM_BUFFER_FRAMES, self.buffer_size
+            )));
+        }
+        if !(1..=MAX_PREFETCH_PACKETS).contains(&self.prefetch_size) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Prefetch size must be 1-{} packets, got {}",
+                MAX_PREFETCH_PACKETS, self.prefetch_size
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl AudioDecoder {
     /// Create a new audio decoder for the given file
+    ///
+    /// Decodes the file's first audio track.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open(path.as_ref(), None)
+    }
+
+    /// Create a decoder for a chosen audio track of the given file
+    ///
+    /// `track_id` is an ID from `list_audio_tracks`; fails with
+    /// `Error::InvalidParameter` when the file has no such audio track.
+    pub fn new_with_track<P: AsRef<Path>>(path: P, track_id: u32) -> Result<Self> {
+        Self::open(path.as_ref(), Some(track_id))
+    }
+
+    /// List the audio tracks of a file in container order
+    ///
+    /// Only the headers are read. The first entry is the track `new`
+    /// decodes. DSD files have a single track with ID 0.
+    pub fn list_audio_tracks<P: AsRef<Path>>(path: P) -> Result<Vec<AudioTrackInfo>> {
         let path = path.as_ref();
 
-        // Open the file
+        if dsd::is_dsd_file(path) {
+            let stream = dsd::DsdStream::open(path)?;
+            return Ok(vec![AudioTrackInfo {
+                id: 0,
+                codec: "dsd".to_string(),
+                channels: Some(stream.channels),
+                sample_rate: Some(stream.pcm_sample_rate()),
+                language: None,
+            }]);
+        }
+
         let file = File::open(path).map_err(crate::Error::Io)?;
         let media_source = MediaSourceStream::new(Box::new(file), Default::default());
 
-        // Create a hint based on file extension
         let mut hint = Hint::new();
-        if let Some(extension) = path.extension() {
-            if let Some(ext_str) = extension.to_str() {
-                hint.with_extension(ext_str);
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                media_source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| crate::Error::UnsupportedFormat(format!("Failed to probe file: {}", e)))?;
+
+        Ok(probed
+            .format
+            .tracks()
+            .iter()
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .map(|track| {
+                let params = &track.codec_params;
+                AudioTrackInfo {
+                    id: track.id,
+                    codec: symphonia::default::get_codecs()
+                        .get_codec(params.codec)
+                        .map_or_else(
+                            || format!("{:?}", params.codec),
+                            |d| d.short_name.to_string(),
+                        ),
+                    channels: channel_count(params).map(|count| count as u16),
+                    sample_rate: params.sample_rate,
+                    language: track.language.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// Open `path`, decoding `track_id` or else the first audio track
+    fn open(path: &Path, track_id: Option<u32>) -> Result<Self> {
+        if dsd::is_dsd_file(path) {
+            if track_id.is_some_and(|id| id != 0) {
+                return Err(crate::Error::InvalidParameter(
+                    "DSD files have a single audio track with ID 0".to_string(),
+                ));
             }
+            let dsd = DsdDecoder::open(path)?;
+            return Ok(Self {
+                format: dsd.format(),
+                duration: Some(dsd.duration()),
+                duration_accuracy: DurationAccuracy::Exact,
+                source: DecoderSource::Dsd(dsd),
+                path: path.to_path_buf(),
+                position: 0,
+                frames: FrameAccounting::default(),
+                // DSD containers carry no tags Symphonia understands
+                tags: TrackTags::default(),
+                replay_gain: ReplayGainTags::default(),
+                seekable: true,
+            });
+        }
+
+        // Open the file
+        let file = File::open(path).map_err(crate::Error::Io)?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let mut decoder =
+            Self::open_source(Box::new(file), extension, path.to_path_buf(), track_id)?;
+
+        if decoder.duration.is_none() {
+            if let DecoderSource::Symphonia { track_id, .. } = decoder.source {
+                if let Some((frames, accuracy)) =
+                    estimate_duration(path, track_id, decoder.format.sample_rate)
+                {
+                    decoder.duration = Some(frames);
+                    decoder.duration_accuracy = accuracy;
+                }
+            }
+        }
+        Ok(decoder)
+    }
+
+    /// Create a decoder reading from an arbitrary media source
+    ///
+    /// For pipes, network streams and in-memory data. `extension` (e.g.
+    /// "flac") helps format detection when given. Sources that report no
+    /// seek support decode normally, but `seek` fails with
+    /// `Error::SeekUnsupported`.
+    pub fn from_source(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<Self> {
+        Self::open_source(source, extension, PathBuf::from("<stream>"), None)
+    }
+
+    /// Probe `source` and set up decoding of `track_id`, or else its first
+    /// audio track
+    fn open_source(
+        source: Box<dyn MediaSource>,
+        extension: Option<&str>,
+        path: PathBuf,
+        track_id: Option<u32>,
+    ) -> Result<Self> {
+        let seekable = source.is_seekable();
+        let media_source = MediaSourceStream::new(source, Default::default());
+
+        // Create a hint based on file extension
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
         }
 
         // Probe the media source
-        let probed = symphonia::default::get_probe()
+        let mut probed = symphonia::default::get_probe()
             .format(
                 &hint,
                 media_source,
                 &FormatOptions::default(),
                 &MetadataOptions::default(),
             )
-            .map_err(|e| crate::Error::Decoding(format!("Failed to probe file: {}", e)))?;
+            .map_err(|e| crate::Error::UnsupportedFormat(format!("Failed to probe file: {}", e)))?;
 
+        let mut tags = TrackTags::default();
+        let mut replay_gain = ReplayGainTags::default();
+        visit_tags(&mut probed, |tag| {
+            tags.apply(tag);
+            replay_gain.apply(tag);
+        });
         let format_reader = probed.format;
 
-        // Find the default audio track
-        let track = format_reader
+        // Find the requested or default audio track
+        let mut audio_tracks = format_reader
             .tracks()
             .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or_else(|| crate::Error::Decoding("No audio tracks found".to_string()))?;
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL);
+        let track = match track_id {
+            Some(id) => audio_tracks.find(|t| t.id == id).ok_or_else(|| {
+                crate::Error::InvalidParameter(format!("No audio track with ID {}", id))
+            })?,
+            None => audio_tracks
+                .next()
+                .ok_or_else(|| crate::Error::Decoding("No audio tracks found".to_string()))?,
+        };
 
         let track_id = track.id;
 
@@ -122,33 +584,76 @@ impl AudioDecoder {
             .map_err(|e| crate::Error::Decoding(format!("Failed to create decoder: {}", e)))?;
 
         // Extract format information
-        let codec_params = &track.codec_params;
-        let sample_rate = codec_params
-            .sample_rate
-            .ok_or_else(|| crate::Error::Decoding("No sample rate information".to_string()))?;
-        let channels = codec_params
-            .channels
-            .ok_or_else(|| crate::Error::Decoding("No channel information".to_string()))?
-            .count() as u16;
-
-        let format = AudioFormat::new(
-            sample_rate,
-            channels,
-            crate::audio::format::SampleFormat::F64, // We'll convert to f64 for precision
-        );
-
-        // Get duration if available
-        let duration = codec_params.n_frames;
+        let format = stream_format(&track.codec_params)?;
+
+        // Get duration if available. MPEG audio demuxers take it from a
+        // Xing/VBRI header when there is one and guess it from the bitrate
+        // otherwise, with no way to tell which happened.
+        let duration = track.codec_params.n_frames;
+        let duration_accuracy = match track.codec_params.codec {
+            CODEC_TYPE_MP1 | CODEC_TYPE_MP2 | CODEC_TYPE_MP3 => DurationAccuracy::Estimated,
+            _ => DurationAccuracy::Exact,
+        };
 
         Ok(Self {
-            format_reader,
-            decoder,
-            track_id,
+            source: DecoderSource::Symphonia {
+                format_reader,
+                decoder,
+                track_id,
+            },
             format,
             duration,
+            duration_accuracy,
+            path,
+            position: 0,
+            frames: FrameAccounting::default(),
+            tags,
+            replay_gain,
+            seekable,
         })
     }
 
+    /// Get the track's title, artist and other textual tags
+    ///
+    /// Read when the decoder was opened, without decoding any audio.
+    pub fn metadata(&self) -> TrackTags {
+        self.tags.clone()
+    }
+
+    /// Get the track's ReplayGain tags
+    ///
+    /// Read when the decoder was opened, without decoding any audio.
+    pub fn replay_gain(&self) -> ReplayGainTags {
+        self.replay_gain
+    }
+
+    /// Describe the file's format and the track being decoded
+    ///
+    /// Built from the probe made when the decoder was opened, without
+    /// reading the file again. Matches `detect_format` for the same track.
+    pub fn format_info(&self) -> Result<AudioFormatInfo> {
+        match &self.source {
+            DecoderSource::Symphonia {
+                format_reader,
+                track_id,
+                ..
+            } => format_info(format_reader.as_ref(), Some(*track_id)),
+            DecoderSource::Dsd(dsd) => Ok(dsd_format_info(dsd.stream(), &self.path)),
+        }
+    }
+
+    /// Get the cue sheet embedded as a native FLAC CUESHEET block
+    ///
+    /// Reads the file's metadata blocks without decoding any audio. The
+    /// sheet has a single FILE entry naming this file, so `from_cue_sheet`
+    /// turns it into virtual tracks like a sidecar `.cue`. `None` for other
+    /// formats and files without the block.
+    pub fn embedded_cuesheet(&self) -> Option<crate::cue::CueSheet> {
+        crate::cue::read_flac_cuesheet(&self.path, self.format.sample_rate)
+            .ok()
+            .flatten()
+    }
+
     /// Get the audio format of the decoded stream
     pub fn format(&self) -> &AudioFormat {
         &self.format
@@ -165,73 +670,346 @@ impl AudioDecoder {
             .map(|frames| frames as f64 / self.format.sample_rate as f64)
     }
 
+    /// Get how the duration was arrived at, or `None` if it is unknown
+    ///
+    /// Files whose container doesn't declare a length get one estimated
+    /// from the file size and bitrate; `compute_exact_duration` replaces an
+    /// estimate with an exact count.
+    pub fn duration_accuracy(&self) -> Option<DurationAccuracy> {
+        self.duration.map(|_| self.duration_accuracy)
+    }
+
+    /// Count the stream's frames exactly and make that the duration
+    ///
+    /// Reads every packet's timestamp without decoding any audio, so is
+    /// much faster than decoding the file, then returns to the current
+    /// position. Fails with `Error::SeekUnsupported` for sources that can't
+    /// be rewound.
+    pub fn compute_exact_duration(&mut self) -> Result<u64> {
+        let (format_reader, track_id) = match &mut self.source {
+            DecoderSource::Symphonia {
+                format_reader,
+                track_id,
+                ..
+            } => (format_reader, *track_id),
+            // DSD lengths follow from the data chunk size
+            DecoderSource::Dsd(dsd) => return Ok(dsd.duration()),
+        };
+        if !self.seekable {
+            return Err(crate::Error::SeekUnsupported(format!(
+                "{} can't be rewound after counting its frames",
+                self.path.display()
+            )));
+        }
+
+        let time_base = format_reader
+            .tracks()
+            .iter()
+            .find(|t| t.id == track_id)
+            .and_then(|t| t.codec_params.time_base);
+        let mut end = 0;
+        while let Some(packet) =
+            next_track_packet(format_reader.as_mut(), track_id).map_err(|e| {
+                crate::Error::Decoding(format!(
+                    "{}: failed to read packet: {}",
+                    self.path.display(),
+                    e
+                ))
+            })?
+        {
+            end = end.max(timestamp_to_samples(
+                packet.ts() + packet.dur(),
+                time_base,
+                self.format.sample_rate,
+            ));
+        }
+
+        let frames = self.frames;
+        self.seek(self.position)?;
+        self.frames = frames;
+        self.duration = Some(end);
+        self.duration_accuracy = DurationAccuracy::Exact;
+        Ok(end)
+    }
+
+    /// Get the container's chapter markers, ordered by start
+    ///
+    /// Returns an empty list for files without chapters.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let (format_reader, track_id) = match &self.source {
+            DecoderSource::Symphonia {
+                format_reader,
+                track_id,
+                ..
+            } => (format_reader, *track_id),
+            DecoderSource::Dsd(_) => return Vec::new(),
+        };
+
+        let time_base = format_reader
+            .tracks()
+            .iter()
+            .find(|t| t.id == track_id)
+            .and_then(|t| t.codec_params.time_base);
+
+        chapters_from_cues(format_reader.cues(), time_base, self.format.sample_rate)
+    }
+
     /// Decode the next packet
+    ///
+    /// Returns `Ok(None)` at the end of the stream. A file that ends early
+    /// (a partial download, a frame cut off mid-way) also ends the stream
+    /// there; other failures are reported with the file path and the
+    /// approximate position in the stream.
     pub fn decode_next(&mut self) -> Result<Option<DecodedPacket>> {
-        // Get the next packet
-        let packet = match self.format_reader.next_packet() {
-            Ok(packet) => packet,
-            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                return Ok(None); // End of stream
-            }
-            Err(e) => {
-                return Err(crate::Error::Decoding(format!(
-                    "Failed to read packet: {}",
-                    e
-                )))
+        let (format_reader, decoder, track_id) = match &mut self.source {
+            DecoderSource::Symphonia {
+                format_reader,
+                decoder,
+                track_id,
+            } => (format_reader, decoder, *track_id),
+            DecoderSource::Dsd(dsd) => {
+                let channels = self.format.channels.max(1) as usize;
+                let timestamp_samples = dsd.position();
+                let Some(samples) = dsd.decode_next()? else {
+                    return Ok(None);
+                };
+                let frames = samples.len() / channels;
+                self.account_packet(timestamp_samples, frames);
+                return Ok(Some(DecodedPacket {
+                    frames,
+                    samples,
+                    timestamp_samples,
+                    format: self.format.clone(),
+                }));
             }
         };
 
-        // Skip packets that don't belong to our track
-        if packet.track_id() != self.track_id {
-            return self.decode_next(); // Recursively try next packet
-        }
+        let stream_error = |context: &str, position: u64, e: SymphoniaError| {
+            crate::Error::Decoding(format!(
+                "{}: {} at {:.2}s: {}",
+                self.path.display(),
+                context,
+                position as f64 / self.format.sample_rate.max(1) as f64,
+                e
+            ))
+        };
+
+        let packet = match next_track_packet(format_reader.as_mut(), track_id) {
+            Ok(Some(packet)) => packet,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(stream_error("failed to read packet", self.position, e)),
+        };
+
+        let time_base = format_reader
+            .tracks()
+            .iter()
+            .find(|t| t.id == track_id)
+            .and_then(|t| t.codec_params.time_base);
+        let timestamp_samples =
+            timestamp_to_samples(packet.ts(), time_base, self.format.sample_rate);
 
         // Decode the packet
-        let decoded = self
-            .decoder
-            .decode(&packet)
-            .map_err(|e| crate::Error::Decoding(format!("Failed to decode packet: {}", e)))?;
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(e) if is_end_of_stream(&e) => return Ok(None),
+            Err(e) => {
+                // A damaged final packet is the tail of a truncated file
+                return match next_track_packet(format_reader.as_mut(), track_id) {
+                    Ok(None) => Ok(None),
+                    _ => Err(stream_error(
+                        "failed to decode packet",
+                        timestamp_samples,
+                        e,
+                    )),
+                };
+            }
+        };
 
         // Convert to our format
         let frames = decoded.frames();
         let samples = Self::convert_audio_buffer_static(&decoded)?;
+        self.account_packet(timestamp_samples, frames);
 
         Ok(Some(DecodedPacket {
             samples,
             frames,
+            timestamp_samples,
             format: self.format.clone(),
         }))
     }
 
+    /// Count a decoded packet and move the position past it
+    fn account_packet(&mut self, timestamp_samples: u64, frames: usize) {
+        let accounting = &mut self.frames;
+        if accounting.decoded > 0
+            && timestamp_samples != self.position
+            && accounting.discontinuity.is_none()
+        {
+            accounting.discontinuity = Some((self.position, timestamp_samples));
+        }
+        accounting.decoded += frames as u64;
+        self.position = timestamp_samples + frames as u64;
+    }
+
+    /// Iterate over the remaining packets
+    ///
+    /// Yields what `decode_next` returns until the end of the stream. The
+    /// iterator ends after yielding an error.
+    pub fn packets(&mut self) -> Packets<'_> {
+        Packets {
+            decoder: self,
+            done: false,
+        }
+    }
+
+    /// Get the number of frames decoded since opening or the last seek
+    ///
+    /// The sum of the `frames` of every packet `decode_next` returned.
+    pub fn decoded_frame_count(&self) -> u64 {
+        self.frames.decoded
+    }
+
+    /// Check the decoded frames against the container's declared length
+    ///
+    /// Call once the stream has been decoded to the end. Fails if a packet
+    /// didn't start where the previous one ended (a dropped or repeated
+    /// stretch), or if decoding from the start produced a different number
+    /// of frames than `duration()`. A no-op for streams of unknown length.
+    pub fn verify_frame_count(&self) -> Result<()> {
+        let Some(duration) = self.duration else {
+            return Ok(());
+        };
+
+        if let Some((expected, actual)) = self.frames.discontinuity {
+            let kind = if actual > expected { "gap" } else { "overlap" };
+            return Err(crate::Error::Decoding(format!(
+                "{}: {} of {} frames between packets at frame {}",
+                self.path.display(),
+                kind,
+                actual.abs_diff(expected),
+                expected
+            )));
+        }
+        if self.frames.start == 0 && self.frames.decoded != duration {
+            return Err(crate::Error::Decoding(format!(
+                "{}: decoded {} frames, container declares {}",
+                self.path.display(),
+                self.frames.decoded,
+                duration
+            )));
+        }
+        Ok(())
+    }
+
     /// Decode all audio data into a single buffer
     pub fn decode_all(&mut self) -> Result<AudioBuffer> {
+        self.decode_all_with_progress(|_| {})
+    }
+
+    /// Decode all audio data, reporting progress as a 0.0–1.0 fraction
+    ///
+    /// Progress is decoded frames over the known duration, reported in steps
+    /// of at least `DECODE_PROGRESS_STEP` and never decreasing. Without a
+    /// known duration `NaN` is reported about once per second of decoded
+    /// audio. A successful decode always ends with 1.0.
+    pub fn decode_all_with_progress<F: FnMut(f64)>(
+        &mut self,
+        mut progress: F,
+    ) -> Result<AudioBuffer> {
         let mut all_samples = Vec::new();
-        let mut _total_frames = 0;
+        let mut total_frames = 0u64;
+        let mut reported = 0.0;
+        let mut next_indeterminate = 0u64;
 
         while let Some(packet) = self.decode_next()? {
             all_samples.extend(packet.samples);
-            _total_frames += packet.frames;
+            total_frames += packet.frames as u64;
+
+            match self.duration.filter(|&frames| frames > 0) {
+                Some(duration) => {
+                    let fraction = (total_frames as f64 / duration as f64).min(1.0);
+                    if fraction - reported >= DECODE_PROGRESS_STEP {
+                        reported = fraction;
+                        progress(fraction);
+                    }
+                }
+                None if total_frames >= next_indeterminate => {
+                    next_indeterminate = total_frames + self.format.sample_rate as u64;
+                    progress(f64::NAN);
+                }
+                None => {}
+            }
         }
 
+        if reported < 1.0 {
+            progress(1.0);
+        }
+        // Packets must concatenate to exactly the frames they declared
+        debug_assert_eq!(
+            all_samples.len() as u64,
+            total_frames * self.format.channels as u64
+        );
         Ok(AudioBuffer::with_data(self.format.clone(), all_samples))
     }
 
+    /// Check whether the source supports seeking
+    ///
+    /// False for pipes and live streams; `seek` then fails up front.
+    pub fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
     /// Seek to a specific position (in samples)
+    ///
+    /// Fails with `Error::SeekUnsupported` if the source isn't seekable.
     pub fn seek(&mut self, position: u64) -> Result<()> {
+        if !self.seekable {
+            return Err(crate::Error::SeekUnsupported(format!(
+                "{} does not support seeking",
+                self.path.display()
+            )));
+        }
+        let (format_reader, track_id) = match &mut self.source {
+            DecoderSource::Symphonia {
+                format_reader,
+                track_id,
+                ..
+            } => (format_reader, *track_id),
+            DecoderSource::Dsd(dsd) => {
+                dsd.seek(position);
+                self.position = dsd.position();
+                self.frames = FrameAccounting {
+                    start: self.position,
+                    ..FrameAccounting::default()
+                };
+                return Ok(());
+            }
+        };
+
         // Convert sample position to time
         let time_seconds = position as f64 / self.format.sample_rate as f64;
-        let time_base = symphonia::core::units::TimeBase::new(1, self.format.sample_rate);
+        let time_base = TimeBase::new(1, self.format.sample_rate);
         let timestamp = time_base.calc_timestamp(symphonia::core::units::Time::from(time_seconds));
 
-        self.format_reader
+        format_reader
             .seek(
                 symphonia::core::formats::SeekMode::Accurate,
                 symphonia::core::formats::SeekTo::TimeStamp {
                     ts: timestamp,
-                    track_id: self.track_id,
+                    track_id,
                 },
             )
-            .map_err(|e| crate::Error::Decoding(format!("Seek failed: {}", e)))?;
+            .map_err(|e| match e {
+                SymphoniaError::SeekError(symphonia::core::errors::SeekErrorKind::Unseekable) => {
+                    crate::Error::SeekUnsupported(format!("Seek failed: {}", e))
+                }
+                _ => crate::Error::Decoding(format!("Seek failed: {}", e)),
+            })?;
+        self.position = position;
+        self.frames = FrameAccounting {
+            start: position,
+            ..FrameAccounting::default()
+        };
 
         Ok(())
     }
@@ -337,24 +1115,35 @@ impl AudioStreamReader {
     /// Create a new audio stream reader
     pub fn new<P: AsRef<Path>>(path: P, config: StreamConfig) -> Result<Self> {
         let decoder = Arc::new(Mutex::new(AudioDecoder::new(path)?));
+        Ok(Self::spawn(decoder.clone(), decoder, config))
+    }
+
+    /// Start a decoding thread pulling packets from `source`
+    ///
+    /// `decoder` answers format, duration and seek queries; it is the same
+    /// decoder as `source` outside of tests.
+    fn spawn<S: PacketSource + 'static>(
+        decoder: Arc<Mutex<AudioDecoder>>,
+        source: Arc<Mutex<S>>,
+        config: StreamConfig,
+    ) -> Self {
         let (packet_sender, packet_receiver) = mpsc::channel();
         let stop_flag = Arc::new(Mutex::new(false));
 
         // Clone references for the thread
-        let decoder_clone = decoder.clone();
         let stop_flag_clone = stop_flag.clone();
 
         // Start the decoding thread
         let decode_thread = thread::spawn(move || {
-            Self::decode_loop(decoder_clone, packet_sender, stop_flag_clone, config);
+            Self::decode_loop(source, packet_sender, stop_flag_clone, config);
         });
 
-        Ok(Self {
+        Self {
             decoder,
             packet_receiver,
             decode_thread: Some(decode_thread),
             stop_flag,
-        })
+        }
     }
 
     /// Get the next decoded packet
@@ -408,19 +1197,29 @@ impl AudioStreamReader {
     }
 
     /// Check if the stream is still active
+    ///
+    /// Turns false on `stop` and once decoding has failed.
     pub fn is_active(&self) -> bool {
         !*self.stop_flag.lock().unwrap()
     }
 
     /// Decoding loop that runs in a separate thread
-    fn decode_loop(
-        decoder: Arc<Mutex<AudioDecoder>>,
+    ///
+    /// A decoding error (including a panic inside the decoder) is sent as
+    /// the last packet and stops the reader.
+    fn decode_loop<S: PacketSource>(
+        decoder: Arc<Mutex<S>>,
         sender: mpsc::Sender<Result<Option<DecodedPacket>>>,
         stop_flag: Arc<Mutex<bool>>,
         config: StreamConfig,
     ) {
-        let mut packet_buffer = Vec::new();
-        let mut eof_reached = false;
+        let mut packet_buffer = VecDeque::new();
+        // Set once the end of the stream or an error is queued
+        let mut finished = false;
+        // Set once an error is queued
+        let mut failed = false;
+        // A looping stream that yields nothing would otherwise restart forever
+        let mut decoded_since_reset = false;
 
         loop {
             // Check stop flag
@@ -429,43 +1228,50 @@ impl AudioStreamReader {
             }
 
             // Maintain prefetch buffer
-            while packet_buffer.len() < config.prefetch_size && !eof_reached {
+            while packet_buffer.len() < config.prefetch_size && !finished {
                 let mut decoder = decoder.lock().unwrap();
 
-                match decoder.decode_next() {
+                match catch_decoder_panic(|| decoder.decode_next()) {
                     Ok(Some(packet)) => {
-                        packet_buffer.push(Ok(Some(packet)));
+                        decoded_since_reset = true;
+                        packet_buffer.push_back(Ok(Some(packet)));
+                    }
+                    Ok(None) if config.loop_playback && decoded_since_reset => {
+                        // Reset to beginning for looping
+                        decoded_since_reset = false;
+                        if let Err(e) = catch_decoder_panic(|| decoder.reset()) {
+                            packet_buffer.push_back(Err(e));
+                            finished = true;
+                            failed = true;
+                        }
                     }
                     Ok(None) => {
                         // End of file
-                        if config.loop_playback {
-                            // Reset to beginning for looping
-                            if let Err(e) = decoder.reset() {
-                                packet_buffer.push(Err(e));
-                                break;
-                            }
-                        } else {
-                            eof_reached = true;
-                            packet_buffer.push(Ok(None));
-                        }
+                        finished = true;
+                        packet_buffer.push_back(Ok(None));
                     }
                     Err(e) => {
-                        packet_buffer.push(Err(e));
-                        break;
+                        // Surface the error once instead of retrying it
+                        finished = true;
+                        failed = true;
+                        packet_buffer.push_back(Err(e));
                     }
                 }
 
                 drop(decoder); // Release lock
             }
 
-            // Send buffered packets
-            if let Some(packet) = packet_buffer.pop() {
+            // Send buffered packets in decode order
+            if let Some(packet) = packet_buffer.pop_front() {
                 if sender.send(packet).is_err() {
                     // Receiver disconnected
                     break;
                 }
-            } else if eof_reached {
-                // No more packets and EOF reached
+            } else if finished {
+                // Everything up to the end or error was sent
+                if failed {
+                    *stop_flag.lock().unwrap() = true;
+                }
                 break;
             } else {
                 // Wait a bit before trying again
@@ -487,52 +1293,130 @@ impl AudioStreamReaderWithRingBuffer {
         path: P,
         config: StreamConfig,
     ) -> Result<(Self, RingBufferConsumer)> {
+        Self::with_ring_buffer_config(path, None, config)
+    }
+
+    /// Create a stream reader with an explicit ring buffer configuration
+    ///
+    /// The ring buffer takes its format from the decoded file; `ring_buffer`
+    /// supplies duration, overwrite and underrun settings. With `None` the
+    /// buffer holds four packets (at least the minimum buffer duration).
+    pub fn with_ring_buffer_config<P: AsRef<Path>>(
+        path: P,
+        ring_buffer: Option<RingBufferConfig>,
+        config: StreamConfig,
+    ) -> Result<(Self, RingBufferConsumer)> {
+        config.validate()?;
         let decoder = Arc::new(Mutex::new(AudioDecoder::new(&path)?));
         let stop_flag = Arc::new(Mutex::new(false));
 
         // Get audio format from decoder
-        let audio_format = {
+        let (audio_format, seekable) = {
             let decoder_guard = decoder.lock().unwrap();
-            decoder_guard.format().clone()
+            (decoder_guard.format().clone(), decoder_guard.is_seekable())
         };
 
         // Create ring buffer with appropriate configuration
-        let ring_buffer_config = RingBufferConfig {
-            buffer_duration_seconds: config.buffer_size as f64 / audio_format.sample_rate as f64
-                * 4.0, // 4x buffer size
-            format: audio_format,
-            allow_overwrite: false,
-            underrun_threshold: 0.1,
+        let ring_buffer_config = match ring_buffer {
+            Some(ring_buffer) => RingBufferConfig {
+                format: audio_format,
+                ..ring_buffer
+            },
+            None => RingBufferConfig {
+                buffer_duration_seconds: (config.buffer_size as f64
+                    / audio_format.sample_rate as f64
+                    * 4.0) // 4x buffer size
+                    .max(MIN_BUFFER_DURATION_SECONDS),
+                format: audio_format,
+                allow_overwrite: false,
+                underrun_threshold: 0.1,
+            },
         };
 
-        let (producer, consumer) = AudioRingBuffer::new(ring_buffer_config).map_err(|e| {
-            crate::Error::AudioEngine(format!("Failed to create ring buffer: {}", e))
-        })?;
+        let (producer, consumer) =
+            AudioRingBuffer::new(ring_buffer_config.clone()).map_err(|e| {
+                crate::Error::InvalidParameter(format!("Invalid ring buffer configuration: {}", e))
+            })?;
 
         // Clone references for the thread
         let decoder_clone = decoder.clone();
         let stop_flag_clone = stop_flag.clone();
+        let error = Arc::new(Mutex::new(None));
+        let error_clone = error.clone();
+        let looping = Arc::new(AtomicBool::new(config.loop_playback));
+        let looping_clone = looping.clone();
 
         // Start the decoding thread
         let decode_thread = thread::spawn(move || {
-            Self::decode_to_ring_buffer_loop(decoder_clone, producer, stop_flag_clone, config);
+            let result = Self::decode_to_ring_buffer_loop(
+                decoder_clone,
+                producer,
+                stop_flag_clone,
+                looping_clone,
+            );
+            if let Err(e) = result {
+                *error_clone.lock().unwrap() = Some(e);
+            }
         });
 
         let reader = Self {
             decoder,
+            config,
+            ring_buffer_config,
             decode_thread: Some(decode_thread),
             stop_flag,
+            looping,
+            error,
+            seekable,
         };
 
         Ok((reader, consumer))
     }
 
+    /// Check whether the file being streamed supports seeking
+    pub fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    /// Get the stream configuration
+    pub fn config(&self) -> &StreamConfig {
+        &self.config
+    }
+
+    /// Restart at the beginning instead of ending the stream
+    ///
+    /// Overrides `StreamConfig::loop_playback` for the running decoder. Has
+    /// no effect once a non-looping stream has been decoded to its end.
+    pub fn set_looping(&self, enabled: bool) {
+        self.looping.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Check whether the stream restarts at its end
+    pub fn is_looping(&self) -> bool {
+        self.looping.load(Ordering::Relaxed)
+    }
+
+    /// Get the ring buffer configuration
+    pub fn ring_buffer_config(&self) -> &RingBufferConfig {
+        &self.ring_buffer_config
+    }
+
     /// Get the audio format
     pub fn format(&self) -> Result<AudioFormat> {
         let decoder = self.decoder.lock().unwrap();
         Ok(decoder.format().clone())
     }
 
+    /// Get the track's ReplayGain tags
+    pub fn replay_gain(&self) -> ReplayGainTags {
+        self.decoder.lock().unwrap().replay_gain()
+    }
+
+    /// Describe the file's format, see `AudioDecoder::format_info`
+    pub fn format_info(&self) -> Result<AudioFormatInfo> {
+        self.decoder.lock().unwrap().format_info()
+    }
+
     /// Get the duration in samples (if known)
     pub fn duration(&self) -> Result<Option<u64>> {
         let decoder = self.decoder.lock().unwrap();
@@ -561,29 +1445,58 @@ impl AudioStreamReaderWithRingBuffer {
         !*self.stop_flag.lock().unwrap()
     }
 
+    /// Check if the decoding thread has exited
+    ///
+    /// Happens at the end of a non-looping stream, on `stop` and after a
+    /// decoding error (see `take_error`).
+    pub fn is_finished(&self) -> bool {
+        self.decode_thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+
+    /// Take the error that stopped decoding, if any
+    pub fn take_error(&self) -> Option<crate::Error> {
+        self.error.lock().unwrap().take()
+    }
+
     /// Decoding loop that writes directly to ring buffer
+    ///
+    /// Runs until the end of the stream (or forever when looping), a stop
+    /// request or the first decoding error, which is returned.
     fn decode_to_ring_buffer_loop(
         decoder: Arc<Mutex<AudioDecoder>>,
         producer: RingBufferProducer,
         stop_flag: Arc<Mutex<bool>>,
-        config: StreamConfig,
-    ) {
-        let mut _eof_reached = false;
+        looping: Arc<AtomicBool>,
+    ) -> Result<()> {
+        // Stream frame the next packet continues from, if known
+        let mut next_timestamp = None;
+        // A looping stream that yields nothing would otherwise restart forever
+        let mut decoded_since_reset = false;
 
         loop {
             // Check stop flag
             if *stop_flag.lock().unwrap() {
-                break;
+                return Ok(());
             }
 
             // Decode next packet
             let packet_result = {
                 let mut decoder = decoder.lock().unwrap();
-                decoder.decode_next()
+                catch_decoder_panic(|| decoder.decode_next())
             };
 
             match packet_result {
                 Ok(Some(packet)) => {
+                    decoded_since_reset = true;
+
+                    // Anchor the consumer's position wherever the stream jumps
+                    if next_timestamp != Some(packet.timestamp_samples) {
+                        producer.mark_timestamp(packet.timestamp_samples);
+                    }
+                    next_timestamp = Some(packet.timestamp_samples + packet.frames as u64);
+
                     // Write samples to ring buffer
                     let mut samples_written = 0;
                     let total_samples = packet.samples.len();
@@ -601,27 +1514,20 @@ impl AudioStreamReaderWithRingBuffer {
 
                         // Check stop flag during writing
                         if *stop_flag.lock().unwrap() {
-                            return;
+                            return Ok(());
                         }
                     }
                 }
-                Ok(None) => {
-                    // End of file
-                    if config.loop_playback {
-                        // Reset to beginning for looping
-                        let mut decoder = decoder.lock().unwrap();
-                        if decoder.reset().is_err() {
-                            break;
-                        }
-                    } else {
-                        _eof_reached = true;
-                        break;
-                    }
-                }
-                Err(_) => {
-                    // Decoding error, stop
-                    break;
+                Ok(None) if looping.load(Ordering::Relaxed) && decoded_since_reset => {
+                    // Reset to beginning for looping
+                    decoded_since_reset = false;
+                    let mut decoder = decoder.lock().unwrap();
+                    catch_decoder_panic(|| decoder.reset())?;
                 }
+                // End of file
+                Ok(None) => return Ok(()),
+                // Decoding error, stop
+                Err(e) => return Err(e),
             }
         }
     }
@@ -633,14 +1539,181 @@ impl Drop for AudioStreamReaderWithRingBuffer {
     }
 }
 
-impl Default for StreamConfig {
-    fn default() -> Self {
-        Self {
-            buffer_size: 1024, // 1024 frames per packet
-            loop_playback: false,
-            prefetch_size: 4, // Buffer 4 packets ahead
-        }
-    }
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 1024, // 1024 frames per packet
+            loop_playback: false,
+            prefetch_size: 4, // Buffer 4 packets ahead
+        }
+    }
+}
+
+/// Read the next packet of `track_id`, skipping packets of other tracks
+///
+/// Returns `Ok(None)` at the end of the stream, including one that ends
+/// unexpectedly.
+fn next_track_packet(
+    format_reader: &mut dyn FormatReader,
+    track_id: u32,
+) -> std::result::Result<Option<symphonia::core::formats::Packet>, SymphoniaError> {
+    loop {
+        match format_reader.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => return Ok(Some(packet)),
+            Ok(_) => continue,
+            Err(e) if is_end_of_stream(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Packets read when estimating a duration from the bitrate
+const DURATION_ESTIMATE_PACKETS: usize = 64;
+
+/// Work out the length of `track_id` in `path` from its first packets
+///
+/// The bytes per frame of the first `DURATION_ESTIMATE_PACKETS` packets are
+/// extrapolated to the whole file. A file that ends within them is counted
+/// exactly instead. Returns `None` if no packet could be read.
+fn estimate_duration(
+    path: &Path,
+    track_id: u32,
+    sample_rate: u32,
+) -> Option<(u64, DurationAccuracy)> {
+    let file = File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    let mut format_reader = symphonia::default::get_probe()
+        .format(
+            &hint,
+            media_source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?
+        .format;
+    let time_base = format_reader
+        .tracks()
+        .iter()
+        .find(|t| t.id == track_id)
+        .and_then(|t| t.codec_params.time_base);
+
+    let mut bytes = 0;
+    let mut end = 0;
+    for _ in 0..DURATION_ESTIMATE_PACKETS {
+        match next_track_packet(format_reader.as_mut(), track_id) {
+            Ok(Some(packet)) => {
+                bytes += packet.data.len() as u64;
+                end = timestamp_to_samples(packet.ts() + packet.dur(), time_base, sample_rate);
+            }
+            Ok(None) => return Some((end, DurationAccuracy::Exact)),
+            Err(_) => break,
+        }
+    }
+    if bytes == 0 {
+        return None;
+    }
+    let frames = (file_len as f64 * end as f64 / bytes as f64).round() as u64;
+    Some((frames, DurationAccuracy::Estimated))
+}
+
+/// Check if a Symphonia error means the data ran out
+fn is_end_of_stream(error: &SymphoniaError) -> bool {
+    matches!(error, SymphoniaError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+/// Convert a container timestamp to a sample position at `sample_rate`
+///
+/// Streams without a time base are assumed to count in samples already.
+fn timestamp_to_samples(ts: u64, time_base: Option<TimeBase>, sample_rate: u32) -> u64 {
+    match time_base {
+        Some(tb) if tb.numer == 1 && tb.denom == sample_rate => ts,
+        Some(tb) => {
+            let time = tb.calc_time(ts);
+            ((time.seconds as f64 + time.frac) * sample_rate as f64).round() as u64
+        }
+        None => ts,
+    }
+}
+
+/// Convert container cues to chapters at `sample_rate`
+///
+/// Containers may declare chapters out of order; they are sorted by start,
+/// and of several chapters starting at the same sample only the first
+/// declared is kept so every chapter has a non-empty span.
+fn chapters_from_cues(
+    cues: &[symphonia::core::formats::Cue],
+    time_base: Option<TimeBase>,
+    sample_rate: u32,
+) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = cues
+        .iter()
+        .map(|cue| Chapter {
+            title: cue
+                .tags
+                .iter()
+                .find(|tag| {
+                    tag.std_key == Some(symphonia::core::meta::StandardTagKey::TrackTitle)
+                        || tag.key.eq_ignore_ascii_case("title")
+                })
+                .map(|tag| tag.value.to_string()),
+            start_sample: timestamp_to_samples(cue.start_ts, time_base, sample_rate),
+            sample_rate,
+        })
+        .collect();
+
+    // Stable, so equal starts keep their declaration order
+    chapters.sort_by_key(|chapter| chapter.start_sample);
+    chapters.dedup_by_key(|chapter| chapter.start_sample);
+    chapters
+}
+
+/// Channel count of a track, from its channel mask or else its layout
+///
+/// Matroska tracks only report a layout.
+fn channel_count(codec_params: &CodecParameters) -> Option<usize> {
+    codec_params
+        .channels
+        .or_else(|| {
+            codec_params
+                .channel_layout
+                .map(|layout| layout.into_channels())
+        })
+        .map(|channels| channels.count())
+}
+
+/// Build the decoded f64 format from a track's codec parameters
+///
+/// Malformed files can report a zero sample rate or channel count, which
+/// would later break frame arithmetic during playback.
+fn stream_format(codec_params: &CodecParameters) -> Result<AudioFormat> {
+    let sample_rate = codec_params
+        .sample_rate
+        .ok_or_else(|| crate::Error::Decoding("No sample rate information".to_string()))?;
+    if sample_rate == 0 {
+        return Err(crate::Error::Decoding(
+            "Invalid sample rate: 0 Hz".to_string(),
+        ));
+    }
+
+    let channels = channel_count(codec_params)
+        .ok_or_else(|| crate::Error::Decoding("No channel information".to_string()))?;
+    if channels == 0 {
+        return Err(crate::Error::Decoding(
+            "Invalid channel layout: 0 channels".to_string(),
+        ));
+    }
+
+    Ok(AudioFormat::new(
+        sample_rate,
+        channels as u16,
+        crate::audio::format::SampleFormat::F64, // We'll convert to f64 for precision
+    ))
 }
 
 /// Check if a file format is supported for decoding
@@ -651,7 +1724,7 @@ pub fn is_format_supported<P: AsRef<Path>>(path: P) -> bool {
         if let Some(ext_str) = extension.to_str() {
             matches!(
                 ext_str.to_lowercase().as_str(),
-                "mp3" | "wav" | "flac" | "ogg" | "m4a" | "aac"
+                "mp3" | "wav" | "flac" | "ogg" | "m4a" | "aac" | "mka" | "dsf" | "dff"
             )
         } else {
             false
@@ -664,6 +1737,9 @@ pub fn is_format_supported<P: AsRef<Path>>(path: P) -> bool {
 /// Detect audio format from file content (not just extension)
 pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<Option<AudioFormatInfo>> {
     let path = path.as_ref();
+    if dsd::is_dsd_file(path) {
+        return detect_dsd_format(path).map(Some);
+    }
 
     // First try to open and probe the file
     let file = File::open(path).map_err(crate::Error::Io)?;
@@ -685,21 +1761,91 @@ pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<Option<AudioFormatInfo>>
             &FormatOptions::default(),
             &MetadataOptions::default(),
         )
-        .map_err(|e| crate::Error::Decoding(format!("Failed to probe file: {}", e)))?;
+        .map_err(|e| crate::Error::UnsupportedFormat(format!("Failed to probe file: {}", e)))?;
+
+    format_info(probed.format.as_ref(), None).map(Some)
+}
+
+/// MIME types `detect_format_from_bytes` can recognize
+///
+/// DSD containers are only detected from files (`detect_format`).
+pub fn supported_mime_types() -> Vec<&'static str> {
+    vec![
+        "audio/mpeg",
+        "audio/mp3",
+        "audio/wav",
+        "audio/wave",
+        "audio/x-wav",
+        "audio/flac",
+        "audio/x-flac",
+        "audio/ogg",
+        "audio/vorbis",
+        "audio/mp4",
+        "audio/x-m4a",
+        "audio/aac",
+    ]
+}
+
+/// Detect the audio format of an in-memory buffer
+///
+/// Meant for sniffing the first few KB of a stream without a file name;
+/// `mime_hint` helps the probe when the content type is known. A buffer that
+/// ends before the container headers do yields an "insufficient data" error
+/// rather than a probe failure.
+pub fn detect_format_from_bytes(
+    data: &[u8],
+    mime_hint: Option<&str>,
+) -> Result<Option<AudioFormatInfo>> {
+    let insufficient = || {
+        crate::Error::Decoding(format!(
+            "Insufficient data to detect format ({} bytes)",
+            data.len()
+        ))
+    };
+    if data.is_empty() {
+        return Err(insufficient());
+    }
+
+    let source = ReadOnlySource::new(std::io::Cursor::new(data.to_vec()));
+    let media_source = MediaSourceStream::new(Box::new(source), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(mime) = mime_hint {
+        hint.mime_type(mime);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            media_source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| match e {
+            SymphoniaError::IoError(ref io) if io.kind() == std::io::ErrorKind::UnexpectedEof => {
+                insufficient()
+            }
+            e => crate::Error::UnsupportedFormat(format!("Failed to probe data: {}", e)),
+        })?;
 
-    let format_reader = probed.format;
+    format_info(probed.format.as_ref(), None).map(Some)
+}
 
-    // Find the default audio track
+/// Describe audio track `track_id`, or else the first audio track, of a
+/// probed container
+fn format_info(format_reader: &dyn FormatReader, track_id: Option<u32>) -> Result<AudioFormatInfo> {
+    // Find the requested or default audio track
     let track = format_reader
         .tracks()
         .iter()
-        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .find(|t| track_id.is_none_or(|id| t.id == id))
         .ok_or_else(|| crate::Error::Decoding("No audio tracks found".to_string()))?;
 
     // Extract format information
     let codec_params = &track.codec_params;
     let sample_rate = codec_params.sample_rate;
-    let channels = codec_params.channels.map(|ch| ch.count() as u16);
+    let channels = channel_count(codec_params).map(|count| count as u16);
     let duration = codec_params.n_frames;
     let codec_type = codec_params.codec;
 
@@ -724,7 +1870,7 @@ pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<Option<AudioFormatInfo>>
     }
     .to_string();
 
-    Ok(Some(AudioFormatInfo {
+    Ok(AudioFormatInfo {
         format_name,
         codec_type: format!("{:?}", codec_type),
         sample_rate,
@@ -746,11 +1892,38 @@ pub fn detect_format<P: AsRef<Path>>(path: P) -> Result<Option<AudioFormatInfo>>
                 | symphonia::core::codecs::CODEC_TYPE_PCM_F64LE
                 | symphonia::core::codecs::CODEC_TYPE_PCM_F64BE
         ),
-    }))
+    })
+}
+
+/// Describe a DSD file
+///
+/// Rate and duration refer to the decimated PCM stream the decoder produces;
+/// DSD sources always count as lossless high resolution.
+fn detect_dsd_format(path: &Path) -> Result<AudioFormatInfo> {
+    Ok(dsd_format_info(&dsd::DsdStream::open(path)?, path))
+}
+
+/// Describe a parsed DSD stream read from `path`
+fn dsd_format_info(stream: &dsd::DsdStream, path: &Path) -> AudioFormatInfo {
+    let is_dff = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dff"));
+    let container = if is_dff { "DSDIFF" } else { "DSF" };
+
+    AudioFormatInfo {
+        format_name: container.to_string(),
+        codec_type: format!("DSD{}", stream.rate_multiple()),
+        sample_rate: Some(stream.pcm_sample_rate()),
+        channels: Some(stream.channels),
+        duration: Some(stream.pcm_frames()),
+        bit_depth: Some(1),
+        is_lossless: true,
+    }
 }
 
 /// Information about detected audio format
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AudioFormatInfo {
     /// Human-readable format name (e.g., "MP3", "FLAC", "WAV")
     pub format_name: String,
@@ -786,6 +1959,59 @@ impl AudioFormatInfo {
             _ => false,
         }
     }
+
+    /// Short user-facing description, e.g. "24-bit/96kHz FLAC"
+    ///
+    /// Unknown depth or rate is left out (lossy formats read as
+    /// "44.1kHz MP3"). DSD sources are named by their DSD rate, e.g.
+    /// "DSD64 DSF".
+    pub fn human_summary(&self) -> String {
+        if self.bit_depth == Some(1) && self.codec_type.starts_with("DSD") {
+            return format!("{} {}", self.codec_type, self.format_name);
+        }
+        let quality = match (self.bit_depth, self.sample_rate) {
+            (Some(bits), Some(rate)) => format!("{}-bit/{} ", bits, format_khz(rate)),
+            (Some(bits), None) => format!("{}-bit ", bits),
+            (None, Some(rate)) => format!("{} ", format_khz(rate)),
+            (None, None) => String::new(),
+        };
+        format!("{}{}", quality, self.format_name)
+    }
+}
+
+/// Formats as "FLAC 96000 Hz / 24-bit / 2ch / lossless / hi-res", leaving
+/// out whatever is unknown
+impl std::fmt::Display for AudioFormatInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut details = Vec::new();
+        if let Some(rate) = self.sample_rate {
+            details.push(format!("{} Hz", rate));
+        }
+        if let Some(bits) = self.bit_depth {
+            details.push(format!("{}-bit", bits));
+        }
+        if let Some(channels) = self.channels {
+            details.push(format!("{}ch", channels));
+        }
+        if self.is_lossless {
+            details.push("lossless".to_string());
+        }
+        if self.is_high_resolution() {
+            details.push("hi-res".to_string());
+        }
+
+        f.write_str(&self.format_name)?;
+        if !details.is_empty() {
+            write!(f, " {}", details.join(" / "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Sample rate in kHz without a trailing ".0", e.g. "44.1kHz", "96kHz"
+fn format_khz(rate: u32) -> String {
+    let khz = format!("{:.1}", rate as f64 / 1000.0);
+    format!("{}kHz", khz.strip_suffix(".0").unwrap_or(&khz))
 }
 
 /// Detect format from file extension only (fast)
@@ -801,6 +2027,9 @@ pub fn detect_format_from_extension<P: AsRef<Path>>(path: P) -> Option<&'static
                 "ogg" => Some("OGG Vorbis"),
                 "m4a" => Some("M4A/AAC"),
                 "aac" => Some("AAC"),
+                "mka" => Some("Matroska"),
+                "dsf" => Some("DSF"),
+                "dff" => Some("DSDIFF"),
                 _ => None,
             }
         } else {
@@ -874,9 +2103,23 @@ pub fn get_audio_metadata<P: AsRef<Path>>(path: P) -> Result<AudioFormatInfo> {
         .ok_or_else(|| crate::Error::Decoding("Could not extract audio metadata".to_string()))
 }
 
+/// Read the chapter markers of an audio file, ordered by start
+///
+/// Returns an empty list for files without chapters.
+pub fn read_chapters<P: AsRef<Path>>(path: P) -> Result<Vec<Chapter>> {
+    Ok(AudioDecoder::new(path)?.chapters())
+}
+
+/// Read the textual tags of an audio file without decoding it
+pub fn read_tags<P: AsRef<Path>>(path: P) -> Result<TrackTags> {
+    Ok(AudioDecoder::new(path)?.metadata())
+}
+
 /// Get supported file extensions
 pub fn supported_extensions() -> Vec<&'static str> {
-    vec!["mp3", "wav", "flac", "ogg", "m4a", "aac"]
+    vec![
+        "mp3", "wav", "flac", "ogg", "m4a", "aac", "mka", "dsf", "dff",
+    ]
 }
 
 /// Create a stream reader with default configuration
@@ -916,6 +2159,15 @@ pub fn create_ring_buffer_stream_reader_with_config<P: AsRef<Path>>(
     AudioStreamReaderWithRingBuffer::new(path, config)
 }
 
+/// Create a stream reader with custom ring buffer and stream configuration
+pub fn create_ring_buffer_stream_reader_with_configs<P: AsRef<Path>>(
+    path: P,
+    ring_buffer: RingBufferConfig,
+    config: StreamConfig,
+) -> Result<(AudioStreamReaderWithRingBuffer, RingBufferConsumer)> {
+    AudioStreamReaderWithRingBuffer::with_ring_buffer_config(path, Some(ring_buffer), config)
+}
+
 /// Create a looping stream reader with ring buffer output
 pub fn create_looping_ring_buffer_stream_reader<P: AsRef<Path>>(
     path: P,
@@ -930,6 +2182,7 @@ pub fn create_looping_ring_buffer_stream_reader<P: AsRef<Path>>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::{write_flac_mka, write_verbatim_flac, FLAC_BLOCK_FRAMES};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -1054,6 +2307,124 @@ mod tests {
         assert!(hires_info.is_lossless);
     }
 
+    /// Write MPEG-1 Layer III frames of silence at 44.1kHz, one per
+    /// bitrate index in `bitrates` (e.g. 0x9 for 128 kbps), with no
+    /// Xing header
+    fn write_mpeg_frames(path: &Path, bitrates: &[u8]) {
+        const KBPS: [usize; 15] = [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ];
+        let mut data = Vec::new();
+        for &index in bitrates {
+            let frame_len = 144 * KBPS[index as usize] * 1000 / 44100;
+            data.extend_from_slice(&[0xFF, 0xFB, index << 4, 0x00]);
+            data.resize(data.len() + frame_len - 4, 0);
+        }
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_duration_estimate_and_exact_scan() {
+        const MPEG_FRAME: u64 = 1152;
+        let dir = tempfile::tempdir().unwrap();
+
+        // Constant bitrate, no Xing header: the length is a bitrate guess
+        let cbr = dir.path().join("cbr.mp3");
+        write_mpeg_frames(&cbr, &[0x9; 200]);
+        let mut decoder = AudioDecoder::new(&cbr).unwrap();
+        let estimate = decoder.duration().unwrap();
+        assert!(estimate.abs_diff(200 * MPEG_FRAME) <= 2 * MPEG_FRAME);
+        assert_eq!(
+            decoder.duration_accuracy(),
+            Some(DurationAccuracy::Estimated)
+        );
+        assert_eq!(decoder.compute_exact_duration().unwrap(), 200 * MPEG_FRAME);
+        assert_eq!(decoder.duration(), Some(200 * MPEG_FRAME));
+        assert_eq!(decoder.duration_accuracy(), Some(DurationAccuracy::Exact));
+        // Decoding carries on from where it was
+        let packet = decoder.decode_next().unwrap().unwrap();
+        assert_eq!(packet.timestamp_samples, 0);
+
+        // Variable bitrate throws the guess off, but not the scan
+        let vbr = dir.path().join("vbr.mp3");
+        let mut bitrates = vec![0x5; 20];
+        bitrates.extend([0xB; 180]);
+        write_mpeg_frames(&vbr, &bitrates);
+        let mut decoder = AudioDecoder::new(&vbr).unwrap();
+        assert_ne!(decoder.duration(), Some(200 * MPEG_FRAME));
+        assert_eq!(decoder.compute_exact_duration().unwrap(), 200 * MPEG_FRAME);
+
+        // Too short for the demuxer to guess: the fallback counts it
+        let short = dir.path().join("short.mp3");
+        write_mpeg_frames(&short, &[0x9; 10]);
+        let decoder = AudioDecoder::new(&short).unwrap();
+        assert_eq!(decoder.duration(), Some(10 * MPEG_FRAME));
+        assert_eq!(decoder.duration_accuracy(), Some(DurationAccuracy::Exact));
+    }
+
+    #[test]
+    fn test_audio_format_info_display() {
+        let hires = AudioFormatInfo {
+            format_name: "FLAC".to_string(),
+            codec_type: "FLAC".to_string(),
+            sample_rate: Some(96000),
+            channels: Some(2),
+            duration: None,
+            bit_depth: Some(24),
+            is_lossless: true,
+        };
+        assert_eq!(
+            hires.to_string(),
+            "FLAC 96000 Hz / 24-bit / 2ch / lossless / hi-res"
+        );
+        assert_eq!(hires.human_summary(), "24-bit/96kHz FLAC");
+
+        // Lossy, with no bit depth
+        let mp3 = AudioFormatInfo {
+            format_name: "MP3".to_string(),
+            codec_type: "MP3".to_string(),
+            sample_rate: Some(44100),
+            channels: Some(2),
+            duration: None,
+            bit_depth: None,
+            is_lossless: false,
+        };
+        assert_eq!(mp3.to_string(), "MP3 44100 Hz / 2ch");
+        assert_eq!(mp3.human_summary(), "44.1kHz MP3");
+
+        // Rate and channels unknown
+        let partial = AudioFormatInfo {
+            format_name: "WAV".to_string(),
+            sample_rate: None,
+            channels: None,
+            bit_depth: Some(16),
+            is_lossless: true,
+            ..mp3.clone()
+        };
+        assert_eq!(partial.to_string(), "WAV 16-bit / lossless");
+        assert_eq!(partial.human_summary(), "16-bit WAV");
+
+        let unknown = AudioFormatInfo {
+            bit_depth: None,
+            is_lossless: false,
+            ..partial
+        };
+        assert_eq!(unknown.to_string(), "WAV");
+        assert_eq!(unknown.human_summary(), "WAV");
+
+        let dsd = AudioFormatInfo {
+            format_name: "DSF".to_string(),
+            codec_type: "DSD64".to_string(),
+            sample_rate: Some(352800),
+            channels: Some(2),
+            duration: None,
+            bit_depth: Some(1),
+            is_lossless: true,
+        };
+        assert_eq!(dsd.human_summary(), "DSD64 DSF");
+        assert_eq!(format_khz(88200), "88.2kHz");
+    }
+
     #[test]
     fn test_stream_config() {
         let default_config = StreamConfig::default();
@@ -1103,6 +2474,57 @@ mod tests {
         assert!(result.is_err()); // File doesn't exist, but should accept looping config
     }
 
+    #[test]
+    fn test_stream_reader_reports_decoder_panic() {
+        struct PanickingSource {
+            packets: usize,
+        }
+
+        impl PacketSource for PanickingSource {
+            fn decode_next(&mut self) -> Result<Option<DecodedPacket>> {
+                if self.packets == 0 {
+                    // As a codec hitting a malformed frame would
+                    panic!("attempt to add with overflow");
+                }
+                self.packets -= 1;
+                Ok(Some(DecodedPacket {
+                    samples: vec![0.0; 2],
+                    frames: 1,
+                    timestamp_samples: 0,
+                    format: AudioFormat::new(44100, 1, crate::audio::format::SampleFormat::I16),
+                }))
+            }
+
+            fn reset(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.flac");
+        write_verbatim_flac(&path, 1);
+        let decoder = Arc::new(Mutex::new(AudioDecoder::new(&path).unwrap()));
+        let source = Arc::new(Mutex::new(PanickingSource { packets: 1 }));
+        let mut reader = AudioStreamReader::spawn(decoder, source, StreamConfig::default());
+
+        assert!(reader.next_packet_blocking().unwrap().is_some());
+        match reader.next_packet_blocking() {
+            Err(crate::Error::Decoding(message)) => {
+                assert!(message.contains("panicked"), "{}", message);
+                assert!(message.contains("overflow"), "{}", message);
+            }
+            other => panic!(
+                "expected a decoding error, got {:?}",
+                other.map(|p| p.is_some())
+            ),
+        }
+
+        // The thread exits after reporting it and the reader goes inactive
+        reader.decode_thread.take().unwrap().join().unwrap();
+        assert!(!reader.is_active());
+        assert!(reader.format().is_ok());
+    }
+
     #[test]
     fn test_stream_reader_stop() {
         // Create a mock stream reader (this will fail due to invalid file, but we can test the structure)
@@ -1327,6 +2749,79 @@ mod tests {
         assert!(result.is_err()); // File doesn't exist, but config should be accepted
     }
 
+    #[test]
+    fn test_stream_config_validation() {
+        assert!(StreamConfig::default().validate().is_ok());
+
+        let too_small = StreamConfig {
+            buffer_size: 16,
+            ..StreamConfig::default()
+        };
+        assert!(matches!(
+            too_small.validate(),
+            Err(crate::Error::InvalidParameter(_))
+        ));
+
+        let no_prefetch = StreamConfig {
+            prefetch_size: 0,
+            ..StreamConfig::default()
+        };
+        assert!(no_prefetch.validate().is_err());
+    }
+
+    #[test]
+    fn test_ring_buffer_stream_reader_configs_propagate() {
+        use crate::audio::format::{AudioFormat, SampleFormat};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("stream.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..48000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config = StreamConfig {
+            buffer_size: 256,
+            loop_playback: false,
+            prefetch_size: 12,
+        };
+        // The ring buffer format is replaced by the file's format
+        let ring = RingBufferConfig::low_latency(AudioFormat::new(8000, 1, SampleFormat::I16));
+
+        let (mut reader, consumer) =
+            create_ring_buffer_stream_reader_with_configs(&path, ring, config).unwrap();
+        assert_eq!(reader.config().buffer_size, 256);
+        assert_eq!(reader.config().prefetch_size, 12);
+        assert_eq!(reader.ring_buffer_config().buffer_duration_seconds, 0.5);
+        assert_eq!(reader.ring_buffer_config().format.sample_rate, 48000);
+        assert_eq!(consumer.capacity(), 48000);
+        reader.stop();
+
+        // Defaults are clamped to the minimum ring buffer duration
+        let (mut reader, _consumer) = create_ring_buffer_stream_reader(&path).unwrap();
+        assert_eq!(
+            reader.ring_buffer_config().buffer_duration_seconds,
+            MIN_BUFFER_DURATION_SECONDS
+        );
+        reader.stop();
+
+        let invalid = StreamConfig {
+            buffer_size: 0,
+            ..StreamConfig::default()
+        };
+        assert!(matches!(
+            create_ring_buffer_stream_reader_with_config(&path, invalid),
+            Err(crate::Error::InvalidParameter(_))
+        ));
+    }
+
     #[test]
     fn test_looping_ring_buffer_stream_reader() {
         let result = create_looping_ring_buffer_stream_reader("nonexistent.mp3");
@@ -1521,4 +3016,585 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_stream_format_rejects_zero_channels_and_rate() {
+        use symphonia::core::audio::Channels;
+
+        let mut params = CodecParameters::new();
+        params
+            .with_sample_rate(44100)
+            .with_channels(Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let format = stream_format(&params).unwrap();
+        assert_eq!((format.sample_rate, format.channels), (44100, 2));
+
+        let mut zero_channels = params.clone();
+        zero_channels.with_channels(Channels::empty());
+        assert!(matches!(
+            stream_format(&zero_channels),
+            Err(crate::Error::Decoding(msg)) if msg.contains("0 channels")
+        ));
+
+        let mut zero_rate = params.clone();
+        zero_rate.with_sample_rate(0);
+        assert!(matches!(
+            stream_format(&zero_rate),
+            Err(crate::Error::Decoding(msg)) if msg.contains("0 Hz")
+        ));
+
+        let missing = CodecParameters::new();
+        assert!(matches!(
+            stream_format(&missing),
+            Err(crate::Error::Decoding(_))
+        ));
+    }
+
+    /// Format reader emitting many packets of another track before one
+    /// stereo 16-bit packet of track 0
+    struct InterleavedTrackReader {
+        foreign_packets: usize,
+        audio_sent: bool,
+        metadata: symphonia::core::meta::MetadataLog,
+    }
+
+    impl FormatReader for InterleavedTrackReader {
+        fn try_new(
+            _source: MediaSourceStream,
+            _options: &FormatOptions,
+        ) -> symphonia::core::errors::Result<Self> {
+            unreachable!("built directly by the test, never probed")
+        }
+
+        fn cues(&self) -> &[symphonia::core::formats::Cue] {
+            &[]
+        }
+
+        fn metadata(&mut self) -> symphonia::core::meta::Metadata<'_> {
+            self.metadata.metadata()
+        }
+
+        fn seek(
+            &mut self,
+            _mode: symphonia::core::formats::SeekMode,
+            _to: symphonia::core::formats::SeekTo,
+        ) -> symphonia::core::errors::Result<symphonia::core::formats::SeekedTo> {
+            Err(SymphoniaError::SeekError(
+                symphonia::core::errors::SeekErrorKind::Unseekable,
+            ))
+        }
+
+        fn tracks(&self) -> &[symphonia::core::formats::Track] {
+            &[]
+        }
+
+        fn next_packet(
+            &mut self,
+        ) -> symphonia::core::errors::Result<symphonia::core::formats::Packet> {
+            use symphonia::core::formats::Packet;
+
+            if self.foreign_packets > 0 {
+                self.foreign_packets -= 1;
+                return Ok(Packet::new_from_slice(7, 0, 0, &[]));
+            }
+            if !self.audio_sent {
+                self.audio_sent = true;
+                let data: Vec<u8> = [16384i16, -16384, 8192, -8192]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect();
+                return Ok(Packet::new_from_slice(0, 0, 2, &data));
+            }
+            Err(SymphoniaError::IoError(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "end of stream",
+            )))
+        }
+
+        fn into_inner(self: Box<Self>) -> MediaSourceStream {
+            unreachable!("the decoder never takes back its media source")
+        }
+    }
+
+    #[test]
+    fn test_decode_next_skips_foreign_packets_iteratively() {
+        use symphonia::core::audio::Channels;
+        use symphonia::core::codecs::CODEC_TYPE_PCM_S16LE;
+
+        let mut params = CodecParameters::new();
+        params
+            .for_codec(CODEC_TYPE_PCM_S16LE)
+            .with_sample_rate(44100)
+            .with_channels(Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+            .with_bits_per_coded_sample(16)
+            .with_bits_per_sample(16)
+            .with_max_frames_per_packet(1024);
+        let decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .unwrap();
+
+        // Far more skipped packets than stack frames a recursive skip could survive
+        let mut audio_decoder = AudioDecoder {
+            source: DecoderSource::Symphonia {
+                format_reader: Box::new(InterleavedTrackReader {
+                    foreign_packets: 1_000_000,
+                    audio_sent: false,
+                    metadata: Default::default(),
+                }),
+                decoder,
+                track_id: 0,
+            },
+            format: stream_format(&params).unwrap(),
+            duration: None,
+            duration_accuracy: DurationAccuracy::Exact,
+            path: PathBuf::from("interleaved.wav"),
+            position: 0,
+            frames: FrameAccounting::default(),
+            tags: TrackTags::default(),
+            replay_gain: ReplayGainTags::default(),
+            seekable: true,
+        };
+
+        let packet = audio_decoder.decode_next().unwrap().unwrap();
+        assert_eq!(packet.frames, 2);
+        assert_eq!(packet.samples.len(), 4);
+        assert!((packet.samples[0] - 0.5).abs() < 1e-3);
+        assert!((packet.samples[1] + 0.5).abs() < 1e-3);
+
+        assert!(audio_decoder.decode_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_detect_format_from_bytes() {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        {
+            let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+            for _ in 0..48000 {
+                writer.write_sample(0i32).unwrap();
+            }
+            writer.finalize().unwrap();
+        }
+        let bytes = cursor.into_inner();
+
+        // The first few KB are enough to identify the stream
+        let info = detect_format_from_bytes(&bytes[..4096], Some("audio/wav"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.format_name, "WAV/PCM");
+        assert_eq!(info.sample_rate, Some(48000));
+        assert_eq!(info.channels, Some(2));
+        assert_eq!(info.bit_depth, Some(24));
+        assert!(info.is_lossless);
+        assert!(detect_format_from_bytes(&bytes[..4096], None).is_ok());
+
+        // A cut-off header is reported as missing data
+        for len in [0, 20] {
+            match detect_format_from_bytes(&bytes[..len], None) {
+                Err(crate::Error::Decoding(message)) => {
+                    assert!(message.contains("Insufficient data"), "{}", message)
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        assert!(matches!(
+            detect_format_from_bytes(&[0x55; 4096], None),
+            Err(crate::Error::UnsupportedFormat(_))
+        ));
+        assert!(supported_mime_types().contains(&"audio/flac"));
+    }
+
+    #[test]
+    fn test_packet_timestamps_are_contiguous() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cbr.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..44100 * 2 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut decoder = AudioDecoder::new(&path).unwrap();
+        let mut expected = 0;
+        let mut packets = 0;
+        while let Some(packet) = decoder.decode_next().unwrap() {
+            assert_eq!(packet.timestamp_samples, expected);
+            expected += packet.frames as u64;
+            packets += 1;
+        }
+        assert!(packets > 1);
+        assert_eq!(expected, 44100);
+
+        // Timestamps restart from the seek target
+        decoder.seek(22050).unwrap();
+        let packet = decoder.decode_next().unwrap().unwrap();
+        assert!(packet.timestamp_samples <= 22050);
+        assert!(packet.timestamp_samples + packet.frames as u64 > 22050);
+    }
+
+    #[test]
+    fn test_format_info_matches_detect_format() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("hires.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 96000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..9600 * 2 {
+            writer.write_sample(0i32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut decoder = AudioDecoder::new(&path).unwrap();
+        let detected = detect_format(&path).unwrap().unwrap();
+        // Still available once the audio has been decoded
+        decoder.decode_all().unwrap();
+        let info = decoder.format_info().unwrap();
+        assert_eq!(info.format_name, detected.format_name);
+        assert_eq!(info.codec_type, detected.codec_type);
+        assert_eq!(info.sample_rate, Some(96000));
+        assert_eq!(info.channels, Some(2));
+        assert_eq!(info.duration, detected.duration);
+        assert_eq!(info.bit_depth, Some(24));
+        assert!(info.is_lossless);
+    }
+
+    fn truncate_file(path: &std::path::Path, len: usize) {
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::write(path, &bytes[..len]).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_flac_ends_stream() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cut.flac");
+        let offsets = write_verbatim_flac(&path, 4);
+
+        let complete = AudioDecoder::new(&path).unwrap().decode_all().unwrap();
+        assert_eq!(complete.frames(), 4 * 4096);
+
+        // Cut the third frame in half
+        truncate_file(&path, offsets[2] + 4096);
+        let mut decoder = AudioDecoder::new(&path).unwrap();
+        let buffer = decoder.decode_all().unwrap();
+        assert!(buffer.frames() >= 2 * 4096 && buffer.frames() < 4 * 4096);
+        assert_eq!(buffer.data()[..2 * 4096], complete.data()[..2 * 4096]);
+        assert!(decoder.decode_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_truncated_wav_ends_streams() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("partial.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..44100 * 2 {
+            writer.write_sample((i % 2000) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        // Half the data and a torn frame, as from an interrupted download
+        truncate_file(&path, 44 + 44100 * 2 + 3);
+
+        let buffer = AudioDecoder::new(&path).unwrap().decode_all().unwrap();
+        assert!(buffer.frames() > 0 && buffer.frames() <= 22050);
+
+        let mut reader = AudioStreamReader::new(&path, StreamConfig::default()).unwrap();
+        let mut frames = 0;
+        while let Some(packet) = reader.next_packet_blocking().unwrap() {
+            frames += packet.frames;
+        }
+        assert_eq!(frames, buffer.frames());
+
+        // A looping ring buffer reader keeps going and never reports an error
+        let config = StreamConfig {
+            loop_playback: true,
+            ..StreamConfig::default()
+        };
+        let (mut reader, consumer) =
+            AudioStreamReaderWithRingBuffer::new(&path, config.clone()).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut drained = 0;
+        while drained < buffer.frames() * 4 && std::time::Instant::now() < deadline {
+            drained += consumer.skip(usize::MAX) / 2;
+        }
+        assert!(drained >= buffer.frames() * 4);
+        assert!(reader.take_error().is_none());
+        reader.stop();
+        assert!(reader.is_finished());
+
+        // A stream with no audio at all can't loop
+        truncate_file(&path, 44);
+        let (reader, _consumer) = AudioStreamReaderWithRingBuffer::new(&path, config).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !reader.is_finished() && std::time::Instant::now() < deadline {
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(reader.is_finished());
+        assert!(reader.take_error().is_none());
+    }
+
+    #[test]
+    fn test_chapters_sorted_in_samples() {
+        use symphonia::core::formats::Cue;
+        use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+        let cue = |index: u32, start_ts: u64, title: Option<&str>| Cue {
+            index,
+            start_ts,
+            tags: title
+                .map(|t| {
+                    vec![Tag::new(
+                        Some(StandardTagKey::TrackTitle),
+                        "TITLE",
+                        Value::from(t),
+                    )]
+                })
+                .unwrap_or_default(),
+            points: Vec::new(),
+        };
+        // Millisecond timestamps, declared out of order with a duplicate start
+        let cues = vec![
+            cue(3, 90_500, Some("Three")),
+            cue(1, 0, Some("One")),
+            cue(2, 61_000, None),
+            cue(4, 61_000, Some("Duplicate")),
+        ];
+
+        let chapters = chapters_from_cues(&cues, Some(TimeBase::new(1, 1000)), 44100);
+        let starts: Vec<u64> = chapters.iter().map(|c| c.start_sample).collect();
+        assert_eq!(starts, vec![0, 61 * 44100, 3_991_050]);
+        assert_eq!(chapters[0].title.as_deref(), Some("One"));
+        assert_eq!(chapters[1].title, None);
+        assert_eq!(chapters[2].title.as_deref(), Some("Three"));
+        assert!((chapters[2].start_seconds() - 90.5).abs() < 1e-9);
+
+        // Timestamps already in samples pass through unchanged
+        let chapters = chapters_from_cues(&cues, Some(TimeBase::new(1, 48000)), 48000);
+        assert_eq!(chapters[2].start_sample, 90_500);
+
+        // Files without chapters report none
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("plain.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..441 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert!(read_chapters(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_leading_number() {
+        assert_eq!(leading_number("3/12"), Some(3));
+        assert_eq!(leading_number("2001-05-03"), Some(2001));
+        assert_eq!(leading_number("unknown"), None);
+    }
+
+    #[test]
+    fn test_read_tags_from_vorbis_comments() {
+        use crate::test_util::{
+            vorbis_comment_block, write_verbatim_flac_with_metadata, FLAC_VORBIS_COMMENT,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged.flac");
+        let comments = vorbis_comment_block(&[
+            "TITLE=Blue in Green",
+            "ARTIST=Miles Davis",
+            "ALBUM=Kind of Blue",
+            "ALBUMARTIST=Miles Davis Sextet",
+            "TRACKNUMBER=3/5",
+            "DISCNUMBER=1",
+            "DATE=1959-08-17",
+            "GENRE=Jazz",
+            "COMPOSER=Bill Evans",
+        ]);
+        write_verbatim_flac_with_metadata(&path, 1, &[(FLAC_VORBIS_COMMENT, comments)]);
+
+        let expected = TrackTags {
+            title: Some("Blue in Green".to_string()),
+            artist: Some("Miles Davis".to_string()),
+            album: Some("Kind of Blue".to_string()),
+            album_artist: Some("Miles Davis Sextet".to_string()),
+            track_number: Some(3),
+            disc_number: Some(1),
+            year: Some(1959),
+            genre: Some("Jazz".to_string()),
+            composer: Some("Bill Evans".to_string()),
+        };
+        assert_eq!(read_tags(&path).unwrap(), expected);
+        assert_eq!(AudioDecoder::new(&path).unwrap().metadata(), expected);
+
+        // Untagged files have no fields set
+        let plain = dir.path().join("plain.flac");
+        write_verbatim_flac(&plain, 1);
+        assert_eq!(read_tags(&plain).unwrap(), TrackTags::default());
+    }
+
+    #[test]
+    fn test_unseekable_source_rejects_seek() {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for i in 0..4410 {
+            writer.write_sample(i as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // A pipe-like source: readable, reporting no seek support
+        let source = ReadOnlySource::new(std::io::Cursor::new(bytes.into_inner()));
+        let mut decoder = AudioDecoder::from_source(Box::new(source), Some("wav")).unwrap();
+        assert!(!decoder.is_seekable());
+        assert!(matches!(
+            decoder.seek(100),
+            Err(crate::Error::SeekUnsupported(_))
+        ));
+        let frames: usize = decoder.packets().map(|p| p.unwrap().frames).sum();
+        assert_eq!(frames, 4410);
+    }
+
+    #[test]
+    fn test_packets_iterates_to_end_of_stream() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("packets.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let frames = 12_345usize;
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..frames * 2 {
+            writer.write_sample(100i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut decoder = AudioDecoder::new(&path).unwrap();
+        let mut total = 0;
+        for packet in decoder.packets() {
+            let packet = packet.unwrap();
+            assert_eq!(packet.samples.len(), packet.frames * 2);
+            total += packet.frames;
+        }
+        assert_eq!(total, frames);
+        assert!(decoder.packets().next().is_none());
+    }
+
+    #[test]
+    fn test_decoded_frame_count_matches_declared_length() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("count.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        // Not a multiple of the packet size, so the last packet is partial
+        let frames = 10_007u64;
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..frames * 2 {
+            writer.write_sample((i % 1000) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut decoder = AudioDecoder::new(&path).unwrap();
+        assert_eq!(decoder.duration(), Some(frames));
+        let mut packet_frames = 0u64;
+        while let Some(packet) = decoder.decode_next().unwrap() {
+            packet_frames += packet.frames as u64;
+            // An incomplete stream doesn't match the declared length yet
+            if packet_frames < frames {
+                assert!(decoder.verify_frame_count().is_err());
+            }
+        }
+        assert_eq!(decoder.decoded_frame_count(), frames);
+        assert_eq!(packet_frames, frames);
+        decoder.verify_frame_count().unwrap();
+
+        // After a seek the count restarts and covers the rest of the stream
+        decoder.seek(4410).unwrap();
+        assert_eq!(decoder.decoded_frame_count(), 0);
+        let rest = decoder.decode_all().unwrap();
+        assert_eq!(decoder.decoded_frame_count(), rest.frames() as u64);
+        decoder.verify_frame_count().unwrap();
+
+        // Streams without a declared length aren't checked
+        decoder.duration = None;
+        decoder.frames.decoded = 1;
+        decoder.verify_frame_count().unwrap();
+    }
+
+    #[test]
+    fn test_multi_track_container_track_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tracks.mka");
+        write_flac_mka(&path, &[("eng", 2), ("fre", 1)]);
+
+        let tracks = AudioDecoder::list_audio_tracks(&path).unwrap();
+        assert_eq!(
+            tracks,
+            vec![
+                AudioTrackInfo {
+                    id: 1,
+                    codec: "flac".to_string(),
+                    channels: Some(1),
+                    sample_rate: Some(44100),
+                    language: Some("eng".to_string()),
+                },
+                AudioTrackInfo {
+                    id: 2,
+                    codec: "flac".to_string(),
+                    channels: Some(1),
+                    sample_rate: Some(44100),
+                    language: Some("fre".to_string()),
+                },
+            ]
+        );
+
+        // The first track stays the default
+        let main = AudioDecoder::new(&path).unwrap().decode_all().unwrap();
+        assert_eq!(main.frames(), 2 * FLAC_BLOCK_FRAMES);
+
+        let mut commentary = AudioDecoder::new_with_track(&path, 2).unwrap();
+        let buffer = commentary.decode_all().unwrap();
+        assert_eq!(buffer.frames(), FLAC_BLOCK_FRAMES);
+        assert!((buffer.data()[999] - 999.0 / 32768.0).abs() < 1e-9);
+
+        assert!(matches!(
+            AudioDecoder::new_with_track(&path, 3),
+            Err(crate::Error::InvalidParameter(_))
+        ));
+    }
 }
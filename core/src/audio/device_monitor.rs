@@ -0,0 +1,189 @@
+//! Output device change monitoring
+//!
+//! Polls the system default output device on a background thread and reports
+//! when it changes (e.g. headphones plugged in). The polling thread itself,
+//! `PollThread`, also drives other periodic checks such as prefetching.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Default interval between default-device checks
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Background thread calling a poll function at a fixed interval
+///
+/// The first poll runs as soon as the thread starts. The thread is stopped
+/// and joined when dropped.
+pub struct PollThread {
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PollThread {
+    /// Start calling `poll` every `interval`
+    pub fn start<F>(interval: Duration, mut poll: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop_flag = stop_flag.clone();
+
+        let thread = std::thread::spawn(move || {
+            while !thread_stop_flag.load(Ordering::Acquire) {
+                poll();
+                Self::sleep_unless_stopped(interval, &thread_stop_flag);
+            }
+        });
+
+        Self {
+            stop_flag,
+            thread: Some(thread),
+        }
+    }
+
+    /// Check if the thread is still running
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stop polling and wait for the thread to exit
+    pub fn stop(&mut self) {
+        self.stop_flag.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Sleep in short slices so a stop request is honored promptly
+    fn sleep_unless_stopped(interval: Duration, stop_flag: &AtomicBool) {
+        let slice = Duration::from_millis(10).min(interval);
+        let mut slept = Duration::ZERO;
+        while slept < interval && !stop_flag.load(Ordering::Acquire) {
+            std::thread::sleep(slice);
+            slept += slice;
+        }
+    }
+}
+
+impl Drop for PollThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Background monitor for default output device changes
+///
+/// The monitor thread is stopped and joined when the monitor is dropped.
+pub struct DeviceMonitor {
+    thread: PollThread,
+}
+
+impl DeviceMonitor {
+    /// Start monitoring
+    ///
+    /// # Arguments
+    /// * `interval` - Time between checks
+    /// * `query` - Returns the name of the current default device
+    /// * `on_change` - Called with the new device name whenever it changes
+    pub fn start<Q, C>(interval: Duration, query: Q, on_change: C) -> Self
+    where
+        Q: Fn() -> Option<String> + Send + 'static,
+        C: Fn(String) + Send + 'static,
+    {
+        // The first poll only records the device in use
+        let mut current = None;
+        let thread = PollThread::start(interval, move || {
+            let latest = query();
+            if let Some(previous) = current.replace(latest.clone()) {
+                if latest != previous {
+                    if let Some(name) = latest {
+                        on_change(name);
+                    }
+                }
+            }
+        });
+
+        Self { thread }
+    }
+
+    /// Check if the monitor thread is still running
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Stop the monitor and wait for the thread to exit
+    pub fn stop(&mut self) {
+        self.thread.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::mpsc;
+
+    /// Upper bound for the monitor thread to get around to a poll
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_reports_default_device_change() {
+        let current = Arc::new(Mutex::new(Some("Speakers".to_string())));
+        let (polled, polls) = mpsc::channel();
+        let (changed, changes) = mpsc::channel();
+
+        let query_current = current.clone();
+        let monitor = DeviceMonitor::start(
+            Duration::from_millis(1),
+            move || {
+                let name = query_current.lock().clone();
+                let _ = polled.send(());
+                name
+            },
+            move |name| {
+                let _ = changed.send(name);
+            },
+        );
+
+        // Polls of an unchanged device report nothing
+        for _ in 0..3 {
+            polls.recv_timeout(TIMEOUT).unwrap();
+        }
+        assert!(changes.try_recv().is_err());
+
+        *current.lock() = Some("Headphones".to_string());
+        assert_eq!(changes.recv_timeout(TIMEOUT).unwrap(), "Headphones");
+        drop(monitor);
+    }
+
+    #[test]
+    fn test_stop_joins_thread() {
+        let mut monitor = DeviceMonitor::start(Duration::from_secs(60), || None, |_| {});
+        assert!(monitor.is_running());
+
+        let started = std::time::Instant::now();
+        monitor.stop();
+
+        assert!(!monitor.is_running());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_poll_thread_polls_until_dropped() {
+        let (polled, polls) = mpsc::channel();
+        let thread = PollThread::start(Duration::from_millis(1), move || {
+            let _ = polled.send(());
+        });
+
+        polls.recv_timeout(TIMEOUT).unwrap();
+        polls.recv_timeout(TIMEOUT).unwrap();
+        assert!(thread.is_running());
+        drop(thread);
+
+        // The joined thread dropped its sender: no poll comes after this
+        let _ = polls.try_iter().count();
+        assert_eq!(polls.recv(), Err(mpsc::RecvError));
+    }
+}
@@ -0,0 +1,834 @@
+//! DSD (Direct Stream Digital) support
+//!
+//! Parses DSF and DSDIFF files and converts their 1-bit streams to PCM with a
+//! low-pass decimation filter, so DSD sources play through the regular PCM
+//! pipeline.
+
+use crate::audio::format::{AudioFormat, SampleFormat};
+use crate::{Error, Result};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// DSD64 bit rate (64 x 44.1 kHz)
+pub const DSD64_RATE: u32 = 2_822_400;
+
+/// DSD128 bit rate (128 x 44.1 kHz)
+pub const DSD128_RATE: u32 = 5_644_800;
+
+/// Ratio between the DSD bit rate and the PCM output rate
+///
+/// DSD64 becomes 88.2 kHz PCM and DSD128 becomes 176.4 kHz PCM.
+pub const DSD_DECIMATION: u32 = 32;
+
+/// Length of the decimation filter in bits (a multiple of 8)
+const FILTER_TAPS: usize = 2048;
+
+/// Filter cutoff as a fraction of the PCM output rate
+const FILTER_CUTOFF: f64 = 0.4;
+
+/// PCM frames produced per `DsdDecoder::decode_next` call
+const DECODE_CHUNK_FRAMES: usize = 4096;
+
+/// A DSD stream: its header and where its channel data comes from
+///
+/// Streams opened from a file keep only the header in memory and read the
+/// channel data on demand.
+pub struct DsdStream {
+    /// DSD bit rate per channel in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Number of 1-bit samples per channel
+    pub sample_count: u64,
+    /// Channel data
+    source: DsdSource,
+}
+
+/// Readable, seekable DSF or DSDIFF file contents
+trait DsdReader: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> DsdReader for T {}
+
+/// Where a stream's channel data is read from
+enum DsdSource {
+    /// Packed MSB-first bytes per channel
+    Memory(Vec<Vec<u8>>),
+    /// Data chunk of a DSF or DSDIFF file
+    File {
+        reader: Box<dyn DsdReader>,
+        layout: DsdLayout,
+        /// File offset of the first data byte
+        data_offset: u64,
+        /// Bytes of channel data available per channel
+        channel_bytes: u64,
+    },
+}
+
+/// How channel data is interleaved in a file's data chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DsdLayout {
+    /// DSF: blocks of `block_size` bytes per channel in turn
+    Blocks { block_size: u64, lsb_first: bool },
+    /// DSDIFF: one MSB-first byte per channel in turn
+    Bytes,
+}
+
+/// Header fields of a DSD file
+struct DsdHeader {
+    sample_rate: u32,
+    channels: u16,
+    /// Sample count the header declares, if it does
+    sample_count: Option<u64>,
+    layout: DsdLayout,
+    data_offset: u64,
+    /// Length of the data chunk, clipped to the file
+    data_len: u64,
+}
+
+impl DsdHeader {
+    /// Bytes every channel has in the data chunk
+    fn channel_bytes(&self) -> u64 {
+        let channels = self.channels as u64;
+        match self.layout {
+            DsdLayout::Bytes => self.data_len / channels,
+            DsdLayout::Blocks { block_size, .. } => {
+                let group = block_size * channels;
+                let partial = (self.data_len % group)
+                    .saturating_sub((channels - 1) * block_size)
+                    .min(block_size);
+                self.data_len / group * block_size + partial
+            }
+        }
+    }
+}
+
+impl DsdStream {
+    /// Create a stream from packed MSB-first bytes per channel
+    pub fn new(sample_rate: u32, data: Vec<Vec<u8>>) -> Result<Self> {
+        if data.is_empty() {
+            return Err(Error::AudioFormat("DSD stream has no channels".to_string()));
+        }
+        check_rate(sample_rate)?;
+
+        let bytes = data.iter().map(Vec::len).min().unwrap_or(0);
+        Ok(Self {
+            sample_rate,
+            channels: data.len() as u16,
+            sample_count: bytes as u64 * 8,
+            source: DsdSource::Memory(data),
+        })
+    }
+
+    /// Open a DSF or DSDIFF file
+    ///
+    /// Only the headers are read; channel data is read as it is needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    fn from_reader(mut reader: Box<dyn DsdReader>) -> Result<Self> {
+        let header = read_header(&mut reader)?;
+        let sample_count = header
+            .sample_count
+            .unwrap_or(u64::MAX)
+            .min(header.channel_bytes().saturating_mul(8));
+        // DSF pads the last block; the padding isn't channel data
+        let channel_bytes = sample_count.div_ceil(8);
+        Ok(Self {
+            sample_rate: header.sample_rate,
+            channels: header.channels,
+            sample_count,
+            source: DsdSource::File {
+                reader,
+                layout: header.layout,
+                data_offset: header.data_offset,
+                channel_bytes,
+            },
+        })
+    }
+
+    /// Sample rate of the decimated PCM output
+    pub fn pcm_sample_rate(&self) -> u32 {
+        self.sample_rate / DSD_DECIMATION
+    }
+
+    /// Number of PCM frames the stream decimates to
+    pub fn pcm_frames(&self) -> u64 {
+        self.sample_count / DSD_DECIMATION as u64
+    }
+
+    /// Multiple of 44.1 kHz (64 for DSD64, 128 for DSD128)
+    pub fn rate_multiple(&self) -> u32 {
+        self.sample_rate / 44_100
+    }
+
+    /// Read bytes `start..end` of every channel as packed MSB-first bits
+    ///
+    /// The range is clipped to the channel data.
+    pub fn read_channels(&mut self, start: u64, end: u64) -> Result<Vec<Vec<u8>>> {
+        let channels = self.channels as usize;
+        match &mut self.source {
+            DsdSource::Memory(data) => Ok(data
+                .iter()
+                .map(|channel| {
+                    let len = channel.len() as u64;
+                    channel[start.min(len) as usize..end.min(len) as usize].to_vec()
+                })
+                .collect()),
+            DsdSource::File {
+                reader,
+                layout,
+                data_offset,
+                channel_bytes,
+            } => {
+                let end = end.min(*channel_bytes);
+                let start = start.min(end);
+                let len = (end - start) as usize;
+                match *layout {
+                    DsdLayout::Bytes => {
+                        let mut interleaved = vec![0u8; len * channels];
+                        reader.seek(SeekFrom::Start(*data_offset + start * channels as u64))?;
+                        read_fully(reader, &mut interleaved)?;
+                        let mut data = vec![Vec::with_capacity(len); channels];
+                        for frame in interleaved.chunks_exact(channels) {
+                            for (channel, &byte) in data.iter_mut().zip(frame) {
+                                channel.push(byte);
+                            }
+                        }
+                        Ok(data)
+                    }
+                    DsdLayout::Blocks {
+                        block_size,
+                        lsb_first,
+                    } => {
+                        let mut data = vec![vec![0u8; len]; channels];
+                        for (channel, bytes) in data.iter_mut().enumerate() {
+                            let mut byte = start;
+                            while byte < end {
+                                let block = byte / block_size;
+                                let within = byte % block_size;
+                                let count = (block_size - within).min(end - byte);
+                                let offset = (block * channels as u64 + channel as u64)
+                                    * block_size
+                                    + within;
+                                let target = (byte - start) as usize;
+                                reader.seek(SeekFrom::Start(*data_offset + offset))?;
+                                read_fully(reader, &mut bytes[target..target + count as usize])?;
+                                byte += count;
+                            }
+                            // bits_per_sample 1 stores the oldest bit in the LSB
+                            if lsb_first {
+                                bytes.iter_mut().for_each(|b| *b = b.reverse_bits());
+                            }
+                        }
+                        Ok(data)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check if a path has a DSD file extension
+pub fn is_dsd_file<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "dsf" | "dff"))
+}
+
+/// Parse DSF or DSDIFF file contents, detected by their magic bytes
+pub fn parse_dsd(bytes: &[u8]) -> Result<DsdStream> {
+    DsdStream::from_reader(Box::new(Cursor::new(bytes.to_vec())))
+}
+
+fn truncated() -> Error {
+    Error::Decoding("Truncated DSD file".to_string())
+}
+
+fn oversized() -> Error {
+    Error::Decoding("DSD chunk size out of range".to_string())
+}
+
+fn check_rate(sample_rate: u32) -> Result<()> {
+    if !sample_rate.is_multiple_of(DSD_DECIMATION) {
+        return Err(Error::UnsupportedFormat(format!(
+            "Unsupported DSD rate {}Hz",
+            sample_rate
+        )));
+    }
+    Ok(())
+}
+
+/// Fill `buf`, reporting a short read as a truncated file
+fn read_fully<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => truncated(),
+        _ => Error::Io(e),
+    })
+}
+
+fn read_array<const N: usize, R: Read + ?Sized>(reader: &mut R) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    read_fully(reader, &mut buf)?;
+    Ok(buf)
+}
+
+/// Read the header fields at `offset`, `N` bytes in all
+fn read_at<const N: usize, R: Read + Seek + ?Sized>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<[u8; N]> {
+    reader.seek(SeekFrom::Start(offset))?;
+    read_array(reader)
+}
+
+/// Offset just past a chunk body, if it doesn't overflow
+fn chunk_end(body: u64, size: u64) -> Result<u64> {
+    body.checked_add(size).ok_or_else(oversized)
+}
+
+/// Read the header of a DSF or DSDIFF file, detected by its magic bytes
+fn read_header<R: Read + Seek + ?Sized>(reader: &mut R) -> Result<DsdHeader> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let magic = read_at::<4, _>(reader, 0).map_err(|_| not_dsd())?;
+    let header = match &magic {
+        b"DSD " => read_dsf_header(reader, len)?,
+        b"FRM8" => read_dff_header(reader, len)?,
+        _ => return Err(not_dsd()),
+    };
+    check_rate(header.sample_rate)?;
+    if header.channels == 0 {
+        return Err(Error::Decoding("DSD file has no channels".to_string()));
+    }
+    Ok(header)
+}
+
+fn not_dsd() -> Error {
+    Error::UnsupportedFormat("Not a DSF or DSDIFF file".to_string())
+}
+
+/// Read a DSF (Sony DSD Stream File) header
+///
+/// Little-endian chunks "DSD ", "fmt " and "data"; channel data is stored in
+/// interleaved blocks with the oldest bit in the least significant position.
+fn read_dsf_header<R: Read + Seek + ?Sized>(reader: &mut R, len: u64) -> Result<DsdHeader> {
+    let mut offset = 0u64;
+    let mut fmt: Option<(u16, u32, u32, u64, u64)> = None;
+
+    while chunk_end(offset, 12)? <= len {
+        let chunk = read_at::<12, _>(reader, offset)?;
+        let size = u64::from_le_bytes(chunk[4..].try_into().unwrap());
+        if size < 12 {
+            return Err(Error::Decoding("Invalid DSF chunk size".to_string()));
+        }
+
+        match &chunk[..4] {
+            b"fmt " => {
+                let body = read_array::<40, _>(reader)?;
+                let u32_at = |at: usize| u32::from_le_bytes(body[at..at + 4].try_into().unwrap());
+                let format_id = u32_at(4);
+                if format_id != 0 {
+                    return Err(Error::UnsupportedFormat(format!(
+                        "Unsupported DSF format id {}",
+                        format_id
+                    )));
+                }
+                fmt = Some((
+                    u32_at(12) as u16,
+                    u32_at(16),
+                    u32_at(20),
+                    u64::from_le_bytes(body[24..32].try_into().unwrap()),
+                    u32_at(32) as u64,
+                ));
+            }
+            b"data" => {
+                let (channels, sample_rate, bits_per_sample, sample_count, block_size) =
+                    fmt.ok_or_else(|| Error::Decoding("DSF data before fmt chunk".to_string()))?;
+                if channels == 0 || block_size == 0 {
+                    return Err(Error::Decoding("Invalid DSF fmt chunk".to_string()));
+                }
+                let data_offset = offset + 12;
+                return Ok(DsdHeader {
+                    sample_rate,
+                    channels,
+                    sample_count: Some(sample_count),
+                    layout: DsdLayout::Blocks {
+                        block_size,
+                        lsb_first: bits_per_sample == 1,
+                    },
+                    data_offset,
+                    data_len: (size - 12).min(len - data_offset),
+                });
+            }
+            _ => {}
+        }
+
+        offset = chunk_end(offset, size)?;
+    }
+
+    Err(Error::Decoding("DSF file has no data chunk".to_string()))
+}
+
+/// Read a DSDIFF (.dff) header
+///
+/// Big-endian "FRM8" container with a "PROP" chunk describing the stream and
+/// a "DSD " chunk holding byte-interleaved, MSB-first channel data. DST
+/// compressed files are not supported.
+fn read_dff_header<R: Read + Seek + ?Sized>(reader: &mut R, len: u64) -> Result<DsdHeader> {
+    if len < 16 || &read_at::<4, _>(reader, 12)? != b"DSD " {
+        return Err(Error::UnsupportedFormat("Not a DSDIFF file".to_string()));
+    }
+
+    let mut offset = 16u64;
+    let mut sample_rate = None;
+    let mut channels = None;
+
+    while chunk_end(offset, 12)? <= len {
+        let chunk = read_at::<12, _>(reader, offset)?;
+        let size = u64::from_be_bytes(chunk[4..].try_into().unwrap());
+        let body = offset + 12;
+        let end = chunk_end(body, size)?.min(len);
+
+        match &chunk[..4] {
+            b"PROP" => {
+                let mut sub = body + 4; // skip "SND "
+                while chunk_end(sub, 12)? <= end {
+                    let sub_chunk = read_at::<12, _>(reader, sub)?;
+                    let sub_size = u64::from_be_bytes(sub_chunk[4..].try_into().unwrap());
+                    match &sub_chunk[..4] {
+                        b"FS  " => sample_rate = Some(u32::from_be_bytes(read_array(reader)?)),
+                        b"CHNL" => channels = Some(u16::from_be_bytes(read_array(reader)?)),
+                        b"CMPR" if &read_array::<4, _>(reader)? != b"DSD " => {
+                            return Err(Error::UnsupportedFormat(
+                                "Compressed (DST) DSDIFF is not supported".to_string(),
+                            ));
+                        }
+                        _ => {}
+                    }
+                    sub = chunk_end(chunk_end(sub + 12, sub_size)?, sub_size & 1)?;
+                }
+            }
+            b"DSD " => {
+                let sample_rate = sample_rate
+                    .ok_or_else(|| Error::Decoding("DSDIFF file has no FS chunk".to_string()))?;
+                let channels = channels
+                    .filter(|&c| c > 0)
+                    .ok_or_else(|| Error::Decoding("DSDIFF file has no CHNL chunk".to_string()))?;
+                return Ok(DsdHeader {
+                    sample_rate,
+                    channels,
+                    sample_count: None,
+                    layout: DsdLayout::Bytes,
+                    data_offset: body,
+                    data_len: end - body,
+                });
+            }
+            _ => {}
+        }
+
+        offset = chunk_end(chunk_end(body, size)?, size & 1)?;
+    }
+
+    Err(Error::Decoding("DSDIFF file has no DSD chunk".to_string()))
+}
+
+/// Low-pass FIR filter that turns 1-bit DSD into PCM
+///
+/// The filter is evaluated a byte at a time through per-position lookup
+/// tables, producing one PCM sample every `DSD_DECIMATION` bits.
+pub struct DsdDecimator {
+    /// `tables[j][byte]` is the filter response to `byte` at byte offset `j`
+    tables: Vec<[f64; 256]>,
+}
+
+impl DsdDecimator {
+    /// Build the filter for a decimation by `DSD_DECIMATION`
+    pub fn new() -> Self {
+        let taps = Self::design_filter();
+
+        let tables = taps
+            .chunks_exact(8)
+            .map(|byte_taps| {
+                let mut table = [0.0; 256];
+                for (byte, entry) in table.iter_mut().enumerate() {
+                    *entry = byte_taps
+                        .iter()
+                        .enumerate()
+                        .map(|(bit, tap)| {
+                            // MSB is the oldest bit
+                            if byte & (0x80 >> bit) != 0 {
+                                *tap
+                            } else {
+                                -*tap
+                            }
+                        })
+                        .sum();
+                }
+                table
+            })
+            .collect();
+
+        Self { tables }
+    }
+
+    /// Blackman-windowed sinc low-pass with unity DC gain
+    fn design_filter() -> Vec<f64> {
+        let cutoff = FILTER_CUTOFF / DSD_DECIMATION as f64;
+        let center = (FILTER_TAPS - 1) as f64 / 2.0;
+        let n = FILTER_TAPS as f64 - 1.0;
+
+        let mut taps: Vec<f64> = (0..FILTER_TAPS)
+            .map(|i| {
+                let x = i as f64 - center;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * std::f64::consts::PI * cutoff * x).sin() / (std::f64::consts::PI * x)
+                };
+                let phase = 2.0 * std::f64::consts::PI * i as f64 / n;
+                let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                sinc * window
+            })
+            .collect();
+
+        let sum: f64 = taps.iter().sum();
+        for tap in &mut taps {
+            *tap /= sum;
+        }
+        taps
+    }
+
+    /// Compute PCM sample `index` of a channel
+    ///
+    /// Bits before the start of the stream count as silence.
+    pub fn sample(&self, data: &[u8], index: u64) -> f64 {
+        self.sample_window(data, 0, index)
+    }
+
+    /// `sample` from the part of a channel starting at byte `offset`
+    ///
+    /// `data` has to start at least `FILTER_TAPS / 8` bytes before the end
+    /// of the sample's input (or at the stream start).
+    fn sample_window(&self, data: &[u8], offset: u64, index: u64) -> f64 {
+        let bytes_per_output = (DSD_DECIMATION / 8) as i64;
+        let last = (index as i64 + 1) * bytes_per_output - offset as i64;
+        let first = last - self.tables.len() as i64;
+
+        self.tables
+            .iter()
+            .enumerate()
+            .filter_map(|(j, table)| {
+                let position = first + j as i64;
+                usize::try_from(position)
+                    .ok()
+                    .and_then(|p| data.get(p))
+                    .map(|&byte| table[byte as usize])
+            })
+            .sum()
+    }
+
+    /// Decimate a whole channel to PCM
+    pub fn process(&self, data: &[u8]) -> Vec<f64> {
+        let frames = data.len() as u64 * 8 / DSD_DECIMATION as u64;
+        (0..frames).map(|i| self.sample(data, i)).collect()
+    }
+}
+
+impl Default for DsdDecimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes a DSD stream to interleaved f64 PCM
+pub struct DsdDecoder {
+    stream: DsdStream,
+    decimator: DsdDecimator,
+    /// Next PCM frame to produce
+    position: u64,
+}
+
+impl DsdDecoder {
+    /// Open a DSF or DSDIFF file, reading its data as it is decoded
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(DsdStream::open(path)?))
+    }
+
+    /// Decode an already parsed stream
+    pub fn new(stream: DsdStream) -> Self {
+        Self {
+            stream,
+            decimator: DsdDecimator::new(),
+            position: 0,
+        }
+    }
+
+    /// The parsed DSD stream
+    pub fn stream(&self) -> &DsdStream {
+        &self.stream
+    }
+
+    /// PCM format produced by the decoder
+    pub fn format(&self) -> AudioFormat {
+        AudioFormat::new(
+            self.stream.pcm_sample_rate(),
+            self.stream.channels,
+            SampleFormat::F64,
+        )
+    }
+
+    /// Total duration in PCM frames
+    pub fn duration(&self) -> u64 {
+        self.stream.pcm_frames()
+    }
+
+    /// PCM frame the next decoded chunk starts at
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Decode the next chunk as interleaved samples
+    ///
+    /// Only the channel data the chunk's samples depend on is read. Returns
+    /// `None` at the end of the stream.
+    pub fn decode_next(&mut self) -> Result<Option<Vec<f64>>> {
+        let total = self.duration();
+        if self.position >= total {
+            return Ok(None);
+        }
+
+        let end = (self.position + DECODE_CHUNK_FRAMES as u64).min(total);
+        let bytes_per_output = (DSD_DECIMATION / 8) as u64;
+        // The filter reaches back FILTER_TAPS bits from each sample's input
+        let first = ((self.position + 1) * bytes_per_output).saturating_sub(FILTER_TAPS as u64 / 8);
+        let data = self.stream.read_channels(first, end * bytes_per_output)?;
+
+        let mut samples = Vec::with_capacity((end - self.position) as usize * data.len());
+        for frame in self.position..end {
+            for channel in &data {
+                samples.push(self.decimator.sample_window(channel, first, frame));
+            }
+        }
+
+        self.position = end;
+        Ok(Some(samples))
+    }
+
+    /// Seek to a PCM frame
+    pub fn seek(&mut self, position: u64) {
+        self.position = position.min(self.duration());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Second-order sigma-delta modulation of a sine into packed MSB-first bits
+    fn modulate_tone(frequency: f64, amplitude: f64, rate: u32, bits: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; bits / 8];
+        let (mut i1, mut i2, mut y) = (0.0f64, 0.0f64, 0.0f64);
+        for n in 0..bits {
+            let x =
+                amplitude * (2.0 * std::f64::consts::PI * frequency * n as f64 / rate as f64).sin();
+            i1 += x - y;
+            i2 += i1 - y;
+            let bit = i2 >= 0.0;
+            y = if bit { 1.0 } else { -1.0 };
+            if bit {
+                bytes[n / 8] |= 0x80 >> (n % 8);
+            }
+        }
+        bytes
+    }
+
+    /// Build a mono DSF file around MSB-first channel data
+    fn build_dsf(rate: u32, channel: &[u8]) -> Vec<u8> {
+        let block_size = 4096;
+        let blocks = channel.len().div_ceil(block_size);
+        let data_size = 12 + blocks * block_size;
+
+        let mut file = Vec::new();
+        file.extend_from_slice(b"DSD ");
+        file.extend_from_slice(&28u64.to_le_bytes());
+        file.extend_from_slice(&((28 + 52 + data_size) as u64).to_le_bytes());
+        file.extend_from_slice(&0u64.to_le_bytes());
+
+        file.extend_from_slice(b"fmt ");
+        file.extend_from_slice(&52u64.to_le_bytes());
+        file.extend_from_slice(&1u32.to_le_bytes()); // version
+        file.extend_from_slice(&0u32.to_le_bytes()); // DSD raw
+        file.extend_from_slice(&1u32.to_le_bytes()); // mono
+        file.extend_from_slice(&1u32.to_le_bytes()); // channels
+        file.extend_from_slice(&rate.to_le_bytes());
+        file.extend_from_slice(&1u32.to_le_bytes()); // LSB first
+        file.extend_from_slice(&(channel.len() as u64 * 8).to_le_bytes());
+        file.extend_from_slice(&(block_size as u32).to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&(data_size as u64).to_le_bytes());
+        let mut data: Vec<u8> = channel.iter().map(|b| b.reverse_bits()).collect();
+        data.resize(blocks * block_size, 0x69);
+        file.extend_from_slice(&data);
+        file
+    }
+
+    #[test]
+    fn test_decimates_tone_to_expected_frequency() {
+        let frequency = 1000.0;
+        let bits = DSD64_RATE as usize / 10;
+        let mut stream = DsdStream::new(
+            DSD64_RATE,
+            vec![modulate_tone(frequency, 0.5, DSD64_RATE, bits)],
+        )
+        .unwrap();
+        assert_eq!(stream.pcm_sample_rate(), 88_200);
+
+        let pcm = DsdDecimator::new().process(&stream.read_channels(0, u64::MAX).unwrap()[0]);
+        assert_eq!(pcm.len() as u64, stream.pcm_frames());
+
+        // Skip the filter warm-up
+        let settled = &pcm[FILTER_TAPS / DSD_DECIMATION as usize..];
+        let crossings = settled
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+        let seconds = settled.len() as f64 / 88_200.0;
+        let measured = crossings as f64 / 2.0 / seconds;
+        assert!(
+            (measured - frequency).abs() < frequency * 0.02,
+            "Expected ~{}Hz, measured {}Hz",
+            frequency,
+            measured
+        );
+
+        let peak = settled.iter().fold(0.0f64, |m, s| m.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.05, "Unexpected peak {}", peak);
+    }
+
+    #[test]
+    fn test_parse_dsf_and_decode() {
+        let channel = modulate_tone(500.0, 0.5, DSD64_RATE, 8 * 10_000);
+        let file = build_dsf(DSD64_RATE, &channel);
+
+        let mut stream = parse_dsd(&file).unwrap();
+        assert_eq!(stream.channels, 1);
+        assert_eq!(stream.sample_rate, DSD64_RATE);
+        assert_eq!(stream.rate_multiple(), 64);
+        assert_eq!(stream.sample_count, 80_000);
+        assert_eq!(
+            stream.read_channels(0, u64::MAX).unwrap(),
+            std::slice::from_ref(&channel)
+        );
+
+        // Decoding chunk by chunk matches decimating the whole channel
+        let expected = DsdDecimator::new().process(&channel);
+        let mut decoder = DsdDecoder::new(stream);
+        assert_eq!(decoder.format().sample_rate, 88_200);
+        let mut decoded = Vec::new();
+        while let Some(samples) = decoder.decode_next().unwrap() {
+            decoded.extend(samples);
+        }
+        assert_eq!(decoded.len() as u64, decoder.duration());
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_parse_dff() {
+        let left = vec![0xAAu8; 64];
+        let right = vec![0x0Fu8; 64];
+
+        let mut prop = Vec::new();
+        prop.extend_from_slice(b"SND ");
+        prop.extend_from_slice(b"FS  ");
+        prop.extend_from_slice(&4u64.to_be_bytes());
+        prop.extend_from_slice(&DSD128_RATE.to_be_bytes());
+        prop.extend_from_slice(b"CHNL");
+        prop.extend_from_slice(&10u64.to_be_bytes());
+        prop.extend_from_slice(&2u16.to_be_bytes());
+        prop.extend_from_slice(b"SLFTSRGT");
+        prop.extend_from_slice(b"CMPR");
+        prop.extend_from_slice(&4u64.to_be_bytes());
+        prop.extend_from_slice(b"DSD ");
+
+        let data: Vec<u8> = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(&l, &r)| [l, r])
+            .collect();
+
+        let mut body = Vec::new();
+        body.extend_from_slice(b"DSD ");
+        body.extend_from_slice(b"PROP");
+        body.extend_from_slice(&(prop.len() as u64).to_be_bytes());
+        body.extend_from_slice(&prop);
+        body.extend_from_slice(b"DSD ");
+        body.extend_from_slice(&(data.len() as u64).to_be_bytes());
+        body.extend_from_slice(&data);
+
+        let mut file = Vec::new();
+        file.extend_from_slice(b"FRM8");
+        file.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        file.extend_from_slice(&body);
+
+        let mut stream = parse_dsd(&file).unwrap();
+        assert_eq!(stream.channels, 2);
+        assert_eq!(stream.sample_rate, DSD128_RATE);
+        assert_eq!(stream.pcm_sample_rate(), 176_400);
+        assert_eq!(stream.read_channels(0, u64::MAX).unwrap(), [left, right]);
+        assert_eq!(stream.read_channels(8, 10).unwrap(), [[0xAA; 2], [0x0F; 2]]);
+
+        // Chunk sizes that overflow are rejected instead of wrapping around
+        let mut hostile = file.clone();
+        hostile[20..28].copy_from_slice(&(u64::MAX - 8).to_be_bytes()); // PROP size
+        assert!(matches!(parse_dsd(&hostile), Err(Error::Decoding(_))));
+    }
+
+    #[test]
+    fn test_rejects_overflowing_dsf_chunk() {
+        let file = build_dsf(DSD64_RATE, &[0x69; 64]);
+        assert!(matches!(parse_dsd(&file[..40]), Err(Error::Decoding(_))));
+
+        // The "DSD " chunk claims to reach past the end of the address space
+        let mut hostile = file.clone();
+        hostile[4..12].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        assert!(matches!(parse_dsd(&hostile), Err(Error::Decoding(_))));
+    }
+
+    #[test]
+    fn test_is_dsd_file() {
+        assert!(is_dsd_file("album.dsf"));
+        assert!(is_dsd_file("album.DFF"));
+        assert!(!is_dsd_file("album.flac"));
+    }
+
+    #[test]
+    fn test_decoder_reads_dsf_as_pcm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.dsf");
+        let channel = modulate_tone(500.0, 0.5, DSD64_RATE, 8 * 20_000);
+        std::fs::write(&path, build_dsf(DSD64_RATE, &channel)).unwrap();
+
+        let mut decoder = crate::audio::decoder::AudioDecoder::new(&path).unwrap();
+        assert_eq!(decoder.format().sample_rate, 88_200);
+        assert_eq!(decoder.format().channels, 1);
+        let buffer = decoder.decode_all().unwrap();
+        assert_eq!(buffer.data().len() as u64, decoder.duration().unwrap());
+
+        let info = crate::audio::decoder::detect_format(&path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.format_name, "DSF");
+        assert_eq!(info.codec_type, "DSD64");
+        assert!(info.is_lossless);
+        assert!(info.is_high_resolution());
+
+        let opened = decoder.format_info().unwrap();
+        assert_eq!(opened.format_name, "DSF");
+        assert_eq!(opened.codec_type, "DSD64");
+        assert_eq!(opened.duration, info.duration);
+    }
+}
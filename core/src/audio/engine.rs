@@ -1,17 +1,120 @@
 //! Main audio engine implementation
 
 use crate::audio::buffer::AudioBuffer;
+use crate::audio::checksum::{
+    count_clipped_samples_interleaved, phase_correlation, ClipStats, DEFAULT_CLIP_RUN,
+};
+use crate::audio::decoder::{AudioFormatInfo, AudioStreamReaderWithRingBuffer, ReplayGainTags};
+use crate::audio::device_monitor::{DeviceMonitor, PollThread, DEFAULT_POLL_INTERVAL};
+use crate::audio::equalizer::{EqPreset, Equalizer};
+use crate::audio::filter::{butterworth_high_pass, loudness_contour, Biquad};
 use crate::audio::format::AudioFormat;
-use crate::audio::ring_buffer::RingBufferConsumer;
+use crate::audio::format::SampleFormat;
+use crate::audio::loudness::{frequency_weighting_filters, FrequencyWeighting};
+use crate::audio::output::{
+    find_bit_perfect_format, is_lossless_conversion, negotiable_configs, pcm_sample_format,
+    remix_channels, sample_format_bits, sample_format_from_cpal, select_channel_matched_format,
+    ChannelMatchPolicy, CpalBackend, Mixer, MixerSource, MixerSourceId, NullBackend, OutputBackend,
+    OutputSample,
+};
+use crate::audio::prefetch::{
+    prefetch_action, PrefetchAction, DEFAULT_PREFETCH_SECONDS, PREFETCH_POLL_INTERVAL,
+};
+use crate::audio::processor::{
+    detect_silence_bounds, AudioProcessor, Ditherer, DitheringAlgorithm, NormalizationMode,
+    ResampleQuality, SampleRateConverter, SaturationConfig, Saturator, StereoWidth, TimeStretcher,
+    MAX_PLAYBACK_RATE, MIN_PLAYBACK_RATE,
+};
+use crate::audio::ring_buffer::{
+    AudioRingBuffer, RingBufferConfig, RingBufferConsumer, RingBufferProducer,
+};
+use crate::cue::VirtualTrack;
+use crate::playlist::manager::Track;
+use crate::playlist::queue::{PlayQueue, RepeatMode};
+use crate::state::persistence::{SessionSettings, SessionState};
 use crate::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Device, Host, OutputCallbackInfo, Stream, StreamConfig};
-use parking_lot::RwLock;
-use std::path::Path;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Number of attempts made to reopen the selected device during recovery
+const RECOVERY_ATTEMPTS: u32 = 3;
+
+/// Delay before the first recovery retry (doubled on each further attempt)
+const RECOVERY_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Seconds into a track after which `previous()` restarts it instead
+const PREVIOUS_RESTART_SECONDS: f64 = 3.0;
+
+/// Default fade applied when starting and stopping playback
+pub(crate) const DEFAULT_FADE_DURATION_MS: u32 = 15;
+
+/// How often a deferred stream pause checks whether the fade-out finished
+const FADE_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Extra time allowed for a fade-out before the stream is paused regardless
+const FADE_PAUSE_GRACE: Duration = Duration::from_millis(500);
+
+/// Ring buffer fill a stream needs before playback starts or resumes
+///
+/// Below this, `play()` and underruns leave the engine `Buffering`.
+const STREAM_PREROLL_FRACTION: f64 = 0.5;
+
+/// Level below which audio counts as silence for `set_skip_silence`
+const SKIP_SILENCE_THRESHOLD_DBFS: f64 = -60.0;
+
+/// Leading/trailing silence shorter than this is left in place
+const SKIP_SILENCE_MIN_SECONDS: f64 = 0.5;
+
+/// Source frames fed to the time stretcher per read
+const STRETCH_CHUNK_FRAMES: usize = 512;
+
+/// Block size in frames the output callback's scratch buffers are sized for
+///
+/// Sized when a stream opens; a device asking for larger blocks grows them
+/// once, on its first callback.
+const SCRATCH_BLOCK_FRAMES: usize = 4096;
+
+/// Usual corner frequency for `set_rumble_filter`, in Hz
+pub const DEFAULT_RUMBLE_CUTOFF_HZ: f64 = 20.0;
+
+/// Frames per callback block rendered by `advance_for_testing`
+#[cfg(any(test, feature = "testing"))]
+const TESTING_BLOCK_FRAMES: usize = 512;
+
+/// Volume change, in dB, after which the loudness compensation is retuned
+const LOUDNESS_RETUNE_DB: f64 = 0.25;
+
+/// Longest edge fade `set_virtual_track_fade` accepts, in milliseconds
+pub const MAX_VIRTUAL_TRACK_FADE_MS: u32 = 50;
+
+/// Default time for the metered peak hold to fall by 20 dB, in milliseconds
+pub const DEFAULT_PEAK_HOLD_DECAY_MS: u32 = 1500;
+
+/// Default time a clip stays flagged in the meter, in milliseconds
+pub const DEFAULT_CLIP_HOLD_MS: u32 = 2000;
+
+/// Time a manual track gain takes to ramp in when the track changes
+const TRACK_GAIN_RAMP_MS: u32 = 50;
+
+/// Ramp back to the previous volume on unmute, in milliseconds
+const UNMUTE_RAMP_MS: u32 = 20;
+
+/// Fade at the end of a scrub preview, in milliseconds
+const PREVIEW_FADE_MS: u32 = 5;
+
+/// How far a loop point may move to reach a zero crossing, in milliseconds
+pub const LOOP_SNAP_WINDOW_MS: u32 = 5;
 
 /// Audio playback state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PlaybackState {
     /// Engine is stopped
     Stopped,
@@ -19,12 +122,31 @@ pub enum PlaybackState {
     Playing,
     /// Engine is paused
     Paused,
-    /// Engine is buffering
+    /// Engine is loading a stream or waiting for it to buffer enough audio
+    ///
+    /// Playback starts (or resumes after an underrun) by itself once the
+    /// stream has caught up.
     Buffering,
     /// Engine encountered an error
     Error,
 }
 
+/// How the current track is fed to the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    /// Decoded into memory in full
+    Buffered,
+    /// Decoded on the fly through a ring buffer
+    Streaming,
+}
+
+impl PlaybackState {
+    /// Whether playback was requested, though it may still be buffering
+    pub fn is_active(self) -> bool {
+        matches!(self, PlaybackState::Playing | PlaybackState::Buffering)
+    }
+}
+
 /// Audio engine events that can be sent to callbacks
 #[derive(Debug, Clone)]
 pub enum AudioEvent {
@@ -38,11 +160,93 @@ pub enum AudioEvent {
     Error(String),
     /// Buffer underrun occurred
     BufferUnderrun,
+    /// Output device changed (new device name)
+    DeviceChanged(String),
+    /// Recoverable problem worth reporting (e.g. a fallback was used)
+    Warning(String),
+    /// A track was loaded buffered or streamed, e.g. streamed because it
+    /// exceeds the decode memory limit
+    PlaybackModeChanged(PlaybackMode),
 }
 
 /// Callback function type for audio events
 pub type AudioCallback = Box<dyn Fn(AudioEvent) + Send + Sync>;
 
+/// Output levels of one audio callback block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterLevels {
+    /// Peak absolute sample value
+    pub peak: f64,
+    /// RMS over all channels
+    pub rms: f64,
+    /// Stereo phase correlation (-1.0 to 1.0, 1.0 for mono output)
+    pub correlation: f64,
+    /// RMS after the meter's frequency weighting (None when unweighted)
+    pub weighted_rms: Option<f64>,
+    /// Highest recent peak, falling back towards `peak` over the decay time
+    pub peak_hold: f64,
+    /// Whether the output clipped within the clip hold time
+    pub clip_held: bool,
+}
+
+impl MeterLevels {
+    /// Weighted RMS in dBFS, if a weighting is selected
+    pub fn weighted_rms_dbfs(&self) -> Option<f64> {
+        self.weighted_rms.map(|rms| 20.0 * rms.log10())
+    }
+}
+
+/// Peak and clip hold state of the output meter
+#[derive(Debug, Clone, Copy)]
+struct MeterHold {
+    /// Time for the held peak to fall by 20 dB, in milliseconds
+    peak_decay_ms: u32,
+    /// How long a clip stays flagged, in milliseconds
+    clip_hold_ms: u32,
+    /// Currently held peak
+    peak: f64,
+    /// Frames until the clip flag clears
+    clip_frames_left: u64,
+}
+
+impl Default for MeterHold {
+    fn default() -> Self {
+        Self {
+            peak_decay_ms: DEFAULT_PEAK_HOLD_DECAY_MS,
+            clip_hold_ms: DEFAULT_CLIP_HOLD_MS,
+            peak: 0.0,
+            clip_frames_left: 0,
+        }
+    }
+}
+
+impl MeterHold {
+    /// Fold one block's peak into the hold, returning (peak hold, clip held)
+    ///
+    /// Blocks peaking at full scale or above count as clipped.
+    fn update(&mut self, peak: f64, frames: usize, sample_rate: u32) -> (f64, bool) {
+        let seconds = frames as f64 / sample_rate.max(1) as f64;
+        let decay = match self.peak_decay_ms {
+            0 => 0.0,
+            ms => 10f64.powf(-seconds * 1000.0 / ms as f64),
+        };
+        self.peak = peak.max(self.peak * decay);
+
+        self.clip_frames_left = self.clip_frames_left.saturating_sub(frames as u64);
+        let clipped = peak >= 1.0;
+        if clipped {
+            self.clip_frames_left = self.clip_hold_ms as u64 * sample_rate as u64 / 1000;
+        }
+        (self.peak, clipped || self.clip_frames_left > 0)
+    }
+}
+
+/// Callback function type for output metering
+pub type MeterCallback = Box<dyn Fn(MeterLevels) + Send + Sync>;
+
+/// Callback function type for load progress (0.0–1.0, `NaN` if indeterminate)
+pub type LoadProgressCallback = Box<dyn Fn(f64) + Send>;
+
 /// Information about an audio device
 #[derive(Debug, Clone)]
 pub struct AudioDeviceInfo {
@@ -54,15 +258,285 @@ pub struct AudioDeviceInfo {
     pub is_default: bool,
 }
 
+/// Playback status read under a single state lock
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackSnapshot {
+    /// Playback state
+    pub state: PlaybackState,
+    /// Position in sample frames
+    pub position: u64,
+    /// Duration in sample frames (if known)
+    pub duration: Option<u64>,
+    /// Sample rate of the loaded track (if any)
+    pub sample_rate: Option<u32>,
+    /// Current volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Whether audio is muted
+    pub is_muted: bool,
+    /// Ring buffer fill (0.0 to 1.0) when streaming
+    pub buffer_utilization: Option<f64>,
+}
+
+/// Full player state for bug reports, see `AudioEngine::debug_snapshot`
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSnapshot {
+    /// Playback state
+    pub state: PlaybackState,
+    /// Position in sample frames
+    pub position: u64,
+    /// Duration in sample frames (if known)
+    pub duration: Option<u64>,
+    /// Current volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Whether audio is muted
+    pub is_muted: bool,
+    /// File of the current track
+    pub current_path: Option<PathBuf>,
+    /// Format of the current track
+    pub format: Option<AudioFormat>,
+    /// Container and codec details of the current track
+    pub source_info: Option<AudioFormatInfo>,
+    /// Whether the current track is streamed through a ring buffer
+    pub using_ring_buffer: bool,
+    /// Ring buffer fill (0.0 to 1.0) when streaming
+    pub buffer_utilization: Option<f64>,
+    /// Ring buffer underruns of the current stream
+    pub underrun_count: usize,
+    /// Output stream configuration, when one is open
+    pub stream_config: Option<StreamConfigInfo>,
+    /// Playback rate (1.0 = normal speed)
+    pub playback_rate: f64,
+    /// Stereo balance (-1.0 to 1.0)
+    pub balance: f32,
+    /// Active equalizer preset
+    pub eq_preset: Option<EqPreset>,
+    /// Rumble filter corner frequency in Hz
+    pub rumble_filter_hz: Option<f64>,
+    /// Whether loudness compensation is enabled
+    pub loudness_compensation: bool,
+    /// Normalization applied to decoded tracks
+    pub normalization: NormalizationMode,
+    /// Play/pause/stop fade in milliseconds
+    pub fade_duration_ms: u32,
+    /// Whether leading/trailing silence is skipped
+    pub skip_silence: bool,
+    /// Pause between queued tracks in milliseconds
+    pub inter_track_gap_ms: u32,
+    /// Whether playback loops
+    pub loop_enabled: bool,
+}
+
+/// Output stream configuration as negotiated with the device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StreamConfigInfo {
+    /// Sample rate of the stream in Hz
+    pub sample_rate: u32,
+    /// Number of output channels
+    pub channels: u16,
+    /// Sample format the device is fed
+    pub sample_format: SampleFormat,
+    /// Callback buffer size in frames (`None` when the backend picks it)
+    pub buffer_size_frames: Option<u32>,
+    /// Whether the source is resampled to the stream rate
+    pub resampling: bool,
+    /// Whether the source is remixed to the stream's channel count
+    pub remixing: bool,
+}
+
+/// Encode a dithering algorithm for sharing with the output callback
+fn dithering_to_u8(algorithm: DitheringAlgorithm) -> u8 {
+    match algorithm {
+        DitheringAlgorithm::None => 0,
+        DitheringAlgorithm::Triangular => 1,
+        DitheringAlgorithm::Rectangular => 2,
+    }
+}
+
+/// Decode a dithering algorithm stored by `dithering_to_u8`
+fn dithering_from_u8(value: u8) -> DitheringAlgorithm {
+    match value {
+        1 => DitheringAlgorithm::Triangular,
+        2 => DitheringAlgorithm::Rectangular,
+        _ => DitheringAlgorithm::None,
+    }
+}
+
+/// Encode a playback state for `PlaybackControls`
+fn playback_state_to_u8(state: PlaybackState) -> u8 {
+    match state {
+        PlaybackState::Stopped => 0,
+        PlaybackState::Playing => 1,
+        PlaybackState::Paused => 2,
+        PlaybackState::Buffering => 3,
+        PlaybackState::Error => 4,
+    }
+}
+
+/// Decode a playback state stored by `playback_state_to_u8`
+fn playback_state_from_u8(value: u8) -> PlaybackState {
+    match value {
+        1 => PlaybackState::Playing,
+        2 => PlaybackState::Paused,
+        3 => PlaybackState::Buffering,
+        4 => PlaybackState::Error,
+        _ => PlaybackState::Stopped,
+    }
+}
+
+/// `PlaybackControls::volume_command` value when no change is pending
+const NO_VOLUME_COMMAND: u64 = u64::MAX;
+
+/// Volume changes and playback status shared with the output callback
+///
+/// Volume setters post a command here instead of taking the state lock, so
+/// they never make the callback miss a block; the callback applies it on
+/// its next block. Position, state and volume are published after every
+/// rendered block and state update, so the getters don't lock either.
+#[derive(Debug)]
+struct PlaybackControls {
+    /// Pending volume change: target f32 bits << 32 | ramp step f32 bits
+    volume_command: AtomicU64,
+    /// Latest volume (f32 bits)
+    volume: AtomicU32,
+    /// Volume the latest change heads for, reached once any ramp ends
+    /// (f32 bits)
+    target_volume: AtomicU32,
+    /// Volume restored by unmute (f32 bits)
+    volume_before_mute: AtomicU32,
+    /// Whether audio is muted
+    muted: AtomicBool,
+    /// Playback position in sample frames
+    position: AtomicU64,
+    /// Playback state (see `playback_state_to_u8`)
+    state: AtomicU8,
+    /// Sample rate of the loaded track (0 = none), for sizing volume ramps
+    sample_rate: AtomicU32,
+    /// Frames played while the state was only readable, not yet added to
+    /// the state's position
+    shared_frames: AtomicU64,
+}
+
+impl Default for PlaybackControls {
+    fn default() -> Self {
+        Self {
+            volume_command: AtomicU64::new(NO_VOLUME_COMMAND),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            target_volume: AtomicU32::new(1.0f32.to_bits()),
+            volume_before_mute: AtomicU32::new(1.0f32.to_bits()),
+            muted: AtomicBool::new(false),
+            position: AtomicU64::new(0),
+            state: AtomicU8::new(playback_state_to_u8(PlaybackState::Stopped)),
+            sample_rate: AtomicU32::new(0),
+            shared_frames: AtomicU64::new(0),
+        }
+    }
+}
+
+impl PlaybackControls {
+    /// Latest volume
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Acquire))
+    }
+
+    /// Ask the callback to move to `target`, over `ramp_ms` if a track is loaded
+    ///
+    /// Without a ramp the new volume is reported right away.
+    fn post_volume(&self, target: f32, ramp_ms: u32) {
+        let sample_rate = self.sample_rate.load(Ordering::Acquire) as f32;
+        let step = if ramp_ms == 0 || sample_rate == 0.0 {
+            0.0
+        } else {
+            let ramp_samples = (sample_rate * ramp_ms as f32 / 1000.0).max(1.0);
+            (target - self.volume()) / ramp_samples
+        };
+        if step == 0.0 {
+            self.volume.store(target.to_bits(), Ordering::Release);
+        }
+        self.target_volume
+            .store(target.to_bits(), Ordering::Release);
+        let command = ((target.to_bits() as u64) << 32) | step.to_bits() as u64;
+        self.volume_command.store(command, Ordering::Release);
+    }
+
+    /// Whether a posted volume change hasn't been applied yet
+    fn volume_pending(&self) -> bool {
+        self.volume_command.load(Ordering::Acquire) != NO_VOLUME_COMMAND
+    }
+
+    /// Take the pending volume change as (target, step per sample)
+    fn take_volume_command(&self) -> Option<(f32, f32)> {
+        match self
+            .volume_command
+            .swap(NO_VOLUME_COMMAND, Ordering::AcqRel)
+        {
+            NO_VOLUME_COMMAND => None,
+            command => Some((
+                f32::from_bits((command >> 32) as u32),
+                f32::from_bits(command as u32),
+            )),
+        }
+    }
+}
+
+/// View of the current track and position, detached from the engine
+///
+/// Taken with `AudioEngine::status`; event callbacks can use it to see what
+/// is playing without access to the engine itself.
+#[derive(Clone)]
+pub struct PlaybackStatus {
+    state: Arc<RwLock<AudioEngineState>>,
+    controls: Arc<PlaybackControls>,
+}
+
+impl PlaybackStatus {
+    /// Get the path of the current track
+    pub fn current_path(&self) -> Option<PathBuf> {
+        self.state.read().current_path.clone()
+    }
+
+    /// Get the playback position in seconds
+    pub fn position_seconds(&self) -> f64 {
+        let sample_rate = self.controls.sample_rate.load(Ordering::Acquire);
+        if sample_rate == 0 {
+            return 0.0;
+        }
+        self.controls.position.load(Ordering::Acquire) as f64 / sample_rate as f64
+    }
+
+    /// Get the duration of the current track in seconds, if known
+    pub fn duration_seconds(&self) -> Option<f64> {
+        let state = self.state.read();
+        let sample_rate = state.format.as_ref()?.sample_rate;
+        let duration = state.duration?;
+        (sample_rate > 0).then(|| duration as f64 / sample_rate as f64)
+    }
+}
+
 /// Trait defining the audio engine interface
+///
+/// The trait is object safe, so `Box<dyn AudioEngineInterface>` can hold the
+/// real engine or a mock; implementors provide `load_file_path` and get the
+/// generic `load_file` for free.
 pub trait AudioEngineInterface {
     /// Load an audio file for playback
-    fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()>;
+    fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()>
+    where
+        Self: Sized,
+    {
+        self.load_file_path(path.as_ref())
+    }
+
+    /// Load an audio file for playback (non-generic form of `load_file`)
+    fn load_file_path(&mut self, path: &Path) -> Result<()>;
 
     /// Start playback
     fn play(&mut self) -> Result<()>;
 
     /// Pause playback
+    ///
+    /// A streamed source keeps the audio decoded ahead (its decoder waits
+    /// while the buffer is full), and resuming continues from the frame
+    /// where playback paused.
     fn pause(&mut self) -> Result<()>;
 
     /// Stop playback and reset position
@@ -85,9 +559,13 @@ pub trait AudioEngineInterface {
     fn volume(&self) -> f32;
 
     /// Mute audio (preserves volume setting)
+    ///
+    /// Muting during a volume ramp remembers the ramp's target volume.
     fn mute(&mut self) -> Result<()>;
 
     /// Unmute audio (restores previous volume)
+    ///
+    /// The volume ramps back in briefly rather than jumping.
     fn unmute(&mut self) -> Result<()>;
 
     /// Check if audio is muted
@@ -112,16 +590,177 @@ pub trait AudioEngineInterface {
     fn clear_callback(&mut self);
 }
 
+/// Event and meter callbacks
+///
+/// Kept outside the state lock, so a slow callback never makes the output
+/// callback miss a block. Shared by the engine, its state and the event
+/// thread of a device stream.
+#[derive(Default)]
+struct EngineCallbacks {
+    /// Event callback
+    event: RwLock<Option<AudioCallback>>,
+    /// Output metering callback
+    meter: RwLock<Option<MeterCallback>>,
+    /// Whether a meter callback is set, checked while rendering
+    metering: AtomicBool,
+}
+
+impl EngineCallbacks {
+    /// Call the event callback, if any
+    fn emit(&self, event: AudioEvent) {
+        if let Some(ref callback) = *self.event.read() {
+            callback(event);
+        }
+    }
+
+    /// Replace the event callback
+    fn set_event(&self, callback: Option<AudioCallback>) {
+        *self.event.write() = callback;
+    }
+
+    /// Replace the meter callback
+    fn set_meter(&self, callback: Option<MeterCallback>) {
+        self.metering.store(callback.is_some(), Ordering::Release);
+        *self.meter.write() = callback;
+    }
+}
+
+/// Blocks of events a device stream queues ahead of its event thread
+const EVENT_QUEUE_BLOCKS: usize = 64;
+
+/// Thread reporting the events a device stream raises
+///
+/// The output callback only queues each block's events, so user callbacks
+/// never run on the realtime thread. Events still queued when the thread
+/// stops are dropped. Stopped and joined when dropped.
+struct EventThread {
+    stop: Option<crossbeam::channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventThread {
+    /// Start reporting to `callbacks`, returning the sender to queue blocks on
+    fn start(callbacks: Arc<EngineCallbacks>) -> (Self, crossbeam::channel::Sender<BlockEvents>) {
+        let (sender, events) = crossbeam::channel::bounded(EVENT_QUEUE_BLOCKS);
+        let (stop, stopped) = crossbeam::channel::bounded::<()>(0);
+        let thread = std::thread::spawn(move || loop {
+            crossbeam::channel::select! {
+                recv(events) -> block => match block {
+                    Ok(block) => AudioEngine::emit_block_events(&callbacks, block),
+                    Err(_) => break,
+                },
+                recv(stopped) -> _ => break,
+            }
+        });
+        (
+            Self {
+                stop: Some(stop),
+                thread: Some(thread),
+            },
+            sender,
+        )
+    }
+}
+
+impl Drop for EventThread {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A stream to pause once the fade-out of `generation` has finished
+struct FadePause {
+    stream: Arc<Stream>,
+    generation: u64,
+    /// Time after which the stream is paused even if the fade is unfinished
+    deadline: Duration,
+}
+
+impl FadePause {
+    /// Wait for the fade-out, then pause the stream
+    ///
+    /// Returns early if another fade started or playback was restarted.
+    /// Polls under the read lock so the audio callback is never kept waiting
+    /// on it; the write lock is only taken to finish the pause.
+    fn run(self, state: &RwLock<AudioEngineState>) {
+        let started = std::time::Instant::now();
+        loop {
+            std::thread::sleep(FADE_POLL_INTERVAL);
+
+            let guard = state.read();
+            if guard.fade_generation != self.generation {
+                return;
+            }
+            if !guard.fade_out_pending || started.elapsed() >= self.deadline {
+                break;
+            }
+        }
+
+        let mut guard = state.write();
+        if guard.fade_generation != self.generation {
+            return;
+        }
+        if guard.fade_out_pending {
+            // The callback stalled; finish the transition here
+            guard.fade_gain = 0.0;
+            guard.fade_step = 0.0;
+            AudioEngine::finish_fade_out(&mut guard);
+            guard.publish_status();
+        }
+        // Hold the lock so a concurrent play() can't be undone
+        let _ = self.stream.pause();
+    }
+}
+
+/// Thread pausing the output stream after fade-outs
+///
+/// Pauses run one after another; a superseded one exits within a poll
+/// interval. Pauses still queued are abandoned once the engine bumps the
+/// fade generation. Joined when dropped.
+struct FadeWorker {
+    pauses: Option<crossbeam::channel::Sender<FadePause>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FadeWorker {
+    fn start(state: Arc<RwLock<AudioEngineState>>) -> Self {
+        let (pauses, pending) = crossbeam::channel::unbounded::<FadePause>();
+        let thread = std::thread::spawn(move || {
+            for pause in pending {
+                pause.run(&state);
+            }
+        });
+        Self {
+            pauses: Some(pauses),
+            thread: Some(thread),
+        }
+    }
+
+    fn pause_after_fade(&self, pause: FadePause) {
+        if let Some(pauses) = &self.pauses {
+            let _ = pauses.send(pause);
+        }
+    }
+}
+
+impl Drop for FadeWorker {
+    fn drop(&mut self) {
+        self.pauses.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 /// Internal audio engine state
 struct AudioEngineState {
     /// Current playback state
     state: PlaybackState,
-    /// Current volume (0.0 to 1.0)
+    /// Current volume (0.0 to 1.0), as ramped by the callback
     volume: f32,
-    /// Volume before mute (for unmute restoration)
-    volume_before_mute: f32,
-    /// Whether audio is muted
-    is_muted: bool,
     /// Target volume for ramping
     target_volume: f32,
     /// Volume ramp step per sample
@@ -132,12 +771,190 @@ struct AudioEngineState {
     duration: Option<u64>,
     /// Current audio format
     format: Option<AudioFormat>,
+    /// Properties of the loaded source file (bit depth, codec, lossless)
+    source_info: Option<AudioFormatInfo>,
+    /// Clipping found in the decoded source (buffer playback only)
+    clip_stats: Option<ClipStats>,
+    /// Path of the loaded file
+    current_path: Option<PathBuf>,
     /// Audio buffer (for non-streaming playback)
     buffer: Option<AudioBuffer>,
     /// Ring buffer consumer (for streaming playback)
     ring_buffer_consumer: Option<RingBufferConsumer>,
-    /// Event callback
-    callback: Option<AudioCallback>,
+    /// Event and meter callbacks (also held by the engine)
+    callbacks: Arc<EngineCallbacks>,
+    /// Playback rate (1.0 = normal speed)
+    playback_rate: f64,
+    /// Time stretcher (present when playback rate is not 1.0)
+    time_stretcher: Option<TimeStretcher>,
+    /// Playback queue
+    queue: PlayQueue,
+    /// Next track decoded ahead of time for gapless transitions
+    next_track: Option<PreparedTrack>,
+    /// Whether `next_track` was decoded by `prefetch_next` rather than the
+    /// automatic check, which then leaves it alone
+    next_track_requested: bool,
+    /// Play/stop fade gain (0.0 to 1.0), applied on top of the volume
+    fade_gain: f32,
+    /// Fade gain step per sample
+    fade_step: f32,
+    /// Whether a pause/stop fade-out is still rendering audio
+    fade_out_pending: bool,
+    /// Incremented on every play/pause/stop to invalidate deferred pauses
+    fade_generation: u64,
+    /// Whether leading/trailing silence is skipped on load
+    skip_silence: bool,
+    /// Whether silence is only trimmed at album boundaries
+    album_trim: bool,
+    /// Album of each queued file, read when album trimming is enabled
+    album_keys: HashMap<PathBuf, Option<AlbumKey>>,
+    /// Manual gain of individual files in dB, on top of normalization
+    track_gains: HashMap<PathBuf, f64>,
+    /// Current manual track gain (linear), as ramped by the callback
+    track_gain: f64,
+    /// Manual track gain being ramped to (linear)
+    track_gain_target: f64,
+    /// Track gain ramp step per sample
+    track_gain_step: f64,
+    /// ReplayGain of the current track in dB, ramped in with the manual gain
+    replay_gain_db: f64,
+    /// Where ReplayGain of newly loaded tracks comes from
+    replaygain_source: ReplayGainSource,
+    /// Whether album gain is preferred over track gain
+    replaygain_album: bool,
+    /// Stored loudness gains for `ReplayGainSource::Analyzed`
+    replaygain_lookup: Option<Arc<dyn ReplayGainLookup>>,
+    /// Normalization applied to tracks decoded for buffer playback
+    normalization: NormalizationMode,
+    /// Silence inserted before a queued track starts, in milliseconds
+    inter_track_gap_ms: u32,
+    /// Frames of inter-track silence still to be rendered
+    gap_remaining: u64,
+    /// Effective playback range in frames (start, exclusive end)
+    play_range: Option<(u64, u64)>,
+    /// Slice of the buffer played as a virtual track (start, exclusive end)
+    virtual_range: Option<(u64, u64)>,
+    /// Fade at the edges of virtual tracks in milliseconds (0 = hard cut)
+    virtual_track_fade_ms: u32,
+    /// Extra sources (e.g. previews) mixed over the main playback
+    mixer: Mixer,
+    /// Mixer source of the scrub preview playing, see `preview_region`
+    scrub_preview: Option<MixerSourceId>,
+    /// Loop points as requested (start, exclusive end), in frames
+    loop_request: Option<(u64, u64)>,
+    /// Loop points in effect, after zero-crossing snapping
+    loop_points: Option<(u64, u64)>,
+    /// Whether loop points snap to the nearest zero crossing
+    loop_snap: bool,
+    /// Stereo balance (-1.0 = left only, 0.0 = center, 1.0 = right only)
+    balance: f32,
+    /// Active equalizer preset
+    eq_preset: Option<EqPreset>,
+    /// Equalizer chain built from `eq_preset`
+    equalizer: Option<Equalizer>,
+    /// Subsonic high-pass ahead of the equalizer, with its corner in Hz
+    rumble_filter: Option<(f64, Equalizer)>,
+    /// Loudness compensation shelves, with the volume they are tuned for
+    loudness_compensation: Option<(f32, Equalizer)>,
+    /// Reused chunk of source samples fed to the time stretcher
+    stretch_chunk: Vec<f64>,
+    /// Reused block of time-stretched samples
+    stretch_output: Vec<f64>,
+    /// Frequency weighting of the metered RMS
+    meter_weighting: FrequencyWeighting,
+    /// Weighting filters, one cascade per channel, with the (rate,
+    /// channels) they were built for
+    meter_weighting_filters: (Vec<Biquad>, u32, u16),
+    /// Peak and clip hold carried between metered blocks
+    meter_hold: MeterHold,
+    /// Events raised while rendering, sent once the lock is released
+    render_events: Vec<AudioEvent>,
+    /// Whether NaN/Inf source samples are replaced with silence
+    sanitize_samples: bool,
+    /// Whether denormal source samples are flushed to zero
+    flush_denormals: bool,
+    /// Soft clipper applied after the equalizer
+    saturator: Option<Saturator>,
+    /// Mid/side width applied to stereo output
+    stereo_width: StereoWidth,
+    /// Sample rate of the output stream, when one is open
+    output_sample_rate: Option<u32>,
+    /// Converter from the source rate to `output_sample_rate`
+    resampler: Option<OutputResampler>,
+    /// Channel count of the output stream, when one is open
+    output_channels: Option<u16>,
+    /// Reused buffers for remixing to `output_channels`
+    remix: OutputRemix,
+    /// Volume commands and published status (shared with `AudioEngine`)
+    controls: Arc<PlaybackControls>,
+}
+
+/// Notifications raised while rendering one block
+struct BlockEvents {
+    /// State changes and underruns, in the order they happened
+    events: Vec<AudioEvent>,
+    /// Position playback wrapped back to, if it looped
+    looped_to: Option<u64>,
+    /// Output levels, if a meter callback is set
+    levels: Option<MeterLevels>,
+}
+
+impl BlockEvents {
+    /// Check if there is nothing to report
+    fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.looped_to.is_none() && self.levels.is_none()
+    }
+}
+
+/// Channel count and sample rate a block is rendered for (`None` = the source's)
+#[derive(Debug, Clone, Copy)]
+struct OutputLayout {
+    channels: Option<u16>,
+    sample_rate: Option<u32>,
+}
+
+/// Scratch buffers for mapping rendered audio to the stream's channel count
+#[derive(Default)]
+struct OutputRemix {
+    /// Source-layout render target
+    rendered: Vec<f64>,
+    /// Remixed output
+    remixed: Vec<f64>,
+}
+
+/// Converts audio rendered at the source rate to the stream's rate
+struct OutputResampler {
+    converter: SampleRateConverter,
+    /// Reused source-rate render target
+    rendered: Vec<f64>,
+}
+
+impl OutputResampler {
+    /// Build the converter and its scratch buffers
+    ///
+    /// Tabulating the sinc kernel is too slow for the audio callback, so
+    /// this only runs on control threads.
+    fn new(source_rate: u32, output_rate: u32, channels: u16) -> Self {
+        let samples = SCRATCH_BLOCK_FRAMES * channels as usize;
+        let mut converter = SampleRateConverter::with_quality(
+            source_rate,
+            output_rate,
+            channels as usize,
+            ResampleQuality::Sinc,
+        );
+        converter.reserve(SCRATCH_BLOCK_FRAMES);
+        Self {
+            converter,
+            rendered: Vec::with_capacity(samples),
+        }
+    }
+
+    /// Check if the converter matches a source and output configuration
+    fn converts(&self, source: &AudioFormat, output_rate: u32) -> bool {
+        self.converter.source_rate() == source.sample_rate
+            && self.converter.target_rate() == output_rate
+            && self.converter.channels() == source.channels as usize
+    }
 }
 
 impl Default for AudioEngineState {
@@ -145,903 +962,1111 @@ impl Default for AudioEngineState {
         Self {
             state: PlaybackState::Stopped,
             volume: 1.0,
-            volume_before_mute: 1.0,
-            is_muted: false,
             target_volume: 1.0,
             volume_ramp_step: 0.0,
             position: 0,
             duration: None,
             format: None,
+            source_info: None,
+            clip_stats: None,
+            current_path: None,
             buffer: None,
             ring_buffer_consumer: None,
-            callback: None,
+            callbacks: Arc::new(EngineCallbacks::default()),
+            playback_rate: 1.0,
+            time_stretcher: None,
+            queue: PlayQueue::new(),
+            next_track: None,
+            next_track_requested: false,
+            fade_gain: 1.0,
+            fade_step: 0.0,
+            fade_out_pending: false,
+            fade_generation: 0,
+            skip_silence: false,
+            album_trim: false,
+            album_keys: HashMap::new(),
+            track_gains: HashMap::new(),
+            replay_gain_db: 0.0,
+            replaygain_source: ReplayGainSource::Off,
+            replaygain_album: false,
+            replaygain_lookup: None,
+            track_gain: 1.0,
+            track_gain_target: 1.0,
+            track_gain_step: 0.0,
+            normalization: NormalizationMode::Off,
+            inter_track_gap_ms: 0,
+            gap_remaining: 0,
+            play_range: None,
+            virtual_range: None,
+            virtual_track_fade_ms: 0,
+            mixer: Mixer::default(),
+            scrub_preview: None,
+            loop_request: None,
+            loop_points: None,
+            loop_snap: false,
+            balance: 0.0,
+            eq_preset: None,
+            equalizer: None,
+            rumble_filter: None,
+            loudness_compensation: None,
+            stretch_chunk: Vec::new(),
+            stretch_output: Vec::new(),
+            meter_weighting: FrequencyWeighting::None,
+            meter_hold: MeterHold::default(),
+            meter_weighting_filters: (Vec::new(), 0, 0),
+            render_events: Vec::new(),
+            sanitize_samples: true,
+            flush_denormals: true,
+            saturator: None,
+            stereo_width: StereoWidth::default(),
+            output_sample_rate: None,
+            resampler: None,
+            output_channels: None,
+            remix: OutputRemix::default(),
+            controls: Arc::new(PlaybackControls::default()),
         }
     }
 }
 
-/// Audio engine for high-fidelity playback
-pub struct AudioEngine {
-    /// Internal state protected by RwLock for thread safety
-    state: Arc<RwLock<AudioEngineState>>,
-    /// CPAL host for audio device management
-    host: Host,
-    /// CPAL audio device
-    device: Option<Device>,
-    /// CPAL audio stream
-    stream: Option<Stream>,
-    /// Stream configuration
-    stream_config: Option<StreamConfig>,
+/// Album grouping of a track for album-aware silence trimming
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AlbumKey {
+    /// Album name
+    album: String,
+    /// Album artist, or the track artist if there is none
+    artist: Option<String>,
 }
 
-impl AudioEngine {
-    /// Create a new audio engine
-    pub fn new() -> Result<Self> {
-        let host = cpal::default_host();
-        Ok(Self {
-            state: Arc::new(RwLock::new(AudioEngineState::default())),
-            host,
-            device: None,
-            stream: None,
-            stream_config: None,
+impl AlbumKey {
+    /// Read the album a file is tagged with (`None` if untagged or unreadable)
+    fn read(path: &Path) -> Option<Self> {
+        let tags = crate::audio::decoder::read_tags(path).ok()?;
+        Some(Self {
+            album: tags.album?,
+            artist: tags.album_artist.or(tags.artist),
         })
     }
 
-    /// Load a file with ring buffer streaming
-    pub fn load_file_with_ring_buffer<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
-        let path = path.as_ref();
+    /// Read the albums of `paths`
+    fn read_all(paths: &[PathBuf]) -> HashMap<PathBuf, Option<Self>> {
+        paths
+            .iter()
+            .map(|path| (path.clone(), Self::read(path)))
+            .collect()
+    }
+}
 
-        // Validate file path
-        if !path.exists() {
-            return Err(crate::Error::Io(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("File not found: {}", path.display()),
-            )));
+/// Where playback gain of a track comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ReplayGainSource {
+    /// No ReplayGain
+    #[default]
+    Off,
+    /// `REPLAYGAIN_*` tags of the file only
+    Tags,
+    /// Tags, falling back to the loudness measurements of the engine's
+    /// `ReplayGainLookup`
+    Analyzed,
+}
+
+/// Loudness-based gains for tracks without ReplayGain tags
+///
+/// Consulted by `ReplayGainSource::Analyzed` as each track is loaded, so
+/// both methods must return quickly. `library::LoudnessLookup` serves the
+/// measurements of a `LibraryDb`.
+pub trait ReplayGainLookup: Send + Sync {
+    /// Gain of `path` in dB, album gain first if `album` is set (`None` if
+    /// the track hasn't been measured)
+    fn gain_db(&self, path: &Path, album: bool) -> Option<f64>;
+
+    /// Ask for `path` to be measured, as it was loaded without a known gain
+    ///
+    /// The measurement is used from the next time the track is loaded.
+    fn request_analysis(&self, _path: &Path) {}
+}
+
+/// ReplayGain settings copied out of the state, for resolving a track's
+/// gain without holding the lock
+#[derive(Clone)]
+struct ReplayGainSettings {
+    /// Where gains come from
+    source: ReplayGainSource,
+    /// Whether album gain is preferred over track gain
+    album: bool,
+    /// Stored loudness gains
+    lookup: Option<Arc<dyn ReplayGainLookup>>,
+}
+
+impl ReplayGainSettings {
+    fn new(state: &AudioEngineState) -> Self {
+        Self {
+            source: state.replaygain_source,
+            album: state.replaygain_album,
+            lookup: state.replaygain_lookup.clone(),
         }
+    }
 
-        // Check if format is supported
-        if !crate::audio::decoder::is_format_supported(path) {
-            return Err(crate::Error::Decoding(format!(
-                "Unsupported file format: {}",
-                path.extension()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("unknown")
-            )));
+    /// Gain of `path` in dB (`None` if unknown or ReplayGain is off)
+    ///
+    /// `tags` are the ReplayGain tags its decoder read.
+    fn gain_db(&self, path: &Path, tags: ReplayGainTags) -> Option<f64> {
+        let from_tags = tags.gain_db(self.album);
+        match self.source {
+            ReplayGainSource::Off => None,
+            ReplayGainSource::Tags => from_tags,
+            ReplayGainSource::Analyzed => from_tags.or_else(|| {
+                let lookup = self.lookup.as_ref()?;
+                let gain = lookup.gain_db(path, self.album);
+                if gain.is_none() {
+                    lookup.request_analysis(path);
+                }
+                gain
+            }),
         }
+    }
+}
 
-        // Create ring buffer stream reader
-        let (stream_reader, consumer) =
-            crate::audio::decoder::create_ring_buffer_stream_reader(path).map_err(|e| {
-                self.update_state(|state| {
-                    state.state = PlaybackState::Error;
-                    Some(AudioEvent::Error(format!(
-                        "Failed to create stream reader: {}",
-                        e
-                    )))
-                });
-                e
-            })?;
+/// Decoded region of the current track played by `preview_region`
+struct ScrubPreview {
+    /// Interleaved samples at the output's rate and channel count
+    samples: Vec<f64>,
+    /// Samples handed to the mixer so far
+    read: usize,
+}
 
-        // Get format and duration information
-        let audio_format = stream_reader.format().map_err(|e| {
-            self.update_state(|state| {
-                state.state = PlaybackState::Error;
-                Some(AudioEvent::Error(format!("Failed to get format: {}", e)))
-            });
-            e
-        })?;
-
-        let duration = stream_reader.duration().map_err(|e| {
-            self.update_state(|state| {
-                state.state = PlaybackState::Error;
-                Some(AudioEvent::Error(format!("Failed to get duration: {}", e)))
-            });
-            e
-        })?;
-
-        // Update state with streaming setup
-        self.update_state(|state| {
-            state.state = PlaybackState::Stopped;
-            state.position = 0;
-            state.duration = duration;
-            state.format = Some(audio_format.clone());
-            state.buffer = None; // Clear regular buffer
-            state.ring_buffer_consumer = Some(consumer);
-            Some(AudioEvent::StateChanged(PlaybackState::Stopped))
-        });
-
-        // Initialize default device if not set
-        if self.device.is_none() {
-            self.init_default_device().map_err(|e| {
-                self.update_state(|state| {
-                    state.state = PlaybackState::Error;
-                    Some(AudioEvent::Error(format!(
-                        "Failed to initialize device: {}",
-                        e
-                    )))
-                });
-                e
-            })?;
+impl ScrubPreview {
+    /// Decode `frames` frames from `start` of `path` and sum them to mono
+    ///
+    /// Positions are in frames at `rate`; the region is converted to the
+    /// file's own rate (the current track may have been resampled) and the
+    /// result back to `rate`, spread over `channels` output channels. Only
+    /// the packets covering the region are decoded.
+    fn decode(path: &Path, start: u64, frames: u64, rate: u32, channels: u16) -> Result<Self> {
+        let mut decoder = crate::audio::decoder::AudioDecoder::new(path)?;
+        let file_rate = decoder.format().sample_rate;
+        let to_file =
+            |frames: u64| (frames as u128 * file_rate as u128 / rate.max(1) as u128) as u64;
+        let (start, end) = (to_file(start), to_file(start + frames));
+        decoder.seek(start)?;
+
+        let mut mono = Vec::with_capacity((end - start) as usize);
+        while let Some(packet) = decoder.decode_next()? {
+            let packet_channels = packet.format.channels.max(1) as usize;
+            for (i, frame) in packet.samples.chunks(packet_channels).enumerate() {
+                let position = packet.timestamp_samples + i as u64;
+                if (start..end).contains(&position) {
+                    mono.push(frame.iter().sum::<f64>() / packet_channels as f64);
+                }
+            }
+            if packet.timestamp_samples + packet.frames as u64 >= end {
+                break;
+            }
         }
 
-        // Initialize output stream with the audio format
-        self.init_output_stream(&audio_format).map_err(|e| {
-            self.update_state(|state| {
-                state.state = PlaybackState::Error;
-                Some(AudioEvent::Error(format!(
-                    "Failed to initialize stream: {}",
-                    e
-                )))
-            });
-            e
-        })?;
+        let mut mono =
+            AudioBuffer::with_data(AudioFormat::new(file_rate, 1, SampleFormat::F64), mono);
+        if file_rate != rate {
+            mono = mono.resample(rate, ResampleQuality::Sinc);
+        }
+        let mut mono = mono.data().to_vec();
+        let fade = (rate as usize * PREVIEW_FADE_MS as usize / 1000).min(mono.len());
+        let fade_start = mono.len() - fade;
+        for (i, sample) in mono[fade_start..].iter_mut().enumerate() {
+            *sample *= 1.0 - (i + 1) as f64 / fade as f64;
+        }
 
-        // Store the stream reader (we need to keep it alive)
-        // For now, we'll let it run in the background
-        // TODO: Store stream reader reference for proper cleanup
-        std::mem::forget(stream_reader); // Prevent drop for now
+        let channels = channels.max(1) as usize;
+        Ok(Self {
+            samples: mono
+                .iter()
+                .flat_map(|&sample| std::iter::repeat_n(sample, channels))
+                .collect(),
+            read: 0,
+        })
+    }
+}
 
-        Ok(())
+impl MixerSource for ScrubPreview {
+    fn read_samples(&mut self, output: &mut [f64]) -> usize {
+        let count = output.len().min(self.samples.len() - self.read);
+        output[..count].copy_from_slice(&self.samples[self.read..self.read + count]);
+        self.read += count;
+        count
     }
+}
+
+/// A fully decoded track ready to become the current source
+struct PreparedTrack {
+    /// Source file path
+    path: PathBuf,
+    /// Decoded audio
+    buffer: AudioBuffer,
+    /// Decoded format
+    format: AudioFormat,
+    /// Duration in samples
+    duration: Option<u64>,
+    /// Source file properties
+    source_info: Option<AudioFormatInfo>,
+    /// Clipping found in the decoded audio
+    clip_stats: ClipStats,
+    /// ReplayGain in dB
+    replay_gain_db: f64,
+}
+
+impl PreparedTrack {
+    /// Decode a file completely, applying `normalization`, and look up its
+    /// ReplayGain
+    fn decode(
+        path: &Path,
+        normalization: NormalizationMode,
+        replay_gain: &ReplayGainSettings,
+    ) -> Result<Self> {
+        let mut decoder = crate::audio::decoder::AudioDecoder::new(path)?;
+        let format = decoder.format().clone();
+        let duration = decoder.duration();
+        let replay_gain_db = replay_gain
+            .gain_db(path, decoder.replay_gain())
+            .unwrap_or(0.0);
+        let mut buffer = decoder.decode_all()?;
+        let clip_stats = source_clip_stats(&buffer, &format);
+        normalization.apply(buffer.data_mut(), &format);
 
-    /// Initialize the audio engine with a specific device
-    pub fn with_device(device: Device) -> Result<Self> {
-        let host = cpal::default_host();
         Ok(Self {
-            state: Arc::new(RwLock::new(AudioEngineState::default())),
-            host,
-            device: Some(device),
-            stream: None,
-            stream_config: None,
+            path: path.to_path_buf(),
+            buffer,
+            format,
+            duration,
+            source_info: decoder.format_info().ok(),
+            clip_stats,
+            replay_gain_db,
         })
     }
 
-    /// Get the CPAL host
-    pub fn host(&self) -> &Host {
-        &self.host
+    /// Convert the decoded audio to `sample_rate` with the sinc resampler
+    ///
+    /// `source_info` keeps describing the file; `format` and `duration`
+    /// follow the converted buffer.
+    fn resample_to(self, sample_rate: u32) -> Self {
+        if sample_rate == 0 || sample_rate == self.format.sample_rate {
+            return self;
+        }
+        let buffer = self.buffer.resample(sample_rate, ResampleQuality::Sinc);
+        Self {
+            format: buffer.format().clone(),
+            duration: Some(buffer.frames() as u64),
+            buffer,
+            ..self
+        }
     }
+}
 
-    /// Get the current audio device
-    pub fn device(&self) -> Option<&Device> {
-        self.device.as_ref()
+/// Replace samples that would harm the output or slow down DSP
+///
+/// NaN/Inf become 0.0 when `sanitize` is set; values too small to be a
+/// normal f32 become 0.0 when `flush` is set.
+#[inline]
+fn sanitize_sample(sample: f64, (sanitize, flush): (bool, bool)) -> f64 {
+    if sanitize && !sample.is_finite() {
+        return 0.0;
     }
+    if flush && sample.abs() < f32::MIN_POSITIVE as f64 {
+        return 0.0;
+    }
+    sample
+}
 
-    /// Set a new audio device
-    pub fn set_device(&mut self, device: Device) -> Result<()> {
-        // Stop current stream if running
-        if self.stream.is_some() {
-            self.stop()?;
+/// Frame within `window` frames of `frame` where the downmixed signal
+/// crosses zero, nearest first
+///
+/// At a sign change the frame closer to zero is taken. Returns `frame`
+/// itself if the signal doesn't cross zero within the window.
+fn nearest_zero_crossing(samples: &[f64], channels: u16, frame: u64, window: u64) -> u64 {
+    let channels = channels.max(1) as usize;
+    let frames = (samples.len() / channels) as u64;
+    let mono = |frame: u64| {
+        let start = frame as usize * channels;
+        samples[start..start + channels].iter().sum::<f64>() / channels as f64
+    };
+    // Sign change between `frame - 1` and `frame`, at the quieter of the two
+    let crossing = |frame: u64| {
+        if frame == 0 || frame >= frames {
+            return None;
         }
+        let (before, at) = (mono(frame - 1), mono(frame));
+        if at == 0.0 {
+            Some(frame)
+        } else if before.signum() != at.signum() {
+            Some(if before.abs() < at.abs() {
+                frame - 1
+            } else {
+                frame
+            })
+        } else {
+            None
+        }
+    };
 
-        self.device = Some(device);
-        self.stream = None;
-        self.stream_config = None;
-
-        Ok(())
-    }
+    (0..=window)
+        .flat_map(|distance| [frame.checked_sub(distance), frame.checked_add(distance)])
+        .flatten()
+        .find_map(crossing)
+        .unwrap_or(frame)
+}
 
-    /// Initialize the default audio device
-    pub fn init_default_device(&mut self) -> Result<()> {
-        let device = self.host.default_output_device().ok_or_else(|| {
-            crate::Error::AudioDevice("No default output device available".to_string())
-        })?;
+/// Gain of `frame` under a virtual track edge fade (see `virtual_fade`)
+///
+/// Ramps linearly from silence at the slice's first frame and back to
+/// silence at its last.
+#[inline]
+fn edge_fade_gain(fade: Option<(u64, u64, u64)>, frame: u64) -> f64 {
+    let Some((start, end, frames)) = fade else {
+        return 1.0;
+    };
+    let from_edge = frame
+        .saturating_sub(start)
+        .min(end.saturating_sub(frame + 1));
+    (from_edge as f64 / frames as f64).min(1.0)
+}
 
-        self.set_device(device)
+impl AudioEngineState {
+    /// Apply a volume change posted to the controls, if any
+    fn apply_volume_command(&mut self) {
+        if let Some((target, step)) = self.controls.take_volume_command() {
+            self.target_volume = target;
+            self.volume_ramp_step = step;
+            self.volume = if step == 0.0 {
+                target
+            } else {
+                // Where the step was sized from, which may be an instant
+                // change this command replaced before it was applied
+                self.controls.volume()
+            };
+        }
     }
 
-    /// Initialize audio output stream
-    pub fn init_output_stream(&mut self, format: &AudioFormat) -> Result<()> {
-        let device = self
-            .device
-            .as_ref()
-            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
-
-        // Validate the format first
-        crate::audio::format::validate_format(format)
-            .map_err(|e| crate::Error::AudioFormat(format!("Invalid format: {}", e)))?;
+    /// Add the frames played from a read-only view to the position
+    fn apply_shared_frames(&mut self) {
+        let frames = self.controls.shared_frames.swap(0, Ordering::AcqRel);
+        if frames > 0 {
+            let position = self.position + frames;
+            self.position = self.buffer_end().map_or(position, |end| position.min(end));
+        }
+    }
 
-        // Get supported configurations
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
+    /// Publish volume, position and state for lock-free reading
+    fn publish_status(&self) {
+        let controls = &self.controls;
+        if !controls.volume_pending() {
+            controls
+                .volume
+                .store(self.volume.to_bits(), Ordering::Release);
+        }
+        controls.position.store(self.position, Ordering::Release);
+        controls
+            .state
+            .store(playback_state_to_u8(self.state), Ordering::Release);
+        let sample_rate = self.format.as_ref().map_or(0, |f| f.sample_rate);
+        controls.sample_rate.store(sample_rate, Ordering::Release);
+    }
 
-        // Find a compatible configuration
-        let config = self.find_compatible_config(supported_configs, format)?;
+    /// Inter-track gap length in frames at the current format's rate
+    fn inter_track_gap_frames(&self) -> u64 {
+        let rate = self.format.as_ref().map_or(0, |f| f.sample_rate) as u64;
+        self.inter_track_gap_ms as u64 * rate / 1000
+    }
 
-        // Create the stream configuration
-        let stream_config = config.into();
+    /// Sanitization flags passed to `sanitize_sample`
+    fn sample_guard(&self) -> (bool, bool) {
+        (self.sanitize_samples, self.flush_denormals)
+    }
 
-        // Create the output stream with error handling
-        let state_clone = self.state.clone();
-        let stream = device
-            .build_output_stream(
-                &stream_config,
-                move |data: &mut [f32], _: &OutputCallbackInfo| {
-                    Self::audio_callback(data, &state_clone);
-                },
-                move |err| {
-                    eprintln!("Audio stream error: {}", err);
-                    // Note: We can't easily propagate errors from this callback
-                    // The main error handling happens in the safe_stream_operation wrapper
-                },
-                None, // No timeout
-            )
-            .map_err(|e| {
-                crate::Error::AudioDevice(format!("Failed to build output stream: {}", e))
-            })?;
+    /// Make a prepared track the current buffer source at position 0
+    fn apply_prepared_track(&mut self, track: PreparedTrack) {
+        self.duration = track.duration;
+        self.format = Some(track.format);
+        self.source_info = track.source_info;
+        self.clip_stats = Some(track.clip_stats);
+        self.current_path = Some(track.path);
+        self.buffer = Some(track.buffer);
+        self.replay_gain_db = track.replay_gain_db;
+        self.ring_buffer_consumer = None;
+        self.virtual_range = None;
+        self.clear_loop_points();
+        self.update_play_range();
+        self.position = self.range_start();
+        self.reset_time_stretcher();
+        self.retarget_track_gain();
+    }
 
-        self.stream = Some(stream);
-        self.stream_config = Some(stream_config);
+    /// Ramp the track gain towards the current file's manual gain plus its
+    /// ReplayGain
+    ///
+    /// Files without either play at unity.
+    fn retarget_track_gain(&mut self) {
+        let gain_db = self
+            .current_path
+            .as_ref()
+            .and_then(|path| self.track_gains.get(path))
+            .copied()
+            .unwrap_or(0.0)
+            + self.replay_gain_db;
+        self.track_gain_target = 10f64.powf(gain_db / 20.0);
+
+        let ramp_samples = self.format.as_ref().map_or(0.0, |format| {
+            format.sample_rate as f64 * format.channels as f64 * TRACK_GAIN_RAMP_MS as f64 / 1000.0
+        });
+        if ramp_samples < 1.0 {
+            self.track_gain = self.track_gain_target;
+            self.track_gain_step = 0.0;
+        } else {
+            self.track_gain_step = (self.track_gain_target - self.track_gain) / ramp_samples;
+        }
+    }
 
-        Ok(())
+    /// Whether `other` is tagged with the same album as the current track
+    fn shares_album(&self, other: Option<&PathBuf>) -> bool {
+        let album = |path: Option<&PathBuf>| path.and_then(|p| self.album_keys.get(p)?.as_ref());
+        match (album(self.current_path.as_ref()), album(other)) {
+            (Some(current), Some(other)) => current == other,
+            _ => false,
+        }
     }
 
-    /// Find a compatible CPAL configuration for the given audio format
-    fn find_compatible_config(
-        &self,
-        supported_configs: cpal::SupportedOutputConfigs,
-        target_format: &AudioFormat,
-    ) -> Result<cpal::SupportedStreamConfig> {
-        let mut best_match: Option<cpal::SupportedStreamConfig> = None;
-        let mut best_score = 0;
+    /// Which edges of the current track may be trimmed (head, tail)
+    ///
+    /// With album trimming an edge is kept when the neighbouring queue item
+    /// on that side belongs to the same album.
+    fn trimmable_edges(&self) -> (bool, bool) {
+        if !self.album_trim {
+            return (self.skip_silence, self.skip_silence);
+        }
+        if self.current_path.is_none() || self.queue.current() != self.current_path.as_ref() {
+            // Not playing from the queue, so the track stands alone
+            return (true, true);
+        }
+        (
+            !self.shares_album(self.queue.peek_previous()),
+            !self.shares_album(self.queue.peek_on_track_end()),
+        )
+    }
 
-        for config in supported_configs {
-            let score = self.calculate_format_compatibility_score(&config, target_format);
+    /// Forget the loop points of the previous track
+    fn clear_loop_points(&mut self) {
+        self.loop_request = None;
+        self.loop_points = None;
+    }
 
-            if score > best_score {
-                // Check if sample rate is supported
-                if config.min_sample_rate() <= target_format.sample_rate
-                    && target_format.sample_rate <= config.max_sample_rate()
-                {
-                    // Check if channel count is supported
-                    if config.channels() == target_format.channels {
-                        best_match = Some(config.with_sample_rate(target_format.sample_rate));
-                        best_score = score;
+    /// Recompute the loop points in effect from the requested ones
+    fn update_loop_points(&mut self) {
+        self.loop_points = self.loop_request.map(|(start, end)| {
+            match (self.loop_snap, &self.buffer, &self.format) {
+                (true, Some(buffer), Some(format)) => {
+                    let window = format.sample_rate as u64 * LOOP_SNAP_WINDOW_MS as u64 / 1000;
+                    let channels = format.channels.max(1);
+                    let snapped = (
+                        nearest_zero_crossing(buffer.data(), channels, start, window),
+                        nearest_zero_crossing(buffer.data(), channels, end, window),
+                    );
+                    if snapped.0 < snapped.1 {
+                        snapped
+                    } else {
+                        (start, end)
                     }
                 }
+                _ => (start, end),
             }
-        }
-
-        best_match.ok_or_else(|| {
-            crate::Error::AudioFormat(format!(
-                "No compatible audio configuration found for {}Hz, {} channels",
-                target_format.sample_rate, target_format.channels
-            ))
-        })
+        });
     }
 
-    /// Calculate compatibility score between a CPAL config and target format
-    fn calculate_format_compatibility_score(
-        &self,
-        config: &cpal::SupportedStreamConfigRange,
-        target_format: &AudioFormat,
-    ) -> u32 {
-        let mut score = 0;
-
-        // Prefer exact sample rate match
-        if config.min_sample_rate() <= target_format.sample_rate
-            && target_format.sample_rate <= config.max_sample_rate()
-        {
-            score += 100;
+    /// Whether playback wraps to the range start instead of ending
+    ///
+    /// Looping is `RepeatMode::One`, so the queue never advances past a
+    /// looping track.
+    fn loops(&self) -> bool {
+        self.queue.repeat_mode() == RepeatMode::One
+    }
 
-            // Bonus for exact sample rate match at boundaries
-            if config.min_sample_rate() == target_format.sample_rate
-                || config.max_sample_rate() == target_format.sample_rate
-            {
-                score += 50;
-            }
+    /// Queue item to decode ahead of the current track's end
+    ///
+    /// `None` while looping, as the track then wraps in place.
+    fn upcoming_track(&self) -> Option<&PathBuf> {
+        if self.loops() {
+            return None;
         }
+        self.queue.upcoming(1).first().copied()
+    }
 
-        // Prefer exact channel count match
-        if config.channels() == target_format.channels {
-            score += 100;
+    /// Recompute the playback range from the loop points or the buffer's
+    /// silent edges
+    fn update_play_range(&mut self) {
+        self.play_range = None;
+        if let Some(points) = self.loop_points.filter(|_| self.loops()) {
+            // Looping between loop points ignores trimming and slices
+            self.play_range = Some(points);
+            return;
         }
-
-        // Prefer higher sample rates for high-resolution audio
-        if target_format.is_high_resolution() {
-            if config.max_sample_rate() >= 96000 {
-                score += 25;
-            }
-            if config.max_sample_rate() >= 192000 {
-                score += 25;
-            }
+        if let Some(range) = self.virtual_range {
+            // A virtual track plays its slice exactly, untrimmed
+            self.play_range = Some(range);
+            return;
         }
-
-        // Prefer configurations that support common sample rates
-        let common_rates = [44100, 48000, 96000, 192000];
-        for &rate in &common_rates {
-            if config.min_sample_rate() <= rate && rate <= config.max_sample_rate() {
-                score += 10;
-            }
+        let (trim_head, trim_tail) = self.trimmable_edges();
+        if !trim_head && !trim_tail {
+            return;
         }
 
-        score
-    }
+        let (buffer, format) = match (&self.buffer, &self.format) {
+            (Some(buffer), Some(format)) => (buffer, format),
+            _ => return,
+        };
 
-    /// Negotiate the best audio format for the current device
-    pub fn negotiate_format(&self, preferred_format: &AudioFormat) -> Result<AudioFormat> {
-        let device = self
-            .device
-            .as_ref()
-            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+        let channels = format.channels.max(1);
+        let frames = (buffer.data().len() / channels as usize) as u64;
+        let Some((first, last)) = detect_silence_bounds(
+            buffer.data(),
+            channels,
+            format.sample_rate,
+            SKIP_SILENCE_THRESHOLD_DBFS,
+        ) else {
+            // Entirely silent; nothing sensible to trim to
+            return;
+        };
 
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
+        let min_frames = (SKIP_SILENCE_MIN_SECONDS * format.sample_rate as f64) as u64;
+        let start = if trim_head && first >= min_frames {
+            first
+        } else {
+            0
+        };
+        let end = if trim_tail && frames - (last + 1) >= min_frames {
+            last + 1
+        } else {
+            frames
+        };
 
-        // Try to find exact match first
-        let supported_configs_vec: Vec<_> = supported_configs.collect();
-        for config in &supported_configs_vec {
-            if config.min_sample_rate() <= preferred_format.sample_rate
-                && preferred_format.sample_rate <= config.max_sample_rate()
-                && config.channels() == preferred_format.channels
-            {
-                return Ok(preferred_format.clone());
-            }
+        if start > 0 || end < frames {
+            self.play_range = Some((start, end));
         }
+    }
 
-        // If no exact match, find the best compatible format
-        let supported_configs_iter = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
-        let best_config = self.find_compatible_config(supported_configs_iter, preferred_format)?;
+    /// Edge fade of the current virtual track as (start, end, frames)
+    ///
+    /// The fade is limited to a quarter of the track so very short slices
+    /// still reach full level.
+    fn virtual_fade(&self) -> Option<(u64, u64, u64)> {
+        let (start, end) = self.virtual_range?;
+        let rate = self.format.as_ref()?.sample_rate as u64;
+        let frames = (self.virtual_track_fade_ms as u64 * rate / 1000).min((end - start) / 4);
+        (frames > 0).then_some((start, end, frames))
+    }
 
-        Ok(AudioFormat::new(
-            best_config.sample_rate(),
-            best_config.channels(),
-            crate::audio::format::SampleFormat::F32, // CPAL uses f32
-        ))
+    /// First frame of the playback range
+    fn range_start(&self) -> u64 {
+        self.play_range.map(|(start, _)| start).unwrap_or(0)
     }
 
-    /// Get the best available format for high-quality playback
-    pub fn get_best_format(&self) -> Result<AudioFormat> {
-        let device = self
-            .device
-            .as_ref()
-            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
-
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
-
-        let mut best_format: Option<AudioFormat> = None;
-        let mut best_sample_rate = 0;
-        let mut best_channels = 0;
-
-        for config in supported_configs {
-            let sample_rate = config.max_sample_rate();
-            let channels = config.channels();
-
-            // Prefer higher sample rates and more channels for quality
-            if sample_rate > best_sample_rate
-                || (sample_rate == best_sample_rate && channels > best_channels)
-            {
-                best_sample_rate = sample_rate;
-                best_channels = channels;
-                best_format = Some(AudioFormat::new(
-                    sample_rate,
-                    channels,
-                    crate::audio::format::SampleFormat::F32,
-                ));
-            }
+    /// Frame at which playback of the current track ends
+    fn playable_end(&self) -> Option<u64> {
+        match (self.play_range, self.duration) {
+            (Some((_, end)), Some(duration)) => Some(end.min(duration)),
+            (Some((_, end)), None) => Some(end),
+            (None, duration) => duration,
         }
-
-        best_format.ok_or_else(|| {
-            crate::Error::AudioDevice("No supported audio formats found".to_string())
-        })
     }
 
-    /// Check if a specific format is supported by the current device
-    pub fn is_format_supported(&self, format: &AudioFormat) -> Result<bool> {
-        let device = self
-            .device
-            .as_ref()
-            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
-
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
-
-        for config in supported_configs {
-            if config.min_sample_rate() <= format.sample_rate
-                && format.sample_rate <= config.max_sample_rate()
-                && config.channels() == format.channels
-            {
-                return Ok(true);
-            }
-        }
+    /// Frame at which buffer playback of the current track ends
+    ///
+    /// Like `playable_end`, but never past the decoded audio, so a wrong
+    /// duration can't leave playback running on silence. `None` unless a
+    /// buffer is loaded.
+    fn buffer_end(&self) -> Option<u64> {
+        let frames = self.buffer.as_ref()?.frames() as u64;
+        Some(self.playable_end().map_or(frames, |end| end.min(frames)))
+    }
 
-        Ok(false)
+    /// Left/right gains for the current balance; `None` at unity or for
+    /// non-stereo sources
+    fn balance_gains(&self) -> Option<[f64; 2]> {
+        let stereo = self.format.as_ref().is_some_and(|f| f.channels == 2);
+        (stereo && self.balance != 0.0).then(|| AudioProcessor::balance_gains(self.balance as f64))
     }
 
-    /// Audio callback function for CPAL stream
-    fn audio_callback(output: &mut [f32], state: &Arc<RwLock<AudioEngineState>>) {
-        let mut state_guard = match state.try_write() {
-            Some(guard) => guard,
-            None => {
-                // If we can't acquire the lock, fill with silence to avoid audio glitches
-                output.fill(0.0);
-                return;
-            }
+    /// Rebuild the time stretcher for the current rate and format
+    ///
+    /// Called whenever the source or playback position changes so no stale
+    /// audio from the previous position is played.
+    fn reset_time_stretcher(&mut self) {
+        self.time_stretcher = if self.playback_rate == 1.0 {
+            None
+        } else {
+            let channels = self.format.as_ref().map(|f| f.channels).unwrap_or(2);
+            let mut stretcher = TimeStretcher::new(channels, self.playback_rate);
+            // Size everything the callback uses so stretching never allocates
+            let block_frames = SCRATCH_BLOCK_FRAMES.max(STRETCH_CHUNK_FRAMES);
+            stretcher.reserve(block_frames + STRETCH_CHUNK_FRAMES);
+            self.stretch_chunk
+                .resize(STRETCH_CHUNK_FRAMES * channels as usize, 0.0);
+            self.stretch_output.clear();
+            self.stretch_output
+                .reserve(block_frames * channels as usize);
+            Some(stretcher)
         };
+    }
 
-        // Fill output buffer based on current state
-        match state_guard.state {
-            PlaybackState::Playing => {
-                // Check which audio source to use
-                let has_ring_buffer = state_guard.ring_buffer_consumer.is_some();
-                let has_buffer = state_guard.buffer.is_some();
+    /// Record the layout of a newly opened output and size the render
+    /// scratch buffers for it
+    fn open_output(&mut self, output: &AudioFormat, source_channels: u16) {
+        self.output_sample_rate = Some(output.sample_rate);
+        self.output_channels = Some(output.channels);
+        let samples = SCRATCH_BLOCK_FRAMES * output.channels.max(source_channels) as usize;
+        self.mixer.reserve(samples);
+        self.reset_resampler();
+    }
 
-                if has_ring_buffer {
-                    // Extract consumer temporarily to avoid borrow conflicts
-                    if let Some(consumer) = state_guard.ring_buffer_consumer.take() {
-                        Self::fill_from_ring_buffer(output, &consumer, &mut state_guard);
-                        state_guard.ring_buffer_consumer = Some(consumer);
-                    }
-                } else if has_buffer {
-                    // Extract buffer temporarily to avoid borrow conflicts
-                    if let Some(buffer) = state_guard.buffer.take() {
-                        Self::fill_from_buffer(output, &buffer, &mut state_guard);
-                        state_guard.buffer = Some(buffer);
-                    }
-                } else {
-                    // No audio source, fill with silence
-                    output.fill(0.0);
-                }
+    /// Build, restart or drop the converter to the output rate
+    ///
+    /// Called whenever the source format, the output or the position
+    /// changes, so the callback never builds a converter itself. A kept
+    /// converter starts over with silent history, so audio from before a
+    /// seek doesn't play after it.
+    fn reset_resampler(&mut self) {
+        let (Some(source), Some(output_rate)) = (self.format.as_ref(), self.output_sample_rate)
+        else {
+            self.resampler = None;
+            return;
+        };
+        if source.sample_rate == output_rate || source.sample_rate == 0 {
+            self.resampler = None;
+            return;
+        }
+        match self.resampler.as_mut() {
+            Some(resampler) if resampler.converts(source, output_rate) => {
+                resampler.converter.reset()
             }
             _ => {
-                // Fill with silence for all other states
-                output.fill(0.0);
+                self.resampler = Some(OutputResampler::new(
+                    source.sample_rate,
+                    output_rate,
+                    source.channels,
+                ))
             }
         }
     }
+}
 
-    /// Fill output buffer from ring buffer
-    fn fill_from_ring_buffer(
-        output: &mut [f32],
-        consumer: &RingBufferConsumer,
-        state: &mut AudioEngineState,
-    ) {
-        let samples_per_frame = state
-            .format
-            .as_ref()
-            .map(|f| f.channels as usize)
-            .unwrap_or(2);
-
-        let frames_needed = output.len() / samples_per_frame;
-        let samples_needed = frames_needed * samples_per_frame;
-
-        // Create temporary buffer for f64 samples
-        let mut temp_buffer = vec![0.0f64; samples_needed];
-        let samples_read = consumer.read_with_silence(&mut temp_buffer);
+/// Audio engine for high-fidelity playback
+pub struct AudioEngine {
+    /// Internal state protected by RwLock for thread safety
+    state: Arc<RwLock<AudioEngineState>>,
+    /// Lock-free volume commands and status (also held by the state)
+    controls: Arc<PlaybackControls>,
+    /// Event and meter callbacks (also held by the state)
+    callbacks: Arc<EngineCallbacks>,
+    /// CPAL host for audio device management
+    host: Host,
+    /// CPAL audio device
+    device: Option<Device>,
+    /// CPAL audio stream (shared with deferred fade-out pauses)
+    stream: Option<Arc<Stream>>,
+    /// Reports the events of `stream` off the realtime thread
+    event_thread: Option<EventThread>,
+    /// Pauses `stream` once a fade-out has finished, started on first use
+    fade_worker: Option<FadeWorker>,
+    /// Stream configuration
+    stream_config: Option<StreamConfig>,
+    /// Format the output stream was opened with
+    output_format: Option<AudioFormat>,
+    /// Delay between the latest callback and playback of its data (ns)
+    output_delay_ns: Arc<AtomicU64>,
+    /// Dithering applied when reducing to an integer output (see `dithering_to_u8`)
+    dithering: Arc<AtomicU8>,
+    /// Whether output must be bit-perfect
+    exclusive_mode: bool,
+    /// Which channel counts other than the source's may be negotiated
+    channel_policy: ChannelMatchPolicy,
+    /// Name of the device explicitly selected by the user (None = follow default)
+    selected_device_name: Option<String>,
+    /// Default output device monitor (when notifications are enabled)
+    device_monitor: Option<DeviceMonitor>,
+    /// Whether to migrate to the new default device when it changes
+    follow_default: bool,
+    /// Default device change detected by the monitor, not yet applied
+    pending_device_change: Arc<Mutex<Option<String>>>,
+    /// Sender handed to each stream's error callback
+    stream_error_sender: crossbeam::channel::Sender<cpal::StreamError>,
+    /// Stream errors reported by the device, handled on the next control call
+    stream_errors: crossbeam::channel::Receiver<cpal::StreamError>,
+    /// Fade applied on play/pause/stop in milliseconds (0 = disabled)
+    fade_duration_ms: u32,
+    /// Whether the next queued track is decoded automatically near the end
+    auto_prefetch: bool,
+    /// Seconds before the end of a track at which the next one is decoded
+    prefetch_seconds: f64,
+    /// Background prefetch checker (running while a queue is set)
+    prefetch_monitor: Option<PollThread>,
+    /// Packet and prefetch sizing for streamed loads
+    stream_reader_config: crate::audio::decoder::StreamConfig,
+    /// Decoder thread feeding the ring buffer of a streamed load
+    stream_reader: Option<AudioStreamReaderWithRingBuffer>,
+    /// Largest decoded size (bytes) `load_file` buffers before streaming instead
+    max_decode_memory: Option<u64>,
+    /// Reused block buffer of `render`
+    render_scratch: Vec<f64>,
+    /// Backend negotiated against instead of a device (see `use_null_output`)
+    null_output: Option<NullBackend>,
+}
 
-        // Convert f64 to f32 and apply volume with ramping
-        for (i, &sample) in temp_buffer.iter().enumerate() {
-            if i < output.len() {
-                // Apply volume ramping
-                if state.volume_ramp_step != 0.0 {
-                    // Check if we've reached the target
-                    if (state.volume_ramp_step > 0.0 && state.volume < state.target_volume)
-                        || (state.volume_ramp_step < 0.0 && state.volume > state.target_volume)
-                    {
-                        state.volume += state.volume_ramp_step;
-                        // Clamp to target to avoid overshooting
-                        if state.volume_ramp_step > 0.0 {
-                            state.volume = state.volume.min(state.target_volume);
-                        } else {
-                            state.volume = state.volume.max(state.target_volume);
-                        }
-                    } else {
-                        // Reached target, stop ramping
-                        state.volume = state.target_volume;
-                        state.volume_ramp_step = 0.0;
-                    }
-                }
+impl AudioEngine {
+    /// Create a new audio engine
+    pub fn new() -> Result<Self> {
+        let host = cpal::default_host();
+        let state = AudioEngineState::default();
+        let (stream_error_sender, stream_errors) = crossbeam::channel::unbounded();
+        Ok(Self {
+            controls: state.controls.clone(),
+            callbacks: state.callbacks.clone(),
+            state: Arc::new(RwLock::new(state)),
+            host,
+            device: None,
+            stream: None,
+            event_thread: None,
+            fade_worker: None,
+            stream_config: None,
+            output_format: None,
+            output_delay_ns: Arc::new(AtomicU64::new(0)),
+            dithering: Arc::new(AtomicU8::new(dithering_to_u8(
+                DitheringAlgorithm::Triangular,
+            ))),
+            selected_device_name: None,
+            device_monitor: None,
+            follow_default: false,
+            pending_device_change: Arc::new(Mutex::new(None)),
+            stream_error_sender,
+            stream_errors,
+            fade_duration_ms: DEFAULT_FADE_DURATION_MS,
+            exclusive_mode: false,
+            channel_policy: ChannelMatchPolicy::default(),
+            auto_prefetch: true,
+            prefetch_seconds: DEFAULT_PREFETCH_SECONDS,
+            prefetch_monitor: None,
+            stream_reader_config: crate::audio::decoder::StreamConfig::default(),
+            stream_reader: None,
+            max_decode_memory: None,
+            render_scratch: Vec::new(),
+            null_output: None,
+        })
+    }
 
-                output[i] = (sample * state.volume as f64) as f32;
-            }
-        }
+    /// Set the packet and prefetch sizing used by subsequent streamed loads
+    ///
+    /// See `StreamConfig::validate` for the accepted ranges.
+    /// `loop_playback` is ignored: streams loop when `is_looping` is set.
+    pub fn set_stream_config(&mut self, config: crate::audio::decoder::StreamConfig) -> Result<()> {
+        config.validate()?;
+        self.stream_reader_config = config;
+        Ok(())
+    }
 
-        // Update position
-        state.position += frames_needed as u64;
+    /// Limit how much memory a whole-file load may decode into
+    ///
+    /// When a file's decoded size (frames x channels x 8 bytes) would exceed
+    /// `bytes`, or its length is unknown, `load_file` streams it through a
+    /// ring buffer instead. `None` (the default) always decodes in full.
+    pub fn set_max_decode_memory(&mut self, bytes: Option<u64>) {
+        self.max_decode_memory = bytes;
+    }
 
-        // Check for buffer underrun
-        if samples_read < samples_needed {
-            // Buffer underrun occurred - we filled with silence
-            // Note: We can't easily emit events from this callback
-            // The main thread should monitor buffer levels
-        }
+    /// Get the whole-file decode limit in bytes (None = unlimited)
+    pub fn max_decode_memory(&self) -> Option<u64> {
+        self.max_decode_memory
     }
 
-    /// Fill output buffer from regular audio buffer
-    fn fill_from_buffer(output: &mut [f32], buffer: &AudioBuffer, state: &mut AudioEngineState) {
-        let samples_per_frame = state
-            .format
-            .as_ref()
-            .map(|f| f.channels as usize)
-            .unwrap_or(2);
+    /// Get the packet and prefetch sizing used by streamed loads
+    pub fn stream_config(&self) -> &crate::audio::decoder::StreamConfig {
+        &self.stream_reader_config
+    }
 
-        let frames_needed = output.len() / samples_per_frame;
-        let start_sample = state.position as usize * samples_per_frame;
-        let buffer_data = buffer.data();
-
-        // Copy audio data to output buffer with volume ramping
-        for (i, output_sample) in output.iter_mut().enumerate() {
-            let buffer_index = start_sample + i;
-
-            // Apply volume ramping
-            if state.volume_ramp_step != 0.0 {
-                // Check if we've reached the target
-                if (state.volume_ramp_step > 0.0 && state.volume < state.target_volume)
-                    || (state.volume_ramp_step < 0.0 && state.volume > state.target_volume)
-                {
-                    state.volume += state.volume_ramp_step;
-                    // Clamp to target to avoid overshooting
-                    if state.volume_ramp_step > 0.0 {
-                        state.volume = state.volume.min(state.target_volume);
-                    } else {
-                        state.volume = state.volume.max(state.target_volume);
-                    }
-                } else {
-                    // Reached target, stop ramping
-                    state.volume = state.target_volume;
-                    state.volume_ramp_step = 0.0;
-                }
-            }
+    /// Load a file with ring buffer streaming
+    pub fn load_file_with_ring_buffer<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let config = self.stream_reader_config.clone();
+        self.load_streaming(path.as_ref(), None, config)
+    }
 
-            if buffer_index < buffer_data.len() {
-                *output_sample = (buffer_data[buffer_index] * state.volume as f64) as f32;
-            } else {
-                *output_sample = 0.0; // End of audio data
-            }
-        }
+    /// Load a file with ring buffer streaming using explicit buffer sizing
+    ///
+    /// The ring buffer's duration and underrun threshold come from
+    /// `ring_buffer` (0.1-30 seconds); its format is taken from the file.
+    pub fn load_file_with_ring_buffer_config<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        ring_buffer: RingBufferConfig,
+        config: crate::audio::decoder::StreamConfig,
+    ) -> Result<()> {
+        config.validate()?;
+        self.load_streaming(path.as_ref(), Some(ring_buffer), config)
+    }
 
-        // Update position
-        state.position += frames_needed as u64;
+    /// Open a streamed source and make it the current one
+    fn load_streaming(
+        &mut self,
+        path: &Path,
+        ring_buffer: Option<RingBufferConfig>,
+        config: crate::audio::decoder::StreamConfig,
+    ) -> Result<()> {
+        let audio_format = self.open_streaming(path, ring_buffer, config)?;
+        self.init_device_and_stream(&audio_format)
+    }
 
-        // Check if we've reached the end
-        if let Some(duration) = state.duration {
-            if state.position >= duration {
-                state.state = PlaybackState::Stopped;
-                state.position = 0;
-                // Note: We can't easily emit events from this callback
-                // The main thread should check for this condition
-            }
-        }
+    /// Create a ring buffer fed by the caller and make it the current source
+    ///
+    /// Generalizes streaming beyond files: an external decoder writes
+    /// interleaved samples in `format` to the returned producer from its own
+    /// thread, and the engine plays them once `play()` is called. The
+    /// buffer's duration and underrun threshold come from `config`; its
+    /// format is replaced by `format`. The duration is unknown, and falling
+    /// behind is reported as `BufferUnderrun` like for file streams. Drop
+    /// the producer to mark the end of the stream.
+    pub fn create_streaming_input(
+        &mut self,
+        format: AudioFormat,
+        config: RingBufferConfig,
+    ) -> Result<RingBufferProducer> {
+        let producer = self.open_streaming_input(format.clone(), config)?;
+        self.init_device_and_stream(&format)?;
+        Ok(producer)
     }
 
-    /// Handle audio stream errors and attempt recovery
-    #[allow(dead_code)]
-    fn handle_stream_error(&mut self, error: cpal::StreamError) -> Result<()> {
-        eprintln!("Audio stream error: {}", error);
+    /// Install a caller-fed ring buffer as the current source
+    ///
+    /// Leaves the engine `Stopped` at position 0 without touching the
+    /// output device.
+    fn open_streaming_input(
+        &mut self,
+        format: AudioFormat,
+        config: RingBufferConfig,
+    ) -> Result<RingBufferProducer> {
+        let config = RingBufferConfig {
+            format: format.clone(),
+            ..config
+        };
+        let (producer, consumer) = AudioRingBuffer::new(config).map_err(|e| {
+            crate::Error::InvalidParameter(format!("Invalid ring buffer configuration: {}", e))
+        })?;
 
-        // Update state to error
         self.update_state(|state| {
-            state.state = PlaybackState::Error;
-            Some(AudioEvent::Error(format!("Stream error: {}", error)))
+            state.state = PlaybackState::Stopped;
+            state.position = 0;
+            state.duration = None;
+            state.format = Some(format);
+            state.source_info = None;
+            state.clip_stats = None;
+            state.buffer = None;
+            state.ring_buffer_consumer = Some(consumer);
+            state.virtual_range = None;
+            state.clear_loop_points();
+            state.play_range = None;
+            state.reset_time_stretcher();
+            state.reset_resampler();
+            Some(AudioEvent::StateChanged(PlaybackState::Stopped))
         });
 
-        // Attempt to recover by reinitializing the stream
-        self.recover_from_error()
-    }
+        // No decoder of ours feeds this source; stop any previous one
+        self.stream_reader = None;
 
-    /// Attempt to recover from audio errors
-    fn recover_from_error(&mut self) -> Result<()> {
-        // Clear the current stream
-        self.stream = None;
-        self.stream_config = None;
+        Ok(producer)
+    }
 
-        // Try to reinitialize with default device
-        if let Err(e) = self.init_default_device() {
-            return Err(crate::Error::AudioEngine(format!(
-                "Failed to recover: {}",
-                e
+    /// Start decoding a file into a ring buffer and make it the current source
+    ///
+    /// The engine reports `Buffering` while the file is opened and is left
+    /// `Stopped` at position 0 without touching the output device; returns
+    /// the stream format.
+    fn open_streaming(
+        &mut self,
+        path: &Path,
+        ring_buffer: Option<RingBufferConfig>,
+        config: crate::audio::decoder::StreamConfig,
+    ) -> Result<AudioFormat> {
+        // Validate file path
+        if !path.exists() {
+            return Err(crate::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("File not found: {}", path.display()),
             )));
         }
 
-        // Try to reinitialize stream with default format
-        let format = AudioFormat::default();
-        if let Err(e) = self.init_output_stream(&format) {
-            return Err(crate::Error::AudioEngine(format!(
-                "Failed to recover stream: {}",
-                e
+        // Check if format is supported
+        if !crate::audio::decoder::is_format_supported(path) {
+            return Err(crate::Error::UnsupportedFormat(format!(
+                "Unsupported file format: {}",
+                path.extension()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("unknown")
             )));
         }
 
-        // Update state back to stopped
+        self.update_state(|state| {
+            state.state = PlaybackState::Buffering;
+            Some(AudioEvent::StateChanged(PlaybackState::Buffering))
+        });
+
+        let config = crate::audio::decoder::StreamConfig {
+            loop_playback: self.is_looping(),
+            ..config
+        };
+        // Create ring buffer stream reader
+        let (stream_reader, consumer) =
+            AudioStreamReaderWithRingBuffer::with_ring_buffer_config(path, ring_buffer, config)
+                .map_err(|e| {
+                    self.update_state(|state| {
+                        state.state = PlaybackState::Error;
+                        Some(AudioEvent::Error(format!(
+                            "Failed to create stream reader: {}",
+                            e
+                        )))
+                    });
+                    e
+                })?;
+
+        // Get format and duration information
+        let audio_format = stream_reader.format().map_err(|e| {
+            self.update_state(|state| {
+                state.state = PlaybackState::Error;
+                Some(AudioEvent::Error(format!("Failed to get format: {}", e)))
+            });
+            e
+        })?;
+
+        let duration = stream_reader.duration().map_err(|e| {
+            self.update_state(|state| {
+                state.state = PlaybackState::Error;
+                Some(AudioEvent::Error(format!("Failed to get duration: {}", e)))
+            });
+            e
+        })?;
+
+        let source_info = stream_reader.format_info().ok();
+        let replay_gain = ReplayGainSettings::new(&self.state.read());
+        let replay_gain_db = replay_gain
+            .gain_db(path, stream_reader.replay_gain())
+            .unwrap_or(0.0);
+
+        // Update state with streaming setup
         self.update_state(|state| {
             state.state = PlaybackState::Stopped;
+            state.position = 0;
+            state.duration = duration;
+            state.format = Some(audio_format.clone());
+            state.source_info = source_info;
+            state.clip_stats = None;
+            state.current_path = Some(path.to_path_buf());
+            state.buffer = None; // Clear regular buffer
+            state.replay_gain_db = replay_gain_db;
+            state.ring_buffer_consumer = Some(consumer);
+            state.virtual_range = None;
+            state.clear_loop_points();
+            state.play_range = None;
+            state.reset_time_stretcher();
+            state.reset_resampler();
+            state.retarget_track_gain();
             Some(AudioEvent::StateChanged(PlaybackState::Stopped))
         });
 
-        Ok(())
-    }
+        // Replacing the previous reader stops its decoder thread
+        self.stream_reader = Some(stream_reader);
+        self.emit_event(AudioEvent::PlaybackModeChanged(PlaybackMode::Streaming));
 
-    /// Validate audio engine state before operations
-    fn validate_state(&self) -> Result<()> {
-        let state = self.state.read();
+        Ok(audio_format)
+    }
 
-        if state.state == PlaybackState::Error {
-            return Err(crate::Error::AudioEngine(
-                "Audio engine is in error state".to_string(),
-            ));
-        }
+    /// Load a file and get the output ready so `play()` starts at once
+    ///
+    /// Decodes the track, opens the device and builds the output stream
+    /// like `load_file`, and makes sure the stream isn't running yet (some
+    /// backends start streams when they are built). The engine is left
+    /// `Stopped`; `play()` then only starts the stream.
+    pub fn preload<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.load_file_path(path.as_ref())?;
+        self.pause_stream()
+    }
 
-        Ok(())
+    /// Load an audio file for buffer playback, reporting decode progress
+    ///
+    /// Like `load_file`, with `progress` called during decoding as
+    /// `AudioDecoder::decode_all_with_progress` describes: a rising fraction
+    /// ending at 1.0, or `NaN` while the length is unknown.
+    pub fn load_file_with_progress<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        progress: LoadProgressCallback,
+    ) -> Result<()> {
+        let audio_format = self.load_buffer_with_progress(path.as_ref(), None, &*progress)?;
+        self.init_device_and_stream(&audio_format)
     }
 
-    /// Safe wrapper for play stream operations
-    fn safe_play_operation(&mut self) -> Result<()> {
-        self.validate_state()?;
+    /// Load a chosen audio track of a multi-track file for buffer playback
+    ///
+    /// `track_id` comes from `AudioDecoder::list_audio_tracks`; `load_file`
+    /// plays the first track. The track is always decoded in full, so a
+    /// decode memory limit it exceeds makes loading fail.
+    pub fn load_file_track<P: AsRef<Path>>(&mut self, path: P, track_id: u32) -> Result<()> {
+        let audio_format =
+            self.load_buffer_with_progress(path.as_ref(), Some(track_id), &|_| {})?;
+        self.init_device_and_stream(&audio_format)
+    }
 
-        if let Some(ref stream) = self.stream {
-            stream.play().map_err(|e| {
-                // Convert CPAL error to our error type and attempt recovery
-                let error = crate::Error::AudioDevice(format!("Play operation failed: {}", e));
+    /// Load a slice of a file described by a CUE sheet or chapter list
+    ///
+    /// The backing file is decoded in full and playback is limited to the
+    /// track's frames, starting at its first frame; `position()` stays
+    /// relative to the file. Silence trimming does not apply to the slice.
+    pub fn load_virtual_track(&mut self, track: &VirtualTrack) -> Result<()> {
+        let audio_format = self.load_virtual_buffer(track)?;
+        self.init_device_and_stream(&audio_format)
+    }
 
-                // Attempt recovery in background (don't propagate recovery errors)
-                if let Err(recovery_error) = self.recover_from_error() {
-                    eprintln!("Recovery failed: {}", recovery_error);
-                }
+    /// `load_buffer` limited to a virtual track's slice
+    fn load_virtual_buffer(&mut self, track: &VirtualTrack) -> Result<AudioFormat> {
+        let audio_format = self.load_buffer(&track.file_path)?;
 
-                error
-            })
-        } else {
-            Err(crate::Error::AudioDevice(
-                "No audio stream available".to_string(),
-            ))
-        }
-    }
-
-    /// Safe wrapper for pause stream operations
-    fn safe_pause_operation(&mut self) -> Result<()> {
-        self.validate_state()?;
-
-        if let Some(ref stream) = self.stream {
-            stream.pause().map_err(|e| {
-                // Convert CPAL error to our error type and attempt recovery
-                let error = crate::Error::AudioDevice(format!("Pause operation failed: {}", e));
-
-                // Attempt recovery in background (don't propagate recovery errors)
-                if let Err(recovery_error) = self.recover_from_error() {
-                    eprintln!("Recovery failed: {}", recovery_error);
-                }
-
-                error
-            })
-        } else {
-            Err(crate::Error::AudioDevice(
-                "No audio stream available".to_string(),
-            ))
-        }
-    }
-
-    /// Start the audio stream
-    pub fn start_stream(&mut self) -> Result<()> {
-        self.safe_play_operation()
-    }
-
-    /// Pause the audio stream
-    pub fn pause_stream(&mut self) -> Result<()> {
-        self.safe_pause_operation()
-    }
-
-    /// Get supported audio formats for the current device
-    pub fn supported_formats(&self) -> Result<Vec<AudioFormat>> {
-        let device = self
-            .device
-            .as_ref()
-            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
-
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
-        })?;
-
-        let mut formats = Vec::new();
-
-        for config in supported_configs {
-            // Add configurations for common sample rates
-            let sample_rates = [
-                config.min_sample_rate(),
-                44100,
-                48000,
-                96000,
-                192000,
-                config.max_sample_rate(),
-            ];
-
-            for &sample_rate in &sample_rates {
-                if sample_rate >= config.min_sample_rate()
-                    && sample_rate <= config.max_sample_rate()
-                {
-                    let audio_format = AudioFormat::new(
-                        sample_rate,
-                        config.channels(),
-                        crate::audio::format::SampleFormat::F32, // CPAL uses f32
-                    );
-
-                    if !formats.contains(&audio_format) {
-                        formats.push(audio_format);
-                    }
-                }
-            }
-        }
-
-        Ok(formats)
-    }
-
-    /// Enumerate all available output devices
-    pub fn enumerate_output_devices(&self) -> Result<Vec<AudioDeviceInfo>> {
-        let devices = self.host.output_devices().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to enumerate devices: {}", e))
-        })?;
-
-        let mut device_infos = Vec::new();
-
-        for device in devices {
-            let name = device
-                .description()
-                .map(|desc| desc.to_string())
-                .unwrap_or_else(|_| "Unknown Device".to_string());
-
-            let supported_configs = device.supported_output_configs().map_err(|e| {
-                crate::Error::AudioDevice(format!("Failed to get device configs: {}", e))
-            })?;
-
-            let mut formats = Vec::new();
-            for config in supported_configs {
-                // Add a representative format for this config
-                let format = AudioFormat::new(
-                    config.max_sample_rate().min(48000), // Use 48kHz as default, or max if lower
-                    config.channels(),
-                    crate::audio::format::SampleFormat::F32,
-                );
-                formats.push(format);
-            }
-
-            let device_info = AudioDeviceInfo {
-                name,
-                supported_formats: formats,
-                is_default: false, // We'll set this separately
-            };
-
-            device_infos.push(device_info);
-        }
-
-        // Mark the default device
-        if let Some(default_device) = self.host.default_output_device() {
-            if let Ok(default_name) = default_device.description().map(|desc| desc.to_string()) {
-                for device_info in &mut device_infos {
-                    if device_info.name == default_name {
-                        device_info.is_default = true;
-                        break;
-                    }
-                }
-            }
-        }
-
-        Ok(device_infos)
-    }
-
-    /// Get information about the current device
-    pub fn current_device_info(&self) -> Result<Option<AudioDeviceInfo>> {
-        let device = match self.device.as_ref() {
-            Some(device) => device,
-            None => return Ok(None),
-        };
-
-        let name = device
-            .description()
-            .map(|desc| desc.to_string())
-            .unwrap_or_else(|_| "Unknown Device".to_string());
-
-        let supported_configs = device.supported_output_configs().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to get device configs: {}", e))
-        })?;
-
-        let mut formats = Vec::new();
-        for config in supported_configs {
-            let format = AudioFormat::new(
-                config.max_sample_rate().min(48000),
-                config.channels(),
-                crate::audio::format::SampleFormat::F32,
-            );
-            formats.push(format);
-        }
-
-        let is_default = if let Some(default_device) = self.host.default_output_device() {
-            default_device
-                .description()
-                .map(|desc| desc.to_string())
-                .unwrap_or_default()
-                == name
-        } else {
-            false
+        // Track positions are in the rate the sheet was resolved with
+        let to_file_frames = |frames: u64| match track.sample_rate {
+            0 => frames,
+            rate if rate == audio_format.sample_rate => frames,
+            rate => frames * audio_format.sample_rate as u64 / rate as u64,
         };
-
-        Ok(Some(AudioDeviceInfo {
-            name,
-            supported_formats: formats,
-            is_default,
-        }))
-    }
-
-    /// Set device by name
-    pub fn set_device_by_name(&mut self, device_name: &str) -> Result<()> {
-        let devices = self.host.output_devices().map_err(|e| {
-            crate::Error::AudioDevice(format!("Failed to enumerate devices: {}", e))
-        })?;
-
-        for device in devices {
-            if let Ok(name) = device.description().map(|desc| desc.to_string()) {
-                if name == device_name {
-                    return self.set_device(device);
-                }
-            }
-        }
-
-        Err(crate::Error::AudioDevice(format!(
-            "Device '{}' not found",
-            device_name
-        )))
-    }
-
-    /// Set ring buffer consumer for streaming playback
-    pub fn set_ring_buffer_consumer(&mut self, consumer: RingBufferConsumer) -> Result<()> {
-        self.update_state(|state| {
-            state.ring_buffer_consumer = Some(consumer);
-            state.buffer = None; // Clear regular buffer when using ring buffer
-            None
-        });
-        Ok(())
-    }
-
-    /// Clear ring buffer consumer
-    pub fn clear_ring_buffer_consumer(&mut self) {
         self.update_state(|state| {
-            state.ring_buffer_consumer = None;
+            let frames = state.duration.unwrap_or(0);
+            let start = to_file_frames(track.start_sample).min(frames);
+            let end = track
+                .end_sample
+                .map_or(frames, to_file_frames)
+                .clamp(start, frames);
+            state.virtual_range = Some((start, end));
+            state.update_play_range();
+            state.position = state.range_start();
             None
         });
-    }
-
-    /// Check if using ring buffer for playback
-    pub fn is_using_ring_buffer(&self) -> bool {
-        self.state.read().ring_buffer_consumer.is_some()
-    }
 
-    /// Get ring buffer utilization (0.0 to 1.0) if using ring buffer
-    pub fn ring_buffer_utilization(&self) -> Option<f64> {
-        let state = self.state.read();
-        state.ring_buffer_consumer.as_ref().map(|consumer| {
-            let available = consumer.available_read();
-            let capacity = consumer.capacity();
-            available as f64 / capacity as f64
-        })
-    }
-    fn emit_event(&self, event: AudioEvent) {
-        let state = self.state.read();
-        if let Some(ref callback) = state.callback {
-            callback(event);
-        }
+        Ok(audio_format)
     }
 
-    /// Update the internal state and emit events as needed
-    fn update_state<F>(&self, updater: F)
-    where
-        F: FnOnce(&mut AudioEngineState) -> Option<AudioEvent>,
-    {
-        let mut state = self.state.write();
-        if let Some(event) = updater(&mut state) {
-            // Drop the write lock before calling the callback
-            drop(state);
-            self.emit_event(event);
-        }
+    /// Decode a whole file into memory and make it the current source
+    ///
+    /// Leaves the engine `Stopped` at position 0 without touching the output
+    /// device; returns the decoded format.
+    fn load_buffer(&mut self, path: &Path) -> Result<AudioFormat> {
+        self.load_buffer_with_progress(path, None, &|_| {})
     }
-}
-
-impl AudioEngineInterface for AudioEngine {
-    fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
-        let path = path.as_ref();
 
+    /// `load_buffer` of audio track `track_id` (or else the first one),
+    /// reporting decode progress to `progress`
+    fn load_buffer_with_progress(
+        &mut self,
+        path: &Path,
+        track_id: Option<u32>,
+        progress: &dyn Fn(f64),
+    ) -> Result<AudioFormat> {
         // Validate file path
         if !path.exists() {
             return Err(crate::Error::Io(std::io::Error::new(
@@ -1052,7 +2077,7 @@ impl AudioEngineInterface for AudioEngine {
 
         // Check if format is supported
         if !crate::audio::decoder::is_format_supported(path) {
-            return Err(crate::Error::Decoding(format!(
+            return Err(crate::Error::UnsupportedFormat(format!(
                 "Unsupported file format: {}",
                 path.extension()
                     .and_then(|s| s.to_str())
@@ -1061,7 +2086,11 @@ impl AudioEngineInterface for AudioEngine {
         }
 
         // Create decoder and get format information
-        let mut decoder = crate::audio::decoder::AudioDecoder::new(path).map_err(|e| {
+        let decoder = match track_id {
+            Some(id) => crate::audio::decoder::AudioDecoder::new_with_track(path, id),
+            None => crate::audio::decoder::AudioDecoder::new(path),
+        };
+        let mut decoder = decoder.map_err(|e| {
             self.update_state(|state| {
                 state.state = PlaybackState::Error;
                 Some(AudioEvent::Error(format!("Failed to load file: {}", e)))
@@ -1072,14 +2101,49 @@ impl AudioEngineInterface for AudioEngine {
         let audio_format = decoder.format().clone();
         let duration = decoder.duration();
 
+        if let Some(limit) = self.max_decode_memory {
+            let estimate =
+                duration.map(|frames| frames.saturating_mul(audio_format.channels as u64 * 8));
+            if estimate.is_none_or(|bytes| bytes > limit) {
+                drop(decoder);
+                if track_id.is_some() {
+                    return Err(crate::Error::AudioEngine(format!(
+                        "{} exceeds the {} byte decode limit; track selection requires decoding in full",
+                        path.display(),
+                        limit
+                    )));
+                }
+                let config = self.stream_reader_config.clone();
+                return self.open_streaming(path, None, config).map_err(|e| {
+                    crate::Error::AudioEngine(format!(
+                        "{} exceeds the {} byte decode limit and cannot be streamed: {}",
+                        path.display(),
+                        limit,
+                        e
+                    ))
+                });
+            }
+        }
+
+        let source_info = decoder.format_info().ok();
+
         // Decode all audio data for now (TODO: implement streaming in ring buffer phase)
-        let audio_buffer = decoder.decode_all().map_err(|e| {
+        let mut audio_buffer = decoder.decode_all_with_progress(progress).map_err(|e| {
             self.update_state(|state| {
                 state.state = PlaybackState::Error;
                 Some(AudioEvent::Error(format!("Failed to decode audio: {}", e)))
             });
             e
         })?;
+        let clip_stats = source_clip_stats(&audio_buffer, &audio_format);
+        let (normalization, replay_gain) = {
+            let state = self.state.read();
+            (state.normalization, ReplayGainSettings::new(&state))
+        };
+        let replay_gain_db = replay_gain
+            .gain_db(path, decoder.replay_gain())
+            .unwrap_or(0.0);
+        normalization.apply(audio_buffer.data_mut(), &audio_format);
 
         // Update state with loaded file information
         self.update_state(|state| {
@@ -1087,13 +2151,32 @@ impl AudioEngineInterface for AudioEngine {
             state.position = 0;
             state.duration = duration;
             state.format = Some(audio_format.clone());
+            state.source_info = source_info;
+            state.clip_stats = Some(clip_stats);
+            state.current_path = Some(path.to_path_buf());
             state.buffer = Some(audio_buffer);
+            state.replay_gain_db = replay_gain_db;
             state.ring_buffer_consumer = None; // Clear ring buffer when loading regular file
+            state.gap_remaining = 0;
+            state.virtual_range = None;
+            state.clear_loop_points();
+            state.update_play_range();
+            state.position = state.range_start();
+            state.reset_time_stretcher();
+            state.reset_resampler();
+            state.retarget_track_gain();
             Some(AudioEvent::StateChanged(PlaybackState::Stopped))
         });
+        self.stream_reader = None;
+        self.emit_event(AudioEvent::PlaybackModeChanged(PlaybackMode::Buffered));
+
+        Ok(audio_format)
+    }
 
+    /// Open the default device if needed and build a stream for `audio_format`
+    fn init_device_and_stream(&mut self, audio_format: &AudioFormat) -> Result<()> {
         // Initialize default device if not set
-        if self.device.is_none() {
+        if self.device.is_none() && self.null_output.is_none() {
             self.init_default_device().map_err(|e| {
                 self.update_state(|state| {
                     state.state = PlaybackState::Error;
@@ -1107,7 +2190,7 @@ impl AudioEngineInterface for AudioEngine {
         }
 
         // Initialize output stream with the audio format
-        self.init_output_stream(&audio_format).map_err(|e| {
+        self.init_output_stream(audio_format).map_err(|e| {
             self.update_state(|state| {
                 state.state = PlaybackState::Error;
                 Some(AudioEvent::Error(format!(
@@ -1121,1182 +2204,7842 @@ impl AudioEngineInterface for AudioEngine {
         Ok(())
     }
 
-    fn play(&mut self) -> Result<()> {
-        self.validate_state()?;
-
-        // Start CPAL stream
-        self.start_stream().map_err(|e| {
-            self.update_state(|state| {
-                state.state = PlaybackState::Error;
+    /// Initialize the audio engine with a specific device
+    pub fn with_device(device: Device) -> Result<Self> {
+        let host = cpal::default_host();
+        let state = AudioEngineState::default();
+        let (stream_error_sender, stream_errors) = crossbeam::channel::unbounded();
+        Ok(Self {
+            controls: state.controls.clone(),
+            callbacks: state.callbacks.clone(),
+            state: Arc::new(RwLock::new(state)),
+            host,
+            selected_device_name: device_name(&device),
+            device: Some(device),
+            stream: None,
+            event_thread: None,
+            fade_worker: None,
+            stream_config: None,
+            output_format: None,
+            output_delay_ns: Arc::new(AtomicU64::new(0)),
+            dithering: Arc::new(AtomicU8::new(dithering_to_u8(
+                DitheringAlgorithm::Triangular,
+            ))),
+            device_monitor: None,
+            follow_default: false,
+            pending_device_change: Arc::new(Mutex::new(None)),
+            stream_error_sender,
+            stream_errors,
+            fade_duration_ms: DEFAULT_FADE_DURATION_MS,
+            exclusive_mode: false,
+            channel_policy: ChannelMatchPolicy::default(),
+            auto_prefetch: true,
+            prefetch_seconds: DEFAULT_PREFETCH_SECONDS,
+            prefetch_monitor: None,
+            stream_reader_config: crate::audio::decoder::StreamConfig::default(),
+            stream_reader: None,
+            max_decode_memory: None,
+            render_scratch: Vec::new(),
+            null_output: None,
+        })
+    }
+
+    /// Get the CPAL host
+    pub fn host(&self) -> &Host {
+        &self.host
+    }
+
+    /// Get the current audio device
+    pub fn device(&self) -> Option<&Device> {
+        self.device.as_ref()
+    }
+
+    /// Set a new audio device
+    pub fn set_device(&mut self, device: Device) -> Result<()> {
+        // Stop current stream if running
+        if self.stream.is_some() {
+            self.stop()?;
+        }
+
+        self.selected_device_name = device_name(&device);
+        self.device = Some(device);
+        self.null_output = None;
+        self.stream = None;
+        self.stream_config = None;
+        self.output_format = None;
+
+        Ok(())
+    }
+
+    /// Initialize the default audio device
+    pub fn init_default_device(&mut self) -> Result<()> {
+        let device = self.host.default_output_device().ok_or_else(|| {
+            crate::Error::AudioDevice("No default output device available".to_string())
+        })?;
+
+        self.set_device(device)?;
+        // The default device is not an explicit selection
+        self.selected_device_name = None;
+        Ok(())
+    }
+
+    /// Pick the output format for `format` from a backend's configurations
+    ///
+    /// Devices that enumerate nothing are negotiated against their default
+    /// configuration, reported with an `AudioEvent::Warning`.
+    ///
+    /// # Returns
+    /// The native source format and the format to open the stream with
+    fn select_stream_format<B: OutputBackend + ?Sized>(
+        &self,
+        backend: &B,
+        format: &AudioFormat,
+    ) -> Result<(AudioFormat, AudioFormat)> {
+        let negotiable = negotiable_configs(backend)?;
+        let configs = negotiable.configs;
+        if negotiable.from_default {
+            self.emit_event(AudioEvent::Warning(format!(
+                "Device reported no output configurations, using its default of {}Hz, {} channels",
+                configs[0].max_sample_rate, configs[0].channels
+            )));
+        }
+
+        // Pick the device's best native sample format for this source
+        let source_format = self.native_source_format(format);
+        let output_format = if self.exclusive_mode {
+            find_bit_perfect_format(&configs, &source_format).ok_or_else(|| {
+                crate::Error::FormatNegotiation(format!(
+                    "Device can't play {}Hz, {} channels, {:?} natively",
+                    source_format.sample_rate, source_format.channels, source_format.sample_format
+                ))
+            })?
+        } else {
+            select_channel_matched_format(&configs, &source_format, self.channel_policy)
+                .ok_or_else(|| {
+                    crate::Error::FormatNegotiation(format!(
+                        "No compatible audio configuration found for {}Hz, {} channels",
+                        format.sample_rate, format.channels
+                    ))
+                })?
+        };
+
+        Ok((source_format, output_format))
+    }
+
+    /// Initialize audio output stream
+    pub fn init_output_stream(&mut self, format: &AudioFormat) -> Result<()> {
+        if let Some(backend) = self.null_output.clone() {
+            return self.init_null_stream(&backend, format);
+        }
+
+        let device = self
+            .device
+            .as_ref()
+            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+
+        // Validate the format first
+        crate::audio::format::validate_format(format)
+            .map_err(|e| crate::Error::AudioFormat(format!("Invalid format: {}", e)))?;
+
+        let (source_format, output_format) =
+            self.select_stream_format(&CpalBackend::new(device.clone()), format)?;
+
+        let stream_config = StreamConfig {
+            channels: output_format.channels,
+            sample_rate: output_format.sample_rate,
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        // Dither when the device can't take the source's full depth
+        let dither = (output_format.sample_format.is_integer()
+            && sample_format_bits(output_format.sample_format)
+                < sample_format_bits(source_format.sample_format))
+        .then(|| self.dithering.clone());
+
+        self.output_delay_ns.store(0, Ordering::Relaxed);
+        let (event_thread, events) = EventThread::start(self.callbacks.clone());
+        let state = self.state.clone();
+        let delay = self.output_delay_ns.clone();
+        let errors = self.stream_error_sender.clone();
+        let config = &stream_config;
+        let stream = match output_format.sample_format {
+            SampleFormat::U8 => {
+                Self::build_stream::<u8>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::I8 => {
+                Self::build_stream::<i8>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::U16 => {
+                Self::build_stream::<u16>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::I16 => {
+                Self::build_stream::<i16>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::I24 => Self::build_stream::<cpal::I24>(
+                device, config, state, delay, events, errors, dither,
+            ),
+            SampleFormat::I32 => {
+                Self::build_stream::<i32>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::F32 => {
+                Self::build_stream::<f32>(device, config, state, delay, events, errors, dither)
+            }
+            SampleFormat::F64 => {
+                Self::build_stream::<f64>(device, config, state, delay, events, errors, dither)
+            }
+        }?;
+
+        // Playback renders in the source format and is converted when they differ
+        self.state
+            .write()
+            .open_output(&output_format, format.channels);
+        self.stream = Some(Arc::new(stream));
+        self.event_thread = Some(event_thread);
+        self.stream_config = Some(stream_config);
+        self.output_format = Some(output_format);
+
+        Ok(())
+    }
+
+    /// Negotiate output with a null backend, without building a stream
+    fn init_null_stream(&mut self, backend: &NullBackend, format: &AudioFormat) -> Result<()> {
+        crate::audio::format::validate_format(format)
+            .map_err(|e| crate::Error::AudioFormat(format!("Invalid format: {}", e)))?;
+        let (_, output_format) = self.select_stream_format(backend, format)?;
+
+        self.state
+            .write()
+            .open_output(&output_format, format.channels);
+        self.stream_config = Some(StreamConfig {
+            channels: output_format.channels,
+            sample_rate: output_format.sample_rate,
+            buffer_size: cpal::BufferSize::Default,
+        });
+        self.output_format = Some(output_format);
+        Ok(())
+    }
+
+    /// Open output against `backend` instead of a device
+    ///
+    /// Loads negotiate their output format with the backend as they would
+    /// with a device, but no stream is built: play, pause and stop take
+    /// effect immediately, and playback only advances through
+    /// `advance_for_testing`.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn use_null_output(&mut self, backend: NullBackend) {
+        self.stream = None;
+        self.null_output = Some(backend);
+    }
+
+    /// Check whether output is open, on a device or a null backend
+    fn has_output(&self) -> bool {
+        self.stream.is_some() || (self.null_output.is_some() && self.output_format.is_some())
+    }
+
+    /// Build an output stream of sample type `T` rendering from the engine state
+    ///
+    /// The callback renders from `state`, reports its device delay into
+    /// `output_delay_ns` and queues block events to the stream's event
+    /// thread through `events`. Stream errors are sent to `errors` for the
+    /// engine to recover from. `dither` carries the selected algorithm
+    /// when the output has fewer bits than the source; the ditherer lives as
+    /// long as the stream so its noise sequence continues across callbacks.
+    ///
+    /// Blocks are rendered in f64 and converted to `T` once, on output.
+    fn build_stream<T: OutputSample>(
+        device: &Device,
+        config: &StreamConfig,
+        state: Arc<RwLock<AudioEngineState>>,
+        output_delay_ns: Arc<AtomicU64>,
+        events: crossbeam::channel::Sender<BlockEvents>,
+        errors: crossbeam::channel::Sender<cpal::StreamError>,
+        dither: Option<Arc<AtomicU8>>,
+    ) -> Result<Stream> {
+        let channels = config.channels.max(1) as usize;
+        let sample_rate = config.sample_rate;
+        let mut rendered: Vec<f64> = Vec::new();
+        let mut ditherer = dither
+            .as_ref()
+            .map(|algorithm| Ditherer::new(dithering_from_u8(algorithm.load(Ordering::Relaxed))));
+
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], info: &OutputCallbackInfo| {
+                    let frames = data.len() / channels;
+                    Self::record_output_delay(&output_delay_ns, info, frames, sample_rate);
+
+                    rendered.resize(data.len(), 0.0);
+                    if let Some((block, _)) = Self::render_callback(&mut rendered, &state) {
+                        if !block.is_empty() {
+                            // A full queue drops the block's events rather
+                            // than wait on the event thread
+                            let _ = events.try_send(block);
+                        }
+                    }
+
+                    if let (Some(ditherer), Some(algorithm)) = (ditherer.as_mut(), &dither) {
+                        // Switching algorithm keeps the RNG state running
+                        ditherer
+                            .set_algorithm(dithering_from_u8(algorithm.load(Ordering::Relaxed)));
+                    }
+                    T::write_samples(&rendered, data, ditherer.as_mut());
+                },
+                move |err| {
+                    // The stream can't be rebuilt from its own callback
+                    let _ = errors.send(err);
+                },
+                None, // No timeout
+            )
+            .map_err(|e| crate::Error::AudioDevice(format!("Failed to build output stream: {}", e)))
+    }
+
+    /// Store how long the data of a callback takes to reach the device
+    ///
+    /// Uses CPAL's playback vs callback timestamps; backends that don't report
+    /// them fall back to the duration of the callback buffer.
+    fn record_output_delay(
+        output_delay_ns: &AtomicU64,
+        info: &OutputCallbackInfo,
+        frames: usize,
+        sample_rate: u32,
+    ) {
+        let timestamp = info.timestamp();
+        let delay = timestamp
+            .playback
+            .duration_since(&timestamp.callback)
+            .filter(|delay| !delay.is_zero())
+            .unwrap_or_else(|| Self::period_duration(frames, sample_rate));
+        output_delay_ns.store(delay.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Duration of a callback buffer of `frames`
+    fn period_duration(frames: usize, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64)
+    }
+
+    /// Get the source format as stored in the file
+    ///
+    /// Decoded audio is always f64, so lossless sources are described by their
+    /// original integer bit depth.
+    fn native_source_format(&self, format: &AudioFormat) -> AudioFormat {
+        let mut source_format = format.clone();
+        if let Some(pcm) = self
+            .state
+            .read()
+            .source_info
+            .as_ref()
+            .filter(|info| info.is_lossless)
+            .and_then(|info| info.bit_depth)
+            .and_then(pcm_sample_format)
+        {
+            source_format.sample_format = pcm;
+        }
+        source_format
+    }
+
+    /// Require bit-perfect output
+    ///
+    /// When enabled, the output stream is only opened at the source's exact
+    /// sample rate, channel count and bit depth (or a lossless integer
+    /// container for it); loading a file the device can't play natively fails
+    /// with `Error::FormatNegotiation` instead of converting. CPAL doesn't expose
+    /// WASAPI exclusive or CoreAudio hog mode, so on those platforms the OS
+    /// mixer may still be in the path; ALSA `hw:` devices are exclusive by
+    /// nature.
+    ///
+    /// An open stream is reopened with the new setting; if that fails the
+    /// previous mode and stream are kept.
+    pub fn set_exclusive_mode(&mut self, enabled: bool) -> Result<()> {
+        let previous = self.exclusive_mode;
+        self.exclusive_mode = enabled;
+
+        let format = self.format();
+        if let (Some(format), true) = (format, self.has_output()) {
+            if let Err(e) = self.init_output_stream(&format) {
+                self.exclusive_mode = previous;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if bit-perfect output is required
+    pub fn exclusive_mode(&self) -> bool {
+        self.exclusive_mode
+    }
+
+    /// Set which device channel counts may be used when the source's isn't offered
+    ///
+    /// With anything but `ChannelMatchPolicy::Exact`, e.g. a mono podcast can
+    /// open a stereo-only device, and is remixed to the device layout during
+    /// playback. Exclusive mode always requires the exact channel count. An
+    /// open stream is reopened with the new policy; if that fails the
+    /// previous policy and stream are kept.
+    pub fn set_channel_match_policy(&mut self, policy: ChannelMatchPolicy) -> Result<()> {
+        let previous = self.channel_policy;
+        self.channel_policy = policy;
+
+        let format = self.format();
+        if let (Some(format), true) = (format, self.has_output()) {
+            if let Err(e) = self.init_output_stream(&format) {
+                self.channel_policy = previous;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the channel count mismatch policy
+    pub fn channel_match_policy(&self) -> ChannelMatchPolicy {
+        self.channel_policy
+    }
+
+    /// Check if the current stream plays the source unaltered
+    ///
+    /// Requires a native output format that carries the source losslessly,
+    /// unity volume, centered balance and normal playback speed.
+    pub fn is_bit_perfect(&self) -> bool {
+        let (output, format) = match (&self.output_format, self.format()) {
+            (Some(output), Some(format)) => (output, format),
+            _ => return false,
+        };
+        let source = self.native_source_format(&format);
+
+        let state = self.state.read();
+        let unprocessed = self.volume() == 1.0
+            && !self.controls.volume_pending()
+            && state.volume_ramp_step == 0.0
+            && !self.is_muted()
+            && state.playback_rate == 1.0
+            && state.balance_gains().is_none()
+            && state.equalizer.is_none()
+            && state.rumble_filter.is_none()
+            && state.saturator.is_none_or(|s| s.is_bypassed())
+            && state.stereo_width.is_neutral();
+
+        unprocessed
+            && output.sample_rate == source.sample_rate
+            && output.channels == source.channels
+            && is_lossless_conversion(source.sample_format, output.sample_format)
+    }
+
+    /// Check whether an output stream has been built for the loaded track
+    pub fn is_stream_initialized(&self) -> bool {
+        self.has_output()
+    }
+
+    /// Get the format the output stream was opened with
+    ///
+    /// Differs from the source format when the device can't play it natively.
+    pub fn output_format(&self) -> Option<AudioFormat> {
+        self.output_format.clone()
+    }
+
+    /// Find a compatible CPAL configuration for the given audio format
+    fn find_compatible_config(
+        &self,
+        supported_configs: cpal::SupportedOutputConfigs,
+        target_format: &AudioFormat,
+    ) -> Result<cpal::SupportedStreamConfig> {
+        let mut best_match: Option<cpal::SupportedStreamConfig> = None;
+        let mut best_score = 0;
+
+        for config in supported_configs {
+            let score = self.calculate_format_compatibility_score(&config, target_format);
+
+            if score > best_score {
+                // Check if sample rate is supported
+                if config.min_sample_rate() <= target_format.sample_rate
+                    && target_format.sample_rate <= config.max_sample_rate()
+                {
+                    // Check if channel count is supported
+                    if self
+                        .channel_policy
+                        .allows(target_format.channels, config.channels())
+                    {
+                        best_match = Some(config.with_sample_rate(target_format.sample_rate));
+                        best_score = score;
+                    }
+                }
+            }
+        }
+
+        best_match.ok_or_else(|| {
+            crate::Error::FormatNegotiation(format!(
+                "No compatible audio configuration found for {}Hz, {} channels",
+                target_format.sample_rate, target_format.channels
+            ))
+        })
+    }
+
+    /// Calculate compatibility score between a CPAL config and target format
+    fn calculate_format_compatibility_score(
+        &self,
+        config: &cpal::SupportedStreamConfigRange,
+        target_format: &AudioFormat,
+    ) -> u32 {
+        let mut score = 0;
+
+        // Prefer exact sample rate match
+        if config.min_sample_rate() <= target_format.sample_rate
+            && target_format.sample_rate <= config.max_sample_rate()
+        {
+            score += 100;
+
+            // Bonus for exact sample rate match at boundaries
+            if config.min_sample_rate() == target_format.sample_rate
+                || config.max_sample_rate() == target_format.sample_rate
+            {
+                score += 50;
+            }
+        }
+
+        // Prefer exact channel count match
+        if config.channels() == target_format.channels {
+            score += 100;
+        }
+
+        // Prefer higher sample rates for high-resolution audio
+        if target_format.is_high_resolution() {
+            if config.max_sample_rate() >= 96000 {
+                score += 25;
+            }
+            if config.max_sample_rate() >= 192000 {
+                score += 25;
+            }
+        }
+
+        // Prefer configurations that support common sample rates
+        let common_rates = [44100, 48000, 96000, 192000];
+        for &rate in &common_rates {
+            if config.min_sample_rate() <= rate && rate <= config.max_sample_rate() {
+                score += 10;
+            }
+        }
+
+        score
+    }
+
+    /// Negotiate the best audio format for the current device
+    pub fn negotiate_format(&self, preferred_format: &AudioFormat) -> Result<AudioFormat> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+
+        // Try to find exact match first, using the best native sample format
+        let negotiable = negotiable_configs(&CpalBackend::new(device.clone()))?;
+        if let Some(format) = select_channel_matched_format(
+            &negotiable.configs,
+            preferred_format,
+            self.channel_policy,
+        ) {
+            return Ok(format);
+        }
+        if negotiable.from_default {
+            // Nothing else to score against
+            let default = &negotiable.configs[0];
+            return Ok(AudioFormat::new(
+                default.max_sample_rate,
+                default.channels,
+                default.sample_format,
+            ));
+        }
+
+        // If no exact match, find the best compatible format
+        let supported_configs_iter = device.supported_output_configs().map_err(|e| {
+            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
+        })?;
+        let best_config = self.find_compatible_config(supported_configs_iter, preferred_format)?;
+
+        Ok(AudioFormat::new(
+            best_config.sample_rate(),
+            best_config.channels(),
+            sample_format_from_cpal(best_config.sample_format()).unwrap_or(SampleFormat::F32),
+        ))
+    }
+
+    /// Get the best available format for high-quality playback
+    pub fn get_best_format(&self) -> Result<AudioFormat> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+
+        let supported_configs = negotiable_configs(&CpalBackend::new(device.clone()))?.configs;
+
+        let mut best_format: Option<AudioFormat> = None;
+        let mut best_sample_rate = 0;
+        let mut best_channels = 0;
+
+        for config in supported_configs {
+            let sample_rate = config.max_sample_rate;
+            let channels = config.channels;
+
+            // Prefer higher sample rates and more channels for quality
+            if sample_rate > best_sample_rate
+                || (sample_rate == best_sample_rate && channels > best_channels)
+            {
+                best_sample_rate = sample_rate;
+                best_channels = channels;
+                best_format = Some(AudioFormat::new(
+                    sample_rate,
+                    channels,
+                    crate::audio::format::SampleFormat::F32,
+                ));
+            }
+        }
+
+        best_format.ok_or_else(|| {
+            crate::Error::AudioDevice("No supported audio formats found".to_string())
+        })
+    }
+
+    /// Check if a specific format is supported by the current device
+    pub fn is_format_supported(&self, format: &AudioFormat) -> Result<bool> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+
+        let supported_configs = device.supported_output_configs().map_err(|e| {
+            crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
+        })?;
+
+        for config in supported_configs {
+            if config.min_sample_rate() <= format.sample_rate
+                && format.sample_rate <= config.max_sample_rate()
+                && config.channels() == format.channels
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Render a block and report its events on the calling thread
+    ///
+    /// Drives playback without a device; device streams use
+    /// `render_callback` and leave the reporting to their event thread.
+    #[cfg(any(test, feature = "testing"))]
+    fn audio_callback(output: &mut [f64], state: &Arc<RwLock<AudioEngineState>>) {
+        if let Some((block, callbacks)) = Self::render_callback(output, state) {
+            Self::emit_block_events(&callbacks, block);
+        }
+    }
+
+    /// Output callback body: render a block under the state lock
+    ///
+    /// # Returns
+    /// The block's events and the callbacks to report them to, or `None`
+    /// if the lock was busy and the block was rendered from a read-only
+    /// view (see `render_shared`) or is silence
+    fn render_callback(
+        output: &mut [f64],
+        state: &Arc<RwLock<AudioEngineState>>,
+    ) -> Option<(BlockEvents, Arc<EngineCallbacks>)> {
+        let mut state_guard = match state.try_write() {
+            Some(guard) => guard,
+            None => {
+                // Getters hold the lock for reading, so playback continues
+                // from a shared view; only writers silence the block
+                match state.try_read() {
+                    Some(guard) => Self::render_shared(output, &guard),
+                    None => output.fill(0.0),
+                }
+                return None;
+            }
+        };
+
+        let layout = OutputLayout {
+            channels: state_guard.output_channels,
+            sample_rate: state_guard.output_sample_rate,
+        };
+        let events = Self::render_block(output, &mut state_guard, layout);
+        Some((events, state_guard.callbacks.clone()))
+    }
+
+    /// Render a block from a read-only view of the state
+    ///
+    /// Plays the source on at the current gains with the stateless effects;
+    /// the frames played are added to the position by the next locked
+    /// render. Blocks that need to change the state to render (ramps,
+    /// filters, conversion, mixing, gaps and track ends) are silent.
+    fn render_shared(output: &mut [f64], state: &AudioEngineState) {
+        output.fill(0.0);
+        let Some(format) = state.format.as_ref() else {
+            return;
+        };
+        let channels = format.channels as usize;
+        let plain = channels > 0
+            && state.state == PlaybackState::Playing
+            && !state.fade_out_pending
+            && state.fade_step == 0.0
+            && state.volume_ramp_step == 0.0
+            && state.track_gain_step == 0.0
+            && !state.controls.volume_pending()
+            && state.time_stretcher.is_none()
+            && state.gap_remaining == 0
+            && state
+                .output_sample_rate
+                .is_none_or(|rate| rate == format.sample_rate)
+            && state
+                .output_channels
+                .is_none_or(|count| count == format.channels)
+            && state.equalizer.is_none()
+            && state.rumble_filter.is_none()
+            && state.loudness_compensation.is_none()
+            && state.mixer.is_empty();
+        if !plain {
+            return;
+        }
+
+        let position = state.position + state.controls.shared_frames.load(Ordering::Acquire);
+        let frames = output.len() / channels;
+        let played = if let Some(consumer) = &state.ring_buffer_consumer {
+            let available = consumer.available_read() / channels * channels;
+            // A handle of our own to borrow from; the state is shared
+            let mut consumer = consumer.clone();
+            let slices = consumer.read_slices((frames * channels).min(available));
+            let (first, second) = slices.as_slices();
+            let (head, tail) = output.split_at_mut(first.len());
+            Self::write_shared(head, first, position, state);
+            let wrapped = position + (first.len() / channels) as u64;
+            Self::write_shared(&mut tail[..second.len()], second, wrapped, state);
+            slices.len() / channels
+        } else if let Some(buffer) = &state.buffer {
+            let end = state.buffer_end().unwrap_or(0);
+            let frames = frames.min(end.saturating_sub(position) as usize);
+            if frames > 0 {
+                let start = position as usize * channels;
+                let source = &buffer.data()[start..start + frames * channels];
+                Self::write_shared(&mut output[..source.len()], source, position, state);
+            }
+            frames
+        } else {
+            0
+        };
+
+        Self::apply_saturation(output, state);
+        Self::apply_stereo_width(output, state);
+        state
+            .controls
+            .shared_frames
+            .fetch_add(played as u64, Ordering::AcqRel);
+        state
+            .controls
+            .position
+            .store(position + played as u64, Ordering::Release);
+    }
+
+    /// Write source samples starting at frame `position` with the state's
+    /// current gains
+    fn write_shared(output: &mut [f64], source: &[f64], position: u64, state: &AudioEngineState) {
+        let channels = state
+            .format
+            .as_ref()
+            .map_or(2, |f| f.channels.max(1) as usize);
+        let volume = state.volume as f64 * state.fade_gain as f64 * state.track_gain;
+        let balance = state.balance_gains();
+        let edge_fade = state.virtual_fade();
+        let guard = state.sample_guard();
+        for (i, (out, &sample)) in output.iter_mut().zip(source).enumerate() {
+            let frame = position + (i / channels) as u64;
+            let gain = balance.map_or(1.0, |gains| gains[i % 2]) * edge_fade_gain(edge_fade, frame);
+            *out = sanitize_sample(sample, guard) * gain * volume;
+        }
+    }
+
+    /// Advance playback by `frames` output frames without a device
+    ///
+    /// Runs the output callback on the calling thread in device-sized
+    /// blocks, so position, track ends, loops and events behave exactly as
+    /// during real playback. The rendered audio is discarded, and the
+    /// output latency becomes one block, as for a device that doesn't
+    /// report timestamps.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn advance_for_testing(&self, frames: u64) {
+        self.run_for_testing(frames, |_| {});
+    }
+
+    /// Advance playback like `advance_for_testing`, keeping the audio
+    ///
+    /// # Returns
+    /// The interleaved output the device would have played
+    #[cfg(any(test, feature = "testing"))]
+    pub fn render_for_testing(&self, frames: u64) -> Vec<f64> {
+        let mut rendered = Vec::new();
+        self.run_for_testing(frames, |block| rendered.extend_from_slice(block));
+        rendered
+    }
+
+    /// Run the output callback for `frames` frames, passing each block to `sink`
+    #[cfg(any(test, feature = "testing"))]
+    fn run_for_testing(&self, frames: u64, mut sink: impl FnMut(&[f64])) {
+        let (channels, sample_rate) = {
+            let state = self.state.read();
+            let channels = state
+                .output_channels
+                .or_else(|| state.format.as_ref().map(|f| f.channels))
+                .unwrap_or(2)
+                .max(1) as usize;
+            let sample_rate = state
+                .output_sample_rate
+                .or_else(|| state.format.as_ref().map(|f| f.sample_rate))
+                .unwrap_or(0);
+            (channels, sample_rate)
+        };
+        let period = Self::period_duration(TESTING_BLOCK_FRAMES, sample_rate);
+        self.output_delay_ns
+            .store(period.as_nanos() as u64, Ordering::Relaxed);
+        let mut block = vec![0.0f64; TESTING_BLOCK_FRAMES * channels];
+        let mut remaining = frames;
+        while remaining > 0 {
+            let len = remaining.min(TESTING_BLOCK_FRAMES as u64) as usize;
+            Self::audio_callback(&mut block[..len * channels], &self.state);
+            sink(&block[..len * channels]);
+            remaining -= len as u64;
+        }
+    }
+
+    /// Render one block of final output for `layout`
+    ///
+    /// This is everything an output callback does while holding the state
+    /// lock. The notifications it raises are reported with
+    /// `emit_block_events` once the lock is released.
+    fn render_block(
+        output: &mut [f64],
+        state_guard: &mut AudioEngineState,
+        layout: OutputLayout,
+    ) -> BlockEvents {
+        state_guard.apply_volume_command();
+        state_guard.apply_shared_frames();
+        let looped_to = match Self::remix_layout(state_guard, layout.channels) {
+            Some((from, to)) => {
+                let mut remix = std::mem::take(&mut state_guard.remix);
+                let looped_to = Self::render_remixed(
+                    output,
+                    &mut remix,
+                    (from, to),
+                    layout.sample_rate,
+                    state_guard,
+                );
+                state_guard.remix = remix;
+                looped_to
+            }
+            None => Self::render_output(output, layout.sample_rate, state_guard),
+        };
+
+        let metering = state_guard.callbacks.metering.load(Ordering::Acquire);
+        let levels = metering.then(|| {
+            let format = state_guard.format.as_ref();
+            let channels = layout
+                .channels
+                .or_else(|| format.map(|f| f.channels))
+                .unwrap_or(2);
+            let sample_rate = layout
+                .sample_rate
+                .or_else(|| format.map(|f| f.sample_rate))
+                .unwrap_or(44100);
+            Self::meter_levels(output, channels, sample_rate, state_guard)
+        });
+
+        state_guard.publish_status();
+        BlockEvents {
+            events: std::mem::take(&mut state_guard.render_events),
+            looped_to,
+            levels,
+        }
+    }
+
+    /// Report what happened while rendering a block
+    fn emit_block_events(callbacks: &EngineCallbacks, block: BlockEvents) {
+        for event in block.events {
+            callbacks.emit(event);
+        }
+        if let Some(position) = block.looped_to {
+            Self::emit_loop_events(callbacks, position);
+        }
+        if let Some(levels) = block.levels {
+            if let Some(ref callback) = *callbacks.meter.read() {
+                callback(levels);
+            }
+        }
+    }
+
+    /// Source and output channel counts, if the output needs remixing
+    fn remix_layout(state: &AudioEngineState, channels: Option<u16>) -> Option<(u16, u16)> {
+        let to = channels?;
+        let from = state.format.as_ref()?.channels;
+        (from != to && from > 0 && to > 0).then_some((from, to))
+    }
+
+    /// Render in the source layout and remix into the device-layout output
+    fn render_remixed(
+        output: &mut [f64],
+        remix: &mut OutputRemix,
+        (from, to): (u16, u16),
+        output_rate: Option<u32>,
+        state: &mut AudioEngineState,
+    ) -> Option<u64> {
+        let frames = output.len() / to as usize;
+        remix.rendered.clear();
+        remix.rendered.resize(frames * from as usize, 0.0);
+        let looped_to = Self::render_output(&mut remix.rendered, output_rate, state);
+
+        let written = match remix_channels(&remix.rendered, from, to, &mut remix.remixed) {
+            Ok(()) => remix.remixed.len().min(output.len()),
+            Err(_) => 0,
+        };
+        output[..written].copy_from_slice(&remix.remixed[..written]);
+        output[written..].fill(0.0);
+
+        looped_to
+    }
+
+    /// Render in the source layout, resampling to the output rate if needed
+    ///
+    /// The converter comes from `reset_resampler`; until one matching the
+    /// source is in place the block stays silent.
+    fn render_output(
+        output: &mut [f64],
+        output_rate: Option<u32>,
+        state: &mut AudioEngineState,
+    ) -> Option<u64> {
+        let converting =
+            |format: &AudioFormat, rate: u32| format.sample_rate != rate && format.sample_rate > 0;
+        let Some(output_rate) =
+            output_rate.filter(|&rate| state.format.as_ref().is_some_and(|f| converting(f, rate)))
+        else {
+            return Self::render_source(output, state);
+        };
+
+        let ready = match (&state.resampler, &state.format) {
+            (Some(resampler), Some(format)) => resampler.converts(format, output_rate),
+            _ => false,
+        };
+        match state.resampler.take() {
+            Some(mut resampler) if ready => {
+                let looped_to = Self::render_resampled(output, &mut resampler, state);
+                state.resampler = Some(resampler);
+                looped_to
+            }
+            resampler => {
+                state.resampler = resampler;
+                output.fill(0.0);
+                None
+            }
+        }
+    }
+
+    /// Render at the source rate and convert into the device-rate output
+    ///
+    /// Position and duration keep counting source frames; only as many
+    /// source frames are rendered as the converter needs for this block.
+    fn render_resampled(
+        output: &mut [f64],
+        resampler: &mut OutputResampler,
+        state: &mut AudioEngineState,
+    ) -> Option<u64> {
+        let channels = resampler.converter.channels();
+        let mut rendered = std::mem::take(&mut resampler.rendered);
+        let mut looped_to = None;
+        let mut written = 0;
+
+        // A short read renders more source instead of leaving a gap; the
+        // second pass only runs if the first estimate fell short
+        for _ in 0..2 {
+            let remaining = (output.len() - written) / channels;
+            if remaining == 0 {
+                break;
+            }
+            let needed = resampler.converter.input_frames_needed(remaining).max(1);
+            rendered.clear();
+            rendered.resize(needed * channels, 0.0);
+            looped_to = Self::render_source(&mut rendered, state).or(looped_to);
+            resampler.converter.push(rendered.iter().copied());
+            written += resampler.converter.read(&mut output[written..]);
+        }
+        resampler.rendered = rendered;
+        output[written..].fill(0.0);
+
+        looped_to
+    }
+
+    /// Render main playback and mixer sources at the source rate
+    ///
+    /// Returns the position playback wrapped back to, if it looped.
+    fn render_source(output: &mut [f64], state_guard: &mut AudioEngineState) -> Option<u64> {
+        let mut looped_to = None;
+
+        if state_guard.state == PlaybackState::Buffering && Self::stream_ready(state_guard) {
+            state_guard.state = PlaybackState::Playing;
+            state_guard
+                .render_events
+                .push(AudioEvent::StateChanged(PlaybackState::Playing));
+        }
+
+        // Fill output buffer based on current state; a pause/stop fade-out
+        // keeps rendering until it reaches silence
+        if state_guard.state == PlaybackState::Playing || state_guard.fade_out_pending {
+            // Check which audio source to use
+            let has_ring_buffer = state_guard.ring_buffer_consumer.is_some();
+            let has_buffer = state_guard.buffer.is_some();
+
+            // An inter-track gap plays out before the source continues
+            let silent = Self::fill_gap(output, state_guard);
+            let source = &mut output[silent..];
+
+            if source.is_empty() {
+                // The whole block is gap silence
+            } else if has_ring_buffer {
+                // Extract consumer temporarily to avoid borrow conflicts
+                if let Some(consumer) = state_guard.ring_buffer_consumer.take() {
+                    looped_to = Self::fill_from_ring_buffer(source, &consumer, state_guard);
+                    state_guard.ring_buffer_consumer = Some(consumer);
+                }
+            } else if has_buffer {
+                // Extract buffer temporarily to avoid borrow conflicts
+                if let Some(buffer) = state_guard.buffer.take() {
+                    let written = Self::fill_from_buffer(source, &buffer, state_guard);
+                    state_guard.buffer = Some(buffer);
+                    looped_to = Self::handle_buffer_end(&mut source[written..], state_guard);
+                }
+            } else {
+                // No audio source, fill with silence
+                source.fill(0.0);
+            }
+
+            Self::apply_rumble_filter(output, state_guard);
+            Self::apply_loudness_compensation(output, state_guard);
+            Self::apply_equalizer(output, state_guard);
+            Self::apply_saturation(output, state_guard);
+            Self::apply_stereo_width(output, state_guard);
+
+            if state_guard.fade_out_pending && state_guard.fade_gain <= 0.0 {
+                Self::finish_fade_out(state_guard);
+            }
+        } else {
+            // Fill with silence for all other states
+            output.fill(0.0);
+        }
+
+        if !state_guard.mixer.is_empty() {
+            Self::mix_sources(output, state_guard);
+        }
+
+        looped_to
+    }
+
+    /// Measure peak, RMS and phase correlation of the final output
+    fn meter_levels(
+        output: &[f64],
+        channels: u16,
+        sample_rate: u32,
+        state: &mut AudioEngineState,
+    ) -> MeterLevels {
+        let weighted_rms = Self::weighted_rms(output, channels, sample_rate, state);
+        let samples = output;
+
+        let peak = samples.iter().fold(0.0f64, |peak, s| peak.max(s.abs()));
+        let rms = if samples.is_empty() {
+            0.0
+        } else {
+            (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+        };
+        let correlation = match channels {
+            2 => phase_correlation(samples),
+            _ => 1.0,
+        };
+        let frames = output.len() / channels.max(1) as usize;
+        let (peak_hold, clip_held) = state.meter_hold.update(peak, frames, sample_rate);
+
+        MeterLevels {
+            peak,
+            rms,
+            correlation,
+            weighted_rms,
+            peak_hold,
+            clip_held,
+        }
+    }
+
+    /// RMS of the output through the meter's weighting filters
+    ///
+    /// The filters keep their state across blocks and are rebuilt when the
+    /// output rate or channel count changes.
+    fn weighted_rms(
+        output: &[f64],
+        channels: u16,
+        sample_rate: u32,
+        state: &mut AudioEngineState,
+    ) -> Option<f64> {
+        if state.meter_weighting == FrequencyWeighting::None {
+            return None;
+        }
+        let (filters, rate, filter_channels) = &mut state.meter_weighting_filters;
+        if (*rate, *filter_channels) != (sample_rate, channels) {
+            let sections = frequency_weighting_filters(state.meter_weighting, sample_rate);
+            *filters = (0..channels)
+                .flat_map(|_| sections.iter().map(|&c| Biquad::new(c)))
+                .collect();
+            (*rate, *filter_channels) = (sample_rate, channels);
+        }
+        if output.is_empty() || filters.is_empty() {
+            return Some(0.0);
+        }
+
+        let sections = filters.len() / channels.max(1) as usize;
+        let mut energy = 0.0;
+        for frame in output.chunks(channels.max(1) as usize) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                let chain = &mut filters[channel * sections..(channel + 1) * sections];
+                let value = chain
+                    .iter_mut()
+                    .fold(sample, |value, filter| filter.process(value));
+                energy += value * value;
+            }
+        }
+        Some((energy / output.len() as f64).sqrt())
+    }
+
+    /// Run the equalizer over the rendered main playback
+    ///
+    /// The chain follows the current format, so a preset authored at another
+    /// rate gets coefficients for the rate being played.
+    fn apply_equalizer(output: &mut [f64], state: &mut AudioEngineState) {
+        let (sample_rate, channels) = match &state.format {
+            Some(format) => (format.sample_rate, format.channels),
+            None => return,
+        };
+        if let Some(equalizer) = state.equalizer.as_mut() {
+            equalizer.configure(sample_rate, channels);
+            equalizer.process_interleaved(output);
+        }
+    }
+
+    /// Remove subsonic content from the rendered main playback
+    fn apply_rumble_filter(output: &mut [f64], state: &mut AudioEngineState) {
+        let (sample_rate, channels) = match &state.format {
+            Some(format) => (format.sample_rate, format.channels),
+            None => return,
+        };
+        if let Some((_, filter)) = state.rumble_filter.as_mut() {
+            filter.configure(sample_rate, channels);
+            filter.process_interleaved(output);
+        }
+    }
+
+    /// Boost bass and treble of the main playback according to the volume
+    ///
+    /// The shelves are retuned whenever the volume has moved by more than
+    /// `LOUDNESS_RETUNE_DB` since they were last tuned, and bypassed at or
+    /// above reference volume where the contour is flat.
+    fn apply_loudness_compensation(output: &mut [f64], state: &mut AudioEngineState) {
+        let (sample_rate, channels) = match &state.format {
+            Some(format) => (format.sample_rate, format.channels),
+            None => return,
+        };
+        let volume = state.volume;
+        if let Some((tuned_for, shelves)) = state.loudness_compensation.as_mut() {
+            let db = |volume: f32| 20.0 * (volume.max(1e-6) as f64).log10();
+            if tuned_for.is_nan() || (db(volume) - db(*tuned_for)).abs() > LOUDNESS_RETUNE_DB {
+                shelves.set_bands(loudness_contour(volume));
+                *tuned_for = volume;
+            }
+            if volume < 1.0 {
+                shelves.configure(sample_rate, channels);
+                shelves.process_interleaved(output);
+            } else {
+                shelves.reset();
+            }
+        }
+    }
+
+    /// Run the soft clipper over the equalized main playback
+    fn apply_saturation(output: &mut [f64], state: &AudioEngineState) {
+        if let Some(saturator) = state.saturator.filter(|s| !s.is_bypassed()) {
+            for sample in output.iter_mut() {
+                *sample = saturator.process(*sample);
+            }
+        }
+    }
+
+    /// Apply the stereo width to stereo output
+    fn apply_stereo_width(output: &mut [f64], state: &AudioEngineState) {
+        let width = state.stereo_width;
+        if width.is_neutral() || state.format.as_ref().is_none_or(|f| f.channels != 2) {
+            return;
+        }
+        for frame in output.chunks_exact_mut(2) {
+            let (left, right) = width.process(frame[0], frame[1]);
+            frame[0] = left;
+            frame[1] = right;
+        }
+    }
+
+    /// Switch to `Playing`, or to `Buffering` while a stream fills up
+    ///
+    /// A stream that hasn't buffered enough yet starts by itself once it
+    /// has; see `stream_ready`.
+    pub(crate) fn enter_playing(&self) {
+        self.update_state(|state| {
+            let target = if state.state == PlaybackState::Playing || Self::stream_ready(state) {
+                PlaybackState::Playing
+            } else {
+                PlaybackState::Buffering
+            };
+            if state.state != target {
+                state.state = target;
+                Some(AudioEvent::StateChanged(target))
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Whether a streamed source has buffered enough to play
+    ///
+    /// Sources that aren't streamed are always ready, as is a stream whose
+    /// decoder has finished.
+    fn stream_ready(state: &AudioEngineState) -> bool {
+        state.ring_buffer_consumer.as_ref().is_none_or(|consumer| {
+            consumer.is_closed()
+                || consumer.available_read() as f64
+                    >= consumer.capacity() as f64 * STREAM_PREROLL_FRACTION
+        })
+    }
+
+    /// Report a loop wrap-around
+    fn emit_loop_events(callbacks: &EngineCallbacks, position: u64) {
+        callbacks.emit(AudioEvent::TrackEnded);
+        callbacks.emit(AudioEvent::PositionChanged(position));
+    }
+
+    /// Wrap playback to the range start if looping applies at the track end
+    fn wrap_for_loop(state: &mut AudioEngineState) -> Option<u64> {
+        if !state.loops() {
+            return None;
+        }
+        state.position = state.range_start();
+        Some(state.position)
+    }
+
+    /// Mix the extra sources over the rendered output
+    fn mix_sources(output: &mut [f64], state: &mut AudioEngineState) {
+        let channels = state.format.as_ref().map(|f| f.channels).unwrap_or(2);
+        state.mixer.mix_into(output, channels);
+    }
+
+    /// Complete a pause/stop once its fade-out has reached silence
+    fn finish_fade_out(state: &mut AudioEngineState) {
+        state.fade_out_pending = false;
+        if state.state == PlaybackState::Stopped {
+            state.position = state.range_start();
+            state.gap_remaining = 0;
+        }
+    }
+
+    /// Fill output buffer from ring buffer
+    ///
+    /// Returns the position playback wrapped to when looping past the end.
+    fn fill_from_ring_buffer(
+        output: &mut [f64],
+        consumer: &RingBufferConsumer,
+        state: &mut AudioEngineState,
+    ) -> Option<u64> {
+        let samples_per_frame = state
+            .format
+            .as_ref()
+            .map(|f| f.channels as usize)
+            .unwrap_or(2);
+        if samples_per_frame == 0 {
+            output.fill(0.0);
+            return None;
+        }
+
+        if let Some(mut stretcher) = state.time_stretcher.take() {
+            Self::fill_stretched(
+                output,
+                &mut stretcher,
+                state,
+                samples_per_frame,
+                |_, chunk| {
+                    // Only whole frames, so no half frame is lost between
+                    // chunks
+                    let available =
+                        consumer.available_read() / samples_per_frame * samples_per_frame;
+                    let len = chunk.len().min(available);
+                    consumer.read(&mut chunk[..len])
+                },
+            );
+            state.time_stretcher = Some(stretcher);
+            return Self::advance_stream_position(state, consumer, 0);
+        }
+
+        let frames_needed = output.len() / samples_per_frame;
+        let samples_needed = frames_needed * samples_per_frame;
+
+        // Create temporary buffer for f64 samples; whatever the ring buffer
+        // can't supply stays silent. Only whole frames are taken, so a
+        // partly written frame waits for the rest of its samples
+        let mut temp_buffer = vec![0.0f64; samples_needed];
+        let available = consumer.available_read() / samples_per_frame * samples_per_frame;
+        let samples_read = consumer.read(&mut temp_buffer[..samples_needed.min(available)]);
+
+        // Convert f64 to f32 and apply balance and volume with ramping
+        let balance = state.balance_gains();
+        let guard = state.sample_guard();
+        for (i, &sample) in temp_buffer.iter().enumerate() {
+            if i < output.len() {
+                let volume = Self::step_volume(state);
+                let gain = balance.map_or(1.0, |gains| gains[i % 2]);
+                output[i] = sanitize_sample(sample, guard) * gain * volume;
+            }
+        }
+
+        // The decoder fell behind: stall until it has caught up again. An
+        // empty buffer after the producer finished is the end of the stream
+        if samples_read < samples_needed
+            && !consumer.is_closed()
+            && state.state == PlaybackState::Playing
+            && !state.fade_out_pending
+        {
+            state.state = PlaybackState::Buffering;
+            consumer.record_underrun();
+            state.render_events.push(AudioEvent::BufferUnderrun);
+            state
+                .render_events
+                .push(AudioEvent::StateChanged(PlaybackState::Buffering));
+        }
+
+        // Only the frames actually played count, so a short read (underrun,
+        // or a fade-out into a pause) can't move the position past the audio
+        Self::advance_stream_position(state, consumer, samples_read / samples_per_frame)
+    }
+
+    /// Update the position after reading from the ring buffer
+    ///
+    /// Follows the decoder's packet timestamps when the feeder marked them,
+    /// so the position stays exact across underruns and decoder seeks; a
+    /// jump back while looping is the reader restarting at the beginning.
+    /// Otherwise the position advances by `frames` and wraps by duration.
+    fn advance_stream_position(
+        state: &mut AudioEngineState,
+        consumer: &RingBufferConsumer,
+        frames: usize,
+    ) -> Option<u64> {
+        match consumer.source_position() {
+            Some(position) => {
+                let wrapped = state.loops() && position < state.position;
+                state.position = position;
+                wrapped.then_some(position)
+            }
+            None => {
+                state.position += frames as u64;
+                Self::wrap_stream_position(state)
+            }
+        }
+    }
+
+    /// Keep a looping stream's position within the track
+    ///
+    /// The looping stream reader restarts decoding at the beginning, so the
+    /// position wraps by the track duration.
+    fn wrap_stream_position(state: &mut AudioEngineState) -> Option<u64> {
+        match state.duration {
+            Some(duration) if state.loops() && duration > 0 && state.position >= duration => {
+                state.position %= duration;
+                Some(state.position)
+            }
+            _ => None,
+        }
+    }
+
+    /// Fill output buffer from regular audio buffer
+    ///
+    /// Returns the number of output samples taken from the buffer; the rest
+    /// of `output` is silence past the end of the audio data.
+    fn fill_from_buffer(
+        output: &mut [f64],
+        buffer: &AudioBuffer,
+        state: &mut AudioEngineState,
+    ) -> usize {
+        let samples_per_frame = state
+            .format
+            .as_ref()
+            .map(|f| f.channels as usize)
+            .unwrap_or(2);
+        if samples_per_frame == 0 {
+            output.fill(0.0);
+            return 0;
+        }
+
+        // Only audio inside the playback range is rendered
+        let buffer_data = match state.playable_end() {
+            Some(end) => {
+                let data = buffer.data();
+                &data[..(end as usize * samples_per_frame).min(data.len())]
+            }
+            None => buffer.data(),
+        };
+
+        let edge_fade = state.virtual_fade();
+
+        if let Some(mut stretcher) = state.time_stretcher.take() {
+            Self::fill_stretched(
+                output,
+                &mut stretcher,
+                state,
+                samples_per_frame,
+                |position, chunk| {
+                    let start = (position as usize * samples_per_frame).min(buffer_data.len());
+                    let count = chunk.len().min(buffer_data.len() - start);
+                    chunk[..count].copy_from_slice(&buffer_data[start..start + count]);
+                    if edge_fade.is_some() {
+                        for (i, sample) in chunk[..count].iter_mut().enumerate() {
+                            let frame = position + (i / samples_per_frame) as u64;
+                            *sample *= edge_fade_gain(edge_fade, frame);
+                        }
+                    }
+                    count
+                },
+            );
+            state.time_stretcher = Some(stretcher);
+            output.len()
+        } else {
+            let frames_needed = output.len() / samples_per_frame;
+            let start_sample = state.position as usize * samples_per_frame;
+            if start_sample >= buffer_data.len() {
+                // At or past the end; `handle_buffer_end` takes it from here
+                output.fill(0.0);
+                return 0;
+            }
+            let end_frame = (buffer_data.len() / samples_per_frame) as u64;
+
+            // Copy audio data to output buffer with balance and volume ramping
+            let balance = state.balance_gains();
+            let guard = state.sample_guard();
+            for (i, output_sample) in output.iter_mut().enumerate() {
+                let buffer_index = start_sample + i;
+                let volume = Self::step_volume(state);
+
+                if buffer_index < buffer_data.len() {
+                    let frame = state.position + (i / samples_per_frame) as u64;
+                    let gain = balance.map_or(1.0, |gains| gains[i % 2])
+                        * edge_fade_gain(edge_fade, frame);
+                    let sample = sanitize_sample(buffer_data[buffer_index], guard);
+                    *output_sample = sample * gain * volume;
+                } else {
+                    *output_sample = 0.0; // End of audio data
+                }
+            }
+
+            // Update position, stopping at the end of the audio
+            state.position = (state.position + frames_needed as u64).min(end_frame);
+
+            output.len().min(buffer_data.len() - start_sample)
+        }
+    }
+
+    /// Handle reaching the end of the buffer
+    ///
+    /// If the next track was prefetched with a compatible format it is spliced
+    /// in immediately (gapless) and `rest` is filled from it. With looping
+    /// enabled, playback instead wraps to the range start and the new
+    /// position is returned; otherwise playback stops.
+    fn handle_buffer_end(rest: &mut [f64], state: &mut AudioEngineState) -> Option<u64> {
+        let ended = state.buffer_end().is_some_and(|end| state.position >= end);
+        if !ended {
+            return None;
+        }
+
+        let looped_to = if let Some(position) = Self::wrap_for_loop(state) {
+            Some(position)
+        } else if Self::advance_to_next_track(state) {
+            state.gap_remaining = state.inter_track_gap_frames();
+            None
+        } else {
+            state.state = PlaybackState::Stopped;
+            state.position = state.range_start();
+            state.render_events.push(AudioEvent::TrackEnded);
+            state
+                .render_events
+                .push(AudioEvent::StateChanged(PlaybackState::Stopped));
+            rest.fill(0.0);
+            return None;
+        };
+
+        let silent = Self::fill_gap(rest, state);
+        let rest = &mut rest[silent..];
+        if !rest.is_empty() {
+            if let Some(buffer) = state.buffer.take() {
+                Self::fill_from_buffer(rest, &buffer, state);
+                state.buffer = Some(buffer);
+            }
+        }
+        looped_to
+    }
+
+    /// Write pending inter-track silence to the start of `output`
+    ///
+    /// Returns the number of samples written.
+    fn fill_gap(output: &mut [f64], state: &mut AudioEngineState) -> usize {
+        if state.gap_remaining == 0 {
+            return 0;
+        }
+        let channels = state
+            .format
+            .as_ref()
+            .map_or(2, |f| f.channels.max(1) as usize);
+        let frames = (output.len() / channels).min(state.gap_remaining as usize);
+        output[..frames * channels].fill(0.0);
+        state.gap_remaining -= frames as u64;
+        frames * channels
+    }
+
+    /// Swap in the prefetched next track if its format matches the stream
+    fn advance_to_next_track(state: &mut AudioEngineState) -> bool {
+        let compatible = match (&state.next_track, &state.format) {
+            (Some(next), Some(format)) => {
+                next.format.sample_rate == format.sample_rate
+                    && next.format.channels == format.channels
+            }
+            _ => false,
+        };
+
+        if !compatible {
+            return false;
+        }
+
+        if let Some(next) = state.next_track.take() {
+            let was_streaming = state.ring_buffer_consumer.is_some();
+            state.queue.advance_on_track_end();
+            state.apply_prepared_track(next);
+            if was_streaming {
+                state
+                    .render_events
+                    .push(AudioEvent::PlaybackModeChanged(PlaybackMode::Buffered));
+            }
+        }
+        true
+    }
+
+    /// Fill output through the time stretcher
+    ///
+    /// `read_source` is given the current source position and a chunk to fill,
+    /// returning the number of samples written. Position advances by the
+    /// number of source frames consumed. Stretched output beyond this block
+    /// stays in the stretcher for the next one; the chunk and output buffers
+    /// are the state's scratch, sized when the stretcher was configured.
+    fn fill_stretched<F>(
+        output: &mut [f64],
+        stretcher: &mut TimeStretcher,
+        state: &mut AudioEngineState,
+        samples_per_frame: usize,
+        mut read_source: F,
+    ) where
+        F: FnMut(u64, &mut [f64]) -> usize,
+    {
+        let mut chunk = std::mem::take(&mut state.stretch_chunk);
+        chunk.resize(STRETCH_CHUNK_FRAMES * samples_per_frame, 0.0);
+        let guard = state.sample_guard();
+
+        while stretcher.available() < output.len() {
+            let read = read_source(state.position, &mut chunk);
+            let frames = read / samples_per_frame;
+            state.position += frames as u64;
+            // Sanitize before the stretcher so a NaN can't poison its overlap
+            for sample in chunk[..read].iter_mut() {
+                *sample = sanitize_sample(*sample, guard);
+            }
+            stretcher.push(&chunk[..frames * samples_per_frame]);
+
+            if read < chunk.len() {
+                // Source exhausted or underrun
+                break;
+            }
+        }
+
+        let mut stretched = std::mem::take(&mut state.stretch_output);
+        stretched.clear();
+        stretched.resize(output.len(), 0.0);
+        stretcher.read(&mut stretched);
+
+        let balance = state.balance_gains();
+        for (i, (output_sample, &sample)) in output.iter_mut().zip(&stretched).enumerate() {
+            let volume = Self::step_volume(state);
+            let gain = balance.map_or(1.0, |gains| gains[i % 2]);
+            *output_sample = sample * gain * volume;
+        }
+        state.stretch_chunk = chunk;
+        state.stretch_output = stretched;
+    }
+
+    /// Advance the volume ramp by one sample and return the gain to apply
+    #[inline]
+    fn step_volume(state: &mut AudioEngineState) -> f64 {
+        if state.volume_ramp_step != 0.0 {
+            // Check if we've reached the target
+            if (state.volume_ramp_step > 0.0 && state.volume < state.target_volume)
+                || (state.volume_ramp_step < 0.0 && state.volume > state.target_volume)
+            {
+                state.volume += state.volume_ramp_step;
+                // Clamp to target to avoid overshooting
+                if state.volume_ramp_step > 0.0 {
+                    state.volume = state.volume.min(state.target_volume);
+                } else {
+                    state.volume = state.volume.max(state.target_volume);
+                }
+            } else {
+                // Reached target, stop ramping
+                state.volume = state.target_volume;
+                state.volume_ramp_step = 0.0;
+            }
+        }
+
+        if state.fade_step != 0.0 {
+            state.fade_gain = (state.fade_gain + state.fade_step).clamp(0.0, 1.0);
+            if state.fade_gain == 0.0 || state.fade_gain == 1.0 {
+                state.fade_step = 0.0;
+            }
+        }
+
+        if state.track_gain_step != 0.0 {
+            state.track_gain += state.track_gain_step;
+            if (state.track_gain_step > 0.0 && state.track_gain >= state.track_gain_target)
+                || (state.track_gain_step < 0.0 && state.track_gain <= state.track_gain_target)
+            {
+                state.track_gain = state.track_gain_target;
+                state.track_gain_step = 0.0;
+            }
+        }
+
+        state.volume as f64 * state.fade_gain as f64 * state.track_gain
+    }
+
+    /// Apply the output changes reported since the last call
+    ///
+    /// Stream errors and default device changes are reported on threads
+    /// where the stream can't be rebuilt, so they are queued and handled here
+    /// at the start of control calls. Several queued errors are recovered
+    /// from once.
+    fn handle_output_events(&mut self) {
+        if let Some(error) = self.stream_errors.try_iter().last() {
+            if let Err(e) = self.handle_stream_error(error) {
+                eprintln!("Warning: Failed to recover from stream error: {}", e);
+            }
+        }
+        if let Err(e) = self.apply_pending_device_change() {
+            eprintln!("Warning: Failed to follow the default device: {}", e);
+        }
+    }
+
+    /// Handle audio stream errors and attempt recovery
+    fn handle_stream_error(&mut self, error: cpal::StreamError) -> Result<()> {
+        eprintln!("Audio stream error: {}", error);
+
+        let previous_state = self.state.read().state;
+
+        // Update state to error
+        self.update_state(|state| {
+            state.state = PlaybackState::Error;
+            Some(AudioEvent::Error(format!("Stream error: {}", error)))
+        });
+
+        // Attempt to recover by reinitializing the stream
+        self.recover_from_error(previous_state)
+    }
+
+    /// Attempt to recover from audio errors
+    ///
+    /// The previously selected device is reopened first, falling back to the
+    /// default device only when it is truly gone. Each is retried with
+    /// backoff, stream and all, so a briefly disconnected USB DAC can come
+    /// back. With a null backend the output is negotiated with it again. The
+    /// current format and position are preserved and playback resumes if
+    /// `resume_state` is `Playing`.
+    fn recover_from_error(&mut self, resume_state: PlaybackState) -> Result<()> {
+        let previous_name = self.device.as_ref().and_then(device_name);
+        let format = self.state.read().format.clone().unwrap_or_default();
+
+        // Clear the current stream
+        self.stream = None;
+        self.stream_config = None;
+        self.output_format = None;
+
+        // Prefer the device the user explicitly selected
+        let reopened = match self.selected_device_name.clone() {
+            Some(name) if self.null_output.is_none() => {
+                retry_with_backoff(RECOVERY_ATTEMPTS, RECOVERY_INITIAL_BACKOFF, || {
+                    self.device = Some(self.find_output_device(&name)?);
+                    self.init_output_stream(&format)
+                })
+                .is_ok()
+            }
+            _ => false,
+        };
+
+        if !reopened {
+            retry_with_backoff(RECOVERY_ATTEMPTS, RECOVERY_INITIAL_BACKOFF, || {
+                if self.null_output.is_none() {
+                    let device = self.host.default_output_device().ok_or_else(|| {
+                        crate::Error::AudioDevice("No default output device available".to_string())
+                    })?;
+                    self.device = Some(device);
+                }
+                // Reinitialize the stream with the format that was playing
+                self.init_output_stream(&format)
+            })
+            .map_err(|e| crate::Error::AudioEngine(format!("Failed to recover stream: {}", e)))?;
+        }
+
+        let new_name = self.device.as_ref().and_then(device_name);
+
+        if new_name != previous_name {
+            let name = new_name.unwrap_or_else(|| "Unknown Device".to_string());
+            self.emit_event(AudioEvent::DeviceChanged(name));
+        }
+
+        // Restore the previous state, resuming playback where it left off
+        let restored = match resume_state {
+            PlaybackState::Playing | PlaybackState::Buffering => {
+                // A null backend has no stream to drive
+                let resumed = match &self.stream {
+                    Some(stream) => stream.play().is_ok(),
+                    None => self.has_output(),
+                };
+                if resumed {
+                    resume_state
+                } else {
+                    PlaybackState::Paused
+                }
+            }
+            PlaybackState::Paused => PlaybackState::Paused,
+            _ => PlaybackState::Stopped,
+        };
+
+        self.update_state(|state| {
+            state.state = restored;
+            Some(AudioEvent::StateChanged(restored))
+        });
+
+        Ok(())
+    }
+
+    /// Find an output device by name
+    fn find_output_device(&self, device_name: &str) -> Result<Device> {
+        let devices = self.host.output_devices().map_err(|e| {
+            crate::Error::AudioDevice(format!("Failed to enumerate devices: {}", e))
+        })?;
+
+        for device in devices {
+            if let Ok(name) = device.description().map(|desc| desc.to_string()) {
+                if name == device_name {
+                    return Ok(device);
+                }
+            }
+        }
+
+        Err(crate::Error::AudioDevice(format!(
+            "Device '{}' not found",
+            device_name
+        )))
+    }
+
+    /// Validate audio engine state before operations
+    fn validate_state(&self) -> Result<()> {
+        let state = self.state.read();
+
+        if state.state == PlaybackState::Error {
+            return Err(crate::Error::AudioEngine(
+                "Audio engine is in error state".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Safe wrapper for play stream operations
+    fn safe_play_operation(&mut self) -> Result<()> {
+        self.validate_state()?;
+
+        if let Some(ref stream) = self.stream {
+            stream.play().map_err(|e| {
+                // Convert CPAL error to our error type and attempt recovery
+                let error = crate::Error::AudioDevice(format!("Play operation failed: {}", e));
+
+                // Attempt recovery in background (don't propagate recovery errors)
+                let current_state = self.state.read().state;
+                if let Err(recovery_error) = self.recover_from_error(current_state) {
+                    eprintln!("Recovery failed: {}", recovery_error);
+                }
+
+                error
+            })
+        } else if self.has_output() {
+            // A null backend has no stream to drive
+            Ok(())
+        } else {
+            Err(crate::Error::AudioDevice(
+                "No audio stream available".to_string(),
+            ))
+        }
+    }
+
+    /// Safe wrapper for pause stream operations
+    fn safe_pause_operation(&mut self) -> Result<()> {
+        self.validate_state()?;
+
+        if let Some(ref stream) = self.stream {
+            stream.pause().map_err(|e| {
+                // Convert CPAL error to our error type and attempt recovery
+                let error = crate::Error::AudioDevice(format!("Pause operation failed: {}", e));
+
+                // Attempt recovery in background (don't propagate recovery errors)
+                let current_state = self.state.read().state;
+                if let Err(recovery_error) = self.recover_from_error(current_state) {
+                    eprintln!("Recovery failed: {}", recovery_error);
+                }
+
+                error
+            })
+        } else if self.has_output() {
+            // A null backend has no stream to drive
+            Ok(())
+        } else {
+            Err(crate::Error::AudioDevice(
+                "No audio stream available".to_string(),
+            ))
+        }
+    }
+
+    /// Start the audio stream
+    pub fn start_stream(&mut self) -> Result<()> {
+        self.safe_play_operation()
+    }
+
+    /// Pause the audio stream
+    pub fn pause_stream(&mut self) -> Result<()> {
+        self.safe_pause_operation()
+    }
+
+    /// Get supported audio formats for the current device
+    pub fn supported_formats(&self) -> Result<Vec<AudioFormat>> {
+        let device = self
+            .device
+            .as_ref()
+            .ok_or_else(|| crate::Error::AudioDevice("No audio device set".to_string()))?;
+
+        let supported_configs = negotiable_configs(&CpalBackend::new(device.clone()))?.configs;
+
+        let mut formats = Vec::new();
+
+        for config in supported_configs {
+            // Add configurations for common sample rates
+            let sample_rates = [
+                config.min_sample_rate,
+                44100,
+                48000,
+                96000,
+                192000,
+                config.max_sample_rate,
+            ];
+
+            for &sample_rate in &sample_rates {
+                if config.supports(sample_rate, config.channels) {
+                    let audio_format = AudioFormat::new(
+                        sample_rate,
+                        config.channels,
+                        crate::audio::format::SampleFormat::F32, // CPAL uses f32
+                    );
+
+                    if !formats.contains(&audio_format) {
+                        formats.push(audio_format);
+                    }
+                }
+            }
+        }
+
+        Ok(formats)
+    }
+
+    /// Enumerate all available output devices
+    pub fn enumerate_output_devices(&self) -> Result<Vec<AudioDeviceInfo>> {
+        let devices = self.host.output_devices().map_err(|e| {
+            crate::Error::AudioDevice(format!("Failed to enumerate devices: {}", e))
+        })?;
+
+        let mut device_infos = Vec::new();
+
+        for device in devices {
+            let name = device
+                .description()
+                .map(|desc| desc.to_string())
+                .unwrap_or_else(|_| "Unknown Device".to_string());
+
+            let supported_configs = device.supported_output_configs().map_err(|e| {
+                crate::Error::AudioDevice(format!("Failed to get device configs: {}", e))
+            })?;
+
+            let mut formats = Vec::new();
+            for config in supported_configs {
+                // Add a representative format for this config
+                let format = AudioFormat::new(
+                    config.max_sample_rate().min(48000), // Use 48kHz as default, or max if lower
+                    config.channels(),
+                    crate::audio::format::SampleFormat::F32,
+                );
+                formats.push(format);
+            }
+
+            let device_info = AudioDeviceInfo {
+                name,
+                supported_formats: formats,
+                is_default: false, // We'll set this separately
+            };
+
+            device_infos.push(device_info);
+        }
+
+        // Mark the default device
+        if let Some(default_device) = self.host.default_output_device() {
+            if let Ok(default_name) = default_device.description().map(|desc| desc.to_string()) {
+                for device_info in &mut device_infos {
+                    if device_info.name == default_name {
+                        device_info.is_default = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(device_infos)
+    }
+
+    /// Get information about the current device
+    pub fn current_device_info(&self) -> Result<Option<AudioDeviceInfo>> {
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => return Ok(None),
+        };
+
+        let name = device
+            .description()
+            .map(|desc| desc.to_string())
+            .unwrap_or_else(|_| "Unknown Device".to_string());
+
+        let supported_configs = device.supported_output_configs().map_err(|e| {
+            crate::Error::AudioDevice(format!("Failed to get device configs: {}", e))
+        })?;
+
+        let mut formats = Vec::new();
+        for config in supported_configs {
+            let format = AudioFormat::new(
+                config.max_sample_rate().min(48000),
+                config.channels(),
+                crate::audio::format::SampleFormat::F32,
+            );
+            formats.push(format);
+        }
+
+        let is_default = if let Some(default_device) = self.host.default_output_device() {
+            default_device
+                .description()
+                .map(|desc| desc.to_string())
+                .unwrap_or_default()
+                == name
+        } else {
+            false
+        };
+
+        Ok(Some(AudioDeviceInfo {
+            name,
+            supported_formats: formats,
+            is_default,
+        }))
+    }
+
+    /// Enable or disable default output device change notifications
+    ///
+    /// While enabled, a background monitor polls the system default output
+    /// device and emits `AudioEvent::DeviceChanged` when it changes. Disabling
+    /// stops and joins the monitor thread.
+    pub fn enable_device_change_notifications(&mut self, enabled: bool) {
+        if !enabled {
+            self.device_monitor = None;
+            return;
+        }
+        self.start_device_monitor(|| {
+            cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device_name(&device))
+        });
+    }
+
+    /// Enable device change notifications with `query` as the default device
+    ///
+    /// Stands in for the system's default device in tests.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn enable_device_change_notifications_with<Q>(&mut self, query: Q)
+    where
+        Q: Fn() -> Option<String> + Send + 'static,
+    {
+        self.start_device_monitor(query);
+    }
+
+    fn start_device_monitor<Q>(&mut self, query: Q)
+    where
+        Q: Fn() -> Option<String> + Send + 'static,
+    {
+        if self.device_monitor.is_some() {
+            return;
+        }
+
+        let callbacks = self.callbacks.clone();
+        let pending = self.pending_device_change.clone();
+        self.device_monitor = Some(DeviceMonitor::start(
+            DEFAULT_POLL_INTERVAL,
+            query,
+            move |name| {
+                *pending.lock() = Some(name.clone());
+                callbacks.emit(AudioEvent::DeviceChanged(name));
+            },
+        ));
+    }
+
+    /// Check if device change notifications are enabled
+    pub fn device_change_notifications_enabled(&self) -> bool {
+        self.device_monitor.is_some()
+    }
+
+    /// Set whether playback follows the system default device when it changes
+    ///
+    /// Only applies when no device was explicitly selected by name. The
+    /// engine can only rebuild its stream when called, so changes reported
+    /// by the monitor are applied at the start of the next load, play,
+    /// pause, stop or seek, or by `apply_pending_device_change`;
+    /// `Player::set_follow_default` applies them as they are reported.
+    pub fn set_follow_default(&mut self, follow: bool) {
+        self.follow_default = follow;
+    }
+
+    /// Check if playback follows the system default device
+    pub fn follow_default(&self) -> bool {
+        self.follow_default
+    }
+
+    /// Check whether the monitor reported a device change not yet applied
+    pub fn has_pending_device_change(&self) -> bool {
+        self.pending_device_change.lock().is_some()
+    }
+
+    /// Migrate the stream to the new default device if a change is pending
+    ///
+    /// The stream can only be rebuilt with exclusive access to the engine,
+    /// so the monitor only records the change; control calls apply it
+    /// before anything else, and `Player::set_follow_default` calls this
+    /// whenever one is reported. Position, volume and playback state are
+    /// preserved. If the new device can't be opened, playback stays on the
+    /// old stream. With a null backend the output is negotiated with it
+    /// again instead.
+    ///
+    /// # Returns
+    /// `true` if the stream was migrated
+    pub fn apply_pending_device_change(&mut self) -> Result<bool> {
+        let pending = self.pending_device_change.lock().take();
+        if pending.is_none() || !self.follow_default || self.selected_device_name.is_some() {
+            return Ok(false);
+        }
+
+        let (resume_state, format) = {
+            let state = self.state.read();
+            (state.state, state.format.clone())
+        };
+
+        // Take the old stream out so switching devices doesn't reset
+        // playback, silenced but kept in case the new device fails
+        let previous_device = self.device.clone();
+        let previous_stream = self.stream.take();
+        if let Some(stream) = &previous_stream {
+            let _ = stream.pause();
+        }
+        let previous_config = self.stream_config.take();
+        let previous_output = self.output_format.take();
+
+        if let Err(e) = self.open_default_output(format.as_ref()) {
+            self.device = previous_device;
+            self.stream = previous_stream;
+            self.stream_config = previous_config;
+            self.output_format = previous_output;
+            if resume_state.is_active() {
+                if let Some(stream) = &self.stream {
+                    let _ = stream.play();
+                }
+            }
+            return Err(e);
+        }
+        drop(previous_stream);
+
+        if format.is_some() && resume_state.is_active() {
+            self.start_stream()?;
+        }
+        Ok(true)
+    }
+
+    /// Open the default device and, with a track loaded, a stream for `format`
+    fn open_default_output(&mut self, format: Option<&AudioFormat>) -> Result<()> {
+        if self.null_output.is_none() {
+            self.init_default_device()?;
+        }
+        match format {
+            Some(format) => self.init_output_stream(format),
+            None => Ok(()),
+        }
+    }
+
+    /// Set device by name
+    pub fn set_device_by_name(&mut self, device_name: &str) -> Result<()> {
+        let device = self.find_output_device(device_name)?;
+        self.set_device(device)
+    }
+
+    /// Get the properties of the loaded source file
+    ///
+    /// Unlike `format()`, which describes the internal f64 pipeline, this
+    /// reports the file's own bit depth, codec and lossless flag.
+    pub fn source_info(&self) -> Option<AudioFormatInfo> {
+        self.state.read().source_info.clone()
+    }
+
+    /// Get the clipping found in the decoded source
+    ///
+    /// Computed when a file is decoded into memory; `None` for streamed
+    /// loads or when nothing is loaded.
+    pub fn source_clip_stats(&self) -> Option<ClipStats> {
+        self.state.read().clip_stats
+    }
+
+    /// Set the playback rate without changing pitch
+    ///
+    /// Rates between 0.5 and 3.0 are supported; 1.0 bypasses the time
+    /// stretcher entirely. Position and duration stay in source samples, so
+    /// the track clock follows the file's timeline at any speed.
+    pub fn set_playback_rate(&mut self, rate: f32) -> Result<()> {
+        let rate = rate as f64;
+        if !(MIN_PLAYBACK_RATE..=MAX_PLAYBACK_RATE).contains(&rate) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Playback rate {} out of range ({}..={})",
+                rate, MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE
+            )));
+        }
+
+        self.update_state(|state| {
+            state.playback_rate = rate;
+            state.reset_time_stretcher();
+            None
+        });
+
+        Ok(())
+    }
+
+    /// Get the current playback rate
+    pub fn playback_rate(&self) -> f32 {
+        self.state.read().playback_rate as f32
+    }
+
+    /// Skip leading and trailing silence of loaded tracks
+    ///
+    /// Silence below -60 dBFS lasting at least half a second at either end
+    /// is excluded from the playback range. Applies to the current track and
+    /// every track loaded afterwards.
+    pub fn set_skip_silence(&mut self, enabled: bool) {
+        self.update_state(|state| {
+            state.skip_silence = enabled;
+            state.update_play_range();
+            if state.state == PlaybackState::Stopped {
+                state.position = state.range_start();
+            }
+            None
+        });
+    }
+
+    /// Check if silence skipping is enabled
+    pub fn skip_silence(&self) -> bool {
+        self.state.read().skip_silence
+    }
+
+    /// Trim silence only at album boundaries
+    ///
+    /// While enabled, leading silence is skipped only when the previous
+    /// queue item belongs to another album, and trailing silence only when
+    /// the next one does, so transitions inside a gapless album keep their
+    /// ambience. Tracks group by their album and album artist tags; untagged
+    /// tracks and files played outside the queue stand alone. Takes
+    /// precedence over `set_skip_silence`.
+    pub fn set_album_trim(&mut self, enabled: bool) {
+        let album_keys = if enabled {
+            AlbumKey::read_all(&self.queue())
+        } else {
+            HashMap::new()
+        };
+        self.update_state(|state| {
+            state.album_trim = enabled;
+            state.album_keys = album_keys;
+            state.update_play_range();
+            if state.state == PlaybackState::Stopped {
+                state.position = state.range_start();
+            }
+            None
+        });
+    }
+
+    /// Check if album-aware silence trimming is enabled
+    pub fn album_trim(&self) -> bool {
+        self.state.read().album_trim
+    }
+
+    /// Set a manual gain for `path`, or clear it with `None`
+    ///
+    /// Applied whenever the file becomes the current track, ramped in over
+    /// a few milliseconds, and stacked on top of normalization. Takes effect
+    /// at once if the file is playing.
+    pub fn set_track_gain<P: AsRef<Path>>(&mut self, path: P, gain_db: Option<f64>) -> Result<()> {
+        if gain_db.is_some_and(|db| !db.is_finite()) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Track gain must be finite, got {:?}",
+                gain_db
+            )));
+        }
+        let path = path.as_ref().to_path_buf();
+        self.update_state(|state| {
+            let current = state.current_path.as_ref() == Some(&path);
+            match gain_db {
+                Some(db) => state.track_gains.insert(path, db),
+                None => state.track_gains.remove(&path),
+            };
+            if current {
+                state.retarget_track_gain();
+            }
+            None
+        });
+        Ok(())
+    }
+
+    /// Get the manual gain set for `path`, in dB
+    pub fn track_gain<P: AsRef<Path>>(&self, path: P) -> Option<f64> {
+        self.state.read().track_gains.get(path.as_ref()).copied()
+    }
+
+    /// Choose where ReplayGain comes from
+    ///
+    /// The gain is looked up as each track is loaded and stacked on top of
+    /// the manual track gain, so this takes effect from the next track.
+    /// It is meant instead of, not together with, normalization.
+    pub fn set_replaygain_source(&mut self, source: ReplayGainSource) {
+        self.update_state(|state| {
+            state.replaygain_source = source;
+            None
+        });
+    }
+
+    /// Get where ReplayGain comes from
+    pub fn replaygain_source(&self) -> ReplayGainSource {
+        self.state.read().replaygain_source
+    }
+
+    /// Prefer album gain over track gain (each falls back to the other)
+    ///
+    /// Takes effect from the next track.
+    pub fn set_replaygain_album(&mut self, album: bool) {
+        self.update_state(|state| {
+            state.replaygain_album = album;
+            None
+        });
+    }
+
+    /// Check if album gain is preferred over track gain
+    pub fn replaygain_album(&self) -> bool {
+        self.state.read().replaygain_album
+    }
+
+    /// Set where `ReplayGainSource::Analyzed` gets gains of untagged
+    /// tracks from
+    ///
+    /// Without one, `Analyzed` behaves like `Tags`.
+    pub fn set_replaygain_lookup(&mut self, lookup: Option<Arc<dyn ReplayGainLookup>>) {
+        self.update_state(|state| {
+            state.replaygain_lookup = lookup;
+            None
+        });
+    }
+
+    /// Replace the queue with playlist tracks and load the first one
+    ///
+    /// Each track's `gain_db` becomes the manual gain of its file, replacing
+    /// any gains set before.
+    pub fn set_queue_tracks(&mut self, tracks: &[Track]) -> Result<()> {
+        if let Some(track) = tracks
+            .iter()
+            .find(|track| track.gain_db.is_some_and(|db| !db.is_finite()))
+        {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Track gain of {} must be finite, got {:?}",
+                track.file_path, track.gain_db
+            )));
+        }
+        self.update_state(|state| {
+            state.track_gains = tracks
+                .iter()
+                .filter_map(|track| Some((PathBuf::from(&track.file_path), track.gain_db?)))
+                .collect();
+            None
+        });
+        self.set_queue(
+            tracks
+                .iter()
+                .map(|track| PathBuf::from(&track.file_path))
+                .collect(),
+        )
+    }
+
+    /// Set a pause of `ms` milliseconds between queued tracks (0 = gapless)
+    ///
+    /// Silence is rendered after a track ends and before the next queued
+    /// track starts; playback stays `Playing` throughout and the position
+    /// holds at the next track's start until the gap has passed.
+    pub fn set_inter_track_gap(&mut self, ms: u32) {
+        self.update_state(|state| {
+            state.inter_track_gap_ms = ms;
+            None
+        });
+    }
+
+    /// Get the pause between queued tracks in milliseconds
+    pub fn inter_track_gap(&self) -> u32 {
+        self.state.read().inter_track_gap_ms
+    }
+
+    /// Fade virtual tracks in and out over `ms` milliseconds (0 = hard cut)
+    ///
+    /// Smooths the click a slice boundary can cause when it is not at a
+    /// zero crossing. Only the edges of a track loaded with
+    /// `load_virtual_track` are faded, by at most a quarter of its length;
+    /// crossfades and whole-file playback are unaffected. At most
+    /// `MAX_VIRTUAL_TRACK_FADE_MS`.
+    pub fn set_virtual_track_fade(&mut self, ms: u32) -> Result<()> {
+        if ms > MAX_VIRTUAL_TRACK_FADE_MS {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Virtual track fade must be at most {} ms, got {}",
+                MAX_VIRTUAL_TRACK_FADE_MS, ms
+            )));
+        }
+        self.update_state(|state| {
+            state.virtual_track_fade_ms = ms;
+            None
+        });
+        Ok(())
+    }
+
+    /// Get the virtual track edge fade in milliseconds
+    pub fn virtual_track_fade(&self) -> u32 {
+        self.state.read().virtual_track_fade_ms
+    }
+
+    /// Set the normalization applied to tracks as they are loaded
+    ///
+    /// Only affects buffer playback, starting with the next track decoded;
+    /// the current buffer keeps its levels.
+    pub fn set_normalization(&mut self, mode: NormalizationMode) -> Result<()> {
+        mode.validate()?;
+        self.update_state(|state| {
+            state.normalization = mode;
+            None
+        });
+        Ok(())
+    }
+
+    /// Get the normalization mode
+    pub fn normalization(&self) -> NormalizationMode {
+        self.state.read().normalization
+    }
+
+    /// Get the effective playback range in frames (start, exclusive end)
+    ///
+    /// `None` when the whole track is played.
+    pub fn play_range(&self) -> Option<(u64, u64)> {
+        self.state.read().play_range
+    }
+
+    /// Set the fade applied when playback starts, pauses or stops
+    ///
+    /// # Arguments
+    /// * `ms` - Fade length in milliseconds (0 disables fading)
+    pub fn set_fade_duration(&mut self, ms: u32) {
+        self.fade_duration_ms = ms;
+    }
+
+    /// Get the play/pause/stop fade length in milliseconds
+    pub fn fade_duration(&self) -> u32 {
+        self.fade_duration_ms
+    }
+
+    /// Mix a source (e.g. a preview) over the main playback
+    ///
+    /// The source fades in and keeps playing while the main playback is
+    /// paused. It must have the output's channel count and sample rate.
+    pub fn add_preview_source(&mut self, consumer: RingBufferConsumer, gain: f32) -> MixerSourceId {
+        self.state
+            .write()
+            .mixer
+            .add_ring_buffer(consumer, gain.clamp(0.0, 1.0) as f64)
+    }
+
+    /// Change the gain of a preview source (ramped)
+    pub fn set_preview_gain(&mut self, id: MixerSourceId, gain: f32) -> Result<()> {
+        self.state
+            .write()
+            .mixer
+            .set_gain(id, gain.clamp(0.0, 1.0) as f64)
+    }
+
+    /// Fade out and remove a preview source
+    pub fn remove_preview_source(&mut self, id: MixerSourceId) -> Result<()> {
+        self.state.write().mixer.remove_source(id)
+    }
+
+    /// Play a short mono preview of the current track, e.g. while scrubbing
+    ///
+    /// Decodes only `duration_samples` frames from `start_sample` (seeking
+    /// a separate decoder), sums the channels to mono and mixes the result
+    /// over the main playback at the current volume. The main position is
+    /// left alone. A new preview fades out the one before it, so rapid
+    /// scrubbing never stacks previews; a paused output stream is resumed
+    /// so the preview is heard.
+    pub fn preview_region(&mut self, start_sample: u64, duration_samples: u64) -> Result<()> {
+        let (path, format, duration) = {
+            let state = self.state.read();
+            (
+                state.current_path.clone(),
+                state.format.clone(),
+                state.duration,
+            )
+        };
+        let (Some(path), Some(format)) = (path, format) else {
+            return Err(crate::Error::AudioEngine(
+                "No track loaded to preview".to_string(),
+            ));
+        };
+        if duration_samples == 0 || duration.is_some_and(|duration| start_sample >= duration) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Preview region {}+{} is empty or past the end",
+                start_sample, duration_samples
+            )));
+        }
+        let duration_samples = match duration {
+            Some(duration) => duration_samples.min(duration - start_sample),
+            None => duration_samples,
+        };
+
+        let preview = ScrubPreview::decode(
+            &path,
+            start_sample,
+            duration_samples,
+            format.sample_rate,
+            format.channels,
+        )?;
+        let gain = if self.is_muted() { 0.0 } else { self.volume() };
+        {
+            let mut state = self.state.write();
+            if let Some(previous) = state.scrub_preview.take() {
+                // Already gone if it was stopped through `remove_preview_source`
+                let _ = state.mixer.remove_source(previous);
+            }
+            let id = state.mixer.add_source(Box::new(preview), gain as f64);
+            state.scrub_preview = Some(id);
+        }
+        if let Some(stream) = &self.stream {
+            let _ = stream.play();
+        }
+        Ok(())
+    }
+
+    /// Fade out the scrub preview, if one is playing
+    pub fn stop_preview(&mut self) {
+        let mut state = self.state.write();
+        if let Some(id) = state.scrub_preview.take() {
+            let _ = state.mixer.remove_source(id);
+        }
+    }
+
+    /// Check whether `seek` can move within the current source
+    ///
+    /// Decoded buffers always can; a stream can when its source supports
+    /// seeking. Hosts can use this to disable the scrubber.
+    pub fn is_seekable(&self) -> bool {
+        self.stream_reader
+            .as_ref()
+            .is_none_or(|reader| reader.is_seekable())
+    }
+
+    /// Seek to a time in seconds, clamped to the track
+    ///
+    /// Converts with the loaded track's sample rate; positions past the end
+    /// land on the end and negative ones on the start.
+    pub fn seek_to_seconds(&mut self, seconds: f64) -> Result<()> {
+        let rate = self.seek_sample_rate(seconds)?;
+        self.seek_clamped(seconds * rate as f64)
+    }
+
+    /// Skip forward (or back, for a negative delta) by `delta_seconds`
+    ///
+    /// The target is clamped to the track like in `seek_to_seconds`, so
+    /// skipping back near the start lands on position 0.
+    pub fn seek_relative(&mut self, delta_seconds: f64) -> Result<()> {
+        let rate = self.seek_sample_rate(delta_seconds)?;
+        let position = self.state.read().position;
+        self.seek_clamped(position as f64 + delta_seconds * rate as f64)
+    }
+
+    /// Sample rate used to convert seek times, validating the time
+    fn seek_sample_rate(&self, seconds: f64) -> Result<u32> {
+        if !seconds.is_finite() {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Seek time must be finite, got {}",
+                seconds
+            )));
+        }
+        self.state
+            .read()
+            .format
+            .as_ref()
+            .map(|format| format.sample_rate)
+            .ok_or_else(|| crate::Error::AudioEngine("No track loaded".to_string()))
+    }
+
+    /// Seek to a fractional frame position clamped to `[0, duration]`
+    fn seek_clamped(&mut self, frames: f64) -> Result<()> {
+        // Float to integer casts saturate, so negative targets become 0
+        let position = frames.round() as u64;
+        let duration = self.state.read().duration;
+        self.seek(duration.map_or(position, |duration| position.min(duration)))
+    }
+
+    /// Get the path of the loaded file
+    pub fn current_path(&self) -> Option<PathBuf> {
+        self.state.read().current_path.clone()
+    }
+
+    /// Get a view of the current track and position that outlives borrows
+    /// of the engine
+    pub fn status(&self) -> PlaybackStatus {
+        PlaybackStatus {
+            state: self.state.clone(),
+            controls: self.controls.clone(),
+        }
+    }
+
+    /// Capture the current session for persistence
+    pub fn session_state(&self) -> SessionState {
+        let state = self.state.read();
+        SessionState {
+            file_path: state.current_path.clone(),
+            position: state.position,
+            playback_state: state.state,
+            volume: if self.is_muted() {
+                f32::from_bits(self.controls.volume_before_mute.load(Ordering::Acquire))
+            } else {
+                self.volume()
+            },
+            settings: SessionSettings {
+                fade_duration_ms: self.fade_duration_ms,
+                playback_rate: state.playback_rate as f32,
+                skip_silence: state.skip_silence,
+                album_trim: state.album_trim,
+                repeat_mode: state.queue.repeat_mode(),
+                shuffle: state.queue.is_shuffled(),
+            },
+        }
+    }
+
+    /// Restore a saved session
+    ///
+    /// Applies the saved settings, loads the file and seeks to the saved
+    /// position. The engine always ends up `Paused`, even if the session was
+    /// saved while playing; call `resume()` to continue.
+    pub fn restore_session(&mut self, session: &SessionState) -> Result<()> {
+        self.apply_session_settings(&session.settings, session.volume)?;
+
+        if let Some(path) = &session.file_path {
+            self.load_file(path)?;
+            self.pause_at_restored_position(session);
+        }
+
+        Ok(())
+    }
+
+    /// Continue playback after `restore_session`
+    pub fn resume(&mut self) -> Result<()> {
+        self.play()
+    }
+
+    fn apply_session_settings(&mut self, settings: &SessionSettings, volume: f32) -> Result<()> {
+        self.set_playback_rate(settings.playback_rate)?;
+        self.set_fade_duration(settings.fade_duration_ms);
+        self.set_skip_silence(settings.skip_silence);
+        self.set_album_trim(settings.album_trim);
+        self.set_repeat_mode(settings.repeat_mode);
+        self.set_shuffle(settings.shuffle);
+        self.set_volume(volume)
+    }
+
+    fn pause_at_restored_position(&mut self, session: &SessionState) {
+        self.update_state(|state| {
+            state.position = session.restore_position(state.duration);
+            state.reset_time_stretcher();
+            state.reset_resampler();
+            state.fade_out_pending = false;
+            state.state = PlaybackState::Paused;
+            Some(AudioEvent::StateChanged(PlaybackState::Paused))
+        });
+    }
+
+    /// Compute the per-sample fade gain step for the current format
+    fn fade_step(&self) -> Option<f32> {
+        if self.fade_duration_ms == 0 {
+            return None;
+        }
+
+        let state = self.state.read();
+        let format = state.format.as_ref()?;
+        let samples =
+            format.sample_rate as f32 * format.channels as f32 * self.fade_duration_ms as f32
+                / 1000.0;
+        Some(1.0 / samples.max(1.0))
+    }
+
+    /// Ramp up from silence (or from a fade-out still in progress)
+    fn begin_fade_in(&self) {
+        let fade_step = self.fade_step();
+        self.update_state(|state| {
+            state.fade_generation += 1;
+            let resuming = state.state != PlaybackState::Playing || state.fade_out_pending;
+            state.fade_out_pending = false;
+
+            match fade_step {
+                Some(step) if resuming => {
+                    if state.state != PlaybackState::Playing && state.fade_step >= 0.0 {
+                        state.fade_gain = 0.0;
+                    }
+                    state.fade_step = step;
+                }
+                Some(_) => {}
+                None => {
+                    state.fade_gain = 1.0;
+                    state.fade_step = 0.0;
+                }
+            }
+            None
+        });
+    }
+
+    /// Begin a fade-out ending in `target`, pausing the stream once silent
+    ///
+    /// # Returns
+    /// `false` if no fade applies and the caller should stop immediately
+    fn begin_fade_out(&mut self, target: PlaybackState) -> bool {
+        let step = match (self.fade_step(), &self.stream) {
+            (Some(step), Some(_)) if self.state() == PlaybackState::Playing => step,
+            _ => return false,
+        };
+
+        let mut generation = 0;
+        self.update_state(|state| {
+            state.state = target;
+            state.fade_out_pending = true;
+            state.fade_step = -step;
+            state.fade_generation += 1;
+            generation = state.fade_generation;
+            Some(AudioEvent::StateChanged(target))
+        });
+
+        self.pause_stream_after_fade(generation);
+        true
+    }
+
+    /// Pause the stream on the fade worker once the fade-out has finished
+    ///
+    /// The worker gives up if playback was restarted in the meantime, so
+    /// callers never block on the fade.
+    fn pause_stream_after_fade(&mut self, generation: u64) {
+        let Some(stream) = self.stream.clone() else {
+            return;
+        };
+        let deadline = Duration::from_millis(self.fade_duration_ms as u64) + FADE_PAUSE_GRACE;
+        let state = &self.state;
+        self.fade_worker
+            .get_or_insert_with(|| FadeWorker::start(state.clone()))
+            .pause_after_fade(FadePause {
+                stream,
+                generation,
+                deadline,
+            });
+    }
+
+    /// Replace the playback queue and load its first track
+    pub fn set_queue(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+        let album_keys = if self.album_trim() {
+            AlbumKey::read_all(&paths)
+        } else {
+            HashMap::new()
+        };
+        let first = self.update_queue(|queue| {
+            queue.set_items(paths);
+            queue.current().cloned()
+        });
+        {
+            let mut state = self.state.write();
+            state.next_track = None;
+            state.album_keys = album_keys;
+        }
+        self.update_prefetch_monitor();
+
+        match first {
+            Some(path) => self.switch_to_track(&path, false),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the index of the current queue item
+    pub fn current_index(&self) -> Option<usize> {
+        self.state.read().queue.current_index()
+    }
+
+    /// Get the queued files in insertion order
+    pub fn queue(&self) -> Vec<PathBuf> {
+        self.state.read().queue.items().to_vec()
+    }
+
+    /// Set the queue repeat mode
+    ///
+    /// `RepeatMode::One` loops the current track, see `set_loop`.
+    pub fn set_repeat_mode(&mut self, mode: RepeatMode) {
+        self.update_queue(|queue| queue.set_repeat_mode(mode));
+        {
+            let mut state = self.state.write();
+            // The track following the current one may have changed
+            state.next_track = None;
+            state.update_play_range();
+        }
+        if let Some(reader) = &self.stream_reader {
+            reader.set_looping(mode == RepeatMode::One);
+        }
+    }
+
+    /// Get the queue repeat mode
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.state.read().queue.repeat_mode()
+    }
+
+    /// Set the stereo balance (-1.0 = left only, 0.0 = center, 1.0 = right only)
+    ///
+    /// Attenuates the opposite channel before the master volume. Has no
+    /// effect on non-stereo sources.
+    pub fn set_balance(&mut self, balance: f32) -> Result<()> {
+        if !(-1.0..=1.0).contains(&balance) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Balance must be between -1.0 and 1.0, got {}",
+                balance
+            )));
+        }
+        self.state.write().balance = balance;
+        Ok(())
+    }
+
+    /// Get the stereo balance
+    pub fn balance(&self) -> f32 {
+        self.state.read().balance
+    }
+
+    /// Set the stereo width (0.0 = mono, 1.0 = unchanged, up to `MAX_STEREO_WIDTH`)
+    ///
+    /// Has no effect on non-stereo content.
+    pub fn set_stereo_width(&mut self, width: f32) -> Result<()> {
+        let width = StereoWidth::new(width as f64)?;
+        self.state.write().stereo_width = width;
+        Ok(())
+    }
+
+    /// Get the stereo width
+    pub fn stereo_width(&self) -> f32 {
+        self.state.read().stereo_width.width() as f32
+    }
+
+    /// Enable soft clipping after the equalizer, or disable it with `None`
+    ///
+    /// Zero drive leaves audio bit-exact, like disabling it.
+    pub fn set_saturation(&mut self, config: Option<SaturationConfig>) -> Result<()> {
+        let saturator = config.map(Saturator::new).transpose()?;
+        self.state.write().saturator = saturator;
+        Ok(())
+    }
+
+    /// Get the soft clipping settings, if enabled
+    pub fn saturation(&self) -> Option<SaturationConfig> {
+        self.state.read().saturator.map(|s| s.config())
+    }
+
+    /// Replace NaN/Inf source samples with silence before volume is applied
+    ///
+    /// On by default; protects the output device from corrupt decodes.
+    pub fn set_sample_sanitization(&mut self, enabled: bool) {
+        self.state.write().sanitize_samples = enabled;
+    }
+
+    /// Check if NaN/Inf sanitization is enabled
+    pub fn sample_sanitization(&self) -> bool {
+        self.state.read().sanitize_samples
+    }
+
+    /// Flush source samples below the smallest normal f32 to zero
+    ///
+    /// On by default. Such values are far below audibility but denormal
+    /// arithmetic is slow in the filters that follow.
+    pub fn set_denormal_protection(&mut self, enabled: bool) {
+        self.state.write().flush_denormals = enabled;
+    }
+
+    /// Check if denormal flushing is enabled
+    pub fn denormal_protection(&self) -> bool {
+        self.state.read().flush_denormals
+    }
+
+    /// Receive peak, RMS and phase correlation of every output block
+    ///
+    /// Called after each output block, on the stream's event thread; keep
+    /// it cheap, as levels queue up behind a slow callback.
+    pub fn set_meter_callback(&mut self, callback: MeterCallback) {
+        self.callbacks.set_meter(Some(callback));
+    }
+
+    /// Remove the metering callback
+    pub fn clear_meter_callback(&mut self) {
+        self.callbacks.set_meter(None);
+    }
+
+    /// Select the frequency weighting of the metered `weighted_rms`
+    ///
+    /// A- and C-weighting give SPL-meter style readings of the output;
+    /// peak, flat RMS and correlation are unaffected.
+    pub fn set_meter_weighting(&mut self, weighting: FrequencyWeighting) {
+        let mut state = self.state.write();
+        state.meter_weighting = weighting;
+        // Rebuilt for the new curve by the next metered block
+        state.meter_weighting_filters = (Vec::new(), 0, 0);
+    }
+
+    /// Get the frequency weighting of the metered `weighted_rms`
+    pub fn meter_weighting(&self) -> FrequencyWeighting {
+        self.state.read().meter_weighting
+    }
+
+    /// Set how fast the metered `peak_hold` falls, as the time in
+    /// milliseconds to drop by 20 dB (0 follows the block peak)
+    pub fn set_peak_hold_decay(&mut self, decay_ms: u32) {
+        self.state.write().meter_hold.peak_decay_ms = decay_ms;
+    }
+
+    /// Get the time in milliseconds for the metered peak hold to fall 20 dB
+    pub fn peak_hold_decay(&self) -> u32 {
+        self.state.read().meter_hold.peak_decay_ms
+    }
+
+    /// Set how long the metered `clip_held` stays set after a clip, in
+    /// milliseconds
+    ///
+    /// Applies from the next clip; 0 flags only the clipping blocks.
+    pub fn set_clip_hold(&mut self, hold_ms: u32) {
+        self.state.write().meter_hold.clip_hold_ms = hold_ms;
+    }
+
+    /// Get how long the metered clip flag is held, in milliseconds
+    pub fn clip_hold(&self) -> u32 {
+        self.state.read().meter_hold.clip_hold_ms
+    }
+
+    /// Clear a held clip indicator before its hold time runs out
+    pub fn reset_clip(&mut self) {
+        self.state.write().meter_hold.clip_frames_left = 0;
+    }
+
+    /// Apply an equalizer preset
+    ///
+    /// The chain is built before taking the state lock and swapped in with a
+    /// single write, so playback never renders a partially applied preset.
+    /// Coefficients are computed for the current sample rate.
+    pub fn apply_eq_preset(&mut self, preset: &EqPreset) -> Result<()> {
+        preset.validate()?;
+
+        let mut equalizer = Equalizer::new(preset.bands.clone());
+        if let Some(format) = self.format() {
+            equalizer.configure(format.sample_rate, format.channels);
+        }
+
+        let mut state = self.state.write();
+        state.eq_preset = Some(preset.clone());
+        state.equalizer = Some(equalizer);
+        Ok(())
+    }
+
+    /// Remove the equalizer
+    pub fn clear_eq(&mut self) {
+        let mut state = self.state.write();
+        state.eq_preset = None;
+        state.equalizer = None;
+    }
+
+    /// Get the active equalizer preset
+    pub fn eq_preset(&self) -> Option<EqPreset> {
+        self.state.read().eq_preset.clone()
+    }
+
+    /// Cut subsonic rumble below `cutoff_hz`, or disable the filter with `None`
+    ///
+    /// A 4th-order Butterworth high-pass (24 dB/octave) runs ahead of the
+    /// equalizer and independently of it; `DEFAULT_RUMBLE_CUTOFF_HZ` suits
+    /// vinyl rips. Coefficients follow the track's sample rate.
+    pub fn set_rumble_filter(&mut self, cutoff_hz: Option<f64>) -> Result<()> {
+        let filter = match cutoff_hz {
+            Some(cutoff) => {
+                let bands = butterworth_high_pass(cutoff);
+                bands.iter().try_for_each(|band| band.validate())?;
+                let mut filter = Equalizer::new(bands);
+                if let Some(format) = self.format() {
+                    filter.configure(format.sample_rate, format.channels);
+                }
+                Some((cutoff, filter))
+            }
+            None => None,
+        };
+        self.state.write().rumble_filter = filter;
+        Ok(())
+    }
+
+    /// Get the rumble filter's corner frequency in Hz (None = disabled)
+    pub fn rumble_filter(&self) -> Option<f64> {
+        self.state
+            .read()
+            .rumble_filter
+            .as_ref()
+            .map(|(cutoff, _)| *cutoff)
+    }
+
+    /// Enable or disable loudness compensation
+    ///
+    /// Bass and treble shelves make up for the ear's reduced sensitivity at
+    /// low listening levels: the lower the master volume, the stronger the
+    /// boost (see `loudness_contour`). The response is flat at full volume.
+    pub fn set_loudness_compensation(&mut self, enabled: bool) {
+        let mut state = self.state.write();
+        if enabled == state.loudness_compensation.is_some() {
+            return;
+        }
+        state.loudness_compensation = enabled.then(|| (f32::NAN, Equalizer::new(Vec::new())));
+    }
+
+    /// Check whether loudness compensation is enabled
+    pub fn loudness_compensation(&self) -> bool {
+        self.state.read().loudness_compensation.is_some()
+    }
+
+    /// Select the dithering used when the output has fewer bits than the source
+    ///
+    /// Defaults to `Triangular`. Float outputs are never dithered. Takes
+    /// effect on the next callback of a running stream.
+    pub fn set_dithering(&mut self, algorithm: DitheringAlgorithm) {
+        self.dithering
+            .store(dithering_to_u8(algorithm), Ordering::Relaxed);
+    }
+
+    /// Get the dithering algorithm
+    pub fn dithering(&self) -> DitheringAlgorithm {
+        dithering_from_u8(self.dithering.load(Ordering::Relaxed))
+    }
+
+    /// Loop the current track instead of moving on at its end
+    ///
+    /// Shorthand for `RepeatMode::One`; turning looping off falls back to
+    /// `RepeatMode::Off` and leaves other repeat modes untouched. Playback
+    /// wraps to the start of the playback range and emits `TrackEnded`
+    /// followed by `PositionChanged`. Applies to a stream that is already
+    /// playing as well.
+    pub fn set_loop(&mut self, enabled: bool) {
+        let mode = match self.repeat_mode() {
+            _ if enabled => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+            mode => mode,
+        };
+        self.set_repeat_mode(mode);
+    }
+
+    /// Check if looping is enabled
+    pub fn is_looping(&self) -> bool {
+        self.repeat_mode() == RepeatMode::One
+    }
+
+    /// Loop between two frames of the current track, or clear with `None`
+    ///
+    /// While looping is enabled the loop points become the playback range:
+    /// playback wraps from `end` (exclusive) back to `start`. With
+    /// zero-crossing snapping on, each point first moves to the nearest
+    /// zero crossing within `LOOP_SNAP_WINDOW_MS`; see `loop_points` for
+    /// where they ended up. Loop points need a decoded buffer and are
+    /// cleared when another track loads.
+    pub fn set_loop_points(&mut self, points: Option<(u64, u64)>) -> Result<()> {
+        let mut state = self.state.write();
+        if let Some((start, end)) = points {
+            let Some(buffer) = &state.buffer else {
+                return Err(crate::Error::NotSupported(
+                    "Loop points need a buffered track".to_string(),
+                ));
+            };
+            let frames = buffer.frames() as u64;
+            if start >= end || end > frames {
+                return Err(crate::Error::InvalidParameter(format!(
+                    "Loop points {}..{} are not within the track's {} frames",
+                    start, end, frames
+                )));
+            }
+        }
+        state.loop_request = points;
+        state.update_loop_points();
+        state.update_play_range();
+        Ok(())
+    }
+
+    /// Get the loop points in effect (start, exclusive end), after snapping
+    pub fn loop_points(&self) -> Option<(u64, u64)> {
+        self.state.read().loop_points
+    }
+
+    /// Snap loop points to the nearest zero crossing of the downmixed
+    /// signal, so the wrap doesn't click
+    ///
+    /// Points move by at most `LOOP_SNAP_WINDOW_MS`, keeping the loop
+    /// length close to the one requested; a point with no zero crossing
+    /// nearby stays where it is. Applies to the current loop points too.
+    pub fn set_loop_snap_to_zero_crossing(&mut self, enabled: bool) {
+        let mut state = self.state.write();
+        state.loop_snap = enabled;
+        state.update_loop_points();
+        state.update_play_range();
+    }
+
+    /// Check if loop points snap to zero crossings
+    pub fn loop_snap_to_zero_crossing(&self) -> bool {
+        self.state.read().loop_snap
+    }
+
+    /// Enable or disable queue shuffle
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.update_queue(|queue| queue.set_shuffle(shuffle));
+        self.state.write().next_track = None;
+    }
+
+    /// Skip to the next queued track
+    ///
+    /// Uses the prefetched track when available so the switch doesn't wait
+    /// for decoding. Playback continues if it was playing.
+    ///
+    /// # Returns
+    /// `false` if there is no next track
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<bool> {
+        let was_playing = self.state().is_active();
+        let next = self.update_queue(|queue| queue.next().cloned());
+
+        match next {
+            Some(path) => {
+                self.switch_to_track(&path, was_playing)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Go to the previous queued track
+    ///
+    /// Within the first few seconds of a track this moves to the prior queue
+    /// item; later (or at the start of the queue) the current track restarts.
+    pub fn previous(&mut self) -> Result<()> {
+        let seconds = {
+            let state = self.state.read();
+            let rate = state
+                .format
+                .as_ref()
+                .map(|f| f.sample_rate)
+                .unwrap_or(44100);
+            state.position as f64 / rate as f64
+        };
+
+        if seconds <= PREVIOUS_RESTART_SECONDS {
+            let was_playing = self.state().is_active();
+            if let Some(path) = self.update_queue(|queue| queue.previous().cloned()) {
+                return self.switch_to_track(&path, was_playing);
+            }
+        }
+
+        self.seek(0)
+    }
+
+    /// Decode the track that follows the current one for a gapless transition
+    ///
+    /// When the current buffer ends, a prefetched track with the same sample
+    /// rate and channel count is spliced in without a gap.
+    ///
+    /// # Returns
+    /// `true` if a track was prefetched
+    pub fn prefetch_next(&mut self) -> Result<bool> {
+        Self::prefetch_into(&self.state, true)
+    }
+
+    /// Decode the track that follows the current one into `next_track`
+    ///
+    /// Decoding happens without holding the state lock. A track at another
+    /// sample rate than the current one is resampled to it here, off the
+    /// audio thread, so the gapless splice keeps the stream rate unchanged.
+    /// A `requested` track is kept when playback moves out of the prefetch
+    /// window.
+    fn prefetch_into(state: &Arc<RwLock<AudioEngineState>>, requested: bool) -> Result<bool> {
+        let (next, current, normalization, replay_gain, stream_rate) = {
+            let state = state.read();
+            (
+                state.upcoming_track().cloned(),
+                state.queue.current().cloned(),
+                state.normalization,
+                ReplayGainSettings::new(&state),
+                state.format.as_ref().map(|f| f.sample_rate),
+            )
+        };
+
+        let path = match next {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+
+        {
+            let mut state = state.write();
+            if state
+                .next_track
+                .as_ref()
+                .is_some_and(|track| track.path == path)
+            {
+                state.next_track_requested |= requested;
+                return Ok(true);
+            }
+        }
+
+        // Repeating the same file reuses the already decoded buffer
+        let prepared = if Some(&path) == current.as_ref() {
+            let state = state.read();
+            match (&state.buffer, &state.format) {
+                (Some(buffer), Some(format)) => Some(PreparedTrack {
+                    path: path.clone(),
+                    buffer: buffer.clone(),
+                    format: format.clone(),
+                    duration: state.duration,
+                    source_info: state.source_info.clone(),
+                    clip_stats: state
+                        .clip_stats
+                        .unwrap_or_else(|| source_clip_stats(buffer, format)),
+                    replay_gain_db: state.replay_gain_db,
+                }),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let prepared = match prepared {
+            Some(prepared) => prepared,
+            None => PreparedTrack::decode(&path, normalization, &replay_gain)?,
+        };
+        let prepared = match stream_rate {
+            Some(rate) => prepared.resample_to(rate),
+            None => prepared,
+        };
+
+        // The queue or the current track may have changed while decoding
+        let mut state = state.write();
+        if state.upcoming_track() != Some(&path)
+            || state.format.as_ref().map(|f| f.sample_rate) != stream_rate
+        {
+            return Ok(false);
+        }
+        state.next_track = Some(prepared);
+        state.next_track_requested = requested;
+        Ok(true)
+    }
+
+    /// Enable or disable automatic prefetching of the next queued track
+    ///
+    /// While enabled and a queue is set, a background check decodes the next
+    /// track once the current one is within `prefetch_seconds` of its end.
+    pub fn set_auto_prefetch(&mut self, enabled: bool) {
+        self.auto_prefetch = enabled;
+        self.update_prefetch_monitor();
+    }
+
+    /// Check if automatic prefetching is enabled
+    pub fn auto_prefetch(&self) -> bool {
+        self.auto_prefetch
+    }
+
+    /// Set how long before the end of a track the next one is decoded
+    pub fn set_prefetch_seconds(&mut self, seconds: f64) -> Result<()> {
+        if !seconds.is_finite() || seconds < 0.0 {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Prefetch time must be a non-negative number of seconds, got {}",
+                seconds
+            )));
+        }
+
+        self.prefetch_seconds = seconds;
+        // Restart the monitor so it picks up the new window
+        self.prefetch_monitor = None;
+        self.update_prefetch_monitor();
+        Ok(())
+    }
+
+    /// Get the prefetch window in seconds
+    pub fn prefetch_seconds(&self) -> f64 {
+        self.prefetch_seconds
+    }
+
+    /// Run one automatic prefetch check
+    ///
+    /// Decodes the next track when playback is inside the prefetch window
+    /// and drops a prefetched track when playback has moved back out of it
+    /// (e.g. after seeking backward), so it is decoded again later. A track
+    /// prefetched with `prefetch_next` is kept.
+    ///
+    /// # Returns
+    /// `true` if a track was prefetched
+    pub fn check_prefetch(&mut self) -> Result<bool> {
+        Self::poll_prefetch(&self.state, self.prefetch_seconds)
+    }
+
+    fn poll_prefetch(state: &Arc<RwLock<AudioEngineState>>, prefetch_seconds: f64) -> Result<bool> {
+        let action = {
+            let state = state.read();
+            if state.upcoming_track().is_none() {
+                return Ok(false);
+            }
+            let sample_rate = state
+                .format
+                .as_ref()
+                .map(|f| f.sample_rate)
+                .unwrap_or(44100);
+            prefetch_action(
+                state.position,
+                state.playable_end(),
+                sample_rate,
+                prefetch_seconds,
+                state.next_track.is_some(),
+            )
+        };
+
+        match action {
+            PrefetchAction::Prefetch => Self::prefetch_into(state, false),
+            PrefetchAction::Cancel => {
+                let mut state = state.write();
+                if !state.next_track_requested {
+                    state.next_track = None;
+                }
+                Ok(false)
+            }
+            PrefetchAction::Wait => Ok(false),
+        }
+    }
+
+    /// Start or stop the prefetch monitor to match the settings and queue
+    fn update_prefetch_monitor(&mut self) {
+        let wanted = self.auto_prefetch && !self.state.read().queue.is_empty();
+        if !wanted {
+            self.prefetch_monitor = None;
+            return;
+        }
+        if self.prefetch_monitor.is_some() {
+            return;
+        }
+
+        let state = self.state.clone();
+        let callbacks = self.callbacks.clone();
+        let prefetch_seconds = self.prefetch_seconds;
+        self.prefetch_monitor = Some(PollThread::start(PREFETCH_POLL_INTERVAL, move || {
+            if let Err(e) = Self::poll_prefetch(&state, prefetch_seconds) {
+                callbacks.emit(AudioEvent::Error(format!(
+                    "Failed to prefetch next track: {}",
+                    e
+                )));
+            }
+        }));
+    }
+
+    /// Check if the next track has been prefetched
+    pub fn has_prefetched_next(&self) -> bool {
+        self.state.read().next_track.is_some()
+    }
+
+    /// Make `path` the current track, optionally resuming playback
+    fn switch_to_track(&mut self, path: &Path, resume: bool) -> Result<()> {
+        // Listeners see the outgoing track end before the new one starts
+        self.update_state(|state| state.current_path.as_ref().map(|_| AudioEvent::TrackEnded));
+
+        let prepared = {
+            let mut state = self.state.write();
+            match state.next_track.take() {
+                Some(track) if track.path == path => Some(track),
+                other => {
+                    state.next_track = other;
+                    None
+                }
+            }
+        };
+
+        match prepared {
+            // Same output format: swap buffers without rebuilding the stream
+            Some(track)
+                if self.has_output()
+                    && self.format().is_some_and(|f| {
+                        f.sample_rate == track.format.sample_rate
+                            && f.channels == track.format.channels
+                    }) =>
+            {
+                self.update_state(|state| {
+                    state.apply_prepared_track(track);
+                    state.reset_resampler();
+                    Some(AudioEvent::PlaybackModeChanged(PlaybackMode::Buffered))
+                });
+            }
+            _ => self.load_file(path)?,
+        }
+
+        if resume {
+            self.play()?;
+        }
+        Ok(())
+    }
+
+    /// Apply a change to the queue
+    fn update_queue<T, F>(&self, updater: F) -> T
+    where
+        F: FnOnce(&mut PlayQueue) -> T,
+    {
+        updater(&mut self.state.write().queue)
+    }
+
+    /// Set ring buffer consumer for streaming playback
+    pub fn set_ring_buffer_consumer(&mut self, consumer: RingBufferConsumer) -> Result<()> {
+        self.update_state(|state| {
+            state.ring_buffer_consumer = Some(consumer);
+            state.buffer = None; // Clear regular buffer when using ring buffer
+            None
+        });
+        Ok(())
+    }
+
+    /// Clear ring buffer consumer
+    pub fn clear_ring_buffer_consumer(&mut self) {
+        self.update_state(|state| {
+            state.ring_buffer_consumer = None;
+            None
+        });
+    }
+
+    /// Check if using ring buffer for playback
+    pub fn is_using_ring_buffer(&self) -> bool {
+        self.state.read().ring_buffer_consumer.is_some()
+    }
+
+    /// Read the playback status in one lock acquisition
+    ///
+    /// All fields come from the same instant, unlike separate calls to
+    /// `state()`, `position()` etc. which may straddle an audio callback.
+    pub fn playback_snapshot(&self) -> PlaybackSnapshot {
+        let state = self.state.read();
+        PlaybackSnapshot {
+            state: state.state,
+            position: state.position,
+            duration: state.duration,
+            sample_rate: state.format.as_ref().map(|f| f.sample_rate),
+            volume: self.volume(),
+            is_muted: self.is_muted(),
+            buffer_utilization: state
+                .ring_buffer_consumer
+                .as_ref()
+                .map(|consumer| consumer.available_read() as f64 / consumer.capacity() as f64),
+        }
+    }
+
+    /// Get ring buffer utilization (0.0 to 1.0) if using ring buffer
+    pub fn ring_buffer_utilization(&self) -> Option<f64> {
+        let state = self.state.read();
+        state.ring_buffer_consumer.as_ref().map(|consumer| {
+            let available = consumer.available_read();
+            let capacity = consumer.capacity();
+            available as f64 / capacity as f64
+        })
+    }
+
+    /// Pull the next block of output, for hosts that drive playback themselves
+    ///
+    /// Fills `output` with interleaved frames for `channels` channels from
+    /// the current source, with volume, fades, EQ and the other processing
+    /// stages applied exactly as the output callback would, and advances the
+    /// position. Audio is produced at the source sample rate; sources with a
+    /// different channel count are remixed as for a device with `channels`
+    /// channels (layouts that cannot be remixed render silence). Loop and
+    /// meter callbacks fire as during normal playback.
+    ///
+    /// Returns the number of frames produced; the rest of `output` is
+    /// zeroed. Do not mix with an open output stream, which pulls from the
+    /// same source.
+    pub fn render(&mut self, output: &mut [f64], channels: u16) -> usize {
+        if channels == 0 {
+            output.fill(0.0);
+            return 0;
+        }
+        let frames = output.len() / channels as usize;
+        let samples = frames * channels as usize;
+
+        let mut block = std::mem::take(&mut self.render_scratch);
+        block.clear();
+        block.resize(samples, 0.0);
+
+        let layout = OutputLayout {
+            channels: Some(channels),
+            sample_rate: None,
+        };
+        let events = {
+            let mut state = self.state.write();
+            Self::render_block(&mut block, &mut state, layout)
+        };
+        Self::emit_block_events(&self.callbacks, events);
+
+        output[..samples].copy_from_slice(&block);
+        output[samples..].fill(0.0);
+        self.render_scratch = block;
+        frames
+    }
+
+    /// Get the configuration the output stream was actually opened with
+    ///
+    /// May differ from the source format after negotiation; compare with
+    /// `format()` or use `is_bit_perfect()` to check for conversions.
+    /// `None` while no output stream is open.
+    pub fn stream_config_info(&self) -> Option<StreamConfigInfo> {
+        self.stream_config_info_for(self.format().as_ref())
+    }
+
+    /// `stream_config_info` for a source of format `source`
+    fn stream_config_info_for(&self, source: Option<&AudioFormat>) -> Option<StreamConfigInfo> {
+        let config = self.stream_config.as_ref()?;
+        let output = self.output_format.as_ref()?;
+
+        Some(StreamConfigInfo {
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            sample_format: output.sample_format,
+            buffer_size_frames: match config.buffer_size {
+                cpal::BufferSize::Fixed(frames) => Some(frames),
+                cpal::BufferSize::Default => None,
+            },
+            resampling: source.is_some_and(|f| f.sample_rate != config.sample_rate),
+            remixing: source.is_some_and(|f| f.channels != config.channels),
+        })
+    }
+
+    /// Capture the whole player configuration for a bug report
+    ///
+    /// Covers playback status, the source and output formats, streaming
+    /// health and the active processing settings. Read under a single state
+    /// lock, so all fields are consistent with each other; serialize it
+    /// (e.g. to JSON) to attach it to a report.
+    pub fn debug_snapshot(&self) -> PlayerSnapshot {
+        let state = self.state.read();
+        let consumer = state.ring_buffer_consumer.as_ref();
+        PlayerSnapshot {
+            state: state.state,
+            position: state.position,
+            duration: state.duration,
+            volume: self.volume(),
+            is_muted: self.is_muted(),
+            current_path: state.current_path.clone(),
+            format: state.format.clone(),
+            source_info: state.source_info.clone(),
+            using_ring_buffer: consumer.is_some(),
+            buffer_utilization: consumer
+                .map(|consumer| consumer.available_read() as f64 / consumer.capacity() as f64),
+            underrun_count: consumer.map_or(0, |consumer| consumer.underrun_count()),
+            stream_config: self.stream_config_info_for(state.format.as_ref()),
+            playback_rate: state.playback_rate,
+            balance: state.balance,
+            eq_preset: state.eq_preset.clone(),
+            rumble_filter_hz: state.rumble_filter.as_ref().map(|(cutoff, _)| *cutoff),
+            loudness_compensation: state.loudness_compensation.is_some(),
+            normalization: state.normalization,
+            fade_duration_ms: self.fade_duration_ms,
+            skip_silence: state.skip_silence,
+            inter_track_gap_ms: state.inter_track_gap_ms,
+            loop_enabled: state.loops(),
+        }
+    }
+
+    /// Time between the current position and the listener hearing it
+    ///
+    /// The device delay reported by the last output callback, or its period
+    /// when the backend has no timestamps. Audio still queued in a stream's
+    /// ring buffer doesn't count: the position only advances as the
+    /// callback takes it out. `None` while no output is open.
+    pub fn output_latency(&self) -> Option<Duration> {
+        if !self.has_output() {
+            return None;
+        }
+        Some(Duration::from_nanos(
+            self.output_delay_ns.load(Ordering::Relaxed),
+        ))
+    }
+
+    /// Playback position (in samples) corrected for output latency
+    ///
+    /// Matches what the listener is hearing right now; equals `position()`
+    /// when no stream is open.
+    pub fn position_with_latency(&self) -> u64 {
+        let position = self.position();
+        let (latency, format) = match (self.output_latency(), self.format()) {
+            (Some(latency), Some(format)) => (latency, format),
+            _ => return position,
+        };
+        let latency_frames = (latency.as_secs_f64() * format.sample_rate as f64).round() as u64;
+        position.saturating_sub(latency_frames)
+    }
+
+    fn emit_event(&self, event: AudioEvent) {
+        self.callbacks.emit(event);
+    }
+
+    /// Update the internal state and emit events as needed
+    fn update_state<F>(&self, updater: F)
+    where
+        F: FnOnce(&mut AudioEngineState) -> Option<AudioEvent>,
+    {
+        let mut state = self.state.write();
+        state.apply_volume_command();
+        state.apply_shared_frames();
+        let event = updater(&mut state);
+        state.publish_status();
+        if let Some(event) = event {
+            // Drop the write lock before calling the callback
+            drop(state);
+            self.emit_event(event);
+        }
+    }
+}
+
+impl Drop for AudioEngine {
+    /// Tear down output first, then the threads feeding it
+    ///
+    /// The stream is paused so no callback runs mid-teardown, pending
+    /// fade-out pauses are told to give up and the fade worker holding the
+    /// last references to the stream is joined, and the monitor and decoder
+    /// threads are stopped and joined before the device goes.
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.pause();
+        }
+        {
+            let mut state = self.state.write();
+            state.fade_generation += 1;
+            state.fade_out_pending = false;
+        }
+        self.fade_worker = None;
+        self.callbacks.set_event(None);
+        self.event_thread = None;
+
+        self.device_monitor = None;
+        self.prefetch_monitor = None;
+        self.stream_reader = None;
+        self.device = None;
+    }
+}
+
+impl AudioEngineInterface for AudioEngine {
+    fn load_file_path(&mut self, path: &Path) -> Result<()> {
+        self.handle_output_events();
+        let audio_format = self.load_buffer(path)?;
+        self.init_device_and_stream(&audio_format)
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.handle_output_events();
+        self.validate_state()?;
+
+        self.begin_fade_in();
+
+        // Start CPAL stream
+        self.start_stream().map_err(|e| {
+            self.update_state(|state| {
+                state.state = PlaybackState::Error;
                 Some(AudioEvent::Error(format!(
                     "Failed to start playback: {}",
                     e
                 )))
             });
-            e
-        })?;
+            e
+        })?;
+
+        self.enter_playing();
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.handle_output_events();
+        self.validate_state()?;
+
+        if self.begin_fade_out(PlaybackState::Paused) {
+            return Ok(());
+        }
+
+        // Pause CPAL stream
+        self.pause_stream().map_err(|e| {
+            self.update_state(|state| {
+                state.state = PlaybackState::Error;
+                Some(AudioEvent::Error(format!(
+                    "Failed to pause playback: {}",
+                    e
+                )))
+            });
+            e
+        })?;
+
+        self.update_state(|state| {
+            if state.state.is_active() {
+                state.state = PlaybackState::Paused;
+                Some(AudioEvent::StateChanged(PlaybackState::Paused))
+            } else {
+                None
+            }
+        });
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.handle_output_events();
+        // Position is reset once the fade-out completes
+        if self.begin_fade_out(PlaybackState::Stopped) {
+            return Ok(());
+        }
+
+        // Pause CPAL stream (CPAL doesn't have explicit stop)
+        if let Err(e) = self.pause_stream() {
+            // Don't fail stop operation if pause fails, just log it
+            eprintln!("Warning: Failed to pause stream during stop: {}", e);
+        }
+
+        self.update_state(|state| {
+            let was_playing = state.state.is_active() || state.state == PlaybackState::Paused;
+            state.state = PlaybackState::Stopped;
+            state.position = state.range_start();
+            state.gap_remaining = 0;
+            state.reset_resampler();
+
+            if was_playing {
+                Some(AudioEvent::StateChanged(PlaybackState::Stopped))
+            } else {
+                None
+            }
+        });
+
+        Ok(())
+    }
+
+    fn seek(&mut self, position: u64) -> Result<()> {
+        self.handle_output_events();
+        if !self.is_seekable() {
+            return Err(crate::Error::SeekUnsupported(
+                "The current stream does not support seeking".to_string(),
+            ));
+        }
+        self.update_state(|state| {
+            // Buffer playback can't go past the decoded audio; landing on
+            // its end finishes the track on the next callback
+            let position = state.buffer_end().map_or(position, |end| position.min(end));
+            let old_position = state.position;
+            state.position = position;
+            // Seeking lands on audio, not in a pending gap
+            state.gap_remaining = 0;
+
+            if old_position != position {
+                state.reset_time_stretcher();
+                state.reset_resampler();
+                Some(AudioEvent::PositionChanged(position))
+            } else {
+                None
+            }
+        });
+
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<()> {
+        // Posted without the state lock; volume changes don't emit events
+        let controls = &self.controls;
+        controls.muted.store(false, Ordering::Release); // Setting volume explicitly unmutes
+        controls.post_volume(volume.clamp(0.0, 1.0), 0);
+        Ok(())
+    }
+
+    fn set_volume_ramped(&mut self, volume: f32, ramp_duration_ms: u32) -> Result<()> {
+        // Ramps over the loaded track's sample rate; instant without a track
+        let controls = &self.controls;
+        controls.muted.store(false, Ordering::Release); // Setting volume explicitly unmutes
+        controls.post_volume(volume.clamp(0.0, 1.0), ramp_duration_ms);
+        Ok(())
+    }
+
+    fn volume(&self) -> f32 {
+        self.controls.volume()
+    }
+
+    fn mute(&mut self) -> Result<()> {
+        let controls = &self.controls;
+        if !controls.muted.swap(true, Ordering::AcqRel) {
+            // A ramp in progress is remembered by where it was going
+            let target = controls.target_volume.load(Ordering::Acquire);
+            controls.volume_before_mute.store(target, Ordering::Release);
+            controls.post_volume(0.0, 0);
+        }
+        Ok(())
+    }
+
+    fn unmute(&mut self) -> Result<()> {
+        let controls = &self.controls;
+        if controls.muted.swap(false, Ordering::AcqRel) {
+            let volume = f32::from_bits(controls.volume_before_mute.load(Ordering::Acquire));
+            controls.post_volume(volume, UNMUTE_RAMP_MS);
+        }
+        Ok(())
+    }
+
+    fn is_muted(&self) -> bool {
+        self.controls.muted.load(Ordering::Acquire)
+    }
+
+    fn state(&self) -> PlaybackState {
+        playback_state_from_u8(self.controls.state.load(Ordering::Acquire))
+    }
+
+    fn position(&self) -> u64 {
+        self.controls.position.load(Ordering::Acquire)
+    }
+
+    fn duration(&self) -> Option<u64> {
+        self.state.read().duration
+    }
+
+    fn format(&self) -> Option<AudioFormat> {
+        self.state.read().format.clone()
+    }
+
+    fn set_callback(&mut self, callback: AudioCallback) {
+        self.callbacks.set_event(Some(callback));
+    }
+
+    fn clear_callback(&mut self) {
+        self.callbacks.set_event(None);
+    }
+}
+
+impl Default for AudioEngine {
+    fn default() -> Self {
+        Self::new().expect("Failed to create audio engine")
+    }
+}
+
+/// Measure clipping per channel of a decoded buffer
+fn source_clip_stats(buffer: &AudioBuffer, format: &AudioFormat) -> ClipStats {
+    count_clipped_samples_interleaved(buffer.data(), format.channels as usize, DEFAULT_CLIP_RUN)
+}
+
+/// Get the display name of a device
+fn device_name(device: &Device) -> Option<String> {
+    device.description().map(|desc| desc.to_string()).ok()
+}
+
+/// Run `operation` up to `attempts` times, doubling the delay between tries
+fn retry_with_backoff<T, F>(attempts: u32, initial_delay: Duration, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut delay = initial_delay;
+    let mut attempt = 1;
+
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(_) => {
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// Ensure AudioEngine is Send + Sync for multi-threading
+unsafe impl Send for AudioEngine {}
+unsafe impl Sync for AudioEngine {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::output::{select_output_format, select_resampled_format};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration as StdDuration;
+
+    #[test]
+    fn test_audio_engine_creation() {
+        let engine = AudioEngine::new();
+        assert!(engine.is_ok());
+    }
+
+    #[test]
+    fn test_audio_engine_default() {
+        let _engine = AudioEngine::default();
+    }
+
+    #[test]
+    fn test_initial_state() {
+        let engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.volume(), 1.0);
+        assert_eq!(engine.position(), 0);
+        assert_eq!(engine.duration(), None);
+        assert!(engine.format().is_none());
+    }
+
+    #[test]
+    fn test_volume_control() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test setting volume
+        engine.set_volume(0.5).unwrap();
+        assert_eq!(engine.volume(), 0.5);
+
+        // Test volume clamping
+        engine.set_volume(1.5).unwrap();
+        assert_eq!(engine.volume(), 1.0);
+
+        engine.set_volume(-0.5).unwrap();
+        assert_eq!(engine.volume(), 0.0);
+    }
+
+    #[test]
+    fn test_volume_mute_unmute() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Set initial volume
+        engine.set_volume(0.75).unwrap();
+        assert_eq!(engine.volume(), 0.75);
+        assert!(!engine.is_muted());
+
+        // Mute should set volume to 0 and preserve original
+        engine.mute().unwrap();
+        assert!(engine.is_muted());
+        assert_eq!(engine.volume(), 0.0);
+
+        // Unmute should restore original volume
+        engine.unmute().unwrap();
+        assert!(!engine.is_muted());
+        assert_eq!(engine.volume(), 0.75);
+
+        // Multiple mutes should be idempotent
+        engine.mute().unwrap();
+        engine.mute().unwrap();
+        assert!(engine.is_muted());
+        assert_eq!(engine.volume(), 0.0);
+
+        // Multiple unmutes should be idempotent
+        engine.unmute().unwrap();
+        engine.unmute().unwrap();
+        assert!(!engine.is_muted());
+        assert_eq!(engine.volume(), 0.75);
+    }
+
+    #[test]
+    fn test_volume_ramping() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Set initial volume
+        engine.set_volume(0.5).unwrap();
+        assert_eq!(engine.volume(), 0.5);
+
+        // Set volume with ramping (without format, should do instant change)
+        engine.set_volume_ramped(0.8, 100).unwrap();
+        // Without format, it should change instantly
+        assert_eq!(engine.volume(), 0.8);
+
+        // Load a format to test ramping properly
+        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        engine.update_state(|state| {
+            state.format = Some(format);
+            None
+        });
+
+        // Now test ramping with format
+        engine.set_volume(0.5).unwrap();
+        engine.set_volume_ramped(1.0, 100).unwrap();
+
+        // Volume should still be at 0.5 initially
+        assert_eq!(engine.volume(), 0.5);
+
+        // The ramping will happen in the audio callback, which picks the
+        // change up on its next block; verify the ramp step was calculated
+        AudioEngine::audio_callback(&mut [0.0; 2], &engine.state);
+        let state = engine.state.read();
+        assert!(state.volume_ramp_step > 0.0);
+        assert_eq!(state.target_volume, 1.0);
+    }
+
+    #[test]
+    fn test_volume_ramping_down() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Set format for ramping
+        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        engine.update_state(|state| {
+            state.format = Some(format);
+            None
+        });
+
+        // Start at high volume
+        engine.set_volume(1.0).unwrap();
+        assert_eq!(engine.volume(), 1.0);
+
+        // Ramp down
+        engine.set_volume_ramped(0.2, 50).unwrap();
+
+        // Volume should still be at 1.0 initially
+        assert_eq!(engine.volume(), 1.0);
+
+        // Verify ramp step is negative once the callback applied it
+        AudioEngine::audio_callback(&mut [0.0; 2], &engine.state);
+        let state = engine.state.read();
+        assert!(state.volume_ramp_step < 0.0);
+        assert_eq!(state.target_volume, 0.2);
+    }
+
+    #[test]
+    fn test_mute_preserves_volume() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test with different volumes
+        for vol in [0.25, 0.5, 0.75, 1.0] {
+            engine.set_volume(vol).unwrap();
+            engine.mute().unwrap();
+            assert_eq!(engine.volume(), 0.0);
+            engine.unmute().unwrap();
+            assert_eq!(engine.volume(), vol);
+        }
+    }
+
+    /// Engine playing a constant 0.5 tone, with fades off
+    fn engine_playing_tone() -> AudioEngine {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(44100);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), vec![0.5; 88200]));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
+        engine
+    }
+
+    #[test]
+    fn test_unmute_restores_ramp_target() {
+        let mut engine = engine_playing_tone();
+        engine.set_volume(0.2).unwrap();
+        engine.render(&mut [0.0; 32], 2);
+
+        // Mute a tenth of the way into a 100ms ramp
+        engine.set_volume_ramped(1.0, 100).unwrap();
+        engine.render(&mut [0.0; 441], 1);
+        let mid_ramp = engine.volume();
+        assert!(mid_ramp > 0.2 && mid_ramp < 0.5);
+        engine.mute().unwrap();
+        engine.render(&mut [0.0; 32], 2);
+        assert_eq!(engine.volume(), 0.0);
+
+        // Unmuting ramps in to where the ramp was heading
+        engine.unmute().unwrap();
+        let mut output = vec![0.0; 4410];
+        engine.render(&mut output, 2);
+        assert!(output[0] < 0.1);
+        assert_eq!(engine.volume(), 1.0);
+        assert_eq!(output[output.len() - 1], 0.5);
+    }
+
+    #[test]
+    fn test_set_volume_cancels_ramp() {
+        let mut engine = engine_playing_tone();
+        engine.set_volume(0.2).unwrap();
+        engine.set_volume_ramped(1.0, 100).unwrap();
+        engine.render(&mut [0.0; 441], 1);
+        assert!(engine.volume() < 1.0);
+
+        engine.set_volume(0.5).unwrap();
+        let mut output = vec![0.0; 4410];
+        engine.render(&mut output, 2);
+        assert!(output.iter().all(|&s| s == 0.25));
+        assert_eq!(engine.volume(), 0.5);
+        let state = engine.state.read();
+        assert_eq!(state.volume_ramp_step, 0.0);
+        assert_eq!(state.target_volume, 0.5);
+    }
+
+    #[test]
+    fn test_volume_change_while_muted() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Set volume and mute
+        engine.set_volume(0.5).unwrap();
+        engine.mute().unwrap();
+        assert_eq!(engine.volume(), 0.0);
+        assert!(engine.is_muted());
+
+        // Change volume while muted - this should unmute
+        engine.set_volume(0.8).unwrap();
+        assert_eq!(engine.volume(), 0.8);
+        assert!(!engine.is_muted()); // set_volume explicitly unmutes
+    }
+
+    #[test]
+    fn test_volume_changes_never_silence_the_callback() {
+        use std::sync::atomic::AtomicBool;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("long.wav");
+        write_constant_wav(&path, 16384, 44100 * 4);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        engine.set_loop(true);
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            None
+        });
+        let state = engine.state.clone();
+
+        // Another thread changes and polls the volume as fast as it can
+        let done = Arc::new(AtomicBool::new(false));
+        let stop = done.clone();
+        let hammer = std::thread::spawn(move || {
+            let mut changes = 0u64;
+            while !stop.load(Ordering::Relaxed) {
+                let volume = if changes.is_multiple_of(2) { 0.5 } else { 1.0 };
+                engine.set_volume(volume).unwrap();
+                engine.set_volume_ramped(1.0 - volume / 2.0, 5).unwrap();
+                assert!(engine.volume() > 0.0);
+                assert_eq!(engine.state(), PlaybackState::Playing);
+                engine.position();
+                changes += 1;
+            }
+            changes
+        });
+
+        let mut output = vec![0.0f64; 128];
+        for block in 0..20000 {
+            AudioEngine::audio_callback(&mut output, &state);
+            assert!(
+                output.iter().all(|&s| s > 0.1),
+                "silence in block {}",
+                block
+            );
+        }
+        done.store(true, Ordering::Relaxed);
+        assert!(hammer.join().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_callback_plays_on_while_state_is_read() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let data: Vec<f64> = (0..4096)
+            .flat_map(|i| [i as f64 / 8192.0, -(i as f64) / 8192.0])
+            .collect();
+        let engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(4096);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data.clone()));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.controls.post_volume(0.5, 0);
+
+        // A getter holds the lock for reading across two callbacks
+        let mut output = vec![0.0f64; 256 * 2];
+        {
+            let _reader = engine.state.read();
+            AudioEngine::audio_callback(&mut output, &engine.state);
+            assert!(output.iter().all(|&s| s == 0.0), "pending volume");
+        }
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        {
+            let _reader = engine.state.read();
+            for _ in 0..2 {
+                AudioEngine::audio_callback(&mut output, &engine.state);
+            }
+            let expected = &data[512 * 2..768 * 2];
+            for (out, &sample) in output.iter().zip(expected) {
+                assert_eq!(*out, sample * 0.5);
+            }
+            assert_eq!(engine.position(), 768);
+        }
+
+        // The next locked block continues where the shared ones stopped
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(output[0], data[768 * 2] * 0.5);
+        assert_eq!(engine.position(), 1024);
+    }
+
+    #[test]
+    fn test_volume_ramping_clamping() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test clamping on ramped volume
+        engine.set_volume_ramped(1.5, 100).unwrap();
+        // Should be clamped to 1.0
+        AudioEngine::audio_callback(&mut [0.0; 2], &engine.state);
+        let state = engine.state.read();
+        assert_eq!(state.target_volume, 1.0);
+
+        drop(state);
+        engine.set_volume_ramped(-0.5, 100).unwrap();
+        // Should be clamped to 0.0
+        AudioEngine::audio_callback(&mut [0.0; 2], &engine.state);
+        let state = engine.state.read();
+        assert_eq!(state.target_volume, 0.0);
+    }
+
+    #[test]
+    fn test_preload_prepares_stream_for_play() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 8192, 4410);
+
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(!engine.is_stream_initialized());
+        if engine.preload(&path).is_err() {
+            // No audio device in this environment
+            return;
+        }
+        assert!(engine.is_stream_initialized());
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+
+        // play() reuses the decoded buffer and the built stream
+        let stream = engine.stream.clone().unwrap();
+        let samples = engine.state.read().buffer.as_ref().unwrap().data().as_ptr();
+        engine.play().unwrap();
+        assert!(Arc::ptr_eq(&stream, engine.stream.as_ref().unwrap()));
+        assert_eq!(
+            engine.state.read().buffer.as_ref().unwrap().data().as_ptr(),
+            samples
+        );
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Initial state should be stopped
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+
+        // Play without proper initialization should fail gracefully
+        let play_result = engine.play();
+        if play_result.is_err() {
+            // This is expected without audio device/stream
+            // The state might be Error due to our error handling
+            let current_state = engine.state();
+            assert!(
+                current_state == PlaybackState::Stopped || current_state == PlaybackState::Error
+            );
+            return;
+        }
+
+        // If play succeeded (audio system available), test normal transitions
+        assert_eq!(engine.state(), PlaybackState::Playing);
+
+        // Pause should change state
+        engine.pause().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Paused);
+
+        // Stop should change state and reset position
+        engine.stop().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Seek should update position
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        engine.seek(5000).unwrap();
+        assert_eq!(engine.position(), 5000);
+    }
+
+    #[test]
+    fn test_callback_system() {
+        let mut engine = AudioEngine::new().unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+
+        // Set up callback to capture events
+        engine.set_callback(Box::new(move |event| {
+            events_clone.lock().unwrap().push(event);
+        }));
+
+        // Trigger some state changes that don't require audio streams
+        engine.seek(1000).unwrap();
+        engine.set_volume(0.5).unwrap();
+
+        // Try play/pause/stop - these might fail without audio system
+        let _ = engine.play(); // Ignore result
+        let _ = engine.pause(); // Ignore result
+        let _ = engine.stop(); // Ignore result
+
+        // Give some time for events to be processed
+        std::thread::sleep(StdDuration::from_millis(10));
+
+        // Check that at least some events were captured (seek should always work)
+        let captured_events = events.lock().unwrap();
+        // We should have at least the position change event from seek
+        assert!(!captured_events.is_empty());
+
+        // Clear callback
+        engine.clear_callback();
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        use std::thread;
+
+        let engine = Arc::new(RwLock::new(AudioEngine::new().unwrap()));
+        let mut handles = vec![];
+
+        // Spawn multiple threads that interact with the engine
+        for i in 0..10 {
+            let engine_clone = engine.clone();
+            let handle = thread::spawn(move || {
+                let mut engine = engine_clone.write();
+                engine.set_volume(i as f32 / 10.0).unwrap();
+                engine.seek(i * 1000).unwrap();
+            });
+            handles.push(handle);
+        }
+
+        // Wait for all threads to complete
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Engine should still be in a valid state
+        let engine = engine.read();
+        assert!(engine.volume() >= 0.0 && engine.volume() <= 1.0);
+    }
+
+    #[test]
+    fn test_device_initialization() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test default device initialization
+        let result = engine.init_default_device();
+        // This might fail in CI environments without audio devices
+        if result.is_ok() {
+            assert!(engine.device().is_some());
+        }
+    }
+
+    #[test]
+    fn test_stream_initialization() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Try to initialize default device first
+        if engine.init_default_device().is_ok() {
+            let format = AudioFormat::default();
+            let result = engine.init_output_stream(&format);
+
+            // This might fail in CI environments without audio devices
+            if result.is_ok() {
+                // Stream should be initialized
+                assert!(engine.stream.is_some());
+                assert!(engine.stream_config.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_supported_formats() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Without a device, should return error
+        let result = engine.supported_formats();
+        assert!(result.is_err());
+
+        // With device (if available)
+        if engine.init_default_device().is_ok() {
+            let formats = engine.supported_formats();
+            if let Ok(formats) = formats {
+                // Should have at least one supported format
+                assert!(!formats.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_control() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Stream control without device should return errors (not panic)
+        let start_result = engine.start_stream();
+        assert!(start_result.is_err());
+
+        let pause_result = engine.pause_stream();
+        assert!(pause_result.is_err());
+
+        // The important thing is that these operations don't panic
+        // and return appropriate errors
+    }
+
+    #[test]
+    fn test_host_access() {
+        let engine = AudioEngine::new().unwrap();
+        let _host = engine.host();
+        // Should not panic and return a valid host reference
+    }
+
+    #[test]
+    fn test_device_enumeration() {
+        let engine = AudioEngine::new().unwrap();
+
+        // Test device enumeration
+        let result = engine.enumerate_output_devices();
+        if let Ok(devices) = result {
+            // Should have at least one device in most environments
+            // In CI environments without audio, this might be empty
+            for device in &devices {
+                assert!(!device.name.is_empty());
+                // At most one device should be marked as default
+                if device.is_default {
+                    println!("Default device: {}", device.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_current_device_info() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Without device, should return None
+        let info = engine.current_device_info().unwrap();
+        assert!(info.is_none());
+
+        // With device (if available)
+        if engine.init_default_device().is_ok() {
+            let info = engine.current_device_info().unwrap();
+            if let Some(device_info) = info {
+                assert!(!device_info.name.is_empty());
+                assert!(!device_info.supported_formats.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_device_by_name() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test with non-existent device
+        let result = engine.set_device_by_name("NonExistentDevice");
+        assert!(result.is_err());
+
+        // Test with real device (if available)
+        if let Ok(devices) = engine.enumerate_output_devices() {
+            if let Some(device) = devices.first() {
+                let result = engine.set_device_by_name(&device.name);
+                if result.is_ok() {
+                    assert!(engine.device().is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_negotiation() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Without device, should return error
+        let preferred_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        let result = engine.negotiate_format(&preferred_format);
+        assert!(result.is_err());
+
+        // With device (if available)
+        if engine.init_default_device().is_ok() {
+            let result = engine.negotiate_format(&preferred_format);
+            if let Ok(negotiated) = result {
+                // Should return a valid format
+                assert!(negotiated.sample_rate > 0);
+                assert!(negotiated.channels > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_best_format() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Without device, should return error
+        let result = engine.get_best_format();
+        assert!(result.is_err());
+
+        // With device (if available)
+        if engine.init_default_device().is_ok() {
+            let result = engine.get_best_format();
+            if let Ok(best_format) = result {
+                // Should return a valid high-quality format
+                assert!(best_format.sample_rate >= 44100);
+                assert!(best_format.channels >= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_support_check() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        let test_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+
+        // Without device, should return error
+        let result = engine.is_format_supported(&test_format);
+        assert!(result.is_err());
+
+        // With device (if available)
+        if engine.init_default_device().is_ok() {
+            let result = engine.is_format_supported(&test_format);
+            if let Ok(_is_supported) = result {
+                // Should return a boolean result
+            }
+        }
+    }
+
+    #[test]
+    fn test_compatibility_scoring() {
+        let _engine = AudioEngine::new().unwrap();
+
+        // Create a mock config range for testing
+        // Note: This test is limited because we can't easily create SupportedStreamConfigRange
+        // In a real scenario, this would be tested with actual device configs
+
+        let target_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+
+        // Test that the scoring function exists and can be called
+        // The actual scoring logic is tested indirectly through format negotiation
+        assert_eq!(target_format.sample_rate, 44100);
+        assert_eq!(target_format.channels, 2);
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test validation without device
+        let result = engine.validate_state();
+        assert!(result.is_ok()); // Should be OK initially
+
+        // Test load_file with non-existent file
+        let result = engine.load_file("non_existent_file.mp3");
+        assert!(result.is_err());
+
+        // Test play without loaded file/device
+        let _result = engine.play();
+        // This might succeed or fail depending on system audio availability
+        // The important thing is that it doesn't panic
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test recovery from error state
+        // Manually set error state
+        engine.update_state(|state| {
+            state.state = PlaybackState::Error;
+            Some(AudioEvent::StateChanged(PlaybackState::Error))
+        });
+
+        // Validate should fail
+        let result = engine.validate_state();
+        assert!(result.is_err());
+
+        // Recovery should work (if audio system is available)
+        let recovery_result = engine.recover_from_error(PlaybackState::Stopped);
+        if recovery_result.is_ok() {
+            // After recovery, validation should pass
+            assert!(engine.validate_state().is_ok());
+            assert_eq!(engine.state(), PlaybackState::Stopped);
+        }
+    }
+
+    #[test]
+    fn test_safe_operations() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test safe stream operations without stream
+        let result = engine.start_stream();
+        // Should return error since no stream is initialized
+        assert!(result.is_err());
+
+        let result = engine.pause_stream();
+        // Should return error since no stream is initialized
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_audio_engine_initialization_complete() {
+        // Test complete initialization process
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Verify initial state
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.volume(), 1.0);
+        assert_eq!(engine.position(), 0);
+        assert!(engine.duration().is_none());
+        assert!(engine.format().is_none());
+        assert!(engine.device().is_none());
+
+        // Test host access
+        let _host = engine.host();
+
+        // Test device initialization (if available)
+        let device_init_result = engine.init_default_device();
+        if device_init_result.is_ok() {
+            // Device should be set
+            assert!(engine.device().is_some());
+
+            // Test stream initialization
+            let format = AudioFormat::default();
+            let stream_init_result = engine.init_output_stream(&format);
+            if stream_init_result.is_ok() {
+                // Stream should be initialized
+                assert!(engine.stream.is_some());
+                assert!(engine.stream_config.is_some());
+
+                // Test that we can get supported formats
+                let formats_result = engine.supported_formats();
+                if let Ok(formats) = formats_result {
+                    assert!(!formats.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_initialization_with_custom_device() {
+        let engine = AudioEngine::new().unwrap();
+
+        // Test device enumeration
+        let devices_result = engine.enumerate_output_devices();
+        if let Ok(devices) = devices_result {
+            if !devices.is_empty() {
+                // Test creating engine with specific device
+                // Note: We can't easily test this without access to actual Device objects
+                // This test verifies the enumeration works
+                for device_info in &devices {
+                    assert!(!device_info.name.is_empty());
+                    assert!(!device_info.supported_formats.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_initialization_error_conditions() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test stream initialization without device
+        let format = AudioFormat::default();
+        let result = engine.init_output_stream(&format);
+        assert!(result.is_err());
+
+        // Test with invalid format
+        let invalid_format = AudioFormat::new(0, 0, crate::audio::format::SampleFormat::F32);
+        let result = engine.init_output_stream(&invalid_format);
+        assert!(result.is_err());
+
+        // Test format validation
+        let validation_result = crate::audio::format::validate_format(&invalid_format);
+        assert!(validation_result.is_err());
+    }
+
+    #[test]
+    fn test_initialization_state_consistency() {
+        let engine = AudioEngine::new().unwrap();
+
+        // Test that all initial state is consistent
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+        assert_eq!(engine.volume(), 1.0);
+        assert!(engine.duration().is_none());
+        assert!(engine.format().is_none());
+
+        // Test that validation passes for initial state
+        assert!(engine.validate_state().is_ok());
+    }
+
+    #[test]
+    fn test_ring_buffer_integration() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test ring buffer consumer setting
+        use crate::audio::format::{AudioFormat, SampleFormat};
+        use crate::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
+
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format: format.clone(),
+            allow_overwrite: false,
+            underrun_threshold: 0.1,
+        };
+
+        let (producer, consumer) = AudioRingBuffer::new(config).unwrap();
+
+        // Test setting ring buffer consumer
+        assert!(!engine.is_using_ring_buffer());
+        engine.set_ring_buffer_consumer(consumer).unwrap();
+        assert!(engine.is_using_ring_buffer());
+
+        // Test ring buffer utilization
+        let utilization = engine.ring_buffer_utilization();
+        assert!(utilization.is_some());
+        assert_eq!(utilization.unwrap(), 0.0); // Empty buffer
+
+        // Write some data to test utilization
+        let test_samples = vec![1.0; 1000];
+        producer.write(&test_samples);
+
+        let utilization_after = engine.ring_buffer_utilization();
+        assert!(utilization_after.is_some());
+        assert!(utilization_after.unwrap() > 0.0); // Should have some data
+
+        // Test clearing ring buffer
+        engine.clear_ring_buffer_consumer();
+        assert!(!engine.is_using_ring_buffer());
+        assert!(engine.ring_buffer_utilization().is_none());
+    }
+
+    #[test]
+    fn test_ring_buffer_file_loading() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test loading with non-existent file
+        let result = engine.load_file_with_ring_buffer("nonexistent.mp3");
+        assert!(result.is_err());
+
+        // Test loading with invalid file
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut temp_file, b"not audio data").unwrap();
+
+        let result = engine.load_file_with_ring_buffer(temp_file.path());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_initialization_thread_safety() {
+        use std::thread;
+
+        // Test that engine creation is thread-safe
+        let mut handles = vec![];
+
+        for _ in 0..5 {
+            let handle = thread::spawn(|| {
+                let engine = AudioEngine::new();
+                assert!(engine.is_ok());
+                let engine = engine.unwrap();
+                assert_eq!(engine.state(), PlaybackState::Stopped);
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_file_loading_validation() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test loading non-existent file
+        let result = engine.load_file("nonexistent.mp3");
+        assert!(result.is_err());
+
+        // Test loading unsupported format
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut temp_file, b"not audio data").unwrap();
+
+        let result = engine.load_file(temp_file.path());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decoder_integration() {
+        // Test that decoder functions are accessible
+        assert!(crate::audio::decoder::is_format_supported("test.mp3"));
+        assert!(!crate::audio::decoder::is_format_supported("test.txt"));
+
+        let extensions = crate::audio::decoder::supported_extensions();
+        assert!(extensions.contains(&"mp3"));
+        assert!(extensions.contains(&"wav"));
+    }
+
+    #[test]
+    fn test_playback_play_function() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Initial state should be stopped
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+
+        // Try to play without loading a file
+        let result = engine.play();
+        // Should either succeed (if audio system available) or fail gracefully
+        if result.is_ok() {
+            // If successful, state should be playing or error
+            let state = engine.state();
+            assert!(
+                state == PlaybackState::Playing || state == PlaybackState::Error,
+                "Expected Playing or Error state, got {:?}",
+                state
+            );
+        }
+    }
+
+    #[test]
+    fn test_playback_pause_function() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Pause without playing should work (no-op or error)
+        let result = engine.pause();
+        // Should either succeed or fail gracefully
+        if result.is_ok() {
+            let state = engine.state();
+            assert!(
+                state == PlaybackState::Stopped
+                    || state == PlaybackState::Paused
+                    || state == PlaybackState::Error
+            );
+        }
+    }
+
+    #[test]
+    fn test_playback_stop_function() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Stop should always succeed and reset position
+        let result = engine.stop();
+        assert!(result.is_ok());
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+
+        // Set position and stop again
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        engine.stop().unwrap();
+        assert_eq!(engine.position(), 0);
+    }
+
+    #[test]
+    fn test_playback_seek_function() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Seek should update position
+        engine.seek(0).unwrap();
+        assert_eq!(engine.position(), 0);
+
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        engine.seek(5000).unwrap();
+        assert_eq!(engine.position(), 5000);
+
+        // Seek backwards
+        engine.seek(2000).unwrap();
+        assert_eq!(engine.position(), 2000);
+
+        // Seek to beginning
+        engine.seek(0).unwrap();
+        assert_eq!(engine.position(), 0);
+    }
+
+    #[test]
+    fn test_playback_state_management() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test initial state
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+
+        // Test state after seeking
+        engine.seek(100).unwrap();
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+
+        // Test stop resets position
+        engine.seek(500).unwrap();
+        engine.stop().unwrap();
+        assert_eq!(engine.position(), 0);
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+    }
+
+    #[test]
+    fn test_playback_state_transitions_comprehensive() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test Stopped -> Playing
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        let play_result = engine.play();
+
+        if play_result.is_ok() && engine.state() == PlaybackState::Playing {
+            // Test Playing -> Paused
+            engine.pause().unwrap();
+            assert_eq!(engine.state(), PlaybackState::Paused);
+
+            // Test Paused -> Playing
+            engine.play().unwrap();
+            assert_eq!(engine.state(), PlaybackState::Playing);
+
+            // Test Playing -> Stopped
+            engine.stop().unwrap();
+            assert_eq!(engine.state(), PlaybackState::Stopped);
+            assert_eq!(engine.position(), 0);
+
+            // Test Paused -> Stopped
+            engine.play().unwrap();
+            engine.pause().unwrap();
+            assert_eq!(engine.state(), PlaybackState::Paused);
+            engine.stop().unwrap();
+            assert_eq!(engine.state(), PlaybackState::Stopped);
+            assert_eq!(engine.position(), 0);
+        }
+    }
+
+    #[test]
+    fn test_playback_position_persistence() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Position should persist through pause/play
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        // Try play/pause cycle
+        let _ = engine.play();
+        let _ = engine.pause();
+
+        // Position should still be at 1000 (or wherever playback moved it)
+        let position = engine.position();
+        assert!(
+            position >= 1000,
+            "Position should be >= 1000, got {}",
+            position
+        );
+    }
+
+    #[test]
+    fn test_playback_multiple_operations() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test multiple play calls
+        let _ = engine.play();
+        let _ = engine.play(); // Should be idempotent or handle gracefully
+
+        // Test multiple pause calls
+        let _ = engine.pause();
+        let _ = engine.pause(); // Should be idempotent
+
+        // Test multiple stop calls
+        engine.stop().unwrap();
+        engine.stop().unwrap(); // Should be idempotent
+
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+    }
+
+    #[test]
+    fn test_playback_seek_boundaries() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        // Test seeking to 0
+        engine.seek(0).unwrap();
+        assert_eq!(engine.position(), 0);
+
+        // Test seeking to large values
+        engine.seek(u64::MAX).unwrap();
+        assert_eq!(engine.position(), u64::MAX);
+
+        // Test seeking back to reasonable value
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.position(), 1000);
+    }
+
+    #[test]
+    fn test_playback_control_thread_safety() {
+        use parking_lot::RwLock;
+        use std::sync::Arc;
+        use std::thread;
+
+        let engine = Arc::new(RwLock::new(AudioEngine::new().unwrap()));
+        let mut handles = vec![];
+
+        // Spawn threads that perform various playback operations
+        for i in 0..5 {
+            let engine_clone = engine.clone();
+            let handle = thread::spawn(move || {
+                let mut eng = engine_clone.write();
+                match i % 4 {
+                    0 => {
+                        let _ = eng.play();
+                    }
+                    1 => {
+                        let _ = eng.pause();
+                    }
+                    2 => {
+                        let _ = eng.stop();
+                    }
+                    _ => {
+                        let _ = eng.seek(i * 1000);
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
 
-        self.update_state(|state| {
-            if state.state != PlaybackState::Playing {
-                state.state = PlaybackState::Playing;
-                Some(AudioEvent::StateChanged(PlaybackState::Playing))
+        // Engine should still be in a valid state
+        let eng = engine.read();
+        let state = eng.state();
+        assert!(
+            state == PlaybackState::Stopped
+                || state == PlaybackState::Playing
+                || state == PlaybackState::Paused
+                || state == PlaybackState::Error
+        );
+    }
+
+    #[test]
+    fn test_retry_with_backoff_succeeds_after_failures() {
+        let mut calls = 0;
+        let result = retry_with_backoff(3, StdDuration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                Err(crate::Error::AudioDevice("not yet".to_string()))
             } else {
-                None
+                Ok(calls)
             }
         });
 
-        Ok(())
+        assert_eq!(result.unwrap(), 3);
     }
 
-    fn pause(&mut self) -> Result<()> {
-        self.validate_state()?;
+    #[test]
+    fn test_retry_with_backoff_gives_up() {
+        let mut calls = 0;
+        let result: Result<()> = retry_with_backoff(3, StdDuration::from_millis(1), || {
+            calls += 1;
+            Err(crate::Error::AudioDevice("gone".to_string()))
+        });
 
-        // Pause CPAL stream
-        self.pause_stream().map_err(|e| {
-            self.update_state(|state| {
-                state.state = PlaybackState::Error;
-                Some(AudioEvent::Error(format!(
-                    "Failed to pause playback: {}",
-                    e
-                )))
-            });
-            e
-        })?;
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
 
-        self.update_state(|state| {
-            if state.state == PlaybackState::Playing {
-                state.state = PlaybackState::Paused;
-                Some(AudioEvent::StateChanged(PlaybackState::Paused))
-            } else {
-                None
-            }
+    #[test]
+    fn test_recovery_preserves_position_and_format() {
+        let mut engine = AudioEngine::new().unwrap();
+        let format = AudioFormat::new(48000, 2, crate::audio::format::SampleFormat::F64);
+
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.position = 12345;
+            state.state = PlaybackState::Error;
+            None
         });
 
-        Ok(())
+        // Recovery depends on an available device; when it succeeds the
+        // position and format must survive and a paused track stays paused
+        if engine.recover_from_error(PlaybackState::Paused).is_ok() {
+            assert_eq!(engine.position(), 12345);
+            assert_eq!(engine.format(), Some(format));
+            assert_eq!(engine.state(), PlaybackState::Paused);
+        }
     }
 
-    fn stop(&mut self) -> Result<()> {
-        // Pause CPAL stream (CPAL doesn't have explicit stop)
-        if let Err(e) = self.pause_stream() {
-            // Don't fail stop operation if pause fails, just log it
-            eprintln!("Warning: Failed to pause stream during stop: {}", e);
-        }
+    #[test]
+    fn test_stream_error_recovers_on_next_control_call() {
+        use crate::audio::output::NullBackend;
 
-        self.update_state(|state| {
-            let was_playing =
-                state.state == PlaybackState::Playing || state.state == PlaybackState::Paused;
-            state.state = PlaybackState::Stopped;
-            state.position = 0;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("error.wav");
+        write_constant_wav(&path, 1000, 44100);
 
-            if was_playing {
-                Some(AudioEvent::StateChanged(PlaybackState::Stopped))
-            } else {
-                None
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        engine.load_file(&path).unwrap();
+        engine.play().unwrap();
+        engine.advance_for_testing(4410);
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let recorded = errors.clone();
+        engine.set_callback(Box::new(move |event| {
+            if let AudioEvent::Error(message) = event {
+                recorded.lock().unwrap().push(message);
             }
-        });
+        }));
 
-        Ok(())
+        // As the stream's error callback reports a disconnect
+        engine
+            .stream_error_sender
+            .send(cpal::StreamError::DeviceNotAvailable)
+            .unwrap();
+        engine.seek(4410).unwrap();
+
+        assert!(engine.stream_errors.is_empty());
+        assert_eq!(errors.lock().unwrap().len(), 1);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 4410);
+        assert!(engine.is_stream_initialized());
+        engine.advance_for_testing(4410);
+        assert_eq!(engine.position(), 8820);
     }
 
-    fn seek(&mut self, position: u64) -> Result<()> {
-        self.update_state(|state| {
-            let old_position = state.position;
-            state.position = position;
+    #[test]
+    fn test_device_change_notifications_toggle() {
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(!engine.device_change_notifications_enabled());
 
-            if old_position != position {
-                Some(AudioEvent::PositionChanged(position))
-            } else {
-                None
-            }
-        });
+        engine.enable_device_change_notifications(true);
+        assert!(engine.device_change_notifications_enabled());
 
-        Ok(())
+        engine.enable_device_change_notifications(false);
+        assert!(!engine.device_change_notifications_enabled());
     }
 
-    fn set_volume(&mut self, volume: f32) -> Result<()> {
-        let clamped_volume = volume.clamp(0.0, 1.0);
-
-        self.update_state(|state| {
-            state.volume = clamped_volume;
-            state.target_volume = clamped_volume;
-            state.volume_ramp_step = 0.0; // Instant change
-            state.is_muted = false; // Setting volume explicitly unmutes
-            None // Volume changes don't emit events by default
-        });
+    #[test]
+    fn test_pending_device_change_requires_follow_default() {
+        let mut engine = AudioEngine::new().unwrap();
+        *engine.pending_device_change.lock() = Some("Headphones".to_string());
 
-        Ok(())
+        assert!(!engine.follow_default());
+        assert!(!engine.apply_pending_device_change().unwrap());
+        assert!(engine.pending_device_change.lock().is_none());
     }
 
-    fn set_volume_ramped(&mut self, volume: f32, ramp_duration_ms: u32) -> Result<()> {
-        let clamped_volume = volume.clamp(0.0, 1.0);
+    #[test]
+    fn test_follow_default_migrates_on_next_control_call() {
+        use crate::audio::output::NullBackend;
 
-        self.update_state(|state| {
-            state.target_volume = clamped_volume;
-            state.is_muted = false; // Setting volume explicitly unmutes
-
-            // Calculate ramp step based on sample rate and duration
-            if let Some(format) = &state.format {
-                let sample_rate = format.sample_rate as f32;
-                let ramp_samples = (sample_rate * ramp_duration_ms as f32 / 1000.0).max(1.0);
-                let volume_diff = clamped_volume - state.volume;
-                state.volume_ramp_step = volume_diff / ramp_samples;
-            } else {
-                // No format available, do instant change
-                state.volume = clamped_volume;
-                state.volume_ramp_step = 0.0;
-            }
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("follow.wav");
+        write_constant_wav(&path, 1000, 44100);
 
-            None
-        });
+        let default_device = Arc::new(parking_lot::Mutex::new(Some("Speakers".to_string())));
+        let query = default_device.clone();
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        engine.set_follow_default(true);
+        engine.enable_device_change_notifications_with(move || query.lock().clone());
+        engine.load_file(&path).unwrap();
+        engine.play().unwrap();
+        engine.advance_for_testing(4410);
+
+        *default_device.lock() = Some("Headphones".to_string());
+        let deadline = std::time::Instant::now() + StdDuration::from_secs(5);
+        while !engine.has_pending_device_change() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(StdDuration::from_millis(10));
+        }
 
-        Ok(())
+        // No player involved: the next control call migrates the stream
+        engine.seek(4410).unwrap();
+        assert!(!engine.has_pending_device_change());
+        assert!(engine.is_stream_initialized());
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 4410);
+        engine.advance_for_testing(4410);
+        assert_eq!(engine.position(), 8820);
     }
 
-    fn volume(&self) -> f32 {
-        self.state.read().volume
+    #[test]
+    fn test_failed_device_change_keeps_old_output() {
+        use crate::audio::output::NullBackend;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("keep.wav");
+        write_constant_wav(&path, 1000, 44100);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        engine.set_follow_default(true);
+        engine.load_file(&path).unwrap();
+        engine.play().unwrap();
+        engine.advance_for_testing(4410);
+        let output_format = engine.output_format();
+
+        // The new device offers nothing the track can be played with
+        engine.null_output = Some(NullBackend::new(Vec::new()));
+        *engine.pending_device_change.lock() = Some("Broken".to_string());
+        assert!(engine.apply_pending_device_change().is_err());
+
+        assert!(engine.is_stream_initialized());
+        assert_eq!(engine.output_format(), output_format);
+        assert!(engine.stream_config.is_some());
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        engine.advance_for_testing(4410);
+        assert_eq!(engine.position(), 8820);
     }
 
-    fn mute(&mut self) -> Result<()> {
-        self.update_state(|state| {
-            if !state.is_muted {
-                state.volume_before_mute = state.volume;
-                state.is_muted = true;
-                state.volume = 0.0;
-                state.target_volume = 0.0;
-                state.volume_ramp_step = 0.0;
-            }
+    #[test]
+    fn test_set_playback_rate_validation() {
+        let mut engine = AudioEngine::new().unwrap();
+
+        assert!(engine.set_playback_rate(0.25).is_err());
+        assert!(engine.set_playback_rate(4.0).is_err());
+        assert!(engine.set_playback_rate(1.5).is_ok());
+        assert_eq!(engine.playback_rate(), 1.5);
+        assert!(engine.state.read().time_stretcher.is_some());
+
+        assert!(engine.set_playback_rate(1.0).is_ok());
+        assert!(engine.state.read().time_stretcher.is_none());
+    }
+
+    #[test]
+    fn test_stretched_stream_reuses_scratch_and_keeps_whole_frames() {
+        use crate::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
+
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let (producer, consumer) =
+            AudioRingBuffer::new(RingBufferConfig::standard(format.clone())).unwrap();
+        // 2048 frames and half of one more
+        let data: Vec<f64> = (0..4097).map(|i| (i as f64 * 0.01).sin() * 0.5).collect();
+        producer.write(&data);
+
+        let engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.ring_buffer_consumer = Some(consumer);
+            state.state = PlaybackState::Playing;
+            state.playback_rate = 1.5;
+            state.reset_time_stretcher();
             None
         });
+        let scratch = {
+            let state = engine.state.read();
+            (state.stretch_chunk.as_ptr(), state.stretch_output.as_ptr())
+        };
 
-        Ok(())
+        let mut output = vec![0.0f64; 256 * 2];
+        for _ in 0..4 {
+            AudioEngine::audio_callback(&mut output, &engine.state);
+        }
+        let state = engine.state.read();
+        assert_eq!(
+            (state.stretch_chunk.as_ptr(), state.stretch_output.as_ptr()),
+            scratch
+        );
+        // The half frame waits for its other sample instead of being dropped
+        let consumer = state.ring_buffer_consumer.as_ref().unwrap();
+        assert_eq!(consumer.available_read() % 2, 1);
     }
 
-    fn unmute(&mut self) -> Result<()> {
-        self.update_state(|state| {
-            if state.is_muted {
-                state.is_muted = false;
-                state.volume = state.volume_before_mute;
-                state.target_volume = state.volume_before_mute;
-                state.volume_ramp_step = 0.0;
-            }
+    #[test]
+    fn test_playback_rate_consumes_source_faster() {
+        let engine = AudioEngine::new().unwrap();
+        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F64);
+        let frames = 44100;
+        let data: Vec<f64> = (0..frames)
+            .flat_map(|i| {
+                let s = (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 44100.0).sin();
+                [s, s]
+            })
+            .collect();
+
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(frames as u64);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data));
+            state.state = PlaybackState::Playing;
+            state.playback_rate = 2.0;
+            state.reset_time_stretcher();
             None
         });
 
-        Ok(())
-    }
+        // Render 0.25s of output: ~0.5s of source should be consumed
+        let mut output = vec![0.0f64; 512 * 2];
+        for _ in 0..(11025 / 512) {
+            AudioEngine::audio_callback(&mut output, &engine.state);
+        }
 
-    fn is_muted(&self) -> bool {
-        self.state.read().is_muted
+        let position = engine.position() as f64;
+        assert!(
+            (position / 44100.0 - 0.5).abs() < 0.05,
+            "Expected ~0.5s consumed, got {}s",
+            position / 44100.0
+        );
     }
 
-    fn state(&self) -> PlaybackState {
-        self.state.read().state
+    /// Write a short 24-bit stereo WAV for loading tests
+    fn write_test_wav_24bit(path: &std::path::Path) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 96000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..9600 {
+            let sample = ((i as f64 * 0.01).sin() * 4_000_000.0) as i32;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
     }
 
-    fn position(&self) -> u64 {
-        self.state.read().position
-    }
+    #[test]
+    fn test_source_info_after_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hires.wav");
+        write_test_wav_24bit(&path);
 
-    fn duration(&self) -> Option<u64> {
-        self.state.read().duration
-    }
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.source_info().is_none());
 
-    fn format(&self) -> Option<AudioFormat> {
-        self.state.read().format.clone()
-    }
+        engine.load_buffer(&path).unwrap();
 
-    fn set_callback(&mut self, callback: AudioCallback) {
-        self.state.write().callback = Some(callback);
+        let info = engine.source_info().unwrap();
+        assert_eq!(info.bit_depth, Some(24));
+        assert_eq!(info.sample_rate, Some(96000));
+        assert!(info.is_lossless);
+        assert!(info.is_high_resolution());
     }
 
-    fn clear_callback(&mut self) {
-        self.state.write().callback = None;
+    fn write_constant_wav(path: &std::path::Path, value: i16, frames: usize) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..frames * 2 {
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
     }
-}
 
-impl Default for AudioEngine {
-    fn default() -> Self {
-        Self::new().expect("Failed to create audio engine")
-    }
-}
+    #[test]
+    fn test_manual_track_gain_ramps_in_and_resets() {
+        let dir = tempfile::tempdir().unwrap();
+        let leveled = dir.path().join("leveled.wav");
+        let plain = dir.path().join("plain.wav");
+        write_constant_wav(&leveled, 8192, 44100);
+        write_constant_wav(&plain, 8192, 44100);
 
-// Ensure AudioEngine is Send + Sync for multi-threading
-unsafe impl Send for AudioEngine {}
-unsafe impl Sync for AudioEngine {}
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        engine.set_track_gain(&leveled, Some(3.0)).unwrap();
+        assert_eq!(engine.track_gain(&leveled), Some(3.0));
+        assert!(engine.set_track_gain(&plain, Some(f64::NAN)).is_err());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::{Arc, Mutex};
-    use std::time::Duration as StdDuration;
+        let rms = |samples: &[f64]| {
+            (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+        };
+        let render_playing = |engine: &mut AudioEngine, path: &Path| {
+            engine.load_buffer(path).unwrap();
+            engine.update_state(|state| {
+                state.state = PlaybackState::Playing;
+                None
+            });
+            // The first block covers the ramp
+            let mut ramp = vec![0.0; 4410 * 2];
+            engine.render(&mut ramp, 2);
+            let mut block = vec![0.0; 4410 * 2];
+            engine.render(&mut block, 2);
+            (ramp, rms(&block))
+        };
 
-    #[test]
-    fn test_audio_engine_creation() {
-        let engine = AudioEngine::new();
-        assert!(engine.is_ok());
+        let (ramp, leveled_rms) = render_playing(&mut engine, &leveled);
+        assert!(ramp[0] < ramp[ramp.len() - 1]);
+        let (ramp, plain_rms) = render_playing(&mut engine, &plain);
+        // Ramped back down from the previous track's gain, not stepped
+        assert!(ramp[0] > 0.3);
+        assert!((plain_rms - 0.25).abs() < 1e-3);
+        assert!((leveled_rms / plain_rms - 1.4125).abs() < 1e-3);
     }
 
     #[test]
-    fn test_audio_engine_default() {
-        let _engine = AudioEngine::default();
-    }
+    fn test_replaygain_from_analyzed_loudness() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..44100 {
+            let sample = (i as f64 * 2.0 * std::f64::consts::PI * 1000.0 / 44100.0).sin();
+            let sample = (sample * 0.1 * i16::MAX as f64) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
 
-    #[test]
-    fn test_initial_state() {
-        let engine = AudioEngine::new().unwrap();
-        assert_eq!(engine.state(), PlaybackState::Stopped);
-        assert_eq!(engine.volume(), 1.0);
-        assert_eq!(engine.position(), 0);
-        assert_eq!(engine.duration(), None);
-        assert!(engine.format().is_none());
+        let library = Arc::new(parking_lot::Mutex::new(
+            crate::library::LibraryDb::open_in_memory().unwrap(),
+        ));
+        let lookup = Arc::new(crate::library::LoudnessLookup::new(library.clone()));
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        engine.set_replaygain_lookup(Some(lookup.clone()));
+        let render_rms = |engine: &mut AudioEngine| {
+            engine.load_buffer(&path).unwrap();
+            engine.update_state(|state| {
+                state.state = PlaybackState::Playing;
+                None
+            });
+            let mut ramp = vec![0.0; 4410 * 2];
+            engine.render(&mut ramp, 2);
+            let mut block = vec![0.0; 4410 * 2];
+            engine.render(&mut block, 2);
+            (block.iter().map(|s| s * s).sum::<f64>() / block.len() as f64).sqrt()
+        };
+
+        // Untagged, so only analysis yields a gain
+        engine.set_replaygain_source(ReplayGainSource::Tags);
+        let plain = render_rms(&mut engine);
+        assert!(library
+            .lock()
+            .loudness(&path.to_string_lossy())
+            .unwrap()
+            .is_none());
+
+        engine.set_replaygain_source(ReplayGainSource::Analyzed);
+        assert_eq!(engine.replaygain_source(), ReplayGainSource::Analyzed);
+        // The first load doesn't wait for the measurement
+        assert!((render_rms(&mut engine) - plain).abs() < 1e-6);
+        lookup.wait_idle();
+        let leveled = render_rms(&mut engine);
+        // Measured in the background after the first play and stored
+        let loudness = library
+            .lock()
+            .loudness(&path.to_string_lossy())
+            .unwrap()
+            .unwrap();
+        let gain_db = loudness.track_gain_db().unwrap();
+        assert!(gain_db > 0.0);
+        assert!((20.0 * (leveled / plain).log10() - gain_db).abs() < 0.01);
+
+        engine.set_replaygain_source(ReplayGainSource::Off);
+        assert!((render_rms(&mut engine) - plain).abs() < 1e-6);
     }
 
     #[test]
-    fn test_volume_control() {
+    fn test_gapless_handoff_to_prefetched_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_constant_wav(&first, 8192, 1000);
+        write_constant_wav(&second, -8192, 1000);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&first).unwrap();
+        engine.update_queue(|queue| queue.set_items(vec![first.clone(), second.clone()]));
 
-        // Test setting volume
-        engine.set_volume(0.5).unwrap();
-        assert_eq!(engine.volume(), 0.5);
+        assert!(engine.prefetch_next().unwrap());
+        assert!(engine.has_prefetched_next());
 
-        // Test volume clamping
-        engine.set_volume(1.5).unwrap();
-        assert_eq!(engine.volume(), 1.0);
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            state.position = 990;
+            state.volume = 1.0;
+            state.target_volume = 1.0;
+            None
+        });
 
-        engine.set_volume(-0.5).unwrap();
-        assert_eq!(engine.volume(), 0.0);
+        // 10 frames left in the first track, then the second one follows
+        let mut output = vec![0.0f64; 40];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        assert!(output[..20].iter().all(|&s| s > 0.2));
+        assert!(output[20..].iter().all(|&s| s < -0.2));
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 10);
+        assert_eq!(engine.current_index(), Some(1));
+        assert!(!engine.has_prefetched_next());
     }
 
     #[test]
-    fn test_volume_mute_unmute() {
+    fn test_gapless_handoff_resamples_to_stream_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_constant_wav(&first, 8192, 1000);
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&second, spec).unwrap();
+        for _ in 0..4800 * 2 {
+            writer.write_sample(-8192i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&first).unwrap();
+        engine.set_fade_duration(0);
+        engine.update_queue(|queue| queue.set_items(vec![first.clone(), second.clone()]));
+        assert!(engine.prefetch_next().unwrap());
+        {
+            let state = engine.state.read();
+            let next = state.next_track.as_ref().unwrap();
+            assert_eq!(next.format.sample_rate, 44100);
+            assert_eq!(next.duration, Some(4410));
+        }
 
-        // Set initial volume
-        engine.set_volume(0.75).unwrap();
-        assert_eq!(engine.volume(), 0.75);
-        assert!(!engine.is_muted());
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            state.position = 500;
+            None
+        });
+        let mut output = vec![0.0f64; 2 * 1000];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        // 500 frames of the first track, then the second at the same rate
+        assert!(output[..1000].iter().all(|&s| s > 0.2));
+        let settled = &output[1100..];
+        assert!(settled.iter().all(|&s| (s + 0.25).abs() < 0.01));
+        assert_eq!(engine.current_index(), Some(1));
+        assert_eq!(engine.position(), 500);
+        assert_eq!(engine.format().unwrap().sample_rate, 44100);
+        assert_eq!(engine.duration(), Some(4410));
+        assert_eq!(engine.source_info().unwrap().sample_rate, Some(48000));
+    }
 
-        // Mute should set volume to 0 and preserve original
-        engine.mute().unwrap();
-        assert!(engine.is_muted());
-        assert_eq!(engine.volume(), 0.0);
+    #[test]
+    fn test_track_end_without_prefetch_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("only.wav");
+        write_constant_wav(&path, 8192, 100);
 
-        // Unmute should restore original volume
-        engine.unmute().unwrap();
-        assert!(!engine.is_muted());
-        assert_eq!(engine.volume(), 0.75);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            state.position = 90;
+            None
+        });
+
+        let mut output = vec![0.0f64; 40];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        assert!(output[20..].iter().all(|&s| s == 0.0));
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+    }
+
+    #[test]
+    fn test_previous_restarts_after_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.wav");
+        write_constant_wav(&path, 0, 44100 * 5);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.update_queue(|queue| queue.set_items(vec![path.clone(), path.clone()]));
+        engine.update_queue(|queue| {
+            queue.next();
+        });
+        engine.update_state(|state| {
+            state.position = 44100 * 4;
+            None
+        });
 
-        // Multiple mutes should be idempotent
-        engine.mute().unwrap();
-        engine.mute().unwrap();
-        assert!(engine.is_muted());
-        assert_eq!(engine.volume(), 0.0);
+        engine.previous().unwrap();
 
-        // Multiple unmutes should be idempotent
-        engine.unmute().unwrap();
-        engine.unmute().unwrap();
-        assert!(!engine.is_muted());
-        assert_eq!(engine.volume(), 0.75);
+        assert_eq!(engine.position(), 0);
+        assert_eq!(engine.current_index(), Some(1));
     }
 
     #[test]
-    fn test_volume_ramping() {
+    fn test_repeat_mode_and_next_at_end() {
         let mut engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.repeat_mode(), RepeatMode::Off);
 
-        // Set initial volume
-        engine.set_volume(0.5).unwrap();
-        assert_eq!(engine.volume(), 0.5);
+        engine.set_repeat_mode(RepeatMode::All);
+        assert_eq!(engine.repeat_mode(), RepeatMode::All);
 
-        // Set volume with ramping (without format, should do instant change)
-        engine.set_volume_ramped(0.8, 100).unwrap();
-        // Without format, it should change instantly
-        assert_eq!(engine.volume(), 0.8);
+        // Nothing queued
+        assert!(!engine.next().unwrap());
+        assert!(!engine.prefetch_next().unwrap());
+        assert!(engine.current_index().is_none());
+    }
 
-        // Load a format to test ramping properly
-        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+    #[test]
+    fn test_play_fades_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 16384, 4410);
+
+        let mut engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.fade_duration(), DEFAULT_FADE_DURATION_MS);
+        engine.load_buffer(&path).unwrap();
+        engine.begin_fade_in();
         engine.update_state(|state| {
-            state.format = Some(format);
+            state.state = PlaybackState::Playing;
             None
         });
 
-        // Now test ramping with format
-        engine.set_volume(0.5).unwrap();
-        engine.set_volume_ramped(1.0, 100).unwrap();
+        let mut output = vec![0.0f64; 64];
+        AudioEngine::audio_callback(&mut output, &engine.state);
 
-        // Volume should still be at 0.5 initially
-        assert_eq!(engine.volume(), 0.5);
+        assert!(output[0].abs() < 0.01);
+        assert!(output.windows(2).all(|w| w[1] >= w[0]));
+        assert!(output[63] < 0.5);
 
-        // The ramping will happen in the audio callback
-        // We can verify the ramp step was calculated
-        let state = engine.state.read();
-        assert!(state.volume_ramp_step > 0.0);
-        assert_eq!(state.target_volume, 1.0);
+        // After the fade the full level is reached
+        let mut output = vec![0.0f64; 4096];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!((output[4095] - 0.5).abs() < 0.01);
     }
 
     #[test]
-    fn test_volume_ramping_down() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_play_without_fade_is_instant() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 16384, 4410);
 
-        // Set format for ramping
-        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        engine.load_buffer(&path).unwrap();
+        engine.begin_fade_in();
         engine.update_state(|state| {
-            state.format = Some(format);
+            state.state = PlaybackState::Playing;
             None
         });
 
-        // Start at high volume
-        engine.set_volume(1.0).unwrap();
-        assert_eq!(engine.volume(), 1.0);
-
-        // Ramp down
-        engine.set_volume_ramped(0.2, 50).unwrap();
-
-        // Volume should still be at 1.0 initially
-        assert_eq!(engine.volume(), 1.0);
-
-        // Verify ramp step is negative
-        let state = engine.state.read();
-        assert!(state.volume_ramp_step < 0.0);
-        assert_eq!(state.target_volume, 0.2);
+        let mut output = vec![0.0f64; 8];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!((output[0] - 0.5).abs() < 0.01);
     }
 
     #[test]
-    fn test_mute_preserves_volume() {
+    fn test_stop_fade_out_resets_position_when_silent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 16384, 4410);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.update_state(|state| {
+            state.state = PlaybackState::Stopped;
+            state.position = 1000;
+            state.fade_out_pending = true;
+            state.fade_step = -1.0 / 32.0;
+            None
+        });
 
-        // Test with different volumes
-        for vol in [0.25, 0.5, 0.75, 1.0] {
-            engine.set_volume(vol).unwrap();
-            engine.mute().unwrap();
-            assert_eq!(engine.volume(), 0.0);
-            engine.unmute().unwrap();
-            assert_eq!(engine.volume(), vol);
-        }
+        let mut output = vec![0.0f64; 64];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        assert!(output[0] > 0.4);
+        assert!(output.windows(2).all(|w| w[1] <= w[0]));
+        assert_eq!(output[63], 0.0);
+        assert_eq!(engine.position(), 0);
+        assert!(!engine.state.read().fade_out_pending);
     }
 
     #[test]
-    fn test_volume_change_while_muted() {
+    fn test_is_bit_perfect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hires.wav");
+        write_test_wav_24bit(&path);
+
         let mut engine = AudioEngine::new().unwrap();
+        assert!(!engine.is_bit_perfect());
+
+        engine.load_buffer(&path).unwrap();
+        assert!(!engine.is_bit_perfect());
+
+        engine.output_format = Some(AudioFormat::new(96000, 2, SampleFormat::I32));
+        assert!(engine.is_bit_perfect());
+
+        // 32-bit sources carry through the f64 render path
+        if let Some(info) = engine.state.write().source_info.as_mut() {
+            info.bit_depth = Some(32);
+        }
+        assert!(engine.is_bit_perfect());
 
-        // Set volume and mute
         engine.set_volume(0.5).unwrap();
-        engine.mute().unwrap();
-        assert_eq!(engine.volume(), 0.0);
-        assert!(engine.is_muted());
+        assert!(!engine.is_bit_perfect());
+        engine.set_volume(1.0).unwrap();
 
-        // Change volume while muted - this should unmute
-        engine.set_volume(0.8).unwrap();
-        assert_eq!(engine.volume(), 0.8);
-        assert!(!engine.is_muted()); // set_volume explicitly unmutes
+        // Depth reduction and resampling are not bit-perfect
+        engine.output_format = Some(AudioFormat::new(96000, 2, SampleFormat::I16));
+        assert!(!engine.is_bit_perfect());
+        engine.output_format = Some(AudioFormat::new(48000, 2, SampleFormat::I24));
+        assert!(!engine.is_bit_perfect());
     }
 
     #[test]
-    fn test_volume_ramping_clamping() {
+    fn test_exclusive_mode_without_stream() {
         let mut engine = AudioEngine::new().unwrap();
+        assert!(!engine.exclusive_mode());
 
-        // Test clamping on ramped volume
-        engine.set_volume_ramped(1.5, 100).unwrap();
-        // Should be clamped to 1.0
-        let state = engine.state.read();
-        assert_eq!(state.target_volume, 1.0);
-
-        drop(state);
-        engine.set_volume_ramped(-0.5, 100).unwrap();
-        // Should be clamped to 0.0
-        let state = engine.state.read();
-        assert_eq!(state.target_volume, 0.0);
+        engine.set_exclusive_mode(true).unwrap();
+        assert!(engine.exclusive_mode());
     }
 
     #[test]
-    fn test_state_transitions() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_exclusive_mode_negotiation_failure() {
+        use crate::audio::output::{NullBackend, OutputConfigRange};
+        use crate::error::ErrorKind;
 
-        // Initial state should be stopped
-        assert_eq!(engine.state(), PlaybackState::Stopped);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_exclusive_mode(true).unwrap();
+        let backend = NullBackend::new(vec![OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::I16,
+        }]);
+        let source = AudioFormat::new(44100, 2, SampleFormat::I16);
+        let err = engine.select_stream_format(&backend, &source).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FormatNegotiationFailed);
+    }
 
-        // Play without proper initialization should fail gracefully
-        let play_result = engine.play();
-        if play_result.is_err() {
-            // This is expected without audio device/stream
-            // The state might be Error due to our error handling
-            let current_state = engine.state();
-            assert!(
-                current_state == PlaybackState::Stopped || current_state == PlaybackState::Error
-            );
-            return;
+    #[test]
+    fn test_skip_silence_sets_play_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("live.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        // 1s silence, 1s tone, 1s silence
+        for frame in 0..44100 * 3 {
+            let value = if (44100..88200).contains(&frame) {
+                ((frame as f64 * 0.05).sin() * 8000.0 + 12000.0) as i16
+            } else {
+                0
+            };
+            writer.write_sample(value).unwrap();
+            writer.write_sample(value).unwrap();
         }
+        writer.finalize().unwrap();
 
-        // If play succeeded (audio system available), test normal transitions
-        assert_eq!(engine.state(), PlaybackState::Playing);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_skip_silence(true);
+        engine.load_buffer(&path).unwrap();
 
-        // Pause should change state
-        engine.pause().unwrap();
-        assert_eq!(engine.state(), PlaybackState::Paused);
+        assert_eq!(engine.play_range(), Some((44100, 88200)));
+        assert_eq!(engine.position(), 44100);
 
-        // Stop should change state and reset position
-        engine.stop().unwrap();
+        // Playback stops at the end of the range
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            state.position = 88190;
+            None
+        });
+        let mut output = vec![0.0f64; 64];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output[20..].iter().all(|&s| s == 0.0));
         assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 44100);
+
+        engine.set_skip_silence(false);
+        assert_eq!(engine.play_range(), None);
         assert_eq!(engine.position(), 0);
     }
 
     #[test]
-    fn test_seek() {
-        let mut engine = AudioEngine::new().unwrap();
-
-        // Seek should update position
-        engine.seek(1000).unwrap();
-        assert_eq!(engine.position(), 1000);
+    fn test_debug_snapshot_of_loaded_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 8192, 4410);
 
-        engine.seek(5000).unwrap();
-        assert_eq!(engine.position(), 5000);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        let snapshot = engine.debug_snapshot();
+
+        assert_eq!(snapshot.state, PlaybackState::Stopped);
+        assert_eq!(snapshot.position, 0);
+        assert_eq!(snapshot.duration, Some(4410));
+        assert_eq!(snapshot.current_path.as_deref(), Some(path.as_path()));
+        let format = snapshot.format.as_ref().unwrap();
+        assert_eq!((format.sample_rate, format.channels), (44100, 2));
+        assert_eq!(
+            snapshot.source_info.as_ref().unwrap().format_name,
+            "WAV/PCM"
+        );
+        assert!(!snapshot.using_ring_buffer);
+        assert_eq!(snapshot.buffer_utilization, None);
+        assert_eq!(snapshot.underrun_count, 0);
+        assert!(snapshot.stream_config.is_none());
+        assert_eq!(snapshot.normalization, NormalizationMode::Off);
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["format"]["sample_rate"], 44100);
+        assert_eq!(json["underrun_count"], 0);
     }
 
     #[test]
-    fn test_callback_system() {
+    fn test_virtual_track_edges_fade() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("album.wav");
+        write_constant_wav(&path, 16384, 44100);
+        let track = VirtualTrack {
+            number: 2,
+            title: None,
+            performer: None,
+            file_path: path,
+            start_sample: 4410,
+            end_sample: Some(8820),
+            sample_rate: 44100,
+        };
+
         let mut engine = AudioEngine::new().unwrap();
-        let events = Arc::new(Mutex::new(Vec::new()));
-        let events_clone = events.clone();
+        assert!(engine
+            .set_virtual_track_fade(MAX_VIRTUAL_TRACK_FADE_MS + 1)
+            .is_err());
+        engine.set_virtual_track_fade(5).unwrap();
+        engine.load_virtual_buffer(&track).unwrap();
+        assert_eq!(engine.play_range(), Some((4410, 8820)));
+        assert_eq!(engine.position(), 4410);
 
-        // Set up callback to capture events
-        engine.set_callback(Box::new(move |event| {
-            events_clone.lock().unwrap().push(event);
-        }));
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            None
+        });
+        let mut output = vec![0.0f64; 4410 * 2];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        // 5 ms = 220 frames at each edge; the middle is untouched
+        let left: Vec<f64> = output.iter().step_by(2).copied().collect();
+        assert_eq!(left[0], 0.0);
+        assert!(left[..220].windows(2).all(|w| w[0] < w[1]));
+        assert!((left[110] - 0.25).abs() < 0.01);
+        assert!(left[220..4190].iter().all(|&s| (s - 0.5).abs() < 1e-3));
+        assert!(left[4190..].windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(left[4409], 0.0);
+        assert_eq!(engine.state(), PlaybackState::Stopped);
 
-        // Trigger some state changes that don't require audio streams
-        engine.seek(1000).unwrap();
-        engine.set_volume(0.5).unwrap();
+        // Whole-file loads are not faded
+        engine.load_buffer(&track.file_path).unwrap();
+        assert_eq!(engine.play_range(), None);
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            None
+        });
+        let mut output = vec![0.0f64; 8];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|&s| (s - 0.5).abs() < 1e-3));
+    }
 
-        // Try play/pause/stop - these might fail without audio system
-        let _ = engine.play(); // Ignore result
-        let _ = engine.pause(); // Ignore result
-        let _ = engine.stop(); // Ignore result
+    #[test]
+    fn test_album_trim_only_trims_album_edges() {
+        // 1s silence, 1s tone, 1s silence of 16-bit stereo, tagged via RIFF INFO
+        fn write_album_track(path: &Path, album: &str) {
+            let mut info = b"INFO".to_vec();
+            for (id, value) in [(b"IPRD", album), (b"IART", "Band")] {
+                let mut value = value.as_bytes().to_vec();
+                value.push(0);
+                if value.len() % 2 == 1 {
+                    value.push(0);
+                }
+                info.extend_from_slice(id);
+                info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                info.extend_from_slice(&value);
+            }
+            let mut data = Vec::new();
+            for frame in 0..44100 * 3 {
+                let value = if (44100..88200).contains(&frame) {
+                    ((frame as f64 * 0.05).sin() * 8000.0 + 12000.0) as i16
+                } else {
+                    0
+                };
+                data.extend_from_slice(&value.to_le_bytes());
+                data.extend_from_slice(&value.to_le_bytes());
+            }
 
-        // Give some time for events to be processed
-        std::thread::sleep(StdDuration::from_millis(10));
+            let mut bytes = b"RIFF".to_vec();
+            bytes.extend_from_slice(
+                &((4 + 24 + 8 + info.len() + 8 + data.len()) as u32).to_le_bytes(),
+            );
+            bytes.extend_from_slice(b"WAVEfmt ");
+            bytes.extend_from_slice(&16u32.to_le_bytes());
+            bytes.extend_from_slice(&1u16.to_le_bytes());
+            bytes.extend_from_slice(&2u16.to_le_bytes());
+            bytes.extend_from_slice(&44100u32.to_le_bytes());
+            bytes.extend_from_slice(&(44100u32 * 4).to_le_bytes());
+            bytes.extend_from_slice(&4u16.to_le_bytes());
+            bytes.extend_from_slice(&16u16.to_le_bytes());
+            bytes.extend_from_slice(b"LIST");
+            bytes.extend_from_slice(&(info.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&info);
+            bytes.extend_from_slice(b"data");
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&data);
+            std::fs::write(path, bytes).unwrap();
+        }
 
-        // Check that at least some events were captured (seek should always work)
-        let captured_events = events.lock().unwrap();
-        // We should have at least the position change event from seek
-        assert!(!captured_events.is_empty());
+        let dir = tempfile::tempdir().unwrap();
+        let tracks: Vec<PathBuf> = (1..=3)
+            .map(|n| {
+                let path = dir.path().join(format!("{:02}.wav", n));
+                write_album_track(&path, "Live at Home");
+                path
+            })
+            .collect();
 
-        // Clear callback
-        engine.clear_callback();
+        let mut engine = AudioEngine::new().unwrap();
+        engine.update_queue(|queue| queue.set_items(tracks.clone()));
+        engine.set_album_trim(true);
+
+        // Only the album's opening head and closing tail are trimmed
+        let expected = [Some((44100, 132300)), None, Some((0, 88200))];
+        for (index, (path, range)) in tracks.iter().zip(expected).enumerate() {
+            if index > 0 {
+                engine.update_queue(|queue| queue.next().cloned());
+            }
+            engine.load_buffer(path).unwrap();
+            assert_eq!(engine.play_range(), range, "track {}", index + 1);
+        }
+
+        // A track from another album closes the first one early
+        let single = dir.path().join("single.wav");
+        write_album_track(&single, "Single");
+        let mut queue = tracks.clone();
+        queue.insert(1, single);
+        engine.update_queue(|q| q.set_items(queue));
+        engine.set_album_trim(true);
+        engine.load_buffer(&tracks[0]).unwrap();
+        assert_eq!(engine.play_range(), Some((44100, 88200)));
+
+        engine.set_album_trim(false);
+        assert_eq!(engine.play_range(), None);
     }
 
     #[test]
-    fn test_thread_safety() {
-        use std::thread;
+    fn test_restore_session_never_auto_plays() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.wav");
+        write_constant_wav(&path, 8192, 1000);
+
+        let saved = SessionState {
+            file_path: Some(path.clone()),
+            position: 600,
+            playback_state: PlaybackState::Playing,
+            volume: 0.4,
+            settings: SessionSettings {
+                fade_duration_ms: 0,
+                ..Default::default()
+            },
+        };
 
-        let engine = Arc::new(RwLock::new(AudioEngine::new().unwrap()));
-        let mut handles = vec![];
+        // Same steps as restore_session, minus opening the output device
+        let mut engine = AudioEngine::new().unwrap();
+        engine
+            .apply_session_settings(&saved.settings, saved.volume)
+            .unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.pause_at_restored_position(&saved);
 
-        // Spawn multiple threads that interact with the engine
-        for i in 0..10 {
-            let engine_clone = engine.clone();
-            let handle = thread::spawn(move || {
-                let mut engine = engine_clone.write();
-                engine.set_volume(i as f32 / 10.0).unwrap();
-                engine.seek(i * 1000).unwrap();
-            });
-            handles.push(handle);
-        }
+        assert_eq!(engine.state(), PlaybackState::Paused);
+        assert_eq!(engine.position(), 600);
+        assert_eq!(engine.volume(), 0.4);
+        assert_eq!(engine.fade_duration(), 0);
+
+        // The callback renders nothing until playback is resumed
+        let mut output = vec![1.0f64; 64];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|&s| s == 0.0));
+        assert_eq!(engine.position(), 600);
+
+        let captured = engine.session_state();
+        assert_eq!(captured.file_path, Some(path.clone()));
+        assert_eq!(captured.playback_state, PlaybackState::Paused);
+
+        // A position at the end restores to the start
+        let finished = SessionState {
+            position: 1000,
+            ..saved
+        };
+        engine.pause_at_restored_position(&finished);
+        assert_eq!(engine.state(), PlaybackState::Paused);
+        assert_eq!(engine.position(), 0);
+    }
 
-        // Wait for all threads to complete
-        for handle in handles {
-            handle.join().unwrap();
-        }
+    #[test]
+    fn test_preview_source_mixed_over_paused_playback() {
+        use crate::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
 
-        // Engine should still be in a valid state
-        let engine = engine.read();
-        assert!(engine.volume() >= 0.0 && engine.volume() <= 1.0);
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let (producer, consumer) =
+            AudioRingBuffer::new(RingBufferConfig::standard(format)).unwrap();
+        producer.write(&[0.5; 4096]);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine
+            .state
+            .write()
+            .open_output(&AudioFormat::new(44100, 2, SampleFormat::F32), 2);
+        let scratch = engine.state.read().mixer.scratch.as_ptr();
+        let id = engine.add_preview_source(consumer, 0.5);
+
+        // Main playback is stopped; only the preview is heard, ramping in
+        let mut output = vec![0.0f64; 2048];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output[0] > 0.0 && output[0] < 0.01);
+        assert!((output[2047] - 0.25).abs() < 1e-6);
+        // Mixing reuses the scratch sized when the output opened
+        assert_eq!(engine.state.read().mixer.scratch.as_ptr(), scratch);
+
+        engine.remove_preview_source(id).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(output[2047], 0.0);
+        assert!(engine.state.read().mixer.is_empty());
     }
 
     #[test]
-    fn test_device_initialization() {
+    fn test_preview_region_leaves_position_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stereo.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..44100 {
+            writer.write_sample(16384i16).unwrap();
+            writer.write_sample(-3277i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
         let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.preview_region(0, 4410).is_err());
+        engine.load_buffer(&path).unwrap();
+        engine.seek(1000).unwrap();
+        assert!(engine.preview_region(44100, 4410).is_err());
+        assert!(engine.preview_region(0, 0).is_err());
 
-        // Test default device initialization
-        let result = engine.init_default_device();
-        // This might fail in CI environments without audio devices
-        if result.is_ok() {
-            assert!(engine.device().is_some());
-        }
+        engine.preview_region(22050, 4410).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        // Summed to mono on both channels, after the mixer's fade-in
+        let mut output = vec![0.0f64; 2048];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!((output[2046] - 0.2).abs() < 1e-3);
+        assert!((output[2047] - 0.2).abs() < 1e-3);
+        assert_eq!(engine.position(), 1000);
+
+        // A new preview replaces the previous one, which fades out
+        engine.preview_region(0, 4410).unwrap();
+        assert_eq!(engine.state.read().mixer.source_count(), 2);
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state.read().mixer.source_count(), 1);
+        assert!((output[2047] - 0.2).abs() < 1e-3);
+
+        engine.stop_preview();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(engine.state.read().mixer.is_empty());
+        assert_eq!(engine.position(), 1000);
     }
 
     #[test]
-    fn test_stream_initialization() {
+    fn test_loop_points_snap_to_zero_crossings() {
+        // 441 Hz at 44.1 kHz crosses zero every 50 frames
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let samples: Vec<f64> = (0..44100)
+            .flat_map(|i| {
+                let sample = 0.5 * (i as f64 * 2.0 * std::f64::consts::PI / 100.0 + 0.3).sin();
+                [sample, sample]
+            })
+            .collect();
         let mut engine = AudioEngine::new().unwrap();
-
-        // Try to initialize default device first
-        if engine.init_default_device().is_ok() {
-            let format = AudioFormat::default();
-            let result = engine.init_output_stream(&format);
-
-            // This might fail in CI environments without audio devices
-            if result.is_ok() {
-                // Stream should be initialized
-                assert!(engine.stream.is_some());
-                assert!(engine.stream_config.is_some());
-            }
+        assert!(engine.set_loop_points(Some((0, 100))).is_err());
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(44100);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), samples.clone()));
+            None
+        });
+        assert!(engine.set_loop_points(Some((500, 100))).is_err());
+        assert!(engine.set_loop_points(Some((0, 44101))).is_err());
+
+        engine.set_loop_points(Some((1020, 30030))).unwrap();
+        assert_eq!(engine.loop_points(), Some((1020, 30030)));
+
+        engine.set_loop_snap_to_zero_crossing(true);
+        let (start, end) = engine.loop_points().unwrap();
+        let window = 44100 * LOOP_SNAP_WINDOW_MS as u64 / 1000;
+        for (snapped, requested) in [(start, 1020), (end, 30030)] {
+            assert!(snapped.abs_diff(requested) <= window);
+            assert!(samples[snapped as usize * 2].abs() < 0.02, "{}", snapped);
         }
+        // The crossing before 1020 is nearer than the one after
+        assert!(start < 1020);
+
+        // Looping plays between the loop points
+        assert_eq!(engine.play_range(), None);
+        engine.set_loop(true);
+        assert_eq!(engine.play_range(), Some((start, end)));
+        engine.update_state(|state| {
+            state.position = end - 10;
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.render(&mut [0.0; 40], 2);
+        assert_eq!(engine.position(), start + 10);
+
+        engine.set_loop_points(None).unwrap();
+        assert_eq!(engine.play_range(), None);
     }
 
     #[test]
-    fn test_supported_formats() {
+    fn test_load_error_kinds() {
+        let dir = tempfile::tempdir().unwrap();
         let mut engine = AudioEngine::new().unwrap();
 
-        // Without a device, should return error
-        let result = engine.supported_formats();
-        assert!(result.is_err());
+        let missing = engine
+            .load_file(dir.path().join("missing.flac"))
+            .unwrap_err();
+        assert_eq!(missing.kind(), crate::ErrorKind::FileNotFound);
 
-        // With device (if available)
-        if engine.init_default_device().is_ok() {
-            let formats = engine.supported_formats();
-            if let Ok(formats) = formats {
-                // Should have at least one supported format
-                assert!(!formats.is_empty());
-            }
-        }
+        let path = dir.path().join("notes.xyz");
+        std::fs::write(&path, b"not audio").unwrap();
+        let unsupported = engine.load_file(&path).unwrap_err();
+        assert_eq!(unsupported.kind(), crate::ErrorKind::UnsupportedFormat);
     }
 
     #[test]
-    fn test_stream_control() {
+    fn test_auto_prefetch_near_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_constant_wav(&first, 8192, 44100 * 3);
+        write_constant_wav(&second, -8192, 1000);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.set_prefetch_seconds(1.0).unwrap();
+        engine.load_buffer(&first).unwrap();
+        engine.update_queue(|queue| queue.set_items(vec![first.clone(), second.clone()]));
 
-        // Stream control without device should return errors (not panic)
-        let start_result = engine.start_stream();
-        assert!(start_result.is_err());
+        // Two seconds left: outside the window
+        engine.seek(44100).unwrap();
+        assert!(!engine.check_prefetch().unwrap());
+        assert!(!engine.has_prefetched_next());
 
-        let pause_result = engine.pause_stream();
-        assert!(pause_result.is_err());
+        // Inside the last second the next track is decoded
+        engine.seek(44100 * 2 + 100).unwrap();
+        assert!(engine.check_prefetch().unwrap());
+        assert!(engine.has_prefetched_next());
 
-        // The important thing is that these operations don't panic
-        // and return appropriate errors
-    }
+        // Seeking back cancels it; re-entering the window decodes it again
+        engine.seek(0).unwrap();
+        assert!(!engine.check_prefetch().unwrap());
+        assert!(!engine.has_prefetched_next());
+        engine.seek(44100 * 3 - 10).unwrap();
+        assert!(engine.check_prefetch().unwrap());
+        assert!(engine.has_prefetched_next());
 
-    #[test]
-    fn test_host_access() {
-        let engine = AudioEngine::new().unwrap();
-        let _host = engine.host();
-        // Should not panic and return a valid host reference
+        // A manually requested track survives leaving the window
+        engine.seek(0).unwrap();
+        assert!(engine.prefetch_next().unwrap());
+        assert!(!engine.check_prefetch().unwrap());
+        assert!(engine.has_prefetched_next());
+
+        assert!(engine.set_prefetch_seconds(-1.0).is_err());
     }
 
     #[test]
-    fn test_device_enumeration() {
-        let engine = AudioEngine::new().unwrap();
+    fn test_output_latency() {
+        use crate::audio::output::NullBackend;
+        use cpal::{OutputStreamTimestamp, StreamInstant};
 
-        // Test device enumeration
-        let result = engine.enumerate_output_devices();
-        if let Ok(devices) = result {
-            // Should have at least one device in most environments
-            // In CI environments without audio, this might be empty
-            for device in &devices {
-                assert!(!device.name.is_empty());
-                // At most one device should be marked as default
-                if device.is_default {
-                    println!("Default device: {}", device.name);
-                }
-            }
-        }
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("latency.wav");
+        write_constant_wav(&path, 1000, 44100);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        assert!(engine.output_latency().is_none());
+        engine.load_file(&path).unwrap();
+
+        // Backend without timestamps: the callback buffer duration is used
+        let callback = StreamInstant::new(1, 0);
+        let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback,
+            playback: callback,
+        });
+        AudioEngine::record_output_delay(&engine.output_delay_ns, &info, 512, 44100);
+        let latency = engine.output_latency().unwrap();
+        let expected = Duration::from_secs_f64(512.0 / 44100.0);
+        assert!(latency.abs_diff(expected) < Duration::from_micros(1));
+
+        // Reported playback timestamp 20ms after the callback
+        let info = OutputCallbackInfo::new(OutputStreamTimestamp {
+            callback,
+            playback: callback.add(Duration::from_millis(20)).unwrap(),
+        });
+        AudioEngine::record_output_delay(&engine.output_delay_ns, &info, 512, 44100);
+        assert_eq!(engine.output_latency(), Some(Duration::from_millis(20)));
+
+        // The audible position trails the raw position by the latency
+        engine.seek(44100).unwrap();
+        assert_eq!(engine.position_with_latency(), 44100 - 882);
+        engine.seek(100).unwrap();
+        assert_eq!(engine.position_with_latency(), 0);
     }
 
     #[test]
-    fn test_current_device_info() {
+    fn test_streaming_latency_excludes_ring_buffer() {
+        use crate::audio::output::NullBackend;
+
+        let format = AudioFormat::new(44100, 2, SampleFormat::F32);
         let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        let producer = engine
+            .create_streaming_input(format.clone(), RingBufferConfig::standard(format))
+            .unwrap();
+
+        // Two seconds of audio wait in the ring while playback starts
+        assert_eq!(producer.write(&vec![0.25; 88200 * 2]), 88200 * 2);
+        engine.play().unwrap();
+        engine.advance_for_testing(4410);
+        assert_eq!(engine.position(), 4410);
+
+        // Only the device period lies between the position and the listener
+        let period = Duration::from_secs_f64(TESTING_BLOCK_FRAMES as f64 / 44100.0);
+        let latency = engine.output_latency().unwrap();
+        assert!(latency.abs_diff(period) < Duration::from_micros(1));
+        assert_eq!(
+            engine.position_with_latency(),
+            4410 - TESTING_BLOCK_FRAMES as u64
+        );
+    }
 
-        // Without device, should return None
-        let info = engine.current_device_info().unwrap();
-        assert!(info.is_none());
+    #[test]
+    fn test_callback_with_zero_channel_format_is_silent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("zero.wav");
+        write_constant_wav(&path, 1000, 4410);
 
-        // With device (if available)
-        if engine.init_default_device().is_ok() {
-            let info = engine.current_device_info().unwrap();
-            if let Some(device_info) = info {
-                assert!(!device_info.name.is_empty());
-                assert!(!device_info.supported_formats.is_empty());
-            }
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        {
+            let mut state = engine.state.write();
+            state.format = Some(AudioFormat::new(44100, 0, SampleFormat::F64));
+            state.state = PlaybackState::Playing;
         }
+
+        let mut output = vec![1.0f64; 512];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|&s| s == 0.0));
+        assert_eq!(engine.position(), 0);
     }
 
     #[test]
-    fn test_set_device_by_name() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_set_stream_config() {
+        use crate::audio::decoder::StreamConfig as DecoderStreamConfig;
 
-        // Test with non-existent device
-        let result = engine.set_device_by_name("NonExistentDevice");
-        assert!(result.is_err());
+        let mut engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.stream_config().buffer_size, 1024);
 
-        // Test with real device (if available)
-        if let Ok(devices) = engine.enumerate_output_devices() {
-            if let Some(device) = devices.first() {
-                let result = engine.set_device_by_name(&device.name);
-                if result.is_ok() {
-                    assert!(engine.device().is_some());
-                }
-            }
-        }
+        let config = DecoderStreamConfig {
+            buffer_size: 4096,
+            loop_playback: false,
+            prefetch_size: 16,
+        };
+        engine.set_stream_config(config).unwrap();
+        assert_eq!(engine.stream_config().buffer_size, 4096);
+        assert_eq!(engine.stream_config().prefetch_size, 16);
+
+        // Invalid sizing is rejected and the previous config kept
+        let invalid = DecoderStreamConfig {
+            prefetch_size: 1000,
+            ..DecoderStreamConfig::default()
+        };
+        assert!(matches!(
+            engine.set_stream_config(invalid),
+            Err(crate::Error::InvalidParameter(_))
+        ));
+        assert_eq!(engine.stream_config().prefetch_size, 16);
     }
 
     #[test]
-    fn test_format_negotiation() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_loop_wraps_at_end() {
+        use std::sync::atomic::AtomicUsize;
 
-        // Without device, should return error
-        let preferred_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
-        let result = engine.negotiate_format(&preferred_format);
-        assert!(result.is_err());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("loop.wav");
+        write_constant_wav(&path, 1000, 1000);
 
-        // With device (if available)
-        if engine.init_default_device().is_ok() {
-            let result = engine.negotiate_format(&preferred_format);
-            if let Ok(negotiated) = result {
-                // Should return a valid format
-                assert!(negotiated.sample_rate > 0);
-                assert!(negotiated.channels > 0);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        engine.set_loop(true);
+        assert!(engine.is_looping());
+
+        let ended = Arc::new(AtomicUsize::new(0));
+        let events = ended.clone();
+        engine.set_callback(Box::new(move |event| {
+            if let AudioEvent::TrackEnded = event {
+                events.fetch_add(1, Ordering::SeqCst);
             }
-        }
+        }));
+
+        engine.seek(900).unwrap();
+        engine.state.write().state = PlaybackState::Playing;
+
+        // 256 stereo frames: 100 from the end, 156 from the start again
+        let mut output = vec![0.0f64; 512];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 156);
+        assert_eq!(ended.load(Ordering::SeqCst), 1);
+        assert!(output.iter().all(|&s| s != 0.0));
+
+        // Without looping playback stops at the end
+        engine.set_loop(false);
+        engine.seek(900).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(ended.load(Ordering::SeqCst), 2);
     }
 
     #[test]
-    fn test_best_format() {
+    fn test_advance_for_testing_reaches_track_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("short.wav");
+        write_constant_wav(&path, 1000, 4410);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        engine.set_callback(Box::new(move |event| sink.lock().unwrap().push(event)));
+        engine.state.write().state = PlaybackState::Playing;
+
+        engine.advance_for_testing(2205);
+        assert_eq!(engine.position(), 2205);
+        assert!(events.lock().unwrap().is_empty());
+
+        engine.advance_for_testing(4410);
+        let events = events.lock().unwrap();
+        assert!(events.iter().any(|e| matches!(e, AudioEvent::TrackEnded)));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, AudioEvent::StateChanged(PlaybackState::Stopped))));
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+    }
 
-        // Without device, should return error
-        let result = engine.get_best_format();
-        assert!(result.is_err());
+    #[test]
+    fn test_seek_near_end_finishes_track() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("short.wav");
+        write_constant_wav(&path, 1000, 1000);
 
-        // With device (if available)
-        if engine.init_default_device().is_ok() {
-            let result = engine.get_best_format();
-            if let Ok(best_format) = result {
-                // Should return a valid high-quality format
-                assert!(best_format.sample_rate >= 44100);
-                assert!(best_format.channels >= 1);
-            }
-        }
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        engine.set_callback(Box::new(move |event| sink.lock().unwrap().push(event)));
+
+        // Seeks past the end land on it
+        engine.seek(5000).unwrap();
+        assert_eq!(engine.position(), 1000);
+
+        engine.seek(990).unwrap();
+        engine.state.write().state = PlaybackState::Playing;
+        let mut output = vec![1.0f64; 64];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        assert!(output[..20].iter().all(|&s| s != 0.0));
+        assert!(output[20..].iter().all(|&s| s == 0.0));
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.position(), 0);
+        let events = events.lock().unwrap();
+        let ended = events
+            .iter()
+            .position(|e| matches!(e, AudioEvent::TrackEnded))
+            .expect("TrackEnded emitted");
+        assert!(matches!(
+            events[ended + 1],
+            AudioEvent::StateChanged(PlaybackState::Stopped)
+        ));
     }
 
     #[test]
-    fn test_format_support_check() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_loop_follows_repeat_mode() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("first.wav");
+        let second = temp_dir.path().join("second.wav");
+        write_constant_wav(&first, 1000, 1000);
+        write_constant_wav(&second, 2000, 1000);
 
-        let test_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_auto_prefetch(false);
+        engine.set_fade_duration(0);
+        engine.update_queue(|queue| queue.set_items(vec![first.clone(), second.clone()]));
+        engine.set_repeat_mode(RepeatMode::All);
+        engine.set_loop(true);
+        assert_eq!(engine.repeat_mode(), RepeatMode::One);
+        engine.load_buffer(&first).unwrap();
+
+        // A looping track wraps even with a track queued after it
+        engine.state.write().state = PlaybackState::Playing;
+        engine.seek(900).unwrap();
+        let mut output = vec![0.0f64; 512];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 156);
+        assert!(!engine.prefetch_next().unwrap());
+
+        // Without looping the queue moves on
+        engine.set_loop(false);
+        assert_eq!(engine.repeat_mode(), RepeatMode::Off);
+        assert!(!engine.is_looping());
+        assert!(engine.prefetch_next().unwrap());
+        engine.seek(900).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.current_path(), Some(second.clone()));
+
+        // A stream already playing picks up the setting
+        engine.use_null_output(crate::audio::output::NullBackend::default());
+        engine.load_file_with_ring_buffer(&second).unwrap();
+        let looping = |engine: &AudioEngine| engine.stream_reader.as_ref().unwrap().is_looping();
+        assert!(!looping(&engine));
+        engine.set_repeat_mode(RepeatMode::One);
+        assert!(looping(&engine));
+
+        // A later stream config keeps the loop state
+        engine
+            .set_stream_config(crate::audio::decoder::StreamConfig::default())
+            .unwrap();
+        assert!(engine.is_looping());
+        engine.load_file_with_ring_buffer(&first).unwrap();
+        assert!(looping(&engine));
+    }
 
-        // Without device, should return error
-        let result = engine.is_format_supported(&test_format);
-        assert!(result.is_err());
+    #[test]
+    fn test_balance_left_silences_right() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("balance.wav");
+        write_constant_wav(&path, 16384, 4410);
 
-        // With device (if available)
-        if engine.init_default_device().is_ok() {
-            let result = engine.is_format_supported(&test_format);
-            if let Ok(_is_supported) = result {
-                // Should return a boolean result
-            }
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        assert!(engine.set_balance(1.5).is_err());
+        assert!(engine.set_balance(f32::NAN).is_err());
+        engine.set_balance(-1.0).unwrap();
+        assert_eq!(engine.balance(), -1.0);
+        engine.state.write().state = PlaybackState::Playing;
+
+        let mut output = vec![0.0f64; 512];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        for frame in output.chunks(2) {
+            assert!((frame[0] - 0.5).abs() < 1e-3);
+            assert_eq!(frame[1], 0.0);
         }
     }
 
     #[test]
-    fn test_compatibility_scoring() {
-        let _engine = AudioEngine::new().unwrap();
+    fn test_source_clip_stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let clean = temp_dir.path().join("clean.wav");
+        let clipped = temp_dir.path().join("clipped.wav");
+        write_constant_wav(&clean, 1000, 100);
+        // Every sample of both channels sits at full scale
+        write_constant_wav(&clipped, i16::MAX, 100);
 
-        // Create a mock config range for testing
-        // Note: This test is limited because we can't easily create SupportedStreamConfigRange
-        // In a real scenario, this would be tested with actual device configs
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.source_clip_stats().is_none());
 
-        let target_format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        engine.load_buffer(&clean).unwrap();
+        assert!(!engine.source_clip_stats().unwrap().is_clipped());
 
-        // Test that the scoring function exists and can be called
-        // The actual scoring logic is tested indirectly through format negotiation
-        assert_eq!(target_format.sample_rate, 44100);
-        assert_eq!(target_format.channels, 2);
+        engine.load_buffer(&clipped).unwrap();
+        let stats = engine.source_clip_stats().unwrap();
+        assert_eq!(stats.clipped_samples, 200);
+        assert_eq!(stats.clip_events, 2);
     }
 
     #[test]
-    fn test_error_handling() {
+    fn test_apply_eq_preset() {
+        use crate::audio::filter::{BiquadParams, FilterType};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("eq.wav");
+        write_constant_wav(&path, 16384, 44100);
+
+        let shelf = BiquadParams::new(FilterType::LowShelf, 1000.0, 0.707, -20.0);
+        let peak = BiquadParams::new(FilterType::Peaking, 30000.0, 1.0, 3.0);
+        let preset = EqPreset::new("Bass Cut", vec![shelf, peak]);
+
         let mut engine = AudioEngine::new().unwrap();
+        let invalid = EqPreset::new(
+            "Broken",
+            vec![BiquadParams::new(FilterType::Peaking, -1.0, 1.0, 0.0)],
+        );
+        assert!(engine.apply_eq_preset(&invalid).is_err());
+        assert!(engine.eq_preset().is_none());
+
+        // Applied before a track is loaded; configured once the format is known
+        engine.apply_eq_preset(&preset).unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        engine.state.write().state = PlaybackState::Playing;
+
+        let mut output = vec![0.0f64; 8192];
+        for _ in 0..10 {
+            AudioEngine::audio_callback(&mut output, &engine.state);
+        }
 
-        // Test validation without device
-        let result = engine.validate_state();
-        assert!(result.is_ok()); // Should be OK initially
+        let applied = engine.eq_preset().unwrap();
+        assert_eq!(applied.bands, preset.bands);
+        {
+            let state = engine.state.read();
+            let equalizer = state.equalizer.as_ref().unwrap();
+            assert_eq!(equalizer.sample_rate(), 44100);
+            // The 30 kHz band was authored beyond this rate's Nyquist
+            assert_eq!(
+                equalizer.coefficients(),
+                vec![shelf.coefficients(44100), peak.coefficients(44100)]
+            );
+        }
 
-        // Test load_file with non-existent file
-        let result = engine.load_file("non_existent_file.mp3");
-        assert!(result.is_err());
+        // DC input settles at the shelf's -20 dB gain (the peak band is
+        // transparent at DC)
+        let tail = &output[output.len() - 2..];
+        assert!(tail.iter().all(|s| (s - 0.05).abs() < 1e-3), "{:?}", tail);
+        assert!(!engine.is_bit_perfect());
 
-        // Test play without loaded file/device
-        let _result = engine.play();
-        // This might succeed or fail depending on system audio availability
-        // The important thing is that it doesn't panic
+        engine.clear_eq();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!((output[0] - 0.5).abs() < 1e-3);
     }
 
     #[test]
-    fn test_error_recovery() {
-        let mut engine = AudioEngine::new().unwrap();
-
-        // Test recovery from error state
-        // Manually set error state
-        engine.update_state(|state| {
-            state.state = PlaybackState::Error;
-            Some(AudioEvent::StateChanged(PlaybackState::Error))
-        });
+    fn test_rumble_filter_cuts_subsonic_tones() {
+        let dir = tempfile::tempdir().unwrap();
+        // Output RMS over the second half of two seconds of a tone
+        let filtered_rms = |frequency: f64, cutoff: Option<f64>| -> f64 {
+            let path = dir.path().join(format!("{}hz.wav", frequency));
+            let spec = hound::WavSpec {
+                channels: 2,
+                sample_rate: 44100,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for frame in 0..88200 {
+                let phase = 2.0 * std::f64::consts::PI * frequency * frame as f64 / 44100.0;
+                let value = (phase.sin() * 16000.0) as i16;
+                writer.write_sample(value).unwrap();
+                writer.write_sample(value).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            let mut engine = AudioEngine::new().unwrap();
+            engine.set_rumble_filter(cutoff).unwrap();
+            engine.load_buffer(&path).unwrap();
+            engine.set_fade_duration(0);
+            engine.state.write().state = PlaybackState::Playing;
+
+            let mut output = vec![0.0f64; 88200 * 2];
+            AudioEngine::audio_callback(&mut output, &engine.state);
+            let tail = &output[88200..];
+            (tail.iter().map(|&s| s.powi(2)).sum::<f64>() / tail.len() as f64).sqrt()
+        };
 
-        // Validate should fail
-        let result = engine.validate_state();
-        assert!(result.is_err());
+        let cutoff = Some(DEFAULT_RUMBLE_CUTOFF_HZ);
+        let rumble = filtered_rms(10.0, cutoff) / filtered_rms(10.0, None);
+        assert!(rumble < 0.1, "10 Hz kept {}", rumble);
+        let music = filtered_rms(1000.0, cutoff) / filtered_rms(1000.0, None);
+        assert!((music - 1.0).abs() < 0.01, "1 kHz kept {}", music);
 
-        // Recovery should work (if audio system is available)
-        let recovery_result = engine.recover_from_error();
-        if recovery_result.is_ok() {
-            // After recovery, validation should pass
-            assert!(engine.validate_state().is_ok());
-            assert_eq!(engine.state(), PlaybackState::Stopped);
-        }
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.set_rumble_filter(Some(0.0)).is_err());
+        engine.set_rumble_filter(cutoff).unwrap();
+        assert_eq!(engine.rumble_filter(), cutoff);
+        engine.set_rumble_filter(None).unwrap();
+        assert_eq!(engine.rumble_filter(), None);
     }
 
     #[test]
-    fn test_safe_operations() {
+    fn test_loudness_compensation_follows_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("constant.wav");
+        write_constant_wav(&path, 8000, 44100);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+        engine.set_loudness_compensation(true);
+        assert!(engine.loudness_compensation());
+        engine.state.write().state = PlaybackState::Playing;
+
+        let shelf_gains = |engine: &mut AudioEngine, volume: f32| -> Vec<f64> {
+            engine.set_volume(volume).unwrap();
+            AudioEngine::audio_callback(&mut [0.0; 512], &engine.state);
+            let state = engine.state.read();
+            let (_, shelves) = state.loudness_compensation.as_ref().unwrap();
+            shelves.bands().iter().map(|band| band.gain_db).collect()
+        };
 
-        // Test safe stream operations without stream
-        let result = engine.start_stream();
-        // Should return error since no stream is initialized
-        assert!(result.is_err());
+        let quiet = shelf_gains(&mut engine, 0.1);
+        assert!(quiet[0] > 0.0 && quiet[1] > 0.0, "{:?}", quiet);
+        let quieter = shelf_gains(&mut engine, 0.01);
+        assert!(quieter[0] > quiet[0]);
+        let reference = shelf_gains(&mut engine, 1.0);
+        assert!(reference.iter().all(|gain| gain.abs() < 1e-9));
 
-        let result = engine.pause_stream();
-        // Should return error since no stream is initialized
-        assert!(result.is_err());
+        engine.set_loudness_compensation(false);
+        assert!(!engine.loudness_compensation());
+        assert!(engine.state.read().loudness_compensation.is_none());
     }
 
     #[test]
-    fn test_audio_engine_initialization_complete() {
-        // Test complete initialization process
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_dithering_reduces_harmonic_distortion() {
+        use rustfft::{num_complex::Complex, FftPlanner};
 
-        // Verify initial state
-        assert_eq!(engine.state(), PlaybackState::Stopped);
-        assert_eq!(engine.volume(), 1.0);
-        assert_eq!(engine.position(), 0);
-        assert!(engine.duration().is_none());
-        assert!(engine.format().is_none());
-        assert!(engine.device().is_none());
+        let engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.dithering(), DitheringAlgorithm::Triangular);
+
+        // A tone 3 LSB high on a 16-bit output, bin-aligned so the harmonics
+        // land exactly on FFT bins
+        let size = 8192;
+        let bin = 64;
+        let lsb = 1.0 / 32768.0;
+        let tone: Vec<f64> = (0..size)
+            .map(|i| {
+                3.0 * lsb * (2.0 * std::f64::consts::PI * bin as f64 * i as f64 / size as f64).sin()
+            })
+            .collect();
+
+        // Harmonic power (2nd to 9th) relative to the fundamental
+        let harmonic_ratio = |algorithm: DitheringAlgorithm| {
+            let mut ditherer = Ditherer::new(algorithm);
+            let mut output = vec![0i16; size];
+            // Converted in callback-sized blocks with one persistent ditherer
+            for (input, out) in tone.chunks(512).zip(output.chunks_mut(512)) {
+                i16::write_samples(input, out, Some(&mut ditherer));
+            }
 
-        // Test host access
-        let _host = engine.host();
+            let mut spectrum: Vec<Complex<f64>> = output
+                .iter()
+                .map(|&s| Complex::new(s as f64, 0.0))
+                .collect();
+            FftPlanner::new()
+                .plan_fft_forward(size)
+                .process(&mut spectrum);
+            let power = |k: usize| spectrum[k].norm_sqr();
+            (2..10).map(|h| power(bin * h)).sum::<f64>() / power(bin)
+        };
 
-        // Test device initialization (if available)
-        let device_init_result = engine.init_default_device();
-        if device_init_result.is_ok() {
-            // Device should be set
-            assert!(engine.device().is_some());
+        let plain = harmonic_ratio(DitheringAlgorithm::None);
+        let dithered = harmonic_ratio(DitheringAlgorithm::Triangular);
+        assert!(plain > 1e-3, "undithered harmonics {}", plain);
+        assert!(dithered * 10.0 < plain, "{} vs {}", dithered, plain);
 
-            // Test stream initialization
-            let format = AudioFormat::default();
-            let stream_init_result = engine.init_output_stream(&format);
-            if stream_init_result.is_ok() {
-                // Stream should be initialized
-                assert!(engine.stream.is_some());
-                assert!(engine.stream_config.is_some());
+        let mut engine = engine;
+        engine.set_dithering(DitheringAlgorithm::None);
+        assert_eq!(engine.dithering(), DitheringAlgorithm::None);
+    }
+
+    #[test]
+    fn test_meter_callback_reports_correlation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("meter.wav");
+        write_constant_wav(&path, 16384, 4410);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        engine.set_fade_duration(0);
+
+        let levels = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = levels.clone();
+        engine.set_meter_callback(Box::new(move |l| sink.lock().push(l)));
+        engine.state.write().state = PlaybackState::Playing;
 
-                // Test that we can get supported formats
-                let formats_result = engine.supported_formats();
-                if let Ok(formats) = formats_result {
-                    assert!(!formats.is_empty());
-                }
-            }
+        let mut output = vec![0.0f64; 512];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        {
+            let levels = levels.lock();
+            assert_eq!(levels.len(), 1);
+            assert!((levels[0].peak - 0.5).abs() < 1e-3);
+            assert!((levels[0].rms - 0.5).abs() < 1e-3);
+            assert!((levels[0].correlation - 1.0).abs() < 1e-9);
+            assert_eq!(levels[0].weighted_rms, None);
         }
+
+        // Balance hard left leaves nothing to correlate against
+        engine.set_balance(-1.0).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(levels.lock()[1].correlation, 0.0);
+
+        engine.clear_meter_callback();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(levels.lock().len(), 2);
     }
 
     #[test]
-    fn test_initialization_with_custom_device() {
-        let engine = AudioEngine::new().unwrap();
+    fn test_a_weighted_meter_attenuates_low_tones() {
+        let dir = tempfile::tempdir().unwrap();
+        // Flat and A-weighted level, in dB, of the last of ten blocks of a tone
+        let levels_db = |frequency: f64| -> (f64, f64) {
+            let path = dir.path().join(format!("{}hz.wav", frequency));
+            let spec = hound::WavSpec {
+                channels: 2,
+                sample_rate: 44100,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for frame in 0..44100 {
+                let phase = 2.0 * std::f64::consts::PI * frequency * frame as f64 / 44100.0;
+                let value = (phase.sin() * 16000.0) as i16;
+                writer.write_sample(value).unwrap();
+                writer.write_sample(value).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            let mut engine = AudioEngine::new().unwrap();
+            engine.load_buffer(&path).unwrap();
+            engine.set_fade_duration(0);
+            engine.set_meter_weighting(FrequencyWeighting::AWeighting);
+            let levels = Arc::new(parking_lot::Mutex::new(Vec::new()));
+            let sink = levels.clone();
+            engine.set_meter_callback(Box::new(move |l| sink.lock().push(l)));
+            engine.state.write().state = PlaybackState::Playing;
+
+            let mut output = vec![0.0f64; 4410 * 2];
+            for _ in 0..10 {
+                AudioEngine::audio_callback(&mut output, &engine.state);
+            }
+            let last = *levels.lock().last().unwrap();
+            (20.0 * last.rms.log10(), last.weighted_rms_dbfs().unwrap())
+        };
 
-        // Test device enumeration
-        let devices_result = engine.enumerate_output_devices();
-        if let Ok(devices) = devices_result {
-            if !devices.is_empty() {
-                // Test creating engine with specific device
-                // Note: We can't easily test this without access to actual Device objects
-                // This test verifies the enumeration works
-                for device_info in &devices {
-                    assert!(!device_info.name.is_empty());
-                    assert!(!device_info.supported_formats.is_empty());
-                }
+        let (flat_100, weighted_100) = levels_db(100.0);
+        let (flat_1k, weighted_1k) = levels_db(1000.0);
+        assert!((flat_100 - flat_1k).abs() < 0.1);
+        assert!((weighted_1k - flat_1k).abs() < 0.1);
+        // IEC 61672: A-weighting is -19.1 dB at 100 Hz
+        let relative = weighted_100 - weighted_1k;
+        assert!((relative + 19.1).abs() < 0.5, "100 Hz at {} dB", relative);
+    }
+
+    #[test]
+    fn test_meter_holds_clip_and_peak() {
+        // One clipping 512-frame block at 10 kHz, then quiet audio
+        let format = AudioFormat::new(10000, 2, SampleFormat::F64);
+        let mut data = vec![1.5; 1024];
+        data.extend(vec![0.25; 1024 * 20]);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        assert_eq!(engine.clip_hold(), DEFAULT_CLIP_HOLD_MS);
+        engine.set_clip_hold(200);
+        engine.set_peak_hold_decay(1000);
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(data.len() as u64 / 2);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        let levels = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = levels.clone();
+        engine.set_meter_callback(Box::new(move |l| sink.lock().push(l)));
+
+        let mut output = vec![0.0f64; 1024];
+        for _ in 0..6 {
+            AudioEngine::audio_callback(&mut output, &engine.state);
+        }
+        {
+            let levels = levels.lock();
+            assert!(levels[0].clip_held);
+            assert_eq!(levels[0].peak_hold, 1.5);
+            // 200 ms is 2000 frames: held through the next three blocks
+            for l in &levels[1..4] {
+                assert!(l.clip_held);
+                assert_eq!(l.peak, 0.25);
             }
+            assert!(!levels[4].clip_held);
+            // 20 dB per second: 51.2 ms later the hold is 1.32 dB down
+            let expected = 1.5 * 10f64.powf(-0.0512);
+            assert!((levels[1].peak_hold - expected).abs() < 1e-9);
+            assert!(levels[5].peak_hold < levels[4].peak_hold);
+            assert!(levels[5].peak_hold > 0.25);
         }
+
+        // A fresh clip is held again until reset
+        engine.update_state(|state| {
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), vec![1.0; 4096]));
+            state.position = 0;
+            None
+        });
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(levels.lock().last().unwrap().clip_held);
+        engine.reset_clip();
+        engine.update_state(|state| {
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), vec![0.5; 4096]));
+            None
+        });
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(!levels.lock().last().unwrap().clip_held);
     }
 
     #[test]
-    fn test_initialization_error_conditions() {
+    fn test_non_finite_samples_are_sanitized() {
         let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.sample_sanitization());
+        assert!(engine.denormal_protection());
+
+        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F64);
+        let data = vec![
+            0.5,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            1e-310,
+            -0.25,
+            f64::NAN,
+            0.1,
+        ];
+        let load = |engine: &AudioEngine| {
+            engine.update_state(|state| {
+                state.format = Some(format.clone());
+                state.duration = Some(4);
+                state.buffer = Some(AudioBuffer::with_data(format.clone(), data.clone()));
+                state.position = 0;
+                state.fade_gain = 1.0;
+                state.state = PlaybackState::Playing;
+                None
+            });
+        };
 
-        // Test stream initialization without device
-        let format = AudioFormat::default();
-        let result = engine.init_output_stream(&format);
-        assert!(result.is_err());
-
-        // Test with invalid format
-        let invalid_format = AudioFormat::new(0, 0, crate::audio::format::SampleFormat::F32);
-        let result = engine.init_output_stream(&invalid_format);
-        assert!(result.is_err());
+        load(&engine);
+        let mut output = vec![0.0f64; 8];
+        let buffer = engine.state.write().buffer.take().unwrap();
+        AudioEngine::fill_from_buffer(&mut output, &buffer, &mut engine.state.write());
+        assert!(output.iter().all(|s| s.is_finite()));
+        assert_eq!(output, vec![0.5, 0.0, 0.0, 0.0, 0.0, -0.25, 0.0, 0.1]);
 
-        // Test format validation
-        let validation_result = crate::audio::format::validate_format(&invalid_format);
-        assert!(validation_result.is_err());
+        // Through the full callback, with the volume applied
+        load(&engine);
+        engine.set_volume(0.5).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|s| s.is_finite()));
+        assert_eq!(output[0], 0.25);
+
+        engine.set_sample_sanitization(false);
+        load(&engine);
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output[1].is_nan());
     }
 
     #[test]
-    fn test_initialization_state_consistency() {
-        let engine = AudioEngine::new().unwrap();
+    fn test_stereo_width_mono_output() {
+        let mut engine = AudioEngine::new().unwrap();
+        let format = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F64);
+        let data: Vec<f64> = (0..1024).flat_map(|_| [0.6, -0.2]).collect();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(1024);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
 
-        // Test that all initial state is consistent
-        assert_eq!(engine.state(), PlaybackState::Stopped);
-        assert_eq!(engine.position(), 0);
-        assert_eq!(engine.volume(), 1.0);
-        assert!(engine.duration().is_none());
-        assert!(engine.format().is_none());
+        assert!(engine.set_stereo_width(2.5).is_err());
+        engine.set_stereo_width(0.0).unwrap();
+        assert_eq!(engine.stereo_width(), 0.0);
 
-        // Test that validation passes for initial state
-        assert!(engine.validate_state().is_ok());
+        let mut output = vec![0.0f64; 256];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        for frame in output.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+            assert!((frame[0] - 0.2).abs() < 1e-6);
+        }
+
+        engine.set_stereo_width(1.0).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(&output[..2], &[0.6, -0.2]);
     }
 
     #[test]
-    fn test_ring_buffer_integration() {
+    fn test_resamples_to_unsupported_device_rate() {
+        use crate::audio::output::{NullBackend, OutputBackend, OutputConfigRange};
+
+        let source_rate = 44100;
+        let frequency = 1000.0;
+        let format = AudioFormat::new(source_rate, 2, SampleFormat::F64);
+        let data: Vec<f64> = (0..source_rate)
+            .flat_map(|i| {
+                let s = (2.0 * std::f64::consts::PI * frequency * i as f64 / source_rate as f64)
+                    .sin()
+                    * 0.5;
+                [s, s]
+            })
+            .collect();
+
+        // A device that only runs at 48 kHz
+        let backend = NullBackend::new(vec![OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::F32,
+        }]);
+        let configs = backend.supported_configs().unwrap();
+        assert!(select_output_format(&configs, &format).is_none());
+        let output_format = select_resampled_format(&configs, &format).unwrap();
+        assert_eq!(output_format.sample_rate, 48000);
+
         let mut engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(source_rate as u64);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data));
+            state.open_output(&output_format, format.channels);
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
+
+        // 0.9 s of device output in 10 ms blocks
+        let mut rendered = Vec::new();
+        let mut block = vec![0.0f64; 480 * 2];
+        for _ in 0..90 {
+            AudioEngine::audio_callback(&mut block, &engine.state);
+            rendered.extend_from_slice(&block);
+        }
 
-        // Test ring buffer consumer setting
-        use crate::audio::format::{AudioFormat, SampleFormat};
-        use crate::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
+        // Position and duration stay in source frames
+        let position = engine.position() as i64;
+        assert!((position - 39690).abs() <= 64, "position {}", position);
+        assert_eq!(engine.duration(), Some(source_rate as u64));
+        assert_eq!(engine.format().unwrap().sample_rate, source_rate);
+
+        // The tone keeps its pitch at the device rate
+        for (i, frame) in rendered.chunks(2).enumerate().skip(64) {
+            let expected =
+                (2.0 * std::f64::consts::PI * frequency * i as f64 / 48000.0).sin() * 0.5;
+            assert!((frame[0] - expected).abs() < 1e-3, "frame {}", i);
+            assert_eq!(frame[0], frame[1]);
+        }
+    }
 
+    #[test]
+    fn test_resampler_is_prepared_and_reset_off_the_callback() {
+        // 0.2 s at 0.5, then silence
         let format = AudioFormat::new(44100, 2, SampleFormat::F64);
-        let config = RingBufferConfig {
-            buffer_duration_seconds: 1.0,
-            format: format.clone(),
-            allow_overwrite: false,
-            underrun_threshold: 0.1,
-        };
+        let data: Vec<f64> = (0..44100)
+            .flat_map(|i| if i < 8820 { [0.5, 0.5] } else { [0.0, 0.0] })
+            .collect();
+        let output_format = AudioFormat::new(48000, 2, SampleFormat::F32);
 
-        let (producer, consumer) = AudioRingBuffer::new(config).unwrap();
+        let mut engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(44100);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data));
+            state.open_output(&output_format, format.channels);
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
+        let scratch = {
+            let state = engine.state.read();
+            let resampler = state.resampler.as_ref().expect("built with the output");
+            resampler.rendered.as_ptr()
+        };
 
-        // Test setting ring buffer consumer
-        assert!(!engine.is_using_ring_buffer());
-        engine.set_ring_buffer_consumer(consumer).unwrap();
-        assert!(engine.is_using_ring_buffer());
+        // Odd block sizes never leave a zero-filled tail
+        let mut rendered = Vec::new();
+        for frames in [441, 97, 480, 333, 1024] {
+            let mut block = vec![0.0f64; frames * 2];
+            AudioEngine::audio_callback(&mut block, &engine.state);
+            rendered.extend_from_slice(&block);
+        }
+        for (i, &sample) in rendered.iter().enumerate().skip(64 * 2) {
+            assert!((sample - 0.5).abs() < 1e-3, "sample {}", i);
+        }
+        {
+            let state = engine.state.read();
+            let resampler = state.resampler.as_ref().unwrap();
+            assert_eq!(resampler.rendered.as_ptr(), scratch);
+        }
 
-        // Test ring buffer utilization
-        let utilization = engine.ring_buffer_utilization();
-        assert!(utilization.is_some());
-        assert_eq!(utilization.unwrap(), 0.0); // Empty buffer
+        // A seek into the silence doesn't replay the converter's history
+        engine.seek(22050).unwrap();
+        let mut block = vec![1.0f64; 256 * 2];
+        AudioEngine::audio_callback(&mut block, &engine.state);
+        assert!(block.iter().all(|&sample| sample.abs() < 1e-6));
+    }
 
-        // Write some data to test utilization
-        let test_samples = vec![1.0; 1000];
-        producer.write(&test_samples);
+    #[test]
+    fn test_normalization_applied_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("quiet.wav");
+        // Roughly -6 dBFS
+        write_constant_wav(&path, 16384, 1000);
 
-        let utilization_after = engine.ring_buffer_utilization();
-        assert!(utilization_after.is_some());
-        assert!(utilization_after.unwrap() > 0.0); // Should have some data
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(engine
+            .set_normalization(NormalizationMode::Peak {
+                target_dbfs: f64::NAN
+            })
+            .is_err());
+        engine
+            .set_normalization(NormalizationMode::Peak { target_dbfs: 0.0 })
+            .unwrap();
+        engine.load_buffer(&path).unwrap();
 
-        // Test clearing ring buffer
-        engine.clear_ring_buffer_consumer();
-        assert!(!engine.is_using_ring_buffer());
-        assert!(engine.ring_buffer_utilization().is_none());
+        let state = engine.state.read();
+        let buffer = state.buffer.as_ref().unwrap();
+        assert!(buffer.data().iter().all(|&s| (s - 1.0).abs() < 1e-9));
+        // Clip statistics describe the source, not the normalized buffer
+        assert_eq!(state.clip_stats.unwrap().clipped_samples, 0);
     }
 
     #[test]
-    fn test_ring_buffer_file_loading() {
+    fn test_load_progress_rises_to_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.wav");
+        write_constant_wav(&path, 1000, 44100 * 5);
+
+        let reports = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = reports.clone();
         let mut engine = AudioEngine::new().unwrap();
+        engine
+            .load_buffer_with_progress(&path, None, &move |fraction| sink.lock().push(fraction))
+            .unwrap();
+
+        let reports = reports.lock();
+        assert!(reports.len() > 10, "{} reports", reports.len());
+        // Throttled to at most one report per step
+        assert!(reports.len() <= (1.0 / crate::audio::decoder::DECODE_PROGRESS_STEP) as usize + 1);
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(reports.iter().all(|&f| (0.0..=1.0).contains(&f)));
+        assert_eq!(*reports.last().unwrap(), 1.0);
+    }
 
-        // Test loading with non-existent file
-        let result = engine.load_file_with_ring_buffer("nonexistent.mp3");
-        assert!(result.is_err());
+    #[test]
+    fn test_inter_track_gap_inserts_silence() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.wav");
+        let second = dir.path().join("second.wav");
+        write_constant_wav(&first, 8192, 1000);
+        write_constant_wav(&second, -8192, 1000);
 
-        // Test loading with invalid file
-        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
-        std::io::Write::write_all(&mut temp_file, b"not audio data").unwrap();
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&first).unwrap();
+        engine.update_queue(|queue| queue.set_items(vec![first.clone(), second.clone()]));
+        assert!(engine.prefetch_next().unwrap());
+        engine.set_fade_duration(0);
+        // 10 ms at 44.1 kHz = 441 frames
+        engine.set_inter_track_gap(10);
+        assert_eq!(engine.inter_track_gap(), 10);
+        engine.update_state(|state| {
+            state.state = PlaybackState::Playing;
+            state.position = 900;
+            None
+        });
 
-        let result = engine.load_file_with_ring_buffer(temp_file.path());
-        assert!(result.is_err());
+        let mut rendered = Vec::new();
+        let mut block = vec![0.0f64; 128 * 2];
+        for _ in 0..8 {
+            AudioEngine::audio_callback(&mut block, &engine.state);
+            rendered.extend_from_slice(&block);
+        }
+
+        let frames: Vec<f64> = rendered.chunks(2).map(|frame| frame[0]).collect();
+        let first_end = frames.iter().position(|&s| s < 0.2).unwrap();
+        assert_eq!(first_end, 100);
+        let silent = frames[first_end..]
+            .iter()
+            .take_while(|&&s| s == 0.0)
+            .count();
+        assert_eq!(silent, 441);
+        assert!(frames[first_end + silent..].iter().all(|&s| s < -0.2));
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.current_index(), Some(1));
+        assert_eq!(
+            engine.position(),
+            (frames.len() - first_end - silent) as u64
+        );
     }
 
     #[test]
-    fn test_initialization_thread_safety() {
-        use std::thread;
+    fn test_mono_source_upmixed_on_stereo_only_device() {
+        use crate::audio::output::{NullBackend, OutputBackend};
 
-        // Test that engine creation is thread-safe
-        let mut handles = vec![];
+        let format = AudioFormat::new(44100, 1, SampleFormat::F64);
+        let data: Vec<f64> = (0..4410).map(|i| (i % 100) as f64 / 200.0).collect();
 
-        for _ in 0..5 {
-            let handle = thread::spawn(|| {
-                let engine = AudioEngine::new();
-                assert!(engine.is_ok());
-                let engine = engine.unwrap();
-                assert_eq!(engine.state(), PlaybackState::Stopped);
-            });
-            handles.push(handle);
-        }
+        let configs = NullBackend::default().supported_configs().unwrap();
+        assert!(
+            select_channel_matched_format(&configs, &format, ChannelMatchPolicy::Exact).is_none()
+        );
+        let output_format =
+            select_channel_matched_format(&configs, &format, ChannelMatchPolicy::BestEffort)
+                .unwrap();
+        assert_eq!(output_format.channels, 2);
+        assert_eq!(output_format.sample_rate, 44100);
 
-        for handle in handles {
-            handle.join().unwrap();
+        let mut engine = AudioEngine::new().unwrap();
+        assert_eq!(engine.channel_match_policy(), ChannelMatchPolicy::Exact);
+        engine
+            .set_channel_match_policy(ChannelMatchPolicy::BestEffort)
+            .unwrap();
+        assert_eq!(
+            engine.channel_match_policy(),
+            ChannelMatchPolicy::BestEffort
+        );
+
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(data.len() as u64);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data.clone()));
+            state.output_sample_rate = Some(output_format.sample_rate);
+            state.output_channels = Some(output_format.channels);
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
+
+        let mut output = vec![0.0f64; 256 * 2];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+
+        // Every mono frame lands on both device channels
+        for (frame, &expected) in output.chunks(2).zip(&data) {
+            assert_eq!(frame[0], expected);
+            assert_eq!(frame[1], expected);
         }
+        assert_eq!(engine.position(), 256);
     }
 
     #[test]
-    fn test_file_loading_validation() {
+    fn test_saturation_stage() {
+        use crate::audio::processor::SaturationCurve;
+
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let data = vec![0.9; 2048];
         let mut engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(1024);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data.clone()));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
+
+        let config = SaturationConfig::new(SaturationCurve::Tanh, 12.0);
+        assert!(engine
+            .set_saturation(Some(SaturationConfig::new(SaturationCurve::Tanh, -3.0)))
+            .is_err());
+        assert_eq!(engine.saturation(), None);
+        engine.set_saturation(Some(config)).unwrap();
+        assert_eq!(engine.saturation(), Some(config));
+
+        let mut output = vec![0.0f64; 256];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        let expected = (0.9 * 10_f64.powf(12.0 / 20.0)).tanh();
+        assert!(output.iter().all(|&s| (s - expected).abs() < 1e-6));
+
+        engine.set_saturation(None).unwrap();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|&s| s == 0.9));
+    }
 
-        // Test loading non-existent file
-        let result = engine.load_file("nonexistent.mp3");
-        assert!(result.is_err());
+    #[test]
+    fn test_null_output_plays_without_device() {
+        use crate::audio::output::{NullBackend, OutputConfigRange};
 
-        // Test loading unsupported format
-        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
-        std::io::Write::write_all(&mut temp_file, b"not audio data").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 8192, 4410);
 
-        let result = engine.load_file(temp_file.path());
-        assert!(result.is_err());
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::new(vec![OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::I16,
+        }]));
+        engine.set_fade_duration(0);
+        engine.load_file(&path).unwrap();
+        assert!(engine.is_stream_initialized());
+        assert_eq!(
+            engine.output_format(),
+            Some(AudioFormat::new(48000, 2, SampleFormat::I16))
+        );
+
+        engine.play().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        engine.advance_for_testing(480);
+        assert!(engine.position() > 0);
+        engine.pause().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Paused);
     }
 
     #[test]
-    fn test_decoder_integration() {
-        // Test that decoder functions are accessible
-        assert!(crate::audio::decoder::is_format_supported("test.mp3"));
-        assert!(!crate::audio::decoder::is_format_supported("test.txt"));
+    fn test_stream_config_info_reports_negotiated_config() {
+        use crate::audio::output::{NullBackend, OutputBackend, OutputConfigRange};
 
-        let extensions = crate::audio::decoder::supported_extensions();
-        assert!(extensions.contains(&"mp3"));
-        assert!(extensions.contains(&"wav"));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.wav");
+        write_constant_wav(&path, 1000, 4410);
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.load_buffer(&path).unwrap();
+        assert!(engine.stream_config_info().is_none());
+        let source = engine.format().unwrap();
+
+        // Open the stream as init_output_stream would on each device
+        let open = |engine: &mut AudioEngine, backend: NullBackend| {
+            let configs = backend.supported_configs().unwrap();
+            let output =
+                select_channel_matched_format(&configs, &source, ChannelMatchPolicy::Exact)
+                    .unwrap();
+            engine.stream_config = Some(StreamConfig {
+                channels: output.channels,
+                sample_rate: output.sample_rate,
+                buffer_size: cpal::BufferSize::Fixed(256),
+            });
+            engine.output_format = Some(output);
+        };
+
+        open(&mut engine, NullBackend::default());
+        assert_eq!(
+            engine.stream_config_info(),
+            Some(StreamConfigInfo {
+                sample_rate: 44100,
+                channels: 2,
+                sample_format: SampleFormat::F32,
+                buffer_size_frames: Some(256),
+                resampling: false,
+                remixing: false,
+            })
+        );
+
+        let fixed_rate = NullBackend::new(vec![OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::I16,
+        }]);
+        open(&mut engine, fixed_rate);
+        let info = engine.stream_config_info().unwrap();
+        assert_eq!(info.sample_rate, 48000);
+        assert_eq!(info.sample_format, SampleFormat::I16);
+        assert!(info.resampling);
+        assert!(!info.remixing);
     }
 
     #[test]
-    fn test_playback_play_function() {
+    fn test_falls_back_to_default_config_when_none_enumerated() {
+        use crate::audio::output::{NullBackend, OutputConfigRange};
+
+        let warnings = Arc::new(Mutex::new(Vec::new()));
         let mut engine = AudioEngine::new().unwrap();
+        let sink = warnings.clone();
+        engine.set_callback(Box::new(move |event| {
+            if let AudioEvent::Warning(message) = event {
+                sink.lock().unwrap().push(message);
+            }
+        }));
 
-        // Initial state should be stopped
-        assert_eq!(engine.state(), PlaybackState::Stopped);
+        // Enumeration yields nothing but the device has a 48 kHz default
+        let backend = NullBackend::new(Vec::new()).with_default_config(OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::I16,
+        });
+        let source = AudioFormat::new(44100, 2, SampleFormat::F32);
+        let (_, output) = engine.select_stream_format(&backend, &source).unwrap();
+        assert_eq!(output, AudioFormat::new(48000, 2, SampleFormat::I16));
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+
+        // Enumerated configurations don't warn
+        warnings.lock().unwrap().clear();
+        engine
+            .select_stream_format(&NullBackend::default(), &source)
+            .unwrap();
+        assert!(warnings.lock().unwrap().is_empty());
+
+        // Without a default there is nothing to fall back to
+        assert!(engine
+            .select_stream_format(&NullBackend::new(Vec::new()), &source)
+            .is_err());
+    }
 
-        // Try to play without loading a file
-        let result = engine.play();
-        // Should either succeed (if audio system available) or fail gracefully
-        if result.is_ok() {
-            // If successful, state should be playing or error
-            let state = engine.state();
-            assert!(
-                state == PlaybackState::Playing || state == PlaybackState::Error,
-                "Expected Playing or Error state, got {:?}",
-                state
-            );
+    #[test]
+    fn test_interface_is_object_safe() {
+        /// Records the calls made through the trait object
+        struct MockEngine {
+            loaded: Option<PathBuf>,
+            state: PlaybackState,
+            position: u64,
+            volume: f32,
+            muted: bool,
+        }
+
+        impl AudioEngineInterface for MockEngine {
+            fn load_file_path(&mut self, path: &Path) -> Result<()> {
+                self.loaded = Some(path.to_path_buf());
+                Ok(())
+            }
+            fn play(&mut self) -> Result<()> {
+                self.state = PlaybackState::Playing;
+                Ok(())
+            }
+            fn pause(&mut self) -> Result<()> {
+                self.state = PlaybackState::Paused;
+                Ok(())
+            }
+            fn stop(&mut self) -> Result<()> {
+                self.state = PlaybackState::Stopped;
+                self.position = 0;
+                Ok(())
+            }
+            fn seek(&mut self, position: u64) -> Result<()> {
+                self.position = position;
+                Ok(())
+            }
+            fn set_volume(&mut self, volume: f32) -> Result<()> {
+                self.volume = volume;
+                Ok(())
+            }
+            fn set_volume_ramped(&mut self, volume: f32, _ramp_duration_ms: u32) -> Result<()> {
+                self.set_volume(volume)
+            }
+            fn volume(&self) -> f32 {
+                self.volume
+            }
+            fn mute(&mut self) -> Result<()> {
+                self.muted = true;
+                Ok(())
+            }
+            fn unmute(&mut self) -> Result<()> {
+                self.muted = false;
+                Ok(())
+            }
+            fn is_muted(&self) -> bool {
+                self.muted
+            }
+            fn state(&self) -> PlaybackState {
+                self.state
+            }
+            fn position(&self) -> u64 {
+                self.position
+            }
+            fn duration(&self) -> Option<u64> {
+                self.loaded.as_ref().map(|_| 44100)
+            }
+            fn format(&self) -> Option<AudioFormat> {
+                None
+            }
+            fn set_callback(&mut self, _callback: AudioCallback) {}
+            fn clear_callback(&mut self) {}
+        }
+
+        let mut engine: Box<dyn AudioEngineInterface> = Box::new(MockEngine {
+            loaded: None,
+            state: PlaybackState::Stopped,
+            position: 0,
+            volume: 1.0,
+            muted: false,
+        });
+        engine.load_file_path(Path::new("track.flac")).unwrap();
+        engine.play().unwrap();
+        engine.seek(1000).unwrap();
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert_eq!(engine.position(), 1000);
+        assert_eq!(engine.duration(), Some(44100));
+        engine.pause().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Paused);
+        engine.stop().unwrap();
+        assert_eq!(engine.position(), 0);
+
+        // The real engine fits the same slot
+        let mut engines: Vec<Box<dyn AudioEngineInterface>> =
+            vec![engine, Box::new(AudioEngine::new().unwrap())];
+        for engine in engines.iter_mut() {
+            engine.set_volume(0.25).unwrap();
+            engine.mute().unwrap();
+            assert!(engine.is_muted());
+            engine.unmute().unwrap();
+            assert_eq!(engine.volume(), 0.25);
         }
+        assert!(engines[1]
+            .load_file_path(Path::new("missing.flac"))
+            .is_err());
+        assert_eq!(engines[1].state(), PlaybackState::Stopped);
     }
 
     #[test]
-    fn test_playback_pause_function() {
+    fn test_pull_render_matches_processed_source() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+        let data: Vec<f64> = (0..2000).map(|i| (i as f64 / 2000.0) - 0.5).collect();
         let mut engine = AudioEngine::new().unwrap();
-
-        // Pause without playing should work (no-op or error)
-        let result = engine.pause();
-        // Should either succeed or fail gracefully
-        if result.is_ok() {
-            let state = engine.state();
-            assert!(
-                state == PlaybackState::Stopped
-                    || state == PlaybackState::Paused
-                    || state == PlaybackState::Error
-            );
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(1000);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), data.clone()));
+            state.state = PlaybackState::Playing;
+            state.volume = 0.5;
+            state.target_volume = 0.5;
+            None
+        });
+        engine.set_fade_duration(0);
+
+        // Odd block sizes, including a trailing partial frame slot
+        let mut rendered = Vec::new();
+        for block in [7, 100, 33, 250].iter().cycle().take(12) {
+            let mut output = vec![1.0; block * 2 + 1];
+            let frames = engine.render(&mut output, 2);
+            assert_eq!(frames, *block);
+            assert_eq!(output[frames * 2], 0.0);
+            rendered.extend_from_slice(&output[..frames * 2]);
+            if rendered.len() < data.len() {
+                assert_eq!(engine.position() as usize, rendered.len() / 2);
+            } else {
+                break;
+            }
         }
+
+        let expected: Vec<f64> = data.iter().map(|&s| s * 0.5).collect();
+        assert_eq!(&rendered[..data.len()], &expected[..]);
+        assert!(rendered[data.len()..].iter().all(|&s| s == 0.0));
+        assert_eq!(engine.render(&mut [0.0; 8], 0), 0);
     }
 
     #[test]
-    fn test_playback_stop_function() {
+    fn test_pull_render_remixes_to_requested_channels() {
+        let format = AudioFormat::new(44100, 1, SampleFormat::F64);
         let mut engine = AudioEngine::new().unwrap();
+        engine.update_state(|state| {
+            state.format = Some(format.clone());
+            state.duration = Some(64);
+            state.buffer = Some(AudioBuffer::with_data(format.clone(), vec![0.25; 64]));
+            state.state = PlaybackState::Playing;
+            None
+        });
+        engine.set_fade_duration(0);
 
-        // Stop should always succeed and reset position
-        let result = engine.stop();
-        assert!(result.is_ok());
-        assert_eq!(engine.state(), PlaybackState::Stopped);
-        assert_eq!(engine.position(), 0);
-
-        // Set position and stop again
-        engine.seek(1000).unwrap();
-        assert_eq!(engine.position(), 1000);
+        let mut output = vec![0.0; 32];
+        assert_eq!(engine.render(&mut output, 2), 16);
+        assert!(output.iter().all(|&s| s == 0.25));
+        assert_eq!(engine.position(), 16);
+    }
 
-        engine.stop().unwrap();
-        assert_eq!(engine.position(), 0);
+    /// Record state changes and underruns reported to the event callback
+    fn record_stream_events(engine: &mut AudioEngine) -> Arc<Mutex<Vec<String>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        engine.set_callback(Box::new(move |event| {
+            if matches!(
+                event,
+                AudioEvent::StateChanged(_) | AudioEvent::BufferUnderrun
+            ) {
+                recorded.lock().unwrap().push(format!("{:?}", event));
+            }
+        }));
+        events
     }
 
     #[test]
-    fn test_playback_seek_function() {
-        let mut engine = AudioEngine::new().unwrap();
-
-        // Seek should update position
-        engine.seek(0).unwrap();
-        assert_eq!(engine.position(), 0);
+    fn test_decode_memory_limit_switches_to_streaming() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        // 4410 stereo frames decode to 70560 bytes
+        write_constant_wav(&path, 8192, 4410);
 
-        engine.seek(1000).unwrap();
-        assert_eq!(engine.position(), 1000);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(crate::audio::output::NullBackend::default());
+        engine.set_max_decode_memory(Some(70560));
+        engine.load_file(&path).unwrap();
+        assert!(!engine.is_using_ring_buffer());
+        assert!(engine.state.read().buffer.is_some());
 
-        engine.seek(5000).unwrap();
-        assert_eq!(engine.position(), 5000);
+        let modes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = modes.clone();
+        engine.set_callback(Box::new(move |event| {
+            if let AudioEvent::PlaybackModeChanged(mode) = event {
+                sink.lock().unwrap().push(mode);
+            }
+        }));
+        engine.load_file(&path).unwrap();
+        engine.set_max_decode_memory(Some(1024));
+        engine.load_file(&path).unwrap();
+        assert_eq!(
+            *modes.lock().unwrap(),
+            [PlaybackMode::Buffered, PlaybackMode::Streaming]
+        );
+        assert!(engine.is_using_ring_buffer());
+        assert!(engine.state.read().buffer.is_none());
+        assert_eq!(engine.duration(), Some(4410));
+    }
 
-        // Seek backwards
-        engine.seek(2000).unwrap();
-        assert_eq!(engine.position(), 2000);
+    #[test]
+    fn test_event_thread_reports_off_the_rendering_thread() {
+        let callbacks = Arc::new(EngineCallbacks::default());
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        callbacks.set_event(Some(Box::new(move |event| {
+            sink.lock()
+                .unwrap()
+                .push((event, std::thread::current().id()));
+        })));
+
+        let (event_thread, events) = EventThread::start(callbacks);
+        events
+            .try_send(BlockEvents {
+                events: vec![AudioEvent::PlaybackModeChanged(PlaybackMode::Buffered)],
+                looped_to: None,
+                levels: None,
+            })
+            .unwrap();
+        let started = std::time::Instant::now();
+        while reported.lock().unwrap().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(event_thread);
+
+        let reported = reported.lock().unwrap();
+        assert!(matches!(
+            reported[0].0,
+            AudioEvent::PlaybackModeChanged(PlaybackMode::Buffered)
+        ));
+        assert_ne!(reported[0].1, std::thread::current().id());
+        // The queue closes with the thread
+        assert!(events
+            .try_send(BlockEvents {
+                events: Vec::new(),
+                looped_to: None,
+                levels: None,
+            })
+            .is_err());
+    }
 
-        // Seek to beginning
-        engine.seek(0).unwrap();
-        assert_eq!(engine.position(), 0);
+    #[test]
+    fn test_drop_stops_background_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.wav");
+        // Far more than the ring buffer holds, so the decoder thread blocks
+        write_constant_wav(&path, 8192, 44100 * 10);
+        let queued = dir.path().join("queued.wav");
+        write_constant_wav(&queued, 8192, 100);
+
+        for _ in 0..20 {
+            let mut engine = AudioEngine::new().unwrap();
+            let config = engine.stream_config().clone();
+            engine.open_streaming(&path, None, config).unwrap();
+            engine.update_queue(|queue| queue.set_items(vec![queued.clone()]));
+            engine.update_prefetch_monitor();
+            assert!(engine.prefetch_monitor.is_some());
+
+            // The producer side closes once the decoder thread has exited
+            let consumer = engine.state.write().ring_buffer_consumer.take().unwrap();
+            assert!(!consumer.is_closed());
+            drop(engine);
+            assert!(consumer.is_closed());
+        }
     }
 
     #[test]
-    fn test_playback_state_management() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_stream_buffers_before_playing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.wav");
+        write_constant_wav(&path, 8192, 44100);
 
-        // Test initial state
-        assert_eq!(engine.state(), PlaybackState::Stopped);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        let events = record_stream_events(&mut engine);
 
-        // Test state after seeking
-        engine.seek(100).unwrap();
+        let config = engine.stream_config().clone();
+        engine.open_streaming(&path, None, config).unwrap();
         assert_eq!(engine.state(), PlaybackState::Stopped);
+        // What play() does once the output stream has started
+        engine.enter_playing();
+
+        // Pull output like the device would until the decoder has caught up
+        let mut output = vec![0.0f64; 512];
+        let deadline = std::time::Instant::now() + StdDuration::from_secs(5);
+        while engine.state() != PlaybackState::Playing && std::time::Instant::now() < deadline {
+            AudioEngine::audio_callback(&mut output, &engine.state);
+            std::thread::sleep(StdDuration::from_millis(1));
+        }
+        assert_eq!(engine.state(), PlaybackState::Playing);
 
-        // Test stop resets position
-        engine.seek(500).unwrap();
-        engine.stop().unwrap();
-        assert_eq!(engine.position(), 0);
-        assert_eq!(engine.state(), PlaybackState::Stopped);
+        let events = events.lock().unwrap();
+        let buffering = events.iter().position(|e| e == "StateChanged(Buffering)");
+        let playing = events.iter().position(|e| e == "StateChanged(Playing)");
+        assert!(buffering.unwrap() < playing.unwrap(), "{:?}", events);
     }
 
     #[test]
-    fn test_playback_state_transitions_comprehensive() {
-        let mut engine = AudioEngine::new().unwrap();
-
-        // Test Stopped -> Playing
-        assert_eq!(engine.state(), PlaybackState::Stopped);
-        let play_result = engine.play();
+    fn test_underrun_buffers_until_stream_catches_up() {
+        use crate::audio::ring_buffer::AudioRingBuffer;
 
-        if play_result.is_ok() && engine.state() == PlaybackState::Playing {
-            // Test Playing -> Paused
-            engine.pause().unwrap();
-            assert_eq!(engine.state(), PlaybackState::Paused);
+        let format = AudioFormat::new(1000, 2, SampleFormat::F64);
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format: format.clone(),
+            allow_overwrite: false,
+            underrun_threshold: 0.1,
+        };
+        let (producer, consumer) = AudioRingBuffer::new(config).unwrap();
 
-            // Test Paused -> Playing
-            engine.play().unwrap();
-            assert_eq!(engine.state(), PlaybackState::Playing);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        engine.set_ring_buffer_consumer(consumer).unwrap();
+        engine.state.write().format = Some(format);
+        let events = record_stream_events(&mut engine);
+
+        // Nothing decoded yet: playback waits silently
+        engine.enter_playing();
+        assert_eq!(engine.state(), PlaybackState::Buffering);
+        let mut output = vec![1.0f64; 200];
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert!(output.iter().all(|&s| s == 0.0));
+        assert_eq!(engine.state(), PlaybackState::Buffering);
+        assert_eq!(engine.position(), 0);
 
-            // Test Playing -> Stopped
-            engine.stop().unwrap();
-            assert_eq!(engine.state(), PlaybackState::Stopped);
-            assert_eq!(engine.position(), 0);
+        // Half the buffer is enough to start
+        producer.write(&[0.5; 1000]);
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert!(output.iter().all(|&s| s == 0.5));
 
-            // Test Paused -> Stopped
-            engine.play().unwrap();
-            engine.pause().unwrap();
-            assert_eq!(engine.state(), PlaybackState::Paused);
-            engine.stop().unwrap();
-            assert_eq!(engine.state(), PlaybackState::Stopped);
-            assert_eq!(engine.position(), 0);
+        // Running dry stalls playback again
+        for _ in 0..5 {
+            AudioEngine::audio_callback(&mut output, &engine.state);
         }
+        assert_eq!(engine.state(), PlaybackState::Buffering);
+
+        // Once the decoder has finished, whatever is left plays out
+        producer.write(&[0.5; 100]);
+        drop(producer);
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        assert!(output[..100].iter().all(|&s| s == 0.5));
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.state(), PlaybackState::Playing);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "StateChanged(Buffering)",
+                "StateChanged(Playing)",
+                "BufferUnderrun",
+                "StateChanged(Buffering)",
+                "StateChanged(Playing)",
+            ]
+        );
     }
 
     #[test]
-    fn test_playback_position_persistence() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_streaming_input_plays_pushed_samples() {
+        use crate::audio::output::NullBackend;
 
-        // Position should persist through pause/play
-        engine.seek(1000).unwrap();
-        assert_eq!(engine.position(), 1000);
+        let format = AudioFormat::new(8000, 2, SampleFormat::F32);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        let events = record_stream_events(&mut engine);
+
+        // The config's format is replaced by the stream format
+        let config = RingBufferConfig::standard(AudioFormat::new(48000, 1, SampleFormat::I16));
+        let producer = engine
+            .create_streaming_input(format.clone(), config)
+            .unwrap();
+        assert!(engine.is_using_ring_buffer());
+        assert_eq!(engine.state(), PlaybackState::Stopped);
+        assert_eq!(engine.duration(), None);
+        assert_eq!(engine.state.read().format, Some(format.clone()));
+        assert_eq!(engine.output_format().map(|f| f.sample_rate), Some(8000));
+
+        // An external decoder feeding a ramp from its own thread, enough
+        // to get past the preroll
+        let samples: Vec<f64> = (0..24000).map(|i| (i / 2) as f64 / 12000.0).collect();
+        let feeder = {
+            let samples = samples.clone();
+            std::thread::spawn(move || {
+                assert_eq!(producer.write(&samples), samples.len());
+                producer
+            })
+        };
+        let producer = feeder.join().unwrap();
 
-        // Try play/pause cycle
-        let _ = engine.play();
-        let _ = engine.pause();
+        engine.play().unwrap();
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        let rendered = engine.render_for_testing(12000);
+        assert_eq!(engine.position(), 12000);
+        assert_eq!(rendered.len(), samples.len());
+        for (out, expected) in rendered.iter().zip(&samples) {
+            assert!((out - *expected).abs() < 1e-6);
+        }
 
-        // Position should still be at 1000 (or wherever playback moved it)
-        let position = engine.position();
-        assert!(
-            position >= 1000,
-            "Position should be >= 1000, got {}",
-            position
+        // Starving the engine is an underrun, not the end of the stream
+        engine.advance_for_testing(100);
+        assert_eq!(engine.state(), PlaybackState::Buffering);
+        assert_eq!(engine.debug_snapshot().underrun_count, 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "StateChanged(Stopped)",
+                "StateChanged(Playing)",
+                "BufferUnderrun",
+                "StateChanged(Buffering)",
+            ]
         );
+        drop(producer);
     }
 
     #[test]
-    fn test_playback_multiple_operations() {
-        let mut engine = AudioEngine::new().unwrap();
+    fn test_seek_by_seconds_clamps_to_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_constant_wav(&path, 8192, 44100 * 2);
 
-        // Test multiple play calls
-        let _ = engine.play();
-        let _ = engine.play(); // Should be idempotent or handle gracefully
+        let mut engine = AudioEngine::new().unwrap();
+        assert!(engine.seek_relative(1.0).is_err());
+        engine.load_buffer(&path).unwrap();
+        let positions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = positions.clone();
+        engine.set_callback(Box::new(move |event| {
+            if let AudioEvent::PositionChanged(position) = event {
+                recorded.lock().unwrap().push(position);
+            }
+        }));
 
-        // Test multiple pause calls
-        let _ = engine.pause();
-        let _ = engine.pause(); // Should be idempotent
+        engine.seek_to_seconds(1.5).unwrap();
+        assert_eq!(engine.position(), 66150);
+        engine.seek_relative(-0.5).unwrap();
+        assert_eq!(engine.position(), 44100);
 
-        // Test multiple stop calls
-        engine.stop().unwrap();
-        engine.stop().unwrap(); // Should be idempotent
+        // Past the end lands on the end
+        engine.seek_to_seconds(10.0).unwrap();
+        assert_eq!(engine.position(), 88200);
+        engine.seek_relative(1.0).unwrap();
+        assert_eq!(engine.position(), 88200);
 
-        assert_eq!(engine.state(), PlaybackState::Stopped);
+        // Skipping back near the start stops at 0 instead of wrapping
+        engine.seek(100).unwrap();
+        engine.seek_relative(-10.0).unwrap();
         assert_eq!(engine.position(), 0);
+        engine.seek_relative(-10.0).unwrap();
+        assert_eq!(engine.position(), 0);
+        engine.seek_to_seconds(-1.0).unwrap();
+        assert_eq!(engine.position(), 0);
+
+        assert!(engine.seek_to_seconds(f64::NAN).is_err());
+        assert_eq!(
+            *positions.lock().unwrap(),
+            vec![66150, 44100, 88200, 100, 0]
+        );
     }
 
     #[test]
-    fn test_playback_seek_boundaries() {
-        let mut engine = AudioEngine::new().unwrap();
-
-        // Test seeking to 0
-        engine.seek(0).unwrap();
-        assert_eq!(engine.position(), 0);
+    fn test_load_chosen_track_of_multi_track_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tracks.mka");
+        crate::test_util::write_flac_mka(&path, &[("eng", 2), ("fre", 1)]);
 
-        // Test seeking to large values
-        engine.seek(u64::MAX).unwrap();
-        assert_eq!(engine.position(), u64::MAX);
+        let mut engine = AudioEngine::new().unwrap();
+        engine
+            .load_buffer_with_progress(&path, Some(2), &|_| {})
+            .unwrap();
+        let state = engine.state.read();
+        let frames = state.buffer.as_ref().unwrap().frames();
+        assert_eq!(frames, crate::test_util::FLAC_BLOCK_FRAMES);
+        assert_eq!(state.source_info.as_ref().unwrap().channels, Some(1));
+        drop(state);
 
-        // Test seeking back to reasonable value
-        engine.seek(1000).unwrap();
-        assert_eq!(engine.position(), 1000);
+        assert!(engine.load_file_track(&path, 7).is_err());
     }
 
     #[test]
-    fn test_playback_control_thread_safety() {
-        use parking_lot::RwLock;
-        use std::sync::Arc;
-        use std::thread;
-
-        let engine = Arc::new(RwLock::new(AudioEngine::new().unwrap()));
-        let mut handles = vec![];
+    fn test_stream_pause_resume_is_contiguous() {
+        // Each frame holds its own index, so gaps and repeats show up
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for frame in 0..44100 * 3 {
+            writer.write_sample((frame % 20000) as i16).unwrap();
+            writer.write_sample((frame % 20000) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
 
-        // Spawn threads that perform various playback operations
-        for i in 0..5 {
-            let engine_clone = engine.clone();
-            let handle = thread::spawn(move || {
-                let mut eng = engine_clone.write();
-                match i % 4 {
-                    0 => {
-                        let _ = eng.play();
-                    }
-                    1 => {
-                        let _ = eng.pause();
-                    }
-                    2 => {
-                        let _ = eng.stop();
-                    }
-                    _ => {
-                        let _ = eng.seek(i * 1000);
-                    }
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        let config = engine.stream_config().clone();
+        engine.open_streaming(&path, None, config).unwrap();
+
+        let mut rendered = Vec::new();
+        // Frames played over `blocks` callbacks; anything else is silent
+        let render = |engine: &AudioEngine, blocks: usize, rendered: &mut Vec<i64>| {
+            let mut output = vec![0.0f64; 1024];
+            for _ in 0..blocks {
+                AudioEngine::audio_callback(&mut output, &engine.state);
+                if engine.state() == PlaybackState::Playing {
+                    let frames = output.chunks(2).map(|f| (f[0] * 32768.0).round() as i64);
+                    rendered.extend(frames);
+                } else {
+                    assert!(output.iter().all(|&s| s == 0.0));
                 }
-            });
-            handles.push(handle);
-        }
+            }
+        };
+        let wait_until = |ready: &dyn Fn() -> bool| {
+            let deadline = std::time::Instant::now() + StdDuration::from_secs(5);
+            while !ready() && std::time::Instant::now() < deadline {
+                std::thread::sleep(StdDuration::from_millis(1));
+            }
+        };
+        // Rendering outpaces real time, so start from a full buffer
+        wait_until(&|| engine.ring_buffer_utilization().unwrap() > 0.99);
+        engine.enter_playing();
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        render(&engine, 4, &mut rendered);
+        let paused_at = engine.position();
+        assert_eq!(paused_at, 2048);
 
-        for handle in handles {
-            handle.join().unwrap();
+        // What pause() does once the output stream is paused: the decoder
+        // fills the buffer and then waits, and nothing is consumed
+        engine.update_state(|state| {
+            state.state = PlaybackState::Paused;
+            None
+        });
+        wait_until(&|| engine.ring_buffer_utilization().unwrap() > 0.99);
+        render(&engine, 10, &mut Vec::new());
+        assert_eq!(engine.position(), paused_at);
+        let buffered = engine
+            .state
+            .read()
+            .ring_buffer_consumer
+            .as_ref()
+            .unwrap()
+            .available_read();
+        std::thread::sleep(StdDuration::from_millis(20));
+        let consumer = engine.state.read().ring_buffer_consumer.clone().unwrap();
+        assert_eq!(consumer.available_read(), buffered);
+
+        engine.enter_playing();
+        assert_eq!(engine.state(), PlaybackState::Playing);
+        render(&engine, 4, &mut rendered);
+        assert_eq!(engine.position(), paused_at + 2048);
+        let expected: Vec<i64> = (0..rendered.len() as i64).collect();
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_stream_position_counts_only_frames_played() {
+        let format = AudioFormat::new(1000, 2, SampleFormat::F32);
+        let mut engine = AudioEngine::new().unwrap();
+        engine.set_fade_duration(0);
+        let producer = engine
+            .open_streaming_input(format.clone(), RingBufferConfig::standard(format))
+            .unwrap();
+
+        // 1500 frames then half a frame: the partial frame isn't played
+        producer.write(&[0.5; 3001]);
+        engine.enter_playing();
+        let mut output = vec![0.0f64; 1024];
+        for _ in 0..3 {
+            AudioEngine::audio_callback(&mut output, &engine.state);
         }
+        assert_eq!(engine.state(), PlaybackState::Buffering);
+        assert_eq!(engine.position(), 1500);
 
-        // Engine should still be in a valid state
-        let eng = engine.read();
-        let state = eng.state();
-        assert!(
-            state == PlaybackState::Stopped
-                || state == PlaybackState::Playing
-                || state == PlaybackState::Paused
-                || state == PlaybackState::Error
-        );
+        // Pausing through the underrun and resuming continues from there
+        engine.update_state(|state| {
+            state.state = PlaybackState::Paused;
+            None
+        });
+        producer.write(&[0.25; 2999]);
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.position(), 1500);
+        engine.enter_playing();
+        AudioEngine::audio_callback(&mut output, &engine.state);
+        assert_eq!(engine.position(), 2012);
+        assert_eq!(output[..2], [0.5, 0.25]);
+        assert!(output[2..].iter().all(|&s| s == 0.25));
     }
 }
@@ -0,0 +1,196 @@
+//! Parametric equalizer
+//!
+//! A chain of biquad bands applied per channel. Bands are described by
+//! sample-rate independent `BiquadParams`, so coefficients are recomputed
+//! whenever the chain is configured for a different rate.
+
+use crate::audio::filter::{Biquad, BiquadCoefficients, BiquadParams};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Named set of equalizer bands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqPreset {
+    /// Display name ("Rock", "Vocal Boost")
+    pub name: String,
+    /// Bands applied in order
+    pub bands: Vec<BiquadParams>,
+}
+
+impl EqPreset {
+    /// Create a preset
+    pub fn new(name: impl Into<String>, bands: Vec<BiquadParams>) -> Self {
+        Self {
+            name: name.into(),
+            bands,
+        }
+    }
+
+    /// Check every band's parameters
+    pub fn validate(&self) -> Result<()> {
+        self.bands.iter().try_for_each(|band| band.validate())
+    }
+}
+
+/// Equalizer chain processing interleaved audio
+///
+/// Unconfigured chains (no sample rate yet) pass audio through unchanged.
+#[derive(Debug, Clone)]
+pub struct Equalizer {
+    bands: Vec<BiquadParams>,
+    sample_rate: u32,
+    channels: u16,
+    /// One filter per band per channel, channel-major
+    filters: Vec<Biquad>,
+}
+
+impl Equalizer {
+    /// Create an unconfigured chain from band parameters
+    pub fn new(bands: Vec<BiquadParams>) -> Self {
+        Self {
+            bands,
+            sample_rate: 0,
+            channels: 0,
+            filters: Vec::new(),
+        }
+    }
+
+    /// Band parameters of the chain
+    pub fn bands(&self) -> &[BiquadParams] {
+        &self.bands
+    }
+
+    /// Sample rate the coefficients were computed for (0 if unconfigured)
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Coefficients of each band at the configured rate
+    pub fn coefficients(&self) -> Vec<BiquadCoefficients> {
+        self.filters
+            .iter()
+            .take(self.bands.len())
+            .map(|filter| *filter.coefficients())
+            .collect()
+    }
+
+    /// Prepare the chain for a sample rate and channel count
+    ///
+    /// Does nothing when already configured for them; otherwise the
+    /// coefficients are recomputed and the filter state cleared.
+    pub fn configure(&mut self, sample_rate: u32, channels: u16) {
+        if sample_rate == self.sample_rate && channels == self.channels {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.filters = if sample_rate == 0 {
+            Vec::new()
+        } else {
+            (0..channels)
+                .flat_map(|_| self.bands.iter())
+                .map(|band| Biquad::new(band.coefficients(sample_rate)))
+                .collect()
+        };
+    }
+
+    /// Replace the band parameters
+    ///
+    /// When the band count is unchanged the filters are retuned in place,
+    /// keeping their state so the change does not click; otherwise the
+    /// chain is rebuilt for the configured rate.
+    pub fn set_bands(&mut self, bands: Vec<BiquadParams>) {
+        let retune = bands.len() == self.bands.len() && !self.filters.is_empty();
+        self.bands = bands;
+        if retune {
+            let bands = &self.bands;
+            let sample_rate = self.sample_rate;
+            for (filter, band) in self.filters.iter_mut().zip(bands.iter().cycle()) {
+                filter.set_coefficients(band.coefficients(sample_rate));
+            }
+        } else {
+            let (sample_rate, channels) = (self.sample_rate, self.channels);
+            self.sample_rate = 0;
+            self.configure(sample_rate, channels);
+        }
+    }
+
+    /// Clear the filter state of every band
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().for_each(Biquad::reset);
+    }
+
+    /// Filter interleaved samples in place
+    pub fn process_interleaved(&mut self, samples: &mut [f64]) {
+        let bands = self.bands.len();
+        let channels = self.channels as usize;
+        if bands == 0 || self.filters.is_empty() {
+            return;
+        }
+
+        for frame in samples.chunks_mut(channels) {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let chain = &mut self.filters[channel * bands..(channel + 1) * bands];
+                let mut value = *sample;
+                for filter in chain.iter_mut() {
+                    value = filter.process(value);
+                }
+                *sample = value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::filter::FilterType;
+
+    #[test]
+    fn test_configure_recomputes_for_rate() {
+        let band = BiquadParams::new(FilterType::Peaking, 1000.0, 1.0, 6.0);
+        let mut eq = Equalizer::new(vec![band]);
+        assert!(eq.coefficients().is_empty());
+
+        eq.configure(44100, 2);
+        assert_eq!(eq.coefficients(), vec![band.coefficients(44100)]);
+
+        eq.configure(96000, 2);
+        assert_eq!(eq.sample_rate(), 96000);
+        assert_eq!(eq.coefficients(), vec![band.coefficients(96000)]);
+    }
+
+    #[test]
+    fn test_low_shelf_cut_attenuates_dc() {
+        let band = BiquadParams::new(FilterType::LowShelf, 1000.0, 0.707, -20.0);
+        let mut eq = Equalizer::new(vec![band]);
+        eq.configure(48000, 2);
+
+        let mut samples = vec![0.5f64; 48000];
+        eq.process_interleaved(&mut samples);
+
+        let tail = &samples[samples.len() - 2..];
+        assert!(tail.iter().all(|s| (s - 0.05).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_set_bands_retunes_configured_chain() {
+        let band = BiquadParams::new(FilterType::LowShelf, 100.0, 0.707, 3.0);
+        let mut eq = Equalizer::new(vec![band]);
+        eq.configure(48000, 2);
+
+        let louder = BiquadParams {
+            gain_db: 9.0,
+            ..band
+        };
+        eq.set_bands(vec![louder]);
+        assert_eq!(eq.coefficients(), vec![louder.coefficients(48000)]);
+
+        let extra = BiquadParams::new(FilterType::HighShelf, 8000.0, 0.707, 2.0);
+        eq.set_bands(vec![louder, extra]);
+        assert_eq!(
+            eq.coefficients(),
+            vec![louder.coefficients(48000), extra.coefficients(48000)]
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! Biquad filter primitives
+//!
+//! Provides second-order IIR sections operating on f64 samples. These are the
+//! building blocks for weighting filters, equalization and other per-channel
+//! DSP stages.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Normalized biquad coefficients (a0 = 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefficients {
+    /// Feed-forward coefficient b0
+    pub b0: f64,
+    /// Feed-forward coefficient b1
+    pub b1: f64,
+    /// Feed-forward coefficient b2
+    pub b2: f64,
+    /// Feedback coefficient a1
+    pub a1: f64,
+    /// Feedback coefficient a2
+    pub a2: f64,
+}
+
+impl BiquadCoefficients {
+    /// Create coefficients from raw values, normalizing by `a0`
+    pub fn new(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Coefficients for a filter that passes the signal unchanged
+    pub fn identity() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        }
+    }
+
+    /// Magnitude response (linear) at the given frequency
+    pub fn magnitude_at(&self, frequency: f64, sample_rate: f64) -> f64 {
+        let w = 2.0 * std::f64::consts::PI * frequency / sample_rate;
+        let (cos1, sin1) = (w.cos(), w.sin());
+        let (cos2, sin2) = ((2.0 * w).cos(), (2.0 * w).sin());
+
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt()
+    }
+}
+
+impl Default for BiquadCoefficients {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Response shape of a parametric filter band
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterType {
+    /// Bell boost/cut around the center frequency
+    Peaking,
+    /// Boost/cut below the corner frequency
+    LowShelf,
+    /// Boost/cut above the corner frequency
+    HighShelf,
+    /// Second-order low-pass
+    LowPass,
+    /// Second-order high-pass
+    HighPass,
+}
+
+/// Sample-rate independent description of a filter band
+///
+/// Coefficients are derived for a concrete rate with `coefficients`, using
+/// the RBJ audio EQ cookbook formulas.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BiquadParams {
+    /// Filter shape
+    pub filter_type: FilterType,
+    /// Center/corner frequency in Hz
+    pub frequency: f64,
+    /// Quality factor (bandwidth)
+    pub q: f64,
+    /// Gain in dB (ignored by pass filters)
+    pub gain_db: f64,
+}
+
+impl BiquadParams {
+    /// Create band parameters
+    pub fn new(filter_type: FilterType, frequency: f64, q: f64, gain_db: f64) -> Self {
+        Self {
+            filter_type,
+            frequency,
+            q,
+            gain_db,
+        }
+    }
+
+    /// Check that frequency and Q are positive and all values finite
+    pub fn validate(&self) -> Result<()> {
+        if !self.frequency.is_finite() || self.frequency <= 0.0 {
+            return Err(Error::InvalidParameter(format!(
+                "Filter frequency must be positive, got {}",
+                self.frequency
+            )));
+        }
+        if !self.q.is_finite() || self.q <= 0.0 {
+            return Err(Error::InvalidParameter(format!(
+                "Filter Q must be positive, got {}",
+                self.q
+            )));
+        }
+        if !self.gain_db.is_finite() {
+            return Err(Error::InvalidParameter(
+                "Filter gain must be finite".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compute the coefficients for `sample_rate`
+    ///
+    /// Frequencies at or above Nyquist are pulled just below it so bands
+    /// authored for higher rates stay stable.
+    pub fn coefficients(&self, sample_rate: u32) -> BiquadCoefficients {
+        let sample_rate = sample_rate as f64;
+        let frequency = self.frequency.min(sample_rate * 0.49);
+        let w0 = 2.0 * std::f64::consts::PI * frequency / sample_rate;
+        let (cos, sin) = (w0.cos(), w0.sin());
+        let alpha = sin / (2.0 * self.q);
+        let a = 10_f64.powf(self.gain_db / 40.0);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        match self.filter_type {
+            FilterType::Peaking => BiquadCoefficients::new(
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            FilterType::LowShelf => BiquadCoefficients::new(
+                a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            FilterType::HighShelf => BiquadCoefficients::new(
+                a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            FilterType::LowPass => BiquadCoefficients::new(
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterType::HighPass => BiquadCoefficients::new(
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+        }
+    }
+}
+
+/// Q of the two sections of a 4th-order Butterworth filter
+const BUTTERWORTH_4_Q: [f64; 2] = [0.541_196_100_146_197, 1.306_562_964_876_376_6];
+
+/// Sections of a 4th-order (24 dB/octave) Butterworth high-pass at `frequency`
+///
+/// Cascading the two sections gives a maximally flat passband, -3 dB at
+/// the corner.
+pub fn butterworth_high_pass(frequency: f64) -> Vec<BiquadParams> {
+    BUTTERWORTH_4_Q
+        .iter()
+        .map(|&q| BiquadParams::new(FilterType::HighPass, frequency, q, 0.0))
+        .collect()
+}
+
+/// Corner of the bass shelf of `loudness_contour`, in Hz
+const LOUDNESS_BASS_HZ: f64 = 100.0;
+/// Corner of the treble shelf of `loudness_contour`, in Hz
+const LOUDNESS_TREBLE_HZ: f64 = 10_000.0;
+/// Bass and treble boost per dB of attenuation below reference volume
+const LOUDNESS_SLOPES: (f64, f64) = (0.3, 0.1);
+/// Largest bass and treble boost, in dB
+const LOUDNESS_MAX_GAIN_DB: (f64, f64) = (12.0, 4.0);
+
+/// Bass and treble shelves compensating for hearing at `volume`
+///
+/// Follows the equal-loudness contours: the ear loses sensitivity to low
+/// and high frequencies at low listening levels, so the shelves boost more
+/// the further `volume` (linear) is below 1.0 and are flat at or above it.
+pub fn loudness_contour(volume: f32) -> Vec<BiquadParams> {
+    let attenuation_db = -20.0 * (volume as f64).max(1e-6).log10();
+    let attenuation_db = attenuation_db.max(0.0);
+    let bass = (attenuation_db * LOUDNESS_SLOPES.0).min(LOUDNESS_MAX_GAIN_DB.0);
+    let treble = (attenuation_db * LOUDNESS_SLOPES.1).min(LOUDNESS_MAX_GAIN_DB.1);
+    vec![
+        BiquadParams::new(FilterType::LowShelf, LOUDNESS_BASS_HZ, 0.707, bass),
+        BiquadParams::new(FilterType::HighShelf, LOUDNESS_TREBLE_HZ, 0.707, treble),
+    ]
+}
+
+/// Single biquad section (transposed direct form II)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    coefficients: BiquadCoefficients,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Create a new biquad with the given coefficients
+    pub fn new(coefficients: BiquadCoefficients) -> Self {
+        Self {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Get the filter coefficients
+    pub fn coefficients(&self) -> &BiquadCoefficients {
+        &self.coefficients
+    }
+
+    /// Replace the coefficients, keeping the filter state
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
+        self.coefficients = coefficients;
+    }
+
+    /// Clear the filter state
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    /// Process a single sample
+    #[inline]
+    pub fn process(&mut self, input: f64) -> f64 {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+
+    /// Process a mono block of samples in place
+    pub fn process_block(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_passthrough() {
+        let mut biquad = Biquad::new(BiquadCoefficients::identity());
+        let mut samples = vec![0.1, -0.5, 0.9, 0.0];
+        let original = samples.clone();
+
+        biquad.process_block(&mut samples);
+
+        assert_eq!(samples, original);
+    }
+
+    #[test]
+    fn test_normalization() {
+        let coeffs = BiquadCoefficients::new(2.0, 4.0, 6.0, 2.0, 1.0, 0.5);
+
+        assert_eq!(coeffs.b0, 1.0);
+        assert_eq!(coeffs.b1, 2.0);
+        assert_eq!(coeffs.b2, 3.0);
+        assert_eq!(coeffs.a1, 0.5);
+        assert_eq!(coeffs.a2, 0.25);
+    }
+
+    #[test]
+    fn test_reset_clears_state() {
+        let coeffs = BiquadCoefficients::new(0.5, 0.5, 0.0, 1.0, -0.5, 0.0);
+        let mut biquad = Biquad::new(coeffs);
+
+        let first = biquad.process(1.0);
+        biquad.process(1.0);
+        biquad.reset();
+
+        assert_eq!(biquad.process(1.0), first);
+    }
+
+    #[test]
+    fn test_band_responses() {
+        let rate = 48000;
+        let to_db = |linear: f64| 20.0 * linear.log10();
+
+        let peak = BiquadParams::new(FilterType::Peaking, 1000.0, 1.0, 6.0).coefficients(rate);
+        assert!((to_db(peak.magnitude_at(1000.0, rate as f64)) - 6.0).abs() < 0.01);
+        assert!(to_db(peak.magnitude_at(50.0, rate as f64)).abs() < 0.1);
+
+        let low = BiquadParams::new(FilterType::LowShelf, 200.0, 0.707, -12.0).coefficients(rate);
+        assert!((to_db(low.magnitude_at(10.0, rate as f64)) + 12.0).abs() < 0.1);
+        assert!(to_db(low.magnitude_at(10000.0, rate as f64)).abs() < 0.1);
+
+        let high = BiquadParams::new(FilterType::HighPass, 100.0, 0.707, 0.0).coefficients(rate);
+        assert!(high.magnitude_at(10.0, rate as f64) < 0.02);
+    }
+
+    #[test]
+    fn test_butterworth_high_pass_response() {
+        let rate = 48000;
+        let response = |frequency: f64| -> f64 {
+            butterworth_high_pass(100.0)
+                .iter()
+                .map(|band| band.coefficients(rate).magnitude_at(frequency, rate as f64))
+                .product()
+        };
+
+        assert!((20.0 * response(100.0).log10() + 3.01).abs() < 0.05);
+        // 24 dB per octave below the corner
+        assert!((20.0 * response(25.0).log10() + 48.0).abs() < 0.5);
+        assert!((response(1000.0) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_loudness_contour_follows_volume() {
+        let gains = |volume: f32| -> Vec<f64> {
+            loudness_contour(volume)
+                .iter()
+                .map(|band| band.gain_db)
+                .collect()
+        };
+
+        assert_eq!(gains(1.0), vec![0.0, 0.0]);
+        assert_eq!(gains(2.0), vec![0.0, 0.0]);
+        let (quiet, quieter) = (gains(0.5), gains(0.05));
+        assert!(quiet.iter().all(|&gain| gain > 0.0));
+        assert!(quieter[0] > quiet[0] && quieter[1] > quiet[1]);
+        assert_eq!(gains(0.0), vec![12.0, 4.0]);
+    }
+
+    #[test]
+    fn test_band_validation() {
+        assert!(BiquadParams::new(FilterType::Peaking, 1000.0, 1.0, 3.0)
+            .validate()
+            .is_ok());
+        assert!(BiquadParams::new(FilterType::Peaking, 0.0, 1.0, 3.0)
+            .validate()
+            .is_err());
+        assert!(BiquadParams::new(FilterType::Peaking, 1000.0, 0.0, 3.0)
+            .validate()
+            .is_err());
+        assert!(
+            BiquadParams::new(FilterType::Peaking, 1000.0, 1.0, f64::NAN)
+                .validate()
+                .is_err()
+        );
+    }
+}
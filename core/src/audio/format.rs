@@ -5,23 +5,35 @@
 use serde::{Deserialize, Serialize};
 
 /// Audio sample format
+///
+/// Serializes as a stable lowercase name ("i16", "f32", ...); the
+/// capitalized variant names written by earlier versions are still read.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SampleFormat {
     /// 8-bit unsigned integer
+    #[serde(alias = "U8")]
     U8,
     /// 8-bit signed integer
+    #[serde(alias = "I8")]
     I8,
     /// 16-bit unsigned integer
+    #[serde(alias = "U16")]
     U16,
     /// 16-bit signed integer
+    #[serde(alias = "I16")]
     I16,
     /// 24-bit signed integer (stored in i32)
+    #[serde(alias = "I24")]
     I24,
     /// 32-bit signed integer
+    #[serde(alias = "I32")]
     I32,
     /// 32-bit floating point
+    #[serde(alias = "F32")]
     F32,
     /// 64-bit floating point
+    #[serde(alias = "F64")]
     F64,
 }
 
@@ -49,6 +61,26 @@ impl SampleFormat {
     pub fn is_integer(&self) -> bool {
         !self.is_float()
     }
+
+    /// Short lowercase name, as used for serialization (e.g. "i16", "f32")
+    pub fn name(&self) -> &'static str {
+        match self {
+            SampleFormat::U8 => "u8",
+            SampleFormat::I8 => "i8",
+            SampleFormat::U16 => "u16",
+            SampleFormat::I16 => "i16",
+            SampleFormat::I24 => "i24",
+            SampleFormat::I32 => "i32",
+            SampleFormat::F32 => "f32",
+            SampleFormat::F64 => "f64",
+        }
+    }
+}
+
+impl std::fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 /// Audio format specification
@@ -115,6 +147,17 @@ impl AudioFormat {
     }
 }
 
+/// Formats as "48000 Hz / 2ch / f32"
+impl std::fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} Hz / {}ch / {}",
+            self.sample_rate, self.channels, self.sample_format
+        )
+    }
+}
+
 impl Default for AudioFormat {
     fn default() -> Self {
         Self::new(44100, 2, SampleFormat::F32)
@@ -362,4 +405,56 @@ mod tests {
         assert_eq!(converted_back.sample_rate, format.sample_rate);
         assert_eq!(converted_back.channels, format.channels);
     }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            AudioFormat::new(48000, 2, SampleFormat::F32).to_string(),
+            "48000 Hz / 2ch / f32"
+        );
+        assert_eq!(
+            AudioFormat::new(44100, 1, SampleFormat::I24).to_string(),
+            "44100 Hz / 1ch / i24"
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let names = [
+            (SampleFormat::U8, "u8"),
+            (SampleFormat::I8, "i8"),
+            (SampleFormat::U16, "u16"),
+            (SampleFormat::I16, "i16"),
+            (SampleFormat::I24, "i24"),
+            (SampleFormat::I32, "i32"),
+            (SampleFormat::F32, "f32"),
+            (SampleFormat::F64, "f64"),
+        ];
+        for (format, name) in names {
+            assert_eq!(format.name(), name);
+            let json = serde_json::to_string(&format).unwrap();
+            assert_eq!(json, format!("\"{}\"", name));
+            assert_eq!(serde_json::from_str::<SampleFormat>(&json).unwrap(), format);
+            // Names written before the lowercase ones still load
+            let legacy = format!("\"{}\"", name.to_uppercase());
+            assert_eq!(
+                serde_json::from_str::<SampleFormat>(&legacy).unwrap(),
+                format
+            );
+        }
+
+        let format = AudioFormat::new(96000, 6, SampleFormat::I24);
+        let json = serde_json::to_value(&format).unwrap();
+        assert_eq!(json["sample_rate"], 96000);
+        assert_eq!(json["sample_format"], "i24");
+        let restored: AudioFormat = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, format);
+
+        let layout = ChannelLayout::Custom(vec![Channel::Left, Channel::LFE]);
+        let json = serde_json::to_string(&layout).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ChannelLayout>(&json).unwrap(),
+            layout
+        );
+    }
 }
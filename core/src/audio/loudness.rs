@@ -0,0 +1,464 @@
+//! Loudness measurement
+//!
+//! Implements ITU-R BS.1770 integrated loudness (K-weighting, 400ms gated
+//! blocks) and oversampled true-peak detection for interleaved f64 samples,
+//! plus the IEC 61672 A and C frequency weightings for level meters.
+
+use crate::audio::filter::{Biquad, BiquadCoefficients};
+use std::f64::consts::PI;
+
+/// Loudness ReplayGain 2.0 gains bring tracks to, in LUFS
+pub const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Absolute gating threshold in LUFS
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Relative gating threshold in LU below the ungated loudness
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// Gating block length in seconds
+const BLOCK_SECONDS: f64 = 0.4;
+
+/// Gating block overlap (75%)
+const BLOCK_OVERLAP: f64 = 0.75;
+
+/// Oversampling factor used for true-peak detection
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Half-length (in input samples) of the true-peak interpolation filter
+const TRUE_PEAK_TAPS: isize = 12;
+
+/// Result of a loudness measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessResult {
+    /// Integrated loudness in LUFS (negative infinity for silence)
+    pub integrated_lufs: f64,
+    /// True peak as a linear amplitude (1.0 = full scale)
+    pub true_peak: f64,
+}
+
+impl LoudnessResult {
+    /// Get the true peak in dBTP
+    pub fn true_peak_dbtp(&self) -> f64 {
+        if self.true_peak > 0.0 {
+            20.0 * self.true_peak.log10()
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+
+    /// Get the gain (in dB) needed to bring the measured loudness to `target_lufs`
+    ///
+    /// Returns 0.0 when the measured signal is silent.
+    pub fn gain_to_target(&self, target_lufs: f64) -> f64 {
+        if self.integrated_lufs.is_finite() {
+            target_lufs - self.integrated_lufs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Build the two-stage K-weighting filter for the given sample rate
+///
+/// Stage one is the head-related high shelf, stage two the RLB high-pass.
+pub fn k_weighting_filters(sample_rate: u32) -> [BiquadCoefficients; 2] {
+    let fs = sample_rate as f64;
+
+    // Stage 1: high shelf
+    let f0 = 1_681.974_450_955_533;
+    let gain_db = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let shelf = BiquadCoefficients::new(
+        vh + vb * k / q + k * k,
+        2.0 * (k * k - vh),
+        vh - vb * k / q + k * k,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    // Stage 2: high pass
+    let f0 = 38.135_470_876_024_44;
+    let q = 0.500_327_037_323_877_3;
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = BiquadCoefficients {
+        b0: 1.0,
+        b1: -2.0,
+        b2: 1.0,
+        a1: 2.0 * (k * k - 1.0) / a0,
+        a2: (1.0 - k / q + k * k) / a0,
+    };
+
+    [shelf, high_pass]
+}
+
+/// Frequency weighting of a level measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrequencyWeighting {
+    /// Flat, unweighted
+    #[default]
+    None,
+    /// IEC 61672 A-weighting (hearing at low levels)
+    AWeighting,
+    /// IEC 61672 C-weighting (hearing at high levels)
+    CWeighting,
+}
+
+/// Pole frequencies of the IEC 61672 weighting curves, in Hz
+const WEIGHTING_POLES: [f64; 4] = [20.598_997, 107.652_65, 737.862_23, 12_194.217];
+
+/// Bilinear transform of an analog section (b2 s² + b1 s + b0) / (a2 s² + a1 s + a0)
+fn bilinear(
+    [b2, b1, b0]: [f64; 3],
+    [a2, a1, a0]: [f64; 3],
+    sample_rate: f64,
+) -> BiquadCoefficients {
+    let k = 2.0 * sample_rate;
+    let kk = k * k;
+    BiquadCoefficients::new(
+        b2 * kk + b1 * k + b0,
+        2.0 * (b0 - b2 * kk),
+        b2 * kk - b1 * k + b0,
+        a2 * kk + a1 * k + a0,
+        2.0 * (a0 - a2 * kk),
+        a2 * kk - a1 * k + a0,
+    )
+}
+
+/// Build the biquad cascade for `weighting` at the given sample rate
+///
+/// The sections are derived from the analog pole/zero layout of the curves
+/// and normalized to 0 dB at 1 kHz. Flat weighting has no sections.
+pub fn frequency_weighting_filters(
+    weighting: FrequencyWeighting,
+    sample_rate: u32,
+) -> Vec<BiquadCoefficients> {
+    let fs = sample_rate as f64;
+    let [w1, w2, w3, w4] = WEIGHTING_POLES.map(|f| 2.0 * PI * f);
+
+    // Both curves: double zero at DC over the low double pole, and the
+    // high double pole
+    let mut sections = match weighting {
+        FrequencyWeighting::None => return Vec::new(),
+        FrequencyWeighting::AWeighting | FrequencyWeighting::CWeighting => vec![
+            bilinear([1.0, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1], fs),
+            bilinear([0.0, 0.0, w4 * w4], [1.0, 2.0 * w4, w4 * w4], fs),
+        ],
+    };
+    if weighting == FrequencyWeighting::AWeighting {
+        sections.push(bilinear([1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3], fs));
+    }
+
+    let gain: f64 = sections
+        .iter()
+        .map(|c| c.magnitude_at(1000.0, fs))
+        .product();
+    let first = &mut sections[0];
+    first.b0 /= gain;
+    first.b1 /= gain;
+    first.b2 /= gain;
+    sections
+}
+
+/// Per-channel weighting used when summing channel energies
+///
+/// For 5.1 material (L, R, C, LFE, Ls, Rs) the LFE channel is excluded and
+/// the surround channels receive +1.5 dB, as specified by BS.1770.
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    if channels == 6 {
+        match channel {
+            3 => 0.0,
+            4 | 5 => 1.41,
+            _ => 1.0,
+        }
+    } else {
+        1.0
+    }
+}
+
+/// Convert a mean-square energy to LUFS
+fn energy_to_lufs(energy: f64) -> f64 {
+    if energy > 0.0 {
+        -0.691 + 10.0 * energy.log10()
+    } else {
+        f64::NEG_INFINITY
+    }
+}
+
+/// Measure integrated loudness and true peak of interleaved samples
+///
+/// # Arguments
+/// * `samples` - Interleaved f64 samples
+/// * `sample_rate` - Sample rate in Hz
+/// * `channels` - Number of interleaved channels
+///
+/// # Returns
+/// The integrated loudness in LUFS and the true peak. Inputs shorter than one
+/// gating block (400ms) or entirely below the absolute gate report negative
+/// infinity for the integrated loudness.
+pub fn measure_lufs(samples: &[f64], sample_rate: u32, channels: u16) -> LoudnessResult {
+    let channels = channels as usize;
+    if channels == 0 || sample_rate == 0 || samples.is_empty() {
+        return LoudnessResult {
+            integrated_lufs: f64::NEG_INFINITY,
+            true_peak: 0.0,
+        };
+    }
+
+    let frames = samples.len() / channels;
+    let filters = k_weighting_filters(sample_rate);
+
+    // K-weighted squared samples, summed per 100ms step for each channel
+    let step_frames =
+        ((BLOCK_SECONDS * (1.0 - BLOCK_OVERLAP)) * sample_rate as f64).round() as usize;
+    let steps_per_block = (1.0 / (1.0 - BLOCK_OVERLAP)).round() as usize;
+    let step_count = frames / step_frames.max(1);
+    let mut step_energy = vec![0.0f64; step_count];
+
+    for channel in 0..channels {
+        let weight = channel_weight(channel, channels);
+        if weight == 0.0 {
+            continue;
+        }
+
+        let mut stage1 = Biquad::new(filters[0]);
+        let mut stage2 = Biquad::new(filters[1]);
+
+        for frame in 0..step_count * step_frames {
+            let filtered = stage2.process(stage1.process(samples[frame * channels + channel]));
+            step_energy[frame / step_frames] += weight * filtered * filtered;
+        }
+    }
+
+    // Overlapping 400ms blocks built from four consecutive 100ms steps
+    let block_frames = (step_frames * steps_per_block) as f64;
+    let blocks: Vec<f64> = if step_count >= steps_per_block {
+        step_energy
+            .windows(steps_per_block)
+            .map(|window| window.iter().sum::<f64>() / block_frames)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let integrated_lufs = gated_loudness(&blocks);
+
+    LoudnessResult {
+        integrated_lufs,
+        true_peak: true_peak(samples, channels),
+    }
+}
+
+/// Apply the absolute and relative gates to block energies
+fn gated_loudness(blocks: &[f64]) -> f64 {
+    let above_absolute: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&energy| energy_to_lufs(energy) > ABSOLUTE_GATE_LUFS)
+        .collect();
+
+    if above_absolute.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+
+    let ungated = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+    let relative_gate = energy_to_lufs(ungated) + RELATIVE_GATE_LU;
+
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|&energy| energy_to_lufs(energy) > relative_gate)
+        .collect();
+
+    if gated.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+
+    energy_to_lufs(gated.iter().sum::<f64>() / gated.len() as f64)
+}
+
+/// Estimate the true peak using 4x windowed-sinc oversampling
+fn true_peak(samples: &[f64], channels: usize) -> f64 {
+    let frames = samples.len() / channels;
+
+    // Precompute the interpolation kernel for each fractional phase
+    let kernels: Vec<Vec<f64>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let fraction = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+            (-TRUE_PEAK_TAPS + 1..=TRUE_PEAK_TAPS)
+                .map(|tap| {
+                    let t = tap as f64 - fraction;
+                    let window = 0.5 + 0.5 * (PI * t / (TRUE_PEAK_TAPS as f64 + 1.0)).cos();
+                    sinc(t) * window
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peak = 0.0f64;
+
+    for channel in 0..channels {
+        let sample_at = |frame: isize| -> f64 {
+            if frame < 0 || frame as usize >= frames {
+                0.0
+            } else {
+                samples[frame as usize * channels + channel]
+            }
+        };
+
+        for frame in 0..frames {
+            peak = peak.max(samples[frame * channels + channel].abs());
+
+            // Interpolated points between this frame and the next
+            for kernel in &kernels {
+                let mut value = 0.0;
+                for (i, coefficient) in kernel.iter().enumerate() {
+                    let offset = i as isize - TRUE_PEAK_TAPS + 1;
+                    value += coefficient * sample_at(frame as isize + offset);
+                }
+                peak = peak.max(value.abs());
+            }
+        }
+    }
+
+    peak
+}
+
+/// Normalized sinc function
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo_sine(frequency: f64, amplitude: f64, seconds: f64, sample_rate: u32) -> Vec<f64> {
+        let frames = (seconds * sample_rate as f64) as usize;
+        let mut samples = Vec::with_capacity(frames * 2);
+        for i in 0..frames {
+            let value = amplitude * (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin();
+            samples.push(value);
+            samples.push(value);
+        }
+        samples
+    }
+
+    #[test]
+    fn test_reference_tone_minus_23_lufs() {
+        // EBU Tech 3341 case 1: stereo 1kHz sine at -23 dBFS reads -23 LUFS
+        let amplitude = 10f64.powf(-23.0 / 20.0);
+        let samples = stereo_sine(1000.0, amplitude, 10.0, 48000);
+
+        let result = measure_lufs(&samples, 48000, 2);
+
+        assert!(
+            (result.integrated_lufs - -23.0).abs() < 0.1,
+            "Expected ~-23 LUFS, got {}",
+            result.integrated_lufs
+        );
+    }
+
+    #[test]
+    fn test_reference_tone_at_44100() {
+        let amplitude = 10f64.powf(-23.0 / 20.0);
+        let samples = stereo_sine(1000.0, amplitude, 5.0, 44100);
+
+        let result = measure_lufs(&samples, 44100, 2);
+
+        assert!((result.integrated_lufs - -23.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_relative_gate_ignores_quiet_section() {
+        // 5s at -23 dBFS followed by 5s at -53 dBFS (30 dB quieter):
+        // the quiet part falls below the relative gate.
+        let loud = stereo_sine(1000.0, 10f64.powf(-23.0 / 20.0), 5.0, 48000);
+        let quiet = stereo_sine(1000.0, 10f64.powf(-53.0 / 20.0), 5.0, 48000);
+        let samples: Vec<f64> = loud.into_iter().chain(quiet).collect();
+
+        let result = measure_lufs(&samples, 48000, 2);
+
+        assert!((result.integrated_lufs - -23.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_silence_is_gated() {
+        let samples = vec![0.0; 48000 * 2 * 2];
+        let result = measure_lufs(&samples, 48000, 2);
+
+        assert_eq!(result.integrated_lufs, f64::NEG_INFINITY);
+        assert_eq!(result.true_peak, 0.0);
+        assert_eq!(result.gain_to_target(-23.0), 0.0);
+    }
+
+    #[test]
+    fn test_short_input() {
+        let samples = stereo_sine(1000.0, 0.5, 0.2, 48000);
+        let result = measure_lufs(&samples, 48000, 2);
+
+        assert_eq!(result.integrated_lufs, f64::NEG_INFINITY);
+        assert!(result.true_peak > 0.0);
+    }
+
+    #[test]
+    fn test_true_peak_detects_inter_sample_peak() {
+        // A sine at fs/4 with 45 degree phase never hits its peak on a sample
+        let sample_rate = 48000;
+        let frames = 4800;
+        let samples: Vec<f64> = (0..frames)
+            .map(|i| (2.0 * PI * 0.25 * i as f64 + PI / 4.0).sin())
+            .collect();
+        let sample_peak = samples.iter().fold(0.0f64, |a, &b| a.max(b.abs()));
+
+        let result = measure_lufs(&samples, sample_rate, 1);
+
+        assert!(sample_peak < 0.71);
+        assert!(
+            result.true_peak > 0.95,
+            "Expected true peak near 1.0, got {}",
+            result.true_peak
+        );
+        assert!(result.true_peak_dbtp() > -0.5);
+    }
+
+    #[test]
+    fn test_gain_to_target() {
+        let result = LoudnessResult {
+            integrated_lufs: -18.0,
+            true_peak: 0.5,
+        };
+
+        assert_eq!(result.gain_to_target(-23.0), -5.0);
+    }
+
+    #[test]
+    fn test_frequency_weighting_curves() {
+        let response_db = |weighting: FrequencyWeighting, frequency: f64| -> f64 {
+            let gain: f64 = frequency_weighting_filters(weighting, 48000)
+                .iter()
+                .map(|c| c.magnitude_at(frequency, 48000.0))
+                .product();
+            20.0 * gain.log10()
+        };
+
+        // IEC 61672 reference values
+        let a = FrequencyWeighting::AWeighting;
+        assert!(response_db(a, 1000.0).abs() < 1e-9);
+        assert!((response_db(a, 100.0) + 19.1).abs() < 0.2);
+        assert!((response_db(a, 31.5) + 39.4).abs() < 0.3);
+        assert!((response_db(a, 4000.0) - 1.0).abs() < 0.2);
+        let c = FrequencyWeighting::CWeighting;
+        assert!((response_db(c, 100.0) + 0.3).abs() < 0.1);
+        assert!((response_db(c, 31.5) + 3.0).abs() < 0.2);
+        assert!(frequency_weighting_filters(FrequencyWeighting::None, 48000).is_empty());
+    }
+}
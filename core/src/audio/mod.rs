@@ -5,19 +5,34 @@
 pub mod buffer;
 pub mod checksum;
 pub mod decoder;
+pub mod device_monitor;
+pub mod dsd;
 pub mod engine;
+pub mod equalizer;
+pub mod filter;
 pub mod format;
+pub mod loudness;
 pub mod output;
+pub mod prefetch;
 pub mod processor;
 pub mod ring_buffer;
 
 pub use buffer::AudioBuffer;
-pub use decoder::{AudioDecoder, AudioFormatInfo, AudioStreamReaderWithRingBuffer, DecodedPacket};
+pub use decoder::{
+    read_chapters, read_tags, AudioDecoder, AudioFormatInfo, AudioStreamReaderWithRingBuffer,
+    AudioTrackInfo, Chapter, DecodedPacket, DurationAccuracy, Packets, TrackTags,
+};
 pub use engine::{
-    AudioCallback, AudioDeviceInfo, AudioEngine, AudioEngineInterface, AudioEvent, PlaybackState,
+    AudioCallback, AudioDeviceInfo, AudioEngine, AudioEngineInterface, AudioEvent,
+    LoadProgressCallback, MeterCallback, MeterLevels, PlaybackMode, PlaybackSnapshot,
+    PlaybackState, PlaybackStatus, PlayerSnapshot, ReplayGainSource, StreamConfigInfo,
 };
+pub use equalizer::{EqPreset, Equalizer};
 pub use format::{AudioFormat, Channel, ChannelLayout, FormatError, SampleFormat};
-pub use ring_buffer::{AudioRingBuffer, RingBufferConfig, RingBufferConsumer, RingBufferProducer};
+pub use loudness::FrequencyWeighting;
+pub use ring_buffer::{
+    AudioRingBuffer, ReadSlices, RingBufferConfig, RingBufferConsumer, RingBufferProducer,
+};
 
 #[cfg(test)]
 mod tests {
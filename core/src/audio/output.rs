@@ -2,4 +2,1396 @@
 //!
 //! Handles audio device management and output streaming
 
-// Will be implemented in Phase 1 & 2
+use crate::audio::decoder::AudioDecoder;
+use crate::audio::format::{AudioFormat, SampleFormat};
+use crate::audio::processor::{
+    Ditherer, DitheringAlgorithm, ResampleQuality, SampleFormatConverter, SampleRateConverter,
+};
+use crate::audio::ring_buffer::RingBufferConsumer;
+use crate::{Error, Result};
+use cpal::traits::DeviceTrait;
+use cpal::Device;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A range of output configurations supported by a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputConfigRange {
+    /// Number of channels
+    pub channels: u16,
+    /// Lowest supported sample rate in Hz
+    pub min_sample_rate: u32,
+    /// Highest supported sample rate in Hz
+    pub max_sample_rate: u32,
+    /// Native sample format
+    pub sample_format: SampleFormat,
+}
+
+impl OutputConfigRange {
+    /// Check if the range covers a sample rate and channel count
+    pub fn supports(&self, sample_rate: u32, channels: u16) -> bool {
+        self.channels == channels
+            && self.min_sample_rate <= sample_rate
+            && sample_rate <= self.max_sample_rate
+    }
+}
+
+/// Audio output backend
+///
+/// Abstracts the device capabilities the engine negotiates against, so format
+/// selection can be exercised without audio hardware.
+pub trait OutputBackend {
+    /// Get the output device name
+    fn name(&self) -> Option<String>;
+
+    /// Get the configurations the device supports natively
+    fn supported_configs(&self) -> Result<Vec<OutputConfigRange>>;
+
+    /// Get the device's default configuration, if it reports one
+    fn default_config(&self) -> Result<Option<OutputConfigRange>>;
+}
+
+/// Output backend for a CPAL device
+pub struct CpalBackend {
+    device: Device,
+}
+
+impl CpalBackend {
+    /// Wrap a CPAL device
+    pub fn new(device: Device) -> Self {
+        Self { device }
+    }
+}
+
+impl OutputBackend for CpalBackend {
+    fn name(&self) -> Option<String> {
+        self.device.description().map(|desc| desc.to_string()).ok()
+    }
+
+    fn supported_configs(&self) -> Result<Vec<OutputConfigRange>> {
+        cpal_output_configs(&self.device)
+    }
+
+    fn default_config(&self) -> Result<Option<OutputConfigRange>> {
+        cpal_default_config(&self.device)
+    }
+}
+
+/// Output backend that reports a fixed set of configurations
+///
+/// Used for tests and headless operation.
+#[derive(Debug, Clone)]
+pub struct NullBackend {
+    name: String,
+    configs: Vec<OutputConfigRange>,
+    default_config: Option<OutputConfigRange>,
+}
+
+impl NullBackend {
+    /// Create a backend reporting the given configurations
+    ///
+    /// The first configuration doubles as the default one.
+    pub fn new(configs: Vec<OutputConfigRange>) -> Self {
+        Self {
+            name: "Null Output".to_string(),
+            default_config: configs.first().cloned(),
+            configs,
+        }
+    }
+
+    /// Report `config` as the device's default configuration
+    pub fn with_default_config(mut self, config: OutputConfigRange) -> Self {
+        self.default_config = Some(config);
+        self
+    }
+}
+
+impl Default for NullBackend {
+    /// Stereo f32 output at any common sample rate
+    fn default() -> Self {
+        Self::new(vec![OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 8000,
+            max_sample_rate: 192000,
+            sample_format: SampleFormat::F32,
+        }])
+    }
+}
+
+impl OutputBackend for NullBackend {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn supported_configs(&self) -> Result<Vec<OutputConfigRange>> {
+        Ok(self.configs.clone())
+    }
+
+    fn default_config(&self) -> Result<Option<OutputConfigRange>> {
+        Ok(self.default_config.clone())
+    }
+}
+
+/// Output configurations to negotiate against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiableConfigs {
+    /// Configurations offered by the device
+    pub configs: Vec<OutputConfigRange>,
+    /// Whether enumeration failed and only the default configuration is offered
+    pub from_default: bool,
+}
+
+/// Get the configurations a backend can be negotiated against
+///
+/// Some minimal ALSA setups enumerate no configurations at all (or fail to);
+/// the device's default configuration is then offered on its own so playback
+/// still works, resampling the source as needed.
+pub fn negotiable_configs<B: OutputBackend + ?Sized>(backend: &B) -> Result<NegotiableConfigs> {
+    let enumerated = backend.supported_configs();
+    if let Ok(configs) = &enumerated {
+        if !configs.is_empty() {
+            return Ok(NegotiableConfigs {
+                configs: configs.clone(),
+                from_default: false,
+            });
+        }
+    }
+
+    match backend.default_config() {
+        Ok(Some(config)) => Ok(NegotiableConfigs {
+            configs: vec![config],
+            from_default: true,
+        }),
+        _ => {
+            enumerated?;
+            Err(Error::FormatNegotiation(
+                "Device reports no output configurations".to_string(),
+            ))
+        }
+    }
+}
+
+/// Query the supported output configurations of a CPAL device
+///
+/// Configurations using sample types the engine can't produce are skipped.
+pub fn cpal_output_configs(device: &Device) -> Result<Vec<OutputConfigRange>> {
+    let configs = device.supported_output_configs().map_err(|e| {
+        crate::Error::AudioDevice(format!("Failed to get supported configs: {}", e))
+    })?;
+
+    Ok(configs
+        .filter_map(|config| {
+            Some(OutputConfigRange {
+                channels: config.channels(),
+                min_sample_rate: config.min_sample_rate(),
+                max_sample_rate: config.max_sample_rate(),
+                sample_format: sample_format_from_cpal(config.sample_format())?,
+            })
+        })
+        .collect())
+}
+
+/// Query the default output configuration of a CPAL device
+///
+/// # Returns
+/// `None` if the default uses a sample type the engine can't produce
+pub fn cpal_default_config(device: &Device) -> Result<Option<OutputConfigRange>> {
+    let config = device
+        .default_output_config()
+        .map_err(|e| crate::Error::AudioDevice(format!("Failed to get default config: {}", e)))?;
+
+    Ok(
+        sample_format_from_cpal(config.sample_format()).map(|sample_format| OutputConfigRange {
+            channels: config.channels(),
+            min_sample_rate: config.sample_rate(),
+            max_sample_rate: config.sample_rate(),
+            sample_format,
+        }),
+    )
+}
+
+/// Map a CPAL sample format to the engine's sample format
+pub fn sample_format_from_cpal(format: cpal::SampleFormat) -> Option<SampleFormat> {
+    match format {
+        cpal::SampleFormat::U8 => Some(SampleFormat::U8),
+        cpal::SampleFormat::I8 => Some(SampleFormat::I8),
+        cpal::SampleFormat::U16 => Some(SampleFormat::U16),
+        cpal::SampleFormat::I16 => Some(SampleFormat::I16),
+        cpal::SampleFormat::I24 => Some(SampleFormat::I24),
+        cpal::SampleFormat::I32 => Some(SampleFormat::I32),
+        cpal::SampleFormat::F32 => Some(SampleFormat::F32),
+        cpal::SampleFormat::F64 => Some(SampleFormat::F64),
+        _ => None,
+    }
+}
+
+/// Pick the output format for a source from the device's native configurations
+///
+/// The source sample rate and channel count are kept. Among the sample
+/// formats available at that rate, the source's own format is preferred
+/// (bit-perfect), then the narrowest format that still represents every
+/// source value exactly, then the widest lossy format.
+///
+/// # Returns
+/// `None` if no configuration supports the source rate and channel count
+pub fn select_output_format(
+    configs: &[OutputConfigRange],
+    source: &AudioFormat,
+) -> Option<AudioFormat> {
+    configs
+        .iter()
+        .filter(|config| config.supports(source.sample_rate, source.channels))
+        .max_by_key(|config| sample_format_rank(source.sample_format, config.sample_format))
+        .map(|config| AudioFormat::new(source.sample_rate, source.channels, config.sample_format))
+}
+
+/// Pick a format at another sample rate for a source the device can't play natively
+///
+/// Used when `select_output_format` finds nothing, so the engine resamples.
+/// The lowest supported standard rate at or above the source rate is
+/// preferred; otherwise the highest rate the device offers.
+pub fn select_resampled_format(
+    configs: &[OutputConfigRange],
+    source: &AudioFormat,
+) -> Option<AudioFormat> {
+    const STANDARD_RATES: [u32; 8] = [44100, 48000, 88200, 96000, 176400, 192000, 352800, 384000];
+
+    configs
+        .iter()
+        .filter(|config| config.channels == source.channels)
+        .map(|config| {
+            let rate = STANDARD_RATES
+                .iter()
+                .copied()
+                .filter(|&rate| rate >= source.sample_rate)
+                .find(|&rate| config.supports(rate, source.channels))
+                .unwrap_or(config.max_sample_rate);
+            (rate, config)
+        })
+        .max_by_key(|&(rate, config)| {
+            // Upsampling beats downsampling, then the closest rate, then the format
+            let keeps_bandwidth = rate >= source.sample_rate;
+            let distance = u32::MAX - rate.abs_diff(source.sample_rate);
+            (
+                keeps_bandwidth,
+                distance,
+                sample_format_rank(source.sample_format, config.sample_format),
+            )
+        })
+        .map(|(rate, config)| AudioFormat::new(rate, source.channels, config.sample_format))
+}
+
+/// How a source may be mapped to a device with a different channel count
+///
+/// Only conversions `remix_channels` can perform are ever negotiated:
+/// mono to any layout and any layout to mono.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelMatchPolicy {
+    /// The device must offer the source channel count
+    #[default]
+    Exact,
+    /// Allow more device channels than the source has
+    Upmix,
+    /// Allow fewer device channels than the source has
+    Downmix,
+    /// Allow either, preferring the closest channel count
+    BestEffort,
+}
+
+impl ChannelMatchPolicy {
+    /// Check if a `source`-channel stream may play on a `device`-channel output
+    pub fn allows(self, source: u16, device: u16) -> bool {
+        if source == device {
+            return true;
+        }
+        if source == 0 || device == 0 || (source != 1 && device != 1) {
+            return false;
+        }
+        match self {
+            ChannelMatchPolicy::Exact => false,
+            ChannelMatchPolicy::Upmix => device > source,
+            ChannelMatchPolicy::Downmix => device < source,
+            ChannelMatchPolicy::BestEffort => true,
+        }
+    }
+}
+
+/// Pick an output format, remixing channels if the policy allows it
+///
+/// The source channel count is kept whenever the device offers it, at the
+/// source rate (`select_output_format`) or resampled
+/// (`select_resampled_format`). Otherwise the channel counts allowed by
+/// `policy` are tried closest first, upmixing on a tie.
+pub fn select_channel_matched_format(
+    configs: &[OutputConfigRange],
+    source: &AudioFormat,
+    policy: ChannelMatchPolicy,
+) -> Option<AudioFormat> {
+    let select = |format: &AudioFormat| {
+        select_output_format(configs, format).or_else(|| select_resampled_format(configs, format))
+    };
+    if let Some(format) = select(source) {
+        return Some(format);
+    }
+
+    let mut channel_counts: Vec<u16> = configs
+        .iter()
+        .map(|config| config.channels)
+        .filter(|&channels| channels != source.channels)
+        .filter(|&channels| policy.allows(source.channels, channels))
+        .collect();
+    channel_counts.sort_by_key(|&channels| {
+        (
+            channels.abs_diff(source.channels),
+            channels < source.channels,
+        )
+    });
+    channel_counts.dedup();
+
+    channel_counts.into_iter().find_map(|channels| {
+        select(&AudioFormat::new(
+            source.sample_rate,
+            channels,
+            source.sample_format,
+        ))
+    })
+}
+
+/// Find a configuration that plays the source without any conversion
+///
+/// The sample rate and channel count must be supported natively and the
+/// sample format must either match the source or carry it losslessly in a
+/// wider integer container (e.g. 24-bit in 32-bit).
+pub fn find_bit_perfect_format(
+    configs: &[OutputConfigRange],
+    source: &AudioFormat,
+) -> Option<AudioFormat> {
+    let transparent = |format: SampleFormat| {
+        format == source.sample_format
+            || (format.is_integer()
+                && source.sample_format.is_integer()
+                && is_lossless_conversion(source.sample_format, format))
+    };
+
+    configs
+        .iter()
+        .filter(|config| config.supports(source.sample_rate, source.channels))
+        .filter(|config| transparent(config.sample_format))
+        .max_by_key(|config| sample_format_rank(source.sample_format, config.sample_format))
+        .map(|config| AudioFormat::new(source.sample_rate, source.channels, config.sample_format))
+}
+
+/// Rank an output sample format for a source format (higher is better)
+fn sample_format_rank(source: SampleFormat, candidate: SampleFormat) -> u32 {
+    let bits = sample_format_bits(candidate);
+    if candidate == source {
+        1000
+    } else if is_lossless_conversion(source, candidate) {
+        500 - bits
+    } else {
+        // Lossy either way: keep the widest, staying float for float sources
+        bits * 2 + u32::from(candidate.is_float() == source.is_float())
+    }
+}
+
+/// Get the integer PCM sample format for a lossless source bit depth
+pub fn pcm_sample_format(bit_depth: u32) -> Option<SampleFormat> {
+    match bit_depth {
+        8 => Some(SampleFormat::U8),
+        16 => Some(SampleFormat::I16),
+        24 => Some(SampleFormat::I24),
+        32 => Some(SampleFormat::I32),
+        _ => None,
+    }
+}
+
+/// Significant bits of a sample format
+pub fn sample_format_bits(format: SampleFormat) -> u32 {
+    format.size_bytes() as u32 * 8
+}
+
+/// Precision of a sample format in bits (mantissa bits for floats)
+fn sample_format_precision(format: SampleFormat) -> u32 {
+    match format {
+        SampleFormat::F32 => 24,
+        SampleFormat::F64 => 53,
+        other => sample_format_bits(other),
+    }
+}
+
+/// Check if every value of `source` can be represented exactly in `target`
+pub fn is_lossless_conversion(source: SampleFormat, target: SampleFormat) -> bool {
+    match (source.is_float(), target.is_float()) {
+        (true, false) => false,
+        (true, true) => sample_format_bits(target) >= sample_format_bits(source),
+        (false, _) => sample_format_precision(target) >= sample_format_precision(source),
+    }
+}
+
+/// Sample types an output stream can be built with
+pub trait OutputSample: cpal::SizedSample + Send + 'static {
+    /// Write f64 samples into a device buffer, dithering if a ditherer is given
+    fn write_samples(samples: &[f64], output: &mut [Self], ditherer: Option<&mut Ditherer>);
+}
+
+macro_rules! impl_output_sample {
+    ($ty:ty, $convert:ident, $convert_dithered:ident) => {
+        impl OutputSample for $ty {
+            fn write_samples(
+                samples: &[f64],
+                output: &mut [Self],
+                ditherer: Option<&mut Ditherer>,
+            ) {
+                let converted = match ditherer {
+                    Some(ditherer) => SampleFormatConverter::$convert_dithered(samples, ditherer),
+                    None => SampleFormatConverter::$convert(samples),
+                };
+                output.copy_from_slice(&converted);
+            }
+        }
+    };
+}
+
+impl_output_sample!(u8, f64_to_u8, f64_to_u8_dithered);
+impl_output_sample!(i8, f64_to_i8, f64_to_i8_dithered);
+impl_output_sample!(u16, f64_to_u16, f64_to_u16_dithered);
+impl_output_sample!(i16, f64_to_i16, f64_to_i16_dithered);
+impl_output_sample!(i32, f64_to_i32, f64_to_i32_dithered);
+
+impl OutputSample for cpal::I24 {
+    fn write_samples(samples: &[f64], output: &mut [Self], ditherer: Option<&mut Ditherer>) {
+        let converted = match ditherer {
+            Some(ditherer) => SampleFormatConverter::f64_to_i24_dithered(samples, ditherer),
+            None => SampleFormatConverter::f64_to_i24(samples),
+        };
+        for (out, value) in output.iter_mut().zip(converted) {
+            *out = cpal::I24::new_unchecked(value);
+        }
+    }
+}
+
+impl OutputSample for f32 {
+    fn write_samples(samples: &[f64], output: &mut [Self], _ditherer: Option<&mut Ditherer>) {
+        output.copy_from_slice(&SampleFormatConverter::f64_to_f32(samples));
+    }
+}
+
+impl OutputSample for f64 {
+    fn write_samples(samples: &[f64], output: &mut [Self], _ditherer: Option<&mut Ditherer>) {
+        output.copy_from_slice(samples);
+    }
+}
+
+/// Frames over which mixer sources fade in and out
+pub const DEFAULT_MIX_RAMP_FRAMES: usize = 512;
+
+/// A stream of interleaved f64 samples that can feed a `Mixer`
+pub trait MixerSource: Send + Sync {
+    /// Fill `output` with samples, returning how many were written
+    fn read_samples(&mut self, output: &mut [f64]) -> usize;
+}
+
+impl MixerSource for RingBufferConsumer {
+    fn read_samples(&mut self, output: &mut [f64]) -> usize {
+        self.read(output)
+    }
+}
+
+/// Identifies a source added to a `Mixer`
+pub type MixerSourceId = u64;
+
+/// A mixer input with its gain state
+struct MixerInput {
+    id: MixerSourceId,
+    source: Box<dyn MixerSource>,
+    gain: f64,
+    target_gain: f64,
+    removing: bool,
+}
+
+/// Sums several sources into one output, each with its own gain
+///
+/// Gain changes, additions and removals are ramped over `ramp_frames` so
+/// sources can come and go mid-stream without clicks. All sources must share
+/// the output's channel layout.
+pub struct Mixer {
+    inputs: Vec<MixerInput>,
+    ramp_frames: usize,
+    next_id: MixerSourceId,
+    /// Per-source read buffer, reused across blocks
+    pub(crate) scratch: Vec<f64>,
+}
+
+impl Mixer {
+    /// Create an empty mixer
+    ///
+    /// # Arguments
+    /// * `ramp_frames` - Length of gain ramps in frames (0 = instant)
+    pub fn new(ramp_frames: usize) -> Self {
+        Self {
+            inputs: Vec::new(),
+            ramp_frames,
+            next_id: 1,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Add a source, fading it in to `gain`
+    pub fn add_source(&mut self, source: Box<dyn MixerSource>, gain: f64) -> MixerSourceId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.inputs.push(MixerInput {
+            id,
+            source,
+            gain: if self.ramp_frames == 0 { gain } else { 0.0 },
+            target_gain: gain,
+            removing: false,
+        });
+        id
+    }
+
+    /// Add a ring buffer consumer as a source
+    pub fn add_ring_buffer(&mut self, consumer: RingBufferConsumer, gain: f64) -> MixerSourceId {
+        self.add_source(Box::new(consumer), gain)
+    }
+
+    /// Change the gain of a source (ramped)
+    pub fn set_gain(&mut self, id: MixerSourceId, gain: f64) -> Result<()> {
+        let input = self.input_mut(id)?;
+        input.target_gain = gain;
+        Ok(())
+    }
+
+    /// Get the target gain of a source
+    pub fn gain(&self, id: MixerSourceId) -> Option<f64> {
+        self.inputs
+            .iter()
+            .find(|input| input.id == id && !input.removing)
+            .map(|input| input.target_gain)
+    }
+
+    /// Fade a source out and drop it once silent
+    pub fn remove_source(&mut self, id: MixerSourceId) -> Result<()> {
+        let ramp_frames = self.ramp_frames;
+        let input = self.input_mut(id)?;
+        input.target_gain = 0.0;
+        input.removing = true;
+        if ramp_frames == 0 {
+            self.inputs.retain(|input| input.id != id);
+        }
+        Ok(())
+    }
+
+    /// Number of sources (including ones still fading out)
+    pub fn source_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Check if the mixer has no sources
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Size the scratch buffer for blocks of up to `samples` samples, so
+    /// mixing them doesn't allocate
+    pub fn reserve(&mut self, samples: usize) {
+        if self.scratch.len() < samples {
+            self.scratch.resize(samples, 0.0);
+        }
+    }
+
+    /// Add all sources into `output`, clamping the sum to [-1.0, 1.0]
+    ///
+    /// Whatever `output` already holds (e.g. the primary playback source)
+    /// is kept and mixed with the sources.
+    pub fn mix_into(&mut self, output: &mut [f64], channels: u16) {
+        let channels = channels.max(1) as usize;
+        if self.scratch.len() < output.len() {
+            self.scratch.resize(output.len(), 0.0);
+        }
+        let step = if self.ramp_frames == 0 {
+            f64::INFINITY
+        } else {
+            1.0 / self.ramp_frames as f64
+        };
+
+        for input in &mut self.inputs {
+            let scratch = &mut self.scratch[..output.len()];
+            let read = input.source.read_samples(scratch);
+            scratch[read..].fill(0.0);
+
+            for (out_frame, in_frame) in output.chunks_mut(channels).zip(scratch.chunks(channels)) {
+                let delta = input.target_gain - input.gain;
+                input.gain += delta.clamp(-step, step);
+                for (out, sample) in out_frame.iter_mut().zip(in_frame) {
+                    *out += sample * input.gain;
+                }
+            }
+        }
+
+        self.inputs
+            .retain(|input| !(input.removing && input.gain == 0.0));
+
+        for sample in output.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+
+    fn input_mut(&mut self, id: MixerSourceId) -> Result<&mut MixerInput> {
+        self.inputs
+            .iter_mut()
+            .find(|input| input.id == id && !input.removing)
+            .ok_or_else(|| Error::InvalidParameter(format!("No mixer source {}", id)))
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIX_RAMP_FRAMES)
+    }
+}
+
+/// Size of the RIFF/WAVE header written by `FileOutput` for PCM formats
+const WAV_HEADER_BYTES: u64 = 44;
+
+/// Extra header bytes of float formats: `cbSize` and a `fact` chunk
+const WAV_FLOAT_HEADER_EXTRA: u64 = 2 + 12;
+
+/// `wFormatTag` of IEEE float samples
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// WAV file sink written incrementally from f64 samples
+///
+/// The header's size fields are filled in by `finalize`. Dropping an
+/// unfinalized output finalizes it too, so a sink abandoned early still
+/// leaves a valid WAV covering the frames written so far.
+///
+/// Float formats are tagged `WAVE_FORMAT_IEEE_FLOAT` and carry the
+/// `cbSize` field and `fact` chunk that non-PCM WAV files require.
+pub struct FileOutput {
+    writer: BufWriter<File>,
+    format: AudioFormat,
+    /// Header length, i.e. the offset of the sample data
+    header_bytes: u64,
+    ditherer: Option<Ditherer>,
+    frames_written: u64,
+    finalized: bool,
+}
+
+impl FileOutput {
+    /// Create a WAV file for interleaved audio in `format`
+    ///
+    /// Integer formats are dithered with `dither` when written; U8, I16, I24,
+    /// I32, F32 and F64 are supported.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: AudioFormat,
+        dither: DitheringAlgorithm,
+    ) -> Result<Self> {
+        crate::audio::format::validate_format(&format)
+            .map_err(|e| Error::AudioFormat(format!("Invalid format: {}", e)))?;
+        let format_tag = wav_format_tag(format.sample_format).ok_or_else(|| {
+            Error::NotSupported(format!(
+                "WAV output does not support {:?}",
+                format.sample_format
+            ))
+        })?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        let bits = format.sample_format.size_bytes() as u16 * 8;
+        let block_align = format.channels * bits / 8;
+        let byte_rate = format.sample_rate * block_align as u32;
+        let float = format_tag == WAVE_FORMAT_IEEE_FLOAT;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&(if float { 18u32 } else { 16 }).to_le_bytes())?;
+        writer.write_all(&format_tag.to_le_bytes())?;
+        writer.write_all(&format.channels.to_le_bytes())?;
+        writer.write_all(&format.sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&bits.to_le_bytes())?;
+        if float {
+            // cbSize, then the frame count in a fact chunk
+            writer.write_all(&0u16.to_le_bytes())?;
+            writer.write_all(b"fact")?;
+            writer.write_all(&4u32.to_le_bytes())?;
+            writer.write_all(&0u32.to_le_bytes())?;
+        }
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        let header_bytes = WAV_HEADER_BYTES + if float { WAV_FLOAT_HEADER_EXTRA } else { 0 };
+
+        let ditherer = (format.sample_format.is_integer() && dither != DitheringAlgorithm::None)
+            .then(|| Ditherer::new(dither));
+
+        Ok(Self {
+            writer,
+            format,
+            header_bytes,
+            ditherer,
+            frames_written: 0,
+            finalized: false,
+        })
+    }
+
+    /// Get the file's audio format
+    pub fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    /// Get the number of frames written so far
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Append interleaved samples (a whole number of frames)
+    pub fn write(&mut self, samples: &[f64]) -> Result<()> {
+        let channels = self.format.channels as usize;
+        if !samples.len().is_multiple_of(channels) {
+            return Err(Error::InvalidParameter(format!(
+                "{} samples is not a whole number of {}-channel frames",
+                samples.len(),
+                channels
+            )));
+        }
+
+        let frames = (samples.len() / channels) as u64;
+        let data_bytes = (self.frames_written + frames)
+            * channels as u64
+            * self.format.sample_format.size_bytes() as u64;
+        if data_bytes + self.header_bytes - 8 > u32::MAX as u64 {
+            return Err(Error::NotSupported(
+                "WAV output is limited to 4 GiB".to_string(),
+            ));
+        }
+
+        let bytes = match self.ditherer.as_mut() {
+            Some(ditherer) => SampleFormatConverter::convert_from_f64_dithered(
+                samples,
+                self.format.sample_format,
+                ditherer,
+            ),
+            None => SampleFormatConverter::convert_from_f64(samples, self.format.sample_format),
+        };
+        self.writer.write_all(&bytes)?;
+        self.frames_written += frames;
+        Ok(())
+    }
+
+    /// Flush buffered samples, write the header's size fields and close the
+    /// file
+    ///
+    /// Blocks until the data has been synced to disk.
+    pub fn finalize(mut self) -> Result<()> {
+        self.finalized = true;
+        self.write_sizes()
+    }
+
+    /// Patch the RIFF and `data` chunk sizes for the frames written
+    fn write_sizes(&mut self) -> Result<()> {
+        let data_bytes = self.frames_written
+            * self.format.channels as u64
+            * self.format.sample_format.size_bytes() as u64;
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&((data_bytes + self.header_bytes - 8) as u32).to_le_bytes())?;
+        if self.header_bytes > WAV_HEADER_BYTES {
+            // fact chunk's sample length, right after the 18-byte fmt chunk
+            self.writer.seek(SeekFrom::Start(46))?;
+            self.writer
+                .write_all(&(self.frames_written as u32).to_le_bytes())?;
+        }
+        self.writer.seek(SeekFrom::Start(self.header_bytes - 4))?;
+        self.writer.write_all(&(data_bytes as u32).to_le_bytes())?;
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        Ok(())
+    }
+}
+
+impl Drop for FileOutput {
+    fn drop(&mut self) {
+        if !self.finalized {
+            if let Err(e) = self.write_sizes() {
+                eprintln!("Warning: Failed to finalize WAV output: {}", e);
+            }
+        }
+    }
+}
+
+/// WAV `wFormatTag` for a sample format, if it can be stored
+fn wav_format_tag(format: SampleFormat) -> Option<u16> {
+    match format {
+        // WAV stores 8-bit PCM unsigned and wider PCM signed
+        SampleFormat::U8 | SampleFormat::I16 | SampleFormat::I24 | SampleFormat::I32 => Some(1),
+        SampleFormat::F32 | SampleFormat::F64 => Some(WAVE_FORMAT_IEEE_FLOAT),
+        SampleFormat::I8 | SampleFormat::U16 => None,
+    }
+}
+
+/// Map interleaved audio between channel counts
+///
+/// Equal counts are copied, mono is duplicated to every output channel and
+/// any layout is averaged down to mono; other conversions are unsupported.
+pub fn remix_channels(samples: &[f64], from: u16, to: u16, output: &mut Vec<f64>) -> Result<()> {
+    let (from, to) = (from as usize, to as usize);
+    output.clear();
+    if from == to {
+        output.extend_from_slice(samples);
+    } else if from == 1 {
+        output.extend(samples.iter().flat_map(|&s| std::iter::repeat_n(s, to)));
+    } else if to == 1 {
+        output.extend(
+            samples
+                .chunks_exact(from)
+                .map(|frame| frame.iter().sum::<f64>() / from as f64),
+        );
+    } else {
+        return Err(Error::NotSupported(format!(
+            "Cannot remix {} channels to {}",
+            from, to
+        )));
+    }
+    Ok(())
+}
+
+/// Decode `src` and write it to `dst` as a WAV file in `target` format
+///
+/// Streams packet by packet, resampling with the sinc `SampleRateConverter`
+/// and remixing channels with `remix_channels` as needed. The output has
+/// exactly `ceil(source frames * target rate / source rate)` frames.
+///
+/// # Returns
+/// The number of frames written
+pub fn transcode_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    target: AudioFormat,
+    dither: DitheringAlgorithm,
+) -> Result<u64> {
+    transcode_file_with_progress(src, dst, target, dither, |_| {})
+}
+
+/// Like `transcode_file`, reporting progress (0.0 to 1.0) after each packet
+///
+/// Progress is the share of the source decoded so far; it is only reported
+/// when the source duration is known, and ends with 1.0 once the file is
+/// complete.
+pub fn transcode_file_with_progress<P: AsRef<Path>, Q: AsRef<Path>, F: FnMut(f64)>(
+    src: P,
+    dst: Q,
+    target: AudioFormat,
+    dither: DitheringAlgorithm,
+    mut progress: F,
+) -> Result<u64> {
+    let mut decoder = AudioDecoder::new(src)?;
+    let source = decoder.format().clone();
+    let mut output = FileOutput::create(dst, target.clone(), dither)?;
+
+    let channels = target.channels as usize;
+    let mut converter = (source.sample_rate != target.sample_rate).then(|| {
+        SampleRateConverter::with_quality(
+            source.sample_rate,
+            target.sample_rate,
+            channels,
+            ResampleQuality::Sinc,
+        )
+    });
+    let mut remixed = Vec::new();
+    let mut resampled = vec![0.0; 4096 * channels];
+    let mut source_frames = 0u64;
+
+    // Write everything the converter can produce from the input so far
+    let mut drain = |converter: &mut SampleRateConverter, output: &mut FileOutput, limit: u64| loop {
+        let remaining = limit.saturating_sub(output.frames_written()) as usize;
+        let len = resampled.len().min(remaining.saturating_mul(channels));
+        let written = converter.read(&mut resampled[..len]);
+        output.write(&resampled[..written])?;
+        if written < resampled.len() || written == 0 {
+            return Ok::<_, Error>(());
+        }
+    };
+
+    let total_frames = decoder.duration().filter(|&frames| frames > 0);
+    while let Some(packet) = decoder.decode_next()? {
+        source_frames += packet.frames as u64;
+        if let Some(total) = total_frames {
+            progress((source_frames as f64 / total as f64).min(1.0));
+        }
+        remix_channels(
+            &packet.samples,
+            source.channels,
+            target.channels,
+            &mut remixed,
+        )?;
+        match converter.as_mut() {
+            Some(converter) => {
+                converter.push(remixed.iter().copied());
+                drain(converter, &mut output, u64::MAX)?;
+            }
+            None => output.write(&remixed)?,
+        }
+    }
+
+    if let Some(converter) = converter.as_mut() {
+        // Flush the kernel's tail with silence up to the exact length
+        let expected =
+            (source_frames * target.sample_rate as u64).div_ceil(source.sample_rate as u64);
+        let missing = expected.saturating_sub(output.frames_written()) as usize;
+        let padding = converter.input_frames_needed(missing);
+        converter.push(std::iter::repeat_n(0.0, padding * channels));
+        drain(converter, &mut output, expected)?;
+    }
+
+    let frames = output.frames_written();
+    output.finalize()?;
+    progress(1.0);
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(format: SampleFormat) -> OutputConfigRange {
+        OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 44100,
+            max_sample_rate: 96000,
+            sample_format: format,
+        }
+    }
+
+    #[test]
+    fn test_prefers_native_source_format() {
+        let backend = NullBackend::new(vec![
+            range(SampleFormat::F32),
+            range(SampleFormat::I16),
+            range(SampleFormat::I32),
+        ]);
+        let configs = backend.supported_configs().unwrap();
+
+        let source = AudioFormat::new(44100, 2, SampleFormat::I16);
+        let selected = select_output_format(&configs, &source).unwrap();
+        assert_eq!(selected, source);
+    }
+
+    #[test]
+    fn test_negotiable_configs_fall_back_to_default() {
+        let stereo = OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 8000,
+            max_sample_rate: 96000,
+            sample_format: SampleFormat::F32,
+        };
+        let fallback = OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 44100,
+            max_sample_rate: 44100,
+            sample_format: SampleFormat::I16,
+        };
+
+        let negotiable = negotiable_configs(
+            &NullBackend::new(vec![stereo.clone()]).with_default_config(fallback.clone()),
+        )
+        .unwrap();
+        assert_eq!(negotiable.configs, vec![stereo]);
+        assert!(!negotiable.from_default);
+
+        let negotiable =
+            negotiable_configs(&NullBackend::new(Vec::new()).with_default_config(fallback.clone()))
+                .unwrap();
+        assert_eq!(negotiable.configs, vec![fallback]);
+        assert!(negotiable.from_default);
+
+        assert!(negotiable_configs(&NullBackend::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_prefers_narrowest_lossless_format() {
+        let configs = NullBackend::new(vec![
+            range(SampleFormat::I16),
+            range(SampleFormat::I32),
+            range(SampleFormat::F32),
+        ])
+        .supported_configs()
+        .unwrap();
+
+        // 24-bit fits exactly in f32 and i32; f32 is narrower
+        let source = AudioFormat::new(96000, 2, SampleFormat::I24);
+        let selected = select_output_format(&configs, &source).unwrap();
+        assert_eq!(selected.sample_format, SampleFormat::F32);
+
+        // 32-bit integer only fits exactly in i32
+        let source = AudioFormat::new(96000, 2, SampleFormat::I32);
+        let selected = select_output_format(&configs, &source).unwrap();
+        assert_eq!(selected.sample_format, SampleFormat::I32);
+
+        // Float sources never pick an integer format when a float one exists
+        let source = AudioFormat::new(48000, 2, SampleFormat::F64);
+        let selected = select_output_format(&configs, &source).unwrap();
+        assert_eq!(selected.sample_format, SampleFormat::F32);
+
+        let reversed: Vec<_> = configs.iter().rev().cloned().collect();
+        let selected = select_output_format(&reversed, &source).unwrap();
+        assert_eq!(selected.sample_format, SampleFormat::F32);
+    }
+
+    #[test]
+    fn test_reduces_depth_only_when_necessary() {
+        let configs = vec![range(SampleFormat::I16)];
+
+        let source = AudioFormat::new(48000, 2, SampleFormat::I24);
+        let selected = select_output_format(&configs, &source).unwrap();
+        assert_eq!(selected.sample_format, SampleFormat::I16);
+        assert!(!is_lossless_conversion(
+            source.sample_format,
+            selected.sample_format
+        ));
+    }
+
+    #[test]
+    fn test_resampled_format_for_unsupported_rate() {
+        let fixed_48k = OutputConfigRange {
+            channels: 2,
+            min_sample_rate: 48000,
+            max_sample_rate: 48000,
+            sample_format: SampleFormat::F32,
+        };
+        let configs = NullBackend::new(vec![fixed_48k])
+            .supported_configs()
+            .unwrap();
+
+        let source = AudioFormat::new(44100, 2, SampleFormat::I16);
+        assert!(select_output_format(&configs, &source).is_none());
+        let selected = select_resampled_format(&configs, &source).unwrap();
+        assert_eq!(selected, AudioFormat::new(48000, 2, SampleFormat::F32));
+
+        // Prefer the closest rate above the source over the device maximum
+        let configs = vec![range(SampleFormat::F32)];
+        let source = AudioFormat::new(22050, 2, SampleFormat::I16);
+        assert_eq!(
+            select_resampled_format(&configs, &source)
+                .unwrap()
+                .sample_rate,
+            44100
+        );
+        let source = AudioFormat::new(192000, 2, SampleFormat::I24);
+        assert_eq!(
+            select_resampled_format(&configs, &source)
+                .unwrap()
+                .sample_rate,
+            96000
+        );
+
+        // The channel count is never changed
+        let source = AudioFormat::new(44100, 6, SampleFormat::I16);
+        assert!(select_resampled_format(&configs, &source).is_none());
+    }
+
+    #[test]
+    fn test_no_config_for_rate_or_channels() {
+        let configs = NullBackend::default().supported_configs().unwrap();
+
+        let source = AudioFormat::new(384000, 2, SampleFormat::I24);
+        assert!(select_output_format(&configs, &source).is_none());
+
+        let source = AudioFormat::new(44100, 6, SampleFormat::I16);
+        assert!(select_output_format(&configs, &source).is_none());
+    }
+
+    #[test]
+    fn test_channel_match_policy() {
+        use ChannelMatchPolicy::*;
+
+        // Stereo-only device
+        let configs = NullBackend::default().supported_configs().unwrap();
+        let mono = AudioFormat::new(44100, 1, SampleFormat::I16);
+        let stereo = AudioFormat::new(44100, 2, SampleFormat::F32);
+
+        assert!(select_channel_matched_format(&configs, &mono, Exact).is_none());
+        assert!(select_channel_matched_format(&configs, &mono, Downmix).is_none());
+        assert_eq!(
+            select_channel_matched_format(&configs, &mono, Upmix),
+            Some(stereo.clone())
+        );
+        assert_eq!(
+            select_channel_matched_format(&configs, &mono, BestEffort),
+            Some(stereo.clone())
+        );
+
+        // A matching channel count always wins
+        let mut with_mono = configs.clone();
+        with_mono.push(OutputConfigRange {
+            channels: 1,
+            ..configs[0].clone()
+        });
+        assert_eq!(
+            select_channel_matched_format(&with_mono, &mono, BestEffort).map(|f| f.channels),
+            Some(1)
+        );
+
+        // Closest count first; only conversions the remixer supports
+        let surround = AudioFormat::new(48000, 6, SampleFormat::F32);
+        assert_eq!(
+            select_channel_matched_format(&with_mono, &surround, BestEffort).map(|f| f.channels),
+            Some(1)
+        );
+        assert!(select_channel_matched_format(&configs, &surround, BestEffort).is_none());
+        assert!(!Upmix.allows(2, 1));
+        assert!(Downmix.allows(2, 1));
+        assert!(!BestEffort.allows(2, 6));
+        assert!(Exact.allows(2, 2));
+    }
+
+    #[test]
+    fn test_write_samples_i24() {
+        let mut output = [cpal::I24::new_unchecked(0); 3];
+        cpal::I24::write_samples(&[1.0, -1.0, 0.5], &mut output, None);
+
+        assert_eq!(output[0].inner(), (1 << 23) - 1);
+        assert_eq!(output[1].inner(), -(1 << 23));
+        assert_eq!(output[2].inner(), 1 << 22);
+    }
+
+    #[test]
+    fn test_bit_perfect_capability() {
+        let configs = vec![range(SampleFormat::I16), range(SampleFormat::I32)];
+
+        let source = AudioFormat::new(44100, 2, SampleFormat::I16);
+        assert_eq!(
+            find_bit_perfect_format(&configs, &source),
+            Some(source.clone())
+        );
+
+        // 24-bit is carried losslessly in a 32-bit container
+        let source = AudioFormat::new(96000, 2, SampleFormat::I24);
+        assert_eq!(
+            find_bit_perfect_format(&configs, &source).map(|f| f.sample_format),
+            Some(SampleFormat::I32)
+        );
+
+        // Float output would go through a conversion
+        let configs = vec![range(SampleFormat::F32)];
+        assert!(find_bit_perfect_format(&configs, &source).is_none());
+
+        // Unsupported sample rate
+        let source = AudioFormat::new(192000, 2, SampleFormat::I16);
+        assert!(find_bit_perfect_format(&[range(SampleFormat::I16)], &source).is_none());
+    }
+
+    /// Mixer source yielding a fixed sequence of samples
+    struct VecSource(Vec<f64>, usize);
+
+    impl MixerSource for VecSource {
+        fn read_samples(&mut self, output: &mut [f64]) -> usize {
+            let n = output.len().min(self.0.len() - self.1);
+            output[..n].copy_from_slice(&self.0[self.1..self.1 + n]);
+            self.1 += n;
+            n
+        }
+    }
+
+    fn tone(frequency: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|i| (2.0 * std::f64::consts::PI * frequency * i as f64 / 48000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_mixer_sums_weighted_tones() {
+        let frames = 4800;
+        let a = tone(440.0, frames);
+        let b = tone(1000.0, frames);
+
+        let mut mixer = Mixer::new(0);
+        mixer.add_source(Box::new(VecSource(a.clone(), 0)), 0.6);
+        mixer.add_source(Box::new(VecSource(b.clone(), 0)), 0.3);
+
+        let mut output = vec![0.0; frames];
+        mixer.mix_into(&mut output, 1);
+
+        for i in 0..frames {
+            let expected = 0.6 * a[i] + 0.3 * b[i];
+            assert!((output[i] - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_mixer_ramps_and_clamps() {
+        let mut mixer = Mixer::new(100);
+        let id = mixer.add_source(Box::new(VecSource(vec![1.0; 1000], 0)), 1.0);
+
+        // Fades in over the ramp instead of jumping
+        let mut output = vec![0.0; 200];
+        mixer.mix_into(&mut output, 1);
+        assert!((output[0] - 0.01).abs() < 1e-9);
+        assert!((output[49] - 0.5).abs() < 1e-9);
+        assert_eq!(output[150], 1.0);
+
+        // The sum is limited on top of existing content
+        let mut output = vec![0.8; 100];
+        mixer.mix_into(&mut output, 1);
+        assert!(output.iter().all(|&s| s == 1.0));
+
+        // Removal fades out, then drops the source
+        mixer.remove_source(id).unwrap();
+        assert!(mixer.set_gain(id, 0.5).is_err());
+        let mut output = vec![0.0; 200];
+        mixer.mix_into(&mut output, 1);
+        assert!(output[0] > 0.98 && output[0] < 1.0);
+        assert_eq!(output[150], 0.0);
+        assert!(mixer.is_empty());
+    }
+
+    fn write_sine_wav(path: &Path, sample_rate: u32, channels: u16, frames: usize) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..frames {
+            let s = (2.0 * std::f64::consts::PI * 440.0 * i as f64 / sample_rate as f64).sin();
+            for _ in 0..channels {
+                writer.write_sample((s * 16000.0) as i16).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_transcode_resamples_to_exact_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("source.wav");
+        let dst = dir.path().join("portable.wav");
+        write_sine_wav(&src, 48000, 2, 48000);
+
+        let target = AudioFormat::new(44100, 2, SampleFormat::I16);
+        let frames = transcode_file(&src, &dst, target, DitheringAlgorithm::Triangular).unwrap();
+        assert_eq!(frames, 44100);
+
+        let mut reader = hound::WavReader::open(&dst).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate, 44100);
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(reader.duration(), 44100);
+
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+        assert!((15500..=16500).contains(&peak), "peak {}", peak);
+
+        // Odd ratios round the length up
+        let frames = transcode_file(
+            &src,
+            &dst,
+            AudioFormat::new(22050, 2, SampleFormat::F32),
+            DitheringAlgorithm::None,
+        )
+        .unwrap();
+        assert_eq!(frames, 22050);
+        assert_eq!(hound::WavReader::open(&dst).unwrap().duration(), 22050);
+    }
+
+    #[test]
+    fn test_transcode_remixes_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("stereo.wav");
+        let dst = dir.path().join("mono.wav");
+        write_sine_wav(&src, 44100, 2, 1000);
+
+        let target = AudioFormat::new(44100, 1, SampleFormat::I16);
+        assert_eq!(
+            transcode_file(&src, &dst, target, DitheringAlgorithm::None).unwrap(),
+            1000
+        );
+        let reader = hound::WavReader::open(&dst).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.duration(), 1000);
+
+        let target = AudioFormat::new(44100, 6, SampleFormat::I16);
+        assert!(transcode_file(&src, &dst, target, DitheringAlgorithm::None).is_err());
+    }
+
+    #[test]
+    fn test_file_output_header_reports_written_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.wav");
+        let format = AudioFormat::new(44100, 2, SampleFormat::I16);
+        let block = vec![0.25; 2 * 300];
+
+        let mut output =
+            FileOutput::create(&path, format.clone(), DitheringAlgorithm::None).unwrap();
+        for _ in 0..3 {
+            output.write(&block).unwrap();
+        }
+        output.finalize().unwrap();
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 900);
+        assert_eq!(reader.len(), 1800);
+
+        // Dropping without finalize still leaves a playable file
+        let mut output = FileOutput::create(&path, format, DitheringAlgorithm::None).unwrap();
+        output.write(&block).unwrap();
+        drop(output);
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.duration(), 300);
+        assert!(reader.samples::<i16>().all(|s| s.unwrap() == 8192));
+    }
+
+    #[test]
+    fn test_file_output_writes_high_resolution_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let samples = [0.5, -0.25, 0.125, -1.0, 0.0, 0.75];
+        let write = |sample_format: SampleFormat| -> std::path::PathBuf {
+            let path = dir.path().join(format!("{:?}.wav", sample_format));
+            let format = AudioFormat::new(96000, 2, sample_format);
+            let mut output = FileOutput::create(&path, format, DitheringAlgorithm::None).unwrap();
+            output.write(&samples).unwrap();
+            output.finalize().unwrap();
+            path
+        };
+
+        let mut reader = hound::WavReader::open(write(SampleFormat::I24)).unwrap();
+        let spec = reader.spec();
+        assert_eq!((spec.bits_per_sample, spec.channels), (24, 2));
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        let read: Vec<i32> = reader.samples::<i32>().map(|s| s.unwrap()).collect();
+        assert_eq!(read, SampleFormatConverter::f64_to_i24(&samples));
+
+        let mut reader = hound::WavReader::open(write(SampleFormat::F32)).unwrap();
+        let spec = reader.spec();
+        assert_eq!((spec.bits_per_sample, spec.sample_rate), (32, 96000));
+        assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+        let read: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        assert_eq!(read, SampleFormatConverter::f64_to_f32(&samples));
+
+        // hound does not read 64-bit float, so check the chunks by hand
+        let bytes = std::fs::read(write(SampleFormat::F64)).unwrap();
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&bytes[12..16], b"fmt ");
+        assert_eq!(u32_at(16), 18);
+        assert_eq!(u16_at(20), WAVE_FORMAT_IEEE_FLOAT);
+        assert_eq!(u16_at(32), 16); // block align
+        assert_eq!(u16_at(34), 64); // bits per sample
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(u32_at(46), 3);
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(u32_at(54), 48);
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        let read: Vec<f64> = bytes[58..]
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(read, samples);
+    }
+}
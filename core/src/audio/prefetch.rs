@@ -0,0 +1,86 @@
+//! Automatic next-track prefetching
+//!
+//! Decides when the next queued track is decoded, shortly before the current
+//! one ends. The engine runs the check on a `PollThread`.
+
+use std::time::Duration;
+
+/// Default time before the end of a track at which the next one is decoded
+pub const DEFAULT_PREFETCH_SECONDS: f64 = 10.0;
+
+/// Interval between prefetch checks
+pub const PREFETCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What the prefetcher should do at the current position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchAction {
+    /// Nothing to do
+    Wait,
+    /// Decode the next track
+    Prefetch,
+    /// Drop the prefetched track (playback moved away from the end)
+    Cancel,
+}
+
+/// Decide whether the next track should be prefetched
+///
+/// # Arguments
+/// * `position` - Playback position in frames
+/// * `end` - Frame at which the current track ends (if known)
+/// * `sample_rate` - Sample rate of the current track
+/// * `prefetch_seconds` - Prefetch window before the end
+/// * `has_prefetched` - Whether a next track is already decoded
+pub fn prefetch_action(
+    position: u64,
+    end: Option<u64>,
+    sample_rate: u32,
+    prefetch_seconds: f64,
+    has_prefetched: bool,
+) -> PrefetchAction {
+    let end = match end {
+        Some(end) => end,
+        None => return PrefetchAction::Wait,
+    };
+
+    let window = (prefetch_seconds * sample_rate as f64) as u64;
+    let in_window = end.saturating_sub(position) < window;
+
+    match (in_window, has_prefetched) {
+        (true, false) => PrefetchAction::Prefetch,
+        (false, true) => PrefetchAction::Cancel,
+        _ => PrefetchAction::Wait,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefetch_window() {
+        let rate = 44100;
+        let end = Some(60 * rate as u64);
+
+        assert_eq!(
+            prefetch_action(0, end, rate, 10.0, false),
+            PrefetchAction::Wait
+        );
+        assert_eq!(
+            prefetch_action(51 * rate as u64, end, rate, 10.0, false),
+            PrefetchAction::Prefetch
+        );
+        assert_eq!(
+            prefetch_action(51 * rate as u64, end, rate, 10.0, true),
+            PrefetchAction::Wait
+        );
+        // Seeking back out of the window drops the prefetched track
+        assert_eq!(
+            prefetch_action(20 * rate as u64, end, rate, 10.0, true),
+            PrefetchAction::Cancel
+        );
+        assert_eq!(
+            prefetch_action(0, None, rate, 10.0, false),
+            PrefetchAction::Wait
+        );
+    }
+}
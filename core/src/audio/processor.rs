@@ -3,7 +3,9 @@
 //! Handles sample format conversion, volume control, and audio processing in 64-bit precision
 
 use crate::audio::format::{AudioFormat, SampleFormat};
+use crate::audio::loudness::measure_lufs;
 use crate::Result;
+use serde::Serialize;
 
 /// Dithering algorithm for bit depth reduction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -17,6 +19,9 @@ pub enum DitheringAlgorithm {
     Rectangular,
 }
 
+/// Seed used by `Ditherer::new`
+pub const DEFAULT_DITHER_SEED: u64 = 0x123456789ABCDEF0;
+
 /// Dithering state for generating dither noise
 pub struct Ditherer {
     /// Dithering algorithm to use
@@ -28,12 +33,26 @@ pub struct Ditherer {
 impl Ditherer {
     /// Create a new ditherer with the specified algorithm
     pub fn new(algorithm: DitheringAlgorithm) -> Self {
+        Self::with_seed(algorithm, DEFAULT_DITHER_SEED)
+    }
+
+    /// Create a ditherer whose noise sequence starts from `seed`
+    ///
+    /// Ditherers with the same seed produce identical output, so renders
+    /// can be compared byte for byte; pass e.g. `rand::random()` for a
+    /// different sequence each time.
+    pub fn with_seed(algorithm: DitheringAlgorithm, seed: u64) -> Self {
         Self {
             algorithm,
-            rng_state: 0x123456789ABCDEF0, // Initial seed
+            rng_state: seed,
         }
     }
 
+    /// Restart the noise sequence from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng_state = seed;
+    }
+
     /// Generate a random number using a simple LCG (Linear Congruential Generator)
     /// This is fast and sufficient for dithering purposes
     fn random(&mut self) -> f64 {
@@ -175,7 +194,7 @@ impl SampleFormatConverter {
             .iter()
             .map(|&sample| {
                 let clamped = sample.clamp(-1.0, 1.0);
-                ((clamped + 1.0) * 0.5 * u8::MAX as f64) as u8
+                ((clamped + 1.0) * 0.5 * u8::MAX as f64).round() as u8
             })
             .collect()
     }
@@ -192,7 +211,7 @@ impl SampleFormatConverter {
             .iter()
             .map(|&sample| {
                 let clamped = sample.clamp(-1.0, 1.0);
-                (clamped * i8::MAX as f64) as i8
+                (clamped * i8::MAX as f64).round() as i8
             })
             .collect()
     }
@@ -209,7 +228,7 @@ impl SampleFormatConverter {
             .iter()
             .map(|&sample| {
                 let clamped = sample.clamp(-1.0, 1.0);
-                ((clamped + 1.0) * 0.5 * u16::MAX as f64) as u16
+                ((clamped + 1.0) * 0.5 * u16::MAX as f64).round() as u16
             })
             .collect()
     }
@@ -226,7 +245,7 @@ impl SampleFormatConverter {
             .iter()
             .map(|&sample| {
                 let clamped = sample.clamp(-1.0, 1.0);
-                (clamped * i16::MAX as f64) as i16
+                (clamped * i16::MAX as f64).round() as i16
             })
             .collect()
     }
@@ -237,13 +256,28 @@ impl SampleFormatConverter {
         Self::f64_to_i16(&dithered)
     }
 
+    /// Convert f64 samples to 24-bit integers stored in i32 (clamps to valid range)
+    pub fn f64_to_i24(samples: &[f64]) -> Vec<i32> {
+        const SCALE: f64 = (1i32 << 23) as f64;
+        samples
+            .iter()
+            .map(|&sample| (sample * SCALE).round().clamp(-SCALE, SCALE - 1.0) as i32)
+            .collect()
+    }
+
+    /// Convert f64 samples to 24-bit integers with dithering
+    pub fn f64_to_i24_dithered(samples: &[f64], ditherer: &mut Ditherer) -> Vec<i32> {
+        let dithered = ditherer.apply(samples, 24);
+        Self::f64_to_i24(&dithered)
+    }
+
     /// Convert f64 samples to i32 (clamps to valid range)
     pub fn f64_to_i32(samples: &[f64]) -> Vec<i32> {
         samples
             .iter()
             .map(|&sample| {
                 let clamped = sample.clamp(-1.0, 1.0);
-                (clamped * i32::MAX as f64) as i32
+                (clamped * i32::MAX as f64).round() as i32
             })
             .collect()
     }
@@ -279,8 +313,8 @@ impl SampleFormatConverter {
                 converted.iter().flat_map(|&s| s.to_le_bytes()).collect()
             }
             SampleFormat::I24 => {
-                // Convert to i32 first, then take only 3 bytes
-                let converted = Self::f64_to_i32(samples);
+                // Scale to the 24-bit range, then take only 3 bytes
+                let converted = Self::f64_to_i24(samples);
                 converted
                     .iter()
                     .flat_map(|&s| {
@@ -325,8 +359,8 @@ impl SampleFormatConverter {
                 converted.iter().flat_map(|&s| s.to_le_bytes()).collect()
             }
             SampleFormat::I24 => {
-                // Convert to i32 first with dithering, then take only 3 bytes
-                let converted = Self::f64_to_i32_dithered(samples, ditherer);
+                // Scale to the 24-bit range with dithering, then take only 3 bytes
+                let converted = Self::f64_to_i24_dithered(samples, ditherer);
                 converted
                     .iter()
                     .flat_map(|&s| {
@@ -357,6 +391,66 @@ pub struct AudioProcessor {
     target_volume: f64,
     /// Volume ramp step per sample
     ramp_step: f64,
+    /// Per-channel gains applied before the volume (empty = unity)
+    channel_gains: Vec<f64>,
+}
+
+/// Highest level normalization may bring a track's peak to
+pub const NORMALIZATION_CEILING_DBFS: f64 = 0.0;
+
+/// Peak below which a track counts as silent and is not normalized
+const NORMALIZATION_SILENCE: f64 = 1e-9;
+
+/// Whole-track normalization applied before playback
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub enum NormalizationMode {
+    /// Play tracks as decoded
+    #[default]
+    Off,
+    /// Scale so the sample peak reaches a level
+    Peak {
+        /// Target peak in dBFS (capped at the ceiling)
+        target_dbfs: f64,
+    },
+    /// Scale so the integrated loudness reaches a level
+    Lufs {
+        /// Target loudness in LUFS
+        target_lufs: f64,
+    },
+}
+
+impl NormalizationMode {
+    /// Check that the target is finite
+    pub fn validate(&self) -> Result<()> {
+        let target = match *self {
+            NormalizationMode::Off => return Ok(()),
+            NormalizationMode::Peak { target_dbfs } => target_dbfs,
+            NormalizationMode::Lufs { target_lufs } => target_lufs,
+        };
+        if !target.is_finite() {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Normalization target must be finite, got {}",
+                target
+            )));
+        }
+        Ok(())
+    }
+
+    /// Normalize interleaved samples of the given format in place
+    pub fn apply(&self, samples: &mut [f64], format: &AudioFormat) {
+        match *self {
+            NormalizationMode::Off => {}
+            NormalizationMode::Peak { target_dbfs } => {
+                AudioProcessor::normalize_to_peak(samples, target_dbfs)
+            }
+            NormalizationMode::Lufs { target_lufs } => AudioProcessor::normalize_to_lufs(
+                samples,
+                format.channels,
+                format.sample_rate,
+                target_lufs,
+            ),
+        }
+    }
 }
 
 /// Sample rate converter for high-quality resampling
@@ -368,8 +462,36 @@ pub struct SampleRateConverter {
     target_rate: u32,
     /// Conversion ratio
     ratio: f64,
+    /// Interpolation kernel used by the streaming interface
+    quality: ResampleQuality,
+    /// Interleaved channels of the streaming interface
+    channels: usize,
+    /// Streamed input not yet consumed (interleaved)
+    pending: Vec<f64>,
+    /// Read position of the next output frame, in frames into `pending`
+    position: f64,
+    /// Sinc kernel rows (`SINC_PHASES + 1` rows of `2 * SINC_HALF_TAPS` taps)
+    kernel: Vec<f64>,
 }
 
+/// Interpolation used by `SampleRateConverter` when streaming
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Linear interpolation between neighbouring frames
+    Linear,
+    /// Blackman-windowed sinc, band-limited to the lower of both rates
+    Sinc,
+}
+
+/// Zero crossings of the sinc kernel on each side of the output position
+const SINC_HALF_TAPS: usize = 32;
+
+/// Fractional positions tabulated for the sinc kernel
+const SINC_PHASES: usize = 256;
+
+/// Passband as a fraction of the lower Nyquist frequency
+const SINC_CUTOFF: f64 = 0.92;
+
 impl SampleRateConverter {
     /// Create a new sample rate converter
     ///
@@ -377,12 +499,177 @@ impl SampleRateConverter {
     /// * `source_rate` - Input sample rate in Hz
     /// * `target_rate` - Output sample rate in Hz
     pub fn new(source_rate: u32, target_rate: u32) -> Self {
+        Self::with_quality(source_rate, target_rate, 1, ResampleQuality::Linear)
+    }
+
+    /// Create a converter for streaming interleaved audio through `push`/`read`
+    ///
+    /// State is kept between calls, so a signal split into arbitrary blocks
+    /// converts exactly as if it were processed at once.
+    pub fn with_quality(
+        source_rate: u32,
+        target_rate: u32,
+        channels: usize,
+        quality: ResampleQuality,
+    ) -> Self {
         let ratio = target_rate as f64 / source_rate as f64;
-        Self {
+        let mut converter = Self {
             source_rate,
             target_rate,
             ratio,
+            quality,
+            channels: channels.max(1),
+            pending: Vec::new(),
+            position: 0.0,
+            kernel: Vec::new(),
+        };
+        if quality == ResampleQuality::Sinc {
+            converter.kernel = Self::sinc_kernel(ratio.min(1.0) * SINC_CUTOFF);
+        }
+        converter.reset();
+        converter
+    }
+
+    /// Tabulate the windowed sinc, each row normalized to unity DC gain
+    fn sinc_kernel(cutoff: f64) -> Vec<f64> {
+        let taps = 2 * SINC_HALF_TAPS;
+        let half = SINC_HALF_TAPS as f64;
+        let mut kernel = Vec::with_capacity((SINC_PHASES + 1) * taps);
+
+        for phase in 0..=SINC_PHASES {
+            let frac = phase as f64 / SINC_PHASES as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|j| {
+                    let t = frac + half - 1.0 - j as f64;
+                    let x = std::f64::consts::PI * cutoff * t;
+                    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+                    let w = std::f64::consts::PI * t / half;
+                    let window = 0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+                    if t.abs() >= half {
+                        0.0
+                    } else {
+                        sinc * window
+                    }
+                })
+                .collect();
+            let sum: f64 = row.iter().sum();
+            kernel.extend(row.iter().map(|tap| tap / sum));
         }
+        kernel
+    }
+
+    /// Frames of input on each side of an output position
+    fn half_taps(&self) -> usize {
+        match self.quality {
+            ResampleQuality::Linear => 1,
+            ResampleQuality::Sinc => SINC_HALF_TAPS,
+        }
+    }
+
+    /// Source frames consumed per output frame
+    fn step(&self) -> f64 {
+        self.source_rate as f64 / self.target_rate as f64
+    }
+
+    /// Get the interpolation quality
+    pub fn quality(&self) -> ResampleQuality {
+        self.quality
+    }
+
+    /// Get the interleaved channel count
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Drop streamed input, starting over with silent history
+    pub fn reset(&mut self) {
+        let history = self.half_taps() - 1;
+        self.pending.clear();
+        self.pending.resize(history * self.channels, 0.0);
+        self.position = history as f64;
+    }
+
+    /// Reserve room for `frames` more frames of queued input
+    pub fn reserve(&mut self, frames: usize) {
+        self.pending
+            .reserve((frames + 2 * self.half_taps()) * self.channels);
+    }
+
+    /// Queue interleaved input samples
+    pub fn push<I: IntoIterator<Item = f64>>(&mut self, samples: I) {
+        self.pending.extend(samples);
+    }
+
+    /// Input frames still to be pushed before `read` can produce `frames`
+    pub fn input_frames_needed(&self, frames: usize) -> usize {
+        if frames == 0 {
+            return 0;
+        }
+        let step = self.step();
+        let mut last = self.position;
+        for _ in 1..frames {
+            last += step;
+        }
+        let required = last.floor() as usize + self.half_taps() + 1;
+        required.saturating_sub(self.pending.len() / self.channels)
+    }
+
+    /// Produce interleaved output from the queued input
+    ///
+    /// Returns the number of samples written, a multiple of the channel
+    /// count; fewer than requested when more input is needed.
+    pub fn read(&mut self, output: &mut [f64]) -> usize {
+        let channels = self.channels;
+        let half_taps = self.half_taps();
+        let available = self.pending.len() / channels;
+        let step = self.step();
+        let mut written = 0;
+
+        while written + channels <= output.len() {
+            let base = self.position.floor() as usize;
+            if base + half_taps >= available {
+                break;
+            }
+            let frac = self.position - base as f64;
+            let start = base + 1 - half_taps;
+
+            match self.quality {
+                ResampleQuality::Linear => {
+                    for c in 0..channels {
+                        let a = self.pending[start * channels + c];
+                        let b = self.pending[(start + 1) * channels + c];
+                        output[written + c] = a * (1.0 - frac) + b * frac;
+                    }
+                }
+                ResampleQuality::Sinc => {
+                    let taps = 2 * half_taps;
+                    let phase = frac * SINC_PHASES as f64;
+                    let row = (phase.floor() as usize).min(SINC_PHASES - 1);
+                    let blend = phase - row as f64;
+                    let lower = &self.kernel[row * taps..(row + 1) * taps];
+                    let upper = &self.kernel[(row + 1) * taps..(row + 2) * taps];
+                    for c in 0..channels {
+                        let mut sum = 0.0;
+                        for j in 0..taps {
+                            let weight = lower[j] + (upper[j] - lower[j]) * blend;
+                            sum += self.pending[(start + j) * channels + c] * weight;
+                        }
+                        output[written + c] = sum;
+                    }
+                }
+            }
+
+            self.position += step;
+            written += channels;
+        }
+
+        // Keep only the history the next output frame needs
+        let consumed = (self.position.floor() as usize + 1).saturating_sub(half_taps);
+        let consumed = consumed.min(available);
+        self.pending.drain(..consumed * channels);
+        self.position -= consumed as f64;
+
+        written
     }
 
     /// Check if conversion is needed
@@ -450,6 +737,409 @@ impl SampleRateConverter {
     }
 }
 
+/// Maximum stereo width (2.0 = side doubled)
+pub const MAX_STEREO_WIDTH: f64 = 2.0;
+
+/// Mid/side stereo width control
+///
+/// Splits each stereo frame into mid `(L + R) / 2` and side `(L - R) / 2`,
+/// scales the side by the width and recombines. Widths above 1.0 are
+/// compensated by `sqrt(2 / (1 + width^2))` so the energy of a decorrelated
+/// signal stays constant instead of growing with the width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoWidth {
+    width: f64,
+}
+
+impl StereoWidth {
+    /// Create a width control (0.0 = mono, 1.0 = unchanged, >1.0 = wider)
+    pub fn new(width: f64) -> Result<Self> {
+        let mut control = Self { width: 1.0 };
+        control.set_width(width)?;
+        Ok(control)
+    }
+
+    /// Get the width factor
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    /// Set the width factor (0.0 to `MAX_STEREO_WIDTH`)
+    pub fn set_width(&mut self, width: f64) -> Result<()> {
+        if !(0.0..=MAX_STEREO_WIDTH).contains(&width) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Stereo width must be between 0.0 and {}, got {}",
+                MAX_STEREO_WIDTH, width
+            )));
+        }
+        self.width = width;
+        Ok(())
+    }
+
+    /// Check if the control leaves audio untouched
+    pub fn is_neutral(&self) -> bool {
+        self.width == 1.0
+    }
+
+    /// Process one stereo frame
+    #[inline]
+    pub fn process(&self, left: f64, right: f64) -> (f64, f64) {
+        if self.is_neutral() {
+            return (left, right);
+        }
+
+        let mid = (left + right) * 0.5;
+        let side = (left - right) * 0.5 * self.width;
+        let gain = if self.width > 1.0 {
+            (2.0 / (1.0 + self.width * self.width)).sqrt()
+        } else {
+            1.0
+        };
+        ((mid + side) * gain, (mid - side) * gain)
+    }
+
+    /// Process interleaved stereo samples in place
+    pub fn process_interleaved(&self, samples: &mut [f64]) {
+        if self.is_neutral() {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(2) {
+            let (left, right) = self.process(frame[0], frame[1]);
+            frame[0] = left;
+            frame[1] = right;
+        }
+    }
+}
+
+impl Default for StereoWidth {
+    fn default() -> Self {
+        Self { width: 1.0 }
+    }
+}
+
+/// Highest accepted saturation drive in dB
+pub const MAX_SATURATION_DRIVE_DB: f64 = 36.0;
+
+/// Transfer curve of a `Saturator`
+///
+/// Every curve is odd, monotonic, has unit-or-greater slope at zero and
+/// never leaves [-1, 1], whatever the input level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaturationCurve {
+    /// Hyperbolic tangent
+    #[default]
+    Tanh,
+    /// Cubic `1.5x - 0.5x^3`, hard limited beyond ±1
+    Cubic,
+    /// Arctangent scaled to unit slope at zero
+    Arctangent,
+}
+
+impl SaturationCurve {
+    /// Map one sample through the curve
+    #[inline]
+    pub fn transfer(self, x: f64) -> f64 {
+        match self {
+            SaturationCurve::Tanh => x.tanh(),
+            SaturationCurve::Cubic => {
+                let x = x.clamp(-1.0, 1.0);
+                1.5 * x - 0.5 * x * x * x
+            }
+            SaturationCurve::Arctangent => {
+                (x * std::f64::consts::FRAC_PI_2).atan() * std::f64::consts::FRAC_2_PI
+            }
+        }
+    }
+}
+
+/// Settings of a `Saturator`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SaturationConfig {
+    /// Transfer curve
+    pub curve: SaturationCurve,
+    /// Gain into the curve in dB (0 = bypass, up to `MAX_SATURATION_DRIVE_DB`)
+    pub drive_db: f64,
+}
+
+impl SaturationConfig {
+    /// Create a configuration
+    pub fn new(curve: SaturationCurve, drive_db: f64) -> Self {
+        Self { curve, drive_db }
+    }
+
+    /// Check that the drive is within 0..=`MAX_SATURATION_DRIVE_DB`
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=MAX_SATURATION_DRIVE_DB).contains(&self.drive_db) {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Saturation drive must be between 0 and {} dB, got {}",
+                MAX_SATURATION_DRIVE_DB, self.drive_db
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Soft clipper shaping peaks through a saturation curve
+///
+/// A musical alternative to hard clipping: the signal is amplified by the
+/// drive and passed through the curve, so the output stays within ±1.0.
+/// Zero drive bypasses the stage entirely, leaving samples bit-exact.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Saturator {
+    config: SaturationConfig,
+    gain: f64,
+}
+
+impl Saturator {
+    /// Create a saturator, validating the configuration
+    pub fn new(config: SaturationConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            gain: 10_f64.powf(config.drive_db / 20.0),
+        })
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> SaturationConfig {
+        self.config
+    }
+
+    /// Check if the saturator leaves audio untouched
+    pub fn is_bypassed(&self) -> bool {
+        self.config.drive_db == 0.0
+    }
+
+    /// Process a single sample
+    #[inline]
+    pub fn process(&self, sample: f64) -> f64 {
+        if self.is_bypassed() {
+            return sample;
+        }
+        self.config.curve.transfer(sample * self.gain)
+    }
+
+    /// Process a block of samples in place
+    pub fn process_block(&self, samples: &mut [f64]) {
+        if self.is_bypassed() {
+            return;
+        }
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+/// Minimum supported playback rate for time stretching
+pub const MIN_PLAYBACK_RATE: f64 = 0.5;
+
+/// Maximum supported playback rate for time stretching
+pub const MAX_PLAYBACK_RATE: f64 = 3.0;
+
+/// WSOLA analysis/synthesis window length in frames
+const STRETCH_WINDOW_FRAMES: usize = 1024;
+
+/// Maximum offset (in frames) searched for the best-aligned segment
+const STRETCH_TOLERANCE_FRAMES: usize = 256;
+
+/// Decimation used when computing the alignment cross-correlation
+const STRETCH_CORRELATION_STEP: usize = 4;
+
+/// Time stretcher changing playback speed without altering pitch
+///
+/// Uses WSOLA (waveform-similarity overlap-add): Hann-windowed segments are
+/// taken from the input at `rate` times the output hop, each shifted within a
+/// small tolerance to best match the natural continuation of the previous
+/// segment, and overlap-added at 50%. Segment alignment is computed on the
+/// channel sum so all channels stay phase-locked. Input and output state is
+/// kept between calls so block edges do not introduce artifacts.
+pub struct TimeStretcher {
+    /// Number of interleaved channels
+    channels: usize,
+    /// Playback rate (output duration = input duration / rate)
+    rate: f64,
+    /// Pending interleaved input samples
+    input: Vec<f64>,
+    /// Analysis position (frames into `input`) of the next segment
+    analysis_pos: f64,
+    /// Natural continuation of the previously chosen segment (frames into `input`)
+    continuation: Option<usize>,
+    /// Overlap tail of the previous windowed segment (interleaved)
+    overlap: Vec<f64>,
+    /// Output samples ready to be read (interleaved)
+    ready: std::collections::VecDeque<f64>,
+    /// Precomputed Hann window
+    window: Vec<f64>,
+}
+
+impl TimeStretcher {
+    /// Create a new time stretcher
+    ///
+    /// # Arguments
+    /// * `channels` - Number of interleaved channels
+    /// * `rate` - Playback rate, clamped to 0.5..=3.0
+    pub fn new(channels: u16, rate: f64) -> Self {
+        let window = (0..STRETCH_WINDOW_FRAMES)
+            .map(|n| {
+                0.5 - 0.5
+                    * (2.0 * std::f64::consts::PI * n as f64 / STRETCH_WINDOW_FRAMES as f64).cos()
+            })
+            .collect();
+
+        let channels = channels.max(1) as usize;
+        Self {
+            channels,
+            rate: rate.clamp(MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE),
+            input: Vec::new(),
+            analysis_pos: 0.0,
+            continuation: None,
+            overlap: vec![0.0; STRETCH_WINDOW_FRAMES / 2 * channels],
+            ready: std::collections::VecDeque::new(),
+            window,
+        }
+    }
+
+    /// Get the playback rate
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Check if the stretcher is a passthrough (rate 1.0)
+    pub fn is_passthrough(&self) -> bool {
+        self.rate == 1.0
+    }
+
+    /// Number of interleaved output samples ready to read
+    pub fn available(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Reserve room for `frames` more frames of input and of output
+    ///
+    /// Lets a realtime caller size the buffers up front instead of growing
+    /// them while streaming.
+    pub fn reserve(&mut self, frames: usize) {
+        let samples = (frames + STRETCH_WINDOW_FRAMES + STRETCH_TOLERANCE_FRAMES) * self.channels;
+        self.input.reserve(samples);
+        self.ready.reserve(samples);
+    }
+
+    /// Clear all buffered input and output (e.g. after a seek)
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.analysis_pos = 0.0;
+        self.continuation = None;
+        self.overlap.iter_mut().for_each(|s| *s = 0.0);
+        self.ready.clear();
+    }
+
+    /// Feed interleaved input samples
+    pub fn push(&mut self, samples: &[f64]) {
+        if self.is_passthrough() {
+            self.ready.extend(samples.iter().copied());
+            return;
+        }
+
+        self.input.extend_from_slice(samples);
+        self.process_segments();
+    }
+
+    /// Read up to `output.len()` interleaved samples, returning the number read
+    pub fn read(&mut self, output: &mut [f64]) -> usize {
+        let count = output.len().min(self.ready.len());
+        for (out, sample) in output.iter_mut().zip(self.ready.drain(..count)) {
+            *out = sample;
+        }
+        count
+    }
+
+    /// Stretch a complete interleaved buffer in one call
+    pub fn process(&mut self, samples: &[f64]) -> Vec<f64> {
+        self.push(samples);
+        let mut output = vec![0.0; self.available()];
+        self.read(&mut output);
+        output
+    }
+
+    /// Produce as many output hops as the buffered input allows
+    fn process_segments(&mut self) {
+        let hop = STRETCH_WINDOW_FRAMES / 2;
+        let channels = self.channels;
+
+        loop {
+            let input_frames = self.input.len() / channels;
+            let nominal = self.analysis_pos.round() as usize;
+            if nominal + STRETCH_TOLERANCE_FRAMES + STRETCH_WINDOW_FRAMES > input_frames {
+                break;
+            }
+
+            let start = match self.continuation {
+                Some(natural) => self.best_aligned_segment(nominal, natural),
+                None => nominal,
+            };
+
+            // Overlap-add: first half completes the previous segment's tail
+            for frame in 0..STRETCH_WINDOW_FRAMES {
+                let w = self.window[frame];
+                for ch in 0..channels {
+                    let sample = self.input[(start + frame) * channels + ch] * w;
+                    if frame < hop {
+                        let index = frame * channels + ch;
+                        self.ready.push_back(self.overlap[index] + sample);
+                    } else {
+                        self.overlap[(frame - hop) * channels + ch] = sample;
+                    }
+                }
+            }
+
+            self.continuation = Some(start + hop);
+            self.analysis_pos += hop as f64 * self.rate;
+
+            // Discard input no longer reachable by future segments
+            let keep_from = (self.analysis_pos.floor() as usize)
+                .saturating_sub(STRETCH_TOLERANCE_FRAMES)
+                .min(start + hop);
+            if keep_from > 0 {
+                self.input.drain(..keep_from * channels);
+                self.analysis_pos -= keep_from as f64;
+                self.continuation = self.continuation.map(|c| c - keep_from);
+            }
+        }
+    }
+
+    /// Find the segment start near `nominal` most similar to the continuation
+    fn best_aligned_segment(&self, nominal: usize, natural: usize) -> usize {
+        let channels = self.channels;
+        let mono = |frame: usize| -> f64 {
+            self.input[frame * channels..(frame + 1) * channels]
+                .iter()
+                .sum()
+        };
+
+        let low = nominal.saturating_sub(STRETCH_TOLERANCE_FRAMES);
+        let high = nominal + STRETCH_TOLERANCE_FRAMES;
+        let compare_len = STRETCH_WINDOW_FRAMES / 2;
+
+        let mut best_start = nominal;
+        let mut best_score = f64::NEG_INFINITY;
+
+        for candidate in low..=high {
+            let mut score = 0.0;
+            let mut frame = 0;
+            while frame < compare_len {
+                score += mono(candidate + frame) * mono(natural + frame);
+                frame += STRETCH_CORRELATION_STEP;
+            }
+            if score > best_score {
+                best_score = score;
+                best_start = candidate;
+            }
+        }
+
+        best_start
+    }
+}
+
 impl AudioProcessor {
     /// Create a new audio processor
     pub fn new(format: AudioFormat) -> Self {
@@ -458,6 +1148,7 @@ impl AudioProcessor {
             volume: 1.0,
             target_volume: 1.0,
             ramp_step: 0.0,
+            channel_gains: Vec::new(),
         }
     }
 
@@ -499,9 +1190,52 @@ impl AudioProcessor {
         }
     }
 
+    /// Set per-channel gains (one linear gain per channel)
+    ///
+    /// Gains multiply the interleaved samples before the master volume. An
+    /// empty vector restores unity gain.
+    pub fn set_channel_gains(&mut self, gains: Vec<f64>) -> Result<()> {
+        if !gains.is_empty() && gains.len() != self.format.channels as usize {
+            return Err(crate::Error::InvalidParameter(format!(
+                "Expected {} channel gains, got {}",
+                self.format.channels,
+                gains.len()
+            )));
+        }
+        if gains.iter().any(|g| !g.is_finite() || *g < 0.0) {
+            return Err(crate::Error::InvalidParameter(
+                "Channel gains must be finite and non-negative".to_string(),
+            ));
+        }
+        self.channel_gains = gains;
+        Ok(())
+    }
+
+    /// Get the per-channel gains (empty = unity)
+    pub fn channel_gains(&self) -> &[f64] {
+        &self.channel_gains
+    }
+
+    /// Left/right gains for a stereo balance (-1.0 = left only, 1.0 = right only)
+    ///
+    /// The centered side stays at full scale; the other side is attenuated
+    /// linearly.
+    pub fn balance_gains(balance: f64) -> [f64; 2] {
+        let balance = balance.clamp(-1.0, 1.0);
+        [1.0 - balance.max(0.0), 1.0 + balance.min(0.0)]
+    }
+
     /// Apply volume to f64 samples
-    /// This method applies the current volume and handles ramping if active
+    /// This method applies the channel gains and the current volume, and
+    /// handles ramping if active
     pub fn apply_volume(&mut self, samples: &mut [f64]) {
+        if !self.channel_gains.is_empty() {
+            let gains = &self.channel_gains;
+            for (i, sample) in samples.iter_mut().enumerate() {
+                *sample *= gains[i % gains.len()];
+            }
+        }
+
         if self.ramp_step.abs() > 1e-10 {
             // Apply ramping
             for sample in samples.iter_mut() {
@@ -563,6 +1297,44 @@ impl AudioProcessor {
         }
     }
 
+    /// Scale samples so their peak reaches `target_dbfs`
+    ///
+    /// Targets above `NORMALIZATION_CEILING_DBFS` are capped to it, so the
+    /// result never clips. Silent input is left unchanged.
+    pub fn normalize_to_peak(samples: &mut [f64], target_dbfs: f64) {
+        let peak = samples.iter().fold(0.0f64, |peak, s| peak.max(s.abs()));
+        if peak < NORMALIZATION_SILENCE {
+            return;
+        }
+
+        let target = 10_f64.powf(target_dbfs.min(NORMALIZATION_CEILING_DBFS) / 20.0);
+        let gain = target / peak;
+        samples.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    /// Scale samples so their integrated loudness reaches `target_lufs`
+    ///
+    /// The gain is reduced where needed to keep the true peak under
+    /// `NORMALIZATION_CEILING_DBFS`. Input too short or quiet to measure is
+    /// left unchanged.
+    pub fn normalize_to_lufs(
+        samples: &mut [f64],
+        channels: u16,
+        sample_rate: u32,
+        target_lufs: f64,
+    ) {
+        let loudness = measure_lufs(samples, sample_rate, channels);
+        if !loudness.integrated_lufs.is_finite() || loudness.true_peak < NORMALIZATION_SILENCE {
+            return;
+        }
+
+        let ceiling = 10_f64.powf(NORMALIZATION_CEILING_DBFS / 20.0);
+        let gain = 10_f64
+            .powf(loudness.gain_to_target(target_lufs) / 20.0)
+            .min(ceiling / loudness.true_peak);
+        samples.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
     /// Convert samples to f64 based on the current format
     pub fn convert_to_f64(&self, samples: &[u8]) -> Result<Vec<f64>> {
         let sample_size = self.format.sample_format.size_bytes();
@@ -645,6 +1417,243 @@ impl AudioProcessor {
     pub fn convert_from_f64(&self, samples: &[f64]) -> Vec<u8> {
         SampleFormatConverter::convert_from_f64(samples, self.format.sample_format)
     }
+
+    /// Split interleaved samples into one buffer per channel
+    pub fn deinterleave(&self, interleaved: &[f64]) -> Result<Vec<Vec<f64>>> {
+        let channels = self.format.channels as usize;
+        if channels == 0 || !interleaved.len().is_multiple_of(channels) {
+            return Err(crate::Error::AudioFormat(format!(
+                "Interleaved length {} is not a multiple of {} channels",
+                interleaved.len(),
+                channels
+            )));
+        }
+
+        let frames = interleaved.len() / channels;
+        let mut planar = vec![Vec::with_capacity(frames); channels];
+        for frame in interleaved.chunks_exact(channels) {
+            for (channel, &sample) in planar.iter_mut().zip(frame) {
+                channel.push(sample);
+            }
+        }
+
+        Ok(planar)
+    }
+
+    /// Merge per-channel buffers into interleaved samples
+    pub fn interleave(&self, planar: &[Vec<f64>]) -> Result<Vec<f64>> {
+        let channels = self.format.channels as usize;
+        if planar.len() != channels {
+            return Err(crate::Error::AudioFormat(format!(
+                "Expected {} channel buffers, got {}",
+                channels,
+                planar.len()
+            )));
+        }
+
+        let frames = planar.first().map(|c| c.len()).unwrap_or(0);
+        if planar.iter().any(|c| c.len() != frames) {
+            return Err(crate::Error::AudioFormat(
+                "Channel buffers must have equal length".to_string(),
+            ));
+        }
+
+        let mut interleaved = Vec::with_capacity(frames * channels);
+        for frame in 0..frames {
+            interleaved.extend(planar.iter().map(|c| c[frame]));
+        }
+
+        Ok(interleaved)
+    }
+}
+
+/// Length of the windows silence detection measures level over
+pub const SILENCE_WINDOW_MS: u32 = 10;
+
+/// Consecutive windows above the threshold needed to count as sound
+///
+/// Keeps isolated clicks in a silent intro/outro from ending the silence.
+pub const SILENCE_MIN_SOUND_WINDOWS: usize = 3;
+
+/// Find the first and last non-silent frames of interleaved audio
+///
+/// Level is measured as the RMS of each [`SILENCE_WINDOW_MS`] window, per
+/// channel; a window is non-silent when any channel exceeds `threshold_dbfs`.
+/// Sound must last at least [`SILENCE_MIN_SOUND_WINDOWS`] consecutive windows.
+/// The bounds are then narrowed to the first and last samples above the
+/// threshold inside the outermost sound windows.
+///
+/// # Returns
+/// `(start_frame, end_frame)` with `end_frame` inclusive, or `None` if the
+/// audio is entirely silent
+pub fn detect_silence_bounds(
+    samples: &[f64],
+    channels: u16,
+    sample_rate: u32,
+    threshold_dbfs: f64,
+) -> Option<(u64, u64)> {
+    let channels = channels.max(1) as usize;
+    let threshold = AudioProcessor::db_to_linear(threshold_dbfs);
+    let window = ((sample_rate * SILENCE_WINDOW_MS / 1000) as usize).max(1) * channels;
+    let loud: Vec<bool> = samples
+        .chunks(window)
+        .map(|block| {
+            let frames = (block.len() / channels).max(1) as f64;
+            (0..channels).any(|channel| {
+                let energy: f64 = block
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .map(|s| s * s)
+                    .sum();
+                (energy / frames).sqrt() > threshold
+            })
+        })
+        .collect();
+
+    let first_run_start = |windows: &mut dyn Iterator<Item = usize>| {
+        let mut run = 0;
+        let mut run_start = 0;
+        for index in windows {
+            if loud[index] {
+                if run == 0 {
+                    run_start = index;
+                }
+                run += 1;
+                if run >= SILENCE_MIN_SOUND_WINDOWS.min(loud.len()) {
+                    return Some(run_start);
+                }
+            } else {
+                run = 0;
+            }
+        }
+        None
+    };
+    let frames_of = |index: usize| {
+        let block = &samples[index * window..((index + 1) * window).min(samples.len())];
+        let first = index * window / channels;
+        let above = |frame: &[f64]| frame.iter().any(|s| s.abs() > threshold);
+        let chunks = block.chunks_exact(channels);
+        let head = chunks.clone().position(above).unwrap_or(0);
+        let tail = chunks.clone().rposition(above).unwrap_or(chunks.len() - 1);
+        (first + head, first + tail)
+    };
+
+    let (start, _) = frames_of(first_run_start(&mut (0..loud.len()))?);
+    let (_, end) = frames_of(first_run_start(&mut (0..loud.len()).rev())?);
+    Some((start as u64, end as u64))
+}
+
+/// Frames per chunk when the length of a file isn't known up front
+const PEAK_CHUNK_FRAMES: usize = 256;
+
+/// Min/max accumulator over a fixed number of buckets
+struct PeakAccumulator {
+    peaks: Vec<(f32, f32)>,
+    total_frames: u64,
+    frame: u64,
+}
+
+impl PeakAccumulator {
+    fn new(buckets: usize, total_frames: u64) -> Self {
+        Self {
+            peaks: vec![(f32::INFINITY, f32::NEG_INFINITY); buckets],
+            total_frames: total_frames.max(1),
+            frame: 0,
+        }
+    }
+
+    /// Add one frame's minimum and maximum across channels
+    fn push(&mut self, min: f32, max: f32) {
+        let buckets = self.peaks.len() as u64;
+        if buckets == 0 {
+            return;
+        }
+        let bucket = (self.frame * buckets / self.total_frames).min(buckets - 1) as usize;
+        let peak = &mut self.peaks[bucket];
+        peak.0 = peak.0.min(min);
+        peak.1 = peak.1.max(max);
+        self.frame += 1;
+    }
+
+    /// Add interleaved frames
+    fn push_samples(&mut self, samples: &[f64], channels: usize) {
+        for frame in samples.chunks_exact(channels) {
+            let (min, max) = frame_min_max(frame);
+            self.push(min, max);
+        }
+    }
+
+    /// Get the peaks, with empty buckets reported as silence
+    fn finish(self) -> Vec<(f32, f32)> {
+        self.peaks
+            .into_iter()
+            .map(|(min, max)| if min > max { (0.0, 0.0) } else { (min, max) })
+            .collect()
+    }
+}
+
+/// Minimum and maximum of a set of samples
+fn frame_min_max(samples: &[f64]) -> (f32, f32) {
+    samples
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &s| {
+            (min.min(s as f32), max.max(s as f32))
+        })
+}
+
+/// Downsample audio into min/max pairs for waveform rendering
+///
+/// Channels are combined, so each pair covers all channels. Exactly `buckets`
+/// pairs are returned; buckets with no audio are `(0.0, 0.0)`.
+pub fn compute_peaks(samples: &[f64], channels: u16, buckets: usize) -> Vec<(f32, f32)> {
+    let channels = channels.max(1) as usize;
+    let frames = (samples.len() / channels) as u64;
+
+    let mut accumulator = PeakAccumulator::new(buckets, frames);
+    accumulator.push_samples(samples, channels);
+    accumulator.finish()
+}
+
+/// Compute waveform peaks by streaming a file through the decoder
+///
+/// The decoded audio is never held in memory as a whole. See
+/// [`compute_peaks`] for the output layout.
+pub fn compute_peaks_for_file<P: AsRef<std::path::Path>>(
+    path: P,
+    buckets: usize,
+) -> Result<Vec<(f32, f32)>> {
+    let mut decoder = crate::audio::decoder::AudioDecoder::new(path)?;
+    let channels = decoder.format().channels.max(1) as usize;
+
+    if let Some(frames) = decoder.duration() {
+        let mut accumulator = PeakAccumulator::new(buckets, frames);
+        while let Some(packet) = decoder.decode_next()? {
+            accumulator.push_samples(&packet.samples, channels);
+        }
+        return Ok(accumulator.finish());
+    }
+
+    // Unknown length: keep coarse chunk peaks, then fold them into buckets
+    let mut chunks = Vec::new();
+    let mut pending: Vec<f64> = Vec::new();
+    while let Some(packet) = decoder.decode_next()? {
+        pending.extend_from_slice(&packet.samples);
+        let whole = pending.len() / (PEAK_CHUNK_FRAMES * channels) * PEAK_CHUNK_FRAMES * channels;
+        for chunk in pending[..whole].chunks(PEAK_CHUNK_FRAMES * channels) {
+            chunks.push(frame_min_max(chunk));
+        }
+        pending.drain(..whole);
+    }
+    if !pending.is_empty() {
+        chunks.push(frame_min_max(&pending));
+    }
+
+    let mut accumulator = PeakAccumulator::new(buckets, chunks.len() as u64);
+    for (min, max) in chunks {
+        accumulator.push(min, max);
+    }
+    Ok(accumulator.finish())
 }
 
 #[cfg(test)]
@@ -732,6 +1741,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_roundtrip_i24() {
+        let max = (1i32 << 23) - 1;
+        let min = -(1i32 << 23);
+        let original: Vec<i32> = vec![
+            min,
+            min + 1,
+            -4_194_304,
+            -256,
+            -1,
+            0,
+            1,
+            255,
+            4_194_304,
+            max - 1,
+            max,
+        ];
+        let bytes: Vec<u8> = original
+            .iter()
+            .flat_map(|&s| {
+                let b = s.to_le_bytes();
+                [b[0], b[1], b[2]]
+            })
+            .collect();
+
+        let processor = AudioProcessor::new(AudioFormat::new(96000, 2, SampleFormat::I24));
+        let f64_samples = processor.convert_to_f64(&bytes).unwrap();
+        let encoded = SampleFormatConverter::convert_from_f64(&f64_samples, SampleFormat::I24);
+
+        assert_eq!(encoded, bytes);
+        assert_eq!(SampleFormatConverter::f64_to_i24(&f64_samples), original);
+    }
+
     #[test]
     fn test_audio_processor_i16() {
         let format = AudioFormat::new(44100, 2, SampleFormat::I16);
@@ -795,6 +1837,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normalize_to_peak() {
+        // -6 dB peak
+        let mut samples: Vec<f64> = (0..1000).map(|i| (i as f64 * 0.05).sin() * 0.5).collect();
+        AudioProcessor::normalize_to_peak(&mut samples, 0.0);
+        let peak = samples.iter().fold(0.0f64, |peak, s| peak.max(s.abs()));
+        assert!((peak - 1.0).abs() < 1e-9);
+
+        // Targets above the ceiling are capped
+        AudioProcessor::normalize_to_peak(&mut samples, 6.0);
+        let peak = samples.iter().fold(0.0f64, |peak, s| peak.max(s.abs()));
+        assert!(peak <= 1.0 + 1e-9);
+
+        let mut silent = vec![0.0; 1000];
+        AudioProcessor::normalize_to_peak(&mut silent, 0.0);
+        assert!(silent.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_normalize_to_lufs() {
+        let rate = 48000;
+        let tone = |amplitude: f64| -> Vec<f64> {
+            (0..rate * 3)
+                .flat_map(|i| {
+                    let s = (2.0 * std::f64::consts::PI * 1000.0 * i as f64 / rate as f64).sin()
+                        * amplitude;
+                    [s, s]
+                })
+                .collect()
+        };
+
+        let mut samples = tone(0.05);
+        AudioProcessor::normalize_to_lufs(&mut samples, 2, rate, -23.0);
+        let loudness = measure_lufs(&samples, rate, 2);
+        assert!((loudness.integrated_lufs + 23.0).abs() < 0.1);
+
+        // Loud targets stop at the clip-safe ceiling
+        let mut samples = tone(0.05);
+        AudioProcessor::normalize_to_lufs(&mut samples, 2, rate, 10.0);
+        assert!(measure_lufs(&samples, rate, 2).true_peak <= 1.0 + 1e-9);
+
+        let mut silent = vec![0.0; rate as usize * 2];
+        AudioProcessor::normalize_to_lufs(&mut silent, 2, rate, -14.0);
+        assert!(silent.iter().all(|&s| s == 0.0));
+    }
+
     #[test]
     fn test_volume_control() {
         let format = AudioFormat::new(44100, 2, SampleFormat::F32);
@@ -830,6 +1918,31 @@ mod tests {
         assert!((samples[3] - (-0.25)).abs() < 1e-10);
     }
 
+    #[test]
+    fn test_channel_gains() {
+        let format = AudioFormat::new(44100, 2, SampleFormat::F32);
+        let mut processor = AudioProcessor::new(format);
+
+        assert!(matches!(
+            processor.set_channel_gains(vec![1.0, 1.0, 1.0]),
+            Err(crate::Error::InvalidParameter(_))
+        ));
+        assert!(processor.set_channel_gains(vec![1.0, -0.5]).is_err());
+        assert!(processor.channel_gains().is_empty());
+
+        // Balance fully left: right silenced, left at full scale
+        processor
+            .set_channel_gains(AudioProcessor::balance_gains(-1.0).to_vec())
+            .unwrap();
+        processor.set_volume(0.5);
+        let mut samples = vec![1.0, 1.0, -0.8, -0.8];
+        processor.apply_volume(&mut samples);
+        assert_eq!(samples, vec![0.5, 0.0, -0.4, 0.0]);
+
+        assert_eq!(AudioProcessor::balance_gains(0.0), [1.0, 1.0]);
+        assert_eq!(AudioProcessor::balance_gains(0.5), [0.5, 1.0]);
+    }
+
     #[test]
     fn test_apply_volume_static() {
         let mut samples = vec![1.0, -1.0, 0.5, -0.5];
@@ -1276,6 +2389,30 @@ mod tests {
         assert_eq!(f64_dithered, f64_undithered);
     }
 
+    #[test]
+    fn test_dither_seed_reproducible() {
+        let samples: Vec<f64> = (0..256).map(|i| (i as f64 * 0.05).sin() * 0.5).collect();
+        let render = |ditherer: &mut Ditherer| {
+            SampleFormatConverter::convert_from_f64_dithered(&samples, SampleFormat::I16, ditherer)
+        };
+
+        let mut first = Ditherer::with_seed(DitheringAlgorithm::Triangular, 42);
+        let mut second = Ditherer::with_seed(DitheringAlgorithm::Triangular, 42);
+        let mut other = Ditherer::with_seed(DitheringAlgorithm::Triangular, 7);
+        let reference = render(&mut first);
+        assert_eq!(render(&mut second), reference);
+        assert_ne!(render(&mut other), reference);
+
+        // Reseeding restarts the sequence
+        other.reseed(42);
+        assert_eq!(render(&mut other), reference);
+
+        // The default constructor keeps its fixed seed
+        let mut default = Ditherer::new(DitheringAlgorithm::Triangular);
+        let mut seeded = Ditherer::with_seed(DitheringAlgorithm::Triangular, DEFAULT_DITHER_SEED);
+        assert_eq!(render(&mut default), render(&mut seeded));
+    }
+
     // ============================================================================
     // Precision Preservation Tests
     // ============================================================================
@@ -1608,4 +2745,349 @@ mod tests {
             "Last sample should be near 1"
         );
     }
+
+    fn estimate_frequency(samples: &[f64], sample_rate: f64) -> f64 {
+        let crossings = samples
+            .windows(2)
+            .filter(|w| w[0] <= 0.0 && w[1] > 0.0)
+            .count();
+        crossings as f64 * sample_rate / samples.len() as f64
+    }
+
+    #[test]
+    fn test_time_stretch_double_speed_preserves_pitch() {
+        let sample_rate = 44100.0;
+        let input: Vec<f64> = (0..44100)
+            .map(|i| 0.5 * (2.0 * std::f64::consts::PI * 440.0 * i as f64 / sample_rate).sin())
+            .collect();
+
+        let mut stretcher = TimeStretcher::new(1, 2.0);
+        let output = stretcher.process(&input);
+
+        // ~0.5 seconds of output (minus the lookahead still buffered)
+        let seconds = output.len() as f64 / sample_rate;
+        assert!(
+            (seconds - 0.5).abs() < 0.05,
+            "Expected ~0.5s of output, got {}s",
+            seconds
+        );
+
+        // Skip the fade-in of the first window before measuring pitch
+        let steady = &output[STRETCH_WINDOW_FRAMES..];
+        let frequency = estimate_frequency(steady, sample_rate);
+        assert!(
+            (frequency - 440.0).abs() < 10.0,
+            "Expected ~440Hz, got {}Hz",
+            frequency
+        );
+    }
+
+    #[test]
+    fn test_time_stretch_slow_speed_length() {
+        let input: Vec<f64> = (0..2 * 44100)
+            .map(|i| (2.0 * std::f64::consts::PI * 220.0 * i as f64 / 44100.0).sin())
+            .collect();
+
+        let mut stretcher = TimeStretcher::new(2, 0.5);
+        let output = stretcher.process(&input);
+
+        // One second of stereo input becomes ~two seconds of stereo output
+        let frames = output.len() / 2;
+        assert!((frames as f64 / 44100.0 - 2.0).abs() < 0.05);
+        assert_eq!(output.len() % 2, 0);
+    }
+
+    #[test]
+    fn test_time_stretch_passthrough() {
+        let input: Vec<f64> = (0..1000).map(|i| i as f64 / 1000.0).collect();
+        let mut stretcher = TimeStretcher::new(2, 1.0);
+
+        assert!(stretcher.is_passthrough());
+        assert_eq!(stretcher.process(&input), input);
+    }
+
+    #[test]
+    fn test_time_stretch_rate_clamped() {
+        assert_eq!(TimeStretcher::new(2, 10.0).rate(), MAX_PLAYBACK_RATE);
+        assert_eq!(TimeStretcher::new(2, 0.1).rate(), MIN_PLAYBACK_RATE);
+    }
+
+    #[test]
+    fn test_time_stretch_block_processing_matches_single_call() {
+        let input: Vec<f64> = (0..20000)
+            .map(|i| (2.0 * std::f64::consts::PI * 330.0 * i as f64 / 44100.0).sin())
+            .collect();
+
+        let mut whole = TimeStretcher::new(1, 1.5);
+        let expected = whole.process(&input);
+
+        let mut blocks = TimeStretcher::new(1, 1.5);
+        let mut actual = Vec::new();
+        for chunk in input.chunks(333) {
+            actual.extend(blocks.process(chunk));
+        }
+
+        assert_eq!(actual.len(), expected.len());
+        for (a, b) in actual.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_interleave_roundtrip() {
+        for channels in [1u16, 2, 6] {
+            let processor =
+                AudioProcessor::new(AudioFormat::new(48000, channels, SampleFormat::F32));
+            let interleaved: Vec<f64> = (0..channels as usize * 100)
+                .map(|i| (i as f64 * 0.37).sin())
+                .collect();
+
+            let planar = processor.deinterleave(&interleaved).unwrap();
+            assert_eq!(planar.len(), channels as usize);
+            assert!(planar.iter().all(|c| c.len() == 100));
+            assert_eq!(
+                planar[channels as usize - 1][1],
+                interleaved[2 * channels as usize - 1]
+            );
+
+            assert_eq!(processor.interleave(&planar).unwrap(), interleaved);
+        }
+    }
+
+    #[test]
+    fn test_interleave_validation() {
+        let processor = AudioProcessor::new(AudioFormat::new(44100, 2, SampleFormat::I16));
+
+        assert!(processor.deinterleave(&[0.0, 1.0, 2.0]).is_err());
+        assert!(processor.interleave(&[vec![0.0; 4]]).is_err());
+        assert!(processor.interleave(&[vec![0.0; 4], vec![0.0; 3]]).is_err());
+        assert!(processor.interleave(&[vec![], vec![]]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_detect_silence_bounds() {
+        // 100-frame windows
+        let rate = 10_000;
+        let channels = 2;
+        let mut samples = vec![0.0; 1000 * channels];
+        for frame in 200..700 {
+            let value = (frame as f64 * 0.1).sin() * 0.5 + 0.25;
+            samples[frame * channels] = value;
+            samples[frame * channels + 1] = -value;
+        }
+        // An isolated click in the intro isn't sound
+        samples[50 * channels] = 0.9;
+
+        assert_eq!(
+            detect_silence_bounds(&samples, channels as u16, rate, -60.0),
+            Some((200, 699))
+        );
+
+        assert_eq!(detect_silence_bounds(&[0.0; 100], 2, rate, -60.0), None);
+        // Sound right at the start is distinct from silence
+        let mut samples = vec![0.0; 100];
+        samples[..SILENCE_MIN_SOUND_WINDOWS * 10].fill(0.5);
+        assert_eq!(
+            detect_silence_bounds(&samples, 1, 1000, -60.0),
+            Some((0, SILENCE_MIN_SOUND_WINDOWS as u64 * 10 - 1))
+        );
+    }
+
+    #[test]
+    fn test_detect_silence_bounds_keeps_quiet_tone() {
+        // A -40 dBFS 1 kHz sine crosses zero far more often than the
+        // minimum sound length, but its level is well above -60 dBFS
+        let rate = 44100;
+        let lead = 22050;
+        let tone = 44100;
+        let amplitude = AudioProcessor::db_to_linear(-40.0);
+        let mut samples = vec![0.0; (lead + tone + lead) * 2];
+        for frame in 0..tone {
+            let t = frame as f64 / rate as f64;
+            let value = amplitude * (2.0 * std::f64::consts::PI * 1000.0 * t).sin();
+            samples[(lead + frame) * 2] = value;
+            samples[(lead + frame) * 2 + 1] = value;
+        }
+
+        let (start, end) = detect_silence_bounds(&samples, 2, rate, -60.0).unwrap();
+        assert!((lead..=lead + 1).contains(&(start as usize)));
+        assert!((lead + tone - 2..lead + tone).contains(&(end as usize)));
+    }
+
+    #[test]
+    fn test_compute_peaks_bucket_count() {
+        let samples: Vec<f64> = (0..2 * 10_000).map(|i| (i as f64 * 0.01).sin()).collect();
+
+        for buckets in [1, 7, 800, 10_000, 25_000] {
+            assert_eq!(compute_peaks(&samples, 2, buckets).len(), buckets);
+        }
+        assert_eq!(compute_peaks(&[], 2, 16), vec![(0.0, 0.0); 16]);
+    }
+
+    #[test]
+    fn test_compute_peaks_full_scale_region() {
+        // Quiet first half, full-scale square wave second half
+        let mut samples = vec![0.01; 2 * 1000];
+        samples.extend((0..2 * 1000).map(|i| if (i / 2) % 2 == 0 { 1.0 } else { -1.0 }));
+
+        let peaks = compute_peaks(&samples, 2, 4);
+        assert!(peaks[0].1 < 0.02);
+        for &(min, max) in &peaks[2..] {
+            assert!((min + 1.0).abs() < 1e-6);
+            assert!((max - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_compute_peaks_for_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peaks.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..44100 {
+            let value = if i < 22050 { 0 } else { i16::MAX };
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let peaks = compute_peaks_for_file(&path, 100).unwrap();
+        assert_eq!(peaks.len(), 100);
+        assert_eq!(peaks[10], (0.0, 0.0));
+        assert!(peaks[90].1 > 0.99);
+    }
+
+    #[test]
+    fn test_saturation_is_bounded_and_bypassable() {
+        let sine: Vec<f64> = (0..4800)
+            .map(|i| (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 48000.0).sin())
+            .collect();
+
+        let tanh = Saturator::new(SaturationConfig::new(
+            SaturationCurve::Tanh,
+            MAX_SATURATION_DRIVE_DB,
+        ))
+        .unwrap();
+        let mut driven = sine.clone();
+        tanh.process_block(&mut driven);
+        assert!(driven.iter().all(|s| (-1.0..=1.0).contains(s)));
+        assert!(driven.iter().any(|&s| s > 0.99));
+
+        // Zero drive is a bit-exact passthrough, even above full scale
+        let bypass = Saturator::new(SaturationConfig::default()).unwrap();
+        assert!(bypass.is_bypassed());
+        let mut loud: Vec<f64> = sine.iter().map(|s| s * 1.5).collect();
+        let original = loud.clone();
+        bypass.process_block(&mut loud);
+        assert_eq!(loud, original);
+
+        // Monotonic and bounded for any input level
+        for curve in [
+            SaturationCurve::Tanh,
+            SaturationCurve::Cubic,
+            SaturationCurve::Arctangent,
+        ] {
+            let saturator = Saturator::new(SaturationConfig::new(curve, 12.0)).unwrap();
+            let mut previous = f64::NEG_INFINITY;
+            for i in -1000..=1000 {
+                let out = saturator.process(i as f64 / 100.0);
+                assert!(out >= previous, "{:?} not monotonic", curve);
+                assert!((-1.0..=1.0).contains(&out));
+                previous = out;
+            }
+        }
+
+        assert!(Saturator::new(SaturationConfig::new(SaturationCurve::Cubic, -1.0)).is_err());
+        assert!(Saturator::new(SaturationConfig::new(SaturationCurve::Cubic, f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_stereo_width() {
+        let input: Vec<f64> = (0..512)
+            .flat_map(|i| {
+                let t = i as f64 / 48.0;
+                [t.sin() * 0.7, (t * 1.3).cos() * 0.3]
+            })
+            .collect();
+
+        let mut unchanged = input.clone();
+        StereoWidth::new(1.0)
+            .unwrap()
+            .process_interleaved(&mut unchanged);
+        assert_eq!(unchanged, input);
+
+        let mut mono = input.clone();
+        StereoWidth::new(0.0)
+            .unwrap()
+            .process_interleaved(&mut mono);
+        for frame in mono.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+
+        // Hard-panned input doesn't gain energy when widened
+        let wide = StereoWidth::new(MAX_STEREO_WIDTH).unwrap();
+        let (left, right) = wide.process(1.0, 0.0);
+        assert!(left * left + right * right <= 1.0 + 1e-9);
+
+        assert!(StereoWidth::new(-0.1).is_err());
+        assert!(StereoWidth::new(MAX_STEREO_WIDTH + 0.1).is_err());
+        assert!(StereoWidth::new(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_streaming_sinc_resampler_keeps_frequency() {
+        let source_rate = 44100;
+        let target_rate = 48000;
+        let frequency = 1000.0;
+        let input: Vec<f64> = (0..source_rate)
+            .flat_map(|i| {
+                let s = (2.0 * std::f64::consts::PI * frequency * i as f64 / source_rate as f64)
+                    .sin()
+                    * 0.5;
+                [s, -s]
+            })
+            .collect();
+
+        let mut converter =
+            SampleRateConverter::with_quality(source_rate, target_rate, 2, ResampleQuality::Sinc);
+        let mut output = Vec::new();
+        let mut block = vec![0.0; 512 * 2];
+        // Stream in uneven blocks
+        for chunk in input.chunks(777 * 2) {
+            converter.push(chunk.iter().copied());
+            loop {
+                let written = converter.read(&mut block);
+                output.extend_from_slice(&block[..written]);
+                if written < block.len() {
+                    break;
+                }
+            }
+        }
+
+        let frames = output.len() / 2;
+        assert!(
+            (frames as i64 - target_rate as i64).abs() < 64,
+            "{} frames",
+            frames
+        );
+
+        // Compare against the ideal tone at the output rate, skipping the
+        // kernel's start-up
+        let mut max_error: f64 = 0.0;
+        for (i, frame) in output.chunks(2).enumerate().skip(64).take(40000) {
+            let expected = (2.0 * std::f64::consts::PI * frequency * i as f64 / target_rate as f64)
+                .sin()
+                * 0.5;
+            max_error = max_error.max((frame[0] - expected).abs());
+            assert_eq!(frame[0], -frame[1]);
+        }
+        assert!(max_error < 1e-3, "max error {}", max_error);
+
+        assert_eq!(converter.input_frames_needed(0), 0);
+        assert!(converter.input_frames_needed(480) > 0);
+    }
 }
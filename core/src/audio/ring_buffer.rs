@@ -3,7 +3,9 @@
 //! Provides zero-copy audio data flow between decoder and output with minimal latency
 
 use crate::audio::format::AudioFormat;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// Lock-free ring buffer for audio samples
@@ -16,6 +18,18 @@ pub struct AudioRingBuffer {
     write_pos: AtomicUsize,
     /// Read position (consumer)
     read_pos: AtomicUsize,
+    /// Total samples ever written
+    written_total: AtomicU64,
+    /// Total samples ever consumed, including ones dropped by overwrites
+    read_total: AtomicU64,
+    /// Source timestamps as (sample index, stream frame) anchors, oldest first
+    timeline: Mutex<VecDeque<(u64, u64)>>,
+    /// Set once the producer is dropped; no more samples will arrive
+    closed: AtomicBool,
+    /// Set while a `ReadSlices` guard borrows unread samples in place
+    slices_borrowed: AtomicBool,
+    /// Whether the buffer was configured to overwrite old data when full
+    allow_overwrite: bool,
     /// Audio format
     format: AudioFormat,
 }
@@ -35,6 +49,12 @@ pub struct RingBufferConsumer {
     underrun_count: Arc<AtomicUsize>,
 }
 
+/// Shortest allowed ring buffer duration in seconds
+pub const MIN_BUFFER_DURATION_SECONDS: f64 = 0.1;
+
+/// Longest allowed ring buffer duration in seconds
+pub const MAX_BUFFER_DURATION_SECONDS: f64 = 30.0;
+
 /// Configuration for ring buffer creation
 #[derive(Debug, Clone)]
 pub struct RingBufferConfig {
@@ -55,10 +75,10 @@ impl RingBufferConfig {
         format: AudioFormat,
         allow_overwrite: bool,
     ) -> Result<Self, String> {
-        if buffer_duration_seconds < 0.1 {
+        if buffer_duration_seconds < MIN_BUFFER_DURATION_SECONDS {
             return Err("Buffer duration must be at least 0.1 seconds".to_string());
         }
-        if buffer_duration_seconds > 30.0 {
+        if buffer_duration_seconds > MAX_BUFFER_DURATION_SECONDS {
             return Err("Buffer duration must not exceed 30 seconds".to_string());
         }
 
@@ -113,10 +133,10 @@ impl RingBufferConfig {
 
     /// Validate the configuration
     pub fn validate(&self) -> Result<(), String> {
-        if self.buffer_duration_seconds < 0.1 {
+        if self.buffer_duration_seconds < MIN_BUFFER_DURATION_SECONDS {
             return Err("Buffer duration must be at least 0.1 seconds".to_string());
         }
-        if self.buffer_duration_seconds > 30.0 {
+        if self.buffer_duration_seconds > MAX_BUFFER_DURATION_SECONDS {
             return Err("Buffer duration must not exceed 30 seconds".to_string());
         }
         if self.format.sample_rate == 0 {
@@ -156,6 +176,12 @@ impl AudioRingBuffer {
             capacity: total_samples,
             write_pos: AtomicUsize::new(0),
             read_pos: AtomicUsize::new(0),
+            written_total: AtomicU64::new(0),
+            read_total: AtomicU64::new(0),
+            timeline: Mutex::new(VecDeque::new()),
+            closed: AtomicBool::new(false),
+            slices_borrowed: AtomicBool::new(false),
+            allow_overwrite: config.allow_overwrite,
             format: config.format,
         });
 
@@ -265,6 +291,9 @@ impl RingBufferProducer {
 
         // Update write position
         let new_write_pos = (write_pos + to_write) % capacity;
+        self.buffer
+            .written_total
+            .fetch_add(to_write as u64, Ordering::AcqRel);
         self.buffer
             .write_pos
             .store(new_write_pos, Ordering::Release);
@@ -274,7 +303,13 @@ impl RingBufferProducer {
 
     /// Write samples with potential overwrite if buffer is full
     /// Returns the number of samples actually written
+    ///
+    /// While the consumer borrows samples with `read_slices` nothing unread
+    /// is overwritten; this then writes like `write`.
     pub fn write_overwrite(&self, samples: &[f64]) -> usize {
+        if self.buffer.slices_borrowed.load(Ordering::Acquire) {
+            return self.write(samples);
+        }
         let capacity = self.buffer.capacity;
         let to_write = samples.len().min(capacity - 1); // Leave one sample gap
 
@@ -283,6 +318,7 @@ impl RingBufferProducer {
         }
 
         let write_pos = self.buffer.write_pos.load(Ordering::Acquire);
+        let overwritten = (self.buffer.available_read() + to_write).saturating_sub(capacity - 1);
 
         // Handle wrap-around
         if write_pos + to_write <= capacity {
@@ -321,22 +357,40 @@ impl RingBufferProducer {
 
         // Update write position
         let new_write_pos = (write_pos + to_write) % capacity;
+        self.buffer
+            .written_total
+            .fetch_add(to_write as u64, Ordering::AcqRel);
         self.buffer
             .write_pos
             .store(new_write_pos, Ordering::Release);
 
-        // If we overwrote data, advance read position
-        let available_after_write = self.buffer.available_read();
-        if available_after_write >= capacity - 1 {
-            // We overwrote some data, advance read position
-            let _read_pos = self.buffer.read_pos.load(Ordering::Acquire);
+        // If we overwrote data, advance read position past it
+        if overwritten > 0 {
             let new_read_pos = (new_write_pos + 1) % capacity;
+            self.buffer
+                .read_total
+                .fetch_add(overwritten as u64, Ordering::AcqRel);
             self.buffer.read_pos.store(new_read_pos, Ordering::Release);
         }
 
         to_write
     }
 
+    /// Record that the next sample written starts stream frame `frame`
+    ///
+    /// Lets the consumer report the source position of what it reads.
+    /// Only discontinuities (the first packet, seeks, loop restarts) need
+    /// marking; positions in between are derived from the sample count.
+    pub fn mark_timestamp(&self, frame: u64) {
+        let index = self.buffer.written_total.load(Ordering::Acquire);
+        let mut timeline = self.buffer.timeline.lock();
+        // A newer mark at the same index supersedes the old one
+        if timeline.back().is_some_and(|&(last, _)| last == index) {
+            timeline.pop_back();
+        }
+        timeline.push_back((index, frame));
+    }
+
     /// Get the number of samples that can be written without blocking
     pub fn available_write(&self) -> usize {
         self.buffer.available_write()
@@ -348,10 +402,20 @@ impl RingBufferProducer {
     }
 }
 
+impl Drop for RingBufferProducer {
+    fn drop(&mut self) {
+        self.buffer.closed.store(true, Ordering::Release);
+    }
+}
+
 impl RingBufferConsumer {
     /// Read samples from the ring buffer
     /// Returns the number of samples actually read
     pub fn read(&self, output: &mut [f64]) -> usize {
+        // A clone's `ReadSlices` guard still borrows the next samples
+        if self.buffer.slices_borrowed.load(Ordering::Acquire) {
+            return 0;
+        }
         let available = self.buffer.available_read();
         let to_read = output.len().min(available);
 
@@ -399,6 +463,9 @@ impl RingBufferConsumer {
 
         // Update read position
         let new_read_pos = (read_pos + to_read) % capacity;
+        self.buffer
+            .read_total
+            .fetch_add(to_read as u64, Ordering::AcqRel);
         self.buffer.read_pos.store(new_read_pos, Ordering::Release);
 
         to_read
@@ -475,6 +542,9 @@ impl RingBufferConsumer {
 
     /// Skip samples without reading them
     pub fn skip(&self, count: usize) -> usize {
+        if self.buffer.slices_borrowed.load(Ordering::Acquire) {
+            return 0;
+        }
         let available = self.buffer.available_read();
         let to_skip = count.min(available);
 
@@ -484,21 +554,119 @@ impl RingBufferConsumer {
 
         let read_pos = self.buffer.read_pos.load(Ordering::Acquire);
         let new_read_pos = (read_pos + to_skip) % self.buffer.capacity;
+        self.buffer
+            .read_total
+            .fetch_add(to_skip as u64, Ordering::AcqRel);
         self.buffer.read_pos.store(new_read_pos, Ordering::Release);
 
         to_skip
     }
 
+    /// Borrow up to `max` buffered samples in place
+    ///
+    /// The samples are returned as up to two contiguous slices, the second
+    /// being the part that wrapped to the start of the buffer (empty if
+    /// none). The read position advances past them when the returned guard
+    /// is dropped. While it lives the consumer can't be used, and clones of
+    /// it read nothing:
+    ///
+    /// ```compile_fail
+    /// use contextune_core::audio::format::{AudioFormat, SampleFormat};
+    /// use contextune_core::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
+    ///
+    /// let format = AudioFormat::new(44100, 2, SampleFormat::F64);
+    /// let config = RingBufferConfig::standard(format);
+    /// let (_producer, mut consumer) = AudioRingBuffer::new(config).unwrap();
+    /// let slices = consumer.read_slices(64);
+    /// consumer.skip(64); // the guard still borrows the consumer
+    /// drop(slices);
+    /// ```
+    ///
+    /// Buffers configured with `allow_overwrite` may reuse unread space at
+    /// any time, so they never lend samples: the guard is empty.
+    pub fn read_slices(&mut self, max: usize) -> ReadSlices<'_> {
+        let borrowed = !self.buffer.allow_overwrite
+            && self
+                .buffer
+                .slices_borrowed
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok();
+        if !borrowed {
+            return ReadSlices {
+                consumer: self,
+                borrowed,
+                first: &[],
+                second: &[],
+            };
+        }
+
+        let len = max.min(self.buffer.available_read());
+        let read_pos = self.buffer.read_pos.load(Ordering::Acquire);
+        let capacity = self.buffer.capacity;
+        let first_len = len.min(capacity - read_pos);
+
+        // SAFETY: the range [read_pos, read_pos + len) (wrapping) holds data
+        // already published by the producer. `slices_borrowed` keeps every
+        // consumer from moving the read position and `write_overwrite` from
+        // overwriting unread data until `ReadSlices::drop`, so the producer
+        // won't write there while the slices live.
+        let (first, second) = unsafe {
+            let buffer_ptr = self.buffer.buffer.as_ptr();
+            (
+                std::slice::from_raw_parts(buffer_ptr.add(read_pos), first_len),
+                std::slice::from_raw_parts(buffer_ptr, len - first_len),
+            )
+        };
+
+        ReadSlices {
+            consumer: self,
+            borrowed,
+            first,
+            second,
+        }
+    }
+
     /// Get the number of samples available for reading
     pub fn available_read(&self) -> usize {
         self.buffer.available_read()
     }
 
+    /// Stream frame of the next sample to be read
+    ///
+    /// Derived from the anchors set by `RingBufferProducer::mark_timestamp`.
+    /// Returns `None` if nothing was marked yet, or if the producer holds
+    /// the timeline at the moment; this never blocks, so it is safe to call
+    /// from the audio callback.
+    pub fn source_position(&self) -> Option<u64> {
+        let read = self.buffer.read_total.load(Ordering::Acquire);
+        let mut timeline = self.buffer.timeline.try_lock()?;
+
+        // Drop anchors that playback has moved past
+        while timeline.get(1).is_some_and(|&(index, _)| index <= read) {
+            timeline.pop_front();
+        }
+
+        let &(index, frame) = timeline.front()?;
+        if index > read {
+            return None;
+        }
+        let channels = self.buffer.format.channels.max(1) as u64;
+        Some(frame + (read - index) / channels)
+    }
+
     /// Check if the buffer is empty
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
 
+    /// Check if the producer is gone, e.g. at the end of a decoded stream
+    ///
+    /// The samples still buffered are then all that will arrive, so an
+    /// empty buffer is the end of the source rather than an underrun.
+    pub fn is_closed(&self) -> bool {
+        self.buffer.closed.load(Ordering::Acquire)
+    }
+
     /// Check if buffer is experiencing underrun (below threshold)
     pub fn is_underrun(&self, threshold: f64) -> bool {
         let utilization = self.buffer.utilization();
@@ -510,6 +678,11 @@ impl RingBufferConsumer {
         self.underrun_count.load(Ordering::Acquire)
     }
 
+    /// Count an underrun noticed by the reader
+    pub fn record_underrun(&self) {
+        self.underrun_count.fetch_add(1, Ordering::AcqRel);
+    }
+
     /// Reset the underrun counter
     pub fn reset_underrun_count(&self) {
         self.underrun_count.store(0, Ordering::Release);
@@ -542,6 +715,52 @@ impl RingBufferConsumer {
     }
 }
 
+/// Samples borrowed from a `RingBufferConsumer` by `read_slices`
+///
+/// Consumes the borrowed samples when dropped.
+pub struct ReadSlices<'a> {
+    consumer: &'a mut RingBufferConsumer,
+    /// Whether this guard holds the buffer's borrow flag
+    borrowed: bool,
+    first: &'a [f64],
+    second: &'a [f64],
+}
+
+impl ReadSlices<'_> {
+    /// Both parts; the second is non-empty only if the data wrapped
+    pub fn as_slices(&self) -> (&[f64], &[f64]) {
+        (self.first, self.second)
+    }
+
+    /// Total number of borrowed samples
+    pub fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    /// Check if no samples were available
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for ReadSlices<'_> {
+    fn drop(&mut self) {
+        if !self.borrowed {
+            return;
+        }
+        let len = self.len();
+        let buffer = &self.consumer.buffer;
+        let capacity = buffer.capacity;
+        let _ = buffer
+            .read_pos
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pos| {
+                Some((pos + len) % capacity)
+            });
+        buffer.read_total.fetch_add(len as u64, Ordering::AcqRel);
+        buffer.slices_borrowed.store(false, Ordering::Release);
+    }
+}
+
 /// Ring buffer status information
 #[derive(Debug, Clone)]
 pub struct RingBufferStatus {
@@ -1024,4 +1243,123 @@ mod tests {
             assert_eq!(*sample, 0.0);
         }
     }
+
+    #[test]
+    fn test_read_slices_across_wrap() {
+        let format = AudioFormat::new(8, 1, SampleFormat::F64);
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format,
+            allow_overwrite: false,
+            underrun_threshold: 0.1,
+        };
+        let (producer, mut consumer) = AudioRingBuffer::new(config).unwrap();
+
+        // Move the read position near the end of the 8-sample buffer
+        producer.write(&[0.0; 6]);
+        consumer.skip(6);
+        producer.write(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        let observer = consumer.clone();
+
+        {
+            let slices = consumer.read_slices(4);
+            let (first, second) = slices.as_slices();
+            assert_eq!(first, &[1.0, 2.0]);
+            assert_eq!(second, &[3.0, 4.0]);
+            assert_eq!([first, second].concat(), vec![1.0, 2.0, 3.0, 4.0]);
+            // Nothing is consumed until the guard is dropped
+            assert_eq!(observer.available_read(), 5);
+        }
+        assert_eq!(consumer.available_read(), 1);
+
+        let slices = consumer.read_slices(10);
+        assert_eq!(slices.as_slices(), (&[5.0][..], &[][..]));
+        drop(slices);
+        assert!(consumer.read_slices(4).is_empty());
+    }
+
+    #[test]
+    fn test_read_slices_guard_blocks_other_reads() {
+        let format = AudioFormat::new(8, 1, SampleFormat::F64);
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format: format.clone(),
+            allow_overwrite: false,
+            underrun_threshold: 0.1,
+        };
+        let (producer, mut consumer) = AudioRingBuffer::new(config).unwrap();
+        let mut other = consumer.clone();
+        producer.write(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        let slices = consumer.read_slices(2);
+        assert_eq!(slices.as_slices(), (&[1.0, 2.0][..], &[][..]));
+        // A clone can neither move the read position nor borrow again
+        let mut output = [0.0; 4];
+        assert_eq!(other.read(&mut output), 0);
+        assert_eq!(other.skip(4), 0);
+        assert!(other.read_slices(4).is_empty());
+        // The producer can't overwrite the borrowed samples
+        assert_eq!(producer.write_overwrite(&[9.0; 6]), 2);
+        assert_eq!(slices.as_slices(), (&[1.0, 2.0][..], &[][..]));
+        drop(slices);
+
+        // Exactly the borrowed samples were consumed
+        assert_eq!(other.read(&mut output), 4);
+        assert_eq!(output, [3.0, 4.0, 5.0, 9.0]);
+
+        // Buffers that may overwrite unread data never lend it
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format,
+            allow_overwrite: true,
+            underrun_threshold: 0.1,
+        };
+        let (producer, mut consumer) = AudioRingBuffer::new(config).unwrap();
+        producer.write(&[1.0, 2.0]);
+        assert!(consumer.read_slices(2).is_empty());
+        assert_eq!(consumer.available_read(), 2);
+    }
+
+    #[test]
+    fn test_source_position_follows_marks() {
+        let format = AudioFormat::new(16, 2, SampleFormat::F64);
+        let config = RingBufferConfig {
+            buffer_duration_seconds: 1.0,
+            format,
+            allow_overwrite: false,
+            underrun_threshold: 0.1,
+        };
+        let (producer, consumer) = AudioRingBuffer::new(config).unwrap();
+        assert_eq!(consumer.source_position(), None);
+
+        // Two frames from 100, then a restart at frame 0
+        producer.mark_timestamp(100);
+        producer.write(&[0.0; 4]);
+        producer.mark_timestamp(0);
+        producer.write(&[0.0; 4]);
+
+        assert_eq!(consumer.source_position(), Some(100));
+        consumer.skip(2);
+        assert_eq!(consumer.source_position(), Some(101));
+        consumer.skip(2);
+        assert_eq!(consumer.source_position(), Some(0));
+        let mut output = [0.0; 2];
+        consumer.read(&mut output);
+        assert_eq!(consumer.source_position(), Some(1));
+    }
+
+    #[test]
+    fn test_closed_when_producer_dropped() {
+        let config = RingBufferConfig::standard(AudioFormat::new(100, 1, SampleFormat::F64));
+        let (producer, consumer) = AudioRingBuffer::new(config).unwrap();
+
+        producer.write(&[1.0, 2.0]);
+        assert!(!consumer.is_closed());
+        drop(producer);
+        assert!(consumer.is_closed());
+
+        // Buffered samples stay readable
+        let mut output = [0.0; 2];
+        assert_eq!(consumer.read(&mut output), 2);
+    }
 }
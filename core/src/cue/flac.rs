@@ -0,0 +1,232 @@
+//! Native FLAC CUESHEET metadata blocks
+//!
+//! Converts the binary CUESHEET block some rippers embed in place of a
+//! sidecar `.cue` into the same `CueSheet` the text parser produces
+
+use crate::cue::sheet::{CueFile, CueIndex, CueSheet, CueTime, CueTrack};
+use crate::error::Result;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// FLAC metadata block type of a CUESHEET block
+pub const FLAC_CUESHEET: u8 = 5;
+
+/// Size of the CUESHEET header before the track count
+const CUESHEET_HEADER_LEN: usize = 128 + 8 + 259;
+
+/// Size of a CUESHEET track before its index count
+const CUESHEET_TRACK_LEN: usize = 8 + 1 + 12 + 14;
+
+/// Size of a CUESHEET index point
+const CUESHEET_INDEX_LEN: usize = 8 + 1 + 3;
+
+/// Read the CUESHEET block of a FLAC file, if it has one
+///
+/// Only the metadata blocks are read. `sample_rate` is the stream's rate,
+/// which the block's sample offsets are converted with. Returns `None` for
+/// files that aren't FLAC, carry no CUESHEET block or a malformed one.
+pub fn read_flac_cuesheet<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Option<CueSheet>> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+
+    // Rippers sometimes put an ID3v2 tag in front of the stream
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+    let start = if header.starts_with(b"ID3") {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |size, &byte| (size << 7) | (byte & 0x7F) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    file.seek(SeekFrom::Start(start))?;
+    let mut magic = [0u8; 4];
+    if file.read_exact(&mut magic).is_err() || &magic != b"fLaC" {
+        return Ok(None);
+    }
+
+    loop {
+        let mut header = [0u8; 4];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        if header[0] & 0x7F == FLAC_CUESHEET {
+            let mut body = vec![0u8; len];
+            if file.read_exact(&mut body).is_err() {
+                return Ok(None);
+            }
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            return Ok(parse_flac_cuesheet(&body, sample_rate, &name));
+        }
+        if header[0] & 0x80 != 0 {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Current(len as i64))?;
+    }
+}
+
+/// Convert the body of a CUESHEET block into a single-file `CueSheet`
+///
+/// Track offsets count from the first sample of the FLAC stream, and index
+/// offsets from their track's offset. The lead-in (the CD's silence before
+/// the first track) is not part of the stream, so it doesn't shift any
+/// index. The lead-out and data tracks don't become tracks; their offsets
+/// end the audio track before them.
+/// Offsets are rounded to CUE frames (1/75 s), which is exact for CD
+/// sheets, where offsets are multiples of 588 samples.
+///
+/// # Arguments
+/// * `body` - CUESHEET block body
+/// * `sample_rate` - Sample rate of the stream
+/// * `file_name` - Name for the sheet's FILE entry
+///
+/// # Returns
+/// `None` if the block is malformed or has no audio tracks
+pub fn parse_flac_cuesheet(body: &[u8], sample_rate: u32, file_name: &str) -> Option<CueSheet> {
+    let u64_at = |offset: usize| -> Option<u64> {
+        Some(u64::from_be_bytes(
+            body.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+
+    let track_count = *body.get(CUESHEET_HEADER_LEN)? as usize;
+    let mut offset = CUESHEET_HEADER_LEN + 1;
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    // Whether the last entry read was an audio track, which the next data
+    // track or the lead-out ends
+    let mut open_audio = false;
+    for i in 0..track_count {
+        let track_offset = u64_at(offset)?;
+        let number = *body.get(offset + 8)?;
+        let is_audio = body.get(offset + 8 + 1 + 12)? & 0x80 == 0;
+        let index_count = *body.get(offset + CUESHEET_TRACK_LEN)? as usize;
+        offset += CUESHEET_TRACK_LEN + 1;
+
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let index_offset = u64_at(offset)?;
+            indices.push(CueIndex {
+                number: *body.get(offset + 8)? as u32,
+                time: CueTime::from_samples(track_offset + index_offset, sample_rate),
+            });
+            offset += CUESHEET_INDEX_LEN;
+        }
+
+        // The last track is the lead-out
+        let is_lead_out = i + 1 == track_count;
+        if (is_lead_out || !is_audio) && open_audio {
+            if let Some(track) = tracks.last_mut() {
+                track.end = Some(CueTime::from_samples(track_offset, sample_rate));
+            }
+        }
+        open_audio = is_audio && !is_lead_out;
+        if open_audio {
+            tracks.push(CueTrack {
+                number: number as u32,
+                indices,
+                ..Default::default()
+            });
+        }
+    }
+
+    if tracks.is_empty() {
+        return None;
+    }
+    Some(CueSheet {
+        title: None,
+        performer: None,
+        files: vec![CueFile {
+            path: file_name.to_string(),
+            file_type: "WAVE".to_string(),
+            tracks,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::decoder::AudioDecoder;
+    use crate::cue::virtual_track::from_cue_sheet;
+    use crate::test_util::{flac_cuesheet_block, write_verbatim_flac_with_metadata};
+
+    #[test]
+    fn test_embedded_cuesheet_yields_virtual_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("album.flac");
+        // 16384 frames: track 2 has a 588-sample pregap before 5880, and a
+        // data track sits before the lead-out
+        let block = flac_cuesheet_block(
+            88200,
+            &[
+                (0, 1, true, &[(0, 1)]),
+                (5292, 2, true, &[(0, 0), (588, 1)]),
+                (11760, 3, true, &[(0, 1), (1176, 2)]),
+                (14700, 4, false, &[(0, 1)]),
+                (16464, 170, true, &[]),
+            ],
+        );
+        write_verbatim_flac_with_metadata(&path, 4, &[(FLAC_CUESHEET, block)]);
+
+        let decoder = AudioDecoder::new(&path).unwrap();
+        let sheet = decoder.embedded_cuesheet().unwrap();
+        let file = &sheet.files[0];
+        assert_eq!(file.path, "album.flac");
+        let numbers: Vec<_> = file.tracks.iter().map(|track| track.number).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        // Index 00 is the pregap; offsets are relative to the track
+        assert_eq!(file.tracks[1].index(0), Some(CueTime::from_total_frames(9)));
+        assert_eq!(file.tracks[1].start(), Some(CueTime::from_total_frames(10)));
+        assert_eq!(
+            file.tracks[2].index(2),
+            Some(CueTime::from_total_frames(22))
+        );
+
+        let tracks = from_cue_sheet(&sheet, dir.path(), 44100);
+        let spans: Vec<_> = tracks
+            .iter()
+            .map(|track| (track.number, track.start_sample, track.end_sample))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (1, 0, Some(5880)),
+                (2, 5880, Some(11760)),
+                (3, 11760, Some(14700))
+            ]
+        );
+        assert_eq!(tracks[0].file_path, path);
+
+        // Files without the block have no embedded sheet
+        let plain = dir.path().join("plain.flac");
+        write_verbatim_flac_with_metadata(&plain, 1, &[]);
+        assert!(AudioDecoder::new(&plain)
+            .unwrap()
+            .embedded_cuesheet()
+            .is_none());
+        assert!(read_flac_cuesheet(&plain, 44100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_malformed_cuesheet_is_ignored() {
+        let block = flac_cuesheet_block(0, &[(0, 1, true, &[(0, 1)]), (4096, 170, true, &[])]);
+        assert!(parse_flac_cuesheet(&block, 44100, "a.flac").is_some());
+        assert!(parse_flac_cuesheet(&block[..block.len() - 1], 44100, "a.flac").is_none());
+        // The lead-out ends the last track
+        let sheet = parse_flac_cuesheet(&block, 44100, "a.flac").unwrap();
+        let track = &sheet.files[0].tracks[0];
+        assert_eq!(track.end, Some(CueTime::from_samples(4096, 44100)));
+        // Only a lead-out
+        let block = flac_cuesheet_block(0, &[(4096, 170, true, &[])]);
+        assert!(parse_flac_cuesheet(&block, 44100, "a.flac").is_none());
+    }
+}
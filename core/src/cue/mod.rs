@@ -2,8 +2,12 @@
 //!
 //! Handles CUE file parsing and virtual track creation
 
+pub mod flac;
 pub mod parser;
 pub mod sheet;
 pub mod virtual_track;
 
-// Will be implemented in Phase 2
+pub use flac::{parse_flac_cuesheet, read_flac_cuesheet};
+pub use parser::parse_cue;
+pub use sheet::{generate_cue, CueFile, CueIndex, CueSheet, CueTime, CueTrack};
+pub use virtual_track::VirtualTrack;
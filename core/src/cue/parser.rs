@@ -2,4 +2,245 @@
 //!
 //! Parses CUE files using nom parser combinator
 
-// Will be implemented in Phase 2
+use crate::cue::sheet::{CueFile, CueIndex, CueSheet, CueTime, CueTrack};
+use crate::error::{Error, Result};
+use nom::branch::alt;
+use nom::bytes::complete::{escaped_transform, is_not, tag, take_while1};
+use nom::character::complete::{char, space0, space1, u32 as parse_u32};
+use nom::combinator::{map, value};
+use nom::sequence::{delimited, preceded};
+use nom::{IResult, Parser};
+
+/// Parse a quoted string, honoring `\"` and `\\` escapes
+fn quoted(input: &str) -> IResult<&str, String> {
+    alt((
+        value(String::new(), tag("\"\"")),
+        delimited(
+            char('"'),
+            escaped_transform(
+                is_not("\\\""),
+                '\\',
+                alt((value("\\", tag("\\")), value("\"", tag("\"")))),
+            ),
+            char('"'),
+        ),
+    ))
+    .parse(input)
+}
+
+/// Parse a quoted string or a bare word
+fn string_value(input: &str) -> IResult<&str, String> {
+    alt((
+        quoted,
+        map(take_while1(|c: char| !c.is_whitespace()), str::to_string),
+    ))
+    .parse(input)
+}
+
+/// Parse a MM:SS:FF timestamp
+fn cue_time(input: &str) -> IResult<&str, CueTime> {
+    map(
+        (
+            parse_u32,
+            preceded(char(':'), parse_u32),
+            preceded(char(':'), parse_u32),
+        ),
+        |(minutes, seconds, frames)| CueTime {
+            minutes,
+            seconds,
+            frames,
+        },
+    )
+    .parse(input)
+}
+
+/// Parse the arguments of a FILE command: path and type
+fn file_args(input: &str) -> IResult<&str, (String, String)> {
+    (string_value, preceded(space0, string_value_or_empty)).parse(input)
+}
+
+/// Parse an optional string value
+fn string_value_or_empty(input: &str) -> IResult<&str, String> {
+    if input.trim().is_empty() {
+        Ok((input, String::new()))
+    } else {
+        string_value(input)
+    }
+}
+
+/// Parse the arguments of an INDEX command
+fn index_args(input: &str) -> IResult<&str, CueIndex> {
+    map((parse_u32, preceded(space1, cue_time)), |(number, time)| {
+        CueIndex { number, time }
+    })
+    .parse(input)
+}
+
+/// Run a parser on a command's arguments
+fn parse_args<'a, O>(
+    line_number: usize,
+    args: &'a str,
+    parser: impl Fn(&'a str) -> IResult<&'a str, O>,
+) -> Result<O> {
+    parser(args)
+        .map(|(_, out)| out)
+        .map_err(|e| Error::CueParsing(format!("Line {}: invalid arguments: {}", line_number, e)))
+}
+
+/// Parse the text of a CUE sheet
+///
+/// Unknown commands (REM, FLAGS, ISRC, ...) are ignored.
+pub fn parse_cue(text: &str) -> Result<CueSheet> {
+    let mut sheet = CueSheet::default();
+    let mut in_track = false;
+
+    // Strip a UTF-8 byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (command, args) = match line.split_once(char::is_whitespace) {
+            Some((command, args)) => (command, args.trim()),
+            None => (line, ""),
+        };
+
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                let (path, file_type) = parse_args(line_number, args, file_args)?;
+                sheet.files.push(CueFile {
+                    path,
+                    file_type,
+                    tracks: Vec::new(),
+                });
+                in_track = false;
+            }
+            "TRACK" => {
+                let number = parse_args(line_number, args, parse_u32)?;
+                let file = sheet.files.last_mut().ok_or_else(|| {
+                    Error::CueParsing(format!("Line {}: TRACK before FILE", line_number))
+                })?;
+                file.tracks.push(CueTrack {
+                    number,
+                    ..Default::default()
+                });
+                in_track = true;
+            }
+            "INDEX" => {
+                let index = parse_args(line_number, args, index_args)?;
+                let track = current_track(&mut sheet, in_track).ok_or_else(|| {
+                    Error::CueParsing(format!("Line {}: INDEX outside TRACK", line_number))
+                })?;
+                track.indices.push(index);
+            }
+            "TITLE" | "PERFORMER" => {
+                let text = parse_args(line_number, args, string_value)?;
+                let is_title = command.eq_ignore_ascii_case("TITLE");
+                match current_track(&mut sheet, in_track) {
+                    Some(track) if is_title => track.title = Some(text),
+                    Some(track) => track.performer = Some(text),
+                    None if is_title => sheet.title = Some(text),
+                    None => sheet.performer = Some(text),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(sheet)
+}
+
+/// The track commands currently apply to
+fn current_track(sheet: &mut CueSheet, in_track: bool) -> Option<&mut CueTrack> {
+    if !in_track {
+        return None;
+    }
+    sheet
+        .files
+        .last_mut()
+        .and_then(|file| file.tracks.last_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::sheet::generate_cue;
+    use crate::cue::virtual_track::{from_cue_sheet, VirtualTrack};
+    use std::path::{Path, PathBuf};
+
+    const SAMPLE_CUE: &str = r#"REM GENRE Jazz
+PERFORMER "The Band"
+TITLE "Live Album"
+FILE "album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Intro"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Song \"Two\""
+    PERFORMER "Guest"
+    INDEX 00 03:58:50
+    INDEX 01 04:00:12
+"#;
+
+    #[test]
+    fn test_parse_cue() {
+        let sheet = parse_cue(SAMPLE_CUE).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("Live Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.files.len(), 1);
+
+        let file = &sheet.files[0];
+        assert_eq!(file.path, "album.flac");
+        assert_eq!(file.file_type, "WAVE");
+        assert_eq!(file.tracks.len(), 2);
+        assert_eq!(file.tracks[1].title.as_deref(), Some("Song \"Two\""));
+        assert_eq!(file.tracks[1].performer.as_deref(), Some("Guest"));
+        assert_eq!(file.tracks[1].start().unwrap().to_string(), "04:00:12");
+    }
+
+    #[test]
+    fn test_parse_rejects_track_before_file() {
+        assert!(matches!(
+            parse_cue("TRACK 01 AUDIO\n"),
+            Err(Error::CueParsing(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_and_parse_round_trip() {
+        let rate = 44100;
+        let path = PathBuf::from("mix.flac");
+        let track = |number: u32, title: &str, start_sample: u64| VirtualTrack {
+            number,
+            title: Some(title.to_string()),
+            performer: Some("DJ \"Q\"".to_string()),
+            file_path: path.clone(),
+            start_sample,
+            end_sample: None,
+            sample_rate: rate,
+        };
+        let tracks = vec![
+            track(1, "Opening", 0),
+            track(2, "Say \"Hello\"", 187 * rate as u64 + 12_345),
+            track(3, "Back\\slash", 3601 * rate as u64 + 300),
+        ];
+
+        let cue = generate_cue(&path, &tracks);
+        let sheet = parse_cue(&cue).unwrap();
+        let parsed = from_cue_sheet(&sheet, Path::new(""), rate);
+
+        assert_eq!(parsed.len(), tracks.len());
+        let frame = rate as u64 / 75;
+        for (original, parsed) in tracks.iter().zip(&parsed) {
+            assert_eq!(parsed.title, original.title);
+            assert_eq!(parsed.performer, original.performer);
+            assert!(parsed.start_sample.abs_diff(original.start_sample) <= frame);
+        }
+        assert_eq!(parsed[0].end_sample, Some(parsed[1].start_sample));
+        assert_eq!(parsed[2].end_sample, None);
+    }
+}
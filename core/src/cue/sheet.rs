@@ -2,4 +2,234 @@
 //!
 //! Defines CueSheet, CueTrack, CueFile, and related types
 
-// Will be implemented in Phase 2
+use crate::cue::virtual_track::VirtualTrack;
+use std::fmt;
+use std::path::Path;
+
+/// CD frames per second used by CUE timestamps
+pub const CUE_FRAMES_PER_SECOND: u64 = 75;
+
+/// A CUE timestamp in MM:SS:FF (frames are 1/75 s)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct CueTime {
+    /// Minutes (may exceed 59)
+    pub minutes: u32,
+    /// Seconds (0-59)
+    pub seconds: u32,
+    /// CD frames (0-74)
+    pub frames: u32,
+}
+
+impl CueTime {
+    /// Create a timestamp from a total number of CD frames
+    pub fn from_total_frames(total: u64) -> Self {
+        let seconds = total / CUE_FRAMES_PER_SECOND;
+        Self {
+            minutes: (seconds / 60) as u32,
+            seconds: (seconds % 60) as u32,
+            frames: (total % CUE_FRAMES_PER_SECOND) as u32,
+        }
+    }
+
+    /// Create a timestamp from a sample offset, rounded to the nearest CD frame
+    pub fn from_samples(samples: u64, sample_rate: u32) -> Self {
+        let rate = sample_rate.max(1) as u128;
+        let scaled = samples as u128 * CUE_FRAMES_PER_SECOND as u128;
+        let total = (scaled + rate / 2) / rate;
+        Self::from_total_frames(total as u64)
+    }
+
+    /// Total number of CD frames
+    pub fn total_frames(&self) -> u64 {
+        (self.minutes as u64 * 60 + self.seconds as u64) * CUE_FRAMES_PER_SECOND
+            + self.frames as u64
+    }
+
+    /// Convert to a sample offset at the given sample rate
+    pub fn to_samples(&self, sample_rate: u32) -> u64 {
+        self.total_frames() * sample_rate as u64 / CUE_FRAMES_PER_SECOND
+    }
+
+    /// Convert to seconds
+    pub fn as_seconds(&self) -> f64 {
+        self.total_frames() as f64 / CUE_FRAMES_PER_SECOND as f64
+    }
+}
+
+impl fmt::Display for CueTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// An INDEX entry of a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CueIndex {
+    /// Index number (0 = pregap, 1 = track start)
+    pub number: u32,
+    /// Position within the file
+    pub time: CueTime,
+}
+
+/// A TRACK entry of a CUE sheet
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CueTrack {
+    /// Track number
+    pub number: u32,
+    /// Track title
+    pub title: Option<String>,
+    /// Track performer
+    pub performer: Option<String>,
+    /// Track indices in file order
+    pub indices: Vec<CueIndex>,
+    /// Explicit end of the track's audio, when the sheet marks one (a FLAC
+    /// lead-out or a following data track); otherwise the track runs to the
+    /// next track's start or the end of the file
+    pub end: Option<CueTime>,
+}
+
+impl CueTrack {
+    /// Get an index by number
+    pub fn index(&self, number: u32) -> Option<CueTime> {
+        self.indices
+            .iter()
+            .find(|index| index.number == number)
+            .map(|index| index.time)
+    }
+
+    /// Start of the track: INDEX 01, or the first index if there is none
+    pub fn start(&self) -> Option<CueTime> {
+        self.index(1)
+            .or_else(|| self.indices.first().map(|index| index.time))
+    }
+}
+
+/// A FILE entry of a CUE sheet
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CueFile {
+    /// File path as written in the sheet
+    pub path: String,
+    /// File type (WAVE, MP3, AIFF, ...)
+    pub file_type: String,
+    /// Tracks stored in this file
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A parsed CUE sheet
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CueSheet {
+    /// Album title
+    pub title: Option<String>,
+    /// Album performer
+    pub performer: Option<String>,
+    /// Referenced files
+    pub files: Vec<CueFile>,
+}
+
+impl CueSheet {
+    /// Total number of tracks over all files
+    pub fn track_count(&self) -> usize {
+        self.files.iter().map(|file| file.tracks.len()).sum()
+    }
+}
+
+/// Quote a string value, escaping embedded quotes and backslashes
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\r' | '\n' => quoted.push(' '),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// CUE file type for an audio file extension
+fn file_type_for(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("mp3") => "MP3",
+        Some("aif") | Some("aiff") => "AIFF",
+        _ => "WAVE",
+    }
+}
+
+/// Generate a CUE sheet for a single file split into virtual tracks
+///
+/// Track starts are rounded to the nearest CD frame. Tracks are numbered in
+/// the order given.
+pub fn generate_cue(file_path: &Path, tracks: &[VirtualTrack]) -> String {
+    let mut cue = String::new();
+
+    // Hoist the performer to album level when all tracks share it
+    let album_performer = tracks
+        .first()
+        .and_then(|track| track.performer.as_deref())
+        .filter(|performer| {
+            tracks
+                .iter()
+                .all(|track| track.performer.as_deref() == Some(*performer))
+        });
+    if let Some(performer) = album_performer {
+        cue.push_str(&format!("PERFORMER {}\n", quote(performer)));
+    }
+
+    cue.push_str(&format!(
+        "FILE {} {}\n",
+        quote(&file_path.to_string_lossy()),
+        file_type_for(file_path)
+    ));
+
+    for (i, track) in tracks.iter().enumerate() {
+        cue.push_str(&format!("  TRACK {:02} AUDIO\n", i + 1));
+        if let Some(title) = &track.title {
+            cue.push_str(&format!("    TITLE {}\n", quote(title)));
+        }
+        if let (None, Some(performer)) = (album_performer, &track.performer) {
+            cue.push_str(&format!("    PERFORMER {}\n", quote(performer)));
+        }
+        let start = CueTime::from_samples(track.start_sample, track.sample_rate);
+        cue.push_str(&format!("    INDEX 01 {}\n", start));
+    }
+
+    cue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cue_time_rounds_to_nearest_frame() {
+        // One frame at 44.1 kHz is 588 samples
+        assert_eq!(CueTime::from_samples(0, 44100).total_frames(), 0);
+        assert_eq!(CueTime::from_samples(293, 44100).total_frames(), 0);
+        assert_eq!(CueTime::from_samples(294, 44100).total_frames(), 1);
+        assert_eq!(CueTime::from_samples(588, 44100).total_frames(), 1);
+
+        let time = CueTime::from_samples(61 * 44100 + 10 * 588, 44100);
+        assert_eq!(time.to_string(), "01:01:10");
+        assert_eq!(time.to_samples(44100), 61 * 44100 + 10 * 588);
+    }
+
+    #[test]
+    fn test_quote_escapes_quotes() {
+        assert_eq!(quote("Plain"), "\"Plain\"");
+        assert_eq!(quote("Say \"Hi\""), "\"Say \\\"Hi\\\"\"");
+    }
+}
@@ -2,4 +2,111 @@
 //!
 //! Creates playable Track objects from CUE track definitions
 
-// Will be implemented in Phase 2
+use crate::audio::decoder::{read_chapters, Chapter};
+use crate::cue::sheet::CueSheet;
+use crate::Result;
+use std::path::{Path, PathBuf};
+
+/// A track that is a slice of a larger audio file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualTrack {
+    /// Track number
+    pub number: u32,
+    /// Track title
+    pub title: Option<String>,
+    /// Track performer
+    pub performer: Option<String>,
+    /// Backing audio file
+    pub file_path: PathBuf,
+    /// First sample frame of the track
+    pub start_sample: u64,
+    /// Sample frame after the track's end (`None` runs to the end of file)
+    pub end_sample: Option<u64>,
+    /// Sample rate of the backing file
+    pub sample_rate: u32,
+}
+
+impl VirtualTrack {
+    /// Length in sample frames (if the end is known)
+    pub fn duration_samples(&self) -> Option<u64> {
+        self.end_sample
+            .map(|end| end.saturating_sub(self.start_sample))
+    }
+
+    /// Start position in seconds
+    pub fn start_seconds(&self) -> f64 {
+        self.start_sample as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// Build virtual tracks from a parsed CUE sheet
+///
+/// # Arguments
+/// * `sheet` - Parsed CUE sheet
+/// * `base_dir` - Directory relative FILE paths are resolved against
+/// * `sample_rate` - Sample rate of the referenced files
+pub fn from_cue_sheet(sheet: &CueSheet, base_dir: &Path, sample_rate: u32) -> Vec<VirtualTrack> {
+    let mut tracks = Vec::new();
+
+    for file in &sheet.files {
+        let file_path = base_dir.join(&file.path);
+        let starts: Vec<Option<u64>> = file
+            .tracks
+            .iter()
+            .map(|track| track.start().map(|time| time.to_samples(sample_rate)))
+            .collect();
+
+        for (i, track) in file.tracks.iter().enumerate() {
+            let Some(start_sample) = starts[i] else {
+                continue;
+            };
+            // A track ends where the sheet says, or where the next one in
+            // the same file begins
+            let end_sample = track
+                .end
+                .map(|time| time.to_samples(sample_rate))
+                .or_else(|| starts[i + 1..].iter().flatten().next().copied());
+
+            tracks.push(VirtualTrack {
+                number: track.number,
+                title: track.title.clone(),
+                performer: track.performer.clone().or_else(|| sheet.performer.clone()),
+                file_path: file_path.clone(),
+                start_sample,
+                end_sample,
+                sample_rate,
+            });
+        }
+    }
+
+    tracks
+}
+
+/// Build virtual tracks from the chapter markers embedded in an audio file
+///
+/// Returns an empty list when the file has no chapters.
+pub fn from_chapters<P: AsRef<Path>>(path: P) -> Result<Vec<VirtualTrack>> {
+    let path = path.as_ref();
+    let chapters = read_chapters(path)?;
+    Ok(from_chapter_list(path, &chapters))
+}
+
+/// Build virtual tracks from chapters of `file_path`, ordered by start
+///
+/// Each track ends where the next chapter begins; the last one runs to the
+/// end of the file.
+pub fn from_chapter_list<P: AsRef<Path>>(file_path: P, chapters: &[Chapter]) -> Vec<VirtualTrack> {
+    chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| VirtualTrack {
+            number: i as u32 + 1,
+            title: chapter.title.clone(),
+            performer: None,
+            file_path: file_path.as_ref().to_path_buf(),
+            start_sample: chapter.start_sample,
+            end_sample: chapters.get(i + 1).map(|next| next.start_sample),
+            sample_rate: chapter.sample_rate,
+        })
+        .collect()
+}
@@ -63,4 +63,93 @@ pub enum Error {
     /// AI classification error
     #[error("AI classification error: {0}")]
     AiClassification(String),
+
+    /// File format not recognized or not supported
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    /// No output configuration compatible with the source
+    #[error("Format negotiation failed: {0}")]
+    FormatNegotiation(String),
+
+    /// The source can't be seeked
+    #[error("Seek not supported: {0}")]
+    SeekUnsupported(String),
+}
+
+/// Stable classification of errors for programmatic handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// File does not exist
+    FileNotFound,
+    /// Other I/O failure
+    Io,
+    /// File format not recognized or not supported
+    UnsupportedFormat,
+    /// Audio device missing or unusable
+    DeviceUnavailable,
+    /// No output configuration compatible with the source
+    FormatNegotiationFailed,
+    /// Audio data could not be decoded
+    DecodeError,
+    /// The source can't be seeked
+    SeekUnsupported,
+    /// Invalid argument or audio format
+    InvalidParameter,
+    /// Operation not supported
+    NotSupported,
+    /// Anything else
+    Other,
+}
+
+impl Error {
+    /// Classify the error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(e) if e.kind() == std::io::ErrorKind::NotFound => ErrorKind::FileNotFound,
+            Error::Io(_) => ErrorKind::Io,
+            Error::UnsupportedFormat(_) => ErrorKind::UnsupportedFormat,
+            Error::AudioDevice(_) => ErrorKind::DeviceUnavailable,
+            Error::FormatNegotiation(_) => ErrorKind::FormatNegotiationFailed,
+            Error::Decoding(_) => ErrorKind::DecodeError,
+            Error::SeekUnsupported(_) => ErrorKind::SeekUnsupported,
+            Error::InvalidParameter(_) | Error::AudioFormat(_) => ErrorKind::InvalidParameter,
+            Error::NotSupported(_) => ErrorKind::NotSupported,
+            Error::AudioEngine(_)
+            | Error::CueParsing(_)
+            | Error::Ffi(_)
+            | Error::Playlist(_)
+            | Error::Library(_)
+            | Error::Database(_)
+            | Error::Network(_)
+            | Error::AiClassification(_) => ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        let missing = Error::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "gone"));
+        assert_eq!(missing.kind(), ErrorKind::FileNotFound);
+
+        let denied = Error::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "denied",
+        ));
+        assert_eq!(denied.kind(), ErrorKind::Io);
+
+        assert_eq!(
+            Error::UnsupportedFormat("xyz".into()).kind(),
+            ErrorKind::UnsupportedFormat
+        );
+        assert_eq!(
+            Error::AudioDevice("none".into()).kind(),
+            ErrorKind::DeviceUnavailable
+        );
+        assert_eq!(Error::Playlist("x".into()).kind(), ErrorKind::Other);
+    }
 }
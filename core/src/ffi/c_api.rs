@@ -2,16 +2,40 @@
 //!
 //! Exports C-compatible functions for FFI
 
-use crate::audio::engine::{AudioEngine, AudioEngineInterface, AudioEvent, PlaybackState};
+use crate::audio::engine::{
+    AudioEngine, AudioEngineInterface, AudioEvent, PlaybackMode, PlaybackState,
+};
+use crate::audio::format::AudioFormat;
+use crate::audio::output::transcode_file_with_progress;
 use crate::ffi::types::{
     validate_not_null, validate_not_null_mut, AudioEngineHandle, FFIAudioCallback, FFIAudioEvent,
-    FFIAudioEventType, FFIPlaybackState, FFIResult,
+    FFIAudioEventType, FFIAudioFormat, FFIDithering, FFIPlaybackInfo, FFIPlaybackMode,
+    FFIPlaybackState, FFIProgressCallback, FFIResult, FFISampleFormat, FFISourceInfo,
 };
 use parking_lot::Mutex;
+use std::cell::RefCell;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_double, c_void};
+use std::path::PathBuf;
+use std::ptr;
 use std::sync::Arc;
 
+thread_local! {
+    /// Description of the last failure on this thread, see `audio_engine_last_error`
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record why the current call failed and return its result code
+fn fail(result: FFIResult, message: impl Into<String>) -> FFIResult {
+    let message = CString::new(message.into().replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    result
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
 /// Convert AudioEngine to opaque handle
 fn engine_to_handle(engine: Arc<Mutex<AudioEngine>>) -> AudioEngineHandle {
     let ptr = Arc::into_raw(engine) as *mut std::ffi::c_void;
@@ -101,7 +125,7 @@ pub unsafe extern "C" fn audio_engine_load_file(
     let mut engine = engine_mutex.lock();
     match engine.load_file(path_str) {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -130,7 +154,7 @@ pub unsafe extern "C" fn audio_engine_set_volume(
     let mut engine = engine_mutex.lock();
     match engine.set_volume(volume as f32) {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -160,7 +184,7 @@ pub unsafe extern "C" fn audio_engine_set_volume_ramped(
     let mut engine = engine_mutex.lock();
     match engine.set_volume_ramped(volume as f32, ramp_duration_ms) {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -182,7 +206,7 @@ pub unsafe extern "C" fn audio_engine_mute(handle: AudioEngineHandle) -> FFIResu
     let mut engine = engine_mutex.lock();
     match engine.mute() {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -204,7 +228,7 @@ pub unsafe extern "C" fn audio_engine_unmute(handle: AudioEngineHandle) -> FFIRe
     let mut engine = engine_mutex.lock();
     match engine.unmute() {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -324,6 +348,200 @@ pub unsafe extern "C" fn audio_engine_get_duration(
     FFIResult::Success
 }
 
+/// Get state, position, duration, volume, mute and buffer fill at once
+///
+/// Takes the engine lock once and reads a point-in-time consistent
+/// snapshot, so UIs polling every frame don't need six separate calls.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `info` must be a valid pointer to write the result
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_get_playback_info(
+    handle: AudioEngineHandle,
+    info: *mut FFIPlaybackInfo,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(info).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let snapshot = engine_mutex.lock().playback_snapshot();
+    let sample_rate = snapshot.sample_rate.unwrap_or(44100) as c_double;
+    *info = FFIPlaybackInfo {
+        state: playback_state_to_ffi(snapshot.state),
+        position_seconds: snapshot.position as c_double / sample_rate,
+        duration_seconds: snapshot.duration.unwrap_or(0) as c_double / sample_rate,
+        volume: snapshot.volume,
+        is_muted: if snapshot.is_muted { 1 } else { 0 },
+        buffer_utilization: snapshot.buffer_utilization.unwrap_or(0.0),
+    };
+    FFIResult::Success
+}
+
+/// Get properties of the loaded source file (bit depth, lossless flag)
+///
+/// Returns `InvalidArgument` if no file is loaded.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `info` must be a valid pointer to write the result
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_get_source_info(
+    handle: AudioEngineHandle,
+    info: *mut FFISourceInfo,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(info).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let engine = engine_mutex.lock();
+    let source = match engine.source_info() {
+        Some(source) => source,
+        None => return FFIResult::InvalidArgument,
+    };
+
+    *info = FFISourceInfo {
+        sample_rate: source.sample_rate.unwrap_or(0),
+        channels: source.channels.unwrap_or(0),
+        bit_depth: source.bit_depth.unwrap_or(0),
+        is_lossless: if source.is_lossless { 1 } else { 0 },
+        is_high_resolution: if source.is_high_resolution() { 1 } else { 0 },
+    };
+    FFIResult::Success
+}
+
+/// Get the format of the loaded source file
+///
+/// Compare with `audio_engine_get_output_format` to see what the stream
+/// changes. Returns `InvalidArgument` if no file is loaded.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `format` must be a valid pointer to write the result
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_get_source_format(
+    handle: AudioEngineHandle,
+    format: *mut FFIAudioFormat,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(format).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let engine = engine_mutex.lock();
+    let source = match engine.source_info() {
+        Some(source) => source,
+        None => return FFIResult::InvalidArgument,
+    };
+
+    *format = FFIAudioFormat {
+        sample_rate: source.sample_rate.unwrap_or(0),
+        channels: source.channels.unwrap_or(0),
+        bit_depth: source.bit_depth.unwrap_or(0),
+    };
+    FFIResult::Success
+}
+
+/// Get the format the output stream was opened with
+///
+/// Returns `InvalidArgument` if no stream is open.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `format` must be a valid pointer to write the result
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_get_output_format(
+    handle: AudioEngineHandle,
+    format: *mut FFIAudioFormat,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(format).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let engine = engine_mutex.lock();
+    let stream = match engine.stream_config_info() {
+        Some(stream) => stream,
+        None => return FFIResult::InvalidArgument,
+    };
+
+    *format = FFIAudioFormat {
+        sample_rate: stream.sample_rate,
+        channels: stream.channels,
+        bit_depth: stream.sample_format.size_bytes() as u32 * 8,
+    };
+    FFIResult::Success
+}
+
+/// Check if the loaded file plays bit-perfect
+///
+/// False while the stream resamples, remixes, reduces bit depth or applies
+/// any processing (volume, EQ, speed...). Returns `InvalidArgument` if no
+/// file is loaded.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `is_bit_perfect` must be a valid pointer to write the result (0 = false, 1 = true)
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_is_bit_perfect(
+    handle: AudioEngineHandle,
+    is_bit_perfect: *mut u8,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(is_bit_perfect).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let engine = engine_mutex.lock();
+    if engine.source_info().is_none() {
+        return FFIResult::InvalidArgument;
+    }
+    *is_bit_perfect = if engine.is_bit_perfect() { 1 } else { 0 };
+    FFIResult::Success
+}
+
 /// Play audio
 ///
 /// # Safety
@@ -342,7 +560,7 @@ pub unsafe extern "C" fn audio_engine_play(handle: AudioEngineHandle) -> FFIResu
     let mut engine = engine_mutex.lock();
     match engine.play() {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -364,7 +582,7 @@ pub unsafe extern "C" fn audio_engine_pause(handle: AudioEngineHandle) -> FFIRes
     let mut engine = engine_mutex.lock();
     match engine.pause() {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -386,7 +604,7 @@ pub unsafe extern "C" fn audio_engine_stop(handle: AudioEngineHandle) -> FFIResu
     let mut engine = engine_mutex.lock();
     match engine.stop() {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
@@ -417,12 +635,15 @@ pub unsafe extern "C" fn audio_engine_seek(
     let position_samples = (position * sample_rate as c_double) as u64;
     match engine.seek(position_samples) {
         Ok(_) => FFIResult::Success,
-        Err(_) => FFIResult::InternalError,
+        Err(e) => (&e).into(),
     }
 }
 
 /// Check if audio is currently playing
 ///
+/// A stream that is still buffering counts as not playing; use
+/// `audio_engine_get_state` to tell it apart from stopped.
+///
 /// # Safety
 /// - `handle` must be a valid audio engine handle
 /// - `is_playing` must be a valid pointer to write the result (0 = false, 1 = true)
@@ -454,6 +675,57 @@ pub unsafe extern "C" fn audio_engine_is_playing(
     FFIResult::Success
 }
 
+/// Get the playback state
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `state` must be a valid pointer to write the result
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_get_state(
+    handle: AudioEngineHandle,
+    state: *mut FFIPlaybackState,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    if let Err(result) = validate_not_null_mut(state).into() {
+        return result;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    *state = playback_state_to_ffi(engine_mutex.lock().state());
+    FFIResult::Success
+}
+
+/// Enable or disable looping of the current track
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `enabled` is 0 for false, any other value for true
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_set_loop(
+    handle: AudioEngineHandle,
+    enabled: u8,
+) -> FFIResult {
+    if handle.is_null() {
+        return FFIResult::NullPointer;
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let mut engine = engine_mutex.lock();
+    engine.set_loop(enabled != 0);
+    FFIResult::Success
+}
+
 /// Register a callback for audio events
 ///
 /// # Safety
@@ -520,6 +792,136 @@ pub unsafe extern "C" fn audio_engine_clear_callback(handle: AudioEngineHandle)
     FFIResult::Success
 }
 
+/// Get a description of the last failed render on the calling thread
+///
+/// Set by `audio_engine_render_to_file` and
+/// `audio_engine_render_to_file_with_progress` when they fail, and cleared
+/// when they start. Returns null if there is nothing to report.
+///
+/// # Safety
+/// The returned string is owned by the library and stays valid until the
+/// next render call on the same thread; it must not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Render a file to a WAV file without an output device
+///
+/// Decodes `source_path`, or the currently loaded file when it is null,
+/// and writes it to `out_path` converted to `sample_rate`, `channels` and
+/// `sample_format` (an `FFISampleFormat` code). Integer formats are
+/// dithered with `dither` (an `FFIDithering` code). Playback is not
+/// affected.
+///
+/// This blocks the calling thread until the whole file is written. On
+/// failure, `audio_engine_last_error` describes what went wrong.
+///
+/// # Safety
+/// - `handle` must be a valid audio engine handle
+/// - `source_path` must be null or a valid null-terminated C string
+/// - `out_path` must be a valid null-terminated C string
+#[no_mangle]
+pub unsafe extern "C" fn audio_engine_render_to_file(
+    handle: AudioEngineHandle,
+    source_path: *const c_char,
+    out_path: *const c_char,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: u32,
+    dither: u32,
+) -> FFIResult {
+    audio_engine_render_to_file_with_progress(
+        handle,
+        source_path,
+        out_path,
+        sample_rate,
+        channels,
+        sample_format,
+        dither,
+        None,
+        ptr::null_mut(),
+    )
+}
+
+/// Render a file to a WAV file, reporting progress
+///
+/// Same as `audio_engine_render_to_file`, calling `progress` (when not
+/// null) on the calling thread as decoding advances, ending with 1.0.
+///
+/// # Safety
+/// - Same requirements as `audio_engine_render_to_file`
+/// - `progress` must be null or a valid function pointer; it must not call
+///   back into the audio engine
+/// - `user_data` can be any pointer (including null) and is passed back to
+///   `progress`
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn audio_engine_render_to_file_with_progress(
+    handle: AudioEngineHandle,
+    source_path: *const c_char,
+    out_path: *const c_char,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: u32,
+    dither: u32,
+    progress: Option<FFIProgressCallback>,
+    user_data: *mut c_void,
+) -> FFIResult {
+    clear_last_error();
+    if handle.is_null() {
+        return fail(FFIResult::NullPointer, "Null engine handle");
+    }
+    if let Err(result) = validate_not_null(out_path).into() {
+        return fail(result, "Null output path");
+    }
+
+    let engine_mutex = match borrow_engine(handle) {
+        Some(e) => e,
+        None => return FFIResult::NullPointer,
+    };
+
+    let Ok(out_path) = crate::ffi::types::c_string_to_rust_str(out_path) else {
+        return fail(FFIResult::InvalidArgument, "Output path is not valid UTF-8");
+    };
+    let source = if source_path.is_null() {
+        // Release the engine before the long render
+        match engine_mutex.lock().current_path() {
+            Some(path) => path,
+            None => return fail(FFIResult::NotFound, "No file loaded and no source given"),
+        }
+    } else {
+        match crate::ffi::types::c_string_to_rust_str(source_path) {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return fail(FFIResult::InvalidArgument, "Source path is not valid UTF-8"),
+        }
+    };
+    let Some(sample_format) = FFISampleFormat::from_code(sample_format) else {
+        return fail(
+            FFIResult::InvalidArgument,
+            format!("Unknown sample format code {}", sample_format),
+        );
+    };
+    let Some(dither) = FFIDithering::from_code(dither) else {
+        return fail(
+            FFIResult::InvalidArgument,
+            format!("Unknown dithering code {}", dither),
+        );
+    };
+
+    let target = AudioFormat::new(sample_rate, channels, sample_format);
+    let report = |value: f64| {
+        if let Some(callback) = progress {
+            // SAFETY: the caller guarantees the callback is valid
+            unsafe { callback(value, user_data) };
+        }
+    };
+    match transcode_file_with_progress(&source, out_path, target, dither, report) {
+        Ok(_) => FFIResult::Success,
+        Err(e) => fail((&e).into(), e.to_string()),
+    }
+}
+
 /// Convert Rust PlaybackState to FFI PlaybackState
 fn playback_state_to_ffi(state: PlaybackState) -> FFIPlaybackState {
     match state {
@@ -531,8 +933,20 @@ fn playback_state_to_ffi(state: PlaybackState) -> FFIPlaybackState {
     }
 }
 
+/// Convert Rust PlaybackMode to FFI PlaybackMode
+fn playback_mode_to_ffi(mode: PlaybackMode) -> FFIPlaybackMode {
+    match mode {
+        PlaybackMode::Buffered => FFIPlaybackMode::Buffered,
+        PlaybackMode::Streaming => FFIPlaybackMode::Streaming,
+    }
+}
+
 /// Convert Rust AudioEvent to FFI AudioEvent
 fn audio_event_to_ffi(event: &AudioEvent) -> (FFIAudioEvent, Option<CString>) {
+    let mode = match event {
+        AudioEvent::PlaybackModeChanged(mode) => playback_mode_to_ffi(*mode),
+        _ => FFIPlaybackMode::Buffered,
+    };
     let (event_type, state, position, error_cstring) = match event {
         AudioEvent::StateChanged(s) => (
             FFIAudioEventType::StateChanged,
@@ -567,6 +981,30 @@ fn audio_event_to_ffi(event: &AudioEvent) -> (FFIAudioEvent, Option<CString>) {
             0,
             None,
         ),
+        AudioEvent::DeviceChanged(name) => {
+            let cstring = CString::new(name.as_str()).unwrap_or_else(|_| CString::new("").unwrap());
+            (
+                FFIAudioEventType::DeviceChanged,
+                FFIPlaybackState::Stopped,
+                0,
+                Some(cstring),
+            )
+        }
+        AudioEvent::Warning(msg) => {
+            let cstring = CString::new(msg.as_str()).unwrap_or_else(|_| CString::new("").unwrap());
+            (
+                FFIAudioEventType::Warning,
+                FFIPlaybackState::Stopped,
+                0,
+                Some(cstring),
+            )
+        }
+        AudioEvent::PlaybackModeChanged(_) => (
+            FFIAudioEventType::PlaybackModeChanged,
+            FFIPlaybackState::Stopped,
+            0,
+            None,
+        ),
     };
 
     let error_ptr = error_cstring
@@ -578,6 +1016,7 @@ fn audio_event_to_ffi(event: &AudioEvent) -> (FFIAudioEvent, Option<CString>) {
         event_type,
         state,
         position,
+        mode,
         error_message: error_ptr,
     };
 
@@ -618,6 +1057,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_file_error_codes() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing =
+            std::ffi::CString::new(dir.path().join("missing.flac").to_str().unwrap()).unwrap();
+        let text_path = dir.path().join("notes.xyz");
+        std::fs::write(&text_path, b"not audio").unwrap();
+        let unsupported = std::ffi::CString::new(text_path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let handle = audio_engine_create();
+            assert_eq!(
+                audio_engine_load_file(handle, missing.as_ptr()),
+                FFIResult::FileNotFound
+            );
+            assert_eq!(
+                audio_engine_load_file(handle, unsupported.as_ptr()),
+                FFIResult::UnsupportedFormat
+            );
+            audio_engine_destroy(handle);
+        }
+    }
+
     #[test]
     fn test_destroy_null_handle() {
         unsafe {
@@ -844,6 +1306,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_playback_info() {
+        unsafe {
+            let handle = audio_engine_create();
+            audio_engine_set_volume(handle, 0.5);
+            audio_engine_mute(handle);
+
+            let mut info = FFIPlaybackInfo {
+                state: FFIPlaybackState::Error,
+                position_seconds: -1.0,
+                duration_seconds: -1.0,
+                volume: -1.0,
+                is_muted: 0,
+                buffer_utilization: -1.0,
+            };
+            let result = audio_engine_get_playback_info(handle, &mut info);
+            assert_eq!(result, FFIResult::Success);
+            assert_eq!(info.state, FFIPlaybackState::Stopped);
+            assert_eq!(info.position_seconds, 0.0);
+            assert_eq!(info.duration_seconds, 0.0);
+            assert_eq!(info.volume, 0.0);
+            assert_eq!(info.is_muted, 1);
+            assert_eq!(info.buffer_utilization, 0.0);
+
+            let result = audio_engine_get_playback_info(handle, std::ptr::null_mut());
+            assert_eq!(result, FFIResult::NullPointer);
+
+            let null_handle = AudioEngineHandle::null();
+            let result = audio_engine_get_playback_info(null_handle, &mut info);
+            assert_eq!(result, FFIResult::NullPointer);
+
+            audio_engine_destroy(handle);
+        }
+    }
+
     #[test]
     fn test_get_volume() {
         unsafe {
@@ -878,6 +1375,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_buffering_state_is_not_playing() {
+        unsafe {
+            let handle = audio_engine_create();
+            let mut state = FFIPlaybackState::Error;
+            assert_eq!(
+                audio_engine_get_state(handle, &mut state),
+                FFIResult::Success
+            );
+            assert_eq!(state, FFIPlaybackState::Stopped);
+
+            // A stream with nothing decoded yet can't start playing
+            use crate::audio::format::{AudioFormat, SampleFormat};
+            use crate::audio::ring_buffer::{AudioRingBuffer, RingBufferConfig};
+            let format = AudioFormat::new(44100, 2, SampleFormat::F32);
+            let (_producer, consumer) =
+                AudioRingBuffer::new(RingBufferConfig::standard(format)).unwrap();
+            {
+                let mut engine = borrow_engine(handle).unwrap().lock();
+                engine.set_ring_buffer_consumer(consumer).unwrap();
+                engine.enter_playing();
+            }
+
+            let mut is_playing = 1;
+            assert_eq!(
+                audio_engine_is_playing(handle, &mut is_playing),
+                FFIResult::Success
+            );
+            assert_eq!(is_playing, 0);
+            assert_eq!(
+                audio_engine_get_state(handle, &mut state),
+                FFIResult::Success
+            );
+            assert_eq!(state, FFIPlaybackState::Buffering);
+            assert_eq!(
+                audio_engine_get_state(handle, std::ptr::null_mut()),
+                FFIResult::NullPointer
+            );
+
+            audio_engine_destroy(handle);
+        }
+    }
+
     #[test]
     fn test_seek_validation() {
         unsafe {
@@ -925,6 +1465,9 @@ mod tests {
                         // Error message should be valid (can be null)
                     }
                     FFIAudioEventType::BufferUnderrun => {}
+                    FFIAudioEventType::DeviceChanged => {}
+                    FFIAudioEventType::Warning => {}
+                    FFIAudioEventType::PlaybackModeChanged => {}
                 }
             }
         }
@@ -1005,4 +1548,208 @@ mod tests {
             audio_engine_destroy(handle);
         }
     }
+
+    #[test]
+    fn test_device_changed_event_conversion() {
+        let event = AudioEvent::DeviceChanged("USB DAC".to_string());
+        let (ffi_event, name) = audio_event_to_ffi(&event);
+
+        assert_eq!(ffi_event.event_type, FFIAudioEventType::DeviceChanged);
+        assert!(!ffi_event.error_message.is_null());
+        assert_eq!(name.unwrap().to_str().unwrap(), "USB DAC");
+    }
+
+    #[test]
+    fn test_playback_mode_event_conversion() {
+        let event = AudioEvent::PlaybackModeChanged(PlaybackMode::Streaming);
+        let (ffi_event, message) = audio_event_to_ffi(&event);
+
+        assert_eq!(ffi_event.event_type, FFIAudioEventType::PlaybackModeChanged);
+        assert_eq!(ffi_event.mode, FFIPlaybackMode::Streaming);
+        assert!(ffi_event.error_message.is_null());
+        assert!(message.is_none());
+    }
+
+    #[test]
+    fn test_get_source_info_without_file() {
+        unsafe {
+            let handle = audio_engine_create();
+            let mut info = FFISourceInfo::default();
+
+            let result = audio_engine_get_source_info(handle, &mut info);
+            assert_eq!(result, FFIResult::InvalidArgument);
+
+            let result = audio_engine_get_source_info(handle, std::ptr::null_mut());
+            assert_eq!(result, FFIResult::NullPointer);
+
+            let result = audio_engine_get_source_info(AudioEngineHandle::null(), &mut info);
+            assert_eq!(result, FFIResult::NullPointer);
+
+            audio_engine_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn test_format_getters_without_file() {
+        unsafe {
+            let handle = audio_engine_create();
+            let mut format = FFIAudioFormat::default();
+            let mut is_bit_perfect = 1u8;
+
+            assert_eq!(
+                audio_engine_get_source_format(handle, &mut format),
+                FFIResult::InvalidArgument
+            );
+            assert_eq!(
+                audio_engine_get_output_format(handle, &mut format),
+                FFIResult::InvalidArgument
+            );
+            assert_eq!(
+                audio_engine_is_bit_perfect(handle, &mut is_bit_perfect),
+                FFIResult::InvalidArgument
+            );
+            assert_eq!(format, FFIAudioFormat::default());
+            assert_eq!(is_bit_perfect, 1);
+
+            assert_eq!(
+                audio_engine_get_source_format(handle, std::ptr::null_mut()),
+                FFIResult::NullPointer
+            );
+            assert_eq!(
+                audio_engine_get_output_format(handle, std::ptr::null_mut()),
+                FFIResult::NullPointer
+            );
+            assert_eq!(
+                audio_engine_is_bit_perfect(handle, std::ptr::null_mut()),
+                FFIResult::NullPointer
+            );
+
+            let null = AudioEngineHandle::null();
+            assert_eq!(
+                audio_engine_get_source_format(null, &mut format),
+                FFIResult::NullPointer
+            );
+            assert_eq!(
+                audio_engine_is_bit_perfect(null, &mut is_bit_perfect),
+                FFIResult::NullPointer
+            );
+
+            audio_engine_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn test_set_loop() {
+        unsafe {
+            let handle = audio_engine_create();
+
+            assert_eq!(audio_engine_set_loop(handle, 1), FFIResult::Success);
+            assert!(borrow_engine(handle).unwrap().lock().is_looping());
+            assert_eq!(audio_engine_set_loop(handle, 0), FFIResult::Success);
+            assert!(!borrow_engine(handle).unwrap().lock().is_looping());
+
+            assert_eq!(
+                audio_engine_set_loop(AudioEngineHandle::null(), 1),
+                FFIResult::NullPointer
+            );
+
+            audio_engine_destroy(handle);
+        }
+    }
+
+    unsafe extern "C" fn record_progress(progress: c_double, user_data: *mut c_void) {
+        (*(user_data as *mut Vec<f64>)).push(progress);
+    }
+
+    #[test]
+    fn test_render_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&source, spec).unwrap();
+        for i in 0..44100 {
+            let value = ((i % 100) as i16 - 50) * 100;
+            writer.write_sample(value).unwrap();
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
+        let out = dir.path().join("export.wav");
+        let source_c = CString::new(source.to_str().unwrap()).unwrap();
+        let out_c = CString::new(out.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let handle = audio_engine_create();
+
+            // Nothing loaded yet
+            let result = audio_engine_render_to_file(
+                handle,
+                ptr::null(),
+                out_c.as_ptr(),
+                48000,
+                2,
+                FFISampleFormat::I24 as u32,
+                FFIDithering::Triangular as u32,
+            );
+            assert_eq!(result, FFIResult::NotFound);
+            assert!(!audio_engine_last_error().is_null());
+
+            let mut progress = Vec::<f64>::new();
+            let result = audio_engine_render_to_file_with_progress(
+                handle,
+                source_c.as_ptr(),
+                out_c.as_ptr(),
+                48000,
+                1,
+                FFISampleFormat::F32 as u32,
+                FFIDithering::None as u32,
+                Some(record_progress),
+                &mut progress as *mut Vec<f64> as *mut c_void,
+            );
+            assert_eq!(result, FFIResult::Success);
+            assert!(audio_engine_last_error().is_null());
+            assert_eq!(progress.last(), Some(&1.0));
+            assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+
+            let reader = hound::WavReader::open(&out).unwrap();
+            let spec = reader.spec();
+            assert_eq!(spec.sample_rate, 48000);
+            assert_eq!(spec.channels, 1);
+            assert_eq!(spec.bits_per_sample, 32);
+            assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+            assert_eq!(reader.duration(), 48000);
+
+            let result = audio_engine_render_to_file(
+                handle,
+                source_c.as_ptr(),
+                out_c.as_ptr(),
+                44100,
+                2,
+                FFISampleFormat::I24 as u32,
+                FFIDithering::Triangular as u32,
+            );
+            assert_eq!(result, FFIResult::Success);
+            let spec = hound::WavReader::open(&out).unwrap().spec();
+            assert_eq!((spec.channels, spec.bits_per_sample), (2, 24));
+
+            let result = audio_engine_render_to_file(
+                handle,
+                source_c.as_ptr(),
+                out_c.as_ptr(),
+                44100,
+                2,
+                42,
+                0,
+            );
+            assert_eq!(result, FFIResult::InvalidArgument);
+            let message = std::ffi::CStr::from_ptr(audio_engine_last_error());
+            assert!(message.to_str().unwrap().contains("42"));
+
+            audio_engine_destroy(handle);
+        }
+    }
 }
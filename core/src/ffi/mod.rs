@@ -10,6 +10,7 @@ pub mod types;
 pub use c_api::*;
 pub use playlist_api::*;
 pub use types::{
-    AudioEngineHandle, FFIAudioCallback, FFIAudioEvent, FFIAudioEventType, FFIPlaybackState,
-    FFIResult,
+    AudioEngineHandle, FFIAudioCallback, FFIAudioEvent, FFIAudioEventType, FFIAudioFormat,
+    FFIDithering, FFIPlaybackMode, FFIPlaybackState, FFIProgressCallback, FFIResult,
+    FFISampleFormat, FFISourceInfo,
 };
@@ -2,8 +2,11 @@
 //!
 //! Type conversions between Rust and C/Java types
 
+use crate::audio::format::SampleFormat;
+use crate::audio::processor::DitheringAlgorithm;
+use crate::error::ErrorKind;
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_double, c_void};
 
 /// FFI-safe result type
 #[repr(C)]
@@ -21,6 +24,45 @@ pub enum FFIResult {
     InternalError = -4,
     /// Resource not found
     NotFound = -5,
+    /// File does not exist
+    FileNotFound = -6,
+    /// File format not recognized or not supported
+    UnsupportedFormat = -7,
+    /// Audio device missing or unusable
+    DeviceUnavailable = -8,
+    /// No output configuration compatible with the source
+    FormatNegotiationFailed = -9,
+    /// Audio data could not be decoded
+    DecodeError = -10,
+    /// The source can't be seeked
+    SeekUnsupported = -11,
+    /// Operation not supported
+    NotSupported = -12,
+    /// Other I/O failure
+    IoError = -13,
+}
+
+impl From<ErrorKind> for FFIResult {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::FileNotFound => FFIResult::FileNotFound,
+            ErrorKind::Io => FFIResult::IoError,
+            ErrorKind::UnsupportedFormat => FFIResult::UnsupportedFormat,
+            ErrorKind::DeviceUnavailable => FFIResult::DeviceUnavailable,
+            ErrorKind::FormatNegotiationFailed => FFIResult::FormatNegotiationFailed,
+            ErrorKind::DecodeError => FFIResult::DecodeError,
+            ErrorKind::SeekUnsupported => FFIResult::SeekUnsupported,
+            ErrorKind::InvalidParameter => FFIResult::InvalidArgument,
+            ErrorKind::NotSupported => FFIResult::NotSupported,
+            ErrorKind::Other => FFIResult::InternalError,
+        }
+    }
+}
+
+impl From<&crate::Error> for FFIResult {
+    fn from(error: &crate::Error) -> Self {
+        error.kind().into()
+    }
 }
 
 /// FFI-safe audio event type
@@ -37,6 +79,22 @@ pub enum FFIAudioEventType {
     Error = 3,
     /// Buffer underrun occurred
     BufferUnderrun = 4,
+    /// Output device changed
+    DeviceChanged = 5,
+    /// Recoverable problem reported
+    Warning = 6,
+    /// Track loaded buffered or streamed
+    PlaybackModeChanged = 7,
+}
+
+/// FFI-safe playback mode
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FFIPlaybackMode {
+    /// Track decoded into memory in full
+    Buffered = 0,
+    /// Track decoded on the fly
+    Streaming = 1,
 }
 
 /// FFI-safe playback state
@@ -65,9 +123,65 @@ pub struct FFIAudioEvent {
     pub state: FFIPlaybackState,
     /// Position value (for PositionChanged events, in samples)
     pub position: u64,
-    /// Error message pointer (for Error events, null-terminated C string)
+    /// Message pointer (error text for Error and Warning events, device
+    /// name for DeviceChanged events; null-terminated C string)
     /// Note: This pointer is only valid during the callback
     pub error_message: *const c_char,
+    /// Playback mode (for PlaybackModeChanged events); appended last so the
+    /// fields before it keep their offsets
+    pub mode: FFIPlaybackMode,
+}
+
+/// FFI-safe description of the loaded source file
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FFISourceInfo {
+    /// Sample rate in Hz (0 if unknown)
+    pub sample_rate: u32,
+    /// Number of channels (0 if unknown)
+    pub channels: u16,
+    /// Bit depth (0 if unknown or not applicable, e.g. lossy codecs)
+    pub bit_depth: u32,
+    /// Whether the source is lossless (0 = false, 1 = true)
+    pub is_lossless: u8,
+    /// Whether the source is high resolution (0 = false, 1 = true)
+    pub is_high_resolution: u8,
+}
+
+/// FFI-safe audio format description
+///
+/// Used for both the source file and the output stream so hosts can compare
+/// them field by field.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FFIAudioFormat {
+    /// Sample rate in Hz (0 if unknown)
+    pub sample_rate: u32,
+    /// Number of channels (0 if unknown)
+    pub channels: u16,
+    /// Bits per sample (0 if unknown or not applicable, e.g. lossy codecs)
+    pub bit_depth: u32,
+}
+
+/// FFI-safe snapshot of the playback status
+///
+/// Filled by `audio_engine_get_playback_info`; all fields are read at the
+/// same point in time.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFIPlaybackInfo {
+    /// Playback state
+    pub state: FFIPlaybackState,
+    /// Position in seconds
+    pub position_seconds: f64,
+    /// Duration in seconds (0 if unknown)
+    pub duration_seconds: f64,
+    /// Volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Whether audio is muted (0 = false, 1 = true)
+    pub is_muted: u8,
+    /// Ring buffer fill (0.0 to 1.0, 0 when not streaming)
+    pub buffer_utilization: f64,
 }
 
 /// FFI-safe callback function type
@@ -81,6 +195,70 @@ pub struct FFIAudioEvent {
 /// - `user_data`: User-provided data pointer passed during registration
 pub type FFIAudioCallback = unsafe extern "C" fn(event: FFIAudioEvent, user_data: *mut c_void);
 
+/// Callback reporting the progress of a long-running operation
+///
+/// # Parameters
+/// - `progress`: Completed share of the work, 0.0 to 1.0
+/// - `user_data`: User-provided data pointer passed with the call
+pub type FFIProgressCallback = unsafe extern "C" fn(progress: c_double, user_data: *mut c_void);
+
+/// FFI sample format codes for rendering to a file
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FFISampleFormat {
+    /// 8-bit unsigned integer
+    U8 = 0,
+    /// 16-bit signed integer
+    I16 = 1,
+    /// 24-bit signed integer
+    I24 = 2,
+    /// 32-bit signed integer
+    I32 = 3,
+    /// 32-bit floating point
+    F32 = 4,
+    /// 64-bit floating point
+    F64 = 5,
+}
+
+impl FFISampleFormat {
+    /// Sample format for a raw code passed over FFI
+    pub fn from_code(code: u32) -> Option<SampleFormat> {
+        Some(match code {
+            0 => SampleFormat::U8,
+            1 => SampleFormat::I16,
+            2 => SampleFormat::I24,
+            3 => SampleFormat::I32,
+            4 => SampleFormat::F32,
+            5 => SampleFormat::F64,
+            _ => return None,
+        })
+    }
+}
+
+/// FFI dithering codes for rendering to a file
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FFIDithering {
+    /// Truncate without dithering
+    None = 0,
+    /// Triangular (TPDF) dithering
+    Triangular = 1,
+    /// Rectangular dithering
+    Rectangular = 2,
+}
+
+impl FFIDithering {
+    /// Dithering algorithm for a raw code passed over FFI
+    pub fn from_code(code: u32) -> Option<DitheringAlgorithm> {
+        Some(match code {
+            0 => DitheringAlgorithm::None,
+            1 => DitheringAlgorithm::Triangular,
+            2 => DitheringAlgorithm::Rectangular,
+            _ => return None,
+        })
+    }
+}
+
 /// FFI-safe audio engine handle
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -157,6 +335,45 @@ mod tests {
         assert_eq!(FFIResult::OutOfMemory as c_int, -3);
         assert_eq!(FFIResult::InternalError as c_int, -4);
         assert_eq!(FFIResult::NotFound as c_int, -5);
+        assert_eq!(FFIResult::FileNotFound as c_int, -6);
+        assert_eq!(FFIResult::IoError as c_int, -13);
+    }
+
+    #[test]
+    fn test_error_kinds_map_to_distinct_results() {
+        let kinds = [
+            ErrorKind::FileNotFound,
+            ErrorKind::Io,
+            ErrorKind::UnsupportedFormat,
+            ErrorKind::DeviceUnavailable,
+            ErrorKind::FormatNegotiationFailed,
+            ErrorKind::DecodeError,
+            ErrorKind::SeekUnsupported,
+            ErrorKind::InvalidParameter,
+            ErrorKind::NotSupported,
+            ErrorKind::Other,
+        ];
+        let results: std::collections::HashSet<c_int> = kinds
+            .iter()
+            .map(|&kind| FFIResult::from(kind) as c_int)
+            .collect();
+        assert_eq!(results.len(), kinds.len());
+        assert!(!results.contains(&(FFIResult::Success as c_int)));
+    }
+
+    #[test]
+    fn test_audio_event_layout() {
+        use std::mem::offset_of;
+
+        // Bindings read the original fields at fixed offsets
+        assert_eq!(offset_of!(FFIAudioEvent, event_type), 0);
+        assert_eq!(offset_of!(FFIAudioEvent, state), 4);
+        assert_eq!(offset_of!(FFIAudioEvent, position), 8);
+        assert_eq!(offset_of!(FFIAudioEvent, error_message), 16);
+        assert!(
+            offset_of!(FFIAudioEvent, mode)
+                >= offset_of!(FFIAudioEvent, error_message) + std::mem::size_of::<*const c_char>()
+        );
     }
 
     #[test]
@@ -13,11 +13,16 @@ pub mod cue;
 pub mod error;
 pub mod ffi;
 pub mod library;
+pub mod player;
 pub mod playlist;
 pub mod state;
 pub mod streaming;
 
-pub use error::{Error, Result};
+#[cfg(test)]
+pub(crate) mod test_util;
+
+pub use error::{Error, ErrorKind, Result};
+pub use player::Player;
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -2,4 +2,789 @@
 //!
 //! Manages music library database
 
-// Will be implemented in Phase 5
+use crate::audio::decoder::AudioDecoder;
+use crate::audio::loudness::{measure_lufs, LoudnessResult, REPLAYGAIN_REFERENCE_LUFS};
+use crate::error::{Error, Result};
+use crate::playlist::Track;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// Schema for the library tables
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tracks (
+        id TEXT NOT NULL,
+        path TEXT PRIMARY KEY,
+        title TEXT,
+        artist TEXT,
+        album TEXT,
+        duration REAL,
+        track_number INTEGER,
+        year INTEGER,
+        genre TEXT,
+        resume_position INTEGER,
+        play_count INTEGER NOT NULL DEFAULT 0,
+        last_played INTEGER,
+        track_peak REAL,
+        track_lufs REAL,
+        album_lufs REAL
+    );
+";
+
+/// Columns added after the first release, for upgrading older databases
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("play_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("last_played", "INTEGER"),
+    ("track_peak", "REAL"),
+    ("track_lufs", "REAL"),
+    ("album_lufs", "REAL"),
+];
+
+/// Loudness measurements of a track, see `LibraryDb::analyze_track`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackLoudness {
+    /// True peak as a linear amplitude (1.0 = full scale)
+    pub track_peak: f64,
+    /// Integrated loudness in LUFS (`None` for silent or very short tracks)
+    pub track_lufs: Option<f64>,
+    /// Integrated loudness of the track's album in LUFS (`None` without an
+    /// album tag or before any of its tracks were measured)
+    pub album_lufs: Option<f64>,
+}
+
+impl TrackLoudness {
+    /// ReplayGain-style track gain in dB, limited so the peak doesn't clip
+    pub fn track_gain_db(&self) -> Option<f64> {
+        self.gain_db(self.track_lufs?)
+    }
+
+    /// ReplayGain-style album gain in dB, limited so the peak doesn't clip
+    pub fn album_gain_db(&self) -> Option<f64> {
+        self.gain_db(self.album_lufs?)
+    }
+
+    fn gain_db(&self, lufs: f64) -> Option<f64> {
+        let gain = REPLAYGAIN_REFERENCE_LUFS - lufs;
+        if self.track_peak > 0.0 {
+            Some(gain.min(-20.0 * self.track_peak.log10()))
+        } else {
+            Some(gain)
+        }
+    }
+}
+
+/// Listening statistics of a track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackStats {
+    /// File path
+    pub path: String,
+    /// Number of counted plays
+    pub play_count: u32,
+    /// Time of the last counted play (Unix seconds)
+    pub last_played: Option<i64>,
+}
+
+/// Music library database
+pub struct LibraryDb {
+    conn: Connection,
+}
+
+impl LibraryDb {
+    /// Open (or create) a library database file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path).map_err(db_error)?;
+        Self::with_connection(conn)
+    }
+
+    /// Open a temporary in-memory library
+    pub fn open_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().map_err(db_error)?;
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(db_error)?;
+        add_missing_columns(&conn)?;
+        Ok(Self { conn })
+    }
+
+    /// Insert a track or refresh its metadata
+    ///
+    /// Per-track playback data such as the resume position is preserved
+    /// when an existing track is rescanned.
+    pub fn upsert_track(&self, track: &Track) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO tracks
+                    (id, path, title, artist, album, duration, track_number, year, genre)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT(path) DO UPDATE SET
+                    title = excluded.title,
+                    artist = excluded.artist,
+                    album = excluded.album,
+                    duration = excluded.duration,
+                    track_number = excluded.track_number,
+                    year = excluded.year,
+                    genre = excluded.genre",
+                params![
+                    track.id,
+                    track.file_path,
+                    track.title,
+                    track.artist,
+                    track.album,
+                    track.duration,
+                    track.track_number,
+                    track.year,
+                    track.genre,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Get a track by file path
+    pub fn get_track(&self, path: &str) -> Result<Option<Track>> {
+        self.conn
+            .query_row(
+                "SELECT id, path, title, artist, album, duration, track_number, year, genre
+                 FROM tracks WHERE path = ?1",
+                params![path],
+                |row| {
+                    Ok(Track {
+                        id: row.get(0)?,
+                        file_path: row.get(1)?,
+                        title: row.get(2)?,
+                        artist: row.get(3)?,
+                        album: row.get(4)?,
+                        duration: row.get(5)?,
+                        track_number: row.get(6)?,
+                        year: row.get(7)?,
+                        genre: row.get(8)?,
+                        gain_db: None,
+                    })
+                },
+            )
+            .optional()
+            .map_err(db_error)
+    }
+
+    /// Number of tracks in the library
+    pub fn track_count(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM tracks", [], |row| row.get(0))
+            .map_err(db_error)?;
+        Ok(count as usize)
+    }
+
+    /// Remember where playback of a file stopped
+    ///
+    /// # Arguments
+    /// * `path` - File path
+    /// * `samples` - Position in sample frames
+    pub fn set_resume_position(&self, path: &str, samples: u64) -> Result<()> {
+        let samples = i64::try_from(samples)
+            .map_err(|_| Error::InvalidParameter(format!("Position {} too large", samples)))?;
+
+        self.conn
+            .execute(
+                "INSERT INTO tracks (id, path, resume_position)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT(path) DO UPDATE SET resume_position = excluded.resume_position",
+                params![uuid::Uuid::new_v4().to_string(), path, samples],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Get the stored resume position of a file in sample frames
+    pub fn get_resume_position(&self, path: &str) -> Result<Option<u64>> {
+        let position: Option<Option<i64>> = self
+            .conn
+            .query_row(
+                "SELECT resume_position FROM tracks WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?;
+
+        Ok(position.flatten().map(|p| p as u64))
+    }
+
+    /// Count a play of a file
+    ///
+    /// # Arguments
+    /// * `path` - File path
+    /// * `timestamp` - Time of the play (Unix seconds)
+    pub fn record_play(&self, path: &str, timestamp: i64) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO tracks (id, path, play_count, last_played)
+                 VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT(path) DO UPDATE SET
+                    play_count = play_count + 1,
+                    last_played = excluded.last_played",
+                params![uuid::Uuid::new_v4().to_string(), path, timestamp],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Get the listening statistics of a file
+    pub fn track_stats(&self, path: &str) -> Result<Option<TrackStats>> {
+        self.conn
+            .query_row(
+                "SELECT path, play_count, last_played FROM tracks WHERE path = ?1",
+                params![path],
+                stats_from_row,
+            )
+            .optional()
+            .map_err(db_error)
+    }
+
+    /// Get the most played tracks, most plays first
+    pub fn most_played(&self, limit: usize) -> Result<Vec<TrackStats>> {
+        self.query_stats(
+            "SELECT path, play_count, last_played FROM tracks
+             WHERE play_count > 0
+             ORDER BY play_count DESC, last_played DESC
+             LIMIT ?1",
+            limit,
+        )
+    }
+
+    /// Get the most recently played tracks, latest first
+    pub fn recently_played(&self, limit: usize) -> Result<Vec<TrackStats>> {
+        self.query_stats(
+            "SELECT path, play_count, last_played FROM tracks
+             WHERE last_played IS NOT NULL
+             ORDER BY last_played DESC
+             LIMIT ?1",
+            limit,
+        )
+    }
+
+    fn query_stats(&self, sql: &str, limit: usize) -> Result<Vec<TrackStats>> {
+        let mut stmt = self.conn.prepare(sql).map_err(db_error)?;
+        let rows = stmt
+            .query_map(params![limit as i64], stats_from_row)
+            .map_err(db_error)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(db_error)
+    }
+
+    /// Forget the resume position of a file
+    pub fn clear_resume_position(&self, path: &str) -> Result<()> {
+        self.conn
+            .execute(
+                "UPDATE tracks SET resume_position = NULL WHERE path = ?1",
+                params![path],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Decode a file, measure its loudness and store the result
+    ///
+    /// The file is added to the library if it isn't in it yet, and the
+    /// album loudness of its album is brought up to date.
+    pub fn analyze_track<P: AsRef<Path>>(&self, path: P) -> Result<TrackLoudness> {
+        let path = path.as_ref();
+        let (loudness, seconds) = measure_file(path)?;
+        self.store_analysis(path, &loudness, seconds)
+    }
+
+    /// Store a measurement taken by `measure_file`, see `analyze_track`
+    pub(crate) fn store_analysis(
+        &self,
+        path: &Path,
+        loudness: &LoudnessResult,
+        seconds: f64,
+    ) -> Result<TrackLoudness> {
+        let path = path.to_string_lossy();
+        if self.get_track(&path)?.is_none() {
+            let track = Track::from_file(path.as_ref())
+                .unwrap_or_else(|_| Track::new(path.clone().into_owned()));
+            self.upsert_track(&track)?;
+        }
+        self.conn
+            .execute(
+                "UPDATE tracks SET duration = COALESCE(duration, ?2) WHERE path = ?1",
+                params![path, seconds],
+            )
+            .map_err(db_error)?;
+        self.store_loudness(&path, loudness)?;
+
+        self.loudness(&path)?
+            .ok_or_else(|| Error::Database(format!("{} vanished while analyzing", path)))
+    }
+
+    /// Analyze every library track without loudness measurements
+    ///
+    /// Files that can't be decoded (moved, deleted, unsupported) are
+    /// skipped. `progress` is called with (tracks done, tracks to do) after
+    /// each track.
+    ///
+    /// # Returns
+    /// The number of tracks measured
+    pub fn analyze_library(&self, mut progress: impl FnMut(usize, usize)) -> Result<usize> {
+        let paths: Vec<String> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT path FROM tracks WHERE track_peak IS NULL ORDER BY path")
+                .map_err(db_error)?;
+            let rows = stmt.query_map([], |row| row.get(0)).map_err(db_error)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(db_error)?
+        };
+
+        let mut measured = 0;
+        for (done, path) in paths.iter().enumerate() {
+            if self.analyze_track(path).is_ok() {
+                measured += 1;
+            }
+            progress(done + 1, paths.len());
+        }
+        Ok(measured)
+    }
+
+    /// Store a loudness measurement of a file and update its album's loudness
+    ///
+    /// For measurements taken elsewhere, e.g. of audio decoded for playback.
+    pub fn store_loudness(&self, path: &str, loudness: &LoudnessResult) -> Result<()> {
+        let lufs = Some(loudness.integrated_lufs).filter(|lufs| lufs.is_finite());
+        self.conn
+            .execute(
+                "INSERT INTO tracks (id, path, track_peak, track_lufs)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(path) DO UPDATE SET
+                    track_peak = excluded.track_peak,
+                    track_lufs = excluded.track_lufs",
+                params![
+                    uuid::Uuid::new_v4().to_string(),
+                    path,
+                    loudness.true_peak,
+                    lufs
+                ],
+            )
+            .map_err(db_error)?;
+        self.update_album_loudness(path)
+    }
+
+    /// Get the stored loudness measurements of a file
+    ///
+    /// `None` if the file hasn't been measured.
+    pub fn loudness(&self, path: &str) -> Result<Option<TrackLoudness>> {
+        self.conn
+            .query_row(
+                "SELECT track_peak, track_lufs, album_lufs FROM tracks
+                 WHERE path = ?1 AND track_peak IS NOT NULL",
+                params![path],
+                |row| {
+                    Ok(TrackLoudness {
+                        track_peak: row.get(0)?,
+                        track_lufs: row.get(1)?,
+                        album_lufs: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(db_error)
+    }
+
+    /// Recompute the album loudness of the album `path` belongs to
+    ///
+    /// An album is the tracks with the same album tag in the same folder,
+    /// so same-named albums of different artists stay apart.
+    fn update_album_loudness(&self, path: &str) -> Result<()> {
+        let album: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT album FROM tracks WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)?
+            .flatten();
+        let Some(album) = album else {
+            return Ok(());
+        };
+
+        let folder = Path::new(path).parent();
+        let tracks: Vec<(String, Option<f64>, Option<f64>)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT path, track_lufs, duration FROM tracks WHERE album = ?1")
+                .map_err(db_error)?;
+            let rows = stmt
+                .query_map(params![album], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })
+                .map_err(db_error)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(db_error)?
+                .into_iter()
+                .filter(|(track, _, _)| Path::new(track).parent() == folder)
+                .collect()
+        };
+
+        let measured: Vec<(f64, f64)> = tracks
+            .iter()
+            .filter_map(|(_, lufs, duration)| Some(((*lufs)?, duration.unwrap_or(1.0))))
+            .collect();
+        let album_lufs = album_loudness(&measured);
+        for (track, _, _) in &tracks {
+            self.conn
+                .execute(
+                    "UPDATE tracks SET album_lufs = ?2 WHERE path = ?1",
+                    params![track, album_lufs],
+                )
+                .map_err(db_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode a file and measure its loudness
+///
+/// # Returns
+/// The measurement and the duration in seconds
+pub(crate) fn measure_file(path: &Path) -> Result<(LoudnessResult, f64)> {
+    let mut decoder = AudioDecoder::new(path)?;
+    let format = decoder.format().clone();
+    let buffer = decoder.decode_all()?;
+    let loudness = measure_lufs(buffer.data(), format.sample_rate, format.channels);
+    let seconds = buffer.frames() as f64 / format.sample_rate.max(1) as f64;
+    Ok((loudness, seconds))
+}
+
+/// Combine per-track loudness into the loudness of the whole album
+///
+/// `tracks` holds (integrated LUFS, duration in seconds) pairs. Each
+/// track's mean-square energy is weighted by its duration, approximating a
+/// measurement of the album played through. Returns `None` for an empty
+/// album.
+pub fn album_loudness(tracks: &[(f64, f64)]) -> Option<f64> {
+    let total: f64 = tracks.iter().map(|&(_, seconds)| seconds).sum();
+    if tracks.is_empty() || total <= 0.0 {
+        return None;
+    }
+    let energy: f64 = tracks
+        .iter()
+        .map(|&(lufs, seconds)| seconds * 10f64.powf(lufs / 10.0))
+        .sum();
+    Some(10.0 * (energy / total).log10())
+}
+
+/// Add the columns of `ADDED_COLUMNS` a database created earlier lacks
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    let existing: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info('tracks')")
+            .map_err(db_error)?;
+        let rows = stmt.query_map([], |row| row.get(0)).map_err(db_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db_error)?
+    };
+    for (name, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|column| column == name) {
+            conn.execute_batch(&format!("ALTER TABLE tracks ADD COLUMN {} {}", name, kind))
+                .map_err(db_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a `TrackStats` from a (path, play_count, last_played) row
+fn stats_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TrackStats> {
+    Ok(TrackStats {
+        path: row.get(0)?,
+        play_count: row.get(1)?,
+        last_played: row.get(2)?,
+    })
+}
+
+/// Convert a SQLite error into the crate error type
+fn db_error(error: rusqlite::Error) -> Error {
+    Error::Database(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get_resume_position() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        assert_eq!(db.get_resume_position("/music/book.m4b").unwrap(), None);
+
+        db.set_resume_position("/music/book.m4b", 441_000).unwrap();
+        assert_eq!(
+            db.get_resume_position("/music/book.m4b").unwrap(),
+            Some(441_000)
+        );
+
+        db.set_resume_position("/music/book.m4b", 882_000).unwrap();
+        assert_eq!(
+            db.get_resume_position("/music/book.m4b").unwrap(),
+            Some(882_000)
+        );
+
+        db.clear_resume_position("/music/book.m4b").unwrap();
+        assert_eq!(db.get_resume_position("/music/book.m4b").unwrap(), None);
+    }
+
+    #[test]
+    fn test_resume_position_survives_rescan() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let mut track = Track::new("/music/episode.mp3".to_string());
+        track.title = Some("Episode 1".to_string());
+        db.upsert_track(&track).unwrap();
+
+        db.set_resume_position(&track.file_path, 1234).unwrap();
+
+        track.title = Some("Episode 1 (retagged)".to_string());
+        db.upsert_track(&track).unwrap();
+
+        assert_eq!(db.track_count().unwrap(), 1);
+        assert_eq!(
+            db.get_track(&track.file_path).unwrap().unwrap().title,
+            Some("Episode 1 (retagged)".to_string())
+        );
+        assert_eq!(
+            db.get_resume_position(&track.file_path).unwrap(),
+            Some(1234)
+        );
+    }
+
+    #[test]
+    fn test_play_statistics() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        db.record_play("/a.flac", 100).unwrap();
+        db.record_play("/b.flac", 200).unwrap();
+        db.record_play("/a.flac", 300).unwrap();
+        db.record_play("/c.flac", 250).unwrap();
+
+        let a = db.track_stats("/a.flac").unwrap().unwrap();
+        assert_eq!(a.play_count, 2);
+        assert_eq!(a.last_played, Some(300));
+
+        let most: Vec<_> = db
+            .most_played(2)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        assert_eq!(most, ["/a.flac", "/c.flac"]);
+
+        let recent: Vec<_> = db
+            .recently_played(10)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        assert_eq!(recent, ["/a.flac", "/c.flac", "/b.flac"]);
+    }
+
+    #[test]
+    fn test_loudness_measurements_round_trip() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let mut track = Track::new("/music/song.flac".to_string());
+        track.album = Some("Album".to_string());
+        track.duration = Some(200.0);
+        db.upsert_track(&track).unwrap();
+        assert_eq!(db.loudness(&track.file_path).unwrap(), None);
+
+        let measured = LoudnessResult {
+            integrated_lufs: -14.0,
+            true_peak: 0.5,
+        };
+        db.store_loudness(&track.file_path, &measured).unwrap();
+        let loudness = db.loudness(&track.file_path).unwrap().unwrap();
+        assert_eq!(loudness.track_peak, 0.5);
+        assert_eq!(loudness.track_lufs, Some(-14.0));
+        // Alone in its album
+        assert_eq!(loudness.album_lufs, Some(-14.0));
+        assert_eq!(loudness.track_gain_db(), Some(-4.0));
+
+        // Quiet tracks are only raised as far as the peak allows
+        let quiet = LoudnessResult {
+            integrated_lufs: -30.0,
+            true_peak: 0.5,
+        };
+        db.store_loudness("/music/quiet.flac", &quiet).unwrap();
+        let gain = db.loudness("/music/quiet.flac").unwrap().unwrap();
+        assert!((gain.track_gain_db().unwrap() - 6.0206).abs() < 1e-3);
+
+        // Silence has no loudness
+        let silent = LoudnessResult {
+            integrated_lufs: f64::NEG_INFINITY,
+            true_peak: 0.0,
+        };
+        db.store_loudness("/music/silence.flac", &silent).unwrap();
+        let silence = db.loudness("/music/silence.flac").unwrap().unwrap();
+        assert_eq!(silence.track_lufs, None);
+        assert_eq!(silence.track_gain_db(), None);
+
+        // A rescan keeps the measurements
+        track.title = Some("Song".to_string());
+        db.upsert_track(&track).unwrap();
+        assert_eq!(db.loudness(&track.file_path).unwrap(), Some(loudness));
+    }
+
+    #[test]
+    fn test_album_loudness_groups_by_album_folder() {
+        // Equal lengths: the energy mean, not the LUFS mean
+        let album = album_loudness(&[(-10.0, 60.0), (-20.0, 60.0)]).unwrap();
+        assert!((album - (-12.596)).abs() < 1e-3);
+        // A longer track weighs more
+        let album = album_loudness(&[(-10.0, 30.0), (-20.0, 270.0)]).unwrap();
+        assert!((album - (-17.212)).abs() < 1e-3);
+        assert_eq!(album_loudness(&[]), None);
+
+        let db = LibraryDb::open_in_memory().unwrap();
+        for (path, album) in [
+            ("/music/a/1.flac", "Greatest Hits"),
+            ("/music/a/2.flac", "Greatest Hits"),
+            ("/music/b/1.flac", "Greatest Hits"),
+        ] {
+            let mut track = Track::new(path.to_string());
+            track.album = Some(album.to_string());
+            track.duration = Some(60.0);
+            db.upsert_track(&track).unwrap();
+        }
+        let store = |path: &str, lufs: f64| {
+            let measured = LoudnessResult {
+                integrated_lufs: lufs,
+                true_peak: 0.1,
+            };
+            db.store_loudness(path, &measured).unwrap();
+        };
+        store("/music/a/1.flac", -10.0);
+        store("/music/a/2.flac", -20.0);
+        store("/music/b/1.flac", -30.0);
+
+        let album_lufs = |path: &str| db.loudness(path).unwrap().unwrap().album_lufs.unwrap();
+        assert!((album_lufs("/music/a/1.flac") - (-12.596)).abs() < 1e-3);
+        assert_eq!(album_lufs("/music/a/1.flac"), album_lufs("/music/a/2.flac"));
+        assert_eq!(album_lufs("/music/b/1.flac"), -30.0);
+    }
+
+    #[test]
+    fn test_analyze_track_measures_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..44100 {
+            let sample = (i as f64 * 2.0 * std::f64::consts::PI * 1000.0 / 44100.0).sin();
+            let sample = (sample * 0.5 * i16::MAX as f64) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let db = LibraryDb::open_in_memory().unwrap();
+        db.upsert_track(&Track::new("/missing.flac".to_string()))
+            .unwrap();
+        let loudness = db.analyze_track(&path).unwrap();
+        assert!((loudness.track_peak - 0.5).abs() < 0.01);
+        let lufs = loudness.track_lufs.unwrap();
+        assert!(lufs > -10.0 && lufs < -5.0);
+
+        let path_str = path.to_string_lossy();
+        assert_eq!(db.loudness(&path_str).unwrap(), Some(loudness));
+        assert_eq!(
+            db.get_track(&path_str).unwrap().unwrap().duration,
+            Some(1.0)
+        );
+
+        // Only the unmeasured (and here undecodable) track is left
+        let mut calls = Vec::new();
+        assert_eq!(
+            db.analyze_library(|done, total| calls.push((done, total)))
+                .unwrap(),
+            0
+        );
+        assert_eq!(calls, [(1, 1)]);
+    }
+
+    #[test]
+    fn test_older_database_gains_loudness_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("library.db");
+        {
+            let conn = Connection::open(&file).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE tracks (
+                    id TEXT NOT NULL,
+                    path TEXT PRIMARY KEY,
+                    title TEXT,
+                    artist TEXT,
+                    album TEXT,
+                    duration REAL,
+                    track_number INTEGER,
+                    year INTEGER,
+                    genre TEXT,
+                    resume_position INTEGER,
+                    play_count INTEGER NOT NULL DEFAULT 0,
+                    last_played INTEGER
+                );
+                INSERT INTO tracks (id, path) VALUES ('1', '/old.flac');",
+            )
+            .unwrap();
+        }
+
+        let db = LibraryDb::open(&file).unwrap();
+        assert_eq!(db.loudness("/old.flac").unwrap(), None);
+        let measured = LoudnessResult {
+            integrated_lufs: -18.0,
+            true_peak: 0.9,
+        };
+        db.store_loudness("/old.flac", &measured).unwrap();
+        assert_eq!(
+            db.loudness("/old.flac").unwrap().unwrap().track_gain_db(),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn test_first_release_database_gains_stats_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("library.db");
+        {
+            let conn = Connection::open(&file).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE tracks (
+                    id TEXT NOT NULL,
+                    path TEXT PRIMARY KEY,
+                    title TEXT,
+                    artist TEXT,
+                    album TEXT,
+                    duration REAL,
+                    track_number INTEGER,
+                    year INTEGER,
+                    genre TEXT,
+                    resume_position INTEGER
+                );
+                INSERT INTO tracks (id, path, resume_position) VALUES ('1', '/old.flac', 4410);",
+            )
+            .unwrap();
+        }
+
+        let db = LibraryDb::open(&file).unwrap();
+        let stats = db.track_stats("/old.flac").unwrap().unwrap();
+        assert_eq!(stats.play_count, 0);
+        assert_eq!(stats.last_played, None);
+
+        db.record_play("/old.flac", 1_700_000_000).unwrap();
+        let stats = db.track_stats("/old.flac").unwrap().unwrap();
+        assert_eq!(stats.play_count, 1);
+        assert_eq!(stats.last_played, Some(1_700_000_000));
+        assert_eq!(db.get_resume_position("/old.flac").unwrap(), Some(4410));
+        assert_eq!(db.loudness("/old.flac").unwrap(), None);
+    }
+}
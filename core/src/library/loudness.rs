@@ -0,0 +1,176 @@
+//! Loudness-based ReplayGain
+//!
+//! Serves the engine's `ReplayGainSource::Analyzed` gains from the library's
+//! loudness measurements, measuring unknown tracks in the background
+
+use crate::audio::engine::ReplayGainLookup;
+use crate::library::database::{measure_file, LibraryDb};
+use crossbeam::channel::Sender;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Work for the analysis thread
+enum Request {
+    /// Measure a track and store the result
+    Analyze(PathBuf),
+    /// Reply once every earlier request is done
+    Flush(Sender<()>),
+}
+
+/// `ReplayGainLookup` backed by a `LibraryDb`
+///
+/// Gains come from the stored `track_lufs`/`album_lufs` measurements.
+/// Tracks the library hasn't measured are decoded and measured one after
+/// another on a background thread, so loading them never waits for the
+/// analysis; their gain applies from the next load. Joined when dropped.
+pub struct LoudnessLookup {
+    library: Arc<Mutex<LibraryDb>>,
+    /// Tracks queued for analysis, so repeated loads queue them once
+    pending: Arc<Mutex<HashSet<PathBuf>>>,
+    requests: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LoudnessLookup {
+    /// Serve gains from `library` and store new measurements in it
+    pub fn new(library: Arc<Mutex<LibraryDb>>) -> Self {
+        let pending = Arc::new(Mutex::new(HashSet::new()));
+        let (requests, queue) = crossbeam::channel::unbounded::<Request>();
+        let thread = {
+            let library = library.clone();
+            let pending = pending.clone();
+            std::thread::spawn(move || {
+                for request in queue {
+                    match request {
+                        Request::Analyze(path) => {
+                            // Decode without holding the library lock
+                            if let Ok((loudness, seconds)) = measure_file(&path) {
+                                let _ = library.lock().store_analysis(&path, &loudness, seconds);
+                            }
+                            pending.lock().remove(&path);
+                        }
+                        Request::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+        };
+        Self {
+            library,
+            pending,
+            requests: Some(requests),
+            thread: Some(thread),
+        }
+    }
+
+    /// Block until every requested analysis has finished
+    pub fn wait_idle(&self) {
+        let (done, finished) = crossbeam::channel::bounded(1);
+        if let Some(requests) = &self.requests {
+            if requests.send(Request::Flush(done)).is_ok() {
+                let _ = finished.recv();
+            }
+        }
+    }
+}
+
+impl ReplayGainLookup for LoudnessLookup {
+    fn gain_db(&self, path: &Path, album: bool) -> Option<f64> {
+        let loudness = self
+            .library
+            .lock()
+            .loudness(&path.to_string_lossy())
+            .ok()
+            .flatten()?;
+        let (preferred, other) = if album {
+            (loudness.album_gain_db(), loudness.track_gain_db())
+        } else {
+            (loudness.track_gain_db(), loudness.album_gain_db())
+        };
+        preferred.or(other)
+    }
+
+    fn request_analysis(&self, path: &Path) {
+        if !self.pending.lock().insert(path.to_path_buf()) {
+            return;
+        }
+        if let Some(requests) = &self.requests {
+            let _ = requests.send(Request::Analyze(path.to_path_buf()));
+        }
+    }
+}
+
+impl Drop for LoudnessLookup {
+    fn drop(&mut self) {
+        self.requests.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tone(path: &Path, amplitude: f64) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..44100 {
+            let sample = (i as f64 * 2.0 * std::f64::consts::PI * 1000.0 / 44100.0).sin();
+            let sample = (sample * amplitude * i16::MAX as f64) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_measures_requested_tracks_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_tone(&path, 0.1);
+
+        let library = Arc::new(Mutex::new(LibraryDb::open_in_memory().unwrap()));
+        let lookup = LoudnessLookup::new(library.clone());
+        assert_eq!(lookup.gain_db(&path, false), None);
+
+        lookup.request_analysis(&path);
+        lookup.request_analysis(&path);
+        lookup.wait_idle();
+        assert!(lookup.pending.lock().is_empty());
+
+        let stored = library
+            .lock()
+            .loudness(&path.to_string_lossy())
+            .unwrap()
+            .unwrap();
+        let gain = lookup.gain_db(&path, false).unwrap();
+        assert!(gain > 0.0);
+        assert_eq!(Some(gain), stored.track_gain_db());
+        // Untagged, so there is no album gain to prefer
+        assert_eq!(lookup.gain_db(&path, true), Some(gain));
+    }
+
+    #[test]
+    fn test_unreadable_track_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.wav");
+
+        let library = Arc::new(Mutex::new(LibraryDb::open_in_memory().unwrap()));
+        let lookup = LoudnessLookup::new(library);
+        lookup.request_analysis(&path);
+        lookup.wait_idle();
+        assert_eq!(lookup.gain_db(&path, false), None);
+        assert!(lookup.pending.lock().is_empty());
+    }
+}
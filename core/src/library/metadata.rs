@@ -1,5 +1,542 @@
 //! Metadata extraction
 //!
-//! Extracts metadata from audio files using Symphonia
+//! Extracts metadata from audio files using Symphonia, and writes edited
+//! tags back with lofty
 
-// Will be implemented in Phase 5
+use crate::audio::decoder::{
+    detect_format, visit_tags, AudioFormatInfo, ReplayGainTags, TrackTags,
+};
+use crate::audio::dsd;
+use crate::cue::sheet::CueSheet;
+use crate::cue::virtual_track::{from_cue_sheet, VirtualTrack};
+use crate::error::{Error, Result};
+use lofty::config::WriteOptions;
+use lofty::prelude::*;
+use lofty::tag::{items::Timestamp, Tag};
+use std::fs::{File, OpenOptions};
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Format and tag information of an audio file
+#[derive(Debug, Clone)]
+pub struct TrackMetadata {
+    /// File path
+    pub path: PathBuf,
+    /// Detected audio format
+    pub format: AudioFormatInfo,
+    /// Track title
+    pub title: Option<String>,
+    /// Artist name
+    pub artist: Option<String>,
+    /// Album name
+    pub album: Option<String>,
+    /// Album artist, when it differs from or supplements the track artist
+    pub album_artist: Option<String>,
+    /// Track number in album
+    pub track_number: Option<u32>,
+    /// Year of release
+    pub year: Option<u32>,
+    /// Genre
+    pub genre: Option<String>,
+    /// ReplayGain track gain in dB, from the file's tags
+    pub replaygain_track_gain: Option<f64>,
+    /// ReplayGain album gain in dB, from the file's tags
+    pub replaygain_album_gain: Option<f64>,
+    /// Slice of `path` this track covers, when split out by a CUE sheet
+    pub virtual_track: Option<VirtualTrack>,
+}
+
+impl TrackMetadata {
+    /// Duration in seconds (if known)
+    ///
+    /// For a CUE track this is the length of its slice of the file.
+    pub fn duration_seconds(&self) -> Option<f64> {
+        match &self.virtual_track {
+            Some(track) => {
+                let end = track.end_sample.or(self.format.duration)?;
+                let frames = end.saturating_sub(track.start_sample);
+                Some(frames as f64 / track.sample_rate.max(1) as f64)
+            }
+            None => self.format.duration_seconds(),
+        }
+    }
+
+    /// Split a whole-file entry into one track per CUE track of `sheet`
+    ///
+    /// Every track of the sheet is taken to be in this file, whatever its
+    /// FILE line says. Album and performer fall back to the file's tags.
+    /// Returns the entry unchanged if the sheet has no usable tracks or the
+    /// sample rate is unknown.
+    pub fn split_by_cue(self, sheet: &CueSheet) -> Vec<TrackMetadata> {
+        let Some(sample_rate) = self.format.sample_rate else {
+            return vec![self];
+        };
+        let tracks = from_cue_sheet(sheet, Path::new(""), sample_rate);
+        if tracks.is_empty() {
+            return vec![self];
+        }
+
+        tracks
+            .into_iter()
+            .map(|track| TrackMetadata {
+                path: self.path.clone(),
+                format: self.format.clone(),
+                title: track.title.clone(),
+                artist: track.performer.clone().or_else(|| self.artist.clone()),
+                album: sheet.title.clone().or_else(|| self.album.clone()),
+                album_artist: sheet
+                    .performer
+                    .clone()
+                    .or_else(|| self.album_artist.clone()),
+                track_number: Some(track.number),
+                year: self.year,
+                genre: self.genre.clone(),
+                // The file's track gain covers the whole image, i.e. the album
+                replaygain_track_gain: None,
+                replaygain_album_gain: self.replaygain_album_gain.or(self.replaygain_track_gain),
+                virtual_track: Some(VirtualTrack {
+                    file_path: self.path.clone(),
+                    ..track
+                }),
+            })
+            .collect()
+    }
+
+    /// Fill the tag fields from normalized tags
+    fn apply_tags(&mut self, tags: TrackTags) {
+        self.title = tags.title;
+        self.artist = tags.artist;
+        self.album = tags.album;
+        self.album_artist = tags.album_artist;
+        self.track_number = tags.track_number;
+        self.year = tags.year;
+        self.genre = tags.genre;
+    }
+}
+
+/// Read format and tag metadata of an audio file
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> Result<TrackMetadata> {
+    read_metadata_with_cue(path.as_ref()).map(|(metadata, _)| metadata)
+}
+
+/// Read metadata along with the text of an embedded CUESHEET tag, if any
+pub(crate) fn read_metadata_with_cue(path: &Path) -> Result<(TrackMetadata, Option<String>)> {
+    let format = detect_format(path)?
+        .ok_or_else(|| Error::Decoding("Could not extract audio metadata".to_string()))?;
+
+    let mut metadata = TrackMetadata {
+        path: path.to_path_buf(),
+        format,
+        title: None,
+        artist: None,
+        album: None,
+        album_artist: None,
+        track_number: None,
+        year: None,
+        genre: None,
+        replaygain_track_gain: None,
+        replaygain_album_gain: None,
+        virtual_track: None,
+    };
+
+    // DSD containers carry no tags Symphonia understands
+    if dsd::is_dsd_file(path) {
+        return Ok((metadata, None));
+    }
+
+    let file = File::open(path)?;
+    let media_source = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            media_source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| Error::UnsupportedFormat(format!("Failed to probe file: {}", e)))?;
+
+    let mut tags = TrackTags::default();
+    let mut replay_gain = ReplayGainTags::default();
+    let mut cue_sheet = None;
+    visit_tags(&mut probed, |tag| {
+        if tag.key.eq_ignore_ascii_case("CUESHEET") {
+            cue_sheet = Some(tag.value.to_string());
+        }
+        tags.apply(tag);
+        replay_gain.apply(tag);
+    });
+    metadata.apply_tags(tags);
+    metadata.replaygain_track_gain = replay_gain.track_gain_db;
+    metadata.replaygain_album_gain = replay_gain.album_gain_db;
+
+    Ok((metadata, cue_sheet))
+}
+
+/// Write edited tags back to an audio file
+///
+/// Only the `Some` fields of `tags` are written; every other tag, embedded
+/// picture and the audio data are kept as they are. Any container lofty can
+/// tag is supported (FLAC, MP3, Ogg, MP4, ...); a tag of the container's
+/// primary type is added when the file has none. Setting the track number
+/// keeps the track total already in the file ("3/12" stays "n/12").
+///
+/// The tags are written into a copy next to the original which is then
+/// renamed over it, so a failure part-way leaves the original untouched.
+pub fn write_tags<P: AsRef<Path>>(path: P, tags: &TrackTags) -> Result<()> {
+    let path = path.as_ref();
+    replace_file(path, |file| {
+        let mut tagged = lofty::read_from(file).map_err(tag_error)?;
+        if tagged.primary_tag().is_none() {
+            tagged.insert_tag(Tag::new(tagged.primary_tag_type()));
+        }
+        let tag = tagged
+            .primary_tag_mut()
+            .ok_or_else(|| Error::NotSupported(format!("Cannot tag {}", path.display())))?;
+        apply_track_tags(tag, tags);
+
+        file.rewind()?;
+        tagged
+            .save_to(file, WriteOptions::default())
+            .map_err(tag_error)
+    })
+}
+
+fn tag_error(err: impl std::fmt::Display) -> Error {
+    Error::Decoding(format!("Tag error: {}", err))
+}
+
+/// Set the `Some` fields of `tags` on `tag`, leaving all other items
+fn apply_track_tags(tag: &mut Tag, tags: &TrackTags) {
+    if let Some(title) = &tags.title {
+        tag.set_title(title.clone());
+    }
+    if let Some(artist) = &tags.artist {
+        tag.set_artist(artist.clone());
+    }
+    if let Some(album) = &tags.album {
+        tag.set_album(album.clone());
+    }
+    if let Some(album_artist) = &tags.album_artist {
+        tag.insert_text(ItemKey::AlbumArtist, album_artist.clone());
+    }
+    if let Some(track) = tags.track_number {
+        // A "3/12" number carries its total inline; keep it as the total
+        let total = tag.track_total().or_else(|| {
+            let number = tag.get_string(ItemKey::TrackNumber)?;
+            number.split_once('/')?.1.trim().parse().ok()
+        });
+        tag.set_track(track);
+        if let Some(total) = total {
+            tag.set_track_total(total);
+        }
+    }
+    if let Some(disc) = tags.disc_number {
+        tag.set_disk(disc);
+    }
+    if let Some(year) = tags.year.and_then(|year| u16::try_from(year).ok()) {
+        tag.set_date(Timestamp {
+            year,
+            ..Default::default()
+        });
+    }
+    if let Some(genre) = &tags.genre {
+        tag.set_genre(genre.clone());
+    }
+    if let Some(composer) = &tags.composer {
+        tag.insert_text(ItemKey::Composer, composer.clone());
+    }
+}
+
+/// Atomically replace `path` with an edited copy, keeping its permissions
+///
+/// `edit` gets the copy opened for reading and writing.
+fn replace_file(path: &Path, edit: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidParameter(format!("Not a file: {}", path.display())))?;
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let write = || -> Result<()> {
+        std::fs::copy(path, &temp_path)?;
+        let mut file = OpenOptions::new().read(true).write(true).open(&temp_path)?;
+        edit(&mut file)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_metadata_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..44100 * 2 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let metadata = read_metadata(&path).unwrap();
+        assert_eq!(metadata.path, path);
+        assert_eq!(metadata.format.sample_rate, Some(44100));
+        assert_eq!(metadata.format.channels, Some(2));
+        assert!((metadata.duration_seconds().unwrap() - 1.0).abs() < 1e-6);
+        assert!(metadata.title.is_none());
+    }
+
+    #[test]
+    fn test_read_replaygain_tags() {
+        use crate::test_util::{
+            vorbis_comment_block, write_verbatim_flac_with_metadata, FLAC_VORBIS_COMMENT,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gain.flac");
+        write_verbatim_flac_with_metadata(
+            &path,
+            2,
+            &[(
+                FLAC_VORBIS_COMMENT,
+                vorbis_comment_block(&[
+                    "ALBUM=Album",
+                    "REPLAYGAIN_TRACK_GAIN=-6.48 dB",
+                    "REPLAYGAIN_ALBUM_GAIN=+1.5 dB",
+                ]),
+            )],
+        );
+
+        let metadata = read_metadata(&path).unwrap();
+        assert_eq!(metadata.replaygain_track_gain, Some(-6.48));
+        assert_eq!(metadata.replaygain_album_gain, Some(1.5));
+
+        use crate::audio::decoder::parse_gain_db;
+        assert_eq!(parse_gain_db("-3.2db"), Some(-3.2));
+        assert_eq!(parse_gain_db("loud"), None);
+        assert_eq!(parse_gain_db("NaN dB"), None);
+    }
+
+    #[test]
+    fn test_write_tags_round_trip() {
+        use crate::audio::decoder::read_tags;
+        use crate::test_util::{
+            vorbis_comment_block, write_verbatim_flac_with_metadata, FLAC_VORBIS_COMMENT,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged.flac");
+        // Front cover PICTURE block: type, MIME, description, size, data
+        let mut picture = 3u32.to_be_bytes().to_vec();
+        picture.extend_from_slice(&9u32.to_be_bytes());
+        picture.extend_from_slice(b"image/png");
+        picture.extend_from_slice(&[0; 4 * 5]);
+        picture.extend_from_slice(&64u32.to_be_bytes());
+        picture.extend_from_slice(&[0xAB; 64]);
+        write_verbatim_flac_with_metadata(
+            &path,
+            2,
+            &[
+                (
+                    FLAC_VORBIS_COMMENT,
+                    vorbis_comment_block(&[
+                        "TITLE=Old",
+                        "COMPOSER=Bach",
+                        "MOOD=calm",
+                        "TRACKNUMBER=1/12",
+                    ]),
+                ),
+                (6, picture.clone()),
+            ],
+        );
+        let original = std::fs::read(&path).unwrap();
+
+        let tags = TrackTags {
+            title: Some("New Title".to_string()),
+            artist: Some("Artist".to_string()),
+            track_number: Some(3),
+            year: Some(2001),
+            ..Default::default()
+        };
+        write_tags(&path, &tags).unwrap();
+
+        let read = read_tags(&path).unwrap();
+        assert_eq!(
+            read,
+            TrackTags {
+                composer: Some("Bach".to_string()),
+                ..tags
+            }
+        );
+
+        // Picture, unknown comments and frames survive
+        let written = std::fs::read(&path).unwrap();
+        let contains = |needle: &[u8]| written.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&picture));
+        assert!(contains(b"MOOD=calm"));
+        assert!(written.ends_with(&original[original.len() - 8192..]));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // The inline total is kept
+        let tagged = lofty::read_from_path(&path).unwrap();
+        assert_eq!(tagged.primary_tag().unwrap().track_total(), Some(12));
+    }
+
+    #[test]
+    fn test_write_tags_adds_comment_block() {
+        use crate::audio::decoder::read_tags;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bare.flac");
+        crate::test_util::write_verbatim_flac(&path, 1);
+
+        let tags = TrackTags {
+            album: Some("Album".to_string()),
+            ..Default::default()
+        };
+        write_tags(&path, &tags).unwrap();
+        assert_eq!(read_tags(&path).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_write_tags_mp3_keeps_frames_and_track_total() {
+        // v2.3 tag with a title, "3/12", a cover and a custom TXXX frame
+        let frame = |id: &[u8; 4], body: &[u8]| {
+            let mut bytes = id.to_vec();
+            bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&[0, 0]);
+            bytes.extend_from_slice(body);
+            bytes
+        };
+        let mut apic = b"\0image/png\0\x03\0".to_vec();
+        apic.extend_from_slice(&[0xAB; 32]);
+        let mut body = frame(b"TIT2", b"\0Old");
+        body.extend(frame(b"TRCK", b"\x003/12"));
+        body.extend(frame(b"APIC", &apic));
+        body.extend(frame(b"TXXX", b"\0MOOD\0calm"));
+        body.extend([0; 16]); // padding
+        let size = [21, 14, 7, 0].map(|shift| ((body.len() >> shift) & 0x7F) as u8);
+        let mut file = vec![b'I', b'D', b'3', 3, 0, 0];
+        file.extend_from_slice(&size);
+        file.extend(body);
+        // MPEG-1 Layer III frames of silence, 128 kbps at 44.1kHz
+        let audio: Vec<u8> = (0..20)
+            .flat_map(|_| {
+                let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+                frame.resize(417, 0);
+                frame
+            })
+            .collect();
+        file.extend_from_slice(&audio);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged.mp3");
+        std::fs::write(&path, &file).unwrap();
+
+        let tags = TrackTags {
+            title: Some("Né".to_string()),
+            track_number: Some(4),
+            year: Some(1999),
+            ..Default::default()
+        };
+        write_tags(&path, &tags).unwrap();
+
+        let tagged = lofty::read_from_path(&path).unwrap();
+        let tag = tagged.primary_tag().unwrap();
+        assert_eq!(tag.title().as_deref(), Some("Né"));
+        assert_eq!(tag.track(), Some(4));
+        assert_eq!(tag.track_total(), Some(12));
+        assert_eq!(tag.date().map(|date| date.year), Some(1999));
+        assert_eq!(tag.pictures().len(), 1);
+        assert_eq!(tag.pictures()[0].data(), &[0xAB; 32]);
+
+        let written = std::fs::read(&path).unwrap();
+        let contains = |needle: &[u8]| written.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"4/12"));
+        assert!(contains(b"calm"));
+        assert!(written.ends_with(&audio));
+    }
+
+    /// Ogg page holding one whole packet
+    fn ogg_page(header_type: u8, granule: u64, sequence: u32, packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(header_type);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&1u32.to_le_bytes()); // serial
+        page.extend_from_slice(&sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // CRC, filled in below
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        page.push(lacing.len() as u8);
+        page.extend(lacing);
+        page.extend_from_slice(packet);
+
+        // CRC-32 with polynomial 0x04C11DB7, no reflection
+        let crc = page.iter().fold(0u32, |crc, &byte| {
+            (0..8).fold(crc ^ (u32::from(byte) << 24), |crc, _| {
+                if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04C1_1DB7
+                } else {
+                    crc << 1
+                }
+            })
+        });
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    #[test]
+    fn test_write_tags_ogg_opus() {
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(&312u16.to_le_bytes()); // pre-skip
+        head.extend_from_slice(&48000u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]); // gain, mapping family
+        let mut comments = b"OpusTags".to_vec();
+        comments.extend(crate::test_util::vorbis_comment_block(&[
+            "TRACKNUMBER=2",
+            "TRACKTOTAL=9",
+        ]));
+
+        let mut file = ogg_page(0x02, 0, 0, &head);
+        file.extend(ogg_page(0, 0, 1, &comments));
+        // A single audio packet whose granule ends one second in
+        file.extend(ogg_page(0x04, 48000 + 312, 2, &[0xF8; 50]));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged.opus");
+        std::fs::write(&path, &file).unwrap();
+
+        let tags = TrackTags {
+            album: Some("Album".to_string()),
+            track_number: Some(5),
+            ..Default::default()
+        };
+        write_tags(&path, &tags).unwrap();
+
+        let tagged = lofty::read_from_path(&path).unwrap();
+        let tag = tagged.primary_tag().unwrap();
+        assert_eq!(tag.album().as_deref(), Some("Album"));
+        assert_eq!(tag.track(), Some(5));
+        assert_eq!(tag.track_total(), Some(9));
+    }
+}
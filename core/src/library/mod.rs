@@ -4,7 +4,14 @@
 
 pub mod database;
 pub mod indexer;
+pub mod loudness;
 pub mod metadata;
+pub mod resume;
 pub mod scanner;
+pub mod stats;
 
-// Will be implemented in Phase 5
+pub use database::{album_loudness, LibraryDb, TrackLoudness, TrackStats};
+pub use loudness::LoudnessLookup;
+pub use metadata::{read_metadata, write_tags, TrackMetadata};
+pub use scanner::{ScanEvent, ScanHandle, ScanListing, ScanOptions, Scanner, SkippedFile};
+pub use stats::{PlayTracker, ScrobbleRule};
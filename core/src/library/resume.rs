@@ -0,0 +1,119 @@
+//! Resume-from-position support
+//!
+//! Restores and records where playback of long-form content stopped
+
+use crate::audio::engine::{AudioEngine, AudioEngineInterface};
+use crate::error::Result;
+use crate::library::database::LibraryDb;
+use std::path::Path;
+
+/// Playback within this many seconds of the end counts as finished
+pub const RESUME_END_MARGIN_SECONDS: f64 = 10.0;
+
+/// Check whether a position is close enough to the end to count as finished
+///
+/// # Arguments
+/// * `position` - Position in sample frames
+/// * `duration` - Track duration in sample frames
+/// * `sample_rate` - Sample rate in Hz
+pub fn is_near_end(position: u64, duration: u64, sample_rate: u32) -> bool {
+    let margin = (RESUME_END_MARGIN_SECONDS * sample_rate as f64) as u64;
+    position.saturating_add(margin) >= duration
+}
+
+/// Load a file, seeking to its stored resume position when `resume` is set
+///
+/// # Returns
+/// The position playback was resumed from, if any
+pub fn load_with_resume(
+    engine: &mut AudioEngine,
+    db: &LibraryDb,
+    path: &Path,
+    resume: bool,
+) -> Result<Option<u64>> {
+    engine.load_file(path)?;
+
+    if !resume {
+        return Ok(None);
+    }
+
+    let key = path.to_string_lossy();
+    let position = match db.get_resume_position(&key)? {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+
+    let finished = match (engine.duration(), engine.format()) {
+        (Some(duration), Some(format)) => is_near_end(position, duration, format.sample_rate),
+        _ => false,
+    };
+    if finished {
+        db.clear_resume_position(&key)?;
+        return Ok(None);
+    }
+
+    engine.seek(position)?;
+    Ok(Some(position))
+}
+
+/// Store the engine's current position as the resume point of `path`
+pub fn save_resume_position(engine: &AudioEngine, db: &LibraryDb, path: &Path) -> Result<()> {
+    let sample_rate = engine.format().map(|f| f.sample_rate).unwrap_or(44100);
+    update_resume_position(
+        db,
+        &path.to_string_lossy(),
+        engine.position(),
+        engine.duration(),
+        sample_rate,
+    )
+}
+
+/// Record a resume position, clearing it once playback is near the end
+pub fn update_resume_position(
+    db: &LibraryDb,
+    path: &str,
+    position: u64,
+    duration: Option<u64>,
+    sample_rate: u32,
+) -> Result<()> {
+    let finished = duration.is_some_and(|d| is_near_end(position, d, sample_rate));
+
+    if finished || position == 0 {
+        db.clear_resume_position(path)
+    } else {
+        db.set_resume_position(path, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_near_end_threshold() {
+        let rate = 44100;
+        let duration = 600 * rate as u64;
+        let margin = (RESUME_END_MARGIN_SECONDS * rate as f64) as u64;
+
+        assert!(!is_near_end(0, duration, rate));
+        assert!(!is_near_end(duration - margin - 1, duration, rate));
+        assert!(is_near_end(duration - margin, duration, rate));
+        assert!(is_near_end(duration, duration, rate));
+    }
+
+    #[test]
+    fn test_update_clears_near_end() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let rate = 44100;
+        let duration = Some(600 * rate as u64);
+
+        update_resume_position(&db, "/pod/ep.mp3", 120 * rate as u64, duration, rate).unwrap();
+        assert_eq!(
+            db.get_resume_position("/pod/ep.mp3").unwrap(),
+            Some(120 * rate as u64)
+        );
+
+        update_resume_position(&db, "/pod/ep.mp3", 595 * rate as u64, duration, rate).unwrap();
+        assert_eq!(db.get_resume_position("/pod/ep.mp3").unwrap(), None);
+    }
+}
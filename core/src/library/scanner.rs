@@ -1,5 +1,948 @@
 //! File system scanner
 //!
-//! Scans directories for audio files
+//! Scans directories for audio files. A file described by a CUE sheet (a
+//! `.cue` next to it, a CUESHEET tag or a FLAC CUESHEET block) yields one
+//! entry per CUE track.
 
-// Will be implemented in Phase 5
+use crate::audio::decoder::is_format_supported;
+use crate::cue::sheet::{CueFile, CueSheet};
+use crate::cue::{parse_cue, read_flac_cuesheet};
+use crate::error::{Error, Result};
+use crate::library::metadata::{read_metadata_with_cue, TrackMetadata};
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Maximum number of scan events buffered ahead of the receiver
+pub const SCAN_CHANNEL_CAPACITY: usize = 32;
+
+/// Event reported by a streaming scan
+#[derive(Debug, Clone)]
+pub enum ScanEvent {
+    /// Metadata of a supported audio file, or of one of its CUE tracks
+    Found(Box<TrackMetadata>),
+    /// A file was left out because of the scan options
+    Skipped(SkippedFile),
+    /// A file or directory could not be read
+    Error {
+        /// Path that failed
+        path: PathBuf,
+        /// Error description
+        message: String,
+    },
+    /// Number of files processed so far out of the total
+    Progress {
+        /// Files processed
+        scanned: usize,
+        /// Files found by the counting pass
+        total: usize,
+    },
+    /// The scan finished without being cancelled
+    Done,
+}
+
+/// Limits and filters applied while walking the library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Directory levels descended below the root (`Some(0)` = root only)
+    pub max_depth: Option<usize>,
+    /// Files larger than this many bytes are recorded as skipped, not probed
+    pub max_file_size: Option<u64>,
+    /// Ignore files and directories whose names start with a dot
+    pub skip_hidden: bool,
+    /// Threads probing files concurrently (0 is treated as 1)
+    pub workers: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            max_file_size: None,
+            skip_hidden: true,
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Audio file left out of a scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    /// Path of the file
+    pub path: PathBuf,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Files found by walking a library root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanListing {
+    /// Supported audio files to probe, sorted by path
+    pub files: Vec<PathBuf>,
+    /// Supported audio files over the size limit
+    pub skipped: Vec<SkippedFile>,
+    /// CUE sheets found alongside the audio files, sorted by path
+    pub cue_sheets: Vec<PathBuf>,
+}
+
+/// Control handle of a streaming scan
+///
+/// Dropping the handle leaves the scan running; dropping the event
+/// receiver stops it.
+pub struct ScanHandle {
+    cancelled: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ScanHandle {
+    /// Ask the scan to stop after the file it is currently reading
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Check if the scan was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Check if the scan thread has exited
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    /// Wait for the scan thread to exit
+    pub fn wait(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Audio file scanner
+#[derive(Debug, Clone, Default)]
+pub struct Scanner {
+    options: ScanOptions,
+}
+
+impl Scanner {
+    /// Create a new scanner with default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a scanner with the given options
+    pub fn with_options(options: ScanOptions) -> Self {
+        Self { options }
+    }
+
+    /// Get the scan options
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Recursively collect supported audio files under `root`, sorted by path
+    ///
+    /// Files over the size limit are left out; see `list_files`.
+    pub fn collect_files<P: AsRef<Path>>(&self, root: P) -> Result<Vec<PathBuf>> {
+        Ok(self.list_files(root)?.files)
+    }
+
+    /// Walk `root`, separating files to probe from those over the size limit
+    pub fn list_files<P: AsRef<Path>>(&self, root: P) -> Result<ScanListing> {
+        Walk::new(&self.options, &mut |_, _| {}).run(root.as_ref())
+    }
+
+    /// Scan `root` and return the metadata of every readable audio file
+    ///
+    /// Files are probed on `ScanOptions::workers` threads; the result is
+    /// sorted by path, with the CUE tracks of a file in sheet order. Files
+    /// and CUE sheets that fail to parse are skipped.
+    pub fn scan<P: AsRef<Path>>(&self, root: P) -> Result<Vec<TrackMetadata>> {
+        let listing = self.list_files(root)?;
+        let cues = CueAssociations::load(&listing.cue_sheets, &mut |_, _| {});
+        let tracks = Mutex::new(Vec::with_capacity(listing.files.len()));
+        probe_files(
+            &listing.files,
+            &cues,
+            self.options.workers,
+            &AtomicBool::new(false),
+            |_, result| {
+                if let Ok(metadata) = result {
+                    tracks.lock().extend(metadata);
+                }
+                true
+            },
+        );
+
+        // Stable, so a file's CUE tracks keep their order
+        let mut tracks = tracks.into_inner();
+        tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(tracks)
+    }
+
+    /// Scan `root` on a background thread, streaming results as they are found
+    ///
+    /// Files are counted first so progress can report a total. The event
+    /// channel closes when the scan ends; `Done` is only sent when it ran to
+    /// completion.
+    pub fn scan_streaming<P: AsRef<Path>>(&self, root: P) -> (ScanHandle, Receiver<ScanEvent>) {
+        let root = root.as_ref().to_path_buf();
+        let options = self.options.clone();
+        let (sender, receiver) = sync_channel(SCAN_CHANNEL_CAPACITY);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = cancelled.clone();
+
+        let thread = std::thread::spawn(move || {
+            Self::run_scan(&root, &options, &sender, &thread_cancelled);
+        });
+
+        (
+            ScanHandle {
+                cancelled,
+                thread: Some(thread),
+            },
+            receiver,
+        )
+    }
+
+    /// Body of the scan thread
+    ///
+    /// Returns early when cancelled or when the receiver is gone.
+    fn run_scan(
+        root: &Path,
+        options: &ScanOptions,
+        sender: &SyncSender<ScanEvent>,
+        cancelled: &AtomicBool,
+    ) {
+        let is_cancelled = || cancelled.load(Ordering::Acquire);
+
+        // Fast counting pass; errors go out as they are found so a dropped
+        // receiver stops the walk too
+        let disconnected = Cell::new(false);
+        let stop = || is_cancelled() || disconnected.get();
+        let report = |path: &Path, message: String| {
+            if !stop()
+                && sender
+                    .send(ScanEvent::Error {
+                        path: path.to_path_buf(),
+                        message,
+                    })
+                    .is_err()
+            {
+                disconnected.set(true);
+            }
+        };
+        let walked = Walk::new(options, &mut |path, error| report(path, error.to_string()))
+            .stop_when(&stop)
+            .run(root);
+        if stop() {
+            return;
+        }
+        let ScanListing {
+            files,
+            skipped,
+            cue_sheets,
+        } = match walked {
+            Ok(listing) => listing,
+            Err(e) => {
+                let _ = sender.send(ScanEvent::Error {
+                    path: root.to_path_buf(),
+                    message: e.to_string(),
+                });
+                return;
+            }
+        };
+        let cues = CueAssociations::load(&cue_sheets, &mut |path, error| {
+            report(path, error.to_string())
+        });
+        if stop() {
+            return;
+        }
+        for event in skipped.into_iter().map(ScanEvent::Skipped) {
+            if is_cancelled() || sender.send(event).is_err() {
+                return;
+            }
+        }
+
+        let total = files.len();
+        if is_cancelled()
+            || sender
+                .send(ScanEvent::Progress { scanned: 0, total })
+                .is_err()
+        {
+            return;
+        }
+
+        // Results go out one at a time so progress counts stay in order
+        let scanned = Mutex::new(0);
+        let completed = AtomicBool::new(true);
+        probe_files(&files, &cues, options.workers, cancelled, |path, result| {
+            let events = match result {
+                Ok(tracks) => tracks
+                    .into_iter()
+                    .map(|track| ScanEvent::Found(Box::new(track)))
+                    .collect(),
+                Err(e) => vec![ScanEvent::Error {
+                    path: path.to_path_buf(),
+                    message: e.to_string(),
+                }],
+            };
+
+            let mut scanned = scanned.lock();
+            *scanned += 1;
+            let progress = ScanEvent::Progress {
+                scanned: *scanned,
+                total,
+            };
+            let sent = !is_cancelled()
+                && events.into_iter().all(|event| sender.send(event).is_ok())
+                && sender.send(progress).is_ok();
+            if !sent {
+                completed.store(false, Ordering::Release);
+            }
+            sent
+        });
+
+        if completed.load(Ordering::Acquire) && !is_cancelled() {
+            let _ = sender.send(ScanEvent::Done);
+        }
+    }
+}
+
+/// Read the tracks of `files` on up to `workers` threads
+///
+/// `handle` receives every result, in no particular order. Workers stop
+/// taking new files once `cancelled` is set or `handle` returns false.
+fn probe_files<F>(
+    files: &[PathBuf],
+    cues: &CueAssociations,
+    workers: usize,
+    cancelled: &AtomicBool,
+    handle: F,
+) where
+    F: Fn(&Path, Result<Vec<TrackMetadata>>) -> bool + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let worker = || {
+        while !stopped.load(Ordering::Acquire) && !cancelled.load(Ordering::Acquire) {
+            let Some(path) = files.get(next.fetch_add(1, Ordering::AcqRel)) else {
+                return;
+            };
+            if !handle(path, read_tracks(path, cues)) {
+                stopped.store(true, Ordering::Release);
+            }
+        }
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 1..workers.clamp(1, files.len().max(1)) {
+            scope.spawn(worker);
+        }
+        worker();
+    });
+}
+
+/// Read the tracks of an audio file
+///
+/// A CUE sheet describing the file splits it into its CUE tracks; a
+/// sibling `.cue` takes precedence over a CUESHEET tag, which takes
+/// precedence over a native FLAC CUESHEET block. Without one the whole
+/// file is a single track.
+fn read_tracks(path: &Path, cues: &CueAssociations) -> Result<Vec<TrackMetadata>> {
+    let (metadata, embedded) = read_metadata_with_cue(path)?;
+    if let Some(sheet) = cues.sheet_for(path) {
+        return Ok(metadata.split_by_cue(sheet));
+    }
+
+    Ok(
+        match embedded
+            .and_then(|text| embedded_sheet(&text, path))
+            .or_else(|| {
+                let rate = metadata.format.sample_rate?;
+                if metadata.format.format_name != "FLAC" {
+                    return None;
+                }
+                read_flac_cuesheet(path, rate).ok().flatten()
+            }) {
+            Some(sheet) => metadata.split_by_cue(&sheet),
+            None => vec![metadata],
+        },
+    )
+}
+
+/// Pick the part of an embedded CUE sheet that describes `path`
+///
+/// Embedded sheets often name the file they were ripped to rather than the
+/// file carrying them, so a sheet with a single FILE entry applies as is.
+fn embedded_sheet(text: &str, path: &Path) -> Option<CueSheet> {
+    let sheet = parse_cue(text).ok()?;
+    let name = file_key(path)?.1;
+    let named = sheet
+        .files
+        .iter()
+        .find(|file| file_key(Path::new(&file.path)).is_some_and(|(_, other)| other == name));
+    let file = match (named, sheet.files.as_slice()) {
+        (Some(file), _) | (None, [file]) => file,
+        _ => return None,
+    };
+    Some(single_file_sheet(&sheet, file))
+}
+
+/// Copy of `sheet` describing only `file`
+fn single_file_sheet(sheet: &CueSheet, file: &CueFile) -> CueSheet {
+    CueSheet {
+        title: sheet.title.clone(),
+        performer: sheet.performer.clone(),
+        files: vec![file.clone()],
+    }
+}
+
+/// Directory and lowercased file name, for case-insensitive FILE matching
+fn file_key(path: &Path) -> Option<(PathBuf, String)> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    Some((path.parent()?.to_path_buf(), name))
+}
+
+/// CUE sheets of a scan, by the audio file each FILE entry refers to
+#[derive(Debug, Default)]
+struct CueAssociations {
+    /// Single-file sheets keyed by `file_key` of the referenced audio file
+    sheets: HashMap<(PathBuf, String), CueSheet>,
+}
+
+impl CueAssociations {
+    /// Parse `cue_paths`, reporting sheets that can't be read or parsed
+    ///
+    /// FILE paths are resolved against the sheet's directory. When several
+    /// sheets claim the same file, the first by path wins.
+    fn load(cue_paths: &[PathBuf], on_error: &mut dyn FnMut(&Path, Error)) -> Self {
+        let mut sheets = HashMap::new();
+        for cue_path in cue_paths {
+            let sheet = match std::fs::read(cue_path) {
+                // Sheets are often Latin-1; keep what decodes
+                Ok(bytes) => {
+                    parse_cue(String::from_utf8_lossy(&bytes).trim_start_matches('\u{feff}'))
+                }
+                Err(e) => Err(e.into()),
+            };
+            let sheet = match sheet {
+                Ok(sheet) => sheet,
+                Err(e) => {
+                    on_error(cue_path, e);
+                    continue;
+                }
+            };
+
+            let base_dir = cue_path.parent().unwrap_or(Path::new(""));
+            for file in &sheet.files {
+                if let Some(key) = file_key(&base_dir.join(&file.path)) {
+                    sheets
+                        .entry(key)
+                        .or_insert_with(|| single_file_sheet(&sheet, file));
+                }
+            }
+        }
+        Self { sheets }
+    }
+
+    /// Sheet describing the audio file at `path`
+    fn sheet_for(&self, path: &Path) -> Option<&CueSheet> {
+        self.sheets.get(&file_key(path)?)
+    }
+}
+
+/// Recursive directory walk honoring `ScanOptions`
+struct Walk<'a> {
+    options: &'a ScanOptions,
+    /// Canonical paths of directories already walked
+    visited: HashSet<PathBuf>,
+    listing: ScanListing,
+    on_error: &'a mut dyn FnMut(&Path, std::io::Error),
+    /// Checked before each entry; the walk ends early once it returns true
+    stop: &'a dyn Fn() -> bool,
+}
+
+impl<'a> Walk<'a> {
+    fn new(options: &'a ScanOptions, on_error: &'a mut dyn FnMut(&Path, std::io::Error)) -> Self {
+        Self {
+            options,
+            visited: HashSet::new(),
+            listing: ScanListing::default(),
+            on_error,
+            stop: &|| false,
+        }
+    }
+
+    /// End the walk early, with what was listed so far, once `stop` is true
+    fn stop_when(mut self, stop: &'a dyn Fn() -> bool) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Walk `root`, returning the collected listing
+    fn run(mut self, root: &Path) -> Result<ScanListing> {
+        self.dir(root, 0)?;
+        Ok(self.listing)
+    }
+
+    /// Walk `dir` recursively, collecting supported files in sorted order
+    ///
+    /// Directories reached again through symlinks are walked only once.
+    /// Unreadable subdirectories are reported through `on_error` and skipped;
+    /// only a failure to read `dir` itself is returned.
+    fn dir(&mut self, dir: &Path, depth: usize) -> Result<()> {
+        if !self.visited.insert(std::fs::canonicalize(dir)?) {
+            return Ok(());
+        }
+
+        let mut entries = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            if (self.stop)() {
+                return Ok(());
+            }
+            if self.options.skip_hidden && is_hidden(&path) {
+                continue;
+            }
+            if path.is_dir() {
+                if self.options.max_depth.is_some_and(|max| depth >= max) {
+                    continue;
+                }
+                if let Err(crate::Error::Io(e)) = self.dir(&path, depth + 1) {
+                    (self.on_error)(&path, e);
+                }
+            } else if is_format_supported(&path) {
+                self.file(path);
+            } else if is_cue_sheet(&path) {
+                self.listing.cue_sheets.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a supported file, or its skip when over the size limit
+    fn file(&mut self, path: PathBuf) {
+        let Some(max_size) = self.options.max_file_size else {
+            self.listing.files.push(path);
+            return;
+        };
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > max_size => {
+                self.listing.skipped.push(SkippedFile {
+                    path,
+                    size: metadata.len(),
+                });
+            }
+            Ok(_) => self.listing.files.push(path),
+            Err(e) => (self.on_error)(&path, e),
+        }
+    }
+}
+
+/// Check if a path has a `.cue` extension (any case)
+fn is_cue_sheet(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
+}
+
+/// Check if a path's file name starts with a dot
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn write_wav(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..80 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn make_library(count: usize) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("album");
+        std::fs::create_dir(&nested).unwrap();
+        for i in 0..count {
+            let parent = if i % 2 == 0 { dir.path() } else { &nested };
+            write_wav(&parent.join(format!("track{:03}.wav", i)));
+        }
+        std::fs::write(dir.path().join("cover.jpg"), b"not audio").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_scan_finds_nested_files() {
+        let dir = make_library(4);
+        let tracks = Scanner::new().scan(dir.path()).unwrap();
+        assert_eq!(tracks.len(), 4);
+        assert!(tracks.iter().all(|t| t.format.sample_rate == Some(8000)));
+    }
+
+    #[test]
+    fn test_scan_streaming_reports_progress_and_done() {
+        let dir = make_library(5);
+        std::fs::write(dir.path().join("broken.wav"), b"garbage").unwrap();
+
+        let (mut handle, events) = Scanner::new().scan_streaming(dir.path());
+        let events: Vec<_> = events.iter().collect();
+        handle.wait();
+
+        let found = events
+            .iter()
+            .filter(|e| matches!(e, ScanEvent::Found(_)))
+            .count();
+        let errors = events
+            .iter()
+            .filter(|e| matches!(e, ScanEvent::Error { .. }))
+            .count();
+        assert_eq!(found, 5);
+        assert_eq!(errors, 1);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ScanEvent::Progress {
+                scanned: 6,
+                total: 6
+            }
+        )));
+        assert!(matches!(events.last(), Some(ScanEvent::Done)));
+    }
+
+    #[test]
+    fn test_cancel_stops_scan_promptly() {
+        let total = 200;
+        let dir = make_library(total);
+
+        let (mut handle, events) = Scanner::new().scan_streaming(dir.path());
+        // Wait for the first result, then cancel
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+                ScanEvent::Found(_) => break,
+                _ => continue,
+            }
+        }
+        handle.cancel();
+
+        let remaining: Vec<_> = events.iter().collect();
+        handle.wait();
+
+        // Only events already buffered (plus one in flight) may still arrive
+        assert!(remaining.len() <= SCAN_CHANNEL_CAPACITY + 2);
+        assert!(!remaining.iter().any(|e| matches!(e, ScanEvent::Done)));
+        let found = remaining
+            .iter()
+            .filter(|e| matches!(e, ScanEvent::Found(_)))
+            .count();
+        assert!(found < total - 1);
+    }
+
+    #[test]
+    fn test_counting_pass_stops_when_cancelled() {
+        let dir = make_library(20);
+        let options = ScanOptions::default();
+
+        // The walk checks before every entry and ends once told to
+        let checks = std::cell::Cell::new(0);
+        let stop = || {
+            checks.set(checks.get() + 1);
+            checks.get() > 3
+        };
+        let listing = Walk::new(&options, &mut |_, _| {})
+            .stop_when(&stop)
+            .run(dir.path())
+            .unwrap();
+        assert!(listing.files.len() < 3);
+
+        // A scan cancelled during counting sends nothing
+        let (sender, events) = sync_channel(SCAN_CHANNEL_CAPACITY);
+        Scanner::run_scan(dir.path(), &options, &sender, &AtomicBool::new(true));
+        drop(sender);
+        assert_eq!(events.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_dropping_receiver_stops_scan() {
+        let dir = make_library(200);
+
+        let (mut handle, events) = Scanner::new().scan_streaming(dir.path());
+        events.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(events);
+
+        handle.wait();
+        assert!(handle.is_finished());
+        assert!(!handle.is_cancelled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_cycle_terminates() {
+        let dir = make_library(4);
+        // album/loop -> root, root/alias -> album
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("album").join("loop")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("album"), dir.path().join("alias")).unwrap();
+
+        let files = Scanner::new().collect_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 4);
+
+        let (mut handle, events) = Scanner::new().scan_streaming(dir.path());
+        let events: Vec<_> = events.iter().collect();
+        handle.wait();
+        assert!(matches!(events.last(), Some(ScanEvent::Done)));
+    }
+
+    #[test]
+    fn test_max_depth_limits_descent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut level = dir.path().to_path_buf();
+        for depth in 0..4 {
+            write_wav(&level.join(format!("depth{}.wav", depth)));
+            level = level.join(format!("level{}", depth + 1));
+            std::fs::create_dir(&level).unwrap();
+        }
+
+        let scan_depth = |max_depth| {
+            Scanner::with_options(ScanOptions {
+                max_depth,
+                ..ScanOptions::default()
+            })
+            .collect_files(dir.path())
+            .unwrap()
+            .len()
+        };
+        assert_eq!(scan_depth(Some(0)), 1);
+        assert_eq!(scan_depth(Some(2)), 3);
+        assert_eq!(scan_depth(None), 4);
+    }
+
+    #[test]
+    fn test_size_limit_and_hidden_entries() {
+        let dir = make_library(2);
+        let hidden = dir.path().join(".cache");
+        std::fs::create_dir(&hidden).unwrap();
+        write_wav(&hidden.join("cached.wav"));
+        write_wav(&dir.path().join(".hidden.wav"));
+        let large = dir.path().join("large.wav");
+        std::fs::write(&large, vec![0u8; 4096]).unwrap();
+
+        let scanner = Scanner::with_options(ScanOptions {
+            max_file_size: Some(1024),
+            ..ScanOptions::default()
+        });
+        let listing = scanner.list_files(dir.path()).unwrap();
+        assert_eq!(listing.files.len(), 2);
+        assert_eq!(
+            listing.skipped,
+            vec![SkippedFile {
+                path: large.clone(),
+                size: 4096
+            }]
+        );
+
+        // The oversized file is reported, never probed
+        let (mut handle, events) = scanner.scan_streaming(dir.path());
+        let events: Vec<_> = events.iter().collect();
+        handle.wait();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ScanEvent::Skipped(skip) if skip.path == large)));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, ScanEvent::Error { path, .. } if *path == large)));
+
+        let with_hidden = Scanner::with_options(ScanOptions {
+            skip_hidden: false,
+            ..ScanOptions::default()
+        });
+        assert_eq!(with_hidden.collect_files(dir.path()).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_single_threaded() {
+        let dir = make_library(24);
+        std::fs::write(dir.path().join("broken.wav"), b"garbage").unwrap();
+
+        let scan_with = |workers| {
+            Scanner::with_options(ScanOptions {
+                workers,
+                ..ScanOptions::default()
+            })
+            .scan(dir.path())
+            .unwrap()
+            .iter()
+            .map(|track| format!("{:?}", track))
+            .collect::<Vec<_>>()
+        };
+        let single = scan_with(1);
+        assert_eq!(single.len(), 24);
+        assert_eq!(scan_with(4), single);
+        assert_eq!(scan_with(0), single);
+
+        let (mut handle, events) = Scanner::with_options(ScanOptions {
+            workers: 4,
+            ..ScanOptions::default()
+        })
+        .scan_streaming(dir.path());
+        let events: Vec<_> = events.iter().collect();
+        handle.wait();
+        let progress: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                ScanEvent::Progress { scanned, .. } => Some(*scanned),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(progress, (0..=25).collect::<Vec<_>>());
+        assert!(matches!(events.last(), Some(ScanEvent::Done)));
+    }
+
+    #[test]
+    fn test_cue_sheets_split_files_into_virtual_tracks() {
+        use crate::test_util::{
+            vorbis_comment_block, write_verbatim_flac, write_verbatim_flac_with_metadata,
+            FLAC_VORBIS_COMMENT,
+        };
+
+        let dir = make_library(1);
+        // FILE names the audio file in another case
+        write_verbatim_flac(&dir.path().join("Album.flac"), 4);
+        std::fs::write(
+            dir.path().join("album.cue"),
+            "PERFORMER \"Band\"\nTITLE \"Record\"\nFILE \"ALBUM.FLAC\" WAVE\n\
+             TRACK 01 AUDIO\nTITLE \"One\"\nINDEX 01 00:00:00\n\
+             TRACK 02 AUDIO\nTITLE \"Two\"\nINDEX 01 00:00:05\n\
+             TRACK 03 AUDIO\nTITLE \"Three\"\nINDEX 01 00:00:10\n",
+        )
+        .unwrap();
+
+        // A sheet embedded as a CUESHEET tag, naming the original rip
+        let embedded = "FILE \"rip.wav\" WAVE\n\
+                        TRACK 01 AUDIO\nINDEX 01 00:00:00\n\
+                        TRACK 02 AUDIO\nINDEX 01 00:00:15\n";
+        write_verbatim_flac_with_metadata(
+            &dir.path().join("tagged.flac"),
+            4,
+            &[(
+                FLAC_VORBIS_COMMENT,
+                vorbis_comment_block(&[&format!("CUESHEET={}", embedded)]),
+            )],
+        );
+
+        let tracks = Scanner::new().scan(dir.path()).unwrap();
+        let album: Vec<_> = tracks
+            .iter()
+            .filter(|t| t.path.ends_with("Album.flac"))
+            .collect();
+        assert_eq!(album.len(), 3);
+        let spans: Vec<_> = album
+            .iter()
+            .map(|t| {
+                let track = t.virtual_track.as_ref().unwrap();
+                assert_eq!(track.file_path, t.path);
+                (t.track_number, track.start_sample, track.end_sample)
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (Some(1), 0, Some(2940)),
+                (Some(2), 2940, Some(5880)),
+                (Some(3), 5880, None),
+            ]
+        );
+        assert_eq!(album[1].title.as_deref(), Some("Two"));
+        assert_eq!(album[1].artist.as_deref(), Some("Band"));
+        assert_eq!(album[1].album.as_deref(), Some("Record"));
+        let last = album[2].duration_seconds().unwrap();
+        assert!((last - (16384.0 - 5880.0) / 44100.0).abs() < 1e-9);
+
+        let tagged: Vec<_> = tracks
+            .iter()
+            .filter(|t| t.path.ends_with("tagged.flac"))
+            .collect();
+        assert_eq!(tagged.len(), 2);
+        assert_eq!(tagged[1].virtual_track.as_ref().unwrap().start_sample, 8820);
+
+        // Files without a sheet stay whole
+        let plain: Vec<_> = tracks
+            .iter()
+            .filter(|t| t.path.extension().is_some_and(|ext| ext == "wav"))
+            .collect();
+        assert_eq!(plain.len(), 1);
+        assert!(plain[0].virtual_track.is_none());
+
+        // Streaming scans report every CUE track and one progress step per file
+        let (mut handle, events) = Scanner::new().scan_streaming(dir.path());
+        let events: Vec<_> = events.iter().collect();
+        handle.wait();
+        let found = events
+            .iter()
+            .filter(|e| matches!(e, ScanEvent::Found(_)))
+            .count();
+        assert_eq!(found, 6);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ScanEvent::Progress {
+                scanned: 3,
+                total: 3
+            }
+        )));
+    }
+
+    #[test]
+    fn test_flac_cuesheet_block_splits_file() {
+        use crate::cue::flac::FLAC_CUESHEET;
+        use crate::test_util::{flac_cuesheet_block, write_verbatim_flac_with_metadata};
+
+        let dir = tempfile::tempdir().unwrap();
+        write_verbatim_flac_with_metadata(
+            &dir.path().join("native.flac"),
+            4,
+            &[(
+                FLAC_CUESHEET,
+                flac_cuesheet_block(
+                    0,
+                    &[
+                        (0, 1, true, &[(0, 1)]),
+                        (11760, 2, true, &[(0, 1)]),
+                        (16464, 170, true, &[]),
+                    ],
+                ),
+            )],
+        );
+
+        let tracks = Scanner::new().scan(dir.path()).unwrap();
+        let spans: Vec<_> = tracks
+            .iter()
+            .map(|t| {
+                let track = t.virtual_track.as_ref().unwrap();
+                (t.track_number, track.start_sample, track.end_sample)
+            })
+            .collect();
+        // The lead-out ends the last track
+        assert_eq!(
+            spans,
+            [(Some(1), 0, Some(11760)), (Some(2), 11760, Some(16464))]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Listening statistics
+//!
+//! Decides when a play counts and records it in the library
+
+use crate::audio::engine::AudioEvent;
+use crate::error::Result;
+use crate::library::database::LibraryDb;
+
+/// Longest position step counted as listening, in seconds
+///
+/// Positions must be reported at least this often; a larger step is a seek.
+pub const POSITION_REPORT_INTERVAL_SECONDS: f64 = 1.0;
+
+/// When a play counts towards the statistics
+///
+/// A track counts once it has been listened to for `min_fraction` of its
+/// length or `max_seconds`, whichever comes first (the common scrobble rule).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrobbleRule {
+    /// Fraction of the track that must be heard (0.0 to 1.0)
+    pub min_fraction: f64,
+    /// Seconds after which a play counts regardless of track length
+    pub max_seconds: f64,
+}
+
+impl Default for ScrobbleRule {
+    fn default() -> Self {
+        Self {
+            min_fraction: 0.5,
+            max_seconds: 240.0,
+        }
+    }
+}
+
+impl ScrobbleRule {
+    /// Seconds of listening needed for a track of `duration_seconds`
+    pub fn threshold_seconds(&self, duration_seconds: f64) -> f64 {
+        (duration_seconds * self.min_fraction).min(self.max_seconds)
+    }
+}
+
+/// Track currently being listened to
+#[derive(Debug, Clone)]
+struct CurrentPlay {
+    path: String,
+    duration_seconds: f64,
+    /// Last reported position
+    position_seconds: f64,
+    /// Time actually listened, excluding seeks
+    listened_seconds: f64,
+}
+
+/// Records plays in the library according to a `ScrobbleRule`
+///
+/// Feed it the engine's position at least every
+/// [`POSITION_REPORT_INTERVAL_SECONDS`] and its `TrackEnded` events; skips
+/// are reported with `skip`. `Player::record_plays` wires one to a player.
+#[derive(Debug, Clone, Default)]
+pub struct PlayTracker {
+    rule: ScrobbleRule,
+    current: Option<CurrentPlay>,
+}
+
+impl PlayTracker {
+    /// Create a tracker with a custom rule
+    pub fn new(rule: ScrobbleRule) -> Self {
+        Self {
+            rule,
+            current: None,
+        }
+    }
+
+    /// Get the scrobble rule
+    pub fn rule(&self) -> ScrobbleRule {
+        self.rule
+    }
+
+    /// Start tracking a new track
+    pub fn start(&mut self, path: impl Into<String>, duration_seconds: f64) {
+        self.current = Some(CurrentPlay {
+            path: path.into(),
+            duration_seconds,
+            position_seconds: 0.0,
+            listened_seconds: 0.0,
+        });
+    }
+
+    /// Report the playback position of the current track
+    ///
+    /// The step since the last report counts as listened when it moves
+    /// forward by at most [`POSITION_REPORT_INTERVAL_SECONDS`]; seeks
+    /// backwards or further ahead only move the reference point.
+    pub fn update_position(&mut self, seconds: f64) {
+        if let Some(current) = &mut self.current {
+            let step = seconds - current.position_seconds;
+            if step > 0.0 && step <= POSITION_REPORT_INTERVAL_SECONDS {
+                current.listened_seconds += step;
+            }
+            current.position_seconds = seconds;
+        }
+    }
+
+    /// Check if the current track has been listened to long enough to count
+    pub fn enough_listened(&self) -> bool {
+        self.current.as_ref().is_some_and(|current| {
+            current.listened_seconds >= self.rule.threshold_seconds(current.duration_seconds)
+        })
+    }
+
+    /// The current track played to the end
+    ///
+    /// Counts like any other report: a track only counts if enough of it
+    /// was heard on the way to the end.
+    ///
+    /// # Returns
+    /// `true` if a play was recorded
+    pub fn track_ended(&mut self, db: &LibraryDb) -> Result<bool> {
+        if let Some(duration) = self.current.as_ref().map(|c| c.duration_seconds) {
+            self.update_position(duration);
+        }
+        self.finish(db)
+    }
+
+    /// The user skipped away from the current track
+    ///
+    /// The play only counts if the threshold was already crossed.
+    pub fn skip(&mut self, db: &LibraryDb) -> Result<bool> {
+        self.finish(db)
+    }
+
+    /// Handle an engine event
+    pub fn handle_event(&mut self, event: &AudioEvent, db: &LibraryDb) -> Result<bool> {
+        match event {
+            AudioEvent::TrackEnded => self.track_ended(db),
+            _ => Ok(false),
+        }
+    }
+
+    fn finish(&mut self, db: &LibraryDb) -> Result<bool> {
+        let counted = self.enough_listened();
+        if let (true, Some(current)) = (counted, &self.current) {
+            db.record_play(&current.path, chrono::Utc::now().timestamp())?;
+        }
+        self.current = None;
+        Ok(counted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Report positions from `from` to `to` as steady playback
+    fn listen(tracker: &mut PlayTracker, from: f64, to: f64) {
+        let mut seconds = from;
+        while seconds < to {
+            seconds = (seconds + 0.5).min(to);
+            tracker.update_position(seconds);
+        }
+    }
+
+    fn play_count(db: &LibraryDb, path: &str) -> u32 {
+        db.track_stats(path)
+            .unwrap()
+            .map(|stats| stats.play_count)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_threshold_is_half_or_four_minutes() {
+        let rule = ScrobbleRule::default();
+        assert_eq!(rule.threshold_seconds(200.0), 100.0);
+        assert_eq!(rule.threshold_seconds(3600.0), 240.0);
+    }
+
+    #[test]
+    fn test_skip_before_threshold_does_not_count() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let mut tracker = PlayTracker::default();
+
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 99.0);
+        assert!(!tracker.skip(&db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 0);
+
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 100.0);
+        assert!(tracker.skip(&db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 1);
+    }
+
+    #[test]
+    fn test_track_end_counts() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let mut tracker = PlayTracker::default();
+
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 199.5);
+        assert!(tracker.handle_event(&AudioEvent::TrackEnded, &db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 1);
+
+        // Nothing playing: nothing to count
+        assert!(!tracker.handle_event(&AudioEvent::TrackEnded, &db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 1);
+
+        // Seeking to the end and letting it play out isn't a listen
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 10.0);
+        tracker.update_position(190.0);
+        listen(&mut tracker, 190.0, 199.5);
+        assert!(!tracker.handle_event(&AudioEvent::TrackEnded, &db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 1);
+    }
+
+    #[test]
+    fn test_seek_then_skip_counts_time_listened() {
+        let db = LibraryDb::open_in_memory().unwrap();
+        let mut tracker = PlayTracker::default();
+
+        // Seeking past the threshold doesn't count the skipped-over part
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 20.0);
+        tracker.update_position(150.0);
+        listen(&mut tracker, 150.0, 170.0);
+        assert!(!tracker.skip(&db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 0);
+
+        // Time listened before and after a seek back adds up
+        tracker.start("/song.flac", 200.0);
+        listen(&mut tracker, 0.0, 60.0);
+        tracker.update_position(10.0);
+        listen(&mut tracker, 10.0, 50.0);
+        assert!(tracker.skip(&db).unwrap());
+        assert_eq!(play_count(&db, "/song.flac"), 1);
+    }
+}
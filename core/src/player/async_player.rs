@@ -0,0 +1,266 @@
+//! Async facade over `Player` for tokio applications
+//!
+//! Blocking engine work runs on tokio's blocking pool, so awaiting a load
+//! or a render never stalls the runtime's worker threads.
+
+use super::Player;
+use crate::audio::engine::AudioEvent;
+use crate::audio::format::AudioFormat;
+use crate::audio::output::transcode_file;
+use crate::Result;
+use futures_core::Stream;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::broadcast;
+
+/// Events buffered per `EventStream` before a slow reader starts missing some
+pub const EVENT_STREAM_CAPACITY: usize = 256;
+
+/// Awaitable handle to a `Player`
+///
+/// Clones share the player and its event broadcast.
+#[derive(Clone)]
+pub struct AsyncPlayer {
+    /// Player doing the work
+    player: Player,
+    /// Broadcast every engine event is copied into
+    events: broadcast::Sender<AudioEvent>,
+}
+
+impl AsyncPlayer {
+    /// Create an async player with a new engine on the default audio host
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_player(Player::new()?))
+    }
+
+    /// Wrap an existing player; its other handles keep working
+    pub fn from_player(player: Player) -> Self {
+        let (events, _) = broadcast::channel(EVENT_STREAM_CAPACITY);
+        let sender = events.clone();
+        // Sending fails while nobody is listening; later streams still want
+        // events, so the listener stays registered
+        player.add_listener(Box::new(move |event| {
+            let _ = sender.send(event.clone());
+            true
+        }));
+        Self { player, events }
+    }
+
+    /// Get the underlying blocking player
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    /// Stream every engine event from now on
+    ///
+    /// A stream that falls more than `EVENT_STREAM_CAPACITY` events behind
+    /// skips the ones it missed.
+    pub fn events(&self) -> EventStream {
+        EventStream::new(self.events.subscribe())
+    }
+
+    /// Load an audio file, resolving once it is decoded and the output
+    /// stream is ready
+    ///
+    /// # Returns
+    /// The track's duration in samples, if known
+    pub async fn load<P: AsRef<Path>>(&self, path: P) -> Result<Option<u64>> {
+        let path = path.as_ref().to_path_buf();
+        self.run_blocking(move |player| {
+            player.load_file(path)?;
+            Ok(player.duration())
+        })
+        .await
+    }
+
+    /// Start playback
+    pub async fn play(&self) -> Result<()> {
+        self.run_blocking(|player| player.play()).await
+    }
+
+    /// Pause playback
+    pub async fn pause(&self) -> Result<()> {
+        self.run_blocking(|player| player.pause()).await
+    }
+
+    /// Stop playback and reset position
+    pub async fn stop(&self) -> Result<()> {
+        self.run_blocking(|player| player.stop()).await
+    }
+
+    /// Skip to the next queued track, resolving once it is loaded
+    ///
+    /// # Returns
+    /// `false` if there is no next track
+    pub async fn next(&self) -> Result<bool> {
+        self.run_blocking(|player| player.next()).await
+    }
+
+    /// Seek to a specific position (in samples)
+    pub async fn seek(&self, position: u64) -> Result<()> {
+        self.run_blocking(move |player| player.seek(position)).await
+    }
+
+    /// Decode `src` and write it to `dst` as a WAV file in `target` format
+    ///
+    /// Dithers with the engine's dithering setting; see `transcode_file`.
+    ///
+    /// # Returns
+    /// The number of frames written
+    pub async fn render_to_file<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        src: P,
+        dst: Q,
+        target: AudioFormat,
+    ) -> Result<u64> {
+        let src = src.as_ref().to_path_buf();
+        let dst = dst.as_ref().to_path_buf();
+        self.run_blocking(move |player| {
+            let dither = player.with_engine(|engine| engine.dithering());
+            transcode_file(src, dst, target, dither)
+        })
+        .await
+    }
+
+    /// Run `f` on the blocking pool and await its result
+    async fn run_blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Player) -> Result<T> + Send + 'static,
+    {
+        let player = self.player.clone();
+        tokio::task::spawn_blocking(move || f(&player))
+            .await
+            .map_err(|e| crate::Error::AudioEngine(format!("Player task failed: {}", e)))?
+    }
+}
+
+/// Pending receive of an `EventStream`, handing the receiver back with the
+/// result
+type RecvFuture = Pin<
+    Box<
+        dyn Future<
+                Output = (
+                    std::result::Result<AudioEvent, broadcast::error::RecvError>,
+                    broadcast::Receiver<AudioEvent>,
+                ),
+            > + Send,
+    >,
+>;
+
+/// `Stream` of engine events from `AsyncPlayer::events`
+///
+/// Ends when every handle to the player is gone.
+pub struct EventStream {
+    /// Receive in progress
+    next: RecvFuture,
+}
+
+impl EventStream {
+    fn new(receiver: broadcast::Receiver<AudioEvent>) -> Self {
+        Self {
+            next: Box::pin(Self::recv(receiver)),
+        }
+    }
+
+    async fn recv(
+        mut receiver: broadcast::Receiver<AudioEvent>,
+    ) -> (
+        std::result::Result<AudioEvent, broadcast::error::RecvError>,
+        broadcast::Receiver<AudioEvent>,
+    ) {
+        let result = receiver.recv().await;
+        (result, receiver)
+    }
+
+    /// Wait for the next event, or `None` once the stream has ended
+    pub async fn recv_event(&mut self) -> Option<AudioEvent> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Stream for EventStream {
+    type Item = AudioEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<AudioEvent>> {
+        loop {
+            let (result, receiver) = match self.next.as_mut().poll(cx) {
+                Poll::Ready(ready) => ready,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.next = Box::pin(Self::recv(receiver));
+            match result {
+                Ok(event) => return Poll::Ready(Some(event)),
+                // Skip what a slow reader missed and carry on
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::engine::{AudioEngine, PlaybackState};
+    use crate::audio::output::NullBackend;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_events_arrive_on_stream() {
+        let player = AsyncPlayer::new().unwrap();
+        let mut first = player.events();
+        let mut second = player.clone().events();
+
+        player.seek(1000).await.unwrap();
+
+        for stream in [&mut first, &mut second] {
+            let event = tokio::time::timeout(Duration::from_secs(1), stream.recv_event())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(matches!(event, AudioEvent::PositionChanged(1000)));
+        }
+        assert_eq!(player.player().position(), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_load_resolves_with_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..22050 * 2 {
+            writer.write_sample(1000i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        let player = AsyncPlayer::from_player(Player::from_engine(engine));
+
+        // Rendering needs no device
+        let rendered = dir.path().join("rendered.wav");
+        let target = AudioFormat::new(44100, 2, crate::audio::format::SampleFormat::F32);
+        let frames = player
+            .render_to_file(&path, &rendered, target)
+            .await
+            .unwrap();
+        assert_eq!(frames, 22050);
+
+        // Loading opens the output, here the null backend's
+        let duration = player.load(&path).await.unwrap();
+        assert_eq!(duration, Some(22050));
+        assert_eq!(player.player().state(), PlaybackState::Stopped);
+        assert!(player
+            .player()
+            .with_engine(|engine| engine.is_stream_initialized()));
+    }
+}
@@ -0,0 +1,505 @@
+//! Shareable player facade
+//!
+//! `Player` wraps an `AudioEngine` behind a lock so it can be cloned into
+//! threads, async tasks and UI code, all driving the same playback. With
+//! the `tokio` feature, `AsyncPlayer` adds awaitable loading and a
+//! broadcast event stream on top.
+
+#[cfg(feature = "tokio")]
+mod async_player;
+
+#[cfg(feature = "tokio")]
+pub use async_player::{AsyncPlayer, EventStream};
+
+use crate::audio::device_monitor::PollThread;
+use crate::audio::engine::{
+    AudioEngine, AudioEngineInterface, AudioEvent, PlaybackState, PlaybackStatus,
+};
+use crate::library::{LibraryDb, PlayTracker, ScrobbleRule};
+use crate::Result;
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Event listener; returns `false` once it wants no more events
+type Listener = Box<dyn Fn(&AudioEvent) -> bool + Send>;
+
+/// Event listeners registered with `Player::subscribe`
+type Subscribers = Arc<Mutex<Vec<Listener>>>;
+
+/// How often the position is reported to the play tracker
+///
+/// Well inside `POSITION_REPORT_INTERVAL_SECONDS` even at triple speed.
+const PLAY_REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Play counting set up by `Player::record_plays`
+struct PlayRecorder {
+    tracker: PlayTracker,
+    db: Arc<Mutex<LibraryDb>>,
+    status: PlaybackStatus,
+}
+
+impl PlayRecorder {
+    /// Start tracking whatever the engine has loaded now
+    fn restart(&mut self) {
+        if let Some(path) = self.status.current_path() {
+            let duration = self.status.duration_seconds().unwrap_or(0.0);
+            self.tracker
+                .start(path.to_string_lossy().into_owned(), duration);
+        }
+    }
+
+    /// Report the engine's position to the tracker
+    fn report(&mut self) {
+        self.tracker.update_position(self.status.position_seconds());
+    }
+
+    /// Count the current track if it was listened to long enough
+    fn skip(&mut self) {
+        self.report();
+        if let Err(e) = self.tracker.skip(&self.db.lock()) {
+            eprintln!("Warning: Failed to record play: {}", e);
+        }
+    }
+
+    fn handle_event(&mut self, event: &AudioEvent) {
+        match event {
+            AudioEvent::TrackEnded => {
+                if let Err(e) = self.tracker.handle_event(event, &self.db.lock()) {
+                    eprintln!("Warning: Failed to record play: {}", e);
+                }
+                // A gapless or looping advance has already moved on
+                self.restart();
+            }
+            AudioEvent::PositionChanged(_) => self.report(),
+            _ => {}
+        }
+    }
+}
+
+/// Thread migrating the engine to each new default output device
+///
+/// Woken by the player's listener for `DeviceChanged`, so the stream is
+/// rebuilt as soon as the engine's monitor reports a change, without
+/// blocking the monitor. Stopped and joined when dropped.
+struct DeviceFollower {
+    stop: Option<crossbeam::channel::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeviceFollower {
+    fn start(engine: Weak<Mutex<AudioEngine>>, changes: crossbeam::channel::Receiver<()>) -> Self {
+        let (stop, stopped) = crossbeam::channel::bounded::<()>(0);
+        let thread = std::thread::spawn(move || loop {
+            crossbeam::channel::select! {
+                recv(changes) -> change => {
+                    let Some(engine) = change.ok().and_then(|_| engine.upgrade()) else {
+                        break;
+                    };
+                    let applied = engine.lock().apply_pending_device_change();
+                    if let Err(e) = applied {
+                        eprintln!("Warning: Failed to follow the default device: {}", e);
+                    }
+                }
+                recv(stopped) -> _ => break,
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for DeviceFollower {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Cheaply cloneable handle to one audio engine and its queue
+///
+/// Every clone controls the same engine; methods take `&self` and lock the
+/// engine for the duration of the call. Engine events are delivered to all
+/// `subscribe` receivers, so the engine's own callback belongs to the
+/// player and shouldn't be replaced through `with_engine`.
+#[derive(Clone)]
+pub struct Player {
+    /// Engine shared by all clones
+    engine: Arc<Mutex<AudioEngine>>,
+    /// Senders of live subscriptions
+    subscribers: Subscribers,
+    /// Play counting, once `record_plays` is called
+    plays: Arc<Mutex<Option<PlayRecorder>>>,
+    /// Periodic position reports to the play counting
+    play_reporter: Arc<Mutex<Option<PollThread>>>,
+    /// Default device following, while `set_follow_default` is on
+    follower: Arc<Mutex<Option<DeviceFollower>>>,
+}
+
+impl Player {
+    /// Create a player with a new engine on the default audio host
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_engine(AudioEngine::new()?))
+    }
+
+    /// Create a player around an existing engine
+    ///
+    /// Replaces the engine's event callback with the player's fan-out.
+    pub fn from_engine(mut engine: AudioEngine) -> Self {
+        let subscribers: Subscribers = Arc::new(Mutex::new(Vec::new()));
+        let listeners = subscribers.clone();
+        engine.set_callback(Box::new(move |event| {
+            listeners.lock().retain(|listener| listener(&event));
+        }));
+
+        Self {
+            engine: Arc::new(Mutex::new(engine)),
+            subscribers,
+            plays: Arc::new(Mutex::new(None)),
+            play_reporter: Arc::new(Mutex::new(None)),
+            follower: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Receive every engine event from now on
+    ///
+    /// Each subscription gets its own copy of each event. Dropping the
+    /// receiver ends the subscription.
+    pub fn subscribe(&self) -> mpsc::Receiver<AudioEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.add_listener(Box::new(move |event| sender.send(event.clone()).is_ok()));
+        receiver
+    }
+
+    /// Call `listener` with every engine event until it returns `false`
+    fn add_listener(&self, listener: Listener) {
+        self.subscribers.lock().push(listener);
+    }
+
+    /// Record plays of the tracks this player plays in `db`
+    ///
+    /// A track counts when it ends or is left after being listened to for
+    /// `rule`'s threshold; skips, loads and queue changes before that don't.
+    /// The position is sampled in the background, so parts skipped over by
+    /// seeking aren't counted as listened.
+    pub fn record_plays(&self, db: Arc<Mutex<LibraryDb>>, rule: ScrobbleRule) {
+        let mut recorder = PlayRecorder {
+            tracker: PlayTracker::new(rule),
+            db,
+            status: self.engine.lock().status(),
+        };
+        recorder.restart();
+        let first = self.plays.lock().replace(recorder).is_none();
+
+        if first {
+            let plays = self.plays.clone();
+            self.add_listener(Box::new(move |event| {
+                if let Some(recorder) = plays.lock().as_mut() {
+                    recorder.handle_event(event);
+                }
+                true
+            }));
+
+            let plays = Arc::downgrade(&self.plays);
+            *self.play_reporter.lock() = Some(PollThread::start(PLAY_REPORT_INTERVAL, move || {
+                if let Some(plays) = plays.upgrade() {
+                    if let Some(recorder) = plays.lock().as_mut() {
+                        recorder.report();
+                    }
+                }
+            }));
+        }
+    }
+
+    /// Move playback to the system default device whenever it changes
+    ///
+    /// Turns on the engine's device change notifications and
+    /// `follow_default`; each change is applied as soon as it is reported,
+    /// keeping position, volume and playback state. Turning it off leaves
+    /// the notifications running.
+    pub fn set_follow_default(&self, follow: bool) {
+        {
+            let mut engine = self.engine.lock();
+            engine.set_follow_default(follow);
+            if follow {
+                engine.enable_device_change_notifications(true);
+            }
+        }
+
+        // Not under the engine lock: the follower may be waiting for it
+        let mut follower = self.follower.lock();
+        if !follow {
+            *follower = None;
+            return;
+        }
+        if follower.is_none() {
+            let (changes, received) = crossbeam::channel::unbounded();
+            self.add_listener(Box::new(move |event| match event {
+                AudioEvent::DeviceChanged(_) => changes.send(()).is_ok(),
+                _ => true,
+            }));
+            *follower = Some(DeviceFollower::start(
+                Arc::downgrade(&self.engine),
+                received,
+            ));
+        }
+    }
+
+    /// Run `change` on the engine as a skip away from the current track
+    fn change_track<R>(&self, change: impl FnOnce(&mut AudioEngine) -> Result<R>) -> Result<R> {
+        if let Some(recorder) = self.plays.lock().as_mut() {
+            recorder.skip();
+        }
+        let result = change(&mut self.engine.lock());
+        if let Some(recorder) = self.plays.lock().as_mut() {
+            recorder.restart();
+        }
+        result
+    }
+
+    /// Run `f` with exclusive access to the engine
+    ///
+    /// For engine settings the player doesn't wrap. Other clones block
+    /// until `f` returns.
+    pub fn with_engine<R>(&self, f: impl FnOnce(&mut AudioEngine) -> R) -> R {
+        f(&mut self.engine.lock())
+    }
+
+    /// Load an audio file for playback
+    pub fn load_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.change_track(|engine| engine.load_file(path))
+    }
+
+    /// Replace the playback queue and load its first track
+    pub fn set_queue(&self, paths: Vec<PathBuf>) -> Result<()> {
+        self.change_track(|engine| engine.set_queue(paths))
+    }
+
+    /// Get the queued files in insertion order
+    pub fn queue(&self) -> Vec<PathBuf> {
+        self.engine.lock().queue()
+    }
+
+    /// Start playback
+    pub fn play(&self) -> Result<()> {
+        self.engine.lock().play()
+    }
+
+    /// Pause playback
+    pub fn pause(&self) -> Result<()> {
+        self.engine.lock().pause()
+    }
+
+    /// Stop playback and reset position
+    pub fn stop(&self) -> Result<()> {
+        self.engine.lock().stop()
+    }
+
+    /// Skip to the next queued track
+    ///
+    /// # Returns
+    /// `false` if there is no next track
+    pub fn next(&self) -> Result<bool> {
+        self.change_track(|engine| engine.next())
+    }
+
+    /// Go to the previous queued track, or restart the current one
+    pub fn previous(&self) -> Result<()> {
+        self.change_track(|engine| engine.previous())
+    }
+
+    /// Seek to a specific position (in samples)
+    pub fn seek(&self, position: u64) -> Result<()> {
+        self.engine.lock().seek(position)
+    }
+
+    /// Seek to a time in seconds, clamped to the track
+    pub fn seek_to_seconds(&self, seconds: f64) -> Result<()> {
+        self.engine.lock().seek_to_seconds(seconds)
+    }
+
+    /// Set playback volume (0.0 to 1.0)
+    pub fn set_volume(&self, volume: f32) -> Result<()> {
+        self.engine.lock().set_volume(volume)
+    }
+
+    /// Get current playback volume
+    pub fn volume(&self) -> f32 {
+        self.engine.lock().volume()
+    }
+
+    /// Get current playback state
+    pub fn state(&self) -> PlaybackState {
+        self.engine.lock().state()
+    }
+
+    /// Get current playback position (in samples)
+    pub fn position(&self) -> u64 {
+        self.engine.lock().position()
+    }
+
+    /// Get total duration of loaded track (in samples)
+    pub fn duration(&self) -> Option<u64> {
+        self.engine.lock().duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_player_is_shareable() {
+        assert_send_sync::<Player>();
+
+        let player = Player::new().unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let player = player.clone();
+                thread::spawn(move || player.set_volume(i as f32 / 4.0).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let volume = player.volume();
+        assert!([0.0, 0.25, 0.5, 0.75].contains(&volume));
+        assert_eq!(player.state(), PlaybackState::Stopped);
+    }
+
+    #[test]
+    fn test_subscriptions_fan_out_events() {
+        let player = Player::new().unwrap();
+        let first = player.subscribe();
+        let second = player.subscribe();
+        let dropped = player.subscribe();
+        drop(dropped);
+
+        let remote = player.clone();
+        thread::spawn(move || remote.seek(1000).unwrap())
+            .join()
+            .unwrap();
+
+        for receiver in [&first, &second] {
+            let event = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert!(matches!(event, AudioEvent::PositionChanged(1000)));
+        }
+        assert_eq!(player.subscribers.lock().len(), 2);
+        assert_eq!(player.position(), 1000);
+    }
+
+    #[test]
+    fn test_record_plays_counts_ends_and_late_skips() {
+        use crate::audio::output::NullBackend;
+
+        let dir = tempfile::tempdir().unwrap();
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let paths: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(format!("{}.wav", name));
+                let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+                for _ in 0..44100 * 2 {
+                    writer.write_sample(1000i16).unwrap();
+                }
+                writer.finalize().unwrap();
+                path
+            })
+            .collect();
+
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.set_fade_duration(0);
+        let player = Player::from_engine(engine);
+        let db = Arc::new(Mutex::new(LibraryDb::open_in_memory().unwrap()));
+        player.record_plays(db.clone(), ScrobbleRule::default());
+        player.set_queue(paths.clone()).unwrap();
+        player.play().unwrap();
+
+        // Skipped before half the track, after it, then played to the end
+        player.with_engine(|engine| engine.advance_for_testing(13230));
+        assert!(player.next().unwrap());
+        player.with_engine(|engine| engine.advance_for_testing(26460));
+        assert!(player.next().unwrap());
+        player.with_engine(|engine| engine.advance_for_testing(48000));
+
+        let play_count = |path: &Path| {
+            db.lock()
+                .track_stats(&path.to_string_lossy())
+                .unwrap()
+                .map_or(0, |stats| stats.play_count)
+        };
+        let counts: Vec<_> = paths.iter().map(|path| play_count(path)).collect();
+        assert_eq!(counts, [0, 1, 1]);
+    }
+
+    #[test]
+    fn test_follows_default_device_change() {
+        use crate::audio::output::NullBackend;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..44100 * 2 {
+            writer.write_sample(1000i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let default_device = Arc::new(Mutex::new(Some("Speakers".to_string())));
+        let query = default_device.clone();
+        let mut engine = AudioEngine::new().unwrap();
+        engine.use_null_output(NullBackend::default());
+        engine.enable_device_change_notifications_with(move || query.lock().clone());
+        engine.load_file(&path).unwrap();
+
+        let player = Player::from_engine(engine);
+        player.set_follow_default(true);
+        player.play().unwrap();
+        player.with_engine(|engine| engine.advance_for_testing(4410));
+        let events = player.subscribe();
+
+        *default_device.lock() = Some("Headphones".to_string());
+        let changed = events
+            .iter()
+            .find(|event| matches!(event, AudioEvent::DeviceChanged(_)));
+        assert!(matches!(changed, Some(AudioEvent::DeviceChanged(name)) if name == "Headphones"));
+
+        // The follower takes the change under the engine lock and migrates
+        let migrated = || player.with_engine(|engine| !engine.has_pending_device_change());
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !migrated() {
+            assert!(std::time::Instant::now() < deadline);
+            thread::yield_now();
+        }
+        assert_eq!(player.state(), PlaybackState::Playing);
+        assert_eq!(player.position(), 4410);
+        player.with_engine(|engine| engine.advance_for_testing(4410));
+        assert_eq!(player.position(), 8820);
+
+        player.set_follow_default(false);
+        assert!(player.follower.lock().is_none());
+    }
+}
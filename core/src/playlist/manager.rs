@@ -5,6 +5,7 @@
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 use uuid::Uuid;
 
@@ -29,6 +30,9 @@ pub struct Track {
     pub year: Option<u32>,
     /// Genre
     pub genre: Option<String>,
+    /// Manual gain in dB applied when the track plays, for leveling by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f64>,
 }
 
 impl Track {
@@ -44,6 +48,7 @@ impl Track {
             track_number: None,
             year: None,
             genre: None,
+            gain_db: None,
         }
     }
 
@@ -65,8 +70,38 @@ impl Track {
             track_number: None,
             year: None,
             genre: None,
+            gain_db: None,
         }
     }
+
+    /// Create a track with its duration and tags read from the file
+    ///
+    /// Only the headers are probed; nothing is decoded. Fails when the
+    /// file can't be opened or isn't a recognized audio format; a file
+    /// whose tags can't be read still gets its duration.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let format = crate::audio::decoder::detect_format(path)?;
+        let tags = crate::audio::decoder::read_tags(path).unwrap_or_default();
+
+        Ok(Self {
+            title: tags.title,
+            artist: tags.artist,
+            album: tags.album,
+            duration: format.and_then(|format| format.duration_seconds()),
+            track_number: tags.track_number,
+            year: tags.year,
+            genre: tags.genre,
+            ..Self::new(path.to_string_lossy().into_owned())
+        })
+    }
+
+    /// Set the manual gain in dB, or clear it with `None`
+    ///
+    /// Stacks with normalization; see `AudioEngine::set_queue_tracks`.
+    pub fn set_gain_db(&mut self, gain_db: Option<f64>) {
+        self.gain_db = gain_db;
+    }
 }
 
 /// Represents a playlist
@@ -276,6 +311,20 @@ impl PlaylistManager {
         Ok(())
     }
 
+    /// Add an audio file to a playlist, with its duration and tags
+    ///
+    /// Files that can't be read are still added with just their path, so
+    /// they show up (and can be fixed) in the playlist. Returns the new
+    /// track's ID.
+    pub fn add_file<P: AsRef<Path>>(&self, playlist_id: &str, path: P) -> Result<String> {
+        let path = path.as_ref();
+        let track = Track::from_file(path)
+            .unwrap_or_else(|_| Track::new(path.to_string_lossy().into_owned()));
+        let id = track.id.clone();
+        self.add_track_to_playlist(playlist_id, track)?;
+        Ok(id)
+    }
+
     /// Remove a track from a playlist
     pub fn remove_track_from_playlist(&self, playlist_id: &str, track_index: usize) -> Result<()> {
         let mut playlists = self
@@ -463,4 +512,36 @@ mod tests {
         // Tracks should be same count but likely different order
         assert_eq!(original.tracks.len(), shuffled.tracks.len());
     }
+
+    #[test]
+    fn test_manager_add_file_reads_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..44100 * 2 * 3 / 2 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let manager = PlaylistManager::new();
+        let id = manager.create_playlist("Test".to_string()).unwrap();
+        manager.add_file(&id, &path).unwrap();
+        // Unreadable files are still added, with just their path
+        let missing = dir.path().join("missing.flac");
+        manager.add_file(&id, &missing).unwrap();
+
+        let playlist = manager.get_playlist(&id).unwrap();
+        assert_eq!(playlist.tracks.len(), 2);
+        assert_eq!(playlist.tracks[0].file_path, path.to_string_lossy());
+        assert_eq!(playlist.tracks[0].duration, Some(1.5));
+        assert_eq!(playlist.tracks[1].file_path, missing.to_string_lossy());
+        assert!(playlist.tracks[1].duration.is_none());
+        assert!(Track::from_file(&missing).is_err());
+    }
 }
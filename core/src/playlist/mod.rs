@@ -7,3 +7,4 @@ pub mod queue;
 pub mod smart;
 
 pub use manager::{Playlist, PlaylistManager, Track};
+pub use queue::{PlayQueue, RepeatMode};
@@ -2,4 +2,513 @@
 //!
 //! Handles playback queue, shuffle, and repeat logic
 
-// Will be implemented in Phase 4
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Repeat behavior when the queue reaches the end of a track or the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RepeatMode {
+    /// Stop after the last track
+    #[default]
+    Off,
+    /// Repeat the current track
+    One,
+    /// Wrap around to the first track after the last
+    All,
+}
+
+/// Ordered playback queue with shuffle and repeat support
+///
+/// Items keep their insertion order; shuffling only changes the play order,
+/// so `current_index` always refers to the position in the original list.
+#[derive(Debug, Clone, Default)]
+pub struct PlayQueue {
+    /// Queued files in insertion order
+    items: Vec<PathBuf>,
+    /// Play order as indices into `items`
+    order: Vec<usize>,
+    /// Position of the current item within `order`
+    current: Option<usize>,
+    /// Repeat mode
+    repeat_mode: RepeatMode,
+    /// Whether the play order is shuffled
+    shuffle: bool,
+}
+
+impl PlayQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the queue contents, making the first item current
+    pub fn set_items(&mut self, items: Vec<PathBuf>) {
+        self.order = (0..items.len()).collect();
+        self.items = items;
+        self.current = if self.items.is_empty() { None } else { Some(0) };
+
+        if self.shuffle {
+            self.shuffle_order();
+        }
+    }
+
+    /// Get all queued items in insertion order
+    pub fn items(&self) -> &[PathBuf] {
+        &self.items
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Remove all items
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.order.clear();
+        self.current = None;
+    }
+
+    /// Get the current item
+    pub fn current(&self) -> Option<&PathBuf> {
+        self.current_index().map(|index| &self.items[index])
+    }
+
+    /// Get the index of the current item in insertion order
+    pub fn current_index(&self) -> Option<usize> {
+        self.current.map(|position| self.order[position])
+    }
+
+    /// Make the item at `index` (insertion order) current
+    pub fn jump_to(&mut self, index: usize) -> Option<&PathBuf> {
+        let position = self.order.iter().position(|&i| i == index)?;
+        self.current = Some(position);
+        self.current()
+    }
+
+    /// Move the item at play-order position `from` to position `to`
+    ///
+    /// Positions refer to the play order (the shuffled order when shuffle is
+    /// on, where only that order changes). The current item stays current.
+    ///
+    /// # Returns
+    /// `false` if either position is out of range
+    pub fn move_item(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.order.len() || to >= self.order.len() {
+            return false;
+        }
+
+        if self.shuffle {
+            let index = self.order.remove(from);
+            self.order.insert(to, index);
+        } else {
+            let item = self.items.remove(from);
+            self.items.insert(to, item);
+        }
+
+        self.current = self.current.map(|current| {
+            if current == from {
+                to
+            } else if from < current && to >= current {
+                current - 1
+            } else if from > current && to <= current {
+                current + 1
+            } else {
+                current
+            }
+        });
+        true
+    }
+
+    /// Remove the item at play-order position `position`
+    ///
+    /// Removing the current item makes the following one current (or the
+    /// new last item when it was last).
+    pub fn remove(&mut self, position: usize) -> Option<PathBuf> {
+        if position >= self.order.len() {
+            return None;
+        }
+
+        let index = self.order.remove(position);
+        let item = self.items.remove(index);
+        for i in self.order.iter_mut().filter(|i| **i > index) {
+            *i -= 1;
+        }
+
+        self.current = match self.current {
+            _ if self.order.is_empty() => None,
+            Some(current) if position < current => Some(current - 1),
+            Some(current) => Some(current.min(self.order.len() - 1)),
+            None => None,
+        };
+        Some(item)
+    }
+
+    /// Queue `track` to play right after the current item
+    ///
+    /// On an empty queue the track becomes current.
+    pub fn insert_next(&mut self, track: PathBuf) {
+        let position = self.current.map_or(self.order.len(), |current| current + 1);
+        self.insert_at(position, track);
+    }
+
+    /// Queue `track` at the end of the play order
+    pub fn append(&mut self, track: PathBuf) {
+        self.insert_at(self.order.len(), track);
+    }
+
+    /// Insert `track` at play-order position `position`
+    fn insert_at(&mut self, position: usize, track: PathBuf) {
+        if self.shuffle {
+            self.items.push(track);
+            self.order.insert(position, self.items.len() - 1);
+        } else {
+            self.items.insert(position, track);
+            self.order.push(self.order.len());
+        }
+
+        match self.current {
+            Some(current) if position <= current => self.current = Some(current + 1),
+            None => self.current = Some(position),
+            _ => {}
+        }
+    }
+
+    /// Get the repeat mode
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.repeat_mode
+    }
+
+    /// Set the repeat mode
+    pub fn set_repeat_mode(&mut self, mode: RepeatMode) {
+        self.repeat_mode = mode;
+    }
+
+    /// Check if shuffle is enabled
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Enable or disable shuffle
+    ///
+    /// Enabling shuffle keeps the current item playing and randomizes the rest;
+    /// disabling restores insertion order.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        if shuffle == self.shuffle {
+            return;
+        }
+        self.shuffle = shuffle;
+
+        if shuffle {
+            self.shuffle_order();
+        } else {
+            let current_index = self.current_index();
+            self.order = (0..self.items.len()).collect();
+            self.current = current_index;
+        }
+    }
+
+    /// Skip to the next item (user action)
+    ///
+    /// Repeat-one does not hold a manual skip on the same track; the end of
+    /// the list wraps only with `RepeatMode::All`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&PathBuf> {
+        let position = self.next_position(false)?;
+        self.current = Some(position);
+        self.current()
+    }
+
+    /// Go back to the previous item (user action)
+    pub fn previous(&mut self) -> Option<&PathBuf> {
+        let previous = self.previous_position()?;
+        self.current = Some(previous);
+        self.current()
+    }
+
+    /// Get the item that precedes the current one in play order
+    pub fn peek_previous(&self) -> Option<&PathBuf> {
+        self.previous_position()
+            .map(|position| &self.items[self.order[position]])
+    }
+
+    /// Advance because the current track finished playing
+    ///
+    /// Honors `RepeatMode::One` by staying on the current item.
+    pub fn advance_on_track_end(&mut self) -> Option<&PathBuf> {
+        let position = self.next_position(true)?;
+        self.current = Some(position);
+        self.current()
+    }
+
+    /// Get the item that will play when the current track finishes
+    pub fn peek_on_track_end(&self) -> Option<&PathBuf> {
+        self.next_position(true)
+            .map(|position| &self.items[self.order[position]])
+    }
+
+    /// Get up to `count` items that follow the current one in play order
+    pub fn upcoming(&self, count: usize) -> Vec<&PathBuf> {
+        let start = match self.current {
+            Some(position) => position + 1,
+            None => return Vec::new(),
+        };
+
+        let mut result: Vec<&PathBuf> = self.order[start.min(self.order.len())..]
+            .iter()
+            .map(|&i| &self.items[i])
+            .take(count)
+            .collect();
+
+        if self.repeat_mode == RepeatMode::All {
+            let remaining = count - result.len();
+            result.extend(
+                self.order
+                    .iter()
+                    .take(remaining.min(start))
+                    .map(|&i| &self.items[i]),
+            );
+        }
+
+        result
+    }
+
+    /// Compute the play-order position after the current one
+    fn next_position(&self, track_ended: bool) -> Option<usize> {
+        let position = self.current?;
+
+        if track_ended && self.repeat_mode == RepeatMode::One {
+            return Some(position);
+        }
+
+        if position + 1 < self.order.len() {
+            Some(position + 1)
+        } else if self.repeat_mode == RepeatMode::All {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the play-order position before the current one
+    fn previous_position(&self) -> Option<usize> {
+        let position = self.current?;
+        if position > 0 {
+            Some(position - 1)
+        } else if self.repeat_mode == RepeatMode::All {
+            Some(self.order.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    /// Shuffle the play order, moving the current item to the front
+    fn shuffle_order(&mut self) {
+        let current_index = self.current_index();
+        let mut rng = rand::rng();
+        self.order.shuffle(&mut rng);
+
+        if let Some(index) = current_index {
+            if let Some(position) = self.order.iter().position(|&i| i == index) {
+                self.order.swap(0, position);
+            }
+            self.current = Some(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_of(count: usize) -> PlayQueue {
+        let mut queue = PlayQueue::new();
+        queue.set_items(
+            (0..count)
+                .map(|i| PathBuf::from(format!("track{}.flac", i)))
+                .collect(),
+        );
+        queue
+    }
+
+    #[test]
+    fn test_next_and_previous() {
+        let mut queue = queue_of(3);
+        assert_eq!(queue.current_index(), Some(0));
+
+        assert_eq!(queue.next(), Some(&PathBuf::from("track1.flac")));
+        assert_eq!(queue.next(), Some(&PathBuf::from("track2.flac")));
+        assert_eq!(queue.next(), None);
+        assert_eq!(queue.current_index(), Some(2));
+
+        assert_eq!(queue.peek_previous(), Some(&PathBuf::from("track1.flac")));
+        assert_eq!(queue.previous(), Some(&PathBuf::from("track1.flac")));
+        assert_eq!(queue.previous(), Some(&PathBuf::from("track0.flac")));
+        assert_eq!(queue.peek_previous(), None);
+        assert_eq!(queue.previous(), None);
+    }
+
+    #[test]
+    fn test_repeat_all_wraps() {
+        let mut queue = queue_of(2);
+        queue.set_repeat_mode(RepeatMode::All);
+
+        queue.next();
+        assert_eq!(queue.next(), Some(&PathBuf::from("track0.flac")));
+        assert_eq!(queue.previous(), Some(&PathBuf::from("track1.flac")));
+    }
+
+    #[test]
+    fn test_repeat_one_only_on_track_end() {
+        let mut queue = queue_of(3);
+        queue.set_repeat_mode(RepeatMode::One);
+
+        assert_eq!(
+            queue.peek_on_track_end(),
+            Some(&PathBuf::from("track0.flac"))
+        );
+        assert_eq!(
+            queue.advance_on_track_end(),
+            Some(&PathBuf::from("track0.flac"))
+        );
+        assert_eq!(queue.next(), Some(&PathBuf::from("track1.flac")));
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let mut queue = queue_of(4);
+        queue.next();
+
+        assert_eq!(
+            queue.upcoming(5),
+            vec![&PathBuf::from("track2.flac"), &PathBuf::from("track3.flac")]
+        );
+
+        queue.set_repeat_mode(RepeatMode::All);
+        assert_eq!(queue.upcoming(3).len(), 3);
+        assert_eq!(queue.upcoming(3)[2], &PathBuf::from("track0.flac"));
+    }
+
+    #[test]
+    fn test_shuffle_keeps_current_and_all_items() {
+        let mut queue = queue_of(20);
+        queue.jump_to(7);
+
+        queue.set_shuffle(true);
+        assert_eq!(queue.current_index(), Some(7));
+
+        let mut seen = vec![7];
+        while let Some(path) = queue.next() {
+            let name = path.to_string_lossy().to_string();
+            let index: usize = name
+                .trim_start_matches("track")
+                .trim_end_matches(".flac")
+                .parse()
+                .unwrap();
+            seen.push(index);
+        }
+        seen.sort();
+        assert_eq!(seen, (0..20).collect::<Vec<_>>());
+
+        queue.set_shuffle(false);
+        assert_eq!(queue.items().len(), 20);
+    }
+
+    #[test]
+    fn test_empty_queue() {
+        let mut queue = PlayQueue::new();
+
+        assert!(queue.is_empty());
+        assert!(queue.current().is_none());
+        assert!(queue.next().is_none());
+        assert!(queue.previous().is_none());
+        assert!(queue.upcoming(1).is_empty());
+    }
+
+    fn name(path: Option<&PathBuf>) -> Option<String> {
+        path.map(|p| p.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn test_move_before_current_keeps_current() {
+        let mut queue = queue_of(5);
+        queue.jump_to(2);
+
+        assert!(queue.move_item(4, 0));
+        assert_eq!(name(queue.current()).as_deref(), Some("track2.flac"));
+        assert_eq!(queue.current_index(), Some(3));
+        assert_eq!(queue.items()[0], PathBuf::from("track4.flac"));
+
+        assert!(queue.move_item(0, 4));
+        assert_eq!(queue.current_index(), Some(2));
+
+        // Moving the current item carries the pointer along
+        assert!(queue.move_item(2, 0));
+        assert_eq!(queue.current_index(), Some(0));
+        assert_eq!(name(queue.current()).as_deref(), Some("track2.flac"));
+        assert!(!queue.move_item(0, 5));
+    }
+
+    #[test]
+    fn test_insert_next_plays_after_current() {
+        let mut queue = queue_of(3);
+        queue.jump_to(1);
+
+        queue.insert_next(PathBuf::from("request.flac"));
+        queue.append(PathBuf::from("last.flac"));
+        assert_eq!(queue.len(), 5);
+        assert_eq!(name(queue.current()).as_deref(), Some("track1.flac"));
+        assert_eq!(name(queue.next()).as_deref(), Some("request.flac"));
+        assert_eq!(name(queue.next()).as_deref(), Some("track2.flac"));
+        assert_eq!(name(queue.next()).as_deref(), Some("last.flac"));
+
+        let mut empty = PlayQueue::new();
+        empty.insert_next(PathBuf::from("only.flac"));
+        assert_eq!(name(empty.current()).as_deref(), Some("only.flac"));
+    }
+
+    #[test]
+    fn test_remove_adjusts_current() {
+        let mut queue = queue_of(4);
+        queue.jump_to(2);
+
+        assert_eq!(queue.remove(0), Some(PathBuf::from("track0.flac")));
+        assert_eq!(name(queue.current()).as_deref(), Some("track2.flac"));
+
+        // Removing the current item moves on to the next one
+        assert_eq!(queue.remove(1), Some(PathBuf::from("track2.flac")));
+        assert_eq!(name(queue.current()).as_deref(), Some("track3.flac"));
+        assert_eq!(queue.remove(1), Some(PathBuf::from("track3.flac")));
+        assert_eq!(name(queue.current()).as_deref(), Some("track1.flac"));
+        assert_eq!(queue.remove(0), Some(PathBuf::from("track1.flac")));
+        assert!(queue.current().is_none());
+        assert!(queue.remove(0).is_none());
+    }
+
+    #[test]
+    fn test_edits_apply_to_shuffled_order() {
+        let mut queue = queue_of(10);
+        queue.set_shuffle(true);
+        let current = name(queue.current());
+        let upcoming: Vec<_> = queue.upcoming(9).into_iter().cloned().collect();
+
+        queue.insert_next(PathBuf::from("request.flac"));
+        assert!(queue.move_item(queue.len() - 1, 1));
+        assert_eq!(queue.remove(2), Some(PathBuf::from("request.flac")));
+
+        // The shuffled view changed, insertion order did not
+        assert_eq!(name(queue.current()), current);
+        let mut expected = upcoming.clone();
+        let last = expected.pop().unwrap();
+        expected.insert(0, last);
+        let now: Vec<_> = queue.upcoming(9).into_iter().cloned().collect();
+        assert_eq!(now, expected);
+        assert_eq!(queue.items()[3], PathBuf::from("track3.flac"));
+    }
+}
@@ -5,4 +5,4 @@
 pub mod persistence;
 pub mod playback;
 
-// Will be implemented in Phase 1 & 3
+pub use persistence::{SessionSettings, SessionState};
@@ -2,4 +2,238 @@
 //!
 //! Serializes and restores playback state
 
-// Will be implemented in Phase 3
+use crate::audio::engine::{PlaybackState, DEFAULT_FADE_DURATION_MS};
+use crate::audio::equalizer::EqPreset;
+use crate::error::{Error, Result};
+use crate::playlist::queue::RepeatMode;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Listening configuration restored together with the session
+///
+/// Missing fields fall back to their defaults so older session files still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    /// Play/pause/stop fade in milliseconds
+    pub fade_duration_ms: u32,
+    /// Playback rate (1.0 = normal speed)
+    pub playback_rate: f32,
+    /// Whether leading/trailing silence is skipped
+    pub skip_silence: bool,
+    /// Whether silence is only trimmed at album boundaries
+    pub album_trim: bool,
+    /// Queue repeat mode
+    pub repeat_mode: RepeatMode,
+    /// Whether the queue is shuffled
+    pub shuffle: bool,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            fade_duration_ms: DEFAULT_FADE_DURATION_MS,
+            playback_rate: 1.0,
+            skip_silence: false,
+            album_trim: false,
+            repeat_mode: RepeatMode::Off,
+            shuffle: false,
+        }
+    }
+}
+
+/// Snapshot of a listening session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionState {
+    /// File that was loaded
+    pub file_path: Option<PathBuf>,
+    /// Playback position in sample frames
+    pub position: u64,
+    /// Playback state at the time of saving
+    pub playback_state: PlaybackState,
+    /// Volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Listening configuration
+    pub settings: SessionSettings,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            file_path: None,
+            position: 0,
+            playback_state: PlaybackState::Stopped,
+            volume: 1.0,
+            settings: SessionSettings::default(),
+        }
+    }
+}
+
+impl SessionState {
+    /// Whether audio was playing when the session was saved
+    pub fn was_playing(&self) -> bool {
+        self.playback_state == PlaybackState::Playing
+    }
+
+    /// Position to restore for a track of `duration` frames
+    ///
+    /// A position at or after the end restores to the start.
+    pub fn restore_position(&self, duration: Option<u64>) -> u64 {
+        match duration {
+            Some(duration) if self.position >= duration => 0,
+            _ => self.position,
+        }
+    }
+
+    /// Write the session to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidParameter(format!("Failed to serialize session: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Read a session from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map_err(|e| Error::InvalidParameter(format!("Invalid session file: {}", e)))
+    }
+}
+
+/// Collection of named equalizer presets stored as a JSON file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqPresetStore {
+    presets: Vec<EqPreset>,
+}
+
+impl EqPresetStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of the stored presets, in insertion order
+    pub fn list(&self) -> Vec<&str> {
+        self.presets.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Get a preset by name
+    pub fn get(&self, name: &str) -> Option<&EqPreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    /// Add a preset, replacing any preset with the same name
+    pub fn insert(&mut self, preset: EqPreset) {
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Remove a preset by name
+    pub fn remove(&mut self, name: &str) -> Option<EqPreset> {
+        let index = self.presets.iter().position(|p| p.name == name)?;
+        Some(self.presets.remove(index))
+    }
+
+    /// Write the presets to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidParameter(format!("Failed to serialize presets: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Read presets from a JSON file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json)
+            .map_err(|e| Error::InvalidParameter(format!("Invalid preset file: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::filter::{BiquadParams, FilterType};
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+
+        let session = SessionState {
+            file_path: Some(PathBuf::from("/music/book.m4b")),
+            position: 123_456,
+            playback_state: PlaybackState::Playing,
+            volume: 0.5,
+            settings: SessionSettings {
+                fade_duration_ms: 40,
+                playback_rate: 1.25,
+                skip_silence: true,
+                album_trim: true,
+                repeat_mode: RepeatMode::All,
+                shuffle: true,
+            },
+        };
+        session.save(&path).unwrap();
+
+        let loaded = SessionState::load(&path).unwrap();
+        assert_eq!(loaded, session);
+        assert!(loaded.was_playing());
+    }
+
+    #[test]
+    fn test_restore_position_past_end_restarts() {
+        let session = SessionState {
+            position: 1000,
+            ..Default::default()
+        };
+        assert_eq!(session.restore_position(Some(2000)), 1000);
+        assert_eq!(session.restore_position(Some(1000)), 0);
+        assert_eq!(session.restore_position(None), 1000);
+    }
+
+    #[test]
+    fn test_eq_preset_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presets.json");
+
+        let mut store = EqPresetStore::new();
+        store.insert(EqPreset::new(
+            "Rock",
+            vec![
+                BiquadParams::new(FilterType::LowShelf, 100.0, 0.707, 4.0),
+                BiquadParams::new(FilterType::Peaking, 2500.0, 1.2, -2.5),
+                BiquadParams::new(FilterType::HighShelf, 8000.0, 0.707, 3.0),
+            ],
+        ));
+        store.insert(EqPreset::new(
+            "Vocal Boost",
+            vec![BiquadParams::new(FilterType::Peaking, 3000.0, 1.0, 4.0)],
+        ));
+        // Same name replaces the earlier entry
+        store.insert(EqPreset::new(
+            "Vocal Boost",
+            vec![BiquadParams::new(FilterType::Peaking, 2000.0, 0.9, 5.0)],
+        ));
+        store.save(&path).unwrap();
+
+        let loaded = EqPresetStore::load(&path).unwrap();
+        assert_eq!(loaded, store);
+        assert_eq!(loaded.list(), vec!["Rock", "Vocal Boost"]);
+        assert_eq!(loaded.get("Rock").unwrap().bands.len(), 3);
+        assert_eq!(
+            loaded.get("Vocal Boost").unwrap().bands[0].frequency,
+            2000.0
+        );
+
+        let mut loaded = loaded;
+        assert!(loaded.remove("Rock").is_some());
+        assert!(loaded.get("Rock").is_none());
+        assert!(EqPresetStore::load(dir.path().join("missing.json")).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Fixtures shared by unit tests
+
+use std::path::Path;
+
+/// Frames per block written by `write_verbatim_flac`
+pub const FLAC_BLOCK_FRAMES: usize = 4096;
+
+/// FLAC metadata block type of a Vorbis comment block
+pub const FLAC_VORBIS_COMMENT: u8 = 4;
+
+/// Write a mono 16-bit 44.1 kHz FLAC file of `blocks` verbatim 4096-frame blocks
+///
+/// Returns the byte offset at which each frame starts.
+pub fn write_verbatim_flac(path: &Path, blocks: usize) -> Vec<usize> {
+    write_verbatim_flac_with_metadata(path, blocks, &[])
+}
+
+/// Like `write_verbatim_flac`, with extra (block type, body) metadata
+/// blocks after STREAMINFO
+pub fn write_verbatim_flac_with_metadata(
+    path: &Path,
+    blocks: usize,
+    metadata: &[(u8, Vec<u8>)],
+) -> Vec<usize> {
+    fn crc8(data: &[u8]) -> u8 {
+        data.iter().fold(0u8, |mut crc, &byte| {
+            crc ^= byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x07
+                } else {
+                    crc << 1
+                };
+            }
+            crc
+        })
+    }
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0u16, |mut crc, &byte| {
+            crc ^= (byte as u16) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x8005
+                } else {
+                    crc << 1
+                };
+            }
+            crc
+        })
+    }
+    // Header of a metadata block; the last one carries the 0x80 flag
+    fn block_header(block_type: u8, last: bool, len: usize) -> [u8; 4] {
+        let len = (len as u32).to_be_bytes();
+        let flag = if last { 0x80 } else { 0 };
+        [flag | block_type, len[1], len[2], len[3]]
+    }
+
+    let mut bytes = b"fLaC".to_vec();
+    // STREAMINFO, 34 bytes
+    bytes.extend_from_slice(&block_header(0, metadata.is_empty(), 34));
+    bytes.extend_from_slice(&(FLAC_BLOCK_FRAMES as u16).to_be_bytes());
+    bytes.extend_from_slice(&(FLAC_BLOCK_FRAMES as u16).to_be_bytes());
+    bytes.extend_from_slice(&[0; 6]); // frame sizes unknown
+    let total = (blocks * FLAC_BLOCK_FRAMES) as u64;
+    // 20-bit rate, 3-bit channels - 1, 5-bit bits - 1, 36-bit total samples
+    let packed = (44100u64 << 44) | (15u64 << 36) | total;
+    bytes.extend_from_slice(&packed.to_be_bytes());
+    bytes.extend_from_slice(&[0; 16]); // no MD5
+
+    for (i, (block_type, body)) in metadata.iter().enumerate() {
+        let last = i + 1 == metadata.len();
+        bytes.extend_from_slice(&block_header(*block_type, last, body.len()));
+        bytes.extend_from_slice(body);
+    }
+
+    let mut offsets = Vec::new();
+    for block in 0..blocks {
+        offsets.push(bytes.len());
+        // Fixed blocking, 4096 frames, 44.1 kHz, mono, 16-bit
+        let mut frame = vec![0xFF, 0xF8, 0xC9, 0x08, block as u8];
+        frame.push(crc8(&frame));
+        frame.push(0x02); // verbatim subframe
+        for i in 0..FLAC_BLOCK_FRAMES {
+            let sample = ((block * FLAC_BLOCK_FRAMES + i) % 1000) as i16;
+            frame.extend_from_slice(&sample.to_be_bytes());
+        }
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_be_bytes());
+        bytes.extend_from_slice(&frame);
+    }
+    std::fs::write(path, bytes).unwrap();
+    offsets
+}
+
+/// Body of a FLAC Vorbis comment block holding `comments` ("KEY=value")
+pub fn vorbis_comment_block(comments: &[&str]) -> Vec<u8> {
+    let vendor = b"contextune";
+    let mut body = (vendor.len() as u32).to_le_bytes().to_vec();
+    body.extend_from_slice(vendor);
+    body.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        body.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        body.extend_from_slice(comment.as_bytes());
+    }
+    body
+}
+
+/// CUESHEET track for `flac_cuesheet_block`:
+/// (offset, number, audio, [(index offset, index number)])
+pub type CuesheetTrack<'a> = (u64, u8, bool, &'a [(u64, u8)]);
+
+/// Body of a CD-DA FLAC CUESHEET block
+///
+/// The last track should be the lead-out.
+pub fn flac_cuesheet_block(lead_in: u64, tracks: &[CuesheetTrack]) -> Vec<u8> {
+    let mut body = vec![0; 128]; // media catalog number
+    body.extend_from_slice(&lead_in.to_be_bytes());
+    body.push(0x80); // CD-DA
+    body.extend_from_slice(&[0; 258]);
+    body.push(tracks.len() as u8);
+    for &(offset, number, audio, indices) in tracks {
+        body.extend_from_slice(&offset.to_be_bytes());
+        body.push(number);
+        body.extend_from_slice(&[0; 12]); // ISRC
+        body.push(if audio { 0 } else { 0x80 });
+        body.extend_from_slice(&[0; 13]);
+        body.push(indices.len() as u8);
+        for &(offset, number) in indices {
+            body.extend_from_slice(&offset.to_be_bytes());
+            body.push(number);
+            body.extend_from_slice(&[0; 3]);
+        }
+    }
+    body
+}
+
+/// Write a Matroska file with one FLAC track per (language, blocks) entry
+///
+/// Track IDs count up from 1; each track carries its language tag and
+/// `blocks` frames of `write_verbatim_flac` audio, all in one cluster.
+pub fn write_flac_mka(path: &Path, tracks: &[(&str, usize)]) {
+    // Element with an 8-byte size, which every EBML reader accepts
+    fn element(id: u32, body: &[u8]) -> Vec<u8> {
+        let id = id.to_be_bytes();
+        let skip = id.iter().position(|&b| b != 0).unwrap_or(3);
+        let mut bytes = id[skip..].to_vec();
+        bytes.push(0x01);
+        bytes.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+    fn uint(id: u32, value: u64) -> Vec<u8> {
+        element(id, &value.to_be_bytes())
+    }
+
+    let ebml = element(0x1A45DFA3, &element(0x4282, b"matroska"));
+    let info = element(0x1549A966, &uint(0x2AD7B1, 1_000_000));
+
+    let mut entries = Vec::new();
+    let mut blocks = Vec::new();
+    for (index, &(language, count)) in tracks.iter().enumerate() {
+        let number = index as u64 + 1;
+        let offsets = write_verbatim_flac(path, count);
+        let flac = std::fs::read(path).unwrap();
+
+        let mut audio = element(0xB5, &44100f64.to_be_bytes());
+        audio.extend(uint(0x9F, 1));
+        audio.extend(uint(0x6264, 16));
+        let mut entry = uint(0xD7, number);
+        entry.extend(uint(0x73C5, number));
+        entry.extend(uint(0x83, 2)); // audio
+        entry.extend(element(0x86, b"A_FLAC"));
+        entry.extend(element(0x63A2, &flac[..offsets[0]]));
+        entry.extend(element(0x22B59C, language.as_bytes()));
+        entry.extend(element(0xE1, &audio));
+        entries.extend(element(0xAE, &entry));
+
+        for (block, &start) in offsets.iter().enumerate() {
+            let end = offsets.get(block + 1).copied().unwrap_or(flac.len());
+            let timecode = (block * FLAC_BLOCK_FRAMES * 1000 / 44100) as i16;
+            // Track number as a 1-byte vint, relative timecode, keyframe
+            let mut body = vec![0x80 | number as u8];
+            body.extend_from_slice(&timecode.to_be_bytes());
+            body.push(0x80);
+            body.extend_from_slice(&flac[start..end]);
+            blocks.extend(element(0xA3, &body));
+        }
+    }
+
+    let mut cluster = uint(0xE7, 0);
+    cluster.extend(blocks);
+    let mut segment = info;
+    segment.extend(element(0x1654AE6B, &entries));
+    segment.extend(element(0x1F43B675, &cluster));
+
+    let mut bytes = ebml;
+    bytes.extend(element(0x18538067, &segment));
+    std::fs::write(path, bytes).unwrap();
+}
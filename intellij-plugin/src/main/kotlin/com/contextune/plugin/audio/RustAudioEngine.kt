@@ -140,7 +140,10 @@ enum class AudioEventType(val value: Int) {
     POSITION_CHANGED(1),
     TRACK_ENDED(2),
     ERROR(3),
-    BUFFER_UNDERRUN(4);
+    BUFFER_UNDERRUN(4),
+    DEVICE_CHANGED(5),
+    WARNING(6),
+    PLAYBACK_MODE_CHANGED(7);
     
     companion object {
         fun fromValue(value: Int): AudioEventType? {
@@ -166,18 +169,34 @@ enum class PlaybackState(val value: Int) {
     }
 }
 
+/**
+ * How a track is played
+ */
+enum class PlaybackMode(val value: Int) {
+    BUFFERED(0),
+    STREAMING(1);
+    
+    companion object {
+        fun fromValue(value: Int): PlaybackMode? {
+            return values().find { it.value == value }
+        }
+    }
+}
+
 /**
  * Audio event structure
  */
-@Structure.FieldOrder("eventType", "state", "position", "errorMessage")
+@Structure.FieldOrder("eventType", "state", "position", "errorMessage", "mode")
 class AudioEvent : Structure() {
     @JvmField var eventType: Int = 0
     @JvmField var state: Int = 0
     @JvmField var position: Long = 0
     @JvmField var errorMessage: Pointer? = null
+    @JvmField var mode: Int = 0
     
     fun getEventType(): AudioEventType? = AudioEventType.fromValue(eventType)
     fun getState(): PlaybackState? = PlaybackState.fromValue(state)
+    fun getMode(): PlaybackMode? = PlaybackMode.fromValue(mode)
     fun getErrorMessage(): String? {
         return errorMessage?.getString(0, "UTF-8")
     }
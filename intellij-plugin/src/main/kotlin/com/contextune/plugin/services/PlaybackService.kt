@@ -290,6 +290,15 @@ class PlaybackService {
                 logger.warn("Buffer underrun detected")
                 // TODO: Show buffering indicator
             }
+            com.contextune.plugin.audio.AudioEventType.DEVICE_CHANGED -> {
+                logger.info("Output device changed to: ${event.getErrorMessage()}")
+            }
+            com.contextune.plugin.audio.AudioEventType.WARNING -> {
+                logger.warn("Audio engine warning: ${event.getErrorMessage()}")
+            }
+            com.contextune.plugin.audio.AudioEventType.PLAYBACK_MODE_CHANGED -> {
+                logger.info("Playback mode changed to: ${event.getMode()}")
+            }
             null -> {
                 logger.warn("Unknown audio event type: ${event.eventType}")
             }
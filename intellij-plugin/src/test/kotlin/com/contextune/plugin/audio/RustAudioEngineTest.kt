@@ -207,6 +207,9 @@ class RustAudioEngineTest : BasePlatformTestCase() {
         assertEquals(2, AudioEventType.TRACK_ENDED.value)
         assertEquals(3, AudioEventType.ERROR.value)
         assertEquals(4, AudioEventType.BUFFER_UNDERRUN.value)
+        assertEquals(5, AudioEventType.DEVICE_CHANGED.value)
+        assertEquals(6, AudioEventType.WARNING.value)
+        assertEquals(7, AudioEventType.PLAYBACK_MODE_CHANGED.value)
         
         assertEquals(AudioEventType.STATE_CHANGED, AudioEventType.fromValue(0))
         assertEquals(AudioEventType.POSITION_CHANGED, AudioEventType.fromValue(1))